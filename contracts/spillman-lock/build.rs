@@ -23,9 +23,12 @@ fn main() {
     .expect("write to auth_code_hash.rs");
 
     // Generate SECP256K1_CODE_HASH
-    let secp256k1_code_hash =
-        hex::decode("9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8")
-            .expect("decode secp256k1 code hash");
+    // The system script hashes default to mainnet/testnet; a custom chain overrides them
+    // through SPILLMAN_SECP256K1_* env vars and must deploy the resulting binary
+    let secp256k1_code_hash = system_script_code_hash(
+        "SPILLMAN_SECP256K1_CODE_HASH",
+        "9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8",
+    );
 
     let out_path = Path::new(&env::var("OUT_DIR").unwrap()).join("secp256k1_code_hash.rs");
     let mut out_file =
@@ -44,9 +47,10 @@ fn main() {
     writeln!(&mut out_file, "];").expect("write closing bracket");
 
     // Generate SECP256K1_MULTISIG_CODE_HASH
-    let secp256k1_multisig_code_hash =
-        hex::decode("5c5069eb0857efc65e1bca0c07df34c31663b3622fd3876c876320fc9634e2a8")
-            .expect("decode secp256k1 multisig code hash");
+    let secp256k1_multisig_code_hash = system_script_code_hash(
+        "SPILLMAN_SECP256K1_MULTISIG_CODE_HASH",
+        "5c5069eb0857efc65e1bca0c07df34c31663b3622fd3876c876320fc9634e2a8",
+    );
 
     write!(
         &mut out_file,
@@ -64,9 +68,10 @@ fn main() {
     writeln!(&mut out_file, "];").expect("write closing bracket");
 
     // Generate SECP256K1_MULTISIG_V2_CODE_HASH
-    let secp256k1_multisig_v2_code_hash =
-        hex::decode("36c971b8d41fbd94aabca77dc75e826729ac98447b46f91e00796155dddb0d29")
-            .expect("decode secp256k1 multisig v2 code hash");
+    let secp256k1_multisig_v2_code_hash = system_script_code_hash(
+        "SPILLMAN_SECP256K1_MULTISIG_V2_CODE_HASH",
+        "36c971b8d41fbd94aabca77dc75e826729ac98447b46f91e00796155dddb0d29",
+    );
 
     write!(
         &mut out_file,
@@ -82,4 +87,38 @@ fn main() {
     }
 
     writeln!(&mut out_file, "];").expect("write closing bracket");
+
+    // Generate OMNILOCK_CODE_HASH
+    // Omnilock is deployed by type id, so its code hash differs per network: the default
+    // is mainnet, testnet builds set SPILLMAN_OMNILOCK_CODE_HASH to
+    // 0xf329effd1c475a2978453c8600e1eaf0bc2087ee093c3ee64cc96ec6847752cb
+    let omnilock_code_hash = system_script_code_hash(
+        "SPILLMAN_OMNILOCK_CODE_HASH",
+        "9b819793a64463aed77c615d6cb226eea5487ccfc0783043a587254cda2b6f26",
+    );
+
+    write!(
+        &mut out_file,
+        "\npub const OMNILOCK_CODE_HASH: [u8; 32] = ["
+    )
+    .expect("write to secp256k1_code_hash.rs");
+
+    for (i, byte) in omnilock_code_hash.iter().enumerate() {
+        if i > 0 {
+            write!(&mut out_file, ", ").expect("write comma");
+        }
+        write!(&mut out_file, "{:#02X}", byte).expect("write byte");
+    }
+
+    writeln!(&mut out_file, "];").expect("write closing bracket");
+}
+
+/// Code hash from `var` (hex, optional 0x prefix), or `default` when unset
+fn system_script_code_hash(var: &str, default: &str) -> Vec<u8> {
+    println!("cargo:rerun-if-env-changed={}", var);
+    let code_hash = env::var(var).unwrap_or_else(|_| default.to_string());
+    let code_hash = hex::decode(code_hash.trim_start_matches("0x"))
+        .unwrap_or_else(|e| panic!("decode {}: {}", var, e));
+    assert_eq!(code_hash.len(), 32, "{} must be 32 bytes", var);
+    code_hash
 }
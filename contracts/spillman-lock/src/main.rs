@@ -22,16 +22,19 @@ use ckb_std::{
     ckb_types::{
         bytes::Bytes,
         core::ScriptHashType,
-        packed::{CellDepVec, Script},
+        packed::{
+            BytesVec, CellDepVec, CellInputVec, CellOutputVec, RawTransaction, Script, WitnessArgs,
+        },
         prelude::*,
     },
     error::SysError,
     high_level::{
-        load_cell, load_cell_capacity, load_cell_data, load_cell_lock, load_cell_occupied_capacity,
-        load_cell_type, load_input_since, load_script, load_transaction, load_witness, spawn_cell,
-        QueryIter,
+        load_cell, load_cell_capacity, load_cell_data, load_cell_data_hash, load_cell_lock,
+        load_cell_lock_hash, load_cell_occupied_capacity, load_cell_type, load_input_out_point,
+        load_input_since, load_script, load_script_hash, load_transaction, load_witness,
+        spawn_cell, QueryIter,
     },
-    since::Since,
+    since::{EpochNumberWithFraction, LockValue, Since},
     syscalls::wait,
 };
 use hex::encode;
@@ -64,6 +67,27 @@ pub enum Error {
     XudtAmountMismatch,
     MerchantCapacityExcessive,
     InvalidMultisigConfig,
+    // algorithm_id is a known ckb-auth id but not accepted by Spillman Lock.
+    // Accepted ids: 0 (CKB single-sig), 6 (CKB multisig Legacy), 7 (CKB multisig V2);
+    // for user_algorithm_id: 0 to 5 (CKB, Ethereum, EOS, Tron, Bitcoin, Dogecoin).
+    // Any other id outside the ckb-auth range is InvalidLockArgs.
+    UnsupportedAuthAlgorithm,
+    UserMerchantLockCollision,
+    // Commitment witness carries a merchant output count that is zero, too large,
+    // or does not match the outputs
+    InvalidMerchantOutputCount,
+    // Commitment output xUDT amounts do not add up to the channel's input amount
+    XudtConservationViolation,
+    // Commitment input carries a since; commitments must be broadcastable immediately
+    UnexpectedSinceOnCommitment,
+    // A commitment channel output holds less capacity than its occupied capacity
+    OutputBelowMinCapacity,
+    // Splice must have exactly two outputs: the new Spillman cell and the merchant payout
+    SpliceMustHaveExactlyTwoOutputs,
+    // Splice output 0 is not a Spillman cell of the same channel (only the timeout may change)
+    SpliceLockMismatch,
+    // Arbitration witness does not name exactly two of user, merchant and arbiter
+    InvalidArbitrationSigners,
 }
 
 impl From<SysError> for Error {
@@ -93,11 +117,21 @@ const AUTH_ALGORITHM_CKB: u8 = 0; // CKB/SECP256K1 single-sig
 const AUTH_ALGORITHM_CKB_MULTISIG_LEGACY: u8 = 6; // CKB multisig Legacy (hash_type = Type)
 const AUTH_ALGORITHM_CKB_MULTISIG_V2: u8 = 7; // CKB multisig V2 (hash_type = Data1)
 
+// User auth algorithm IDs: CKB (0) up to Dogecoin (5), i.e. Ethereum (1), EOS (2),
+// Tron (3) and Bitcoin (4) in between. All of them use one 65-byte recoverable signature.
+const AUTH_ALGORITHM_USER_MAX: u8 = 5;
+
 // Note: When calling ckb_auth, both LEGACY and V2 should use algorithm_id = 6
 const AUTH_ALGORITHM_FOR_CKB_AUTH: u8 = 6;
 
-// Script args layout (fixed 50 bytes):
+// Highest algorithm ID defined by ckb-auth (0..=16), used to tell
+// known-but-unsupported IDs apart from invalid ones
+const AUTH_ALGORITHM_MAX_KNOWN: u8 = 16;
+
+// Script args layout (50 bytes, plus the optional trailing fields below):
 // [merchant_lock_arg(20)] + [user_pubkey_hash(20)] + [timeout(8)] + [algorithm_id(1)] + [version(1)]
+//   + [user_algorithm_id(1), optional] + [fallback_timeout(8), optional]
+//   + [arbiter_pubkey_hash(20), optional]
 //
 // Fields:
 //   merchant_lock_arg: 20 bytes
@@ -105,18 +139,30 @@ const AUTH_ALGORITHM_FOR_CKB_AUTH: u8 = 6;
 //     - Multi-sig Legacy (algorithm_id=6): blake160(multisig_config)
 //     - Multi-sig V2 (algorithm_id=7): blake160(multisig_config)
 //       multisig_config format: S | R | M | N | PubKeyHash1 | PubKeyHash2 | ...
-//         S = format_version (1 byte: 0=Legacy, 1=V2)
+//         S = reserved format byte (1 byte, always 0: Legacy and V2 share the
+//             layout, V2 is selected by algorithm_id and the multisig lock script)
 //         R = first_n (1 byte, at least R signatures must match first R pubkeys)
 //         M = threshold (1 byte, require M signatures)
 //         N = pubkey_cnt (1 byte, total N pubkeys)
 //         PubKeyHashX = blake160(pubkey) (20 bytes each)
-//   user_pubkey_hash: 20 bytes - blake160(user_pubkey)
-//   timeout: 8 bytes - timestamp since value (little-endian u64)
+//   user_pubkey_hash: 20 bytes - the user's auth id for user_algorithm_id
+//     - CKB (0): blake160(user_pubkey)
+//     - Ethereum (1): the Ethereum address, keccak256(uncompressed_pubkey)[12..32]
+//     - others: the ckb-auth pubkey hash of that chain
+//   timeout: 8 bytes - since value (little-endian u64), absolute or relative
 //   algorithm_id: 1 byte
 //     - 0: single-sig (CKB default)
 //     - 6: multi-sig legacy (hash_type = Type)
 //     - 7: multi-sig V2 (hash_type = Data1)
-//   version: 1 byte - set to 0
+//   version: 1 byte - signing message scheme (low 7 bits) + flags (high bit)
+//     - 0: cell_deps cleared, deps can be swapped without re-signing
+//     - 1: cell_deps included, signatures bind the exact Spillman Lock and auth cells
+//     - 0x80 flag: user-only refund, the timeout path needs only the user signature
+//   user_algorithm_id: 1 byte, optional - ckb-auth algorithm of the user signature
+//     - absent or 0: CKB secp256k1, the user receives on a secp256k1 sighash lock
+//     - 1..=5: the user receives on an Omnilock with args
+//       [user_algorithm_id(1)] + [user_pubkey_hash(20)] + [omnilock_flags(1) = 0]
+//   fallback_timeout, arbiter_pubkey_hash: see below
 const MERCHANT_LOCK_ARG_LEN: usize = 20;
 const USER_PUBKEY_HASH_LEN: usize = 20;
 const TIMEOUT_LEN: usize = 8;
@@ -125,12 +171,40 @@ const VERSION_LEN: usize = 1;
 const MULTISIG_HEADER_LEN: usize = 4; // S + R + M + N
 const ARGS_LEN: usize =
     MERCHANT_LOCK_ARG_LEN + USER_PUBKEY_HASH_LEN + TIMEOUT_LEN + ALGORITHM_ID_LEN + VERSION_LEN; // 50 bytes
+const USER_ALGORITHM_ID_LEN: usize = 1;
+const FALLBACK_TIMEOUT_LEN: usize = 8;
+const ARBITER_PUBKEY_HASH_LEN: usize = 20;
+const OMNILOCK_FLAGS_NONE: u8 = 0; // Omnilock args flags: no admin/ACP/time lock/supply modes
+
+// Args versions (signing message schemes)
+const VERSION_DETACHED_CELL_DEPS: u8 = 0;
+const VERSION_BOUND_CELL_DEPS: u8 = 1;
+const VERSION_SCHEME_MASK: u8 = 0x7f;
+// The version byte sits at the same offset in every layout, so it is read before the layout
+// is known
+const VERSION_OFFSET: usize = ARGS_LEN - VERSION_LEN;
+
+// Version flag: after timeout + grace the user refunds alone, the merchant never pre-signs.
+// The merchant could not stop a refund after the timeout anyway; the flag only drops the
+// setup round trip. Output structure, since and fee checks are unchanged.
+const VERSION_FLAG_USER_ONLY_REFUND: u8 = 0x80;
+
+// Optional fallback timeout (8 bytes, since-encoded) after the optional user_algorithm_id:
+// from fallback_timeout + grace the user refunds alone, for a merchant that disappeared
+// without pre-signing a refund. It must use the timeout's form and metric and be later than
+// it; before it the timeout path still needs both signatures.
+
+// Optional arbiter pubkey hash (20 bytes, blake160 of a secp256k1 key) at the end of args:
+// it enables the arbitration path, where any two of user, merchant and arbiter close the
+// channel with an agreed split. It must differ from both the user and the merchant.
 
 // Script args field offsets (removed - use direct indexing)
 
 // Unlock type layout: [unlock_type(1)]
 const UNLOCK_TYPE_COMMITMENT: u8 = 0x00; // Commitment Path
 const UNLOCK_TYPE_TIMEOUT: u8 = 0x01; // Timeout Path
+const UNLOCK_TYPE_SPLICE: u8 = 0x02; // Splice Path
+const UNLOCK_TYPE_ARBITRATION: u8 = 0x03; // Arbitration Path
 const UNLOCK_TYPE_LEN: usize = 1;
 
 // Witness layout:
@@ -144,66 +218,123 @@ const UNLOCK_TYPE_LEN: usize = 1;
 //   Total: 16 + 1 + (4+N*20) + M*65 + 65
 const SIGNATURE_LEN: usize = 65; // Each signature is 65 bytes
 
+// Optional trailing byte of a commitment witness: number of merchant outputs (1..=MAX).
+// Without it the commitment has exactly two outputs (user + one merchant output).
+//   [empty_witness_args(16)] + [unlock_type(1)] + [merchant part] + [user_signature(65)] + [merchant_output_count(1)]
+const MERCHANT_OUTPUT_COUNT_LEN: usize = 1;
+const MAX_MERCHANT_OUTPUTS: usize = 4;
+// Flag in the high bit of the count byte: the user output (index 0) may use another lock
+// than the one derived from args, e.g. a fresh address. The user's signature covers output
+// 0, so the lock is still the user's choice; it only may not be the merchant's lock.
+const COMMITMENT_FLAG_USER_LOCK_OVERRIDE: u8 = 0x80;
+
+// Arbitration witness layout:
+//   [empty_witness_args(16)] + [unlock_type(1)] + [signers(1)]
+//     + [merchant part, if the merchant signs] + [user_signature(65), if the user signs]
+//     + [arbiter_signature(65), if the arbiter signs]
+// signers is a bitmask naming exactly two of the three; the merchant part is the same as on
+// the other paths (a signature, or multisig_config + M signatures).
+const ARBITRATION_SIGNER_USER: u8 = 0x01;
+const ARBITRATION_SIGNER_MERCHANT: u8 = 0x02;
+const ARBITRATION_SIGNER_ARBITER: u8 = 0x04;
+const ARBITRATION_SIGNERS_ALL: u8 =
+    ARBITRATION_SIGNER_USER | ARBITRATION_SIGNER_MERCHANT | ARBITRATION_SIGNER_ARBITER;
+
 // Maximum allowed transaction fee (1 CKB = 100,000,000 shannons)
 const MAX_FEE: u64 = 100_000_000;
 
+// Grace window after the timeout reserved for the merchant: the timeout path requires
+// since >= timeout + grace, so during [timeout, timeout + grace) only a commitment can
+// spend the channel. The grace is added in the timeout's own metric (~10 minutes each).
+const TIMEOUT_GRACE_BLOCKS: u64 = 75;
+const TIMEOUT_GRACE_SECONDS: u64 = 600;
+const TIMEOUT_GRACE_EPOCH_DENOMINATOR: u64 = 24; // 1/24 epoch
+
 fn verify() -> Result<(), Error> {
+    // One Spillman cell per transaction: GroupInput only holds inputs locked by this
+    // script, so inputs under other locks (a refund sponsor's or the merchant's settlement
+    // fee inputs) are not limited here. Source::Input indexes every input of the
+    // transaction; each path decides which outputs those inputs' change may take.
     if load_input_since(1, Source::GroupInput).is_ok() {
         return Err(Error::MultipleInputs);
     }
 
-    let mut witness = load_witness(0, Source::GroupInput)?;
-
-    // Check minimum witness length
-    if witness.len() < EMPTY_WITNESS_ARGS.len() + UNLOCK_TYPE_LEN + SIGNATURE_LEN {
-        return Err(Error::WitnessLen);
-    }
-
-    // Verify and remove the empty WitnessArgs prefix (16 bytes)
-    if witness
-        .drain(0..EMPTY_WITNESS_ARGS.len())
-        .collect::<Vec<_>>()
-        != EMPTY_WITNESS_ARGS
-    {
-        return Err(Error::EmptyWitnessArgs);
-    }
-
-    let message = {
-        let raw_tx = load_transaction()?
-            .raw()
-            .as_builder()
-            .cell_deps(CellDepVec::default())
-            .build();
-        blake2b_256(raw_tx.as_slice())
-    };
+    let mut witness = load_spillman_witness()?;
 
     let script = load_script()?;
     let args: Bytes = script.args().unpack();
 
-    // Verify args length (fixed 50 bytes)
-    if args.len() != ARGS_LEN {
-        return Err(Error::ArgsLen);
+    // The version selects the args layout
+    let ParsedArgs {
+        merchant_lock_arg,
+        user_pubkey_hash,
+        timeout,
+        algorithm_id,
+        version,
+        user_algorithm_id,
+        fallback_timeout,
+        arbiter_pubkey_hash,
+    } = parse_args(args_version(&args)?, &args)?;
+
+    let raw_tx = signed_raw_transaction(version & VERSION_SCHEME_MASK)?;
+
+    validate_algorithm_id(algorithm_id)?;
+    validate_user_algorithm_id(user_algorithm_id)?;
+    if let Some(fallback_timeout) = fallback_timeout {
+        validate_fallback_timeout(timeout, fallback_timeout)?;
+    }
+    if let Some(arbiter_pubkey_hash) = arbiter_pubkey_hash {
+        if arbiter_pubkey_hash == merchant_lock_arg || arbiter_pubkey_hash == user_pubkey_hash {
+            return Err(Error::InvalidLockArgs);
+        }
     }
 
-    // Parse args fields
-    let merchant_lock_arg = &args[0..MERCHANT_LOCK_ARG_LEN];
-    let user_pubkey_hash =
-        &args[MERCHANT_LOCK_ARG_LEN..MERCHANT_LOCK_ARG_LEN + USER_PUBKEY_HASH_LEN];
-    let timeout = u64::from_le_bytes(
-        args[MERCHANT_LOCK_ARG_LEN + USER_PUBKEY_HASH_LEN
-            ..MERCHANT_LOCK_ARG_LEN + USER_PUBKEY_HASH_LEN + TIMEOUT_LEN]
-            .try_into()
-            .map_err(|_| Error::LengthNotEnough)?,
-    );
-    let algorithm_id = args[MERCHANT_LOCK_ARG_LEN + USER_PUBKEY_HASH_LEN + TIMEOUT_LEN];
-    let version =
-        args[MERCHANT_LOCK_ARG_LEN + USER_PUBKEY_HASH_LEN + TIMEOUT_LEN + ALGORITHM_ID_LEN];
+    let unlock_type = witness.remove(0);
 
-    if version != 0 {
-        return Err(Error::UnsupportedVersion);
+    // Arbitration: any two of user, merchant and arbiter, only when args name an arbiter
+    if unlock_type == UNLOCK_TYPE_ARBITRATION {
+        let arbiter_pubkey_hash = arbiter_pubkey_hash.ok_or(Error::InvalidUnlockType)?;
+        return verify_arbitration_path(
+            algorithm_id,
+            merchant_lock_arg,
+            user_algorithm_id,
+            user_pubkey_hash,
+            arbiter_pubkey_hash,
+            signing_message(&raw_tx)?,
+            witness,
+        );
     }
 
-    let unlock_type = witness.remove(0);
+    // The deadline from which the user refunds alone: the timeout itself with the user-only
+    // flag, otherwise the fallback timeout if args carry one
+    let user_only_refund = version & VERSION_FLAG_USER_ONLY_REFUND != 0;
+    let user_only_timeout = if user_only_refund {
+        Some(timeout)
+    } else {
+        fallback_timeout
+    };
+
+    // User-only refund: the witness holds the user signature alone, no merchant part.
+    // With the flag it is the only refund form; with a fallback timeout a lone user
+    // signature selects it (any merchant part is longer).
+    if unlock_type == UNLOCK_TYPE_TIMEOUT
+        && (user_only_refund || (user_only_timeout.is_some() && witness.len() == SIGNATURE_LEN))
+    {
+        if witness.len() != SIGNATURE_LEN {
+            return Err(Error::WitnessLen);
+        }
+        return verify_timeout_path(
+            algorithm_id,
+            merchant_lock_arg,
+            None,
+            user_algorithm_id,
+            user_pubkey_hash,
+            timeout,
+            user_only_timeout,
+            signing_message(&raw_tx)?,
+            witness,
+        );
+    }
 
     // Determine merchant signature type based on algorithm_id
     // After removing empty_witness_args(16) and unlock_type(1), remaining witness is:
@@ -211,45 +342,19 @@ fn verify() -> Result<(), Error> {
     // - Multi-sig (algorithm_id=6 or 7): multisig_config(4+N*20) + merchant_sigs(M*65) + user_sig(65)
     let (merchant_algorithm_id, merchant_lock_arg_for_auth) = match algorithm_id {
         AUTH_ALGORITHM_CKB => {
-            // Single-sig: witness should be exactly 130 bytes (merchant_sig + user_sig)
-            if witness.len() != 130 {
+            // Single-sig: witness should be exactly 130 bytes (merchant_sig + user_sig),
+            // plus the optional merchant output count byte
+            if witness.len() != 2 * SIGNATURE_LEN
+                && witness.len() != 2 * SIGNATURE_LEN + MERCHANT_OUTPUT_COUNT_LEN
+            {
                 return Err(Error::WitnessLen);
             }
             (AUTH_ALGORITHM_CKB, merchant_lock_arg.to_vec())
         }
         AUTH_ALGORITHM_CKB_MULTISIG_LEGACY | AUTH_ALGORITHM_CKB_MULTISIG_V2 => {
-            // Multi-sig: extract and verify multisig_config from witness
-            if witness.len() < MULTISIG_HEADER_LEN + SIGNATURE_LEN {
-                return Err(Error::WitnessLen);
-            }
-
-            // Verify multisig_config format version
-            // Both Legacy and V2 use format_version=0 to support both
-            if witness[0] != 0 {
-                return Err(Error::InvalidMultisigConfig);
-            }
-
-            // Parse multisig header to determine config length
-            let pubkey_cnt = witness[3] as usize;
-            let multisig_config_len = MULTISIG_HEADER_LEN + pubkey_cnt * MERCHANT_LOCK_ARG_LEN;
-
-            if witness.len() < multisig_config_len + SIGNATURE_LEN {
-                return Err(Error::WitnessLen);
-            }
-
-            // Extract multisig_config from witness
-            let multisig_config = witness[0..multisig_config_len].to_vec();
-
-            // Verify blake160(multisig_config) == merchant_lock_arg
-            let multisig_hash = &blake2b_256(&multisig_config)[0..20];
-            if multisig_hash != merchant_lock_arg {
-                return Err(Error::InvalidMultisigConfig);
-            }
-
-            // Remove multisig_config from witness, leaving only signatures
-            witness.drain(0..multisig_config_len);
-
-            // Use the same algorithm_id for auth verification
+            // Multi-sig: extract and verify multisig_config from witness, leaving only
+            // signatures; the same algorithm_id is used for auth verification
+            let multisig_config = take_multisig_config(&mut witness, merchant_lock_arg)?;
             (algorithm_id, multisig_config)
         }
         _ => return Err(Error::InvalidLockArgs),
@@ -258,17 +363,32 @@ fn verify() -> Result<(), Error> {
     match unlock_type {
         UNLOCK_TYPE_COMMITMENT => verify_commitment_path(
             merchant_algorithm_id,
+            merchant_lock_arg,
             &merchant_lock_arg_for_auth,
+            user_algorithm_id,
             user_pubkey_hash,
-            message,
+            raw_tx,
             witness,
         )?,
         UNLOCK_TYPE_TIMEOUT => verify_timeout_path(
             merchant_algorithm_id,
-            &merchant_lock_arg_for_auth,
+            merchant_lock_arg,
+            Some(&merchant_lock_arg_for_auth),
+            user_algorithm_id,
             user_pubkey_hash,
             timeout,
-            message,
+            user_only_timeout,
+            signing_message(&raw_tx)?,
+            witness,
+        )?,
+        UNLOCK_TYPE_SPLICE => verify_splice_path(
+            merchant_algorithm_id,
+            merchant_lock_arg,
+            &merchant_lock_arg_for_auth,
+            user_algorithm_id,
+            user_pubkey_hash,
+            &script,
+            signing_message(&raw_tx)?,
             witness,
         )?,
         _ => return Err(Error::InvalidUnlockType),
@@ -276,25 +396,344 @@ fn verify() -> Result<(), Error> {
     Ok(())
 }
 
+/// Raw transaction both parties sign; the message hashes it with the funding OutPoint
+///
+/// Version 0 clears cell_deps so the deps can change without re-signing; version 1 keeps
+/// them, so a signature commits to the exact Spillman Lock and auth cells referenced.
+fn signed_raw_transaction(version: u8) -> Result<RawTransaction, Error> {
+    let raw_tx = load_transaction()?.raw();
+    match version {
+        VERSION_DETACHED_CELL_DEPS => {
+            Ok(raw_tx.as_builder().cell_deps(CellDepVec::default()).build())
+        }
+        VERSION_BOUND_CELL_DEPS => Ok(raw_tx),
+        _ => Err(Error::UnsupportedVersion),
+    }
+}
+
+/// Message signed on the commitment path: the signed raw transaction restricted to the
+/// Spillman input and the leading `channel_outputs` outputs
+///
+/// The merchant may add fee inputs and their change when settling without touching what
+/// the user signed. Without fee inputs this is the whole signed raw transaction.
+fn commitment_signing_message(
+    raw_tx: RawTransaction,
+    channel_outputs: usize,
+) -> Result<[u8; 32], Error> {
+    let script_hash = load_script_hash()?;
+    let mut inputs = Vec::new();
+    for (index, input) in raw_tx.inputs().into_iter().enumerate() {
+        if load_cell_lock_hash(index, Source::Input)? == script_hash {
+            inputs.push(input);
+        }
+    }
+
+    let outputs = raw_tx.outputs().into_iter().take(channel_outputs);
+    let outputs_data = raw_tx.outputs_data().into_iter().take(channel_outputs);
+    let raw_tx = raw_tx
+        .as_builder()
+        .inputs(CellInputVec::new_builder().extend(inputs).build())
+        .outputs(CellOutputVec::new_builder().extend(outputs).build())
+        .outputs_data(BytesVec::new_builder().extend(outputs_data).build())
+        .build();
+    signing_message(&raw_tx)
+}
+
+/// Message both parties sign: blake2b_256(funding OutPoint || signed raw transaction)
+///
+/// The OutPoint of the Spillman cell being spent is hashed in explicitly, so a signature
+/// made for one channel never verifies on another channel with identical args, whatever
+/// the rest of the two transactions look like.
+fn signing_message(raw_tx: &RawTransaction) -> Result<[u8; 32], Error> {
+    let out_point = load_input_out_point(0, Source::GroupInput)?;
+    let mut preimage = Vec::with_capacity(out_point.as_slice().len() + raw_tx.as_slice().len());
+    preimage.extend_from_slice(out_point.as_slice());
+    preimage.extend_from_slice(raw_tx.as_slice());
+    Ok(blake2b_256(preimage))
+}
+
+/// Load the Spillman payload (unlock_type + merchant part + user signature)
+///
+/// Two witness layouts are accepted:
+/// - A `WitnessArgs` molecule with the payload in its `lock` field, as produced by
+///   standard wallets and signing tooling
+/// - The bare layout: EMPTY_WITNESS_ARGS (16 bytes) followed by the payload
+///
+/// A bare witness is never a valid `WitnessArgs` (its total size header says 16 bytes),
+/// so the two layouts cannot be confused.
+fn load_spillman_witness() -> Result<Vec<u8>, Error> {
+    let witness = load_witness(0, Source::GroupInput)?;
+
+    let lock = WitnessArgs::from_slice(&witness)
+        .ok()
+        .and_then(|witness_args| witness_args.lock().to_opt());
+    if let Some(lock) = lock {
+        let payload = lock.raw_data().to_vec();
+        if payload.len() < UNLOCK_TYPE_LEN + SIGNATURE_LEN {
+            return Err(Error::WitnessLen);
+        }
+        return Ok(payload);
+    }
+
+    // Check minimum witness length
+    if witness.len() < EMPTY_WITNESS_ARGS.len() + UNLOCK_TYPE_LEN + SIGNATURE_LEN {
+        return Err(Error::WitnessLen);
+    }
+
+    // Verify and remove the empty WitnessArgs prefix (16 bytes)
+    if witness[0..EMPTY_WITNESS_ARGS.len()] != EMPTY_WITNESS_ARGS {
+        return Err(Error::EmptyWitnessArgs);
+    }
+    Ok(witness[EMPTY_WITNESS_ARGS.len()..].to_vec())
+}
+
+// Remove the merchant's multisig_config from the front of `witness` and check it against
+// `merchant_lock_arg`; returns the config, the merchant's auth input.
+fn take_multisig_config(witness: &mut Vec<u8>, merchant_lock_arg: &[u8]) -> Result<Vec<u8>, Error> {
+    if witness.len() < MULTISIG_HEADER_LEN + SIGNATURE_LEN {
+        return Err(Error::WitnessLen);
+    }
+
+    // S must be 0 for both Legacy and V2, as the multisig scripts and
+    // ckb-sdk's MultisigConfig::to_witness_data() write it
+    if witness[0] != 0 {
+        return Err(Error::InvalidMultisigConfig);
+    }
+
+    // Parse multisig header to determine config length, rejecting thresholds
+    // and key counts ckb-auth can never satisfy before N sizes the config.
+    // N is a single byte, so the config is at most 4 + 255 * 20 bytes and the
+    // witness length check below bounds it
+    let threshold = witness[2] as usize;
+    let pubkey_cnt = witness[3] as usize;
+    if pubkey_cnt == 0 || threshold == 0 || threshold > pubkey_cnt {
+        return Err(Error::InvalidMultisigConfig);
+    }
+    let multisig_config_len = MULTISIG_HEADER_LEN + pubkey_cnt * MERCHANT_LOCK_ARG_LEN;
+
+    if witness.len() < multisig_config_len + SIGNATURE_LEN {
+        return Err(Error::WitnessLen);
+    }
+
+    // Extract multisig_config from witness
+    let multisig_config: Vec<u8> = witness.drain(0..multisig_config_len).collect();
+
+    // A repeated pubkey hash would let one key count more than once toward M
+    if has_duplicate_pubkey_hash(&multisig_config[MULTISIG_HEADER_LEN..]) {
+        return Err(Error::InvalidMultisigConfig);
+    }
+
+    // Verify blake160(multisig_config) == merchant_lock_arg
+    let multisig_hash = &blake2b_256(&multisig_config)[0..20];
+    if multisig_hash != merchant_lock_arg {
+        return Err(Error::InvalidMultisigConfig);
+    }
+
+    Ok(multisig_config)
+}
+
+// Whether two of the 20-byte pubkey hashes of a multisig config are equal.
+//
+// Pairwise comparison: N is at most 255, and real configs hold a handful
+// of keys, so this costs far less than the signature checks that follow.
+fn has_duplicate_pubkey_hash(pubkey_hashes: &[u8]) -> bool {
+    let hashes: Vec<&[u8]> = pubkey_hashes.chunks_exact(MERCHANT_LOCK_ARG_LEN).collect();
+    hashes
+        .iter()
+        .enumerate()
+        .any(|(i, hash)| hashes[i + 1..].contains(hash))
+}
+
+fn validate_algorithm_id(algorithm_id: u8) -> Result<(), Error> {
+    match algorithm_id {
+        AUTH_ALGORITHM_CKB
+        | AUTH_ALGORITHM_CKB_MULTISIG_LEGACY
+        | AUTH_ALGORITHM_CKB_MULTISIG_V2 => Ok(()),
+        id if id <= AUTH_ALGORITHM_MAX_KNOWN => Err(Error::UnsupportedAuthAlgorithm),
+        _ => Err(Error::InvalidLockArgs),
+    }
+}
+
+// The user signs with one key: multisig and schemes without a 65-byte recoverable
+// signature are known to ckb-auth but not accepted for the user
+fn validate_user_algorithm_id(user_algorithm_id: u8) -> Result<(), Error> {
+    match user_algorithm_id {
+        AUTH_ALGORITHM_CKB..=AUTH_ALGORITHM_USER_MAX => Ok(()),
+        id if id <= AUTH_ALGORITHM_MAX_KNOWN => Err(Error::UnsupportedAuthAlgorithm),
+        _ => Err(Error::InvalidLockArgs),
+    }
+}
+
+// Script args of any supported version
+struct ParsedArgs<'a> {
+    merchant_lock_arg: &'a [u8],
+    user_pubkey_hash: &'a [u8],
+    timeout: u64,
+    algorithm_id: u8,
+    // Full version byte, flags included
+    version: u8,
+    user_algorithm_id: u8,
+    fallback_timeout: Option<u64>,
+    arbiter_pubkey_hash: Option<&'a [u8]>,
+}
+
+fn args_version(args: &[u8]) -> Result<u8, Error> {
+    args.get(VERSION_OFFSET).copied().ok_or(Error::ArgsLen)
+}
+
+// Fixed args length of a version scheme, the optional trailing fields follow it. A new
+// layout gets its own scheme here instead of changing the length of an existing one.
+fn fixed_args_len(version: u8) -> Result<usize, Error> {
+    match version & VERSION_SCHEME_MASK {
+        // Both signing schemes share the 50-byte layout
+        VERSION_DETACHED_CELL_DEPS | VERSION_BOUND_CELL_DEPS => Ok(ARGS_LEN),
+        _ => Err(Error::UnsupportedVersion),
+    }
+}
+
+// Parse args whose version byte is `version`. The version only picks the fixed part (and
+// the signing scheme); the optional trailing fields are told apart by length. An unknown
+// version is rejected before its length is checked.
+fn parse_args(version: u8, args: &[u8]) -> Result<ParsedArgs<'_>, Error> {
+    let fixed_len = fixed_args_len(version)?;
+    if args.len() < fixed_len {
+        return Err(Error::ArgsLen);
+    }
+    let (fixed, trailing) = args.split_at(fixed_len);
+
+    let timeout_start = MERCHANT_LOCK_ARG_LEN + USER_PUBKEY_HASH_LEN;
+    let timeout = u64::from_le_bytes(
+        fixed[timeout_start..timeout_start + TIMEOUT_LEN]
+            .try_into()
+            .map_err(|_| Error::LengthNotEnough)?,
+    );
+    let OptionalArgs {
+        user_algorithm_id,
+        fallback_timeout,
+        arbiter_pubkey_hash,
+    } = parse_optional_args(trailing)?;
+
+    Ok(ParsedArgs {
+        merchant_lock_arg: &fixed[..MERCHANT_LOCK_ARG_LEN],
+        user_pubkey_hash: &fixed[MERCHANT_LOCK_ARG_LEN..timeout_start],
+        timeout,
+        algorithm_id: fixed[timeout_start + TIMEOUT_LEN],
+        version,
+        user_algorithm_id,
+        fallback_timeout,
+        arbiter_pubkey_hash,
+    })
+}
+
+// Optional trailing args fields after the fixed bytes
+struct OptionalArgs<'a> {
+    // CKB when absent
+    user_algorithm_id: u8,
+    fallback_timeout: Option<u64>,
+    arbiter_pubkey_hash: Option<&'a [u8]>,
+}
+
+// Trailing fields in order: user_algorithm_id (1), fallback timeout (8) and arbiter pubkey
+// hash (20). Every combination has a distinct length.
+fn parse_optional_args(mut trailing: &[u8]) -> Result<OptionalArgs<'_>, Error> {
+    let arbiter_pubkey_hash = if trailing.len() >= ARBITER_PUBKEY_HASH_LEN {
+        let (rest, arbiter) = trailing.split_at(trailing.len() - ARBITER_PUBKEY_HASH_LEN);
+        trailing = rest;
+        Some(arbiter)
+    } else {
+        None
+    };
+    let (user_algorithm_id, fallback_timeout) = match trailing.len() {
+        0 => (None, None),
+        USER_ALGORITHM_ID_LEN => (Some(trailing[0]), None),
+        FALLBACK_TIMEOUT_LEN => (None, Some(trailing)),
+        len if len == USER_ALGORITHM_ID_LEN + FALLBACK_TIMEOUT_LEN => {
+            (Some(trailing[0]), Some(&trailing[USER_ALGORITHM_ID_LEN..]))
+        }
+        _ => return Err(Error::ArgsLen),
+    };
+    let fallback_timeout = fallback_timeout
+        .map(|bytes| bytes.try_into().map(u64::from_le_bytes))
+        .transpose()
+        .map_err(|_| Error::LengthNotEnough)?;
+    Ok(OptionalArgs {
+        user_algorithm_id: user_algorithm_id.unwrap_or(AUTH_ALGORITHM_CKB),
+        fallback_timeout,
+        arbiter_pubkey_hash,
+    })
+}
+
+// The fallback timeout must be comparable with the timeout (same form and metric) and
+// strictly later, otherwise it would shorten the merchant's window instead of extending it
+fn validate_fallback_timeout(timeout: u64, fallback_timeout: u64) -> Result<(), Error> {
+    let (timeout, fallback_timeout) = (Since::new(timeout), Since::new(fallback_timeout));
+    if timeout_reached(fallback_timeout, timeout) && fallback_timeout != timeout {
+        Ok(())
+    } else {
+        Err(Error::InvalidLockArgs)
+    }
+}
+
+// `merchant_lock_arg` is the 20-byte arg from args; `merchant_auth_arg` is the merchant's
+// auth input (the arg, or the full multisig_config).
 fn verify_commitment_path(
     merchant_algorithm_id: u8,
     merchant_lock_arg: &[u8],
+    merchant_auth_arg: &[u8],
+    user_algorithm_id: u8,
     user_pubkey_hash: &[u8],
-    message: [u8; 32],
-    witness: Vec<u8>,
+    raw_tx: RawTransaction,
+    mut witness: Vec<u8>,
 ) -> Result<(), Error> {
+    // Signatures are whole 65-byte units, so a remainder of one byte is the optional
+    // merchant output count, with the user lock override flag in its high bit
+    let (merchant_output_count, user_lock_override) =
+        if witness.len() % SIGNATURE_LEN == MERCHANT_OUTPUT_COUNT_LEN {
+            let count_byte = witness.pop().ok_or(Error::WitnessLen)?;
+            let count = (count_byte & !COMMITMENT_FLAG_USER_LOCK_OVERRIDE) as usize;
+            if count == 0 || count > MAX_MERCHANT_OUTPUTS {
+                return Err(Error::InvalidMerchantOutputCount);
+            }
+            (
+                Some(count),
+                count_byte & COMMITMENT_FLAG_USER_LOCK_OVERRIDE != 0,
+            )
+        } else {
+            (None, false)
+        };
+    if witness.len() < 2 * SIGNATURE_LEN || !witness.len().is_multiple_of(SIGNATURE_LEN) {
+        return Err(Error::WitnessLen);
+    }
+
+    // A commitment has no timelock: any since would delay the merchant's settlement
+    if load_input_since(0, Source::GroupInput)? != 0 {
+        return Err(Error::UnexpectedSinceOnCommitment);
+    }
+
     // Split witness into merchant part and user signature
     // - Single-sig: merchant_sig(65) + user_sig(65)
     // - Multi-sig: merchant_sigs(M*65) + user_sig(65)
     let merchant_sig_len = witness.len() - SIGNATURE_LEN;
     let (merchant_signature, user_signature) = witness.split_at(merchant_sig_len);
 
-    // Verify commitment output structure
-    verify_commitment_output_structure(merchant_lock_arg, user_pubkey_hash, merchant_algorithm_id)?;
+    // Verify commitment output structure against the channel's type script (xUDT or none)
+    let input_type = load_cell_type(0, Source::GroupInput)?;
+    verify_commitment_output_structure(
+        merchant_lock_arg,
+        user_algorithm_id,
+        user_pubkey_hash,
+        merchant_algorithm_id,
+        merchant_output_count,
+        user_lock_override,
+        input_type.as_ref(),
+    )?;
 
-    // Verify user signature (always single-sig)
+    // Fee inputs and change added at settlement are not part of the signed message
+    let message = commitment_signing_message(raw_tx, 1 + merchant_output_count.unwrap_or(1))?;
+
+    // Verify user signature (single key, user_algorithm_id)
     verify_signature_with_auth(
-        AUTH_ALGORITHM_CKB,
+        user_algorithm_id,
         user_pubkey_hash,
         &message,
         user_signature,
@@ -303,7 +742,7 @@ fn verify_commitment_path(
     // Verify merchant signature
     verify_merchant_signature(
         merchant_algorithm_id,
-        merchant_lock_arg,
+        merchant_auth_arg,
         merchant_signature,
         &message,
     )?;
@@ -311,44 +750,71 @@ fn verify_commitment_path(
     Ok(())
 }
 
+// `merchant_lock_arg` is the 20-byte arg from args; `merchant_auth_arg` is the merchant's
+// auth input (the arg, or the full multisig_config), None for a user-only refund.
+// `user_only_timeout` is the deadline a user-only refund waits for, None if args allow none.
+#[allow(clippy::too_many_arguments)]
 fn verify_timeout_path(
     merchant_algorithm_id: u8,
     merchant_lock_arg: &[u8],
+    merchant_auth_arg: Option<&[u8]>,
+    user_algorithm_id: u8,
     user_pubkey_hash: &[u8],
     timeout: u64,
+    user_only_timeout: Option<u64>,
     message: [u8; 32],
     witness: Vec<u8>,
 ) -> Result<(), Error> {
+    // The merchant output count only applies to commitments
+    if !witness.len().is_multiple_of(SIGNATURE_LEN) {
+        return Err(Error::WitnessLen);
+    }
+
     // Split witness into merchant part and user signature
     // - Single-sig: merchant_sig(65) + user_sig(65)
     // - Multi-sig: merchant_sigs(M*65) + user_sig(65)
+    // - User-only refund: user_sig(65)
     let merchant_sig_len = witness.len() - SIGNATURE_LEN;
     let (merchant_signature, user_signature) = witness.split_at(merchant_sig_len);
 
     let raw_since = load_input_since(0, Source::GroupInput)?;
     let since = Since::new(raw_since);
-    let timeout_since = Since::new(timeout);
 
-    // Security: Only proceed with verification if since >= timeout
-    if since >= timeout_since {
+    // Both signatures refund from the timeout; the user alone only from the user-only deadline
+    let deadline = match merchant_auth_arg {
+        Some(_) => timeout,
+        None => user_only_timeout.ok_or(Error::WitnessLen)?,
+    };
+    let refund_since = timeout_with_grace(Since::new(deadline)).ok_or(Error::TimeoutNotReached)?;
+
+    // Security: Only proceed with verification if since >= timeout + grace
+    if timeout_reached(since, refund_since) {
         // Verify refund output structure
-        verify_refund_output_structure(merchant_lock_arg, user_pubkey_hash, merchant_algorithm_id)?;
+        verify_refund_output_structure(
+            merchant_lock_arg,
+            user_algorithm_id,
+            user_pubkey_hash,
+            merchant_algorithm_id,
+            merchant_auth_arg.is_some(),
+        )?;
 
-        // Verify user signature (always single-sig)
+        // Verify user signature (single key, user_algorithm_id)
         verify_signature_with_auth(
-            AUTH_ALGORITHM_CKB,
+            user_algorithm_id,
             user_pubkey_hash,
             &message,
             user_signature,
         )?;
 
         // Verify merchant signature
-        verify_merchant_signature(
-            merchant_algorithm_id,
-            merchant_lock_arg,
-            merchant_signature,
-            &message,
-        )?;
+        if let Some(merchant_auth_arg) = merchant_auth_arg {
+            verify_merchant_signature(
+                merchant_algorithm_id,
+                merchant_auth_arg,
+                merchant_signature,
+                &message,
+            )?;
+        }
 
         Ok(())
     } else {
@@ -356,6 +822,169 @@ fn verify_timeout_path(
     }
 }
 
+// Splice path: the merchant sweeps its payments while the channel stays open.
+//
+// Both parties sign the whole transaction, like the timeout path. The user's remaining
+// balance moves into a new Spillman cell of the same channel (only the timeout may
+// change) and the rest goes to the merchant.
+#[allow(clippy::too_many_arguments)]
+fn verify_splice_path(
+    merchant_algorithm_id: u8,
+    merchant_lock_arg: &[u8],
+    merchant_auth_arg: &[u8],
+    user_algorithm_id: u8,
+    user_pubkey_hash: &[u8],
+    script: &Script,
+    message: [u8; 32],
+    witness: Vec<u8>,
+) -> Result<(), Error> {
+    // The merchant output count only applies to commitments
+    if !witness.len().is_multiple_of(SIGNATURE_LEN) {
+        return Err(Error::WitnessLen);
+    }
+
+    // Split witness into merchant part and user signature
+    // - Single-sig: merchant_sig(65) + user_sig(65)
+    // - Multi-sig: merchant_sigs(M*65) + user_sig(65)
+    let merchant_sig_len = witness.len() - SIGNATURE_LEN;
+    let (merchant_signature, user_signature) = witness.split_at(merchant_sig_len);
+
+    verify_splice_output_structure(merchant_lock_arg, merchant_algorithm_id, script)?;
+
+    // Verify user signature (single key, user_algorithm_id)
+    verify_signature_with_auth(
+        user_algorithm_id,
+        user_pubkey_hash,
+        &message,
+        user_signature,
+    )?;
+
+    // Verify merchant signature
+    verify_merchant_signature(
+        merchant_algorithm_id,
+        merchant_auth_arg,
+        merchant_signature,
+        &message,
+    )?;
+
+    Ok(())
+}
+
+// Arbitration path: any two of user, merchant and arbiter close the channel with an
+// agreed split, at any time.
+//
+// The two signatures cover the whole transaction, so the outputs are whatever they agreed
+// on; the contract only checks that the channel's value is not burnt.
+fn verify_arbitration_path(
+    algorithm_id: u8,
+    merchant_lock_arg: &[u8],
+    user_algorithm_id: u8,
+    user_pubkey_hash: &[u8],
+    arbiter_pubkey_hash: &[u8],
+    message: [u8; 32],
+    mut witness: Vec<u8>,
+) -> Result<(), Error> {
+    if witness.is_empty() {
+        return Err(Error::WitnessLen);
+    }
+    let signers = witness.remove(0);
+    if signers & !ARBITRATION_SIGNERS_ALL != 0 || signers.count_ones() != 2 {
+        return Err(Error::InvalidArbitrationSigners);
+    }
+
+    // The merchant part comes first; a multisig config sizes the signatures after it
+    let merchant_auth_arg = if signers & ARBITRATION_SIGNER_MERCHANT == 0 {
+        None
+    } else if algorithm_id == AUTH_ALGORITHM_CKB {
+        Some(merchant_lock_arg.to_vec())
+    } else {
+        Some(take_multisig_config(&mut witness, merchant_lock_arg)?)
+    };
+
+    // Two signers, the second always a single 65-byte signature; only a multisig merchant
+    // part holds more than one signature
+    let one_signature_each = algorithm_id == AUTH_ALGORITHM_CKB || merchant_auth_arg.is_none();
+    if !witness.len().is_multiple_of(SIGNATURE_LEN)
+        || witness.len() < 2 * SIGNATURE_LEN
+        || (one_signature_each && witness.len() != 2 * SIGNATURE_LEN)
+    {
+        return Err(Error::WitnessLen);
+    }
+    let (first_signature, second_signature) = witness.split_at(witness.len() - SIGNATURE_LEN);
+
+    verify_arbitration_output_structure()?;
+
+    let (user_signature, arbiter_signature) = match merchant_auth_arg {
+        Some(merchant_auth_arg) => {
+            verify_merchant_signature(algorithm_id, &merchant_auth_arg, first_signature, &message)?;
+            if signers & ARBITRATION_SIGNER_USER != 0 {
+                (Some(second_signature), None)
+            } else {
+                (None, Some(second_signature))
+            }
+        }
+        None => (Some(first_signature), Some(second_signature)),
+    };
+
+    if let Some(user_signature) = user_signature {
+        verify_signature_with_auth(
+            user_algorithm_id,
+            user_pubkey_hash,
+            &message,
+            user_signature,
+        )?;
+    }
+    if let Some(arbiter_signature) = arbiter_signature {
+        verify_signature_with_auth(
+            AUTH_ALGORITHM_CKB,
+            arbiter_pubkey_hash,
+            &message,
+            arbiter_signature,
+        )?;
+    }
+
+    Ok(())
+}
+
+// The earliest since accepted by the timeout path: the timeout plus the merchant's grace.
+//
+// Returns None for a timeout with invalid flags (it can never be reached) or when adding
+// the grace overflows the since value.
+fn timeout_with_grace(timeout: Since) -> Option<Since> {
+    if !timeout.flags_is_valid() {
+        return None;
+    }
+    let absolute = timeout.is_absolute();
+    match timeout.extract_lock_value()? {
+        LockValue::BlockNumber(number) => {
+            Since::from_block_number(number.checked_add(TIMEOUT_GRACE_BLOCKS)?, absolute)
+        }
+        // The lock value is in milliseconds, the since encoding in seconds
+        LockValue::Timestamp(millis) => Since::from_timestamp(
+            (millis / 1000).checked_add(TIMEOUT_GRACE_SECONDS)?,
+            absolute,
+        ),
+        LockValue::EpochNumberWithFraction(epoch) => {
+            let grace = EpochNumberWithFraction::new(0, 1, TIMEOUT_GRACE_EPOCH_DENOMINATOR);
+            Some(Since::from_epoch((epoch + grace)?, absolute))
+        }
+    }
+}
+
+// Whether the input since satisfies the timeout stored in args.
+//
+// The timeout may be absolute (e.g. "after 2025-01-01") or relative (e.g. "1 day after
+// the funding cell was committed"). The input since must use the same form and metric:
+// CKB itself enforces a relative since against the funding cell's commitment block, so
+// comparing the two relative values is enough. Mixed absolute/relative or different
+// metrics are incomparable and never reach the timeout.
+fn timeout_reached(since: Since, timeout: Since) -> bool {
+    since.flags_is_valid()
+        && timeout.flags_is_valid()
+        && since.is_relative() == timeout.is_relative()
+        && since >= timeout
+}
+
 fn verify_merchant_signature(
     merchant_algorithm_id: u8,
     merchant_lock_arg: &[u8],
@@ -401,6 +1030,7 @@ fn verify_signature_with_auth(
 ) -> Result<(), Error> {
     // Map algorithm_id for ckb_auth:
     // - Both Legacy (6) and V2 (7) multisig use algorithm_id = 6 in ckb_auth
+    // - User algorithms (0..=5) are ckb_auth ids already
     let auth_algorithm_id = if algorithm_id == AUTH_ALGORITHM_CKB_MULTISIG_V2 {
         AUTH_ALGORITHM_FOR_CKB_AUTH
     } else {
@@ -420,6 +1050,7 @@ fn verify_signature_with_auth(
     ];
 
     // Spawn auth contract to verify signature
+    ensure_auth_cell_dep()?;
     let pid =
         spawn_cell(&AUTH_CODE_HASH, ScriptHashType::Data1, &args, &[]).map_err(|_| Error::Auth)?;
 
@@ -432,177 +1063,240 @@ fn verify_signature_with_auth(
     }
 }
 
+// The auth binary is pinned at build time (AUTH_CODE_HASH = data hash of deps/auth).
+// spawn_cell with hash_type Data1 only ever runs a cell dep whose data hash matches, so a
+// different "auth" binary in the cell deps can never be spawned; checking explicitly turns
+// a missing or substituted auth dep into a clear Error::Auth before any spawn is attempted.
+fn ensure_auth_cell_dep() -> Result<(), Error> {
+    if QueryIter::new(load_cell_data_hash, Source::CellDep).any(|hash| hash == AUTH_CODE_HASH) {
+        Ok(())
+    } else {
+        Err(Error::Auth)
+    }
+}
+
+// The merchant's output lock for `merchant_lock_arg`, the 20-byte arg from args:
+// - Single-sig (algorithm_id=0): secp256k1 sighash, args = blake160(pubkey)
+// - Multi-sig Legacy (algorithm_id=6): multisig with hash_type = Type
+// - Multi-sig V2 (algorithm_id=7): multisig with hash_type = Data1
+// For multisig the arg is blake160(multisig_config), already checked against the witness
+// config in verify, so the config is not hashed again here.
+fn expected_merchant_lock(merchant_lock_arg: &[u8], algorithm_id: u8) -> Script {
+    let (code_hash, hash_type) = match algorithm_id {
+        AUTH_ALGORITHM_CKB_MULTISIG_LEGACY => (SECP256K1_MULTISIG_CODE_HASH, ScriptHashType::Type),
+        AUTH_ALGORITHM_CKB_MULTISIG_V2 => (SECP256K1_MULTISIG_V2_CODE_HASH, ScriptHashType::Data1),
+        _ => (SECP256K1_CODE_HASH, ScriptHashType::Type),
+    };
+    Script::new_builder()
+        .code_hash(code_hash.pack())
+        .hash_type(hash_type)
+        .args(merchant_lock_arg.pack())
+        .build()
+}
+
+// The user's output lock:
+// - CKB (user_algorithm_id=0): secp256k1 sighash, args = blake160(pubkey)
+// - Other chains: Omnilock with the same auth, args = user_algorithm_id | pubkey hash | flags
+// Omnilock's auth flags use the ckb-auth algorithm ids, so the same key unlocks it.
+fn expected_user_lock(user_pubkey_hash: &[u8], user_algorithm_id: u8) -> Script {
+    if user_algorithm_id == AUTH_ALGORITHM_CKB {
+        return Script::new_builder()
+            .code_hash(SECP256K1_CODE_HASH.pack())
+            .hash_type(ScriptHashType::Type)
+            .args(user_pubkey_hash.pack())
+            .build();
+    }
+    let mut args = Vec::with_capacity(USER_ALGORITHM_ID_LEN + USER_PUBKEY_HASH_LEN + 1);
+    args.push(user_algorithm_id);
+    args.extend_from_slice(user_pubkey_hash);
+    args.push(OMNILOCK_FLAGS_NONE);
+    Script::new_builder()
+        .code_hash(OMNILOCK_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type)
+        .args(args.pack())
+        .build()
+}
+
+// Commitment outputs: user at index 0, merchant at 1..=merchant_output_count.
+// `merchant_output_count` is None when the witness has no count byte, meaning exactly
+// one merchant output. With `user_lock_override` the user output may use any lock but the
+// merchant's. `input_type` is the type script of the Spillman input.
 fn verify_commitment_output_structure(
-    merchant_lock_data: &[u8],
+    merchant_lock_arg: &[u8],
+    user_algorithm_id: u8,
     user_pubkey_hash: &[u8],
     algorithm_id: u8,
+    merchant_output_count: Option<usize>,
+    user_lock_override: bool,
+    input_type: Option<&Script>,
 ) -> Result<(), Error> {
-    // Verify that there are exactly two outputs
-    if load_cell(2, Source::Output).is_ok() {
-        return Err(Error::CommitmentMustHaveExactlyTwoOutputs);
+    let count_error = match merchant_output_count {
+        Some(_) => Error::InvalidMerchantOutputCount,
+        None => Error::CommitmentMustHaveExactlyTwoOutputs,
+    };
+    let merchant_output_count = merchant_output_count.unwrap_or(1);
+
+    // Outputs after the 1 + merchant_output_count channel outputs can only be change of
+    // fee inputs added by the merchant at settlement
+    let fee_locks = fee_input_lock_hashes()?;
+    if QueryIter::new(load_cell_lock_hash, Source::Output)
+        .skip(merchant_output_count + 1)
+        .any(|lock_hash| !fee_locks.contains(&lock_hash))
+    {
+        return Err(count_error);
     }
 
-    // Verify that there is a merchant output
-    if load_cell(1, Source::Output).is_err() {
-        return Err(Error::CommitmentMustHaveExactlyTwoOutputs);
+    // Verify that the last merchant output exists
+    if load_cell(merchant_output_count, Source::Output).is_err() {
+        return Err(count_error);
     }
 
     let user_lock = load_cell_lock(0, Source::Output)?;
 
-    let expected_user_lock = Script::new_builder()
-        .code_hash(SECP256K1_CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type)
-        .args(user_pubkey_hash.pack())
-        .build();
+    let expected_user_lock = expected_user_lock(user_pubkey_hash, user_algorithm_id);
 
-    if user_lock != expected_user_lock {
+    if !user_lock_override && user_lock != expected_user_lock {
         return Err(Error::UserPubkeyHashMismatch);
     }
 
-    // Build expected merchant lock based on algorithm_id
-    // Note: merchant_lock_data parameter contains:
-    //   - Single-sig (algorithm_id=0): 20 bytes blake160(pubkey) from args
-    //   - Multi-sig (algorithm_id=6 or 7): 4+N*20 bytes full multisig_config from witness
-    let expected_merchant_lock = if merchant_lock_data.len() == MERCHANT_LOCK_ARG_LEN {
-        // Single-sig output: code_hash=SECP256K1, args=blake160(pubkey) (20 bytes)
-        Script::new_builder()
-            .code_hash(SECP256K1_CODE_HASH.pack())
-            .hash_type(ScriptHashType::Type)
-            .args(merchant_lock_data.pack())
-            .build()
-    } else {
-        // Multi-sig output: code_hash=SECP256K1_MULTISIG, args=blake160(multisig_config) (20 bytes)
-        // Need to hash the full multisig_config to get the 20-byte args
-        let multisig_hash = &blake2b_256(merchant_lock_data)[0..20];
-
-        // Determine code_hash and hash_type based on algorithm_id:
-        // - algorithm_id = 6: Legacy multisig (code_hash = SECP256K1_MULTISIG_CODE_HASH, hash_type = Type)
-        // - algorithm_id = 7: V2 multisig (code_hash = SECP256K1_MULTISIG_V2_CODE_HASH, hash_type = Data1)
-        let (code_hash, hash_type) = if algorithm_id == AUTH_ALGORITHM_CKB_MULTISIG_V2 {
-            (SECP256K1_MULTISIG_V2_CODE_HASH, ScriptHashType::Data1)
-        } else {
-            (SECP256K1_MULTISIG_CODE_HASH, ScriptHashType::Type)
-        };
-
-        Script::new_builder()
-            .code_hash(code_hash.pack())
-            .hash_type(hash_type)
-            .args(multisig_hash.pack())
-            .build()
-    };
-
-    let merchant_lock = load_cell_lock(1, Source::Output)?;
+    let expected_merchant_lock = expected_merchant_lock(merchant_lock_arg, algorithm_id);
 
-    if merchant_lock != expected_merchant_lock {
-        return Err(Error::MerchantPubkeyHashMismatch);
+    // User and merchant must receive on different locks (applies to single-sig and multisig),
+    // an overridden user lock included
+    if expected_user_lock == expected_merchant_lock || user_lock == expected_merchant_lock {
+        return Err(Error::UserMerchantLockCollision);
     }
 
-    // Verify type script consistency for xUDT channels
-    let type_script = load_cell_type(0, Source::GroupInput)?;
-
-    // If input has type script, both outputs must have the same type script
-    if let Some(input_t) = type_script {
-        // Verify user output type script - MUST exist
-        let user_output_type =
-            load_cell_type(0, Source::Output)?.ok_or(Error::TypeScriptMismatch)?;
-        if user_output_type != input_t {
-            return Err(Error::TypeScriptMismatch);
+    for index in 1..=merchant_output_count {
+        let merchant_lock = load_cell_lock(index, Source::Output)?;
+        if merchant_lock != expected_merchant_lock {
+            return Err(Error::MerchantPubkeyHashMismatch);
         }
+    }
 
-        // Verify merchant output type script - MUST exist and xUDT amount > 0
-        let merchant_output_type =
-            load_cell_type(1, Source::Output)?.ok_or(Error::TypeScriptMismatch)?;
-
-        // Verify type script matches input
-        if merchant_output_type != input_t {
+    // Every channel output carries exactly the input's type script: the xUDT type of an
+    // xUDT channel, none for a pure CKB channel
+    for index in 0..=merchant_output_count {
+        if load_cell_type(index, Source::Output)?.as_ref() != input_type {
             return Err(Error::TypeScriptMismatch);
         }
+    }
 
-        // Merchant has type script: verify xUDT amount > 0 (merchant receives payment)
-        let merchant_output_data = load_cell_data(1, Source::Output)?;
-        // xUDT amount is stored in first 16 bytes (u128 little-endian)
-        if merchant_output_data.len() < 16 {
-            return Err(Error::XudtAmountMismatch);
+    if input_type.is_some() {
+        // Merchant outputs must receive a non-zero xUDT payment
+        for index in 1..=merchant_output_count {
+            if xudt_amount(&load_cell_data(index, Source::Output)?)? == 0 {
+                return Err(Error::XudtAmountMismatch);
+            }
         }
-        if merchant_output_data[0..16] == [0u8; 16] {
-            return Err(Error::XudtAmountMismatch);
+
+        // Tokens are only moved between user and merchant: the output amounts must add
+        // up to exactly the channel's input amount
+        let input_amount = xudt_amount(&load_cell_data(0, Source::GroupInput)?)?;
+        let mut output_amount: u128 = 0;
+        for index in 0..=merchant_output_count {
+            output_amount = output_amount
+                .checked_add(xudt_amount(&load_cell_data(index, Source::Output)?)?)
+                .ok_or(Error::XudtConservationViolation)?;
         }
-    } else {
-        // If input has no type script, outputs should not have type script either
-        let user_output_type = load_cell_type(0, Source::Output)?;
-        if user_output_type.is_some() {
-            return Err(Error::TypeScriptMismatch);
+        if output_amount != input_amount {
+            return Err(Error::XudtConservationViolation);
         }
+    }
 
-        let merchant_output_type = load_cell_type(1, Source::Output)?;
-        if merchant_output_type.is_some() {
-            return Err(Error::TypeScriptMismatch);
+    // Every channel output must hold at least its occupied capacity (lock, type and data),
+    // otherwise the node would reject the cell after script verification
+    for index in 0..=merchant_output_count {
+        if load_cell_capacity(index, Source::Output)?
+            < load_cell_occupied_capacity(index, Source::Output)?
+        {
+            return Err(Error::OutputBelowMinCapacity);
         }
     }
 
+    // Total merchant value is bounded by what the channel holds: merchant outputs may
+    // only take what is left after the user's output
+    let input_capacity = load_cell_capacity(0, Source::GroupInput)?;
+    let user_capacity = load_cell_capacity(0, Source::Output)?;
+    let mut merchant_capacity: u64 = 0;
+    for index in 1..=merchant_output_count {
+        merchant_capacity = merchant_capacity
+            .checked_add(load_cell_capacity(index, Source::Output)?)
+            .ok_or(Error::MerchantCapacityExcessive)?;
+    }
+    if merchant_capacity > input_capacity.saturating_sub(user_capacity) {
+        return Err(Error::MerchantCapacityExcessive);
+    }
+
+    // Verify the implicit CKB fee (input - channel outputs) is not excessive; fee inputs
+    // pay the rest of the fee from their own capacity
+    let total_output_capacity = QueryIter::new(load_cell_capacity, Source::Output)
+        .take(merchant_output_count + 1)
+        .sum();
+
+    let fee = input_capacity.saturating_sub(total_output_capacity);
+    if fee > MAX_FEE {
+        return Err(Error::ExcessiveFee);
+    }
+
     Ok(())
 }
 
+/// xUDT amount stored in the first 16 bytes of cell data (u128 little-endian)
+fn xudt_amount(data: &[u8]) -> Result<u128, Error> {
+    let amount: [u8; 16] = data
+        .get(0..16)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(Error::XudtAmountMismatch)?;
+    Ok(u128::from_le_bytes(amount))
+}
+
+// `merchant_signed` is set when the merchant's signature covers the refund: it signs the whole
+// transaction, so it also commits to the lock of the merchant output.
 fn verify_refund_output_structure(
-    merchant_lock_data: &[u8],
+    merchant_lock_arg: &[u8],
+    user_algorithm_id: u8,
     user_pubkey_hash: &[u8],
     algorithm_id: u8,
+    merchant_signed: bool,
 ) -> Result<(), Error> {
-    // Refund can have 1 or 2 outputs
+    // Refund can have 1 or 2 outputs (plus trailing sponsor change, see below)
     // 1 output: user funded alone
     // 2 outputs: user + merchant co-funded (merchant gets capacity back)
-    if load_cell(2, Source::Output).is_ok() {
+    let spillman_outputs = refund_spillman_output_count()?;
+    if spillman_outputs > 2 {
         return Err(Error::RefundMustHaveOneOrTwoOutputs);
     }
+    let merchant_output = if spillman_outputs == 2 {
+        Some(load_cell(1, Source::Output)?)
+    } else {
+        None
+    };
 
     // 1. Verify Output 0 is user address
     let user_lock = load_cell_lock(0, Source::Output)?;
-    let expected_user_lock = Script::new_builder()
-        .code_hash(SECP256K1_CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type)
-        .args(user_pubkey_hash.pack())
-        .build();
+    let expected_user_lock = expected_user_lock(user_pubkey_hash, user_algorithm_id);
 
     if user_lock != expected_user_lock {
         return Err(Error::UserPubkeyHashMismatch);
     }
 
-    // 2. If there's Output 1, verify it's merchant address and capacity is exact
-    if let Ok(merchant_output) = load_cell(1, Source::Output) {
-        // Build expected merchant lock based on algorithm_id
-        // Note: merchant_lock_data parameter contains:
-        //   - Single-sig (algorithm_id=0): 20 bytes blake160(pubkey) from args
-        //   - Multi-sig (algorithm_id=6 or 7): 4+N*20 bytes full multisig_config from witness
-        let expected_merchant_lock = if merchant_lock_data.len() == MERCHANT_LOCK_ARG_LEN {
-            // Single-sig output: code_hash=SECP256K1, args=blake160(pubkey) (20 bytes)
-            Script::new_builder()
-                .code_hash(SECP256K1_CODE_HASH.pack())
-                .hash_type(ScriptHashType::Type)
-                .args(merchant_lock_data.pack())
-                .build()
-        } else {
-            // Multi-sig output: code_hash=SECP256K1_MULTISIG, args=blake160(multisig_config) (20 bytes)
-            // Need to hash the full multisig_config to get the 20-byte args
-            let multisig_hash = &blake2b_256(merchant_lock_data)[0..20];
-
-            // Determine code_hash and hash_type based on algorithm_id:
-            // - algorithm_id = 6: Legacy multisig (code_hash = SECP256K1_MULTISIG_CODE_HASH, hash_type = Type)
-            // - algorithm_id = 7: V2 multisig (code_hash = SECP256K1_MULTISIG_V2_CODE_HASH, hash_type = Data1)
-            let (code_hash, hash_type) = if algorithm_id == AUTH_ALGORITHM_CKB_MULTISIG_V2 {
-                (SECP256K1_MULTISIG_V2_CODE_HASH, ScriptHashType::Data1)
-            } else {
-                (SECP256K1_MULTISIG_CODE_HASH, ScriptHashType::Type)
-            };
-
-            Script::new_builder()
-                .code_hash(code_hash.pack())
-                .hash_type(hash_type)
-                .args(multisig_hash.pack())
-                .build()
-        };
-
-        if merchant_output.lock() != expected_merchant_lock {
-            return Err(Error::MerchantPubkeyHashMismatch);
+    // 2. If there's Output 1, verify it's merchant address and capacity is exact.
+    // A refund the merchant signed may send it to another lock of the merchant's choice, as
+    // long as that lock is no larger: the exact occupied capacity below must not grow at the
+    // user's expense. A user-only refund always pays the lock derived from args.
+    if let Some(ref merchant_output) = merchant_output {
+        let expected_merchant_lock = expected_merchant_lock(merchant_lock_arg, algorithm_id);
+        let merchant_lock = merchant_output.lock();
+        if merchant_lock != expected_merchant_lock {
+            if !merchant_signed {
+                return Err(Error::MerchantPubkeyHashMismatch);
+            }
+            if merchant_lock.args().raw_data().len()
+                > expected_merchant_lock.args().raw_data().len()
+            {
+                return Err(Error::MerchantCapacityExcessive);
+            }
         }
 
         // Verify merchant output capacity equals exactly the occupied capacity
@@ -635,7 +1329,7 @@ fn verify_refund_output_structure(
         }
 
         // If there's merchant output (Output 1), verify type script and xUDT amount = 0
-        if let Ok(_merchant_output) = load_cell(1, Source::Output) {
+        if merchant_output.is_some() {
             // Merchant output MUST have type script
             let merchant_output_type =
                 load_cell_type(1, Source::Output)?.ok_or(Error::TypeScriptMismatch)?;
@@ -663,7 +1357,7 @@ fn verify_refund_output_structure(
             return Err(Error::TypeScriptMismatch);
         }
 
-        if let Ok(_merchant_output) = load_cell(1, Source::Output) {
+        if merchant_output.is_some() {
             let merchant_output_type = load_cell_type(1, Source::Output)?;
             if merchant_output_type.is_some() {
                 return Err(Error::TypeScriptMismatch);
@@ -674,8 +1368,11 @@ fn verify_refund_output_structure(
     // 5. Verify CKB capacity fee is not excessive
     let input_capacity = load_cell_capacity(0, Source::GroupInput)?;
 
-    // Collect all outputs capacity (1 or 2 outputs)
-    let total_output_capacity = QueryIter::new(load_cell_capacity, Source::Output).sum();
+    // Collect the Spillman outputs capacity (1 or 2 outputs); sponsor change is excluded
+    // so a sponsor's own value can neither hide nor inflate the fee taken from the channel
+    let total_output_capacity = QueryIter::new(load_cell_capacity, Source::Output)
+        .take(spillman_outputs)
+        .sum();
 
     let fee = input_capacity.saturating_sub(total_output_capacity);
     if fee > MAX_FEE {
@@ -684,3 +1381,157 @@ fn verify_refund_output_structure(
 
     Ok(())
 }
+
+// Number of leading refund outputs that belong to the channel (user + optional merchant).
+//
+// A third-party sponsor may pay the refund fee by adding inputs not locked by this script.
+// Trailing outputs locked like one of those inputs are the sponsor's change; once the first
+// sponsor output appears, every later output must be sponsor change too.
+fn refund_spillman_output_count() -> Result<usize, Error> {
+    let sponsor_locks = fee_input_lock_hashes()?;
+    let output_locks: Vec<[u8; 32]> = QueryIter::new(load_cell_lock_hash, Source::Output).collect();
+
+    let count = output_locks
+        .iter()
+        .position(|lock_hash| sponsor_locks.contains(lock_hash))
+        .unwrap_or(output_locks.len());
+    if output_locks[count..]
+        .iter()
+        .any(|lock_hash| !sponsor_locks.contains(lock_hash))
+    {
+        return Err(Error::RefundMustHaveOneOrTwoOutputs);
+    }
+
+    Ok(count)
+}
+
+// Lock hashes of the inputs not locked by this script: a refund sponsor's or the merchant's
+// settlement fee inputs. Their change may follow the channel outputs.
+fn fee_input_lock_hashes() -> Result<Vec<[u8; 32]>, Error> {
+    let script_hash = load_script_hash()?;
+    Ok(QueryIter::new(load_cell_lock_hash, Source::Input)
+        .filter(|lock_hash| *lock_hash != script_hash)
+        .collect())
+}
+
+// Splice outputs: the new Spillman cell at index 0, the merchant payout at index 1.
+fn verify_splice_output_structure(
+    merchant_lock_arg: &[u8],
+    algorithm_id: u8,
+    script: &Script,
+) -> Result<(), Error> {
+    if load_cell(1, Source::Output).is_err() || load_cell(2, Source::Output).is_ok() {
+        return Err(Error::SpliceMustHaveExactlyTwoOutputs);
+    }
+
+    // 1. Output 0 keeps the user's balance in the same channel: same Spillman Lock code and
+    // the same args except the timeout (user_algorithm_id and arbiter included)
+    let new_lock = load_cell_lock(0, Source::Output)?;
+    let args: Bytes = script.args().unpack();
+    let new_args: Bytes = new_lock.args().unpack();
+    let timeout_start = MERCHANT_LOCK_ARG_LEN + USER_PUBKEY_HASH_LEN;
+    let timeout_end = timeout_start + TIMEOUT_LEN;
+    if new_lock.code_hash() != script.code_hash()
+        || new_lock.hash_type() != script.hash_type()
+        || new_args.len() != args.len()
+        || new_args[..timeout_start] != args[..timeout_start]
+        || new_args[timeout_end..] != args[timeout_end..]
+    {
+        return Err(Error::SpliceLockMismatch);
+    }
+
+    // A timeout the refund path can never reach would lock the user's balance for good
+    let new_timeout = u64::from_le_bytes(
+        new_args[timeout_start..timeout_end]
+            .try_into()
+            .map_err(|_| Error::LengthNotEnough)?,
+    );
+    if timeout_with_grace(Since::new(new_timeout)).is_none() {
+        return Err(Error::SpliceLockMismatch);
+    }
+
+    // The fallback timeout is carried over unchanged and must stay after the new timeout
+    if let Some(fallback_timeout) =
+        parse_args(args_version(&new_args)?, &new_args)?.fallback_timeout
+    {
+        validate_fallback_timeout(new_timeout, fallback_timeout)
+            .map_err(|_| Error::SpliceLockMismatch)?;
+    }
+
+    // 2. Output 1 is the merchant payout
+    let expected_merchant_lock = expected_merchant_lock(merchant_lock_arg, algorithm_id);
+    if load_cell_lock(1, Source::Output)? != expected_merchant_lock {
+        return Err(Error::MerchantPubkeyHashMismatch);
+    }
+
+    // 3. Both outputs carry the input's type script, and an xUDT channel only moves tokens
+    // between the new channel and the merchant
+    let input_type = load_cell_type(0, Source::GroupInput)?;
+    for index in 0..2 {
+        if load_cell_type(index, Source::Output)? != input_type {
+            return Err(Error::TypeScriptMismatch);
+        }
+    }
+    if input_type.is_some() {
+        let input_amount = xudt_amount(&load_cell_data(0, Source::GroupInput)?)?;
+        let output_amount = xudt_amount(&load_cell_data(0, Source::Output)?)?
+            .checked_add(xudt_amount(&load_cell_data(1, Source::Output)?)?)
+            .ok_or(Error::XudtConservationViolation)?;
+        if output_amount != input_amount {
+            return Err(Error::XudtConservationViolation);
+        }
+    }
+
+    // 4. Both outputs hold at least their occupied capacity
+    for index in 0..2 {
+        if load_cell_capacity(index, Source::Output)?
+            < load_cell_occupied_capacity(index, Source::Output)?
+        {
+            return Err(Error::OutputBelowMinCapacity);
+        }
+    }
+
+    // 5. Verify CKB capacity fee is not excessive
+    let input_capacity = load_cell_capacity(0, Source::GroupInput)?;
+    let total_output_capacity = load_cell_capacity(0, Source::Output)?
+        .checked_add(load_cell_capacity(1, Source::Output)?)
+        .ok_or(Error::ExcessiveFee)?;
+    if input_capacity.saturating_sub(total_output_capacity) > MAX_FEE {
+        return Err(Error::ExcessiveFee);
+    }
+
+    Ok(())
+}
+
+// Arbitration outputs are free-form: the channel's tokens must all reach the outputs and
+// at most MAX_FEE of its capacity may go to the fee.
+fn verify_arbitration_output_structure() -> Result<(), Error> {
+    let input_type = load_cell_type(0, Source::GroupInput)?;
+    if input_type.is_some() {
+        let input_amount = xudt_amount(&load_cell_data(0, Source::GroupInput)?)?;
+        let mut output_amount: u128 = 0;
+        for (index, output_type) in QueryIter::new(load_cell_type, Source::Output).enumerate() {
+            if output_type == input_type {
+                output_amount = output_amount
+                    .checked_add(xudt_amount(&load_cell_data(index, Source::Output)?)?)
+                    .ok_or(Error::XudtConservationViolation)?;
+            }
+        }
+        if output_amount != input_amount {
+            return Err(Error::XudtConservationViolation);
+        }
+    }
+
+    let input_capacity = load_cell_capacity(0, Source::GroupInput)?;
+    let mut total_output_capacity: u64 = 0;
+    for capacity in QueryIter::new(load_cell_capacity, Source::Output) {
+        total_output_capacity = total_output_capacity
+            .checked_add(capacity)
+            .ok_or(Error::ExcessiveFee)?;
+    }
+    if input_capacity.saturating_sub(total_output_capacity) > MAX_FEE {
+        return Err(Error::ExcessiveFee);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,705 @@
+/// Channel lifecycle as a Rust API
+///
+/// The CLI commands read and write JSON files and print progress; `Channel` drives the
+/// same lifecycle in memory and hands back the transactions instead:
+///
+/// ```ignore
+/// let mut channel = Channel::open(backend, params).await?;
+/// let commitment = channel.pay(10 * 100_000_000).await?;
+/// let commitment = channel.pay(5 * 100_000_000).await?; // 15 CKB in total
+/// let settle_tx = channel.settle(commitment).await?;
+/// ```
+///
+/// A channel funded earlier is picked up with [`Channel::resume`], which is how `pay` and
+/// `settle` drive it from the channel info file and the commitment on disk.
+///
+/// Transaction construction goes through a [`ChannelBackend`]; [`BuilderBackend`] uses
+/// the v2 builders (funding_v2, commitment_v2, refund_v2) with in-process signers.
+use anyhow::{anyhow, Result};
+use ckb_sdk::unlock::MultisigConfig;
+use ckb_types::{
+    core::TransactionView,
+    packed::{CellDep, Script},
+    prelude::*,
+};
+use std::sync::Arc;
+
+use crate::commands::splice::merchant_multisig_config;
+use crate::signer::{RawKeySigner, Signer};
+use crate::tx_builder::capacity_utils::{merchant_min_capacity, ChangePolicy};
+use crate::tx_builder::commitment_v2::{
+    code_cell_dep, CommitmentContext, CommitmentRequest, CommitmentTx,
+};
+use crate::tx_builder::funding_v2::{
+    find_funding_output_index, FundingContext, FundingRequest, FundingTx,
+};
+use crate::tx_builder::refund_v2::{RefundContext, RefundRequest, RefundTx};
+use crate::utils::config::Config;
+use crate::utils::crypto::SpillmanArgs;
+
+/// Parameters of a CKB channel
+#[derive(Clone, Debug)]
+pub struct ChannelParams {
+    /// Spillman Lock script of the channel (args carry merchant, user and timeout)
+    pub spillman_lock: Script,
+    /// User's lock script (funding source, change and refund destination)
+    pub user_lock: Script,
+    /// Merchant's lock script (payment destination)
+    pub merchant_lock: Script,
+    /// Capacity locked in the Spillman cell, in shannons
+    ///
+    /// Not all of it can be paid: see [`ChannelParams::payable_capacity`].
+    pub capacity: u64,
+    /// Fee rate in shannon/KB
+    pub fee_rate: u64,
+}
+
+impl ChannelParams {
+    /// Most the merchant can be paid in total, in shannons
+    ///
+    /// A commitment keeps an empty user cell and an empty merchant cell, so their
+    /// occupied capacity comes out of the Spillman cell first. The commitment fee is
+    /// taken from the user cell on top of that and checked by the backend.
+    pub fn payable_capacity(&self) -> Result<u64> {
+        let merchant_floor = merchant_min_capacity(&self.merchant_lock, None)?;
+        let user_floor = merchant_min_capacity(&self.user_lock, None)?;
+        Ok(self
+            .capacity
+            .saturating_sub(merchant_floor)
+            .saturating_sub(user_floor))
+    }
+}
+
+/// Where the channel is in its lifecycle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelStatus {
+    /// Funded, the user can keep paying
+    Open,
+    /// Closed by the merchant with the latest commitment
+    Settled,
+    /// Closed by the user through the timeout path
+    Refunded,
+}
+
+/// State of an opened channel
+#[derive(Clone, Debug)]
+pub struct ChannelState {
+    /// Signed funding transaction
+    pub funding_tx: TransactionView,
+    /// Index of the Spillman Lock cell in the funding transaction outputs
+    pub funding_output_index: u32,
+    pub status: ChannelStatus,
+    /// Total paid to the merchant so far, in shannons
+    pub paid_amount: u64,
+    /// Latest commitment signed by the user (None before the first payment)
+    pub latest_commitment: Option<TransactionView>,
+}
+
+/// Builds and signs the transactions of a channel
+#[async_trait::async_trait]
+pub trait ChannelBackend: Send + Sync {
+    /// Build and sign the funding transaction
+    async fn fund(&self, params: &ChannelParams) -> Result<TransactionView>;
+
+    /// Build a commitment paying `paid_amount` in total, signed by the user
+    async fn commit(
+        &self,
+        params: &ChannelParams,
+        state: &ChannelState,
+        paid_amount: u64,
+    ) -> Result<CommitmentTx>;
+
+    /// Add the merchant signature to a commitment signed by the user
+    async fn settle(
+        &self,
+        params: &ChannelParams,
+        commitment: CommitmentTx,
+    ) -> Result<TransactionView>;
+
+    /// Build the refund transaction signed by both parties
+    async fn refund(&self, params: &ChannelParams, state: &ChannelState)
+        -> Result<TransactionView>;
+}
+
+/// A Spillman channel driven from Rust code
+pub struct Channel<B> {
+    backend: B,
+    params: ChannelParams,
+    state: ChannelState,
+}
+
+impl<B: ChannelBackend> Channel<B> {
+    /// Fund a new channel
+    pub async fn open(backend: B, params: ChannelParams) -> Result<Self> {
+        let funding_tx = backend.fund(&params).await?;
+        let funding_output_index = find_funding_output_index(&funding_tx, &params.spillman_lock)?;
+        let state = ChannelState {
+            funding_tx,
+            funding_output_index,
+            status: ChannelStatus::Open,
+            paid_amount: 0,
+            latest_commitment: None,
+        };
+        Ok(Self {
+            backend,
+            params,
+            state,
+        })
+    }
+
+    /// Pick up a channel funded earlier in the given state
+    pub fn resume(backend: B, params: ChannelParams, state: ChannelState) -> Self {
+        Self {
+            backend,
+            params,
+            state,
+        }
+    }
+
+    pub fn params(&self) -> &ChannelParams {
+        &self.params
+    }
+
+    pub fn state(&self) -> &ChannelState {
+        &self.state
+    }
+
+    /// Pay `amount` more to the merchant
+    ///
+    /// Returns a commitment for the new total; the state only advances once the
+    /// backend has produced it.
+    pub async fn pay(&mut self, amount: u64) -> Result<CommitmentTx> {
+        self.ensure_open()?;
+        if amount == 0 {
+            return Err(anyhow!("Payment amount must be greater than zero"));
+        }
+        let payable = self.params.payable_capacity()?;
+        let paid_amount = self
+            .state
+            .paid_amount
+            .checked_add(amount)
+            .filter(|total| *total <= payable)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Payment exceeds channel capacity: already paid {}, paying {}, payable {}",
+                    self.state.paid_amount,
+                    amount,
+                    payable
+                )
+            })?;
+
+        let commitment = self
+            .backend
+            .commit(&self.params, &self.state, paid_amount)
+            .await?;
+        let tx = commitment
+            .clone()
+            .into_inner()
+            .ok_or_else(|| anyhow!("Backend returned an empty commitment"))?;
+
+        self.state.paid_amount = paid_amount;
+        self.state.latest_commitment = Some(tx);
+        Ok(commitment)
+    }
+
+    /// Close the channel as the merchant with the latest commitment
+    pub async fn settle(&mut self, commitment: CommitmentTx) -> Result<TransactionView> {
+        self.ensure_open()?;
+        let latest_hash = self
+            .state
+            .latest_commitment
+            .as_ref()
+            .map(|tx| tx.hash())
+            .ok_or_else(|| anyhow!("No commitment to settle, the user has not paid yet"))?;
+        let tx = commitment
+            .clone()
+            .into_inner()
+            .ok_or_else(|| anyhow!("Empty commitment"))?;
+        if tx.hash() != latest_hash {
+            return Err(anyhow!(
+                "Stale commitment {:#x}, the latest one is {:#x}",
+                tx.hash(),
+                latest_hash
+            ));
+        }
+
+        let settle_tx = self.backend.settle(&self.params, commitment).await?;
+        self.state.status = ChannelStatus::Settled;
+        Ok(settle_tx)
+    }
+
+    /// Close the channel as the user through the timeout path
+    pub async fn refund(&mut self) -> Result<TransactionView> {
+        self.ensure_open()?;
+        let refund_tx = self.backend.refund(&self.params, &self.state).await?;
+        self.state.status = ChannelStatus::Refunded;
+        Ok(refund_tx)
+    }
+
+    fn ensure_open(&self) -> Result<()> {
+        match self.state.status {
+            ChannelStatus::Open => Ok(()),
+            status => Err(anyhow!("Channel is closed ({:?})", status)),
+        }
+    }
+}
+
+/// Backend over the v2 transaction builders, signing with in-process keys
+///
+/// Each party usually holds only its own key: without a user signer the backend cannot
+/// fund, pay or refund, without a merchant signer it cannot settle.
+#[derive(Clone)]
+pub struct BuilderBackend {
+    pub rpc_url: String,
+    pub user_signer: Option<Arc<dyn Signer>>,
+    pub merchant_signer: Option<Arc<dyn Signer>>,
+    /// Multisig configuration for merchant (if merchant uses multisig)
+    pub merchant_multisig_config: Option<MultisigConfig>,
+    pub spillman_lock_dep: CellDep,
+    pub auth_dep: CellDep,
+}
+
+impl BuilderBackend {
+    /// Backend for the channel locked by `spillman_lock`, with the keys, cell deps and
+    /// node of `config`
+    ///
+    /// A multisig merchant needs its keys in `config` even on the user side: they give
+    /// the size of the merchant part of the commitment witness.
+    pub fn from_config(config: &Config, spillman_lock: &Script) -> Result<Self> {
+        let args = SpillmanArgs::from_bytes(&spillman_lock.args().raw_data())?;
+
+        let user_signer = if config.user.has_private_key() {
+            Some(RawKeySigner::new_arc(config.user.get_secret_keys()?)?)
+        } else {
+            None
+        };
+        let (merchant_signer, merchant_multisig_config) = if config.merchant.is_multisig() {
+            let keys = config.merchant.get_secret_keys()?;
+            let multisig_config = merchant_multisig_config(config, &keys, args.algorithm_id)?;
+            (Some(RawKeySigner::new_arc(keys)?), multisig_config)
+        } else if config.merchant.has_private_key() {
+            let keys = config.merchant.get_secret_keys()?;
+            (Some(RawKeySigner::new_arc(keys)?), None)
+        } else {
+            (None, None)
+        };
+
+        Ok(Self {
+            rpc_url: config.network.rpc_url.clone(),
+            user_signer,
+            merchant_signer,
+            merchant_multisig_config,
+            spillman_lock_dep: code_cell_dep(
+                &config.spillman_lock.tx_hash,
+                config.spillman_lock.index,
+            )?,
+            auth_dep: code_cell_dep(&config.auth.tx_hash, config.auth.index)?,
+        })
+    }
+
+    fn user_signer(&self) -> Result<&Arc<dyn Signer>> {
+        self.user_signer
+            .as_ref()
+            .ok_or_else(|| anyhow!("User private key is required"))
+    }
+
+    fn merchant_signer(&self) -> Result<&Arc<dyn Signer>> {
+        self.merchant_signer
+            .as_ref()
+            .ok_or_else(|| anyhow!("Merchant private key is required"))
+    }
+}
+
+#[async_trait::async_trait]
+impl ChannelBackend for BuilderBackend {
+    async fn fund(&self, params: &ChannelParams) -> Result<TransactionView> {
+        let request = FundingRequest {
+            script: params.spillman_lock.clone(),
+            local_amount: params.capacity,
+            fee_rate: params.fee_rate,
+            xudt_type_script: None,
+            xudt_amount: None,
+            exclude_outpoints: vec![],
+            change_lock: None,
+            change_policy: ChangePolicy::default(),
+            explicit_inputs: None,
+        };
+        let context = FundingContext::new_single_source(
+            self.user_signer()?.clone(),
+            None,
+            self.rpc_url.clone(),
+            params.user_lock.clone(),
+            None,
+            None,
+        );
+        FundingTx::new()
+            .build(request, context)
+            .await?
+            .into_inner()
+            .ok_or_else(|| anyhow!("Failed to build funding transaction"))
+    }
+
+    async fn commit(
+        &self,
+        params: &ChannelParams,
+        state: &ChannelState,
+        paid_amount: u64,
+    ) -> Result<CommitmentTx> {
+        let request = CommitmentRequest::new(
+            state.funding_tx.hash().unpack(),
+            state.funding_tx.clone(),
+            state.funding_output_index,
+            params.user_lock.clone(),
+            params.merchant_lock.clone(),
+            paid_amount,
+            params.fee_rate,
+        );
+        let user_signer = self.user_signer()?;
+        let context = CommitmentContext {
+            merchant_multisig_config: self.merchant_multisig_config.clone(),
+            spillman_lock_dep: self.spillman_lock_dep.clone(),
+            auth_dep: self.auth_dep.clone(),
+        };
+        CommitmentTx::new()
+            .build(request, context)
+            .await?
+            .sign_for_spillman_lock(
+                user_signer.as_ref(),
+                &params.spillman_lock.args().raw_data(),
+                self.merchant_multisig_config.as_ref(),
+            )
+    }
+
+    async fn settle(
+        &self,
+        params: &ChannelParams,
+        commitment: CommitmentTx,
+    ) -> Result<TransactionView> {
+        commitment
+            .sign_as_merchant(
+                self.merchant_signer()?.as_ref(),
+                &params.spillman_lock.args().raw_data(),
+                self.merchant_multisig_config.as_ref(),
+            )?
+            .into_inner()
+            .ok_or_else(|| anyhow!("Failed to sign commitment transaction"))
+    }
+
+    async fn refund(
+        &self,
+        params: &ChannelParams,
+        state: &ChannelState,
+    ) -> Result<TransactionView> {
+        let request = RefundRequest {
+            funding_tx_hash: state.funding_tx.hash().unpack(),
+            funding_tx: state.funding_tx.clone(),
+            funding_output_index: state.funding_output_index,
+            user_lock_script: params.user_lock.clone(),
+            merchant_lock_script: None,
+            co_funded: false,
+            fee_rate: params.fee_rate,
+            xudt_cell_dep: None,
+            sponsor: None,
+        };
+        let user_signer = self.user_signer()?;
+        let merchant_signer = self.merchant_signer()?;
+        let context = RefundContext {
+            user_signer: user_signer.clone(),
+            merchant_signer: Some(merchant_signer.clone()),
+            merchant_multisig_config: self.merchant_multisig_config.clone(),
+            rpc_url: self.rpc_url.clone(),
+            spillman_lock_dep: self.spillman_lock_dep.clone(),
+            auth_dep: self.auth_dep.clone(),
+        };
+        RefundTx::new()
+            .build(request, context)
+            .await?
+            .sign_for_spillman_lock(
+                user_signer.as_ref(),
+                merchant_signer.as_ref(),
+                &params.spillman_lock.args().raw_data(),
+                self.merchant_multisig_config.as_ref(),
+            )?
+            .into_inner()
+            .ok_or_else(|| anyhow!("Failed to build refund transaction"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::{
+        bytes::Bytes,
+        core::{Capacity, ScriptHashType},
+        packed::CellOutput,
+        H256,
+    };
+
+    fn lock(byte: u8) -> Script {
+        Script::new_builder()
+            .code_hash(H256([byte; 32]).pack())
+            .hash_type(ScriptHashType::Type)
+            .args(Bytes::from(vec![byte; 20]).pack())
+            .build()
+    }
+
+    fn params() -> ChannelParams {
+        ChannelParams {
+            spillman_lock: lock(1),
+            user_lock: lock(2),
+            merchant_lock: lock(3),
+            capacity: 1000 * 100_000_000,
+            fee_rate: 1_000,
+        }
+    }
+
+    fn cell(lock: Script, capacity: u64) -> CellOutput {
+        CellOutput::new_builder()
+            .lock(lock)
+            .capacity(Capacity::shannons(capacity))
+            .build()
+    }
+
+    /// Builds placeholder transactions whose outputs mirror the requested amounts
+    struct MockBackend;
+
+    #[async_trait::async_trait]
+    impl ChannelBackend for MockBackend {
+        async fn fund(&self, params: &ChannelParams) -> Result<TransactionView> {
+            // Change first, so the funding cell is not at index 0
+            Ok(TransactionView::new_advanced_builder()
+                .output(cell(params.user_lock.clone(), 10))
+                .output_data(Bytes::new().pack())
+                .output(cell(params.spillman_lock.clone(), params.capacity))
+                .output_data(Bytes::new().pack())
+                .build())
+        }
+
+        async fn commit(
+            &self,
+            params: &ChannelParams,
+            state: &ChannelState,
+            paid_amount: u64,
+        ) -> Result<CommitmentTx> {
+            Ok(TransactionView::new_advanced_builder()
+                .output(cell(
+                    params.user_lock.clone(),
+                    params.capacity - paid_amount,
+                ))
+                .output_data(Bytes::new().pack())
+                .output(cell(params.merchant_lock.clone(), paid_amount))
+                .output_data(Bytes::new().pack())
+                .witness(Bytes::from(vec![state.funding_output_index as u8]).pack())
+                .build()
+                .into())
+        }
+
+        async fn settle(
+            &self,
+            _params: &ChannelParams,
+            commitment: CommitmentTx,
+        ) -> Result<TransactionView> {
+            commitment
+                .into_inner()
+                .ok_or_else(|| anyhow!("Empty commitment"))
+        }
+
+        async fn refund(
+            &self,
+            params: &ChannelParams,
+            _state: &ChannelState,
+        ) -> Result<TransactionView> {
+            Ok(TransactionView::new_advanced_builder()
+                .output(cell(params.user_lock.clone(), params.capacity))
+                .output_data(Bytes::new().pack())
+                .build())
+        }
+    }
+
+    fn merchant_capacity(commitment: &CommitmentTx) -> u64 {
+        let tx = commitment.clone().into_inner().unwrap();
+        tx.outputs().get(1).unwrap().capacity().unpack()
+    }
+
+    #[tokio::test]
+    async fn test_channel_pay_is_cumulative_and_monotonic() {
+        let mut channel = Channel::open(MockBackend, params()).await.unwrap();
+        assert_eq!(channel.state().status, ChannelStatus::Open);
+        assert_eq!(channel.state().funding_output_index, 1);
+        assert!(channel.state().latest_commitment.is_none());
+
+        let first = channel.pay(100).await.unwrap();
+        assert_eq!(merchant_capacity(&first), 100);
+        let second = channel.pay(50).await.unwrap();
+        assert_eq!(merchant_capacity(&second), 150);
+        assert_eq!(channel.state().paid_amount, 150);
+
+        // Both 61 CKB cells stay in the commitment: 878 CKB can be paid
+        let payable = channel.params().payable_capacity().unwrap();
+        assert_eq!(payable, 878 * 100_000_000);
+
+        // Zero and over-capacity payments leave the state untouched
+        assert!(channel.pay(0).await.is_err());
+        let err = channel.pay(payable - 149).await.unwrap_err();
+        assert!(err.to_string().contains("exceeds channel capacity"));
+        assert!(channel.pay(u64::MAX).await.is_err());
+        assert_eq!(channel.state().paid_amount, 150);
+        assert_eq!(
+            channel.state().latest_commitment.as_ref().unwrap().hash(),
+            second.into_inner().unwrap().hash()
+        );
+
+        // Paying out everything payable is allowed
+        channel.pay(payable - 150).await.unwrap();
+        assert_eq!(channel.state().paid_amount, payable);
+    }
+
+    #[tokio::test]
+    async fn test_channel_settle_only_with_latest_commitment() {
+        let mut channel = Channel::open(MockBackend, params()).await.unwrap();
+        let err = channel
+            .settle(CommitmentTx::from(
+                TransactionView::new_advanced_builder().build(),
+            ))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("No commitment to settle"));
+
+        let stale = channel.pay(100).await.unwrap();
+        let latest = channel.pay(200).await.unwrap();
+        let err = channel.settle(stale).await.unwrap_err();
+        assert!(err.to_string().contains("Stale commitment"));
+        assert_eq!(channel.state().status, ChannelStatus::Open);
+
+        let settle_tx = channel.settle(latest).await.unwrap();
+        let paid: u64 = settle_tx.outputs().get(1).unwrap().capacity().unpack();
+        assert_eq!(paid, 300);
+        assert_eq!(channel.state().status, ChannelStatus::Settled);
+
+        // A settled channel accepts no further payment or close
+        assert!(channel.pay(1).await.is_err());
+        assert!(channel.refund().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_channel_refund_closes_channel() {
+        let mut channel = Channel::open(MockBackend, params()).await.unwrap();
+        let commitment = channel.pay(100).await.unwrap();
+
+        channel.refund().await.unwrap();
+        assert_eq!(channel.state().status, ChannelStatus::Refunded);
+        let err = channel.settle(commitment).await.unwrap_err();
+        assert!(err.to_string().contains("Channel is closed (Refunded)"));
+        assert!(channel.refund().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_builder_backend_pays_and_settles_version_1_channel() {
+        use crate::tx_builder::commitment_v2::COMMITMENT_CHANNEL_OUTPUTS;
+        use crate::tx_builder::signing::{
+            spillman_commitment_signing_message, spillman_signing_message,
+            spillman_signing_message_for_version, ARGS_VERSION_BOUND_CELL_DEPS,
+        };
+        use crate::tx_builder::witness_utils::{
+            EMPTY_WITNESS_ARGS_SIZE, SIGNATURE_SIZE, UNLOCK_TYPE_SIZE,
+        };
+        use crate::utils::crypto::pubkey_hash;
+        use ckb_crypto::secp::{Privkey, Signature};
+        use ckb_types::packed::{CellInput, OutPoint};
+
+        let user_key = secp256k1::SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let merchant_key = secp256k1::SecretKey::from_slice(&[8u8; 32]).unwrap();
+        let user_pubkey = Privkey::from_slice(&[7u8; 32]).pubkey().unwrap();
+        let merchant_pubkey = Privkey::from_slice(&[8u8; 32]).pubkey().unwrap();
+
+        let mut args = SpillmanArgs::new_with_algorithm(
+            pubkey_hash(&merchant_pubkey),
+            pubkey_hash(&user_pubkey),
+            0x4000_0000_6553_f100,
+            0,
+        );
+        args.version = ARGS_VERSION_BOUND_CELL_DEPS;
+        let spillman_lock = Script::new_builder()
+            .code_hash(H256([0x11; 32]).pack())
+            .hash_type(ScriptHashType::Type)
+            .args(Bytes::from(args.to_bytes()).pack())
+            .build();
+        let params = ChannelParams {
+            spillman_lock: spillman_lock.clone(),
+            user_lock: lock(2),
+            merchant_lock: lock(3),
+            capacity: 1000 * 100_000_000,
+            fee_rate: 1000,
+        };
+        let funding_tx = TransactionView::new_advanced_builder()
+            .output(cell(spillman_lock, params.capacity))
+            .output_data(Bytes::new().pack())
+            .build();
+        let state = ChannelState {
+            funding_tx,
+            funding_output_index: 0,
+            status: ChannelStatus::Open,
+            paid_amount: 0,
+            latest_commitment: None,
+        };
+        let dep = |index: u32| {
+            CellDep::new_builder()
+                .out_point(OutPoint::new(H256([0x55; 32]).pack(), index))
+                .build()
+        };
+        let merchant_signer = RawKeySigner::new_arc(vec![merchant_key]).unwrap();
+        let backend = BuilderBackend {
+            rpc_url: String::new(),
+            user_signer: Some(RawKeySigner::new_arc(vec![user_key]).unwrap()),
+            merchant_signer: Some(merchant_signer.clone()),
+            merchant_multisig_config: None,
+            spillman_lock_dep: dep(0),
+            auth_dep: dep(1),
+        };
+
+        // pay, then settle, both signing the version 1 message that binds the cell deps
+        let mut channel = Channel::resume(backend, params, state);
+        let commitment = channel.pay(100 * 100_000_000).await.unwrap();
+        let settled = channel.settle(commitment).await.unwrap();
+        let message = spillman_signing_message_for_version(&settled, args.version).unwrap();
+        assert_ne!(message, spillman_signing_message(&settled));
+
+        let witness = settled.witnesses().get(0).unwrap().raw_data();
+        let sig_start = EMPTY_WITNESS_ARGS_SIZE + UNLOCK_TYPE_SIZE;
+        let recover = |sig: &[u8]| {
+            Signature::from_slice(sig)
+                .unwrap()
+                .recover(&message.into())
+                .unwrap()
+        };
+        assert_eq!(
+            recover(&witness[sig_start..sig_start + SIGNATURE_SIZE]),
+            merchant_pubkey
+        );
+        assert_eq!(recover(&witness[sig_start + SIGNATURE_SIZE..]), user_pubkey);
+
+        // A fee input and change appended by the merchant keep both signatures valid
+        let with_fee = settled
+            .as_advanced_builder()
+            .input(CellInput::new(OutPoint::new(H256([0x66; 32]).pack(), 0), 0))
+            .output(cell(lock(3), 100 * 100_000_000))
+            .output_data(Bytes::new().pack())
+            .witness(Bytes::new().pack())
+            .build();
+        assert_eq!(
+            spillman_commitment_signing_message(
+                &with_fee,
+                args.version,
+                COMMITMENT_CHANNEL_OUTPUTS
+            )
+            .unwrap(),
+            message
+        );
+
+        // The merchant part can only be filled in once
+        let err = CommitmentTx::from(settled)
+            .sign_as_merchant(merchant_signer.as_ref(), &args.to_bytes(), None)
+            .unwrap_err();
+        assert!(err.to_string().contains("already present"));
+    }
+}
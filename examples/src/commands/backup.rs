@@ -0,0 +1,54 @@
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::utils::storage::{export, import, ChannelSnapshot};
+
+/// Save a snapshot of the channel in `channel_dir` (private keys excluded) to `output`
+pub async fn execute_backup(channel_dir: &str, output: &str) -> Result<()> {
+    println!("💾 备份通道: {}", channel_dir);
+
+    let snapshot = export(Path::new(channel_dir))?;
+    if let Some(parent) = Path::new(output).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output, serde_json::to_string_pretty(&snapshot)?)?;
+
+    print_snapshot(&snapshot);
+    println!("\n✅ 备份已保存: {}", output);
+    println!("💡 备份不包含私钥，恢复后仍需原配置文件中的密钥");
+    Ok(())
+}
+
+/// Restore the channel saved in `snapshot_file` into `channel_dir`
+pub async fn execute_restore(snapshot_file: &str, channel_dir: &str) -> Result<()> {
+    println!("📥 恢复通道: {}", snapshot_file);
+
+    let content = fs::read_to_string(snapshot_file)
+        .map_err(|e| anyhow!("Failed to read snapshot file {}: {}", snapshot_file, e))?;
+    let snapshot: ChannelSnapshot = serde_json::from_str(&content)
+        .map_err(|e| anyhow!("Failed to parse channel snapshot: {}", e))?;
+    import(&snapshot, Path::new(channel_dir))?;
+
+    print_snapshot(&snapshot);
+    println!("\n✅ 通道已恢复到: {}", channel_dir);
+    Ok(())
+}
+
+fn print_snapshot(snapshot: &ChannelSnapshot) {
+    println!("  - 通道信息: {}", snapshot.channel_info_file);
+    println!("  - Funding tx: {}", snapshot.channel_info.funding_tx_hash);
+    println!(
+        "  - Funding 交易: {}",
+        if snapshot.funding_tx.is_some() {
+            "✓"
+        } else {
+            "无"
+        }
+    );
+    match snapshot.refund_tx {
+        Some((ref name, _)) => println!("  - Refund 交易: {}", name),
+        None => println!("  - Refund 交易: 无"),
+    }
+    println!("  - 支付记录: {} 条", snapshot.commitments.len());
+}
@@ -0,0 +1,341 @@
+use anyhow::{anyhow, Result};
+use ckb_crypto::secp::Privkey;
+use ckb_sdk::{
+    constants::{MultisigScript, ONE_CKB, SIGHASH_TYPE_HASH},
+    Since, SinceType,
+};
+use ckb_testtool::ckb_types as testtool_types;
+use ckb_testtool::ckb_types::prelude::{Entity as _, IntoTransactionView as _};
+use ckb_testtool::context::Context;
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, ScriptHashType, TransactionBuilder, TransactionView},
+    packed::{CellDep, CellInput, CellOutput, OutPoint, Script},
+    prelude::*,
+};
+use std::fs;
+
+use crate::signer::{RawKeySigner, Signer};
+use crate::tx_builder::funding_v2::build_multisig_config;
+use crate::tx_builder::signing::spillman_signing_message;
+use crate::tx_builder::witness_utils::{
+    assemble_spillman_witness, MerchantSig, UNLOCK_TYPE_COMMITMENT, UNLOCK_TYPE_TIMEOUT,
+};
+use crate::utils::crypto::{pubkey_hash, SpillmanArgs};
+
+/// Cycle limit of a bench run (the node's default `max_tx_verify_cycles`)
+const MAX_CYCLES: u64 = 70_000_000;
+
+/// Timeout of the benchmarked channel: 2025-01-01 00:00:00 UTC
+const BENCH_TIMEOUT_TIMESTAMP: u64 = 1_735_689_600;
+
+/// Spillman cell capacity, 1 CKB of which is paid as fee
+const SPILLMAN_CAPACITY: u64 = 1001 * ONE_CKB;
+
+/// xUDT amount locked in the Spillman cell of an xUDT channel
+const XUDT_AMOUNT: u128 = 1000;
+
+/// Unlock path to benchmark
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BenchPath {
+    Commitment,
+    Timeout,
+}
+
+/// Channel shape of a bench run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchCase {
+    pub path: BenchPath,
+    /// Merchant multisig (threshold, total), single-sig when None
+    pub multisig: Option<(u8, u8)>,
+    pub xudt: bool,
+}
+
+/// Contract binaries deployed into the in-memory chain
+pub struct BenchBinaries {
+    pub spillman_lock: Bytes,
+    pub auth: Bytes,
+    /// simple_udt, only needed for xUDT channels
+    pub simple_udt: Option<Bytes>,
+}
+
+impl BenchBinaries {
+    pub fn load(spillman_lock: &str, auth: &str, simple_udt: Option<&str>) -> Result<Self> {
+        let read = |path: &str| {
+            fs::read(path)
+                .map(Bytes::from)
+                .map_err(|e| anyhow!("Failed to read binary {}: {}", path, e))
+        };
+        Ok(Self {
+            spillman_lock: read(spillman_lock)?,
+            auth: read(auth)?,
+            simple_udt: simple_udt.map(read).transpose()?,
+        })
+    }
+}
+
+/// Execute bench command - measure the cycles of one unlock path
+pub async fn execute(
+    path: BenchPath,
+    multisig: Option<&str>,
+    xudt: bool,
+    binary: &str,
+    auth_binary: &str,
+    udt_binary: &str,
+) -> Result<()> {
+    println!("\n═══════════════════════════════════════════════════════");
+    println!("  ⏱️  合约 cycles 测量");
+    println!("═══════════════════════════════════════════════════════\n");
+
+    let case = BenchCase {
+        path,
+        multisig: multisig.map(parse_multisig).transpose()?,
+        xudt,
+    };
+    println!("📋 场景:");
+    println!("  - 解锁路径: {:?}", case.path);
+    match case.multisig {
+        Some((threshold, total)) => println!("  - 商户: 多签 ({}-of-{})", threshold, total),
+        None => println!("  - 商户: 单签"),
+    }
+    println!("  - xUDT: {}", if case.xudt { "是" } else { "否" });
+
+    println!("\n📦 加载合约二进制...");
+    let binaries = BenchBinaries::load(binary, auth_binary, xudt.then_some(udt_binary))?;
+    println!(
+        "  - spillman-lock: {} ({} bytes)",
+        binary,
+        binaries.spillman_lock.len()
+    );
+    println!("  - auth: {} ({} bytes)", auth_binary, binaries.auth.len());
+
+    match run(case, &binaries) {
+        Ok(cycles) => {
+            println!("\n✅ 验证通过");
+            println!("  - 消耗 cycles: {}", cycles);
+            Ok(())
+        }
+        Err(e) => {
+            println!("\n❌ 验证失败: {}", e);
+            Err(e)
+        }
+    }
+}
+
+/// Parse a `M-of-N` multisig shape
+pub fn parse_multisig(value: &str) -> Result<(u8, u8)> {
+    let invalid = || {
+        anyhow!(
+            "Invalid multisig '{}', expected M-of-N (e.g. 2-of-3)",
+            value
+        )
+    };
+    let (threshold, total) = value.split_once("-of-").ok_or_else(invalid)?;
+    let threshold: u8 = threshold.parse().map_err(|_| invalid())?;
+    let total: u8 = total.parse().map_err(|_| invalid())?;
+    if threshold == 0 || threshold > total {
+        return Err(invalid());
+    }
+    Ok((threshold, total))
+}
+
+/// Build a representative transaction for `case`, verify it in-memory and return its cycles
+///
+/// Keys are fixed so repeated runs hash and sign the same bytes. Commitments split the
+/// cell 500/500 CKB (300/700 xUDT), refunds return 1000 CKB to the user at the earliest
+/// allowed since; both pay 1 CKB fee.
+pub fn run(case: BenchCase, binaries: &BenchBinaries) -> Result<u64> {
+    let mut context = Context::default();
+    let spillman_lock_out_point = context.deploy_cell(binaries.spillman_lock.clone());
+    let auth_out_point = context.deploy_cell(binaries.auth.clone());
+    let mut cell_deps = vec![
+        cell_dep(&spillman_lock_out_point)?,
+        cell_dep(&auth_out_point)?,
+    ];
+
+    // Key 1 is the user, keys 2.. the merchant
+    let user_key = bench_key(1)?;
+    let merchant_keys = (0..case.multisig.map_or(1, |(_, total)| total))
+        .map(|i| bench_key(2 + i))
+        .collect::<Result<Vec<_>>>()?;
+    let user_pubkey_hash = key_hash(&user_key)?;
+    let signer = RawKeySigner::new([&[user_key][..], &merchant_keys].concat())?;
+
+    let (merchant_lock_arg, multisig_config, algorithm_id, merchant_lock) = match case.multisig {
+        Some((threshold, total)) => {
+            let config = build_multisig_config(&merchant_keys, threshold, total)?;
+            let lock_arg = config.hash160().0;
+            let script_id = MultisigScript::V2.script_id();
+            let lock = lock_script(&script_id.code_hash.0, script_id.hash_type, &lock_arg);
+            (lock_arg, Some(config), 7, lock)
+        }
+        None => {
+            let lock_arg = key_hash(&merchant_keys[0])?;
+            let lock = lock_script(&SIGHASH_TYPE_HASH.0, ScriptHashType::Type, &lock_arg);
+            (lock_arg, None, 0, lock)
+        }
+    };
+    let user_lock = lock_script(
+        &SIGHASH_TYPE_HASH.0,
+        ScriptHashType::Type,
+        &user_pubkey_hash,
+    );
+
+    let args = SpillmanArgs::new_with_algorithm(
+        merchant_lock_arg,
+        user_pubkey_hash,
+        Since::new(SinceType::Timestamp, BENCH_TIMEOUT_TIMESTAMP, false).value(),
+        algorithm_id,
+    );
+    let spillman_lock = context
+        .build_script(&spillman_lock_out_point, args.to_bytes().into())
+        .ok_or_else(|| anyhow!("Failed to build Spillman Lock script"))?;
+
+    let type_script = if case.xudt {
+        let simple_udt = binaries
+            .simple_udt
+            .clone()
+            .ok_or_else(|| anyhow!("xUDT bench needs the simple_udt binary"))?;
+        let simple_udt_out_point = context.deploy_cell(simple_udt);
+        cell_deps.push(cell_dep(&simple_udt_out_point)?);
+        let type_script = context
+            .build_script(&simple_udt_out_point, vec![42u8; 32].into())
+            .ok_or_else(|| anyhow!("Failed to build xUDT type script"))?;
+        Some(from_testtool::<Script>(type_script.as_slice())?)
+    } else {
+        None
+    };
+    let xudt_data = |amount: u128| match type_script {
+        Some(_) => Bytes::from(amount.to_le_bytes().to_vec()),
+        None => Bytes::new(),
+    };
+    let output = |lock: &Script, capacity: u64| {
+        CellOutput::new_builder()
+            .capacity(Capacity::shannons(capacity))
+            .lock(lock.clone())
+            .type_(type_script.clone().pack())
+            .build()
+    };
+
+    let spillman_cell = output(&from_testtool(spillman_lock.as_slice())?, SPILLMAN_CAPACITY);
+    let spillman_out_point = context.create_cell(
+        to_testtool(spillman_cell.as_slice())?,
+        xudt_data(XUDT_AMOUNT),
+    );
+    let spillman_out_point: OutPoint = from_testtool(spillman_out_point.as_slice())?;
+
+    let (unlock_type, since, outputs, outputs_data) = match case.path {
+        BenchPath::Commitment => (
+            UNLOCK_TYPE_COMMITMENT,
+            0,
+            vec![
+                output(&user_lock, 500 * ONE_CKB),
+                output(&merchant_lock, 500 * ONE_CKB),
+            ],
+            vec![xudt_data(300), xudt_data(700)],
+        ),
+        BenchPath::Timeout => (
+            UNLOCK_TYPE_TIMEOUT,
+            args.refund_since()?,
+            vec![output(&user_lock, 1000 * ONE_CKB)],
+            vec![xudt_data(XUDT_AMOUNT)],
+        ),
+    };
+
+    let tx = TransactionBuilder::default()
+        .cell_deps(cell_deps)
+        .input(CellInput::new(spillman_out_point, since))
+        .outputs(outputs)
+        .outputs_data(outputs_data.pack())
+        .build();
+    let message = spillman_signing_message(&tx);
+    let merchant_sig = MerchantSig::sign(
+        &signer,
+        message,
+        &merchant_lock_arg,
+        multisig_config.as_ref(),
+    )?;
+    let user_sig = signer.sign(message, &user_pubkey_hash)?;
+    let witness = assemble_spillman_witness(unlock_type, merchant_sig, &user_sig);
+    let tx = tx.as_advanced_builder().witness(witness.pack()).build();
+
+    verify(&context, &tx)
+}
+
+/// Run the contract on `tx` with ckb-testtool
+fn verify(context: &Context, tx: &TransactionView) -> Result<u64> {
+    let tx = to_testtool::<testtool_types::packed::Transaction>(tx.data().as_slice())?.into_view();
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .map_err(|e| anyhow!("{}", e))
+}
+
+/// Deterministic bench key filled with `byte`
+fn bench_key(byte: u8) -> Result<secp256k1::SecretKey> {
+    secp256k1::SecretKey::from_slice(&[byte; 32]).map_err(|e| anyhow!("Invalid bench key: {}", e))
+}
+
+fn key_hash(secret_key: &secp256k1::SecretKey) -> Result<[u8; 20]> {
+    let pubkey = Privkey::from_slice(&secret_key.secret_bytes())
+        .pubkey()
+        .map_err(|e| anyhow!("Failed to derive pubkey: {:?}", e))?;
+    Ok(pubkey_hash(&pubkey))
+}
+
+fn lock_script(code_hash: &[u8; 32], hash_type: ScriptHashType, args: &[u8; 20]) -> Script {
+    Script::new_builder()
+        .code_hash(code_hash.pack())
+        .hash_type(hash_type)
+        .args(Bytes::copy_from_slice(args).pack())
+        .build()
+}
+
+fn cell_dep(out_point: &testtool_types::packed::OutPoint) -> Result<CellDep> {
+    Ok(CellDep::new_builder()
+        .out_point(from_testtool::<OutPoint>(out_point.as_slice())?)
+        .build())
+}
+
+// ckb-testtool pins an older ckb-types; molecule values cross over as serialized bytes
+
+fn from_testtool<T: Entity>(slice: &[u8]) -> Result<T> {
+    T::from_slice(slice).map_err(|e| anyhow!("Failed to convert ckb-testtool value: {}", e))
+}
+
+fn to_testtool<T: testtool_types::prelude::Entity>(slice: &[u8]) -> Result<T> {
+    T::from_slice(slice).map_err(|e| anyhow!("Failed to convert to ckb-testtool value: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Needs the contract built with `make build`
+    #[test]
+    fn test_bench_commitment_single_sig_consumes_cycles() {
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/..");
+        let binaries = BenchBinaries::load(
+            &format!("{}/build/release/spillman-lock", root),
+            &format!("{}/deps/auth", root),
+            None,
+        )
+        .unwrap();
+
+        let case = BenchCase {
+            path: BenchPath::Commitment,
+            multisig: None,
+            xudt: false,
+        };
+        let cycles = run(case, &binaries).unwrap();
+        assert!(cycles > 0);
+    }
+
+    #[test]
+    fn test_parse_multisig() {
+        assert_eq!(parse_multisig("2-of-3").unwrap(), (2, 3));
+        assert_eq!(parse_multisig("1-of-1").unwrap(), (1, 1));
+        for invalid in ["3-of-2", "0-of-3", "2of3", "a-of-3", "2-of-"] {
+            assert!(parse_multisig(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use anyhow::{anyhow, Result};
+use ckb_sdk::{HumanCapacity, Since, SinceType};
+use ckb_types::{
+    core::{EpochNumberWithFraction, TransactionView},
+    prelude::*,
+};
+use std::fs;
+
+use crate::utils::crypto::SpillmanArgs;
+
+/// Decode Spillman Lock args (hex) and print the channel parameters
+pub async fn execute(args_hex: &str) -> Result<()> {
+    println!("\n═══════════════════════════════════════════════════════");
+    println!("  🔎 解析 Spillman Lock Args");
+    println!("═══════════════════════════════════════════════════════\n");
+
+    let bytes = hex::decode(args_hex.trim_start_matches("0x"))
+        .map_err(|e| anyhow!("Invalid args hex: {}", e))?;
+    let args = SpillmanArgs::from_bytes(&bytes)?;
+
+    for line in describe_args(&args) {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+/// Decode a commitment transaction file and print how it splits the channel
+pub async fn execute_commitment(tx_file: &str, funding_capacity: u64) -> Result<()> {
+    println!("\n═══════════════════════════════════════════════════════");
+    println!("  🔎 解析 Commitment 交易");
+    println!("═══════════════════════════════════════════════════════\n");
+
+    let tx_json_str = fs::read_to_string(tx_file)
+        .map_err(|e| anyhow!("Failed to read transaction file: {}", e))?;
+    let tx_json: ckb_jsonrpc_types::TransactionView = serde_json::from_str(&tx_json_str)
+        .map_err(|e| anyhow!("Failed to parse transaction JSON: {}", e))?;
+    let tx = ckb_types::packed::Transaction::from(tx_json.inner).into_view();
+
+    let split = commitment_split(&tx, funding_capacity)?;
+    println!("  - 交易: {:#x}", tx.hash());
+    println!("  - 通道资金: {}", HumanCapacity(funding_capacity));
+    for line in split.describe() {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+/// How a commitment splits the channel between user, merchant and fee
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitmentSplit {
+    /// CKB of the user output (index 0)
+    pub user_capacity: u64,
+    /// CKB of all merchant outputs
+    pub merchant_capacity: u64,
+    /// Number of merchant outputs
+    pub merchant_outputs: usize,
+    /// Funding capacity not paid to user or merchant
+    pub fee: u64,
+    /// xUDT amounts (user, merchant) of an xUDT channel
+    pub xudt: Option<(u128, u128)>,
+}
+
+impl CommitmentSplit {
+    /// Human-readable lines of the split
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("  - 用户余额: {}", HumanCapacity(self.user_capacity)),
+            format!(
+                "  - 商户收到: {} ({} 个输出)",
+                HumanCapacity(self.merchant_capacity),
+                self.merchant_outputs
+            ),
+        ];
+        if let Some((user_xudt, merchant_xudt)) = self.xudt {
+            lines.push(format!("  - 用户 xUDT 余额: {}", user_xudt));
+            lines.push(format!("  - 商户收到 xUDT: {}", merchant_xudt));
+        }
+        lines.push(format!("  - 隐含手续费: {}", HumanCapacity(self.fee)));
+        lines
+    }
+}
+
+/// Split of a commitment funded with `funding_capacity` shannons
+///
+/// Output 0 is the user's; the merchant outputs are the following outputs locked like
+/// output 1. Fee change the merchant adds at settlement uses another lock and is skipped.
+/// An xUDT channel (output 0 has a type script) reads the 16-byte amounts of the data.
+pub fn commitment_split(tx: &TransactionView, funding_capacity: u64) -> Result<CommitmentSplit> {
+    let outputs: Vec<_> = tx.outputs().into_iter().collect();
+    if outputs.len() < 2 {
+        return Err(anyhow!(
+            "Commitment transaction must have a user and a merchant output"
+        ));
+    }
+    let merchant_lock = outputs[1].lock();
+    let merchant_outputs = 1 + outputs[2..]
+        .iter()
+        .take_while(|output| output.lock() == merchant_lock)
+        .count();
+
+    let capacity = |index: usize| -> u64 { outputs[index].capacity().unpack() };
+    let user_capacity = capacity(0);
+    let merchant_capacity: u64 = (1..=merchant_outputs).map(capacity).sum();
+    let fee = funding_capacity
+        .checked_sub(user_capacity + merchant_capacity)
+        .ok_or_else(|| {
+            anyhow!(
+                "Commitment pays out {} but the channel only holds {}",
+                HumanCapacity(user_capacity + merchant_capacity),
+                HumanCapacity(funding_capacity)
+            )
+        })?;
+
+    let xudt = if outputs[0].type_().is_some() {
+        let amount = |index: usize| -> Result<u128> {
+            let data: Vec<u8> = tx
+                .outputs_data()
+                .get(index)
+                .map(|data| data.unpack())
+                .unwrap_or_default();
+            let bytes: [u8; 16] = data
+                .get(..16)
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| anyhow!("xUDT output {} has no 16-byte amount", index))?;
+            Ok(u128::from_le_bytes(bytes))
+        };
+        let merchant_xudt = (1..=merchant_outputs).map(amount).sum::<Result<u128>>()?;
+        Some((amount(0)?, merchant_xudt))
+    } else {
+        None
+    };
+
+    Ok(CommitmentSplit {
+        user_capacity,
+        merchant_capacity,
+        merchant_outputs,
+        fee,
+        xudt,
+    })
+}
+
+/// Human-readable lines describing every field of the args
+pub fn describe_args(args: &SpillmanArgs) -> Vec<String> {
+    let mut lines = vec![
+        format!(
+            "  - 商户 lock arg: 0x{}",
+            hex::encode(args.merchant_lock_arg)
+        ),
+        format!(
+            "  - 用户 pubkey hash: 0x{}",
+            hex::encode(args.user_pubkey_hash)
+        ),
+        format!(
+            "  - 超时: {} (raw {:#x})",
+            describe_since(args.timeout),
+            args.timeout
+        ),
+    ];
+    if let Ok(refund_since) = args.refund_since() {
+        lines.push(format!(
+            "  - 用户可退款: {} (含商户宽限期)",
+            describe_since(refund_since)
+        ));
+    }
+    lines.push(format!(
+        "  - algorithm_id: {} ({})",
+        args.algorithm_id,
+        algorithm_label(args.algorithm_id)
+    ));
+    lines.push(format!("  - version: {}", args.version));
+    if let Some(arbiter_pubkey_hash) = args.arbiter_pubkey_hash {
+        lines.push(format!(
+            "  - 仲裁人 pubkey hash: 0x{}",
+            hex::encode(arbiter_pubkey_hash)
+        ));
+    }
+    lines
+}
+
+/// Meaning of the merchant's algorithm_id
+pub fn algorithm_label(algorithm_id: u8) -> &'static str {
+    match algorithm_id {
+        0 => "CKB 单签",
+        6 => "CKB 多签 (Legacy)",
+        7 => "CKB 多签 (V2)",
+        _ => "不支持的算法",
+    }
+}
+
+/// Interpret a since value: absolute or relative, and its metric
+pub fn describe_since(value: u64) -> String {
+    let since = Since::from_raw_value(value);
+    let Some((since_type, metric)) = since.extract_metric().filter(|_| since.flags_is_valid())
+    else {
+        return "无效的 since".to_string();
+    };
+
+    let relative = since.is_relative();
+    let kind = if relative { "相对" } else { "绝对" };
+    match since_type {
+        SinceType::BlockNumber => format!("{}区块高度 {}", kind, metric),
+        SinceType::EpochNumberWithFraction => {
+            let epoch = EpochNumberWithFraction::from_full_value(metric);
+            format!(
+                "{} epoch {} + {}/{}",
+                kind,
+                epoch.number(),
+                epoch.index(),
+                epoch.length()
+            )
+        }
+        SinceType::Timestamp if relative => format!("相对时间 {} 秒", metric),
+        SinceType::Timestamp => {
+            let date = chrono::DateTime::from_timestamp(metric as i64, 0)
+                .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                .unwrap_or_else(|| "超出范围".to_string());
+            format!("绝对时间戳 {} ({})", metric, date)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_known_args() {
+        // Multisig V2 merchant, absolute timeout at 2025-01-01 00:00:00 UTC
+        let args_hex = format!(
+            "{}{}{}0700",
+            "11".repeat(20),
+            "22".repeat(20),
+            hex::encode(0x4000_0000_6774_8580u64.to_le_bytes())
+        );
+        let args = SpillmanArgs::from_bytes(&hex::decode(args_hex).unwrap()).unwrap();
+
+        let lines = describe_args(&args);
+        assert_eq!(
+            lines[2],
+            "  - 超时: 绝对时间戳 1735689600 (2025-01-01 00:00:00 UTC) (raw 0x4000000067748580)"
+        );
+        assert_eq!(
+            lines[3],
+            "  - 用户可退款: 绝对时间戳 1735690200 (2025-01-01 00:10:00 UTC) (含商户宽限期)"
+        );
+        assert_eq!(lines[4], "  - algorithm_id: 7 (CKB 多签 (V2))");
+        assert_eq!(lines[5], "  - version: 0");
+
+        // Relative block timeout and an unsupported algorithm
+        assert_eq!(
+            describe_since(Since::new(SinceType::BlockNumber, 100, true).value()),
+            "相对区块高度 100"
+        );
+        assert_eq!(algorithm_label(5), "不支持的算法");
+    }
+
+    #[test]
+    fn test_commitment_split() {
+        use ckb_types::{
+            bytes::Bytes,
+            core::{Capacity, ScriptHashType},
+            packed::{CellOutput, Script, Transaction},
+            H256,
+        };
+
+        const ONE_CKB: u64 = 100_000_000;
+        let lock = |byte: u8| {
+            Script::new_builder()
+                .code_hash(H256([0x11; 32]).pack())
+                .hash_type(ScriptHashType::Type)
+                .args(Bytes::from(vec![byte; 20]).pack())
+                .build()
+        };
+        let output = |capacity: u64, lock_byte: u8| {
+            CellOutput::new_builder()
+                .capacity(Capacity::shannons(capacity))
+                .lock(lock(lock_byte))
+        };
+
+        // 1000 CKB channel: user keeps 699.99, merchant gets 200 + 100, settlement change
+        let tx = Transaction::default()
+            .as_advanced_builder()
+            .output(output(69_999 * ONE_CKB / 100, 0x01).build())
+            .output(output(200 * ONE_CKB, 0x02).build())
+            .output(output(100 * ONE_CKB, 0x02).build())
+            .output(output(500 * ONE_CKB, 0x03).build())
+            .outputs_data(vec![Bytes::new().pack(); 4])
+            .build();
+        let split = commitment_split(&tx, 1000 * ONE_CKB).unwrap();
+        assert_eq!(
+            split,
+            CommitmentSplit {
+                user_capacity: 69_999 * ONE_CKB / 100,
+                merchant_capacity: 300 * ONE_CKB,
+                merchant_outputs: 2,
+                fee: ONE_CKB / 100,
+                xudt: None,
+            }
+        );
+        assert_eq!(split.describe()[1], "  - 商户收到: 300.0 (2 个输出)");
+
+        // Paying out more than the funding is rejected
+        assert!(commitment_split(&tx, 900 * ONE_CKB).is_err());
+
+        // xUDT channel: amounts come from the output data
+        let xudt_type = lock(0x44);
+        let tx = Transaction::default()
+            .as_advanced_builder()
+            .output(
+                output(200 * ONE_CKB, 0x01)
+                    .type_(Some(xudt_type.clone()).pack())
+                    .build(),
+            )
+            .output(
+                output(143 * ONE_CKB, 0x02)
+                    .type_(Some(xudt_type).pack())
+                    .build(),
+            )
+            .output_data(Bytes::from(700u128.to_le_bytes().to_vec()).pack())
+            .output_data(Bytes::from(300u128.to_le_bytes().to_vec()).pack())
+            .build();
+        let split = commitment_split(&tx, 343 * ONE_CKB + 1000).unwrap();
+        assert_eq!(split.xudt, Some((700, 300)));
+        assert_eq!(split.fee, 1000);
+    }
+}
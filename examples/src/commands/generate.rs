@@ -0,0 +1,210 @@
+use anyhow::{anyhow, Result};
+use ckb_crypto::secp::Generator;
+use ckb_sdk::{Address, AddressPayload, NetworkType};
+use std::path::Path;
+
+/// A freshly generated secp256k1 keypair with its sighash address
+struct GeneratedKey {
+    private_key: String,
+    address: String,
+}
+
+impl GeneratedKey {
+    fn random(network: NetworkType) -> Self {
+        let secret_key = Generator::random_secret_key();
+        let secp = secp256k1::Secp256k1::new();
+        let pubkey = secp256k1::PublicKey::from_secret_key(&secp, &secret_key);
+        let address = Address::new(network, AddressPayload::from_pubkey(&pubkey), true);
+
+        Self {
+            private_key: hex::encode(secret_key.secret_bytes()),
+            address: address.to_string(),
+        }
+    }
+}
+
+/// Execute generate command - create fresh user/merchant keys and a starter config.toml
+pub async fn execute(network: &str, output: &str, force: bool) -> Result<()> {
+    println!("🔑 执行 Generate 命令");
+    println!("═══════════════════════════════════════════");
+
+    let (network_type, rpc_url) = parse_network(network)?;
+
+    if Path::new(output).exists() && !force {
+        return Err(anyhow!(
+            "Config file {} already exists, use --force to overwrite",
+            output
+        ));
+    }
+
+    let user = GeneratedKey::random(network_type);
+    let merchant = GeneratedKey::random(network_type);
+
+    // Default timeout: 1 day from now (must be at least 20 minutes in the future)
+    let timeout_timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs()
+        + 24 * 60 * 60;
+
+    let config_toml = render_config(rpc_url, &user, &merchant, timeout_timestamp);
+
+    if let Some(parent) = Path::new(output).parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    std::fs::write(output, config_toml)
+        .map_err(|e| anyhow!("Failed to write config file {}: {}", output, e))?;
+
+    println!("✓ 网络: {}", network);
+    println!("✓ 用户地址: {}", user.address);
+    println!("✓ 商户地址: {}", merchant.address);
+    println!("\n✅ 配置文件已生成: {}", output);
+    println!("\n💡 下一步：");
+    println!("  - 填写 [spillman_lock] 和 [auth] 的部署信息（code_hash / tx_hash / index）");
+    println!("  - 如需 xUDT 通道，取消 [usdi] 段落的注释并填写");
+    println!("  - 为用户地址充值 CKB 后即可执行 set-up");
+    println!("\n⚠️  配置文件包含私钥，请妥善保管，不要提交到代码仓库！");
+
+    Ok(())
+}
+
+/// Map `--network` to the address network type and a default public RPC
+fn parse_network(network: &str) -> Result<(NetworkType, &'static str)> {
+    match network {
+        "testnet" => Ok((NetworkType::Testnet, "https://testnet.ckb.dev")),
+        "mainnet" => Ok((NetworkType::Mainnet, "https://mainnet.ckb.dev")),
+        _ => Err(anyhow!(
+            "Unsupported network '{}', expected testnet or mainnet",
+            network
+        )),
+    }
+}
+
+/// Render a commented config.toml skeleton
+fn render_config(
+    rpc_url: &str,
+    user: &GeneratedKey,
+    merchant: &GeneratedKey,
+    timeout_timestamp: u64,
+) -> String {
+    format!(
+        r#"# Spillman Channel Configuration
+# Generated by `spillman-cli generate`, fill in the deployment placeholders below
+
+[network]
+# CKB RPC URL
+rpc_url = "{rpc_url}"
+
+[user]
+# User's private key (without 0x prefix)
+private_key = "{user_private_key}"
+# User's CKB address
+address = "{user_address}"
+
+[merchant]
+# Merchant's private key (without 0x prefix)
+private_key = "{merchant_private_key}"
+# Merchant's CKB address
+address = "{merchant_address}"
+
+[channel]
+# Channel capacity in CKB (e.g., 1000 CKB)
+capacity_ckb = 1000
+
+# Timeout in timestamp (Unix timestamp in seconds)
+# Must be at least 20 minutes (1200 seconds) greater than current time
+timeout_timestamp = {timeout_timestamp}
+
+# Transaction fee in shannon (1 CKB = 100,000,000 shannon)
+tx_fee_shannon = 100000
+
+[spillman_lock]
+# Spillman Lock script code hash (type_id from deployment)
+code_hash = "0x<spillman_lock_code_hash>"
+# Hash type
+hash_type = "type"
+# Cell dep (for transaction)
+tx_hash = "0x<spillman_lock_deploy_tx_hash>"
+index = 0
+
+[auth]
+# Auth cell dep (for transaction signing)
+tx_hash = "0x<auth_deploy_tx_hash>"
+index = 0
+
+# ============ Optional: xUDT (e.g., USDI) Configuration ============
+# Uncomment below to enable xUDT token support
+
+# [usdi]
+# # xUDT type script code hash
+# code_hash = "0x<xudt_code_hash>"
+# # Hash type
+# hash_type = "data1"
+# # xUDT args (token identifier)
+# args = "0x<xudt_args>"
+# # Cell dep for xUDT type script
+# tx_hash = "0x<xudt_deploy_tx_hash>"
+# index = 0
+# # Token decimal places (e.g., 6 for USDI)
+# decimal = 6
+"#,
+        rpc_url = rpc_url,
+        user_private_key = user.private_key,
+        user_address = user.address,
+        merchant_private_key = merchant.private_key,
+        merchant_address = merchant.address,
+        timeout_timestamp = timeout_timestamp,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::config::load_config;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_generated_config_parses_with_load_config() {
+        let (network_type, rpc_url) = parse_network("testnet").unwrap();
+        let user = GeneratedKey::random(network_type);
+        let merchant = GeneratedKey::random(network_type);
+        let config_toml = render_config(rpc_url, &user, &merchant, 1_800_000_000);
+
+        let path = std::env::temp_dir().join(format!(
+            "spillman_generate_test_{}.toml",
+            std::process::id()
+        ));
+        std::fs::write(&path, config_toml).unwrap();
+        let config = load_config(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        assert_eq!(config.network.rpc_url, "https://testnet.ckb.dev");
+        assert_eq!(
+            config.user.private_key.as_deref(),
+            Some(user.private_key.as_str())
+        );
+        assert_eq!(config.merchant.address, merchant.address);
+        assert_eq!(config.channel.timeout_timestamp, 1_800_000_000);
+        assert!(config.usdi.is_none());
+
+        // Generated keys derive the generated addresses
+        let secret_keys = config.user.get_secret_keys().unwrap();
+        let secp = secp256k1::Secp256k1::new();
+        let pubkey = secp256k1::PublicKey::from_secret_key(&secp, &secret_keys[0]);
+        let expected_address =
+            Address::new(network_type, AddressPayload::from_pubkey(&pubkey), true);
+        assert_eq!(config.user.address, expected_address.to_string());
+        assert_eq!(
+            Address::from_str(&config.user.address).unwrap().network(),
+            NetworkType::Testnet
+        );
+    }
+
+    #[test]
+    fn test_parse_network_rejects_unknown() {
+        assert!(parse_network("mainnet").is_ok());
+        assert!(parse_network("devnet").is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::Result;
+use ckb_sdk::HumanCapacity;
+use std::path::Path;
+
+use crate::utils::storage::{commitments_dir, load_commitment_records, CommitmentRecord};
+
+/// Print every commitment recorded for the channel in `channel_dir`
+pub async fn execute(channel_dir: &str) -> Result<()> {
+    println!("\n═══════════════════════════════════════════════════════");
+    println!("  📜 通道支付历史");
+    println!("═══════════════════════════════════════════════════════\n");
+
+    let channel_dir = Path::new(channel_dir);
+    let records = load_commitment_records(channel_dir)?;
+    if records.is_empty() {
+        println!(
+            "⚠️  没有找到支付记录: {}",
+            commitments_dir(channel_dir).display()
+        );
+        return Ok(());
+    }
+
+    let is_xudt = records
+        .iter()
+        .any(|record| record.xudt_paid_amount.is_some());
+    if is_xudt {
+        println!(
+            "{:>4}  {:<19}  {:>24}  {:>24}  {:>24}  最新",
+            "序号", "时间", "累计支付 (xUDT)", "用户余额 (xUDT)", "商户余额 (CKB)"
+        );
+    } else {
+        println!(
+            "{:>4}  {:<19}  {:>20}  {:>20}  {:>20}  最新",
+            "序号", "时间", "累计支付 (CKB)", "用户余额 (CKB)", "商户余额 (CKB)"
+        );
+    }
+
+    let latest = records.last().map(|record| record.sequence);
+    for record in &records {
+        let marker = if Some(record.sequence) == latest {
+            "✓"
+        } else {
+            ""
+        };
+        if is_xudt {
+            println!(
+                "{:>4}  {:<19}  {:>24}  {:>24}  {:>24}  {}",
+                record.sequence,
+                format_timestamp(record),
+                record.xudt_paid_amount.unwrap_or_default(),
+                record.user_xudt_amount.unwrap_or_default(),
+                HumanCapacity::from(record.merchant_capacity).to_string(),
+                marker
+            );
+        } else {
+            println!(
+                "{:>4}  {:<19}  {:>20}  {:>20}  {:>20}  {}",
+                record.sequence,
+                format_timestamp(record),
+                HumanCapacity::from(record.paid_amount).to_string(),
+                HumanCapacity::from(record.user_capacity).to_string(),
+                HumanCapacity::from(record.merchant_capacity).to_string(),
+                marker
+            );
+        }
+    }
+
+    if let Some(record) = records.last() {
+        println!("\n📌 最新 commitment:");
+        println!("  - TX Hash: {}", record.tx_hash);
+        println!("  - 交易文件: {}", record.tx_file);
+    }
+    if is_xudt {
+        println!("\n💡 xUDT 数量为最小单位");
+    }
+
+    Ok(())
+}
+
+fn format_timestamp(record: &CommitmentRecord) -> String {
+    chrono::DateTime::from_timestamp(record.created_at as i64, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| record.created_at.to_string())
+}
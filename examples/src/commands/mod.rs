@@ -1,5 +1,15 @@
+pub mod backup;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod decode;
+pub mod generate;
+pub mod history;
 pub mod pay;
 pub mod refund;
+pub mod rotate;
 pub mod settle;
 pub mod setup;
 pub mod sign;
+pub mod splice;
+pub mod verify;
+pub mod watch;
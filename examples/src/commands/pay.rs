@@ -1,46 +1,40 @@
 use anyhow::{anyhow, Result};
-use ckb_sdk::{rpc::CkbRpcClient, Address, HumanCapacity};
+use ckb_sdk::{Address, HumanCapacity};
 use ckb_types::{
-    core::{Capacity, TransactionView},
-    packed::{CellOutput, Script},
+    core::TransactionView,
+    packed::{OutPoint, Script},
     prelude::*,
     H256,
 };
-use serde::{Deserialize, Serialize};
-use std::{fs, str::FromStr};
-
-use crate::{tx_builder::commitment::build_commitment_transaction, utils::config::load_config};
-
-/// Channel information loaded from file
-#[derive(Debug, Serialize, Deserialize)]
-struct ChannelInfo {
-    user_address: String,
-    merchant_address: String,
-    capacity_ckb: u64,
-    #[allow(dead_code)]
-    timeout_epochs: u64,
-    #[allow(dead_code)]
-    current_timestamp: u64,
-    #[allow(dead_code)]
-    timeout_timestamp: u64,
-    #[allow(dead_code)]
-    spillman_lock_script_hash: String,
-    funding_tx_hash: String,
-    funding_output_index: u32,
-    // xUDT fields (optional, only present in xUDT channels)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[allow(dead_code)]
-    xudt_type_script: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[allow(dead_code)]
-    xudt_amount: Option<String>,
-}
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::channel::{BuilderBackend, Channel, ChannelParams, ChannelState, ChannelStatus};
+use crate::error::ChannelError;
+use crate::tx_builder::capacity_utils::merchant_min_capacity;
+use crate::utils::rpc;
+use crate::{
+    tx_builder::{commitment::build_commitment_transaction, commitment_v2},
+    utils::{
+        channel_info::load_channel_info,
+        config::{load_config, Config},
+        storage::{
+            load_commitment_records, next_sequence, save_commitment_record, CommitmentRecord,
+        },
+    },
+};
 
+#[allow(clippy::too_many_arguments)]
 pub async fn execute(
     amount: &str,
     channel_file: &str,
     config_path: &str,
     fee_rate: u64,
+    xudt: bool,
+    use_v2: bool,
+    close_after: bool,
+    force_near_timeout: bool,
+    save_unsigned: Option<&str>,
 ) -> Result<()> {
     println!("\n═══════════════════════════════════════════════════════");
     println!("  💸 创建 Commitment Transaction (链下支付)");
@@ -63,14 +57,29 @@ pub async fn execute(
 
     // 3. Get Spillman Lock cell info from chain
     println!("\n🔍 从链上查询 Spillman Lock cell...");
-    let rpc_client = CkbRpcClient::new(&config.network.rpc_url);
+    let rpc_client = rpc::client(&config.network.rpc_url);
+
+    // The user can refund once the median time passes the timeout, so a commitment
+    // signed close to it may never be settled
+    let median_time: u64 = rpc_client
+        .get_blockchain_info()
+        .map_err(|e| ChannelError::RpcError(format!("{:?}", e)))?
+        .median_time
+        .value()
+        / 1000;
+    check_timeout_window(
+        median_time,
+        channel_info.timeout_timestamp,
+        config.channel.timeout_warn_seconds,
+        force_near_timeout,
+    )?;
 
     let funding_tx_hash = H256::from_str(channel_info.funding_tx_hash.trim_start_matches("0x"))
         .map_err(|e| anyhow!("Invalid funding tx hash: {}", e))?;
 
     let funding_tx_with_status = rpc_client
         .get_transaction(funding_tx_hash.clone())
-        .map_err(|e| anyhow!("RPC error: {:?}", e))?
+        .map_err(|e| ChannelError::RpcError(format!("{:?}", e)))?
         .ok_or_else(|| anyhow!("Funding transaction not found on chain"))?;
 
     let funding_tx_json = funding_tx_with_status
@@ -127,6 +136,24 @@ pub async fn execute(
             (None, None)
         };
 
+    // Cross-check the channel type recorded at setup with the on-chain cell
+    if channel_info.is_xudt() && channel_info.xudt_type_script()? != xudt_type_script {
+        return Err(anyhow!(
+            "xUDT type script in channel info does not match the Spillman Lock cell on chain"
+        ));
+    }
+    if let (Some(channel_amount), Some(cell_amount)) =
+        (channel_info.xudt_amount()?, xudt_total_amount)
+    {
+        if channel_amount != cell_amount {
+            return Err(anyhow!(
+                "xUDT amount in channel info ({}) does not match the Spillman Lock cell ({})",
+                channel_amount,
+                cell_amount
+            ));
+        }
+    }
+
     println!("✓ Spillman Lock cell 信息:");
     println!(
         "  - Capacity: {}",
@@ -140,30 +167,63 @@ pub async fn execute(
         println!("  - xUDT amount: {}", xudt_amount);
     }
 
-    // 3.5 Parse payment amount based on channel type
-    let (payment_amount_shannons, xudt_payment_amount) = if xudt_type_script.is_some() {
+    // 3.5 Parse addresses
+    let user_address = Address::from_str(&channel_info.user_address)
+        .map_err(|e| anyhow!("Invalid user address: {}", e))?;
+    let merchant_address = Address::from_str(&channel_info.merchant_address)
+        .map_err(|e| anyhow!("Invalid merchant address: {}", e))?;
+
+    let user_lock_script = Script::from(&user_address);
+    let merchant_lock_script = Script::from(&merchant_address);
+
+    // 3.6 Calculate merchant's minimum occupied capacity (including type script for xUDT)
+    let user_min_capacity = merchant_min_capacity(&user_lock_script, None)?;
+    let merchant_min_capacity =
+        merchant_min_capacity(&merchant_lock_script, xudt_type_script.as_ref())?;
+
+    // 4 Parse payment amount based on channel type
+    match (xudt, xudt_type_script.is_some()) {
+        (true, false) => {
+            return Err(anyhow!(
+                "--xudt specified but the channel is not an xUDT channel"
+            ));
+        }
+        (false, true) => {
+            return Err(anyhow!(
+                "xUDT channel detected, use --xudt to pay in token units"
+            ));
+        }
+        _ => {}
+    }
+
+    let pay_amount = PayAmount::parse(amount)?;
+    let (payment_amount_shannons, xudt_payment_amount) = if xudt {
         // xUDT channel: amount is xUDT quantity, need to convert using decimal
         let usdi_config = config
             .usdi
             .as_ref()
             .ok_or_else(|| anyhow!("xUDT channel detected but usdi config not found"))?;
 
-        let payment_amount_f64 = amount
-            .parse::<f64>()
-            .map_err(|e| anyhow!("Invalid xUDT amount '{}': {}", amount, e))?;
-
         let decimal = usdi_config.decimal;
-        let multiplier = 10u128.pow(decimal as u32);
-        let xudt_payment = (payment_amount_f64 * multiplier as f64) as u128;
+
+        // Validate cumulative xUDT payment against the funded amount recorded at setup
+        let xudt_total = match channel_info.xudt_amount()? {
+            Some(funded_amount) => funded_amount,
+            None => xudt_total_amount.ok_or_else(|| anyhow!("xUDT total amount not found"))?,
+        };
+        let xudt_payment = match pay_amount.share_of(xudt_total) {
+            Some(share) => share,
+            None => parse_xudt_amount(amount, decimal)?,
+        };
 
         println!("\n💰 xUDT 支付详情:");
         println!(
             "  - 支付 xUDT 数量: {} (decimal: {}, smallest unit: {})",
-            payment_amount_f64, decimal, xudt_payment
+            amount, decimal, xudt_payment
         );
-
-        // Validate xUDT payment amount
-        let xudt_total = xudt_total_amount.ok_or_else(|| anyhow!("xUDT total amount not found"))?;
+        if xudt_payment == 0 {
+            return Err(anyhow!("xUDT payment amount must be greater than 0"));
+        }
         if xudt_payment > xudt_total {
             return Err(anyhow!(
                 "xUDT 支付金额过大：支付 {}，通道总量 {}",
@@ -175,46 +235,32 @@ pub async fn execute(
         // For xUDT channel, CKB payment is 0 (merchant only gets minimum occupied capacity)
         (0u64, Some(xudt_payment))
     } else {
-        // Regular CKB channel: amount is CKB quantity
-        let payment_capacity = HumanCapacity::from_str(amount)
-            .map_err(|e| anyhow!("Invalid CKB amount '{}': {}", amount, e))?;
-        let payment_shannons: u64 = payment_capacity.into();
+        // Regular CKB channel: amount is CKB quantity, or a share of what the cell can pay
+        let payable = ckb_payable_capacity(
+            spillman_lock_capacity,
+            merchant_min_capacity,
+            user_min_capacity,
+            fee_rate,
+        );
+        let payment_shannons = match pay_amount.share_of(payable as u128) {
+            Some(share) => share as u64,
+            None => HumanCapacity::from_str(amount)
+                .map_err(|e| anyhow!("Invalid CKB amount '{}': {}", amount, e))?
+                .into(),
+        };
 
         println!("\n💰 CKB 支付详情:");
-        println!("  - 支付 CKB 数量: {}", payment_capacity);
+        println!(
+            "  - 支付 CKB 数量: {}",
+            HumanCapacity::from(payment_shannons)
+        );
+        if pay_amount != PayAmount::Absolute {
+            println!("  - 可支付上限: {}", HumanCapacity::from(payable));
+        }
 
         (payment_shannons, None)
     };
 
-    // 4. Parse addresses
-    let user_address = Address::from_str(&channel_info.user_address)
-        .map_err(|e| anyhow!("Invalid user address: {}", e))?;
-    let merchant_address = Address::from_str(&channel_info.merchant_address)
-        .map_err(|e| anyhow!("Invalid merchant address: {}", e))?;
-
-    let user_lock_script = Script::from(&user_address);
-    let merchant_lock_script = Script::from(&merchant_address);
-
-    // 5. Calculate merchant's minimum occupied capacity (including type script for xUDT)
-    let mut merchant_cell_builder = CellOutput::new_builder()
-        .capacity(Capacity::shannons(0))
-        .lock(merchant_lock_script.clone());
-
-    // Add type script if xUDT channel
-    let data_size = if let Some(ref type_script) = xudt_type_script {
-        merchant_cell_builder = merchant_cell_builder.type_(Some(type_script.clone()).pack());
-        16 // 16 bytes for xUDT data
-    } else {
-        0
-    };
-
-    let merchant_cell = merchant_cell_builder.build();
-
-    let merchant_min_capacity = merchant_cell
-        .occupied_capacity(Capacity::bytes(data_size).unwrap())
-        .map_err(|e| anyhow!("Failed to calculate merchant minimum capacity: {:?}", e))?
-        .as_u64();
-
     // Merchant receives: payment amount + minimum occupied capacity
     let merchant_total_capacity = payment_amount_shannons + merchant_min_capacity;
 
@@ -255,26 +301,116 @@ pub async fn execute(
     }
 
     // 7. Build and save commitment transaction
-    // Use cleaned amount string for filename (replace '.' with '_')
-    let amount_str = amount.replace('.', "_");
-    let output_file = generate_tx_filename("commitment", Some(&format!("{}_ckb", amount_str)));
-
-    let (_tx_hash, _tx) = build_commitment_transaction(
-        &config,
-        funding_tx_hash,
-        channel_info.funding_output_index,
-        spillman_lock_capacity,
-        spillman_lock_script,
-        user_lock_script,
-        merchant_lock_script,
-        payment_amount_shannons,
-        merchant_min_capacity,
-        fee_rate,
+    // Use cleaned amount string for filename (replace '.' with '_', '%' with "pct")
+    let amount_str = amount.replace('.', "_").replace('%', "pct");
+    let unit = if xudt { "xudt" } else { "ckb" };
+    let output_file = match save_unsigned {
+        Some(path) => path.to_string(),
+        None => generate_tx_filename("commitment", Some(&format!("{}_{}", amount_str, unit))),
+    };
+
+    // Commitments already signed for this channel, kept next to the channel info for `history`
+    let channel_dir = Path::new(channel_file)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let records = load_commitment_records(channel_dir)?;
+
+    let (_, commitment_tx) = if use_v2 && !xudt && save_unsigned.is_none() {
+        let params = ChannelParams {
+            spillman_lock: spillman_lock_script,
+            user_lock: user_lock_script,
+            merchant_lock: merchant_lock_script,
+            capacity: spillman_lock_capacity,
+            fee_rate,
+        };
+        let state = ChannelState {
+            funding_tx,
+            funding_output_index: channel_info.funding_output_index,
+            status: ChannelStatus::Open,
+            paid_amount: 0,
+            latest_commitment: None,
+        };
+        let state = resume_from_records(state, &records);
+        let tx = pay_through_channel(&config, params, state, payment_amount_shannons).await?;
+        let tx_hash = commitment_v2::save_commitment_transaction(
+            &tx,
+            spillman_lock_capacity,
+            payment_amount_shannons,
+            &output_file,
+            false,
+        )?;
+        (tx_hash, tx)
+    } else if use_v2 {
+        commitment_v2::build_commitment_transaction(
+            &config,
+            funding_tx_hash,
+            &funding_tx,
+            channel_info.funding_output_index,
+            user_lock_script,
+            merchant_lock_script,
+            payment_amount_shannons,
+            xudt_payment_amount,
+            fee_rate,
+            &output_file,
+            save_unsigned.is_some(),
+        )
+        .await?
+    } else {
+        build_commitment_transaction(
+            &config,
+            funding_tx_hash,
+            channel_info.funding_output_index,
+            spillman_lock_capacity,
+            spillman_lock_script,
+            user_lock_script,
+            merchant_lock_script,
+            payment_amount_shannons,
+            merchant_min_capacity,
+            fee_rate,
+            &output_file,
+            xudt_type_script,
+            xudt_total_amount,
+            xudt_payment_amount,
+        )?
+    };
+
+    // Record the commitment next to the channel info for `history`
+    let created_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    let record = CommitmentRecord::from_commitment_tx(
+        next_sequence(&records),
+        created_at,
         &output_file,
-        xudt_type_script,
-        xudt_total_amount,
-        xudt_payment_amount,
+        payment_amount_shannons,
+        &commitment_tx,
     )?;
+    let record_path = save_commitment_record(channel_dir, &record)?;
+    println!(
+        "✓ 支付记录 #{} 已保存: {}",
+        record.sequence,
+        record_path.display()
+    );
+
+    // 8. Optionally also produce the final close transaction
+    // There is no dedicated cooperative-close path in Spillman Lock yet, so the close is
+    // the commitment itself: the merchant countersigns and broadcasts it via `settle`.
+    let close_file = if close_after {
+        let close_file = save_close_transaction(&output_file)?;
+        println!("\n🔒 关闭通道交易已生成: {}", close_file);
+        println!("  - 与本次 commitment 相同，等待商户补充签名后广播即可关闭通道");
+        Some(close_file)
+    } else {
+        None
+    };
+
+    if save_unsigned.is_some() {
+        println!("\n✅ 未签名 Commitment Transaction 已保存: {}", output_file);
+        println!("  - witness 为占位符（大小与最终签名一致，手续费已按此计算）");
+        println!("  - 请用外部工具填入用户签名后交给商户结算");
+        return Ok(());
+    }
 
     // Success message and next steps
     println!("\n✅ Commitment Transaction 创建成功!");
@@ -285,28 +421,211 @@ pub async fn execute(
     println!("  - 商户可以随时广播此交易到链上结算");
     println!("\n🎯 商户结算命令：");
     println!(
-        "  spillman-cli settle --tx-file {} --config {}",
-        output_file, config_path
+        "  spillman-cli settle --tx-file {} --channel-file {} --config {}",
+        output_file, channel_file, config_path
     );
+    if let Some(close_file) = close_file {
+        println!("\n🔒 关闭通道（最终支付）：");
+        println!(
+            "  spillman-cli settle --tx-file {} --channel-file {} --config {} --broadcast",
+            close_file, channel_file, config_path
+        );
+        return Ok(());
+    }
     println!("\n💸 继续支付（创建新的 commitment）：");
     println!(
-        "  spillman-cli pay --amount <更大的金额> --channel-file {} --config {}",
-        channel_file, config_path
+        "  spillman-cli pay --amount <更大的金额> --channel-file {} --config {}{}",
+        channel_file,
+        config_path,
+        if xudt { " --xudt" } else { "" }
     );
     println!("\n⚠️  注意：每次支付的金额必须大于上一次！");
 
     Ok(())
 }
 
-/// Load channel information from JSON file
-fn load_channel_info(file_path: &str) -> Result<ChannelInfo> {
-    let json = fs::read_to_string(file_path)
-        .map_err(|e| anyhow!("Failed to read channel info file {}: {}", file_path, e))?;
+/// Sign the commitment paying `paid_amount` in total through [`Channel`]
+///
+/// The channel resumes from its funding transaction and the amount of the latest
+/// recorded commitment, so a payment has to raise the cumulative amount.
+async fn pay_through_channel(
+    config: &Config,
+    params: ChannelParams,
+    state: ChannelState,
+    paid_amount: u64,
+) -> Result<TransactionView> {
+    let previous_amount = state.paid_amount;
+    if paid_amount <= previous_amount {
+        return Err(anyhow!(
+            "支付金额必须大于上一次：本次 {}，上一次 {}",
+            HumanCapacity::from(paid_amount),
+            HumanCapacity::from(previous_amount)
+        ));
+    }
 
-    let info: ChannelInfo =
-        serde_json::from_str(&json).map_err(|e| anyhow!("Failed to parse channel info: {}", e))?;
+    let backend = BuilderBackend::from_config(config, &params.spillman_lock)?;
+    let mut channel = Channel::resume(backend, params, state);
+    println!("📝 构建 Commitment 交易 (v2)...");
+    println!("🔐 签名 Commitment 交易 (User)...");
+    channel
+        .pay(paid_amount - previous_amount)
+        .await?
+        .into_inner()
+        .ok_or_else(|| anyhow!("No transaction"))
+}
 
-    Ok(info)
+/// Advance a freshly funded channel state to the latest recorded commitment spending it
+///
+/// Records whose commitment file is gone or spends another funding cell (an earlier
+/// channel in the same directory) are skipped.
+fn resume_from_records(mut state: ChannelState, records: &[CommitmentRecord]) -> ChannelState {
+    let funding_out_point = OutPoint::new(state.funding_tx.hash(), state.funding_output_index);
+    let latest = records.iter().rev().find_map(|record| {
+        let json = std::fs::read_to_string(&record.tx_file).ok()?;
+        let tx: ckb_jsonrpc_types::TransactionView = serde_json::from_str(&json).ok()?;
+        let tx = ckb_types::packed::Transaction::from(tx.inner).into_view();
+        let spends_channel = tx
+            .inputs()
+            .get(0)
+            .is_some_and(|input| input.previous_output() == funding_out_point);
+        spends_channel.then_some((record.paid_amount, tx))
+    });
+    if let Some((paid_amount, tx)) = latest {
+        state.paid_amount = paid_amount;
+        state.latest_commitment = Some(tx);
+    }
+    state
+}
+
+/// Size allowance for a signed commitment when reserving its fee out of `--amount all`
+const COMMITMENT_SIZE_ALLOWANCE: u64 = 2048;
+
+/// Form of the `pay --amount` argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayAmount {
+    /// `all`: everything the channel can pay
+    All,
+    /// `50%` or `12.5%`: a share of what the channel can pay, in basis points
+    Percent(u32),
+    /// A plain CKB or token figure, parsed according to the channel type
+    Absolute,
+}
+
+impl PayAmount {
+    /// Recognise `all` and percentages, rejecting shares over 100%
+    pub fn parse(amount: &str) -> Result<Self> {
+        if amount.eq_ignore_ascii_case("all") {
+            return Ok(Self::All);
+        }
+        let Some(percent) = amount.strip_suffix('%') else {
+            return Ok(Self::Absolute);
+        };
+        let basis_points = parse_xudt_amount(percent.trim(), 2)
+            .map_err(|_| anyhow!("Invalid percentage '{}'", amount))?;
+        if basis_points > 10_000 {
+            return Err(anyhow!("Percentage '{}' exceeds 100%", amount));
+        }
+        Ok(Self::Percent(basis_points as u32))
+    }
+
+    /// Resolve `all` and percentages against `payable`; `None` for an absolute amount
+    pub fn share_of(self, payable: u128) -> Option<u128> {
+        match self {
+            Self::All => Some(payable),
+            Self::Percent(basis_points) => Some(payable * basis_points as u128 / 10_000),
+            Self::Absolute => None,
+        }
+    }
+}
+
+/// CKB a commitment can pay out of the Spillman Lock cell
+///
+/// The merchant output's occupied capacity, the user change cell's occupied capacity and
+/// the transaction fee all come out of the cell before anything can be paid.
+fn ckb_payable_capacity(
+    spillman_lock_capacity: u64,
+    merchant_min_capacity: u64,
+    user_min_capacity: u64,
+    fee_rate: u64,
+) -> u64 {
+    let fee_reserve = fee_rate * COMMITMENT_SIZE_ALLOWANCE / 1000;
+    spillman_lock_capacity
+        .saturating_sub(merchant_min_capacity)
+        .saturating_sub(user_min_capacity)
+        .saturating_sub(fee_reserve)
+}
+
+/// Parse a decimal token amount (e.g. "5" or "5.25") into the smallest xUDT unit
+///
+/// Parsed exactly from the string, rejecting more fractional digits than `decimal` allows.
+pub fn parse_xudt_amount(amount: &str, decimal: u8) -> Result<u128> {
+    let invalid = || anyhow!("Invalid xUDT amount '{}'", amount);
+
+    let (integer_part, fraction_part) = match amount.split_once('.') {
+        Some((integer, fraction)) => (integer, fraction),
+        None => (amount, ""),
+    };
+    if integer_part.is_empty() && fraction_part.is_empty() {
+        return Err(invalid());
+    }
+    if !integer_part.chars().all(|c| c.is_ascii_digit())
+        || !fraction_part.chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+    if fraction_part.len() > decimal as usize {
+        return Err(anyhow!(
+            "xUDT amount '{}' has more than {} decimal places",
+            amount,
+            decimal
+        ));
+    }
+
+    let multiplier = 10u128
+        .checked_pow(decimal as u32)
+        .ok_or_else(|| anyhow!("Unsupported xUDT decimal: {}", decimal))?;
+    let integer: u128 = if integer_part.is_empty() {
+        0
+    } else {
+        integer_part.parse().map_err(|_| invalid())?
+    };
+    let fraction: u128 = if fraction_part.is_empty() {
+        0
+    } else {
+        let padded = format!("{:0<width$}", fraction_part, width = decimal as usize);
+        padded.parse().map_err(|_| invalid())?
+    };
+
+    integer
+        .checked_mul(multiplier)
+        .and_then(|value| value.checked_add(fraction))
+        .ok_or_else(|| anyhow!("xUDT amount '{}' overflows u128", amount))
+}
+
+/// Save the final close transaction next to a commitment file
+///
+/// `secrets/commitment_<suffix>.json` becomes `secrets/close_<suffix>.json`. Returns the
+/// path of the close transaction.
+pub fn save_close_transaction(commitment_file: &str) -> Result<String> {
+    let commitment_path = std::path::Path::new(commitment_file);
+    let file_name = commitment_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("Invalid commitment file path: {}", commitment_file))?;
+    let close_name = match file_name.strip_prefix("commitment") {
+        Some(rest) => format!("close{}", rest),
+        None => format!("close_{}", file_name),
+    };
+    let close_path = commitment_path.with_file_name(close_name);
+
+    std::fs::copy(commitment_path, &close_path).map_err(|e| {
+        anyhow!(
+            "Failed to save close transaction {}: {}",
+            close_path.display(),
+            e
+        )
+    })?;
+    Ok(close_path.to_string_lossy().into_owned())
 }
 
 pub fn generate_tx_filename(tx_type: &str, suffix: Option<&str>) -> String {
@@ -321,3 +640,204 @@ pub fn generate_tx_filename(tx_type: &str, suffix: Option<&str>) -> String {
         format!("secrets/{}_{}.json", tx_type, timestamp)
     }
 }
+
+/// Refuse to pay within `warn_window` seconds of the channel timeout unless `force` is set
+///
+/// `now` is the node's median time, the clock the timeout is checked against.
+fn check_timeout_window(
+    now: u64,
+    timeout_timestamp: u64,
+    warn_window: u64,
+    force: bool,
+) -> Result<()> {
+    let remaining = timeout_timestamp.saturating_sub(now);
+    if remaining > warn_window {
+        return Ok(());
+    }
+
+    println!("\n⚠️ ═══════════════════════════════════════════════════");
+    if remaining == 0 {
+        println!("⚠️  通道已超时！用户随时可以广播退款，商户可能来不及结算");
+    } else {
+        println!(
+            "⚠️  距离通道超时仅剩 {} 秒（约 {} 分钟）！",
+            remaining,
+            remaining / 60
+        );
+        println!("⚠️  超时后用户可以退款，商户可能来不及结算这笔支付");
+    }
+    println!("⚠️ ═══════════════════════════════════════════════════\n");
+
+    if !force {
+        return Err(anyhow!(
+            "Channel times out at {} (node time {}), within the {}s warning window; \
+             pass --force-near-timeout to pay anyway",
+            timeout_timestamp,
+            now,
+            warn_window
+        ));
+    }
+    println!("⚠️  已指定 --force-near-timeout，继续支付");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_xudt_amount() {
+        assert_eq!(parse_xudt_amount("5", 6).unwrap(), 5_000_000);
+        assert_eq!(parse_xudt_amount("5.25", 6).unwrap(), 5_250_000);
+        assert_eq!(parse_xudt_amount("0.000001", 6).unwrap(), 1);
+        assert_eq!(parse_xudt_amount(".5", 6).unwrap(), 500_000);
+        assert_eq!(parse_xudt_amount("42", 0).unwrap(), 42);
+    }
+
+    #[test]
+    fn test_pay_amount_forms() {
+        assert_eq!(PayAmount::parse("all").unwrap(), PayAmount::All);
+        assert_eq!(PayAmount::parse("ALL").unwrap(), PayAmount::All);
+        assert_eq!(PayAmount::parse("50%").unwrap(), PayAmount::Percent(5_000));
+        assert_eq!(
+            PayAmount::parse("12.5%").unwrap(),
+            PayAmount::Percent(1_250)
+        );
+        assert_eq!(
+            PayAmount::parse("100%").unwrap(),
+            PayAmount::Percent(10_000)
+        );
+        assert_eq!(PayAmount::parse("100").unwrap(), PayAmount::Absolute);
+        assert_eq!(PayAmount::parse("1.5").unwrap(), PayAmount::Absolute);
+
+        assert_eq!(PayAmount::All.share_of(900), Some(900));
+        assert_eq!(PayAmount::Percent(5_000).share_of(900), Some(450));
+        assert_eq!(PayAmount::Percent(1_250).share_of(1_000_000), Some(125_000));
+        assert_eq!(PayAmount::Absolute.share_of(900), None);
+    }
+
+    #[test]
+    fn test_pay_amount_rejects_invalid_percentage() {
+        assert!(PayAmount::parse("100.01%").is_err());
+        assert!(PayAmount::parse("150%").is_err());
+        assert!(PayAmount::parse("%").is_err());
+        assert!(PayAmount::parse("-5%").is_err());
+        assert!(PayAmount::parse("0.001%").is_err());
+    }
+
+    #[test]
+    fn test_ckb_payable_capacity_keeps_cell_floors() {
+        let ckb = 100_000_000;
+        // 1000 CKB cell, 61 CKB for each output, 2048 bytes at 1000 shannons/KB
+        let payable = ckb_payable_capacity(1000 * ckb, 61 * ckb, 61 * ckb, 1000);
+        assert_eq!(payable, 878 * ckb - 2048);
+        assert_eq!(ckb_payable_capacity(100 * ckb, 61 * ckb, 61 * ckb, 1000), 0);
+    }
+
+    #[test]
+    fn test_close_after_saves_commitment_and_close_files() {
+        let dir = std::env::temp_dir().join(format!("spillman_pay_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let commitment_file = dir.join("commitment_100_ckb_1700000000.json");
+        std::fs::write(&commitment_file, "{}").unwrap();
+
+        let close_file = save_close_transaction(commitment_file.to_str().unwrap()).unwrap();
+
+        assert_eq!(
+            close_file,
+            dir.join("close_100_ckb_1700000000.json").to_str().unwrap()
+        );
+        assert!(commitment_file.exists());
+        assert_eq!(std::fs::read_to_string(&close_file).unwrap(), "{}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_resume_from_records_skips_other_channels() {
+        use ckb_types::{bytes::Bytes, core::Capacity, packed::CellInput, packed::CellOutput};
+
+        let dir =
+            std::env::temp_dir().join(format!("spillman_pay_resume_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let funding_tx = TransactionView::new_advanced_builder()
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(1000 * 100_000_000))
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build();
+        let commitment = |out_point: OutPoint, name: &str| {
+            let tx = TransactionView::new_advanced_builder()
+                .input(CellInput::new(out_point, 0))
+                .build();
+            let path = dir.join(name);
+            let json = ckb_jsonrpc_types::TransactionView::from(tx.clone());
+            std::fs::write(&path, serde_json::to_string(&json).unwrap()).unwrap();
+            (tx, path.to_str().unwrap().to_string())
+        };
+        let record = |sequence: u64, tx_file: String, paid_amount: u64| CommitmentRecord {
+            sequence,
+            created_at: 0,
+            tx_hash: String::new(),
+            tx_file,
+            paid_amount,
+            user_capacity: 0,
+            merchant_capacity: 0,
+            xudt_paid_amount: None,
+            user_xudt_amount: None,
+        };
+        let (ours, ours_file) = commitment(OutPoint::new(funding_tx.hash(), 0), "ours.json");
+        let (_, other_file) = commitment(OutPoint::new(H256([0x22; 32]).pack(), 0), "other.json");
+        let records = vec![
+            record(1, ours_file, 100),
+            record(2, other_file, 900),
+            record(3, dir.join("gone.json").to_str().unwrap().to_string(), 950),
+        ];
+        let state = ChannelState {
+            funding_tx,
+            funding_output_index: 0,
+            status: ChannelStatus::Open,
+            paid_amount: 0,
+            latest_commitment: None,
+        };
+
+        let resumed = resume_from_records(state.clone(), &records);
+        let fresh = resume_from_records(state, &[]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(resumed.paid_amount, 100);
+        assert_eq!(resumed.latest_commitment.unwrap().hash(), ours.hash());
+        assert_eq!(fresh.paid_amount, 0);
+        assert!(fresh.latest_commitment.is_none());
+    }
+
+    #[test]
+    fn test_parse_xudt_amount_rejects_invalid() {
+        assert!(parse_xudt_amount("0.0000001", 6).is_err()); // Too many decimals
+        assert!(parse_xudt_amount("1.5", 0).is_err());
+        assert!(parse_xudt_amount("", 6).is_err());
+        assert!(parse_xudt_amount(".", 6).is_err());
+        assert!(parse_xudt_amount("-1", 6).is_err());
+        assert!(parse_xudt_amount("1e3", 6).is_err());
+        assert!(parse_xudt_amount(&u128::MAX.to_string(), 6).is_err()); // Overflow
+    }
+
+    #[test]
+    fn test_check_timeout_window() {
+        const TIMEOUT: u64 = 1_700_000_000;
+
+        // Outside the window
+        assert!(check_timeout_window(TIMEOUT - 3601, TIMEOUT, 3600, false).is_ok());
+
+        // Inside the window, or already timed out: refused unless forced
+        assert!(check_timeout_window(TIMEOUT - 3600, TIMEOUT, 3600, false).is_err());
+        assert!(check_timeout_window(TIMEOUT + 10, TIMEOUT, 3600, false).is_err());
+        assert!(check_timeout_window(TIMEOUT - 60, TIMEOUT, 3600, true).is_ok());
+
+        // A zero window only blocks payments after the timeout
+        assert!(check_timeout_window(TIMEOUT - 1, TIMEOUT, 0, false).is_ok());
+        assert!(check_timeout_window(TIMEOUT, TIMEOUT, 0, false).is_err());
+    }
+}
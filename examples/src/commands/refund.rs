@@ -1,13 +1,34 @@
 use anyhow::{anyhow, Result};
 use ckb_sdk::Address;
-use ckb_types::{core::TransactionView, prelude::*, H256};
+use ckb_types::{
+    core::TransactionView,
+    packed::{OutPoint, Script},
+    prelude::*,
+    H256,
+};
 use std::str::FromStr;
 
 use crate::{
-    tx_builder::refund::build_refund_transaction, tx_builder::refund_v2, utils::config::load_config,
+    tx_builder::refund::build_refund_transaction,
+    tx_builder::refund_v2,
+    utils::{
+        chain::{funding_cell_status, FundingCellStatus},
+        channel_info::{load_channel_info, ChannelInfo},
+        ckb_cli::ckb_cli_tx_file,
+        config::{load_config, Config},
+        crypto::TIMEOUT_GRACE_SECONDS,
+        rpc,
+    },
 };
 
-pub async fn execute(tx_file: &str, config_path: &str, fee_rate: u64) -> Result<()> {
+pub async fn execute(
+    tx_file: &str,
+    config_path: &str,
+    fee_rate: u64,
+    channel_file: Option<&str>,
+    merchant_out: Option<&str>,
+    raw: bool,
+) -> Result<()> {
     println!("🔄 执行 Refund 命令");
     println!("═══════════════════════════════════════════");
 
@@ -33,6 +54,13 @@ pub async fn execute(tx_file: &str, config_path: &str, fee_rate: u64) -> Result<
     println!("  - Inputs: {}", funding_tx.inputs().len());
     println!("  - Outputs: {}", funding_tx.outputs().len());
 
+    if let Some(channel_file) = channel_file {
+        let channel_info = load_channel_info(channel_file)?;
+        verify_channel_info(&channel_info, &config, funding_tx_hash.clone(), &funding_tx)?;
+        println!("✓ 通道信息校验通过: {}", channel_file);
+    }
+    ensure_funding_cell_live(&config, &funding_tx_hash, 0)?;
+
     // Analyze funding transaction to determine mode
     println!("\n📊 分析 Funding 交易模式...");
 
@@ -55,10 +83,10 @@ pub async fn execute(tx_file: &str, config_path: &str, fee_rate: u64) -> Result<
     let user_lock = ckb_types::packed::Script::from(&user_address);
 
     let merchant_lock = if is_cofund {
-        let merchant_address = Address::from_str(&config.merchant.address)
-            .map_err(|e| anyhow!("Failed to parse merchant address: {}", e))?;
+        let merchant_address = merchant_refund_address(&config.merchant.address, merchant_out)?;
         Some(ckb_types::packed::Script::from(&merchant_address))
     } else {
+        reject_merchant_out(merchant_out)?;
         None
     };
 
@@ -73,7 +101,7 @@ pub async fn execute(tx_file: &str, config_path: &str, fee_rate: u64) -> Result<
         .as_secs();
     let output_path = format!("secrets/refund_tx_{}.json", timestamp);
 
-    let _refund_tx = build_refund_transaction(
+    let refund_tx = build_refund_transaction(
         &config,
         funding_tx_hash,
         &funding_tx,
@@ -84,6 +112,11 @@ pub async fn execute(tx_file: &str, config_path: &str, fee_rate: u64) -> Result<
         &output_path,
     )?;
 
+    if raw {
+        print_refund_request(refund_tx);
+        return Ok(());
+    }
+
     println!("\n✅ Refund 交易构建成功！");
     println!("═══════════════════════════════════════════");
     println!("📄 交易已保存: {}", output_path);
@@ -93,12 +126,12 @@ pub async fn execute(tx_file: &str, config_path: &str, fee_rate: u64) -> Result<
     println!("    1. Merchant 在通道创建时预签名（保证用户退款权利）");
     println!("    2. User 在超时后补充签名");
     println!(
-        "  - 等待超时时间戳 ({}) 后可以广播此交易",
-        timeout_timestamp
+        "  - 等待超时时间戳 ({}) 及商户宽限期 ({} 秒) 后可以广播此交易",
+        timeout_timestamp, TIMEOUT_GRACE_SECONDS
     );
     println!(
         "  - 使用 ckb-cli 广播: ckb-cli tx send --tx-file {}",
-        output_path
+        ckb_cli_tx_file(&output_path)?.display()
     );
     if is_cofund {
         println!("\n📊 Co-fund 模式退款：");
@@ -116,7 +149,18 @@ pub async fn execute(tx_file: &str, config_path: &str, fee_rate: u64) -> Result<
 ///
 /// This is the v2 implementation using the refactored refund_v2 module.
 /// The original execute() function above is kept as v1 backup.
-pub async fn execute_v2(tx_file: &str, config_path: &str, fee_rate: u64) -> Result<()> {
+/// `bump_fee_rate` replaces a refund already broadcast at `fee_rate` with one paying more.
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_v2(
+    tx_file: &str,
+    config_path: &str,
+    fee_rate: u64,
+    bump_fee_rate: Option<u64>,
+    channel_file: Option<&str>,
+    save_unsigned: Option<&str>,
+    merchant_out: Option<&str>,
+    raw: bool,
+) -> Result<()> {
     println!("🔄 执行 Refund 命令 (v2)");
     println!("═══════════════════════════════════════════");
 
@@ -142,11 +186,23 @@ pub async fn execute_v2(tx_file: &str, config_path: &str, fee_rate: u64) -> Resu
     println!("  - Inputs: {}", funding_tx.inputs().len());
     println!("  - Outputs: {}", funding_tx.outputs().len());
 
+    // Spillman Lock cell index and funding mode: from channel info if given
+    let (funding_output_index, recorded_co_funded) = if let Some(channel_file) = channel_file {
+        let channel_info = load_channel_info(channel_file)?;
+        verify_channel_info(&channel_info, &config, funding_tx_hash.clone(), &funding_tx)?;
+        println!("✓ 通道信息校验通过: {}", channel_file);
+        (channel_info.funding_output_index, channel_info.co_funded)
+    } else {
+        (0, None)
+    };
+    println!("  - Spillman Lock output index: {}", funding_output_index);
+    ensure_funding_cell_live(&config, &funding_tx_hash, funding_output_index)?;
+
     // Analyze funding transaction to determine mode
     println!("\n📊 分析 Funding 交易模式...");
 
-    // Check if co-fund mode by checking if inputs > 1
-    let is_cofund = funding_tx.inputs().len() > 1;
+    // Co-fund mode as recorded at setup, otherwise guessed from the input count
+    let is_cofund = recorded_co_funded.unwrap_or(funding_tx.inputs().len() > 1);
 
     println!(
         "  - 模式: {}",
@@ -162,10 +218,12 @@ pub async fn execute_v2(tx_file: &str, config_path: &str, fee_rate: u64) -> Resu
         .map_err(|e| anyhow!("Failed to parse user address: {}", e))?;
 
     let merchant_address = if is_cofund {
-        let merchant_addr = Address::from_str(&config.merchant.address)
-            .map_err(|e| anyhow!("Failed to parse merchant address: {}", e))?;
-        Some(merchant_addr)
+        Some(merchant_refund_address(
+            &config.merchant.address,
+            merchant_out,
+        )?)
     } else {
+        reject_merchant_out(merchant_out)?;
         None
     };
 
@@ -175,19 +233,38 @@ pub async fn execute_v2(tx_file: &str, config_path: &str, fee_rate: u64) -> Resu
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap()
         .as_secs();
-    let output_path = format!("secrets/refund_tx_{}.json", timestamp);
+    let output_path = match save_unsigned {
+        Some(path) => path.to_string(),
+        None => format!("secrets/refund_tx_{}.json", timestamp),
+    };
 
-    let (_tx_hash, _tx) = refund_v2::build_refund_transaction(
+    let (_tx_hash, refund_tx) = refund_v2::build_refund_transaction(
         &config,
         funding_tx_hash,
         &funding_tx,
+        funding_output_index,
         &user_address,
         merchant_address.as_ref(),
+        is_cofund,
         fee_rate,
+        bump_fee_rate,
         &output_path,
+        save_unsigned.is_some(),
     )
     .await?;
 
+    if save_unsigned.is_some() {
+        println!("\n✅ 未签名 Refund 交易已保存: {}", output_path);
+        println!("  - witness 为占位符（大小与最终签名一致，手续费已按此计算）");
+        println!("  - 请用外部工具填入商户和用户签名，超时后广播");
+        return Ok(());
+    }
+
+    if raw {
+        print_refund_request(refund_tx);
+        return Ok(());
+    }
+
     println!("\n✅ Refund 交易构建成功！(v2)");
     println!("═══════════════════════════════════════════");
     println!("📄 交易已保存: {}", output_path);
@@ -199,7 +276,7 @@ pub async fn execute_v2(tx_file: &str, config_path: &str, fee_rate: u64) -> Resu
     println!("  - 等待超时后可以签名并广播此交易");
     println!(
         "  - 使用 ckb-cli 广播: ckb-cli tx send --tx-file {}",
-        output_path
+        ckb_cli_tx_file(&output_path)?.display()
     );
     if is_cofund {
         println!("\n📊 Co-fund 模式退款：");
@@ -212,3 +289,172 @@ pub async fn execute_v2(tx_file: &str, config_path: &str, fee_rate: u64) -> Resu
 
     Ok(())
 }
+
+/// Abort when the funding cell is already spent instead of building a refund the node would
+/// reject as a double spend
+fn ensure_funding_cell_live(config: &Config, funding_tx_hash: &H256, index: u32) -> Result<()> {
+    println!("\n🔍 检查 Funding cell 状态...");
+    let out_point = OutPoint::new(funding_tx_hash.pack(), index);
+    let client = rpc::client(&config.network.rpc_url);
+    match funding_cell_status(&client, &out_point) {
+        FundingCellStatus::Live => println!("✓ Funding cell 未被花费"),
+        FundingCellStatus::Spent => {
+            return Err(anyhow!(
+                "Channel already settled by merchant: funding cell {:#x}:{} is spent",
+                funding_tx_hash,
+                index
+            ));
+        }
+        FundingCellStatus::Unknown => {
+            println!("⚠️  无法确认 Funding cell 状态（交易未上链或节点不可用），继续构建退款")
+        }
+    }
+    Ok(())
+}
+
+/// `--raw`: print the refund's `send_transaction` request for broadcasting after the timeout
+fn print_refund_request(refund_tx: TransactionView) {
+    println!(
+        "\n⏳ 需等待超时及商户宽限期 ({} 秒) 后发送，否则节点会拒绝",
+        TIMEOUT_GRACE_SECONDS
+    );
+    rpc::print_send_transaction_request(&ckb_jsonrpc_types::TransactionView::from(refund_tx).inner);
+}
+
+/// Length of the merchant lock arg in Spillman Lock args
+const MERCHANT_LOCK_ARG_LEN: usize = 20;
+
+/// Merchant destination of a co-fund refund: `merchant_out` if given, else the configured
+/// merchant address
+///
+/// The contract accepts a lock other than the one in args only on a refund the merchant
+/// signs, and only if its args are no longer than the 20-byte merchant lock arg, so the
+/// merchant's occupied capacity cannot grow at the user's expense. The configured address
+/// is the lock the args were derived from and is always accepted, so only an override is
+/// checked.
+fn merchant_refund_address(merchant_address: &str, merchant_out: Option<&str>) -> Result<Address> {
+    let Some(merchant_out) = merchant_out else {
+        return Address::from_str(merchant_address)
+            .map_err(|e| anyhow!("Failed to parse merchant address: {}", e));
+    };
+    let address = Address::from_str(merchant_out)
+        .map_err(|e| anyhow!("Failed to parse --merchant-out address: {}", e))?;
+    let args_len = Script::from(&address).args().raw_data().len();
+    if args_len > MERCHANT_LOCK_ARG_LEN {
+        return Err(anyhow!(
+            "Merchant refund lock args are {} bytes, the contract accepts at most {}",
+            args_len,
+            MERCHANT_LOCK_ARG_LEN
+        ));
+    }
+    println!("  - 商户退款地址 (--merchant-out): {}", merchant_out);
+    Ok(address)
+}
+
+/// A single-fund refund has no merchant output to redirect
+fn reject_merchant_out(merchant_out: Option<&str>) -> Result<()> {
+    if merchant_out.is_some() {
+        return Err(anyhow!(
+            "--merchant-out only applies to a co-funded channel's refund"
+        ));
+    }
+    Ok(())
+}
+
+/// Verify that the funding transaction matches the channel info recorded at setup
+///
+/// The Spillman Lock script is rebuilt from `config`, so a config that drifted since
+/// setup is caught before a refund is built against the wrong keys or contract.
+fn verify_channel_info(
+    channel_info: &ChannelInfo,
+    config: &Config,
+    funding_tx_hash: H256,
+    funding_tx: &TransactionView,
+) -> Result<()> {
+    let expected_tx_hash = H256::from_str(channel_info.funding_tx_hash.trim_start_matches("0x"))
+        .map_err(|e| anyhow!("Invalid funding tx hash in channel info: {}", e))?;
+    if expected_tx_hash != funding_tx_hash {
+        return Err(anyhow!(
+            "Funding tx hash mismatch: channel info has {:#x}, tx file has {:#x}",
+            expected_tx_hash,
+            funding_tx_hash
+        ));
+    }
+
+    let index = channel_info.funding_output_index as usize;
+    let spillman_cell = funding_tx
+        .outputs()
+        .get(index)
+        .ok_or_else(|| anyhow!("Spillman Lock cell not found at output index {}", index))?;
+
+    if spillman_cell.lock() != channel_info.rebuild_spillman_script(config)? {
+        return Err(anyhow!(
+            "Output {} of the funding tx is not locked by the channel's Spillman Lock script",
+            index
+        ));
+    }
+
+    let cell_type_script = spillman_cell.type_().to_opt();
+    if channel_info.xudt_type_script()? != cell_type_script {
+        return Err(anyhow!(
+            "xUDT type script in channel info does not match the Spillman Lock cell"
+        ));
+    }
+
+    if let Some(channel_amount) = channel_info.xudt_amount()? {
+        let data: Vec<u8> = funding_tx
+            .outputs_data()
+            .get(index)
+            .ok_or_else(|| anyhow!("Spillman Lock cell data not found"))?
+            .unpack();
+        if data.len() < 16 {
+            return Err(anyhow!("Invalid xUDT data length: {}", data.len()));
+        }
+        let cell_amount = u128::from_le_bytes(data[0..16].try_into().unwrap());
+        if channel_amount != cell_amount {
+            return Err(anyhow!(
+                "xUDT amount in channel info ({}) does not match the Spillman Lock cell ({})",
+                channel_amount,
+                cell_amount
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_sdk::{constants::MultisigScript, AddressPayload, NetworkType};
+    use ckb_types::bytes::Bytes;
+
+    #[test]
+    fn test_merchant_refund_address_checks_only_the_override() {
+        // A multisig lock with a since: 20-byte hash + 8-byte since
+        let multisig = MultisigScript::V2.script_id();
+        let long_args_lock = Script::new_builder()
+            .code_hash(multisig.code_hash.pack())
+            .hash_type(multisig.hash_type)
+            .args(Bytes::from(vec![0x11; 28]).pack())
+            .build();
+        let long_args_address = Address::new(
+            NetworkType::Testnet,
+            AddressPayload::from(long_args_lock),
+            true,
+        )
+        .to_string();
+
+        // The configured merchant address is accepted whatever its args length
+        let address = merchant_refund_address(&long_args_address, None).unwrap();
+        assert_eq!(address.to_string(), long_args_address);
+
+        // The same lock as an override would grow the merchant's occupied capacity
+        let err =
+            merchant_refund_address(&long_args_address, Some(&long_args_address)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Merchant refund lock args are 28 bytes, the contract accepts at most 20"
+        );
+    }
+}
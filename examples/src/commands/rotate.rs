@@ -0,0 +1,544 @@
+use anyhow::{anyhow, Result};
+use ckb_sdk::{
+    constants::ONE_CKB, traits::DefaultTransactionDependencyProvider,
+    transaction::builder::FeeCalculator, tx_builder::unlock_tx, unlock::MultisigConfig, Address,
+    HumanCapacity, Since, SinceType,
+};
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, TransactionView},
+    packed::{CellDep, CellInput, CellOutput, OutPoint, Script},
+    prelude::*,
+    H256,
+};
+use serde_json::json;
+use std::{fs, path::Path, str::FromStr};
+
+use crate::utils::ckb_cli::ckb_cli_tx_file;
+use crate::utils::rpc;
+use crate::{
+    commands::settle::{collect_merchant_fee_cells, SettlementFee},
+    commands::splice::{merchant_multisig_config, sign_splice_transaction},
+    signer::RawKeySigner,
+    tx_builder::commitment_v2::code_cell_dep,
+    tx_builder::funding_v2::build_unlockers,
+    tx_builder::witness_utils::calculate_commitment_witness_size,
+    tx_builder::xudt::{write_xudt_amount, XUDT_AMOUNT_LEN},
+    utils::{
+        channel_info::{load_channel_info, write_channel_info, ChannelInfo, InfoFormat},
+        config::load_config,
+        crypto::{SpillmanArgs, TIMEOUT_GRACE_SECONDS},
+        log::log_event,
+    },
+};
+
+/// Execute rotate command - move the whole channel into a Spillman cell with a later timeout
+///
+/// Spends the funding cell of `channel_file` through the splice path: output 0 is the
+/// same cell (capacity, type and data) under args with `new_timeout_timestamp`. The splice
+/// path needs a merchant output, so the merchant's own cells pass through output 1 and pay
+/// the fee. Both parties sign with the keys in the config; the rotated channel gets its own
+/// directory with the rotation transaction and a new channel info.
+pub async fn execute(
+    channel_file: &str,
+    config_path: &str,
+    new_timeout_timestamp: u64,
+    fee_rate: u64,
+    broadcast: bool,
+) -> Result<()> {
+    println!("\n═══════════════════════════════════════════════════════");
+    println!("  🔁 Rotate: 双方协作延长通道超时，余额保持不变");
+    println!("═══════════════════════════════════════════════════════\n");
+
+    println!("📋 加载配置...");
+    let config = load_config(config_path)?;
+    let channel_info = load_channel_info(channel_file)?;
+    println!("✓ 配置加载完成");
+    println!("  - 当前超时时间戳: {}", channel_info.timeout_timestamp);
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    if new_timeout_timestamp <= channel_info.timeout_timestamp {
+        return Err(anyhow!(
+            "New timeout {} must be later than the current timeout {}",
+            new_timeout_timestamp,
+            channel_info.timeout_timestamp
+        ));
+    }
+    if new_timeout_timestamp <= now {
+        return Err(anyhow!(
+            "New timeout {} is not in the future (now {})",
+            new_timeout_timestamp,
+            now
+        ));
+    }
+
+    println!("\n🔍 从链上查询 Funding cell...");
+    let spillman_script = channel_info.rebuild_spillman_script(&config)?;
+    let funding_tx_hash = H256::from_str(channel_info.funding_tx_hash.trim_start_matches("0x"))
+        .map_err(|e| anyhow!("Invalid funding_tx_hash in channel info: {}", e))?;
+    let funding_out_point =
+        OutPoint::new(funding_tx_hash.pack(), channel_info.funding_output_index);
+    let rpc_client = rpc::client(&config.network.rpc_url);
+    let (spillman_cell, spillman_data) = live_funding_cell(&rpc_client, &funding_out_point)?;
+    if spillman_cell.lock() != spillman_script {
+        return Err(anyhow!(
+            "Funding cell {:#x}:{} is not locked by this channel's Spillman Lock",
+            funding_tx_hash,
+            channel_info.funding_output_index
+        ));
+    }
+    let args = SpillmanArgs::from_bytes(&spillman_cell.lock().args().raw_data())?;
+    println!(
+        "✓ 通道容量: {}",
+        HumanCapacity::from(Unpack::<u64>::unpack(&spillman_cell.capacity()))
+    );
+
+    let merchant_secret_keys = config.merchant.get_secret_keys()?;
+    let merchant_multisig_config =
+        merchant_multisig_config(&config, &merchant_secret_keys, args.algorithm_id)?;
+
+    let mut cell_deps = vec![
+        code_cell_dep(&config.spillman_lock.tx_hash, config.spillman_lock.index)?,
+        code_cell_dep(&config.auth.tx_hash, config.auth.index)?,
+    ];
+    let type_script = spillman_cell.type_().to_opt();
+    if type_script.is_some() {
+        let xudt = config
+            .usdi
+            .as_ref()
+            .ok_or_else(|| anyhow!("xUDT channel detected but usdi config not found"))?
+            .validate()?;
+        cell_deps.push(xudt.cell_dep);
+    }
+
+    println!("\n💰 收集商户 cell（支付手续费并经 output 1 找零）...");
+    let merchant_address = Address::from_str(&config.merchant.address)
+        .map_err(|e| anyhow!("Failed to parse merchant address: {}", e))?;
+    let payout_min = payout_min_capacity(&Script::from(&merchant_address), type_script)?;
+    let fee = collect_merchant_fee_cells(
+        &config,
+        merchant_multisig_config.as_ref(),
+        payout_min + ONE_CKB,
+    )
+    .await?;
+    println!("✓ 已收集 {} 个商户 cell", fee.cells.len());
+
+    println!("\n🔨 构建 Rotate 交易...");
+    let timeout = Since::new(SinceType::Timestamp, new_timeout_timestamp, false).value();
+    let tx = build_rotate_transaction(
+        funding_out_point,
+        &spillman_cell,
+        &spillman_data,
+        timeout,
+        cell_deps,
+        &fee,
+        merchant_multisig_config.as_ref(),
+        fee_rate,
+    )?;
+    println!("✓ Rotate 交易构建完成");
+    println!(
+        "  - 新通道容量: {}",
+        HumanCapacity::from(Unpack::<u64>::unpack(
+            &tx.outputs().get(0).unwrap().capacity()
+        ))
+    );
+    println!("  - 新超时时间戳: {}", new_timeout_timestamp);
+
+    println!("\n🔐 双方签名...");
+    let tx = sign_splice_transaction(
+        tx,
+        &args,
+        RawKeySigner::new(config.user.get_secret_keys()?)?,
+        RawKeySigner::new_arc(merchant_secret_keys.clone())?,
+        merchant_multisig_config.as_ref(),
+    )?;
+    // Merchant inputs are unlocked by the merchant's own lock
+    let unlockers = build_unlockers(
+        RawKeySigner::new_arc(merchant_secret_keys)?,
+        merchant_multisig_config.clone(),
+    );
+    let tx_dep_provider = DefaultTransactionDependencyProvider::new(&config.network.rpc_url, 10);
+    let (tx, still_locked_groups) = unlock_tx(tx, &tx_dep_provider, &unlockers)?;
+    // Only the Spillman Lock group (signed above) is left
+    if still_locked_groups.len() > 1 {
+        return Err(anyhow!(
+            "Some merchant inputs are still locked: {:?}",
+            still_locked_groups
+        ));
+    }
+    println!("✓ 用户和商户签名完成");
+    println!("  - TX Hash: {:#x}", tx.hash());
+
+    // The rotation transaction funds the new channel, which starts without commitments
+    let rotated_info = rotated_channel_info(&channel_info, &tx, new_timeout_timestamp, now)?;
+    let channel_dir = Path::new("secrets").join(format!("rotate_{}", now));
+    fs::create_dir_all(&channel_dir)?;
+    let tx_json = ckb_jsonrpc_types::TransactionView::from(tx);
+    let tx_path = channel_dir.join("rotate_tx.json");
+    fs::write(&tx_path, serde_json::to_string_pretty(&tx_json)?)?;
+    let channel_info_path = write_channel_info(
+        &rotated_info,
+        &channel_dir,
+        InfoFormat::from_path(Path::new(channel_file)),
+    )?;
+    println!("\n💾 Rotate 交易已保存到: {}", tx_path.display());
+    println!("💾 新通道信息已保存到: {}", channel_info_path.display());
+    log_event(
+        "rotate",
+        "saved",
+        json!({
+            "tx_hash": format!("{:#x}", tx_json.hash),
+            "timeout_timestamp": new_timeout_timestamp,
+            "channel_file": channel_info_path.display().to_string(),
+        }),
+    );
+
+    if broadcast {
+        println!("\n📡 广播交易到链上...");
+        let tx_hash = rpc_client
+            .send_transaction(tx_json.inner.clone(), None)
+            .map_err(|e| anyhow!("Failed to broadcast transaction: {:?}", e))?;
+        println!("✓ 交易已广播");
+        println!("  - TX Hash: {:#x}", tx_hash);
+        log_event(
+            "rotate",
+            "broadcast",
+            json!({ "tx_hash": format!("{:#x}", tx_hash) }),
+        );
+    }
+
+    println!("\n✅ Rotate 完成！");
+    println!("\n📌 后续操作:");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!("  - 旧通道的 commitment 和 refund 交易均已失效");
+    println!(
+        "  - 之后的 pay / refund 使用新通道信息: --channel-file {}",
+        channel_info_path.display()
+    );
+    println!(
+        "  - 用户需要为新通道重新构建 refund 交易: spillman-cli refund --tx-file {}",
+        tx_path.display()
+    );
+    if !broadcast {
+        println!("\n📡 手动广播交易:");
+        println!(
+            "  ckb-cli tx send --tx-file {}",
+            ckb_cli_tx_file(&tx_path)?.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Build the unsigned rotation transaction of a channel
+///
+/// Input 0 is the funding cell at `funding_out_point`, followed by the merchant's `fee`
+/// cells. Output 0 is `spillman_cell` with its data under the same args with `timeout`
+/// replaced. Output 1 returns the merchant's cells minus the fee to the merchant, typed
+/// like the channel (zero xUDT) since the splice path requires it.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn build_rotate_transaction(
+    funding_out_point: OutPoint,
+    spillman_cell: &CellOutput,
+    spillman_data: &Bytes,
+    timeout: u64,
+    cell_deps: Vec<CellDep>,
+    fee: &SettlementFee,
+    merchant_multisig_config: Option<&MultisigConfig>,
+    fee_rate: u64,
+) -> Result<TransactionView> {
+    // Same channel, new timeout
+    let mut args = SpillmanArgs::from_bytes(&spillman_cell.lock().args().raw_data())?;
+    args.timeout = timeout;
+    let new_lock: Script = spillman_cell
+        .lock()
+        .as_builder()
+        .args(Bytes::copy_from_slice(&args.to_bytes()).pack())
+        .build();
+    let new_cell = spillman_cell.clone().as_builder().lock(new_lock).build();
+
+    let type_script = spillman_cell.type_().to_opt();
+    let payout_data = match type_script {
+        Some(_) => write_xudt_amount(0),
+        None => Bytes::new(),
+    };
+    let merchant_capacity: u64 = fee
+        .cells
+        .iter()
+        .map(|cell| Unpack::<u64>::unpack(&cell.output.capacity()))
+        .sum();
+    let witness_size = calculate_commitment_witness_size(merchant_multisig_config);
+    let build = |payout_capacity: u64| {
+        let mut builder = TransactionView::new_advanced_builder()
+            .cell_deps(cell_deps.clone())
+            .input(CellInput::new(funding_out_point.clone(), 0))
+            .witness(Bytes::from(vec![0u8; witness_size]).pack());
+        if let Some(ref cell_dep) = fee.cell_dep {
+            builder = builder.cell_dep(cell_dep.clone());
+        }
+        for cell in &fee.cells {
+            builder = builder
+                .input(CellInput::new(cell.out_point.clone(), 0))
+                .witness(fee.witness_placeholder.as_bytes().pack());
+        }
+        builder
+            .output(new_cell.clone())
+            .output_data(spillman_data.pack())
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(payout_capacity))
+                    .lock(fee.change_lock.clone())
+                    .type_(type_script.clone().pack())
+                    .build(),
+            )
+            .output_data(payout_data.pack())
+            .build()
+    };
+
+    // The payout capacity does not change the size, so one pass fixes the fee
+    let tx_size = build(0).data().as_reader().serialized_size_in_block() as u64;
+    let rotate_fee = FeeCalculator::new(fee_rate).fee(tx_size);
+    let payout_min = payout_min_capacity(&fee.change_lock, type_script.clone())?;
+    let payout_capacity = merchant_capacity
+        .checked_sub(rotate_fee)
+        .filter(|capacity| *capacity >= payout_min)
+        .ok_or_else(|| {
+            anyhow!(
+                "Merchant cells hold {}, need {} for the fee plus {} for the payout",
+                HumanCapacity::from(merchant_capacity),
+                HumanCapacity::from(rotate_fee),
+                HumanCapacity::from(payout_min)
+            )
+        })?;
+
+    Ok(build(payout_capacity))
+}
+
+/// Channel info of the rotated channel, funded by output 0 of `rotate_tx`
+///
+/// Everything but the timeout, the Spillman Lock script hash and the funding cell is
+/// carried over: the rotation keeps the parties, capacity and xUDT balance.
+pub fn rotated_channel_info(
+    channel_info: &ChannelInfo,
+    rotate_tx: &TransactionView,
+    timeout_timestamp: u64,
+    created_at: u64,
+) -> Result<ChannelInfo> {
+    let new_cell = rotate_tx
+        .outputs()
+        .get(0)
+        .ok_or_else(|| anyhow!("Rotate transaction has no output 0"))?;
+    let script_hash: H256 = new_cell.lock().calc_script_hash().unpack();
+    let funding_tx_hash: H256 = rotate_tx.hash().unpack();
+    Ok(ChannelInfo {
+        timeout_timestamp,
+        spillman_lock_script_hash: format!("{:#x}", script_hash),
+        funding_tx_hash: format!("{:#x}", funding_tx_hash),
+        funding_output_index: 0,
+        refund_available_at: Some(timeout_timestamp + TIMEOUT_GRACE_SECONDS),
+        funding_created_at: Some(created_at),
+        ..channel_info.clone()
+    })
+}
+
+/// Occupied capacity of the merchant output: its lock, the channel's type and xUDT data
+fn payout_min_capacity(lock: &Script, type_script: Option<Script>) -> Result<u64> {
+    let data_len = if type_script.is_some() {
+        XUDT_AMOUNT_LEN
+    } else {
+        0
+    };
+    CellOutput::new_builder()
+        .lock(lock.clone())
+        .type_(type_script.pack())
+        .build()
+        .occupied_capacity(Capacity::bytes(data_len)?)
+        .map(|capacity| capacity.as_u64())
+        .map_err(|e| anyhow!("Failed to calculate payout capacity: {:?}", e))
+}
+
+/// The live funding cell and its data, looked up on chain
+fn live_funding_cell(
+    rpc_client: &ckb_sdk::rpc::CkbRpcClient,
+    out_point: &OutPoint,
+) -> Result<(CellOutput, Bytes)> {
+    let cell = rpc::with_retry("get_live_cell", || {
+        rpc_client.get_live_cell(out_point.clone().into(), true)
+    })
+    .map_err(|e| anyhow!("RPC error: {:?}", e))?;
+    let info = match (cell.status.as_str(), cell.cell) {
+        ("live", Some(info)) => info,
+        (status, _) => {
+            return Err(anyhow!(
+                "Funding cell {} is not live (status: {})",
+                out_point,
+                status
+            ))
+        }
+    };
+    let data = info
+        .data
+        .map(|data| data.content.into_bytes())
+        .unwrap_or_default();
+    Ok((info.output.into(), data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_crypto::secp::Privkey;
+    use ckb_sdk::traits::LiveCell;
+    use ckb_types::{core::ScriptHashType, packed::WitnessArgs};
+
+    use crate::utils::crypto::pubkey_hash;
+
+    fn key_hash(byte: u8) -> [u8; 20] {
+        pubkey_hash(&Privkey::from_slice(&[byte; 32]).pubkey().unwrap())
+    }
+
+    fn sighash_lock(args: [u8; 20]) -> Script {
+        Script::new_builder()
+            .code_hash(H256([0x9b; 32]).pack())
+            .hash_type(ScriptHashType::Type)
+            .args(Bytes::copy_from_slice(&args).pack())
+            .build()
+    }
+
+    fn merchant_fee(capacity: u64) -> SettlementFee {
+        let output = CellOutput::new_builder()
+            .capacity(Capacity::shannons(capacity))
+            .lock(sighash_lock(key_hash(2)))
+            .build();
+        SettlementFee {
+            cells: vec![LiveCell {
+                output,
+                output_data: Bytes::new(),
+                out_point: OutPoint::new(H256([0x22; 32]).pack(), 1),
+                block_number: 1,
+                tx_index: 0,
+            }],
+            change_lock: sighash_lock(key_hash(2)),
+            witness_placeholder: WitnessArgs::new_builder()
+                .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+                .build(),
+            cell_dep: None,
+        }
+    }
+
+    #[test]
+    fn test_rotate_only_changes_the_timeout() {
+        // An arbitration channel: the trailing arbiter must survive the rotation too
+        let args = SpillmanArgs {
+            arbiter_pubkey_hash: Some(key_hash(3)),
+            ..SpillmanArgs::new_with_algorithm(key_hash(2), key_hash(1), 0x4000_0000_6774_8580, 0)
+        };
+        let spillman_cell = CellOutput::new_builder()
+            .capacity(Capacity::shannons(1_000 * ONE_CKB))
+            .lock(
+                Script::new_builder()
+                    .code_hash(H256([0x5a; 32]).pack())
+                    .hash_type(ScriptHashType::Data1)
+                    .args(Bytes::copy_from_slice(&args.to_bytes()).pack())
+                    .build(),
+            )
+            .build();
+        let funding_out_point = OutPoint::new(H256([0x11; 32]).pack(), 0);
+
+        let new_timeout = 0x4000_0000_6800_0000;
+        let tx = build_rotate_transaction(
+            funding_out_point.clone(),
+            &spillman_cell,
+            &Bytes::new(),
+            new_timeout,
+            vec![],
+            &merchant_fee(100 * ONE_CKB),
+            None,
+            1_000,
+        )
+        .unwrap();
+
+        // Output 0 keeps the whole channel; its args differ only in the timeout field
+        let new_cell = tx.outputs().get(0).unwrap();
+        assert_eq!(new_cell.capacity(), spillman_cell.capacity());
+        assert_eq!(
+            new_cell.lock().code_hash(),
+            spillman_cell.lock().code_hash()
+        );
+        let old_args = spillman_cell.lock().args().raw_data();
+        let new_args = new_cell.lock().args().raw_data();
+        assert_eq!(new_args.len(), old_args.len());
+        assert_eq!(new_args[..40], old_args[..40]);
+        assert_eq!(new_args[48..], old_args[48..]);
+        assert_eq!(new_args[40..48], new_timeout.to_le_bytes());
+
+        // Input 0 is the funding cell; the merchant's cells pay the fee through output 1
+        assert_eq!(
+            tx.inputs().get(0).unwrap().previous_output(),
+            funding_out_point
+        );
+        let fee =
+            FeeCalculator::new(1_000).fee(tx.data().as_reader().serialized_size_in_block() as u64);
+        let payout = tx.outputs().get(1).unwrap();
+        assert_eq!(payout.lock(), sighash_lock(key_hash(2)));
+        assert_eq!(
+            Unpack::<u64>::unpack(&payout.capacity()),
+            100 * ONE_CKB - fee
+        );
+
+        // The new channel info points at output 0 of the rotation
+        let channel_info = ChannelInfo {
+            user_address: "ckt1user".to_string(),
+            merchant_address: "ckt1merchant".to_string(),
+            capacity_ckb: 1_000,
+            timeout_epochs: 0,
+            current_timestamp: 1_700_000_000,
+            timeout_timestamp: 0x6774_8580,
+            spillman_lock_script_hash: format!(
+                "{:#x}",
+                Unpack::<H256>::unpack(&spillman_cell.lock().calc_script_hash())
+            ),
+            funding_tx_hash: format!("{:#x}", H256([0x11; 32])),
+            funding_output_index: 0,
+            xudt_type_script: None,
+            xudt_amount: None,
+            refund_available_at: Some(0x6774_8580 + TIMEOUT_GRACE_SECONDS),
+            funding_created_at: Some(1_700_000_000),
+            co_funded: Some(false),
+            args_version: None,
+        };
+        let rotated = rotated_channel_info(&channel_info, &tx, 0x6800_0000, 1_750_000_000).unwrap();
+        assert_eq!(rotated.timeout_timestamp, 0x6800_0000);
+        assert_eq!(
+            rotated.refund_available_at,
+            Some(0x6800_0000 + TIMEOUT_GRACE_SECONDS)
+        );
+        assert_eq!(
+            rotated.funding_tx_hash,
+            format!("{:#x}", Unpack::<H256>::unpack(&tx.hash()))
+        );
+        assert_eq!(
+            rotated.spillman_lock_script_hash,
+            format!(
+                "{:#x}",
+                Unpack::<H256>::unpack(&new_cell.lock().calc_script_hash())
+            )
+        );
+        assert_eq!(rotated.capacity_ckb, channel_info.capacity_ckb);
+        assert_eq!(rotated.user_address, channel_info.user_address);
+
+        // Merchant cells that cannot cover the fee and the payout are rejected
+        assert!(build_rotate_transaction(
+            funding_out_point,
+            &spillman_cell,
+            &Bytes::new(),
+            new_timeout,
+            vec![],
+            &merchant_fee(61 * ONE_CKB),
+            None,
+            1_000,
+        )
+        .is_err());
+    }
+}
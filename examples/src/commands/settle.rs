@@ -1,24 +1,68 @@
 use anyhow::{anyhow, Result};
-use ckb_crypto::secp::Privkey;
-use ckb_hash::blake2b_256;
-use ckb_sdk::{constants::MultisigScript, rpc::CkbRpcClient, Address};
+use ckb_sdk::{
+    constants::{MultisigScript, ONE_CKB},
+    rpc::CkbRpcClient,
+    traits::{
+        CellCollector, CellDepResolver, CellQueryOptions, DefaultCellCollector,
+        DefaultTransactionDependencyProvider, LiveCell, ValueRangeOption,
+    },
+    transaction::builder::FeeCalculator,
+    tx_builder::unlock_tx,
+    Address,
+};
 use ckb_types::{
     bytes::Bytes,
-    core::TransactionView,
-    packed::{CellDepVec, Script as PackedScript},
+    core::{Capacity, TransactionView},
+    packed::{CellDep, CellInput, CellOutput, OutPoint, Script as PackedScript, WitnessArgs},
     prelude::*,
     H256,
 };
-use std::{fs, str::FromStr};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
+use crate::utils::rpc;
 use crate::{
-    tx_builder::funding_v2::build_multisig_config_with_type,
-    tx_builder::witness_utils::{EMPTY_WITNESS_ARGS_SIZE, SIGNATURE_SIZE, UNLOCK_TYPE_SIZE},
-    utils::config::load_config,
+    channel::{BuilderBackend, Channel, ChannelParams, ChannelState, ChannelStatus},
+    signer::RawKeySigner,
+    tx_builder::capacity_utils::merchant_min_capacity,
+    tx_builder::commitment_v2::CommitmentTx,
+    tx_builder::funding_v2::{build_multisig_config_with_type, build_unlockers},
+    tx_builder::signing::ARGS_VERSION_BOUND_CELL_DEPS,
+    tx_builder::witness_utils::SIGNATURE_SIZE,
+    tx_builder::xudt::read_xudt_amount,
+    utils::{
+        chain::ConfirmationWait,
+        config::{load_config, Config},
+        crypto::SpillmanArgs,
+        deps::load_cell_dep_resolver,
+        fee::{resolve_fee_rate, tx_fee_rate, FeeRateStrategy},
+        log::log_event,
+        storage::load_commitment_records,
+    },
 };
+use serde_json::json;
 
 /// Execute settle command - merchant signs and broadcasts commitment transaction
-pub async fn execute(tx_file: &str, config_path: &str, broadcast: bool) -> Result<()> {
+///
+/// With `bump_fee`, a commitment paying less than the strategy's fee rate gets a fee
+/// input and change from the merchant's own cells; the signed channel outputs are kept.
+/// With `raw` the `send_transaction` request is printed instead of broadcasting.
+/// `channel_file` locates the commitment ledger `pay` keeps, which catches stale commitments.
+#[allow(clippy::too_many_arguments)]
+pub async fn execute(
+    tx_file: &str,
+    channel_file: &str,
+    config_path: &str,
+    broadcast: bool,
+    raw: bool,
+    wait: Option<ConfirmationWait>,
+    fee_rate_strategy: Option<FeeRateStrategy>,
+    bump_fee: bool,
+    allow_stale: bool,
+) -> Result<()> {
     println!("\n═══════════════════════════════════════════════════════");
     println!("  🏦 商户结算 Commitment Transaction");
     println!("═══════════════════════════════════════════════════════\n");
@@ -104,8 +148,7 @@ pub async fn execute(tx_file: &str, config_path: &str, broadcast: bool) -> Resul
         // Parse single private key
         let key_str = config
             .merchant
-            .private_key
-            .as_ref()
+            .resolve_private_key()?
             .ok_or_else(|| anyhow!("Merchant private_key is required"))?;
 
         let key_bytes = hex::decode(key_str.trim_start_matches("0x"))
@@ -133,111 +176,100 @@ pub async fn execute(tx_file: &str, config_path: &str, broadcast: bool) -> Resul
     println!("  - Inputs: {}", tx.inputs().len());
     println!("  - Outputs: {}", tx.outputs().len());
 
-    // 4. Verify witness structure and determine sizes
-    let witness = tx
-        .witnesses()
-        .get(0)
-        .ok_or_else(|| anyhow!("Missing witness"))?;
-    let witness_data = witness.raw_data();
-
-    // Calculate expected witness size based on multisig config
-    let (merchant_sig_start, merchant_sig_size, expected_size) =
-        if let Some(ref multisig_config) = merchant_multisig_config {
-            let config_data = multisig_config.to_witness_data();
-            let threshold = multisig_config.threshold() as usize;
-            let merchant_sigs_size = threshold * SIGNATURE_SIZE;
-
-            let start = EMPTY_WITNESS_ARGS_SIZE + UNLOCK_TYPE_SIZE;
-            let size = config_data.len() + merchant_sigs_size;
-            let total = start + size + SIGNATURE_SIZE;
-
-            (start, size, total)
-        } else {
-            let start = EMPTY_WITNESS_ARGS_SIZE + UNLOCK_TYPE_SIZE;
-            let size = SIGNATURE_SIZE;
-            let total = start + size + SIGNATURE_SIZE;
-
-            (start, size, total)
-        };
-
-    if witness_data.len() != expected_size {
-        return Err(anyhow!(
-            "Invalid witness size: expected {}, got {}",
-            expected_size,
-            witness_data.len()
-        ));
-    }
-
-    // Check if merchant signature is placeholder (all zeros)
-    let merchant_sig_end = merchant_sig_start + merchant_sig_size;
-    let merchant_sig_placeholder = &witness_data[merchant_sig_start..merchant_sig_end];
-
-    if !merchant_sig_placeholder.iter().all(|&b| b == 0) {
-        return Err(anyhow!("Merchant signature already present in transaction"));
+    // The merchant always wants the highest-value commitment; refuse older ones
+    check_latest_commitment(channel_file, &tx, allow_stale)?;
+
+    // Resume the channel from the funding transaction of the Spillman cell
+    let rpc_client = rpc::client(&config.network.rpc_url);
+    let (funding_tx, funding_output_index) = spillman_funding_tx(&rpc_client, &tx)?;
+    let spillman_cell = funding_tx
+        .outputs()
+        .get(funding_output_index as usize)
+        .ok_or_else(|| {
+            anyhow!(
+                "Input cell not found at output index {}",
+                funding_output_index
+            )
+        })?;
+
+    // The commitment fee is fixed by the user's signature: warn if it is too low, or let
+    // the merchant top it up from its own cells with --bump-fee
+    let mut fee_bump = None;
+    if let Some(strategy) = fee_rate_strategy {
+        let fee = commitment_fee(&tx, &spillman_cell)?;
+        let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+        let commitment_rate = tx_fee_rate(fee, tx_size);
+        let network_rate = resolve_fee_rate(&rpc_client, strategy)?;
+        println!("  - Commitment 费率: {} shannons/KB", commitment_rate);
+        println!(
+            "  - 当前网络费率 ({:?}): {} shannons/KB",
+            strategy, network_rate
+        );
+        if commitment_rate < network_rate && bump_fee {
+            fee_bump = Some((fee, network_rate));
+        } else if commitment_rate < network_rate {
+            println!("⚠️  Commitment 交易费率低于当前网络费率，广播后可能无法及时上链");
+            println!("💡 使用 --bump-fee 由商户追加手续费");
+        }
     }
 
-    println!("✓ Witness 结构验证通过");
-
-    // 5. Sign transaction
+    // 4. Sign as the merchant; the message covers the Spillman input and channel outputs only
     println!("\n🔐 商户签名交易...");
-    let signing_message = compute_signing_message(&tx);
-
-    // Build merchant signatures based on single-sig or multisig
-    let merchant_witness_data = if let Some(ref multisig_config) = merchant_multisig_config {
-        // Multisig: need to sign with threshold number of keys
-        let threshold = multisig_config.threshold() as usize;
-        let mut signatures = Vec::new();
-
-        for (i, key) in merchant_privkeys.iter().take(threshold).enumerate() {
-            let privkey_bytes = key.secret_bytes();
-            let privkey = Privkey::from_slice(&privkey_bytes);
-
-            let sig = privkey
-                .sign_recoverable(&signing_message.into())
-                .map_err(|e| anyhow!("Failed to sign with key {}: {:?}", i, e))?
-                .serialize();
+    let mut signed_tx =
+        settle_through_channel(&config, &tx, funding_tx, funding_output_index).await?;
+    println!("  ✓ 签名完成");
+
+    // 5. Fee inputs attached after signing leave both signatures valid; the merchant's
+    // own lock unlocks them
+    if let Some((fee, network_rate)) = fee_bump {
+        println!("\n💰 商户追加手续费 input...");
+        let args = SpillmanArgs::from_bytes(&spillman_cell.lock().args().raw_data())?;
+        let settlement_fee = collect_settlement_fee(
+            &config,
+            &signed_tx,
+            merchant_multisig_config.as_ref(),
+            &args,
+        )
+        .await?;
+        signed_tx = attach_settlement_fee(&signed_tx, &settlement_fee, fee, network_rate)?;
+        println!(
+            "✓ 已追加 {} 个商户 input（找零回商户地址），新费率: {} shannons/KB",
+            settlement_fee.cells.len(),
+            network_rate
+        );
 
-            signatures.extend_from_slice(&sig);
-            println!("  ✓ 签名 {}/{} 完成", i + 1, threshold);
+        let unlockers = build_unlockers(
+            RawKeySigner::new_arc(merchant_privkeys.clone())?,
+            merchant_multisig_config.clone(),
+        );
+        let tx_dep_provider =
+            DefaultTransactionDependencyProvider::new(&config.network.rpc_url, 10);
+        let (unlocked_tx, still_locked_groups) =
+            unlock_tx(signed_tx, &tx_dep_provider, &unlockers)?;
+        // Only the Spillman Lock group (signed above) is left
+        if still_locked_groups.len() > 1 {
+            return Err(anyhow!(
+                "Some fee inputs are still locked: {:?}",
+                still_locked_groups
+            ));
         }
-
-        // Build multisig witness: multisig_config + signatures
-        let mut multisig_witness = multisig_config.to_witness_data();
-        multisig_witness.extend_from_slice(&signatures);
-        multisig_witness
-    } else {
-        // Single-sig: just one signature
-        let privkey_bytes = merchant_privkeys[0].secret_bytes();
-        let privkey = Privkey::from_slice(&privkey_bytes);
-
-        let sig = privkey
-            .sign_recoverable(&signing_message.into())
-            .map_err(|e| anyhow!("Failed to sign transaction: {:?}", e))?
-            .serialize();
-
-        println!("  ✓ 签名完成");
-        sig.to_vec()
-    };
-
-    // 6. Update witness with merchant signature
-    let mut new_witness = Vec::with_capacity(expected_size);
-    new_witness.extend_from_slice(&witness_data[..merchant_sig_start]); // EMPTY_WITNESS_ARGS + UNLOCK_TYPE
-    new_witness.extend_from_slice(&merchant_witness_data); // Merchant signature(s)
-    new_witness.extend_from_slice(&witness_data[merchant_sig_end..]); // User signature
-
-    let signed_tx = tx
-        .as_advanced_builder()
-        .set_witnesses(vec![Bytes::from(new_witness).pack()])
-        .build();
+        signed_tx = unlocked_tx;
+        println!("  ✓ 手续费 input 签名完成");
+    }
 
     let signed_tx_hash = signed_tx.hash();
     println!("✓ 交易签名更新完成");
     println!("  - New TX Hash: {:#x}", signed_tx_hash);
 
-    // 7. Broadcast transaction (optional)
+    // 6. Broadcast transaction (optional), or hand the request to another tool
+    if raw {
+        let signed_tx_json = ckb_jsonrpc_types::TransactionView::from(signed_tx);
+        rpc::print_send_transaction_request(&signed_tx_json.inner);
+        return Ok(());
+    }
     if broadcast {
         println!("\n📡 广播交易到链上...");
-        let rpc_client = CkbRpcClient::new(&config.network.rpc_url);
+        let rpc_client = rpc::client(&config.network.rpc_url);
 
         // Convert to JSON RPC format (standard SDK method)
         let signed_tx_json = ckb_jsonrpc_types::TransactionView::from(signed_tx.clone());
@@ -248,6 +280,22 @@ pub async fn execute(tx_file: &str, config_path: &str, broadcast: bool) -> Resul
 
         println!("✓ 交易已广播");
         println!("  - TX Hash: {:#x}", tx_hash);
+        log_event(
+            "settle",
+            "broadcast",
+            json!({ "tx_hash": format!("{:#x}", tx_hash) }),
+        );
+        if let Some(wait) = wait {
+            let confirmations = wait.wait(&rpc_client, &tx_hash).await?;
+            log_event(
+                "settle",
+                "confirmed",
+                json!({
+                    "tx_hash": format!("{:#x}", tx_hash),
+                    "confirmations": confirmations,
+                }),
+            );
+        }
 
         // 8. Success message
         println!("\n✅ 结算成功！");
@@ -255,8 +303,10 @@ pub async fn execute(tx_file: &str, config_path: &str, broadcast: bool) -> Resul
         println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
         println!("\n🔍 查询交易状态：");
         println!("  ckb-cli rpc get_transaction --hash {:#x}", tx_hash);
-        println!("\n⏳ 等待交易上链确认...");
-        println!("  交易确认后，支付金额将到达商户地址");
+        if wait.is_none() {
+            println!("\n⏳ 等待交易上链确认...");
+            println!("  交易确认后，支付金额将到达商户地址");
+        }
     } else {
         // Save signed transaction to file
         println!("\n💾 保存已签名交易...");
@@ -268,6 +318,14 @@ pub async fn execute(tx_file: &str, config_path: &str, broadcast: bool) -> Resul
         fs::write(&output_path, json_str)?;
 
         println!("✓ 已签名交易已保存到: {}", output_path);
+        log_event(
+            "settle",
+            "signed",
+            json!({
+                "tx_hash": format!("{:#x}", signed_tx_hash),
+                "output_path": output_path,
+            }),
+        );
 
         // 8. Success message
         println!("\n✅ 交易签名完成 - 未广播");
@@ -275,23 +333,560 @@ pub async fn execute(tx_file: &str, config_path: &str, broadcast: bool) -> Resul
         println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
         println!("\n📄 已签名交易文件: {}", output_path);
         println!("\n📡 手动广播交易:");
-        println!("  spillman-cli settle --tx-file {} --broadcast", tx_file);
+        println!(
+            "  spillman-cli settle --tx-file {} --channel-file {} --broadcast",
+            tx_file, channel_file
+        );
         println!("  或者使用其他工具手动发送交易");
     }
 
     Ok(())
 }
 
-/// Compute signing message for Spillman Lock
+/// Amount paid to the merchant by a commitment: every output after the user's that
+/// carries the merchant lock of output 1
+///
+/// xUDT channels are compared by token amount, CKB channels by capacity.
+fn merchant_amount(tx: &TransactionView) -> Result<u128> {
+    let merchant_lock = tx
+        .outputs()
+        .get(1)
+        .ok_or_else(|| anyhow!("Commitment transaction has no merchant output"))?
+        .lock();
+
+    let mut amount = 0u128;
+    for (index, output) in tx.outputs().into_iter().enumerate().skip(1) {
+        if output.lock() != merchant_lock {
+            continue;
+        }
+        let paid = if output.type_().is_some() {
+            let data = tx
+                .outputs_data()
+                .get(index)
+                .map(|data| data.raw_data())
+                .unwrap_or_default();
+            read_xudt_amount(&data)?
+        } else {
+            Unpack::<u64>::unpack(&output.capacity()) as u128
+        };
+        amount = amount
+            .checked_add(paid)
+            .ok_or_else(|| anyhow!("Merchant amount overflow"))?;
+    }
+    Ok(amount)
+}
+
+/// Find the highest-value commitment `pay` recorded in the ledger of `channel_dir` for
+/// the same channel
+///
+/// Records whose commitment file is gone or spends another Spillman Lock cell than `tx`
+/// (an earlier channel in the same directory) are skipped.
+fn latest_recorded_commitment(
+    channel_dir: &Path,
+    tx: &TransactionView,
+) -> Result<Option<(PathBuf, u128)>> {
+    let spillman_out_point = tx
+        .inputs()
+        .get(0)
+        .ok_or_else(|| anyhow!("Commitment transaction has no input"))?
+        .previous_output();
+
+    let mut latest: Option<(PathBuf, u128)> = None;
+    for record in load_commitment_records(channel_dir)? {
+        let path = PathBuf::from(&record.tx_file);
+        let Some(recorded) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str::<ckb_jsonrpc_types::TransactionView>(&json).ok())
+            .map(|tx_json| ckb_types::packed::Transaction::from(tx_json.inner).into_view())
+        else {
+            continue;
+        };
+        let same_channel = recorded
+            .inputs()
+            .get(0)
+            .is_some_and(|input| input.previous_output() == spillman_out_point);
+        if !same_channel {
+            continue;
+        }
+
+        let amount = merchant_amount(&recorded)?;
+        if latest
+            .as_ref()
+            .is_none_or(|(_, latest_amount)| amount > *latest_amount)
+        {
+            latest = Some((path, amount));
+        }
+    }
+
+    Ok(latest)
+}
+
+/// Refuse to settle a commitment paying the merchant less than the latest one recorded
+/// next to the channel info file `channel_file`
+pub fn check_latest_commitment(
+    channel_file: &str,
+    tx: &TransactionView,
+    allow_stale: bool,
+) -> Result<()> {
+    let channel_dir = Path::new(channel_file)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let amount = merchant_amount(tx)?;
+    let Some((latest_path, latest_amount)) = latest_recorded_commitment(channel_dir, tx)? else {
+        return Ok(());
+    };
+
+    if amount < latest_amount {
+        if !allow_stale {
+            return Err(anyhow!(
+                "Stale commitment: merchant amount {} is lower than {} in {}, use --allow-stale to settle anyway",
+                amount,
+                latest_amount,
+                latest_path.display()
+            ));
+        }
+        println!(
+            "⚠️  该 Commitment 商户金额 {} 低于最新记录 {} ({})，已通过 --allow-stale 放行",
+            amount,
+            latest_amount,
+            latest_path.display()
+        );
+    } else {
+        println!("✓ 已是最新的 Commitment（商户金额 {}）", amount);
+    }
+
+    Ok(())
+}
+
+/// Merchant cells paying an extra settlement fee on top of the commitment's own fee
+pub(crate) struct SettlementFee {
+    /// Plain CKB cells of the merchant, spent after the Spillman input
+    pub(crate) cells: Vec<LiveCell>,
+    /// Lock of the change output (the merchant's address)
+    pub(crate) change_lock: PackedScript,
+    /// Witness placeholder of each fee input, sized like its final signature
+    pub(crate) witness_placeholder: WitnessArgs,
+    /// Cell dep of the fee inputs' lock, when the transaction does not have it yet
+    pub(crate) cell_dep: Option<CellDep>,
+}
+
+/// Collect merchant cells for a settlement fee
+///
+/// Version 1 args bind the commitment's cell deps, so the fee inputs' lock dep cannot
+/// be added after the user signed; such commitments can only be bumped if it is there.
+async fn collect_settlement_fee(
+    config: &crate::utils::config::Config,
+    tx: &TransactionView,
+    merchant_multisig_config: Option<&ckb_sdk::unlock::MultisigConfig>,
+    args: &SpillmanArgs,
+) -> Result<SettlementFee> {
+    let change_lock = merchant_change_lock(config)?;
+    // Enough for the change cell and any realistic fee
+    let min_total_capacity = change_min_capacity(&change_lock)? + ONE_CKB;
+    let mut fee =
+        collect_merchant_fee_cells(config, merchant_multisig_config, min_total_capacity).await?;
+
+    if fee
+        .cell_dep
+        .as_ref()
+        .is_some_and(|cell_dep| tx.cell_deps().into_iter().any(|dep| &dep == cell_dep))
+    {
+        fee.cell_dep = None;
+    } else if args.version == ARGS_VERSION_BOUND_CELL_DEPS {
+        return Err(anyhow!(
+            "Version 1 commitment binds its cell deps: the merchant lock dep cannot be added for a fee input"
+        ));
+    }
+    Ok(fee)
+}
+
+/// Collect plain CKB cells of the merchant holding at least `min_total_capacity`
+///
+/// The returned fee always carries the cell dep of the merchant lock; callers drop it when
+/// their transaction already has it.
+pub(crate) async fn collect_merchant_fee_cells(
+    config: &crate::utils::config::Config,
+    merchant_multisig_config: Option<&ckb_sdk::unlock::MultisigConfig>,
+    min_total_capacity: u64,
+) -> Result<SettlementFee> {
+    let change_lock = merchant_change_lock(config)?;
+    let cell_dep = load_cell_dep_resolver(&config.network.rpc_url)?
+        .resolve(&change_lock)
+        .ok_or_else(|| anyhow!("Failed to resolve cell dep of the merchant lock"))?;
+
+    let mut query = CellQueryOptions::new_lock(change_lock.clone());
+    query.secondary_script_len_range = Some(ValueRangeOption::new_exact(0));
+    query.data_len_range = Some(ValueRangeOption::new_exact(0));
+    query.min_total_capacity = min_total_capacity;
+    let (cells, _) = DefaultCellCollector::new(&config.network.rpc_url)
+        .collect_live_cells_async(&query, false)
+        .await?;
+    if cells.is_empty() {
+        return Err(anyhow!("Merchant has no plain CKB cells to pay the fee"));
+    }
+
+    let witness_placeholder = match merchant_multisig_config {
+        Some(multisig_config) => multisig_config.placeholder_witness(),
+        None => WitnessArgs::new_builder()
+            .lock(Some(Bytes::from(vec![0u8; SIGNATURE_SIZE])).pack())
+            .build(),
+    };
+
+    Ok(SettlementFee {
+        cells,
+        change_lock,
+        witness_placeholder,
+        cell_dep: Some(cell_dep),
+    })
+}
+
+/// Lock of the merchant's address, which receives the change of its fee inputs
+fn merchant_change_lock(config: &crate::utils::config::Config) -> Result<PackedScript> {
+    let merchant_address = Address::from_str(&config.merchant.address)
+        .map_err(|e| anyhow!("Failed to parse merchant address: {}", e))?;
+    Ok(PackedScript::from(&merchant_address))
+}
+
+/// Append the merchant's fee inputs and change output to a commitment
+///
+/// The Spillman input and the signed outputs are left untouched. The fee inputs top up
+/// `commitment_fee` (paid by the channel, fixed by the user's signature) to `fee_rate`
+/// for the final size; the rest of their capacity goes back as change.
+fn attach_settlement_fee(
+    tx: &TransactionView,
+    fee: &SettlementFee,
+    commitment_fee: u64,
+    fee_rate: u64,
+) -> Result<TransactionView> {
+    let build = |change_capacity: u64| {
+        let mut builder = tx.as_advanced_builder();
+        if let Some(ref cell_dep) = fee.cell_dep {
+            builder = builder.cell_dep(cell_dep.clone());
+        }
+        for cell in &fee.cells {
+            builder = builder
+                .input(CellInput::new(cell.out_point.clone(), 0))
+                .witness(fee.witness_placeholder.as_bytes().pack());
+        }
+        builder
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(change_capacity))
+                    .lock(fee.change_lock.clone())
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build()
+    };
+
+    // The change capacity does not change the size, so one pass fixes the fee
+    let tx_size = build(0).data().as_reader().serialized_size_in_block() as u64;
+    let extra_fee = FeeCalculator::new(fee_rate)
+        .fee(tx_size)
+        .saturating_sub(commitment_fee);
+    let fee_capacity: u64 = fee
+        .cells
+        .iter()
+        .map(|cell| Unpack::<u64>::unpack(&cell.output.capacity()))
+        .sum();
+    let min_change = change_min_capacity(&fee.change_lock)?;
+    let change = fee_capacity
+        .checked_sub(extra_fee)
+        .filter(|change| *change >= min_change)
+        .ok_or_else(|| {
+            anyhow!(
+                "Fee inputs hold {} shannons, need {} for the fee plus {} for the change",
+                fee_capacity,
+                extra_fee,
+                min_change
+            )
+        })?;
+
+    Ok(build(change))
+}
+
+/// Minimum occupied capacity of a plain change cell locked by `lock`
+pub(crate) fn change_min_capacity(lock: &PackedScript) -> Result<u64> {
+    CellOutput::new_builder()
+        .lock(lock.clone())
+        .build()
+        .occupied_capacity(Capacity::zero())
+        .map(|capacity| capacity.as_u64())
+        .map_err(|e| anyhow!("Failed to calculate change capacity: {:?}", e))
+}
+
+/// Add the merchant signature to `commitment` through [`Channel`]
 ///
-/// Spillman Lock signs the raw transaction without cell_deps
-fn compute_signing_message(tx: &TransactionView) -> [u8; 32] {
-    let raw_tx = tx
-        .data()
-        .raw()
-        .as_builder()
-        .cell_deps(CellDepVec::default())
-        .build();
-
-    blake2b_256(raw_tx.as_slice())
+/// The channel resumes from the funding transaction with `commitment` as its latest
+/// commitment; staleness against the recorded commitments is checked by the caller.
+async fn settle_through_channel(
+    config: &Config,
+    commitment: &TransactionView,
+    funding_tx: TransactionView,
+    funding_output_index: u32,
+) -> Result<TransactionView> {
+    let spillman_cell = funding_tx
+        .outputs()
+        .get(funding_output_index as usize)
+        .ok_or_else(|| {
+            anyhow!(
+                "Input cell not found at output index {}",
+                funding_output_index
+            )
+        })?;
+    let output = |index: usize| {
+        commitment
+            .outputs()
+            .get(index)
+            .ok_or_else(|| anyhow!("Commitment transaction has no output {}", index))
+    };
+    let (user_output, merchant_output) = (output(0)?, output(1)?);
+
+    let fee = commitment_fee(commitment, &spillman_cell)?;
+    let tx_size = commitment.data().as_reader().serialized_size_in_block() as u64;
+    let merchant_capacity: u64 = merchant_output.capacity().unpack();
+    let paid_amount = merchant_capacity.saturating_sub(merchant_min_capacity(
+        &merchant_output.lock(),
+        merchant_output.type_().to_opt().as_ref(),
+    )?);
+
+    let params = ChannelParams {
+        spillman_lock: spillman_cell.lock(),
+        user_lock: user_output.lock(),
+        merchant_lock: merchant_output.lock(),
+        capacity: spillman_cell.capacity().unpack(),
+        fee_rate: tx_fee_rate(fee, tx_size),
+    };
+    let state = ChannelState {
+        funding_tx,
+        funding_output_index,
+        status: ChannelStatus::Open,
+        paid_amount,
+        latest_commitment: Some(commitment.clone()),
+    };
+    let backend = BuilderBackend::from_config(config, &params.spillman_lock)?;
+    let mut channel = Channel::resume(backend, params, state);
+    channel.settle(CommitmentTx::from(commitment.clone())).await
+}
+
+/// Funding transaction of the Spillman cell spent by the commitment (input 0) and the
+/// cell's index in it, looked up on chain
+pub fn spillman_funding_tx(
+    rpc_client: &CkbRpcClient,
+    tx: &TransactionView,
+) -> Result<(TransactionView, u32)> {
+    let out_point: OutPoint = tx
+        .inputs()
+        .get(0)
+        .ok_or_else(|| anyhow!("Commitment transaction has no input"))?
+        .previous_output();
+    let prev_tx = rpc_client
+        .get_transaction(out_point.tx_hash().unpack())
+        .map_err(|e| anyhow!("RPC error: {:?}", e))?
+        .and_then(|tx_with_status| tx_with_status.transaction)
+        .ok_or_else(|| anyhow!("Input transaction not found on chain"))?;
+    let prev_tx: ckb_types::packed::Transaction = match prev_tx.inner {
+        ckb_jsonrpc_types::Either::Left(tx_view) => tx_view.inner.into(),
+        ckb_jsonrpc_types::Either::Right(_) => {
+            return Err(anyhow!("Unexpected transaction format"));
+        }
+    };
+    Ok((prev_tx.into_view(), out_point.index().unpack()))
+}
+
+/// The Spillman Lock cell spent by the commitment (input 0), looked up on chain
+pub fn spillman_input_cell(rpc_client: &CkbRpcClient, tx: &TransactionView) -> Result<CellOutput> {
+    let (funding_tx, index) = spillman_funding_tx(rpc_client, tx)?;
+    funding_tx
+        .outputs()
+        .get(index as usize)
+        .ok_or_else(|| anyhow!("Input cell not found at output index {}", index))
+}
+
+/// Fee the channel pays for the commitment: the Spillman cell minus its outputs
+fn commitment_fee(tx: &TransactionView, spillman_cell: &CellOutput) -> Result<u64> {
+    let input_capacity: u64 = spillman_cell.capacity().unpack();
+    let output_capacity: u64 = tx
+        .outputs()
+        .into_iter()
+        .map(|output| Unpack::<u64>::unpack(&output.capacity()))
+        .sum();
+    input_capacity
+        .checked_sub(output_capacity)
+        .ok_or_else(|| anyhow!("Outputs exceed inputs"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tx_builder::signing::{
+        spillman_commitment_signing_message, spillman_signing_message,
+        ARGS_VERSION_DETACHED_CELL_DEPS,
+    };
+    use crate::utils::storage::{save_commitment_record, CommitmentRecord};
+    use ckb_crypto::secp::Generator;
+    use ckb_types::packed::Transaction;
+
+    fn test_commitment(spillman_tx_hash: [u8; 32], merchant_capacity: u64) -> TransactionView {
+        Transaction::default()
+            .as_advanced_builder()
+            .input(
+                CellInput::new_builder()
+                    .previous_output(OutPoint::new(H256(spillman_tx_hash).pack(), 0))
+                    .build(),
+            )
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(500 * 100_000_000))
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(merchant_capacity))
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build()
+    }
+
+    fn write_commitment(dir: &Path, name: &str, tx: &TransactionView) -> String {
+        let path = dir.join(name);
+        let tx_json = ckb_jsonrpc_types::TransactionView::from(tx.clone());
+        fs::write(&path, serde_json::to_string_pretty(&tx_json).unwrap()).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_stale_commitment_is_rejected() {
+        let dir = std::env::temp_dir().join(format!("spillman_settle_test_{}", std::process::id()));
+        let channel_dir = dir.join("channel");
+        // Commitments saved elsewhere (e.g. with --save-unsigned) are found through the ledger
+        let saved_dir = dir.join("elsewhere");
+        fs::create_dir_all(&channel_dir).unwrap();
+        fs::create_dir_all(&saved_dir).unwrap();
+        let channel_file = channel_dir.join("channel_info.json");
+        let channel_file = channel_file.to_str().unwrap();
+
+        let old_tx = test_commitment([0x01; 32], 161 * 100_000_000);
+        let new_tx = test_commitment([0x01; 32], 261 * 100_000_000);
+        // A larger commitment from another channel must not count
+        let other_tx = test_commitment([0x02; 32], 961 * 100_000_000);
+        for (sequence, (name, tx)) in [("old.json", &old_tx), ("new.json", &new_tx)]
+            .into_iter()
+            .chain([("other.json", &other_tx)])
+            .enumerate()
+        {
+            let tx_file = write_commitment(&saved_dir, name, tx);
+            let record =
+                CommitmentRecord::from_commitment_tx(sequence as u64 + 1, 0, &tx_file, 0, tx)
+                    .unwrap();
+            save_commitment_record(&channel_dir, &record).unwrap();
+        }
+
+        let stale = check_latest_commitment(channel_file, &old_tx, false);
+        let allowed = check_latest_commitment(channel_file, &old_tx, true);
+        let latest = check_latest_commitment(channel_file, &new_tx, false);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let err = stale.unwrap_err().to_string();
+        assert!(err.contains("Stale commitment"), "{}", err);
+        assert!(err.contains("new.json"), "{}", err);
+        assert!(allowed.is_ok());
+        assert!(latest.is_ok());
+    }
+
+    #[test]
+    fn test_merchant_amount_sums_merchant_outputs() {
+        let merchant_lock = PackedScript::new_builder()
+            .args(Bytes::from(vec![0x22; 20]).pack())
+            .build();
+        let single = test_commitment([0x01; 32], 161 * 100_000_000);
+        let split = single
+            .as_advanced_builder()
+            .set_outputs(vec![
+                single.outputs().get(0).unwrap(),
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(100 * 100_000_000))
+                    .lock(merchant_lock.clone())
+                    .build(),
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(90 * 100_000_000))
+                    .lock(merchant_lock)
+                    .build(),
+            ])
+            .set_outputs_data(vec![Bytes::new().pack(); 3])
+            .build();
+
+        assert_eq!(merchant_amount(&single).unwrap(), 161 * 100_000_000);
+        assert_eq!(merchant_amount(&split).unwrap(), 190 * 100_000_000);
+    }
+
+    #[test]
+    fn test_settlement_fee_keeps_commitment_signature() {
+        let lock = |arg_byte: u8| {
+            PackedScript::new_builder()
+                .code_hash(ckb_sdk::constants::SIGHASH_TYPE_HASH.pack())
+                .hash_type(ckb_types::core::ScriptHashType::Type)
+                .args(Bytes::from(vec![arg_byte; 20]).pack())
+                .build()
+        };
+        let commitment = test_commitment([0x01; 32], 161 * 100_000_000);
+        let user_key = Generator::random_privkey();
+        let message = spillman_signing_message(&commitment);
+        let user_sig = user_key.sign_recoverable(&message.into()).unwrap();
+
+        let fee_cell = LiveCell {
+            output: CellOutput::new_builder()
+                .capacity(Capacity::shannons(200 * 100_000_000))
+                .lock(lock(0x22))
+                .build(),
+            output_data: Bytes::new(),
+            out_point: OutPoint::new(H256([0x33; 32]).pack(), 1),
+            block_number: 0,
+            tx_index: 0,
+        };
+        let fee = SettlementFee {
+            cells: vec![fee_cell],
+            change_lock: lock(0x22),
+            witness_placeholder: WitnessArgs::new_builder()
+                .lock(Some(Bytes::from(vec![0u8; SIGNATURE_SIZE])).pack())
+                .build(),
+            cell_dep: Some(CellDep::new_builder().build()),
+        };
+        // 1 CKB/KB is far above what the commitment's own fee covers
+        let fee_rate = 100_000_000;
+        let settled = attach_settlement_fee(&commitment, &fee, 1_000, fee_rate).unwrap();
+
+        // The channel input and outputs are untouched, the fee input and change follow
+        assert_eq!(settled.inputs().get(0), commitment.inputs().get(0));
+        assert_eq!(settled.inputs().len(), 2);
+        assert_eq!(settled.outputs().get(0), commitment.outputs().get(0));
+        assert_eq!(settled.outputs().get(1), commitment.outputs().get(1));
+        assert_eq!(settled.cell_deps().len(), 1);
+        let change = settled.outputs().get(2).unwrap();
+        assert_eq!(change.lock(), lock(0x22));
+        let tx_size = settled.data().as_reader().serialized_size_in_block() as u64;
+        let extra_fee = FeeCalculator::new(fee_rate).fee(tx_size) - 1_000;
+        let change_capacity: u64 = change.capacity().unpack();
+        assert_eq!(change_capacity, 200 * 100_000_000 - extra_fee);
+
+        // The user's signature still verifies against the settled transaction
+        let settled_message = spillman_commitment_signing_message(
+            &settled,
+            ARGS_VERSION_DETACHED_CELL_DEPS,
+            commitment.outputs().len(),
+        )
+        .unwrap();
+        assert_ne!(spillman_signing_message(&settled), message);
+        assert_eq!(
+            user_sig.recover(&settled_message.into()).unwrap(),
+            user_key.pubkey().unwrap()
+        );
+
+        // Fee inputs that cannot leave a change cell are refused
+        let err = attach_settlement_fee(&commitment, &fee, 0, 1_000 * fee_rate).unwrap_err();
+        assert!(err.to_string().contains("Fee inputs hold"));
+    }
 }
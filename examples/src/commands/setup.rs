@@ -1,33 +1,34 @@
 use anyhow::{anyhow, Result};
-use ckb_sdk::Address;
-use serde::{Deserialize, Serialize};
+use ckb_sdk::{
+    constants::ONE_CKB,
+    traits::{CellCollector, CellQueryOptions, DefaultCellCollector, ValueRangeOption},
+    Address, HumanCapacity,
+};
+use ckb_types::packed::Script;
+use serde::Deserialize;
 use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+use crate::error::ChannelError;
+use crate::tx_builder::capacity_utils::{merchant_min_capacity, ChangePolicy, XUDT_DATA_SIZE};
 use crate::tx_builder::funding::{build_cofund_funding_transaction, build_funding_transaction};
 use crate::tx_builder::funding_v2;
 use crate::tx_builder::spillman_lock::build_spillman_lock_script_with_hash;
-use crate::utils::config::load_config;
-use crate::utils::crypto::parse_privkey;
-
-#[derive(Debug, Serialize, Deserialize)]
-struct ChannelInfo {
-    user_address: String,
-    merchant_address: String,
-    capacity_ckb: u64,
-    timeout_epochs: u64, // Deprecated, keeping for backwards compatibility
-    current_timestamp: u64,
-    timeout_timestamp: u64,
-    spillman_lock_script_hash: String,
-    funding_tx_hash: String,
-    funding_output_index: u32,
-    // xUDT fields (optional, only present in xUDT channels)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    xudt_type_script: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    xudt_amount: Option<String>, // Store as string to avoid u128 parsing issues
-}
-
+use crate::utils::chain::ConfirmationWait;
+use crate::utils::channel_info::{
+    encode_xudt_type_script, write_channel_info, ChannelInfo, InfoFormat,
+};
+use crate::utils::ckb_cli::ckb_cli_tx_file;
+use crate::utils::config::{load_config, Config};
+use crate::utils::crypto::{parse_privkey, TIMEOUT_GRACE_SECONDS};
+use crate::utils::log::log_event;
+use crate::utils::rpc;
+use crate::utils::storage::FUNDING_TX_FILE;
+use serde_json::json;
+
+#[allow(clippy::too_many_arguments)]
 pub async fn execute(
     config_path: &str,
     output_dir: &str,
@@ -35,14 +36,18 @@ pub async fn execute(
     capacity: Option<u64>,
     timeout_timestamp: Option<u64>,
     fee_rate: u64,
+    change_policy: ChangePolicy,
     co_fund: bool,
+    bind_cell_deps: bool,
+    info_format: InfoFormat,
 ) -> Result<()> {
     println!("🚀 执行 set-up 命令 - 准备 Spillman Channel");
     println!("==========================================\n");
 
     // 1. Load configuration
     println!("📋 加载配置文件: {}", config_path);
-    let config = load_config(config_path)?;
+    let mut config = load_config(config_path)?;
+    config.channel.bind_cell_deps |= bind_cell_deps;
     println!("✓ 配置加载成功");
 
     // Use values from config file, allow CLI to override
@@ -55,10 +60,9 @@ pub async fn execute(
 
     // Parse user (must be single-sig for now)
     let user_privkey = parse_privkey(
-        config
+        &config
             .user
-            .private_key
-            .as_ref()
+            .resolve_private_key()?
             .expect("User private_key is required"),
     )?;
     let user_pubkey = user_privkey.pubkey()?;
@@ -96,10 +100,9 @@ pub async fn execute(
         // Single-sig: use pubkey_hash(merchant_pubkey)
         println!("✓ 商户模式: 单签");
         let merchant_privkey = parse_privkey(
-            config
+            &config
                 .merchant
-                .private_key
-                .as_ref()
+                .resolve_private_key()?
                 .expect("Merchant private_key is required"),
         )?;
         let merchant_pubkey = merchant_privkey.pubkey()?;
@@ -140,24 +143,11 @@ pub async fn execute(
             .unwrap_or_else(|| "Invalid".to_string())
     );
 
-    // Validate timeout timestamp must be at least 20 minutes (1200 seconds) in the future
-    let min_timeout = current_timestamp + 1200; // 20 minutes = 1200 seconds
-    if timeout_timestamp < min_timeout {
-        return Err(anyhow!(
-            "超时时间戳必须大于当前时间至少 20 分钟！\n\
-             当前时间戳: {}\n\
-             最小超时时间戳: {} (当前时间 + 20 分钟)\n\
-             您设置的超时时间戳: {}",
-            current_timestamp,
-            min_timeout,
-            timeout_timestamp
-        ));
-    }
-    println!(
-        "✓ 超时时间验证通过 (距离当前时间 {} 秒 ≈ {} 分钟)",
-        timeout_timestamp - current_timestamp,
-        (timeout_timestamp - current_timestamp) / 60
-    );
+    validate_timeout(
+        current_timestamp,
+        timeout_timestamp,
+        config.channel.min_timeout_seconds,
+    )?;
 
     // 4. Build Spillman Lock script
     println!("\n🔒 构建 Spillman Lock script...");
@@ -183,7 +173,7 @@ pub async fn execute(
     let secrets_dir = output_path.join("secrets");
     fs::create_dir_all(&secrets_dir)?;
 
-    let funding_tx_path = secrets_dir.join("funding_tx_signed.json");
+    let funding_tx_path = secrets_dir.join(FUNDING_TX_FILE);
     let funding_info_path = funding_tx_path
         .to_str()
         .ok_or_else(|| anyhow!("invalid output path"))?;
@@ -204,16 +194,19 @@ pub async fn execute(
             capacity,
             &spillman_lock_script,
             fee_rate,
+            change_policy,
             funding_info_path,
         )
         .await?
     } else {
         // User-only funding mode
+        let capacity_human = HumanCapacity::from_str(&capacity.to_string())
+            .map_err(|e| anyhow!("Failed to parse capacity: {}", e))?;
         build_funding_transaction(
             &config,
             &user_addr_parsed,
             &spillman_lock_script,
-            capacity,
+            capacity_human,
             funding_info_path,
         )
         .await?
@@ -233,15 +226,25 @@ pub async fn execute(
         spillman_lock_script_hash: format!("{:#x}", script_hash),
         funding_tx_hash: format!("{:#x}", funding_tx_hash),
         funding_output_index,
-        xudt_type_script: None, // TODO: Will be filled in xUDT mode
-        xudt_amount: None,      // TODO: Will be filled in xUDT mode
+        xudt_type_script: None, // v1 funding does not support xUDT channels
+        xudt_amount: None,
+        refund_available_at: Some(timeout_timestamp + TIMEOUT_GRACE_SECONDS),
+        funding_created_at: Some(current_timestamp),
+        co_funded: Some(co_fund),
+        args_version: Some(config.channel.args_version()),
     };
 
-    let channel_info_json = serde_json::to_string_pretty(&channel_info)?;
-    let channel_info_path = secrets_dir.join("channel_info.json");
-
-    fs::write(&channel_info_path, channel_info_json)?;
+    let channel_info_path = write_channel_info(&channel_info, &secrets_dir, info_format)?;
     println!("✓ 通道信息已保存到: {}", channel_info_path.display());
+    log_event(
+        "setup",
+        "channel_info_saved",
+        json!({
+            "funding_tx_hash": channel_info.funding_tx_hash,
+            "funding_output_index": channel_info.funding_output_index,
+            "channel_file": channel_info_path.display().to_string(),
+        }),
+    );
 
     // 7. Build refund transaction template
     println!("\n📝 构建 Refund Transaction 模板...");
@@ -255,7 +258,10 @@ pub async fn execute(
     println!("   - 已签名交易: {}", funding_tx_path.display());
     println!("   - 通道信息: {}", channel_info_path.display());
     println!("\n2️⃣  广播 funding transaction:");
-    println!("   ckb-cli tx send --tx-file {}", funding_tx_path.display());
+    println!(
+        "   ckb-cli tx send --tx-file {}",
+        ckb_cli_tx_file(&funding_tx_path)?.display()
+    );
     println!("\n3️⃣  交易上链后即可开始使用:");
     println!("   spillman-cli pay --amount <CKB数量>");
 
@@ -266,6 +272,7 @@ pub async fn execute(
 ///
 /// This is the v2 implementation using the refactored funding_v2 module.
 /// The original execute() function above is kept as execute_v1 backup.
+#[allow(clippy::too_many_arguments)]
 pub async fn execute_v2(
     config_path: &str,
     output_dir: &str,
@@ -273,18 +280,71 @@ pub async fn execute_v2(
     capacity: Option<u64>,
     timeout_timestamp: Option<u64>,
     fee_rate: u64,
+    change_policy: ChangePolicy,
     co_fund: bool,
+    bind_cell_deps: bool,
     broadcast: bool,
+    raw: bool,
+    wait: Option<ConfirmationWait>,
     xudt_amount: Option<u128>,
+    info_format: InfoFormat,
+    save_unsigned: Option<&str>,
 ) -> Result<()> {
     println!("🚀 执行 set-up 命令 - 准备 Spillman Channel (v2)");
     println!("==========================================\n");
 
     // 1. Load configuration
     println!("📋 加载配置文件: {}", config_path);
-    let config = load_config(config_path)?;
+    let mut config = load_config(config_path)?;
+    config.channel.bind_cell_deps |= bind_cell_deps;
     println!("✓ 配置加载成功");
 
+    let secrets_dir = std::path::Path::new(output_dir).join("secrets");
+    setup_channel_v2(
+        &config,
+        &secrets_dir,
+        merchant_address,
+        capacity,
+        timeout_timestamp,
+        fee_rate,
+        change_policy,
+        co_fund,
+        broadcast,
+        raw,
+        wait,
+        xudt_amount,
+        info_format,
+        save_unsigned.map(Path::new),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Set up one channel from an already loaded config, writing its files to `secrets_dir`
+///
+/// With `save_unsigned` the funding transaction is saved there unsigned instead of to
+/// `secrets_dir`; the channel info is still written, the tx hash does not cover witnesses.
+/// With `raw` the `send_transaction` request is printed instead of broadcasting.
+///
+/// Returns the path of the saved channel info.
+#[allow(clippy::too_many_arguments)]
+async fn setup_channel_v2(
+    config: &Config,
+    secrets_dir: &Path,
+    merchant_address: Option<&str>,
+    capacity: Option<u64>,
+    timeout_timestamp: Option<u64>,
+    fee_rate: u64,
+    change_policy: ChangePolicy,
+    co_fund: bool,
+    broadcast: bool,
+    raw: bool,
+    wait: Option<ConfirmationWait>,
+    xudt_amount: Option<u128>,
+    info_format: InfoFormat,
+    save_unsigned: Option<&Path>,
+) -> Result<PathBuf> {
     // Use values from config file, allow CLI to override
     let user_address = &config.user.address;
     let capacity = capacity.unwrap_or(config.channel.capacity_ckb);
@@ -295,10 +355,9 @@ pub async fn execute_v2(
 
     // Parse user (must be single-sig for now)
     let user_privkey = parse_privkey(
-        config
+        &config
             .user
-            .private_key
-            .as_ref()
+            .resolve_private_key()?
             .expect("User private_key is required"),
     )?;
     let user_pubkey = user_privkey.pubkey()?;
@@ -336,10 +395,9 @@ pub async fn execute_v2(
         // Single-sig: use pubkey_hash(merchant_pubkey)
         println!("✓ 商户模式: 单签");
         let merchant_privkey = parse_privkey(
-            config
+            &config
                 .merchant
-                .private_key
-                .as_ref()
+                .resolve_private_key()?
                 .expect("Merchant private_key is required"),
         )?;
         let merchant_pubkey = merchant_privkey.pubkey()?;
@@ -380,28 +438,16 @@ pub async fn execute_v2(
             .unwrap_or_else(|| "Invalid".to_string())
     );
 
-    // Validate timeout timestamp must be at least 20 minutes (1200 seconds) in the future
-    // let min_timeout = current_timestamp + 1200; // 20 minutes = 1200 seconds
-    // if timeout_timestamp < min_timeout {
-    //     return Err(anyhow!(
-    //         "超时时间戳必须大于当前时间至少 20 分钟！\n\
-    //          当前时间戳: {}\n\
-    //          最小超时时间戳: {} (当前时间 + 20 分钟)\n\
-    //          您设置的超时时间戳: {}",
-    //         current_timestamp,
-    //         min_timeout,
-    //         timeout_timestamp
-    //     ));
-    // }
-    // println!("✓ 超时时间验证通过 (距离当前时间 {} 秒 ≈ {} 分钟)",
-    //     timeout_timestamp - current_timestamp,
-    //     (timeout_timestamp - current_timestamp) / 60
-    // );
+    validate_timeout(
+        current_timestamp,
+        timeout_timestamp,
+        config.channel.min_timeout_seconds,
+    )?;
 
     // 4. Build Spillman Lock script
     println!("\n🔒 构建 Spillman Lock script...");
     let spillman_lock_script = build_spillman_lock_script_with_hash(
-        &config,
+        config,
         &user_pubkey,
         &merchant_pubkey_hash,
         timeout_timestamp,
@@ -418,11 +464,12 @@ pub async fn execute_v2(
     println!("\n📝 构建并签名 Funding Transaction (v2)...");
 
     // Create output directory structure
-    let output_path = std::path::Path::new(output_dir);
-    let secrets_dir = output_path.join("secrets");
-    fs::create_dir_all(&secrets_dir)?;
+    fs::create_dir_all(secrets_dir)?;
 
-    let funding_tx_path = secrets_dir.join("funding_tx_signed.json");
+    let funding_tx_path = match save_unsigned {
+        Some(path) => path.to_path_buf(),
+        None => secrets_dir.join(FUNDING_TX_FILE),
+    };
     let funding_info_path = funding_tx_path
         .to_str()
         .ok_or_else(|| anyhow!("invalid output path"))?;
@@ -431,7 +478,6 @@ pub async fn execute_v2(
         Address::from_str(user_address).map_err(|e| anyhow!("invalid user address: {}", e))?;
 
     // Convert capacity from CKB to HumanCapacity
-    use ckb_sdk::HumanCapacity;
     let capacity_human = HumanCapacity::from_str(&capacity.to_string())
         .map_err(|e| anyhow!("Failed to parse capacity: {}", e))?;
 
@@ -468,12 +514,13 @@ pub async fn execute_v2(
         };
 
         funding_v2::build_cofund_funding_transaction(
-            &config,
+            config,
             &user_addr_parsed,
             &merchant_addr_parsed,
             capacity_human,
             &spillman_lock_script,
             fee_rate,
+            change_policy,
             funding_info_path,
             user_xudt_amount,
             merchant_xudt_amount,
@@ -482,13 +529,15 @@ pub async fn execute_v2(
     } else {
         // User-only funding mode
         funding_v2::build_funding_transaction(
-            &config,
+            config,
             &user_addr_parsed,
             &spillman_lock_script,
             capacity_human,
             fee_rate,
+            change_policy,
             funding_info_path,
             xudt_amount_smallest_unit,
+            save_unsigned.is_some(),
         )
         .await?
     };
@@ -496,40 +545,21 @@ pub async fn execute_v2(
     // 6. Save channel info with actual funding tx info
     println!("\n💾 保存通道信息...");
 
-    // Build xUDT type script hash if xUDT channel
-    let xudt_type_script_str = if xudt_amount.is_some() {
-        if let Some(ref usdi_config) = config.usdi {
-            use ckb_types::core::ScriptHashType;
-            use ckb_types::prelude::*;
-            use std::str::FromStr;
-
-            let code_hash =
-                ckb_types::H256::from_str(usdi_config.code_hash.trim_start_matches("0x"))
-                    .map_err(|e| anyhow!("Invalid code_hash: {}", e))?;
-            let args = ckb_types::bytes::Bytes::from(
-                hex::decode(usdi_config.args.trim_start_matches("0x"))
-                    .map_err(|e| anyhow!("Invalid args hex: {}", e))?,
-            );
-
-            let hash_type = match usdi_config.hash_type.as_str() {
-                "type" => ScriptHashType::Type,
-                "data" => ScriptHashType::Data,
-                "data1" => ScriptHashType::Data1,
-                _ => return Err(anyhow!("Invalid hash_type: {}", usdi_config.hash_type)),
-            };
-
-            let type_script = ckb_types::packed::Script::new_builder()
-                .code_hash(code_hash.pack())
-                .hash_type(ckb_types::packed::Byte::new(hash_type as u8))
-                .args(args.pack())
-                .build();
-
-            Some(format!("{:#x}", type_script.calc_script_hash()))
-        } else {
-            return Err(anyhow!("xUDT amount provided but usdi config not found"));
-        }
+    // Read xUDT type script and amount back from the funding cell, so ChannelInfo
+    // always describes exactly what was locked on chain
+    let (xudt_type_script_str, xudt_amount_str) = if xudt_amount.is_some() {
+        let (type_script, amount) = read_funding_cell_xudt(&funding_tx_path, funding_output_index)?;
+        println!(
+            "✓ xUDT type script hash: {:#x}",
+            type_script.calc_script_hash()
+        );
+        println!("✓ xUDT amount (smallest unit): {}", amount);
+        (
+            Some(encode_xudt_type_script(&type_script)),
+            Some(amount.to_string()),
+        )
     } else {
-        None
+        (None, None)
     };
 
     let channel_info = ChannelInfo {
@@ -545,16 +575,32 @@ pub async fn execute_v2(
         funding_tx_hash: format!("{:#x}", funding_tx_hash),
         funding_output_index,
         xudt_type_script: xudt_type_script_str,
-        xudt_amount: xudt_amount.map(|amt| amt.to_string()),
+        xudt_amount: xudt_amount_str,
+        refund_available_at: Some(timeout_timestamp + TIMEOUT_GRACE_SECONDS),
+        funding_created_at: Some(current_timestamp),
+        co_funded: Some(co_fund),
+        args_version: Some(config.channel.args_version()),
     };
 
-    let channel_info_json = serde_json::to_string_pretty(&channel_info)?;
-    let channel_info_path = secrets_dir.join("channel_info.json");
-
-    fs::write(&channel_info_path, channel_info_json)?;
+    let channel_info_path = write_channel_info(&channel_info, secrets_dir, info_format)?;
     println!("✓ 通道信息已保存到: {}", channel_info_path.display());
+    log_event(
+        "setup",
+        "channel_info_saved",
+        json!({
+            "funding_tx_hash": channel_info.funding_tx_hash,
+            "funding_output_index": channel_info.funding_output_index,
+            "channel_file": channel_info_path.display().to_string(),
+        }),
+    );
 
-    // 7. Broadcast funding transaction (optional)
+    // 7. Broadcast funding transaction (optional), or hand the request to another tool
+    if raw {
+        let funding_tx_json: ckb_jsonrpc_types::TransactionView =
+            serde_json::from_str(&fs::read_to_string(&funding_tx_path)?)?;
+        rpc::print_send_transaction_request(&funding_tx_json.inner);
+        return Ok(channel_info_path);
+    }
     if broadcast {
         println!("\n📡 广播 Funding Transaction 到链上...");
 
@@ -564,13 +610,29 @@ pub async fn execute_v2(
             serde_json::from_str(&funding_tx_json_str)?;
 
         // Send transaction via RPC (use inner Transaction without hash)
-        let rpc_client = ckb_sdk::rpc::CkbRpcClient::new(&config.network.rpc_url);
+        let rpc_client = rpc::client(&config.network.rpc_url);
         let broadcast_tx_hash = rpc_client
             .send_transaction(funding_tx_json.inner, None)
             .map_err(|e| anyhow!("Failed to broadcast transaction: {:?}", e))?;
 
         println!("✓ Funding Transaction 已广播");
         println!("  - TX Hash: {:#x}", broadcast_tx_hash);
+        log_event(
+            "funding",
+            "broadcast",
+            json!({ "tx_hash": format!("{:#x}", broadcast_tx_hash) }),
+        );
+        if let Some(wait) = wait {
+            let confirmations = wait.wait(&rpc_client, &broadcast_tx_hash).await?;
+            log_event(
+                "funding",
+                "confirmed",
+                json!({
+                    "tx_hash": format!("{:#x}", broadcast_tx_hash),
+                    "confirmations": confirmations,
+                }),
+            );
+        }
 
         // 8. Build refund transaction template
         println!("\n📝 构建 Refund Transaction 模板...");
@@ -585,8 +647,10 @@ pub async fn execute_v2(
             "   ckb-cli rpc get_transaction --hash {:#x}",
             broadcast_tx_hash
         );
-        println!("\n⏳ 等待交易上链确认...");
-        println!("   交易确认后即可开始支付");
+        if wait.is_none() {
+            println!("\n⏳ 等待交易上链确认...");
+            println!("   交易确认后即可开始支付");
+        }
         println!("\n💸 创建支付:");
         println!(
             "   spillman-cli pay --amount <CKB数量> --channel-file {}",
@@ -602,12 +666,538 @@ pub async fn execute_v2(
         println!("\n📌 下一步操作:");
         println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
         println!("\n📄 生成的文件:");
-        println!("   - 已签名交易: {}", funding_tx_path.display());
+        if save_unsigned.is_some() {
+            println!("   - 未签名交易: {}", funding_tx_path.display());
+            println!("     witness 为占位符（大小与最终签名一致），请用外部工具签名");
+        } else {
+            println!("   - 已签名交易: {}", funding_tx_path.display());
+        }
         println!("   - 通道信息: {}", channel_info_path.display());
         println!("\n📡 手动广播交易:");
         println!("   spillman-cli set-up --use-v2 --broadcast ... (重新运行带 --broadcast)");
         println!("   或者使用其他工具手动发送交易");
     }
 
+    Ok(channel_info_path)
+}
+
+/// One channel of a `set-up --batch` file
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchChannel {
+    pub user_address: String,
+    /// Funding key for this user; defaults to `[user] private_key` in the config
+    #[serde(default)]
+    pub user_private_key: Option<String>,
+    pub capacity_ckb: u64,
+    pub timeout_timestamp: u64,
+}
+
+/// Channels opened by one `set-up --batch` run, sharing the merchant/network config
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchFile {
+    pub channels: Vec<BatchChannel>,
+}
+
+impl BatchChannel {
+    /// The shared config with this channel's user, capacity and timeout applied
+    fn apply(&self, config: &Config) -> Config {
+        let mut config = config.clone();
+        config.user.address = self.user_address.clone();
+        if let Some(key) = &self.user_private_key {
+            config.user.private_key = Some(key.clone());
+            config.user.private_key_env = None;
+            config.user.private_key_file = None;
+        }
+        config.channel.capacity_ckb = self.capacity_ckb;
+        config.channel.timeout_timestamp = self.timeout_timestamp;
+        config
+    }
+}
+
+/// Load a batch file (TOML, one `[[channels]]` table per channel)
+pub fn load_batch_file(path: &str) -> Result<BatchFile> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read batch file {}: {}", path, e))?;
+    let batch: BatchFile = toml::from_str(&content)
+        .map_err(|e| anyhow!("Failed to parse batch file {}: {}", path, e))?;
+    if batch.channels.is_empty() {
+        return Err(anyhow!("Batch file {} has no [[channels]] entries", path));
+    }
+    Ok(batch)
+}
+
+/// Execute set-up for every channel of a batch file (v2 funding, one funding tx per channel)
+///
+/// Channel `i` writes its files to `<output_dir>/secrets/<i>/`.
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_batch(
+    config_path: &str,
+    batch_path: &str,
+    output_dir: &str,
+    merchant_address: Option<&str>,
+    fee_rate: u64,
+    change_policy: ChangePolicy,
+    co_fund: bool,
+    bind_cell_deps: bool,
+    broadcast: bool,
+    wait: Option<ConfirmationWait>,
+    xudt_amount: Option<u128>,
+    info_format: InfoFormat,
+) -> Result<()> {
+    println!("🚀 执行 set-up 命令 - 批量创建 Spillman Channel (v2)");
+    println!("==========================================\n");
+
+    println!("📋 加载配置文件: {}", config_path);
+    let mut config = load_config(config_path)?;
+    config.channel.bind_cell_deps |= bind_cell_deps;
+    println!("✓ 配置加载成功");
+
+    println!("📋 加载批量文件: {}", batch_path);
+    let batch = load_batch_file(batch_path)?;
+    println!("✓ 共 {} 个通道", batch.channels.len());
+
+    let secrets_root = Path::new(output_dir).join("secrets");
+    let channel_files = run_batch(&config, &batch, &secrets_root, |config, dir| async move {
+        setup_channel_v2(
+            &config,
+            &dir,
+            merchant_address,
+            None,
+            None,
+            fee_rate,
+            change_policy,
+            co_fund,
+            broadcast,
+            false,
+            wait,
+            xudt_amount,
+            info_format,
+            None,
+        )
+        .await
+    })
+    .await?;
+
+    println!("\n✅ 批量创建完成");
+    for (index, path) in channel_files.iter().enumerate() {
+        println!("  - 通道 {}: {}", index, path.display());
+    }
+
     Ok(())
 }
+
+/// Run `setup` for each batch channel with its own config and secrets dir, in order
+///
+/// Stops at the first failing channel; the channels before it keep their files.
+async fn run_batch<F, Fut>(
+    config: &Config,
+    batch: &BatchFile,
+    secrets_root: &Path,
+    mut setup: F,
+) -> Result<Vec<PathBuf>>
+where
+    F: FnMut(Config, PathBuf) -> Fut,
+    Fut: Future<Output = Result<PathBuf>>,
+{
+    let mut channel_files = Vec::with_capacity(batch.channels.len());
+    for (index, channel) in batch.channels.iter().enumerate() {
+        println!(
+            "\n━━━━━━━━━━ 通道 {} / {}: {} ━━━━━━━━━━\n",
+            index,
+            batch.channels.len(),
+            channel.user_address
+        );
+        let channel_config = channel.apply(config);
+        channel_config.validate()?;
+        let path = setup(channel_config, secrets_root.join(index.to_string()))
+            .await
+            .map_err(|e| anyhow!("Channel {} ({}) failed: {}", index, channel.user_address, e))?;
+        channel_files.push(path);
+    }
+    Ok(channel_files)
+}
+
+/// Read the xUDT type script and amount of the funding cell from a saved funding transaction
+fn read_funding_cell_xudt(
+    funding_tx_path: &std::path::Path,
+    funding_output_index: u32,
+) -> Result<(ckb_types::packed::Script, u128)> {
+    use ckb_types::prelude::*;
+
+    let funding_tx_json_str = fs::read_to_string(funding_tx_path)?;
+    let funding_tx_json: ckb_jsonrpc_types::TransactionView =
+        serde_json::from_str(&funding_tx_json_str)?;
+    let funding_tx_packed: ckb_types::packed::Transaction = funding_tx_json.inner.into();
+    let funding_tx = funding_tx_packed.into_view();
+
+    let funding_cell = funding_tx
+        .outputs()
+        .get(funding_output_index as usize)
+        .ok_or_else(|| anyhow!("Funding cell not found at index {}", funding_output_index))?;
+    let type_script = funding_cell
+        .type_()
+        .to_opt()
+        .ok_or_else(|| anyhow!("xUDT channel funding cell has no type script"))?;
+
+    let data: Vec<u8> = funding_tx
+        .outputs_data()
+        .get(funding_output_index as usize)
+        .ok_or_else(|| anyhow!("Funding cell data not found"))?
+        .unpack();
+    if data.len() < 16 {
+        return Err(anyhow!("Invalid xUDT data length: {}", data.len()));
+    }
+    let amount = u128::from_le_bytes(data[0..16].try_into().unwrap());
+
+    Ok((type_script, amount))
+}
+
+/// Funding transaction size assumed by `estimate_required_capacity`: a few sighash
+/// inputs, the Spillman cell and a change cell
+const ESTIMATED_FUNDING_TX_SIZE: u64 = 600;
+
+/// Extra size of a co-funded transaction (the merchant's inputs and change)
+const ESTIMATED_COFUND_EXTRA_SIZE: u64 = 300;
+
+/// Extra size of an xUDT funding (type script, xUDT inputs and cell dep)
+const ESTIMATED_XUDT_EXTRA_SIZE: u64 = 300;
+
+/// Estimate the CKB (shannons) the user must hold before set-up
+///
+/// Channel capacity plus a fee estimate at `fee_rate`. Co-fund adds the 1 CKB buffer the
+/// user puts on top of the channel, xUDT channels add the xUDT change cell the user gets
+/// back. It is a guide for a pre-flight check, the collector picks the real inputs.
+pub fn estimate_required_capacity(
+    config: &Config,
+    capacity_ckb: u64,
+    fee_rate: u64,
+    co_fund: bool,
+    xudt: bool,
+) -> Result<u64> {
+    let mut tx_size = ESTIMATED_FUNDING_TX_SIZE;
+    let mut required = capacity_ckb
+        .checked_mul(ONE_CKB)
+        .ok_or_else(|| anyhow!("Capacity {} CKB overflows", capacity_ckb))?;
+
+    if co_fund {
+        tx_size += ESTIMATED_COFUND_EXTRA_SIZE;
+        required += ONE_CKB;
+    }
+    if xudt {
+        tx_size += ESTIMATED_XUDT_EXTRA_SIZE;
+        let xudt = config
+            .usdi
+            .as_ref()
+            .ok_or_else(|| anyhow!("xUDT amount specified but usdi config not found"))?
+            .validate()?;
+        let user_lock = Script::from(
+            &Address::from_str(&config.user.address)
+                .map_err(|e| anyhow!("invalid user address: {}", e))?,
+        );
+        required += merchant_min_capacity(&user_lock, Some(&xudt.type_script))?;
+    }
+
+    Ok(required + (tx_size * fee_rate).div_ceil(1000))
+}
+
+/// `set-up --check`: compare the user's live cells with the estimate, without building a tx
+///
+/// Fails with `ChannelError::InsufficientCapacity` / `InsufficientXudt` when funding
+/// would not succeed, so scripts can run it before the real set-up.
+pub async fn execute_check(
+    config_path: &str,
+    capacity: Option<u64>,
+    fee_rate: u64,
+    co_fund: bool,
+    xudt_amount: Option<u128>,
+) -> Result<()> {
+    println!("🔍 执行 set-up 预检 - 检查用户余额是否足够出资");
+    println!("==========================================\n");
+
+    let config = load_config(config_path)?;
+    let capacity = capacity.unwrap_or(config.channel.capacity_ckb);
+    let required =
+        estimate_required_capacity(&config, capacity, fee_rate, co_fund, xudt_amount.is_some())?;
+
+    let user_lock = Script::from(
+        &Address::from_str(&config.user.address)
+            .map_err(|e| anyhow!("invalid user address: {}", e))?,
+    );
+    let mut collector = DefaultCellCollector::new(&config.network.rpc_url);
+
+    // Plain CKB cells of the user
+    let mut query = CellQueryOptions::new_lock(user_lock.clone());
+    query.min_total_capacity = u64::MAX;
+    let (_, mut available) = collector.collect_live_cells_async(&query, false).await?;
+
+    // xUDT cells are spent by the funding too, their capacity counts
+    let xudt_check = if let Some(amount) = xudt_amount {
+        let xudt = config
+            .usdi
+            .as_ref()
+            .ok_or_else(|| anyhow!("xUDT amount specified but usdi config not found"))?
+            .validate()?;
+        let type_script = xudt.type_script;
+        let mut query = CellQueryOptions::new_lock(user_lock);
+        query.secondary_script = Some(type_script.clone());
+        query.data_len_range = Some(ValueRangeOption::new_min(XUDT_DATA_SIZE as u64));
+        query.min_total_capacity = u64::MAX;
+        let (cells, xudt_capacity) = collector.collect_live_cells_async(&query, false).await?;
+        available += xudt_capacity;
+
+        let held: u128 = cells
+            .iter()
+            .filter(|cell| cell.output.type_().to_opt().as_ref() == Some(&type_script))
+            .filter_map(|cell| cell.output_data.get(..XUDT_DATA_SIZE))
+            .map(|data| u128::from_le_bytes(data.try_into().unwrap()))
+            .sum();
+        Some((held, amount * 10u128.pow(xudt.decimal as u32)))
+    } else {
+        None
+    };
+
+    println!(
+        "📊 出资预检 ({}):",
+        if co_fund {
+            "Co-fund"
+        } else {
+            "User 单独出资"
+        }
+    );
+    println!("  - 通道容量: {} CKB", capacity);
+    println!("  - 预计所需: {}", HumanCapacity::from(required));
+    println!("  - 可用余额: {}", HumanCapacity::from(available));
+    if let Some((held, needed)) = xudt_check {
+        println!("  - xUDT 所需: {} / 持有: {}", needed, held);
+        if held < needed {
+            return Err(ChannelError::InsufficientXudt {
+                collected: held,
+                required: needed,
+            }
+            .into());
+        }
+    }
+    if available < required {
+        return Err(ChannelError::InsufficientCapacity {
+            required,
+            available,
+        }
+        .into());
+    }
+
+    println!("\n✅ 余额充足，可以执行 set-up");
+    Ok(())
+}
+
+/// Check that the timeout is at least `min_timeout_seconds` after the current timestamp
+fn validate_timeout(
+    current_timestamp: u64,
+    timeout_timestamp: u64,
+    min_timeout_seconds: u64,
+) -> Result<()> {
+    let min_timeout = current_timestamp.saturating_add(min_timeout_seconds);
+    if timeout_timestamp < min_timeout {
+        return Err(anyhow!(
+            "超时时间戳必须大于当前时间至少 {} 秒！\n\
+             当前时间戳: {}\n\
+             最小超时时间戳: {} (当前时间 + {} 秒)\n\
+             您设置的超时时间戳: {}",
+            min_timeout_seconds,
+            current_timestamp,
+            min_timeout,
+            min_timeout_seconds,
+            timeout_timestamp
+        ));
+    }
+    println!(
+        "✓ 超时时间验证通过 (距离当前时间 {} 秒 ≈ {} 分钟)",
+        timeout_timestamp - current_timestamp,
+        (timeout_timestamp - current_timestamp) / 60
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::channel_info::load_channel_info;
+    use crate::utils::config::DEFAULT_MIN_TIMEOUT_SECONDS;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn test_validate_timeout_accepts_minimum_lead_time() {
+        assert!(validate_timeout(
+            NOW,
+            NOW + DEFAULT_MIN_TIMEOUT_SECONDS,
+            DEFAULT_MIN_TIMEOUT_SECONDS
+        )
+        .is_ok());
+        assert!(validate_timeout(NOW, NOW + 60, 60).is_ok());
+    }
+
+    #[test]
+    fn test_validate_timeout_rejects_too_soon_timeout() {
+        let err = validate_timeout(NOW, NOW + 600, DEFAULT_MIN_TIMEOUT_SECONDS).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains(&format!("当前时间戳: {}", NOW)));
+        assert!(msg.contains(&format!(
+            "最小超时时间戳: {}",
+            NOW + DEFAULT_MIN_TIMEOUT_SECONDS
+        )));
+
+        // An already-expired timeout is rejected even with no minimum lead time
+        assert!(validate_timeout(NOW, NOW - 1, 0).is_err());
+    }
+
+    const BASE_CONFIG: &str = r#"
+[network]
+rpc_url = "http://127.0.0.1:8114"
+
+[user]
+private_key = "01"
+address = "ckt1-default-user"
+
+[merchant]
+private_key = "02"
+address = "ckt1-merchant"
+
+[channel]
+capacity_ckb = 1000
+timeout_timestamp = 1763367827
+tx_fee_shannon = 100000
+
+[spillman_lock]
+code_hash = "0x41fa54ee27a517db245b014116fe2baff1dcb639d42fc14be43c315ea3cef9f2"
+hash_type = "type"
+tx_hash = "0x3ad0f4b3f08927b79d8a94bbad5f265694e969ab2ddfde178893e1c6a954dd5f"
+index = 0
+
+[auth]
+tx_hash = "0x3f0fe5376b847b0c286184bb59d38765841e135d7d64f87b2bf7014c6316eee2"
+index = 0
+"#;
+
+    #[tokio::test]
+    async fn test_batch_setup_writes_one_channel_info_per_entry() {
+        let dir = std::env::temp_dir().join(format!("spillman_batch_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let batch_path = dir.join("batch.toml");
+        fs::write(
+            &batch_path,
+            r#"
+[[channels]]
+user_address = "ckt1-user-a"
+capacity_ckb = 500
+timeout_timestamp = 1800000000
+
+[[channels]]
+user_address = "ckt1-user-b"
+user_private_key = "03"
+capacity_ckb = 2000
+timeout_timestamp = 1900000000
+"#,
+        )
+        .unwrap();
+
+        let config: Config = toml::from_str(BASE_CONFIG).unwrap();
+        let batch = load_batch_file(batch_path.to_str().unwrap()).unwrap();
+        assert_eq!(batch.channels.len(), 2);
+
+        // Stand-in for the on-chain funding: save each channel's config as its channel info
+        let secrets_root = dir.join("secrets");
+        let mut configs = Vec::new();
+        let files = run_batch(&config, &batch, &secrets_root, |config, dir| {
+            configs.push(config.clone());
+            async move {
+                fs::create_dir_all(&dir)?;
+                let info = ChannelInfo {
+                    user_address: config.user.address,
+                    merchant_address: config.merchant.address,
+                    capacity_ckb: config.channel.capacity_ckb,
+                    timeout_epochs: 0,
+                    current_timestamp: 0,
+                    timeout_timestamp: config.channel.timeout_timestamp,
+                    spillman_lock_script_hash: String::new(),
+                    funding_tx_hash: String::new(),
+                    funding_output_index: 0,
+                    xudt_type_script: None,
+                    xudt_amount: None,
+                    refund_available_at: Some(config.channel.timeout_timestamp),
+                    funding_created_at: None,
+                    co_funded: None,
+                    args_version: None,
+                };
+                write_channel_info(&info, &dir, InfoFormat::Json)
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            files,
+            vec![
+                secrets_root.join("0").join("channel_info.json"),
+                secrets_root.join("1").join("channel_info.json"),
+            ]
+        );
+        let first = load_channel_info(files[0].to_str().unwrap()).unwrap();
+        assert_eq!(first.user_address, "ckt1-user-a");
+        assert_eq!(first.capacity_ckb, 500);
+        assert_eq!(first.merchant_address, "ckt1-merchant");
+        let second = load_channel_info(files[1].to_str().unwrap()).unwrap();
+        assert_eq!(second.user_address, "ckt1-user-b");
+        assert_eq!(second.timeout_timestamp, 1900000000);
+
+        // Entries without their own key fund with the config's user key
+        assert_eq!(configs[0].user.private_key.as_deref(), Some("01"));
+        assert_eq!(configs[1].user.private_key.as_deref(), Some("03"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_estimate_required_capacity() {
+        use crate::utils::config::XudtConfig;
+        use ckb_types::{core::ScriptHashType, prelude::*, H256};
+
+        let mut config: Config = toml::from_str(BASE_CONFIG).unwrap();
+        let user_lock = Script::new_builder()
+            .code_hash(ckb_sdk::constants::SIGHASH_TYPE_HASH.pack())
+            .hash_type(ScriptHashType::Type)
+            .args(ckb_types::bytes::Bytes::from(vec![0x33; 20]).pack())
+            .build();
+        config.user.address =
+            Address::new(ckb_sdk::NetworkType::Testnet, user_lock.into(), true).to_string();
+        config.usdi = Some(XudtConfig {
+            code_hash: format!("{:#x}", H256([0x50; 32])),
+            hash_type: "type".to_string(),
+            args: format!("0x{}", hex::encode([0xab; 32])),
+            tx_hash: format!("{:#x}", H256([0x51; 32])),
+            index: 0,
+            decimal: 6,
+        });
+
+        // Single fund: capacity + fee of a 600-byte tx at 1000 shannons/KB
+        let single = estimate_required_capacity(&config, 1000, 1000, false, false).unwrap();
+        assert_eq!(single, 1000 * ONE_CKB + 600);
+
+        // Co-fund: 1 CKB buffer on top and a larger tx
+        let co_fund = estimate_required_capacity(&config, 1000, 1000, true, false).unwrap();
+        assert_eq!(co_fund, 1001 * ONE_CKB + 900);
+
+        // xUDT: the user's xUDT change cell, 61 CKB sighash cell + 65-byte type + 16-byte data
+        let xudt = estimate_required_capacity(&config, 1000, 1000, false, true).unwrap();
+        assert_eq!(xudt, 1142 * ONE_CKB + 900);
+        let co_fund_xudt = estimate_required_capacity(&config, 1000, 1000, true, true).unwrap();
+        assert_eq!(co_fund_xudt, 1143 * ONE_CKB + 1200);
+
+        // The fee rounds up and scales with the rate
+        let fast = estimate_required_capacity(&config, 1000, 1501, false, false).unwrap();
+        assert_eq!(fast, 1000 * ONE_CKB + 901);
+
+        // xUDT needs the usdi config
+        config.usdi = None;
+        assert!(estimate_required_capacity(&config, 1000, 1000, false, true).is_err());
+    }
+}
@@ -1,4 +1,49 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use ckb_crypto::secp::{Privkey, Signature};
+use ckb_types::{core::TransactionView, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::{
+    tx_builder::{
+        commitment_v2::COMMITMENT_CHANNEL_OUTPUTS,
+        signing::{spillman_commitment_signing_message, spillman_signing_message_for_version},
+        witness_utils::{
+            assemble_spillman_witness, MerchantSig, EMPTY_WITNESS_ARGS, EMPTY_WITNESS_ARGS_SIZE,
+            SIGNATURE_SIZE, UNLOCK_TYPE_COMMITMENT, UNLOCK_TYPE_SIZE,
+        },
+    },
+    utils::{
+        config::load_config,
+        crypto::{parse_privkey, pubkey_hash, SpillmanArgs},
+        rpc,
+    },
+};
+
+/// Size of the multisig config header: S | R | M | N
+const MULTISIG_HEADER_SIZE: usize = 4;
+
+/// One merchant key's signature over a Spillman transaction, for offline aggregation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartialSignature {
+    /// Transaction hash the signature was made for
+    pub tx_hash: String,
+    /// Index of the signer's pubkey hash in the multisig config
+    pub index: u8,
+    /// blake160 of the signer's pubkey
+    pub pubkey_hash: String,
+    /// 65-byte recoverable signature over the Spillman signing message
+    pub signature: String,
+}
+
+/// Multisig merchant part of a Spillman witness
+struct MultisigWitness {
+    unlock_type: u8,
+    config: Vec<u8>,
+    threshold: usize,
+    pubkey_hashes: Vec<[u8; 20]>,
+    user_sig: [u8; SIGNATURE_SIZE],
+}
 
 pub async fn execute(tx_file: &str, privkey_path: &str, is_merchant: bool) -> Result<()> {
     println!("执行 sign-tx 命令...");
@@ -11,3 +56,352 @@ pub async fn execute(tx_file: &str, privkey_path: &str, is_merchant: bool) -> Re
 
     Ok(())
 }
+
+/// Execute `sign-tx --partial` - sign with one multisig merchant key only
+pub async fn execute_partial(
+    tx_file: &str,
+    privkey_path: &str,
+    out: &str,
+    config_path: &str,
+) -> Result<()> {
+    println!("✍️  多签部分签名");
+    println!("═══════════════════════════════════════════");
+
+    let tx = load_tx(tx_file)?;
+    let args_version = load_args_version(config_path, &tx)?;
+    let privkey_hex = fs::read_to_string(privkey_path)
+        .map_err(|e| anyhow!("Failed to read private key file {}: {}", privkey_path, e))?;
+    let privkey = parse_privkey(privkey_hex.trim())?;
+
+    let partial = sign_partial(&tx, args_version, &privkey)?;
+    fs::write(out, serde_json::to_string_pretty(&partial)?)?;
+
+    println!("✓ 交易: {}", partial.tx_hash);
+    println!("✓ 多签配置中的位置: {}", partial.index);
+    println!("✓ 部分签名已保存: {}", out);
+    println!("\n📌 收集足够的部分签名后执行:");
+    println!(
+        "  spillman-cli sign-tx --tx-file {} --aggregate --sigs <a.sig,b.sig>",
+        tx_file
+    );
+
+    Ok(())
+}
+
+/// Execute `sign-tx --aggregate` - assemble the multisig merchant part from partial signatures
+pub async fn execute_aggregate(
+    tx_file: &str,
+    sig_files: &[String],
+    out: Option<&str>,
+    config_path: &str,
+) -> Result<()> {
+    println!("🧩 聚合多签部分签名");
+    println!("═══════════════════════════════════════════");
+
+    let tx = load_tx(tx_file)?;
+    let args_version = load_args_version(config_path, &tx)?;
+    let partials = sig_files
+        .iter()
+        .map(|path| {
+            let json = fs::read_to_string(path)
+                .map_err(|e| anyhow!("Failed to read signature file {}: {}", path, e))?;
+            serde_json::from_str::<PartialSignature>(&json)
+                .map_err(|e| anyhow!("Invalid signature file {}: {}", path, e))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    println!("✓ 已加载 {} 个部分签名", partials.len());
+
+    let signed_tx = aggregate_signatures(&tx, args_version, &partials)?;
+
+    let output_path = match out {
+        Some(path) => path.to_string(),
+        None => format!("{}_signed.json", tx_file.trim_end_matches(".json")),
+    };
+    let tx_json = ckb_jsonrpc_types::TransactionView::from(signed_tx);
+    fs::write(&output_path, serde_json::to_string_pretty(&tx_json)?)?;
+
+    println!("✓ 商户多签 witness 已组装");
+    println!("✓ 已签名交易已保存: {}", output_path);
+
+    Ok(())
+}
+
+/// Sign the transaction with one merchant key of the multisig config in its witness
+///
+/// `args_version` is the version byte of the Spillman cell's args.
+pub fn sign_partial(
+    tx: &TransactionView,
+    args_version: u8,
+    privkey: &Privkey,
+) -> Result<PartialSignature> {
+    let witness = parse_multisig_witness(tx)?;
+    let pubkey = privkey
+        .pubkey()
+        .map_err(|e| anyhow!("Failed to derive pubkey: {:?}", e))?;
+    let hash = pubkey_hash(&pubkey);
+    let index = witness
+        .pubkey_hashes
+        .iter()
+        .position(|h| *h == hash)
+        .ok_or_else(|| {
+            anyhow!(
+                "Key {} is not part of the merchant multisig config",
+                hex::encode(hash)
+            )
+        })?;
+
+    let message = signing_message(tx, witness.unlock_type, args_version)?;
+    let signature = privkey
+        .sign_recoverable(&message.into())
+        .map_err(|e| anyhow!("Failed to sign: {:?}", e))?
+        .serialize();
+
+    Ok(PartialSignature {
+        tx_hash: format!("{:#x}", tx.hash()),
+        index: index as u8,
+        pubkey_hash: hex::encode(hash),
+        signature: hex::encode(signature),
+    })
+}
+
+/// Fill the multisig merchant part of the witness with `threshold` partial signatures
+///
+/// Signatures are checked against the pubkey hash at their index and ordered by index,
+/// as CKB multisig requires.
+pub fn aggregate_signatures(
+    tx: &TransactionView,
+    args_version: u8,
+    partials: &[PartialSignature],
+) -> Result<TransactionView> {
+    let witness = parse_multisig_witness(tx)?;
+    let tx_hash = format!("{:#x}", tx.hash());
+    let message = signing_message(tx, witness.unlock_type, args_version)?;
+
+    let mut sigs: Vec<(u8, [u8; SIGNATURE_SIZE])> = Vec::new();
+    for partial in partials {
+        if partial.tx_hash != tx_hash {
+            return Err(anyhow!(
+                "Signature from key #{} is for transaction {}, expected {}",
+                partial.index,
+                partial.tx_hash,
+                tx_hash
+            ));
+        }
+        let expected_hash = witness
+            .pubkey_hashes
+            .get(partial.index as usize)
+            .ok_or_else(|| anyhow!("Signer index {} out of range", partial.index))?;
+        let signature: [u8; SIGNATURE_SIZE] = hex::decode(&partial.signature)?
+            .try_into()
+            .map_err(|sig: Vec<u8>| anyhow!("Invalid signature length: {}", sig.len()))?;
+        let recovered = Signature::from_slice(&signature)
+            .and_then(|sig| sig.recover(&message.into()))
+            .map_err(|e| anyhow!("Invalid signature from key #{}: {:?}", partial.index, e))?;
+        if pubkey_hash(&recovered) != *expected_hash {
+            return Err(anyhow!(
+                "Signature from key #{} does not match pubkey hash {}",
+                partial.index,
+                hex::encode(expected_hash)
+            ));
+        }
+        if sigs.iter().all(|(index, _)| *index != partial.index) {
+            sigs.push((partial.index, signature));
+        }
+    }
+
+    if sigs.len() < witness.threshold {
+        return Err(anyhow!(
+            "Not enough signatures: collected {}, threshold {}",
+            sigs.len(),
+            witness.threshold
+        ));
+    }
+    sigs.sort_by_key(|(index, _)| *index);
+    sigs.truncate(witness.threshold);
+
+    let merchant = MerchantSig::Multi {
+        config: witness.config,
+        sigs: sigs.into_iter().map(|(_, sig)| sig).collect(),
+    };
+    let new_witness = assemble_spillman_witness(witness.unlock_type, merchant, &witness.user_sig);
+
+    let mut witnesses: Vec<_> = tx.witnesses().into_iter().collect();
+    witnesses[0] = new_witness.pack();
+    Ok(tx.as_advanced_builder().set_witnesses(witnesses).build())
+}
+
+/// The message the contract checks for this unlock type and args version
+///
+/// A commitment is signed over the Spillman input and the channel outputs; every other
+/// path signs the whole transaction.
+fn signing_message(tx: &TransactionView, unlock_type: u8, args_version: u8) -> Result<[u8; 32]> {
+    if unlock_type == UNLOCK_TYPE_COMMITMENT {
+        spillman_commitment_signing_message(tx, args_version, COMMITMENT_CHANNEL_OUTPUTS)
+    } else {
+        spillman_signing_message_for_version(tx, args_version)
+    }
+}
+
+/// Args version of the Spillman cell spent by `tx`, looked up on chain
+fn load_args_version(config_path: &str, tx: &TransactionView) -> Result<u8> {
+    let config = load_config(config_path)?;
+    let rpc_client = rpc::client(&config.network.rpc_url);
+    let spillman_cell = crate::commands::settle::spillman_input_cell(&rpc_client, tx)?;
+    Ok(SpillmanArgs::from_bytes(&spillman_cell.lock().args().raw_data())?.version)
+}
+
+/// Parse a bare Spillman witness with a multisig merchant part
+///
+/// Layout: EMPTY_WITNESS_ARGS | unlock_type | S R M N PubKeyHash*N | M signatures | user signature
+fn parse_multisig_witness(tx: &TransactionView) -> Result<MultisigWitness> {
+    let witness = tx
+        .witnesses()
+        .get(0)
+        .ok_or_else(|| anyhow!("Transaction has no witness"))?
+        .raw_data();
+    let header_start = EMPTY_WITNESS_ARGS_SIZE + UNLOCK_TYPE_SIZE;
+    if witness.len() < header_start + MULTISIG_HEADER_SIZE
+        || witness[..EMPTY_WITNESS_ARGS_SIZE] != EMPTY_WITNESS_ARGS
+    {
+        return Err(anyhow!("Witness is not a Spillman Lock witness"));
+    }
+
+    let header = &witness[header_start..header_start + MULTISIG_HEADER_SIZE];
+    let threshold = header[2] as usize;
+    let pubkey_count = header[3] as usize;
+    let config_len = MULTISIG_HEADER_SIZE + pubkey_count * 20;
+    let expected_len = header_start + config_len + (threshold + 1) * SIGNATURE_SIZE;
+    if threshold == 0 || threshold > pubkey_count || witness.len() != expected_len {
+        return Err(anyhow!(
+            "Witness has no multisig merchant part (is the merchant single-sig?)"
+        ));
+    }
+
+    let config = witness[header_start..header_start + config_len].to_vec();
+    let pubkey_hashes = config[MULTISIG_HEADER_SIZE..]
+        .chunks(20)
+        .map(|chunk| chunk.try_into().unwrap())
+        .collect();
+    let user_sig = witness[witness.len() - SIGNATURE_SIZE..]
+        .try_into()
+        .unwrap();
+
+    Ok(MultisigWitness {
+        unlock_type: witness[EMPTY_WITNESS_ARGS_SIZE],
+        config,
+        threshold,
+        pubkey_hashes,
+        user_sig,
+    })
+}
+
+fn load_tx(tx_file: &str) -> Result<TransactionView> {
+    let tx_json_str = fs::read_to_string(tx_file)
+        .map_err(|e| anyhow!("Failed to read transaction file: {}", e))?;
+    let tx_json: ckb_jsonrpc_types::TransactionView = serde_json::from_str(&tx_json_str)
+        .map_err(|e| anyhow!("Failed to parse transaction JSON: {}", e))?;
+    let tx_packed: ckb_types::packed::Transaction = tx_json.inner.into();
+    Ok(tx_packed.into_view())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tx_builder::signing::{spillman_signing_message, ARGS_VERSION_BOUND_CELL_DEPS};
+    use ckb_types::{
+        bytes::Bytes,
+        core::Capacity,
+        packed::{CellDep, CellInput, CellOutput, OutPoint, Transaction},
+        H256,
+    };
+
+    fn test_key(byte: u8) -> Privkey {
+        Privkey::from_slice(&[byte; 32])
+    }
+
+    /// Commitment tx with a 2-of-3 multisig placeholder, as built for the user
+    fn test_commitment(keys: &[Privkey]) -> (TransactionView, Vec<u8>) {
+        let mut config = vec![0u8, 0, 2, keys.len() as u8];
+        for key in keys {
+            config.extend_from_slice(&pubkey_hash(&key.pubkey().unwrap()));
+        }
+        let tx = Transaction::default()
+            .as_advanced_builder()
+            .cell_dep(CellDep::new_builder().build())
+            .input(CellInput::new(OutPoint::new(H256([0x11; 32]).pack(), 0), 0))
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(500 * 100_000_000))
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build();
+        let placeholder = assemble_spillman_witness(
+            UNLOCK_TYPE_COMMITMENT,
+            MerchantSig::Multi {
+                config: config.clone(),
+                sigs: vec![[0u8; SIGNATURE_SIZE]; 2],
+            },
+            &[0xaa; SIGNATURE_SIZE],
+        );
+        let tx = tx.as_advanced_builder().witness(placeholder.pack()).build();
+        (tx, config)
+    }
+
+    #[test]
+    fn test_partial_sign_two_of_three_and_aggregate() {
+        let keys = vec![test_key(1), test_key(2), test_key(3)];
+        let (tx, config) = test_commitment(&keys);
+        // A version 1 channel binds the cell deps into the message
+        let version = ARGS_VERSION_BOUND_CELL_DEPS;
+        let message = spillman_signing_message_for_version(&tx, version).unwrap();
+        assert_ne!(message, spillman_signing_message(&tx));
+
+        // Keys #2 and #0 sign independently, in any order
+        let sig_c = sign_partial(&tx, version, &keys[2]).unwrap();
+        let sig_a = sign_partial(&tx, version, &keys[0]).unwrap();
+        assert_eq!(sig_a.index, 0);
+        assert_eq!(sig_c.index, 2);
+
+        // One signature is below the 2-of-3 threshold
+        let err = aggregate_signatures(&tx, version, std::slice::from_ref(&sig_a)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Not enough signatures: collected 1, threshold 2"
+        );
+
+        let signed = aggregate_signatures(&tx, version, &[sig_c.clone(), sig_a.clone()]).unwrap();
+        let witness = signed.witnesses().get(0).unwrap().raw_data();
+        let sign = |key: &Privkey| key.sign_recoverable(&message.into()).unwrap().serialize();
+        let expected = [
+            &EMPTY_WITNESS_ARGS[..],
+            &[UNLOCK_TYPE_COMMITMENT][..],
+            &config[..],
+            &sign(&keys[0])[..],
+            &sign(&keys[2])[..],
+            &[0xaa; SIGNATURE_SIZE][..],
+        ]
+        .concat();
+        assert_eq!(witness.to_vec(), expected);
+        // The witness is not part of the signing message
+        assert_eq!(
+            spillman_signing_message_for_version(&signed, version).unwrap(),
+            message
+        );
+
+        // A signature claiming another key's index is rejected
+        let forged = PartialSignature { index: 1, ..sig_a };
+        assert!(aggregate_signatures(&tx, version, &[forged, sig_c]).is_err());
+    }
+
+    #[test]
+    fn test_partial_sign_rejects_key_outside_config() {
+        let keys = vec![test_key(1), test_key(2), test_key(3)];
+        let (tx, _) = test_commitment(&keys);
+
+        let err = sign_partial(&tx, ARGS_VERSION_BOUND_CELL_DEPS, &test_key(9)).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("is not part of the merchant multisig config"));
+    }
+}
@@ -0,0 +1,458 @@
+use anyhow::{anyhow, Result};
+use ckb_sdk::{
+    constants::MultisigScript, transaction::builder::FeeCalculator, unlock::MultisigConfig,
+    HumanCapacity, Since, SinceType,
+};
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, TransactionView},
+    packed::{CellOutput, Script},
+    prelude::*,
+};
+use serde_json::json;
+use std::fs;
+use std::sync::Arc;
+
+use crate::utils::ckb_cli::ckb_cli_tx_file;
+use crate::utils::rpc;
+use crate::{
+    commands::settle::{check_latest_commitment, spillman_input_cell},
+    signer::{RawKeySigner, Signer},
+    tx_builder::funding_v2::build_multisig_config_with_type,
+    tx_builder::signing::spillman_signing_message_for_version,
+    tx_builder::witness_utils::{
+        assemble_spillman_witness, calculate_commitment_witness_size, MerchantSig,
+        UNLOCK_TYPE_SPLICE,
+    },
+    utils::{
+        config::{load_config, Config},
+        crypto::SpillmanArgs,
+        log::log_event,
+    },
+};
+
+/// Execute splice command - the merchant sweeps its payments, the channel stays open
+///
+/// Spends the Spillman cell of the latest commitment `tx_file` into the merchant payout
+/// and a new Spillman cell holding the user's remaining balance, whose timeout moves to
+/// `timeout_timestamp`. Both parties sign with the keys in the config.
+pub async fn execute(
+    tx_file: &str,
+    channel_file: &str,
+    config_path: &str,
+    timeout_timestamp: u64,
+    fee_rate: u64,
+    broadcast: bool,
+) -> Result<()> {
+    println!("\n═══════════════════════════════════════════════════════");
+    println!("  🔀 Splice: 商户提取付款，通道继续保持");
+    println!("═══════════════════════════════════════════════════════\n");
+
+    println!("📋 加载配置...");
+    let config = load_config(config_path)?;
+    println!("✓ 配置加载完成");
+
+    println!("\n📄 加载最新 Commitment 交易: {}", tx_file);
+    let tx_json: ckb_jsonrpc_types::TransactionView = serde_json::from_str(
+        &fs::read_to_string(tx_file)
+            .map_err(|e| anyhow!("Failed to read transaction file: {}", e))?,
+    )
+    .map_err(|e| anyhow!("Failed to parse transaction JSON: {}", e))?;
+    let commitment = ckb_types::packed::Transaction::from(tx_json.inner).into_view();
+    // The splice pays out what the latest commitment owes the merchant
+    check_latest_commitment(channel_file, &commitment, false)?;
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    if timeout_timestamp <= now {
+        return Err(anyhow!(
+            "New timeout {} is not in the future (now {})",
+            timeout_timestamp,
+            now
+        ));
+    }
+
+    println!("\n🔍 从链上查询 Spillman Lock cell...");
+    let rpc_client = rpc::client(&config.network.rpc_url);
+    let spillman_cell = spillman_input_cell(&rpc_client, &commitment)?;
+    let args = SpillmanArgs::from_bytes(&spillman_cell.lock().args().raw_data())?;
+    println!(
+        "✓ 通道容量: {}",
+        HumanCapacity::from(Unpack::<u64>::unpack(&spillman_cell.capacity()))
+    );
+
+    let merchant_secret_keys = config.merchant.get_secret_keys()?;
+    let merchant_multisig_config =
+        merchant_multisig_config(&config, &merchant_secret_keys, args.algorithm_id)?;
+
+    println!("\n🔨 构建 Splice 交易...");
+    let timeout = Since::new(SinceType::Timestamp, timeout_timestamp, false).value();
+    let tx = build_splice_transaction(
+        &commitment,
+        &spillman_cell,
+        timeout,
+        merchant_multisig_config.as_ref(),
+        fee_rate,
+    )?;
+    let new_capacity: u64 = tx.outputs().get(0).unwrap().capacity().unpack();
+    let payout_capacity: u64 = tx.outputs().get(1).unwrap().capacity().unpack();
+    println!("✓ Splice 交易构建完成");
+    println!(
+        "  - 新通道容量 (用户余额): {}",
+        HumanCapacity::from(new_capacity)
+    );
+    println!("  - 商户提取: {}", HumanCapacity::from(payout_capacity));
+    println!("  - 新超时时间戳: {}", timeout_timestamp);
+
+    println!("\n🔐 双方签名...");
+    let tx = sign_splice_transaction(
+        tx,
+        &args,
+        RawKeySigner::new(config.user.get_secret_keys()?)?,
+        RawKeySigner::new_arc(merchant_secret_keys)?,
+        merchant_multisig_config.as_ref(),
+    )?;
+    println!("✓ 用户和商户签名完成");
+    println!("  - TX Hash: {:#x}", tx.hash());
+
+    let tx_json = ckb_jsonrpc_types::TransactionView::from(tx);
+    if broadcast {
+        println!("\n📡 广播交易到链上...");
+        let tx_hash = rpc_client
+            .send_transaction(tx_json.inner.clone(), None)
+            .map_err(|e| anyhow!("Failed to broadcast transaction: {:?}", e))?;
+        println!("✓ 交易已广播");
+        println!("  - TX Hash: {:#x}", tx_hash);
+        log_event(
+            "splice",
+            "broadcast",
+            json!({ "tx_hash": format!("{:#x}", tx_hash) }),
+        );
+    }
+
+    // The splice transaction is the funding transaction of the new channel
+    let output_path = format!("secrets/splice_tx_{}.json", now);
+    fs::write(&output_path, serde_json::to_string_pretty(&tx_json)?)?;
+    println!("\n💾 Splice 交易已保存到: {}", output_path);
+    log_event(
+        "splice",
+        "saved",
+        json!({
+            "tx_hash": format!("{:#x}", tx_json.hash),
+            "output_path": output_path,
+        }),
+    );
+
+    println!("\n✅ Splice 完成！");
+    println!("\n📌 后续操作:");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!("  - 新通道的 Spillman Lock cell 是 splice 交易的 output 0");
+    println!("  - 旧通道的 commitment 和 refund 交易均已失效");
+    println!(
+        "  - 用户需要为新通道重新构建 refund 交易: spillman-cli refund --tx-file {}",
+        output_path
+    );
+    if !broadcast {
+        println!("\n📡 手动广播交易:");
+        println!(
+            "  ckb-cli tx send --tx-file {}",
+            ckb_cli_tx_file(&output_path)?.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Multisig config of a merchant configured as multisig, typed by the channel's algorithm_id
+pub fn merchant_multisig_config(
+    config: &Config,
+    merchant_secret_keys: &[secp256k1::SecretKey],
+    algorithm_id: u8,
+) -> Result<Option<MultisigConfig>> {
+    let Some((threshold, total)) = config.merchant.get_multisig_config() else {
+        return Ok(None);
+    };
+    let multisig_type = match algorithm_id {
+        6 => MultisigScript::Legacy,
+        7 => MultisigScript::V2,
+        id => {
+            return Err(anyhow!(
+                "Merchant is configured as multisig but the channel uses algorithm_id {}",
+                id
+            ))
+        }
+    };
+    Ok(Some(build_multisig_config_with_type(
+        merchant_secret_keys,
+        threshold,
+        total,
+        multisig_type,
+    )?))
+}
+
+/// Build the unsigned splice transaction of a channel
+///
+/// `commitment` is the latest commitment and `spillman_cell` the Spillman cell it spends.
+/// Output 0 is the new Spillman cell: the user's commitment output (capacity, type and
+/// data) under the same Spillman Lock args with `timeout` replaced. Output 1 pays the
+/// merchant outputs of the commitment minus the splice fee.
+pub fn build_splice_transaction(
+    commitment: &TransactionView,
+    spillman_cell: &CellOutput,
+    timeout: u64,
+    merchant_multisig_config: Option<&MultisigConfig>,
+    fee_rate: u64,
+) -> Result<TransactionView> {
+    let input = commitment
+        .inputs()
+        .get(0)
+        .ok_or_else(|| anyhow!("Commitment transaction has no input"))?;
+    let output = |index: usize| -> Result<(CellOutput, Bytes)> {
+        let output = commitment
+            .outputs()
+            .get(index)
+            .ok_or_else(|| anyhow!("Commitment transaction has no output {}", index))?;
+        let data = commitment
+            .outputs_data()
+            .get(index)
+            .map(|data| data.raw_data())
+            .unwrap_or_default();
+        Ok((output, data))
+    };
+    let (user_output, user_data) = output(0)?;
+    let (merchant_output, _) = output(1)?;
+    let type_script = spillman_cell.type_().to_opt();
+
+    // Same channel, new timeout
+    let mut args = SpillmanArgs::from_bytes(&spillman_cell.lock().args().raw_data())?;
+    args.timeout = timeout;
+    let new_lock: Script = spillman_cell
+        .lock()
+        .as_builder()
+        .args(Bytes::copy_from_slice(&args.to_bytes()).pack())
+        .build();
+    let new_output = user_output.as_builder().lock(new_lock).build();
+    let new_capacity: u64 = new_output.capacity().unpack();
+    let min_capacity = new_output
+        .occupied_capacity(Capacity::bytes(user_data.len())?)?
+        .as_u64();
+    if new_capacity < min_capacity {
+        return Err(anyhow!(
+            "User balance {} is below the {} a Spillman cell occupies",
+            HumanCapacity::from(new_capacity),
+            HumanCapacity::from(min_capacity)
+        ));
+    }
+
+    // Every merchant output of the commitment is swept into one payout
+    let merchant_lock = merchant_output.lock();
+    let mut merchant_xudt: u128 = 0;
+    for index in 1..commitment.outputs().len() {
+        let (output, data) = output(index)?;
+        if output.lock() != merchant_lock {
+            continue;
+        }
+        if type_script.is_some() {
+            let amount = data
+                .get(0..16)
+                .and_then(|bytes| bytes.try_into().ok())
+                .map(u128::from_le_bytes)
+                .ok_or_else(|| anyhow!("Invalid xUDT data length: {}", data.len()))?;
+            merchant_xudt = merchant_xudt
+                .checked_add(amount)
+                .ok_or_else(|| anyhow!("Merchant xUDT amount overflow"))?;
+        }
+    }
+    let payout_data = if type_script.is_some() {
+        Bytes::from(merchant_xudt.to_le_bytes().to_vec())
+    } else {
+        Bytes::new()
+    };
+
+    let spillman_capacity: u64 = spillman_cell.capacity().unpack();
+    let witness_size = calculate_commitment_witness_size(merchant_multisig_config);
+    let build = |fee: u64| -> Result<TransactionView> {
+        let payout_capacity = spillman_capacity
+            .checked_sub(new_capacity)
+            .and_then(|capacity| capacity.checked_sub(fee))
+            .ok_or_else(|| anyhow!("Channel capacity does not cover the splice fee"))?;
+        let payout = CellOutput::new_builder()
+            .capacity(Capacity::shannons(payout_capacity))
+            .lock(merchant_lock.clone())
+            .type_(type_script.clone().pack())
+            .build();
+        Ok(TransactionView::new_advanced_builder()
+            .cell_deps(commitment.cell_deps())
+            .input(input.clone())
+            .output(new_output.clone())
+            .output_data(user_data.pack())
+            .output(payout)
+            .output_data(payout_data.pack())
+            .witness(Bytes::from(vec![0u8; witness_size]).pack())
+            .build())
+    };
+
+    // The fee does not change the size, so one pass fixes it
+    let tx_size = build(0)?.data().as_reader().serialized_size_in_block() as u64;
+    let tx = build(FeeCalculator::new(fee_rate).fee(tx_size))?;
+
+    let payout_output = tx.outputs().get(1).unwrap();
+    let payout_capacity: u64 = payout_output.capacity().unpack();
+    let payout_min = payout_output
+        .occupied_capacity(Capacity::bytes(payout_data.len())?)?
+        .as_u64();
+    if payout_capacity < payout_min {
+        return Err(anyhow!(
+            "Merchant payout {} is below its occupied capacity {}",
+            HumanCapacity::from(payout_capacity),
+            HumanCapacity::from(payout_min)
+        ));
+    }
+
+    Ok(tx)
+}
+
+/// Sign a splice transaction as both the user and the merchant
+///
+/// The splice path signs the whole raw transaction (cell deps per the args version),
+/// with `UNLOCK_TYPE_SPLICE` in the Spillman witness. Only witness 0 is replaced, the
+/// witnesses of other inputs are left for their own locks.
+pub fn sign_splice_transaction(
+    tx: TransactionView,
+    args: &SpillmanArgs,
+    user_signer: RawKeySigner,
+    merchant_signer: Arc<dyn Signer>,
+    merchant_multisig_config: Option<&MultisigConfig>,
+) -> Result<TransactionView> {
+    let message = spillman_signing_message_for_version(&tx, args.version)?;
+    let user_sig = user_signer.sign(message, &args.user_pubkey_hash)?;
+
+    let merchant = MerchantSig::sign(
+        merchant_signer.as_ref(),
+        message,
+        &args.merchant_lock_arg,
+        merchant_multisig_config,
+    )?;
+
+    let witness = assemble_spillman_witness(UNLOCK_TYPE_SPLICE, merchant, &user_sig);
+    let mut witnesses: Vec<_> = tx.witnesses().into_iter().collect();
+    match witnesses.first_mut() {
+        Some(first) => *first = witness.pack(),
+        None => witnesses.push(witness.pack()),
+    }
+    Ok(tx.as_advanced_builder().set_witnesses(witnesses).build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_crypto::secp::Privkey;
+    use ckb_types::{
+        core::ScriptHashType,
+        packed::{CellInput, OutPoint},
+        H256,
+    };
+
+    use crate::utils::crypto::pubkey_hash;
+
+    fn secret_key(byte: u8) -> secp256k1::SecretKey {
+        secp256k1::SecretKey::from_slice(&[byte; 32]).unwrap()
+    }
+
+    fn key_hash(byte: u8) -> [u8; 20] {
+        pubkey_hash(&Privkey::from_slice(&[byte; 32]).pubkey().unwrap())
+    }
+
+    fn sighash_lock(args: [u8; 20]) -> Script {
+        Script::new_builder()
+            .code_hash(H256([0x9b; 32]).pack())
+            .hash_type(ScriptHashType::Type)
+            .args(Bytes::copy_from_slice(&args).pack())
+            .build()
+    }
+
+    #[test]
+    fn test_splice_keeps_user_balance_in_the_channel() {
+        let args =
+            SpillmanArgs::new_with_algorithm(key_hash(2), key_hash(1), 0x4000_0000_6774_8580, 0);
+        let spillman_cell = CellOutput::new_builder()
+            .capacity(Capacity::shannons(1_000 * 100_000_000))
+            .lock(
+                Script::new_builder()
+                    .code_hash(H256([0x5a; 32]).pack())
+                    .hash_type(ScriptHashType::Data1)
+                    .args(Bytes::copy_from_slice(&args.to_bytes()).pack())
+                    .build(),
+            )
+            .build();
+        let output = |capacity: u64, lock: Script| {
+            CellOutput::new_builder()
+                .capacity(Capacity::shannons(capacity * 100_000_000))
+                .lock(lock)
+                .build()
+        };
+        let commitment = TransactionView::new_advanced_builder()
+            .input(CellInput::new(OutPoint::new(H256([0x11; 32]).pack(), 0), 0))
+            .output(output(600, sighash_lock(key_hash(1))))
+            .output_data(Bytes::new().pack())
+            .output(output(399, sighash_lock(key_hash(2))))
+            .output_data(Bytes::new().pack())
+            .build();
+
+        let new_timeout = 0x4000_0000_6800_0000;
+        let tx = build_splice_transaction(&commitment, &spillman_cell, new_timeout, None, 1_000)
+            .unwrap();
+
+        // Output 0: the user's 600 CKB stay under the same args with the new timeout
+        let new_cell = tx.outputs().get(0).unwrap();
+        let new_args = SpillmanArgs::from_bytes(&new_cell.lock().args().raw_data()).unwrap();
+        assert_eq!(
+            new_args,
+            SpillmanArgs {
+                timeout: new_timeout,
+                ..args.clone()
+            }
+        );
+        assert_eq!(
+            new_cell.lock().code_hash(),
+            spillman_cell.lock().code_hash()
+        );
+        assert_eq!(
+            Unpack::<u64>::unpack(&new_cell.capacity()),
+            600 * 100_000_000
+        );
+
+        // Output 1: the merchant takes the rest minus the splice fee
+        let payout = tx.outputs().get(1).unwrap();
+        let fee =
+            FeeCalculator::new(1_000).fee(tx.data().as_reader().serialized_size_in_block() as u64);
+        assert_eq!(payout.lock(), sighash_lock(key_hash(2)));
+        assert_eq!(
+            Unpack::<u64>::unpack(&payout.capacity()),
+            400 * 100_000_000 - fee
+        );
+
+        let signed = sign_splice_transaction(
+            tx,
+            &args,
+            RawKeySigner::new(vec![secret_key(1)]).unwrap(),
+            RawKeySigner::new_arc(vec![secret_key(2)]).unwrap(),
+            None,
+        )
+        .unwrap();
+        let witness = signed.witnesses().get(0).unwrap().raw_data();
+        assert_eq!(witness.len(), calculate_commitment_witness_size(None));
+        assert_eq!(witness[16], UNLOCK_TYPE_SPLICE);
+
+        // A user balance too small for a Spillman cell cannot be spliced
+        let dust = commitment
+            .as_advanced_builder()
+            .set_outputs(vec![
+                output(70, sighash_lock(key_hash(1))),
+                output(929, sighash_lock(key_hash(2))),
+            ])
+            .build();
+        assert!(build_splice_transaction(&dust, &spillman_cell, new_timeout, None, 1_000).is_err());
+    }
+}
@@ -0,0 +1,447 @@
+use anyhow::{anyhow, Result};
+use ckb_chain_spec::consensus::ConsensusBuilder;
+use ckb_mock_tx_types::{MockCellDep, MockInfo, MockResourceLoader, MockTransaction, Resource};
+use ckb_script::{TransactionScriptsVerifier, TxVerifyEnv};
+use ckb_sdk::rpc::CkbRpcClient;
+use ckb_types::{
+    bytes::Bytes,
+    core::{
+        cell::resolve_transaction,
+        hardfork::{HardForks, CKB2021, CKB2023},
+        Cycle, HeaderBuilder, HeaderView, TransactionView,
+    },
+    packed::{Byte32, CellOutput, OutPoint},
+    prelude::*,
+    H256,
+};
+use std::{collections::HashSet, fs, str::FromStr, sync::Arc};
+
+use crate::utils::config::load_config;
+use crate::utils::rpc;
+
+/// Execute verify command - run the transaction's scripts locally before broadcasting
+pub async fn execute(tx_file: &str, config_path: &str) -> Result<()> {
+    println!("\n═══════════════════════════════════════════════════════");
+    println!("  🔬 本地验证交易");
+    println!("═══════════════════════════════════════════════════════\n");
+
+    // 1. Load configuration
+    println!("📋 加载配置...");
+    let config = load_config(config_path)?;
+    println!("✓ 配置加载完成");
+
+    // 2. Load transaction from file
+    println!("\n📄 加载交易: {}", tx_file);
+    let tx_json_str = fs::read_to_string(tx_file)
+        .map_err(|e| anyhow!("Failed to read transaction file: {}", e))?;
+    let tx_json: ckb_jsonrpc_types::TransactionView = serde_json::from_str(&tx_json_str)
+        .map_err(|e| anyhow!("Failed to parse transaction JSON: {}", e))?;
+    let tx_packed: ckb_types::packed::Transaction = tx_json.inner.into();
+    let tx: TransactionView = tx_packed.into_view();
+    println!("✓ 交易加载完成");
+    println!("  - TX Hash: {:#x}", tx.hash());
+    println!("  - Inputs: {}", tx.inputs().len());
+    println!("  - Outputs: {}", tx.outputs().len());
+
+    // 3. Replace the Spillman Lock / auth dep data with the local binaries
+    println!("\n📦 加载合约二进制...");
+    let mut loader = RpcResourceLoader::new(&config.network.rpc_url);
+    let mut local_binaries = Vec::new();
+    for (name, tx_hash, index, binary_path) in [
+        (
+            "spillman-lock",
+            &config.spillman_lock.tx_hash,
+            config.spillman_lock.index,
+            &config.spillman_lock.binary_path,
+        ),
+        (
+            "auth",
+            &config.auth.tx_hash,
+            config.auth.index,
+            &config.auth.binary_path,
+        ),
+    ] {
+        match binary_path {
+            Some(path) => {
+                let binary = fs::read(path)
+                    .map_err(|e| anyhow!("Failed to read {} binary '{}': {}", name, path, e))?;
+                println!("  - {}: {} ({} bytes)", name, path, binary.len());
+                local_binaries.push((deployment_out_point(tx_hash, index)?, Bytes::from(binary)));
+            }
+            None => println!("  - {}: 使用链上数据（未配置 binary_path）", name),
+        }
+    }
+    let mock_tx = build_mock_tx(&tx, local_binaries, &mut loader)?;
+    println!("✓ 依赖解析完成");
+
+    // 4. Run all scripts in ckb-vm
+    println!("\n⚙️  运行脚本...");
+    match verify_mock_tx(&mock_tx, &mut loader) {
+        Ok(cycles) => {
+            println!("✅ 验证通过");
+            println!("  - Cycles: {}", cycles);
+            Ok(())
+        }
+        Err(e) => {
+            println!("❌ 验证失败: {}", e);
+            Err(e)
+        }
+    }
+}
+
+/// Out point of a deployed script cell from its config entry
+fn deployment_out_point(tx_hash: &str, index: u32) -> Result<OutPoint> {
+    let tx_hash = H256::from_str(tx_hash.trim_start_matches("0x"))
+        .map_err(|e| anyhow!("Invalid cell dep tx hash '{}': {}", tx_hash, e))?;
+    Ok(OutPoint::new_builder()
+        .tx_hash(tx_hash.pack())
+        .index(index)
+        .build())
+}
+
+/// Build a mock transaction whose matching cell deps carry the given local binaries
+///
+/// The dep cell itself (capacity, type script) still comes from `loader`, so type-id
+/// based code hashes resolve exactly as they do on-chain. Everything else is left to
+/// `loader` when the transaction is resolved.
+pub fn build_mock_tx<L: MockResourceLoader>(
+    tx: &TransactionView,
+    local_binaries: Vec<(OutPoint, Bytes)>,
+    loader: &mut L,
+) -> Result<MockTransaction> {
+    let mut cell_deps = Vec::new();
+    for (out_point, data) in local_binaries {
+        let Some(cell_dep) = tx
+            .cell_deps()
+            .into_iter()
+            .find(|dep| dep.out_point() == out_point)
+        else {
+            return Err(anyhow!(
+                "Transaction has no cell dep at {:#x}:{}",
+                out_point.tx_hash(),
+                Unpack::<u32>::unpack(&out_point.index())
+            ));
+        };
+        let (output, _, header) = loader
+            .get_live_cell(out_point.clone())
+            .map_err(|e| anyhow!("Failed to load cell dep: {}", e))?
+            .ok_or_else(|| anyhow!("Cell dep {} is not live", out_point))?;
+        cell_deps.push(MockCellDep {
+            cell_dep,
+            output,
+            data,
+            header,
+        });
+    }
+
+    Ok(MockTransaction {
+        mock_info: MockInfo {
+            cell_deps,
+            ..Default::default()
+        },
+        tx: tx.data(),
+    })
+}
+
+/// Run every script of the transaction in ckb-vm and return the consumed cycles
+///
+/// Cells missing from `mock_tx.mock_info` are fetched through `loader`.
+pub fn verify_mock_tx<L: MockResourceLoader>(
+    mock_tx: &MockTransaction,
+    loader: &mut L,
+) -> Result<Cycle> {
+    let resource = Resource::from_both(mock_tx, loader)
+        .map_err(|e| anyhow!("Failed to resolve transaction cells: {}", e))?;
+    let rtx = resolve_transaction(
+        mock_tx.core_transaction(),
+        &mut HashSet::new(),
+        &resource,
+        &resource,
+    )
+    .map_err(|e| anyhow!("Failed to resolve transaction: {:?}", e))?;
+
+    let consensus = ConsensusBuilder::default()
+        .hardfork_switch(HardForks {
+            ckb2021: CKB2021::new_dev_default(),
+            ckb2023: CKB2023::new_dev_default(),
+        })
+        .build();
+    let max_cycles = consensus.max_block_cycles();
+    let tip = HeaderBuilder::default().number(0).build();
+
+    let verifier = TransactionScriptsVerifier::new(
+        Arc::new(rtx),
+        resource,
+        Arc::new(consensus),
+        Arc::new(TxVerifyEnv::new_submit(&tip)),
+    );
+    verifier
+        .verify(max_cycles)
+        .map_err(|e| anyhow!("Script verification failed: {}", e))
+}
+
+/// Loads live cells and headers from a CKB node
+struct RpcResourceLoader {
+    client: CkbRpcClient,
+}
+
+impl RpcResourceLoader {
+    fn new(rpc_url: &str) -> Self {
+        Self {
+            client: rpc::client(rpc_url),
+        }
+    }
+}
+
+impl MockResourceLoader for RpcResourceLoader {
+    fn get_header(&mut self, hash: H256) -> Result<Option<HeaderView>, String> {
+        rpc::with_retry("get_header", || self.client.get_header(hash.clone()))
+            .map(|header| header.map(Into::into))
+            .map_err(|e| e.to_string())
+    }
+
+    fn get_live_cell(
+        &mut self,
+        out_point: OutPoint,
+    ) -> Result<Option<(CellOutput, Bytes, Option<Byte32>)>, String> {
+        let cell = rpc::with_retry("get_live_cell", || {
+            self.client.get_live_cell(out_point.clone().into(), true)
+        })
+        .map_err(|e| e.to_string())?;
+        Ok(cell.cell.map(|info| {
+            let data = info
+                .data
+                .map(|data| data.content.into_bytes())
+                .unwrap_or_default();
+            (info.output.into(), data, None)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tx_builder::{
+        signing::spillman_signing_message,
+        witness_utils::{assemble_spillman_witness, MerchantSig, UNLOCK_TYPE_TIMEOUT},
+    };
+    use crate::utils::crypto::{pubkey_hash, SpillmanArgs};
+    use ckb_crypto::secp::Privkey;
+    use ckb_hash::blake2b_256;
+    use ckb_mock_tx_types::{DummyResourceLoader, MockInput};
+    use ckb_sdk::{Since, SinceType};
+    use ckb_types::{
+        core::{Capacity, DepType, ScriptHashType, TransactionBuilder},
+        packed::{CellDep, CellInput, Script},
+    };
+    use std::path::Path;
+
+    const TIMEOUT_TIMESTAMP: u64 = 1735689600;
+
+    /// Deploy `binary` as a code cell dep
+    fn deploy(binary: &Bytes, tx_hash_byte: u8) -> MockCellDep {
+        let out_point = OutPoint::new_builder()
+            .tx_hash(H256([tx_hash_byte; 32]).pack())
+            .index(0u32)
+            .build();
+        MockCellDep {
+            cell_dep: CellDep::new_builder()
+                .out_point(out_point)
+                .dep_type(DepType::Code)
+                .build(),
+            output: CellOutput::new_builder()
+                .capacity(Capacity::bytes(binary.len() + 100).unwrap())
+                .build(),
+            data: binary.clone(),
+            header: None,
+        }
+    }
+
+    /// A refund spending one Spillman Lock cell after the timeout, signed by both parties
+    fn signed_refund(
+        spillman_lock_bin: &Bytes,
+        auth_bin: &Bytes,
+        refund_capacity: u64,
+    ) -> MockTransaction {
+        let user_key = Privkey::from_slice(&[1u8; 32]);
+        let merchant_key = Privkey::from_slice(&[2u8; 32]);
+        let timeout_since = Since::new(SinceType::Timestamp, TIMEOUT_TIMESTAMP, false);
+        let args = SpillmanArgs::new_with_algorithm(
+            pubkey_hash(&merchant_key.pubkey().unwrap()),
+            pubkey_hash(&user_key.pubkey().unwrap()),
+            timeout_since.value(),
+            0,
+        );
+
+        let spillman_dep = deploy(spillman_lock_bin, 0x01);
+        let auth_dep = deploy(auth_bin, 0x02);
+        let lock = Script::new_builder()
+            .code_hash(blake2b_256(spillman_lock_bin).pack())
+            .hash_type(ScriptHashType::Data1)
+            .args(Bytes::copy_from_slice(&args.to_bytes()).pack())
+            .build();
+        let input = CellInput::new_builder()
+            .previous_output(
+                OutPoint::new_builder()
+                    .tx_hash(H256([0x03; 32]).pack())
+                    .index(0u32)
+                    .build(),
+            )
+            .since(Since::new(SinceType::Timestamp, TIMEOUT_TIMESTAMP + 86400, false).value())
+            .build();
+        let user_lock = Script::new_builder()
+            .code_hash(H256([0x44; 32]).pack())
+            .hash_type(ScriptHashType::Type)
+            .args(Bytes::from(args.user_pubkey_hash.to_vec()).pack())
+            .build();
+
+        let tx = TransactionBuilder::default()
+            .cell_dep(spillman_dep.cell_dep.clone())
+            .cell_dep(auth_dep.cell_dep.clone())
+            .input(input.clone())
+            .output(
+                CellOutput::new_builder()
+                    .capacity(refund_capacity)
+                    .lock(user_lock)
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build();
+
+        let message = spillman_signing_message(&tx);
+        let sign = |key: &Privkey| {
+            let sig = key.sign_recoverable(&message.into()).unwrap().serialize();
+            <[u8; 65]>::try_from(sig.as_slice()).unwrap()
+        };
+        let witness = assemble_spillman_witness(
+            UNLOCK_TYPE_TIMEOUT,
+            MerchantSig::Single(sign(&merchant_key)),
+            &sign(&user_key),
+        );
+        let tx = tx.as_advanced_builder().witness(witness.pack()).build();
+
+        MockTransaction {
+            mock_info: MockInfo {
+                inputs: vec![MockInput {
+                    input,
+                    output: CellOutput::new_builder()
+                        .capacity(1001 * 100_000_000u64)
+                        .lock(lock)
+                        .build(),
+                    data: Bytes::new(),
+                    header: None,
+                }],
+                cell_deps: vec![spillman_dep, auth_dep],
+                ..Default::default()
+            },
+            tx: tx.data(),
+        }
+    }
+
+    fn repo_file(relative: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join(relative)
+    }
+
+    #[test]
+    fn test_verify_refund_against_built_contract() {
+        let spillman_lock_path = repo_file("build/release/spillman-lock");
+        let Ok(spillman_lock_bin) = fs::read(&spillman_lock_path) else {
+            eprintln!(
+                "skipping: {} not built (run `make build`)",
+                spillman_lock_path.display()
+            );
+            return;
+        };
+        let spillman_lock_bin = Bytes::from(spillman_lock_bin);
+        let auth_bin = Bytes::from(fs::read(repo_file("deps/auth")).unwrap());
+
+        // Known-good: 1000 CKB back to the user, 1 CKB fee
+        let good = signed_refund(&spillman_lock_bin, &auth_bin, 1000 * 100_000_000);
+        let cycles = verify_mock_tx(&good, &mut DummyResourceLoader {}).unwrap();
+        assert!(cycles > 0);
+
+        // Known-bad: output capacity changed after signing
+        let mut bad = signed_refund(&spillman_lock_bin, &auth_bin, 1000 * 100_000_000);
+        let tampered = bad
+            .core_transaction()
+            .as_advanced_builder()
+            .set_outputs(vec![bad
+                .tx
+                .raw()
+                .outputs()
+                .get(0)
+                .unwrap()
+                .as_builder()
+                .capacity(999 * 100_000_000u64)
+                .build()])
+            .build();
+        bad.tx = tampered.data();
+        assert!(verify_mock_tx(&bad, &mut DummyResourceLoader {}).is_err());
+    }
+
+    #[test]
+    fn test_verify_fails_when_lock_binary_is_missing() {
+        // The refund's lock points at a binary that no cell dep provides
+        let auth_bin = Bytes::from(fs::read(repo_file("deps/auth")).unwrap());
+        let mut refund = signed_refund(&Bytes::from_static(b"not deployed"), &auth_bin, 1000);
+        refund.mock_info.cell_deps.remove(0);
+        let tx = refund
+            .core_transaction()
+            .as_advanced_builder()
+            .set_cell_deps(vec![refund.mock_info.cell_deps[0].cell_dep.clone()])
+            .build();
+        refund.tx = tx.data();
+
+        let err = verify_mock_tx(&refund, &mut DummyResourceLoader {}).unwrap_err();
+        assert!(err.to_string().contains("Script verification failed"));
+    }
+
+    #[test]
+    fn test_build_mock_tx_replaces_dep_data_with_local_binary() {
+        let auth_bin = Bytes::from(fs::read(repo_file("deps/auth")).unwrap());
+        let refund = signed_refund(&Bytes::from_static(b"on-chain"), &auth_bin, 1000);
+        let tx = refund.core_transaction();
+        let spillman_out_point = refund.mock_info.cell_deps[0].cell_dep.out_point();
+
+        let local = Bytes::from_static(b"local build");
+        let mock_tx = build_mock_tx(
+            &tx,
+            vec![(spillman_out_point.clone(), local.clone())],
+            &mut FixedLoader(refund.mock_info.cell_deps.clone()),
+        )
+        .unwrap();
+
+        assert_eq!(mock_tx.mock_info.cell_deps.len(), 1);
+        assert_eq!(mock_tx.mock_info.cell_deps[0].data, local);
+        assert_eq!(
+            mock_tx.mock_info.cell_deps[0].output,
+            refund.mock_info.cell_deps[0].output
+        );
+
+        // A binary for a dep the transaction does not use is rejected
+        let unrelated = deployment_out_point(&format!("{:#x}", H256([0x09; 32])), 0).unwrap();
+        assert!(build_mock_tx(&tx, vec![(unrelated, local)], &mut DummyResourceLoader {}).is_err());
+    }
+
+    /// Serves a fixed set of cells, standing in for the node
+    struct FixedLoader(Vec<MockCellDep>);
+
+    impl MockResourceLoader for FixedLoader {
+        fn get_header(&mut self, hash: H256) -> Result<Option<HeaderView>, String> {
+            Err(format!("Header {:x} is missing!", hash))
+        }
+
+        fn get_live_cell(
+            &mut self,
+            out_point: OutPoint,
+        ) -> Result<Option<(CellOutput, Bytes, Option<Byte32>)>, String> {
+            Ok(self
+                .0
+                .iter()
+                .find(|dep| dep.cell_dep.out_point() == out_point)
+                .map(|dep| (dep.output.clone(), dep.data.clone(), dep.header.clone())))
+        }
+    }
+}
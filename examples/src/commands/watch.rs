@@ -0,0 +1,232 @@
+use anyhow::{anyhow, Result};
+use ckb_sdk::{rpc::CkbRpcClient, Address};
+use ckb_types::{core::TransactionView, packed::OutPoint, prelude::*, H256};
+use serde_json::json;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::error::ChannelError;
+use crate::utils::rpc;
+use crate::{
+    tx_builder::refund_v2,
+    utils::{
+        channel_info::load_channel_info, config::load_config, crypto::TIMEOUT_GRACE_SECONDS,
+        log::log_event,
+    },
+};
+
+/// What the watcher should do after one poll
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefundDecision {
+    /// Timeout not reached yet (or funding cell not visible yet), keep polling
+    Wait,
+    /// Timeout passed and the funding cell is still live: refund now
+    Refund,
+    /// Funding cell was spent (merchant settled), nothing left to refund
+    Settled,
+}
+
+/// Decide whether to refund, given the chain median time and the funding cell status
+///
+/// `tip_ts` and `timeout_ts` are Unix timestamps in seconds. `funding_live` is `None`
+/// when the node does not know the cell (e.g. funding not committed yet).
+pub fn should_refund(tip_ts: u64, timeout_ts: u64, funding_live: Option<bool>) -> RefundDecision {
+    match funding_live {
+        Some(false) => RefundDecision::Settled,
+        Some(true) if tip_ts > timeout_ts => RefundDecision::Refund,
+        _ => RefundDecision::Wait,
+    }
+}
+
+/// Execute watch command - wait for the timeout, then refund automatically
+pub async fn execute(
+    channel_file: &str,
+    config_path: &str,
+    fee_rate: u64,
+    poll_interval: u64,
+) -> Result<()> {
+    println!("👀 执行 Watch 命令 - 超时后自动退款");
+    println!("═══════════════════════════════════════════");
+
+    let config = load_config(config_path)?;
+    println!("✓ 配置文件已加载: {}", config_path);
+
+    let channel_info = load_channel_info(channel_file)?;
+    let funding_tx_hash = H256::from_str(channel_info.funding_tx_hash.trim_start_matches("0x"))
+        .map_err(|e| anyhow!("Invalid funding tx hash: {}", e))?;
+    let timeout_timestamp = channel_info.timeout_timestamp;
+    let funding_out_point =
+        OutPoint::new(funding_tx_hash.pack(), channel_info.funding_output_index);
+
+    println!("✓ 通道信息: {}", channel_file);
+    println!("  - Funding TX: {:#x}", funding_tx_hash);
+    println!("  - Output Index: {}", channel_info.funding_output_index);
+    println!("  - 超时时间戳: {}", timeout_timestamp);
+    println!("  - 轮询间隔: {} 秒", poll_interval);
+
+    let rpc_client = rpc::client(&config.network.rpc_url);
+    // The contract keeps a grace window after the timeout for the merchant to settle
+    let refund_at = timeout_timestamp + TIMEOUT_GRACE_SECONDS;
+
+    loop {
+        // Since-based timeouts are checked against the median time of recent blocks
+        let median_time: u64 =
+            rpc::with_retry("get_blockchain_info", || rpc_client.get_blockchain_info())
+                .map_err(|e| ChannelError::RpcError(format!("{:?}", e)))?
+                .median_time
+                .value()
+                / 1000;
+        let cell_status = rpc::with_retry("get_live_cell", || {
+            rpc_client.get_live_cell(funding_out_point.clone().into(), false)
+        })
+        .map_err(|e| ChannelError::RpcError(format!("{:?}", e)))?
+        .status;
+        let funding_live = match cell_status.as_str() {
+            "live" => Some(true),
+            "dead" => Some(false),
+            _ => None,
+        };
+
+        match should_refund(median_time, refund_at, funding_live) {
+            RefundDecision::Wait => {
+                println!(
+                    "⏳ 链上时间 {}，距离超时还有 {} 秒（funding cell: {}）",
+                    median_time,
+                    refund_at.saturating_sub(median_time),
+                    cell_status
+                );
+                tokio::time::sleep(Duration::from_secs(poll_interval)).await;
+            }
+            RefundDecision::Settled => {
+                println!("\n✅ Funding cell 已被花费（商户已结算），无需退款");
+                log_event(
+                    "watch",
+                    "settled",
+                    json!({ "funding_tx_hash": format!("{:#x}", funding_tx_hash) }),
+                );
+                return Ok(());
+            }
+            RefundDecision::Refund => break,
+        }
+    }
+
+    println!("\n⏰ 已超时且 funding cell 仍未花费，开始退款...");
+    let funding_tx = fetch_transaction(&rpc_client, &funding_tx_hash)?;
+
+    // Co-fund channels refund the merchant's share too
+    let is_cofund = channel_info
+        .co_funded
+        .unwrap_or(funding_tx.inputs().len() > 1);
+    let user_address = Address::from_str(&channel_info.user_address)
+        .map_err(|e| anyhow!("Invalid user address: {}", e))?;
+    let merchant_address = if is_cofund {
+        Some(
+            Address::from_str(&channel_info.merchant_address)
+                .map_err(|e| anyhow!("Invalid merchant address: {}", e))?,
+        )
+    } else {
+        None
+    };
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let output_path = format!("secrets/refund_tx_{}.json", timestamp);
+
+    let (_tx_hash, refund_tx) = refund_v2::build_refund_transaction(
+        &config,
+        funding_tx_hash,
+        &funding_tx,
+        channel_info.funding_output_index,
+        &user_address,
+        merchant_address.as_ref(),
+        is_cofund,
+        fee_rate,
+        None,
+        &output_path,
+        false,
+    )
+    .await?;
+
+    println!("\n📡 广播 Refund 交易...");
+    let refund_tx_json = ckb_jsonrpc_types::TransactionView::from(refund_tx);
+    let tx_hash = rpc_client
+        .send_transaction(refund_tx_json.inner, None)
+        .map_err(|e| anyhow!("Failed to broadcast refund transaction: {:?}", e))?;
+
+    println!("✓ Refund 交易已广播");
+    println!("  - TX Hash: {:#x}", tx_hash);
+    log_event(
+        "watch",
+        "refund_broadcast",
+        json!({
+            "tx_hash": format!("{:#x}", tx_hash),
+            "output_path": output_path,
+        }),
+    );
+
+    println!("\n✅ 自动退款完成！");
+    Ok(())
+}
+
+/// Fetch a committed transaction from the node
+fn fetch_transaction(rpc_client: &CkbRpcClient, tx_hash: &H256) -> Result<TransactionView> {
+    let tx_with_status = rpc_client
+        .get_transaction(tx_hash.clone())
+        .map_err(|e| ChannelError::RpcError(format!("{:?}", e)))?
+        .ok_or_else(|| anyhow!("Funding transaction not found on chain"))?;
+    let tx_json = tx_with_status
+        .transaction
+        .ok_or_else(|| anyhow!("Transaction view not found"))?;
+
+    match tx_json.inner {
+        ckb_jsonrpc_types::Either::Left(tx_view) => {
+            let tx_packed: ckb_types::packed::Transaction = tx_view.inner.into();
+            Ok(tx_packed.into_view())
+        }
+        ckb_jsonrpc_types::Either::Right(_) => Err(anyhow!("Unexpected transaction format")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_refund_decision() {
+        let timeout = 1_800_000_000;
+
+        // Before (or exactly at) the timeout: keep waiting
+        assert_eq!(
+            should_refund(timeout - 60, timeout, Some(true)),
+            RefundDecision::Wait
+        );
+        assert_eq!(
+            should_refund(timeout, timeout, Some(true)),
+            RefundDecision::Wait
+        );
+
+        // After the timeout with a live funding cell: refund
+        assert_eq!(
+            should_refund(timeout + 1, timeout, Some(true)),
+            RefundDecision::Refund
+        );
+
+        // Funding cell spent: merchant settled, before or after the timeout
+        assert_eq!(
+            should_refund(timeout - 60, timeout, Some(false)),
+            RefundDecision::Settled
+        );
+        assert_eq!(
+            should_refund(timeout + 1, timeout, Some(false)),
+            RefundDecision::Settled
+        );
+
+        // Unknown cell (funding not committed yet): wait
+        assert_eq!(
+            should_refund(timeout + 1, timeout, None),
+            RefundDecision::Wait
+        );
+    }
+}
@@ -0,0 +1,72 @@
+/// Typed errors for channel operations
+///
+/// Library-style functions return these (wrapped in `anyhow::Error`) so callers can match
+/// on the failure kind with `err.downcast_ref::<ChannelError>()`; the CLI just prints them.
+use ckb_sdk::HumanCapacity;
+use ckb_types::H256;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ChannelError {
+    /// Not enough CKB to cover outputs and fee
+    #[error(
+        "Insufficient capacity: need {}, have {}",
+        HumanCapacity::from(*required),
+        HumanCapacity::from(*available)
+    )]
+    InsufficientCapacity { required: u64, available: u64 },
+
+    /// Not enough xUDT in the live cells to fund the channel
+    #[error("Insufficient xUDT balance: collected {collected}, required {required}")]
+    InsufficientXudt { collected: u128, required: u128 },
+
+    /// The signing key does not match the pubkey hash in the Spillman Lock args
+    #[error("{role} pubkey hash mismatch!")]
+    PubkeyHashMismatch { role: &'static str },
+
+    /// CKB node RPC failure
+    #[error("RPC error: {0}")]
+    RpcError(String),
+
+    /// Spillman Lock args are not 50 bytes plus the optional user_algorithm_id (1),
+    /// fallback timeout (8) and arbiter pubkey hash (20)
+    #[error(
+        "Invalid Spillman Lock args length: expected 50, 51, 58 or 59, or 70, 71, 78 or 79 with an arbiter, got {0}"
+    )]
+    InvalidArgsLength(usize),
+
+    /// xUDT funding cell data is not a bare 16-byte amount
+    #[error("Invalid xUDT funding cell data: expected 16 bytes, got {0}")]
+    InvalidXudtData(usize),
+
+    /// Change below `--min-change` with `--on-dust fail`
+    #[error(
+        "Change {} is below the minimum {}",
+        HumanCapacity::from(*change),
+        HumanCapacity::from(*min_change)
+    )]
+    DustChange { change: u64, min_change: u64 },
+
+    /// Timeout refund of a co-funded channel without the merchant's refund output
+    #[error("Co-funded channel refund needs a merchant refund output, got a single-output refund")]
+    CoFundedRefundWithoutMerchantOutput,
+
+    /// The Spillman Lock script rebuilt from the config differs from the one set-up recorded
+    #[error(
+        "Spillman Lock script hash mismatch: channel info has {recorded:#x}, config rebuilds {rebuilt:#x} (config changed since set-up?)"
+    )]
+    SpillmanScriptMismatch { recorded: H256, rebuilt: H256 },
+
+    /// Co-fund merchant contribution other than what the refund returns it: exactly the
+    /// occupied minimum of its refund cell and no xUDT
+    #[error(
+        "Merchant co-funding contribution {} (xUDT {xudt}) differs from its refundable {} (xUDT 0)",
+        HumanCapacity::from(*capacity),
+        HumanCapacity::from(*refundable)
+    )]
+    MerchantContributionMismatch {
+        capacity: u64,
+        xudt: u128,
+        refundable: u64,
+    },
+}
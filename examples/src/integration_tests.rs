@@ -0,0 +1,136 @@
+//! End-to-end tests driving the CLI commands against a running CKB node
+//!
+//! Compiled only with `--features integration` and skipped unless
+//! `SPILLMAN_IT_RPC_URL` is set. See docs/spillman-lock-design.md section 10 for how to
+//! prepare a dev node.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    commands,
+    tx_builder::capacity_utils::ChangePolicy,
+    utils::{chain::ConfirmationWait, channel_info::InfoFormat, config::load_config},
+};
+
+/// RPC URL of the node under test; the tests are skipped when it is absent
+const RPC_URL_ENV: &str = "SPILLMAN_IT_RPC_URL";
+/// Config with funded user / merchant keys and the deployed contracts (default: config.toml)
+const CONFIG_ENV: &str = "SPILLMAN_IT_CONFIG";
+
+/// Channel timeout lead time, beyond both the set-up minimum and the pay warning window
+const CHANNEL_TIMEOUT_SECONDS: u64 = 2 * 60 * 60;
+
+/// Wait for one confirmation of every broadcast transaction
+const WAIT: ConfirmationWait = ConfirmationWait {
+    confirmations: 1,
+    timeout: Duration::from_secs(300),
+};
+
+/// Copy the configured config into `dir`, pointing it at the node under test
+fn integration_config(rpc_url: &str, dir: &Path) -> PathBuf {
+    let config_path = std::env::var(CONFIG_ENV).unwrap_or_else(|_| "config.toml".to_string());
+    let mut config = load_config(&config_path).unwrap();
+    config.network.rpc_url = rpc_url.to_string();
+
+    let path = dir.join("config.toml");
+    fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+    path
+}
+
+/// Commitment files currently saved by `pay` under `secrets/`
+fn commitment_files() -> HashSet<PathBuf> {
+    fs::read_dir("secrets")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    let name = path.file_name().unwrap().to_string_lossy();
+                    name.starts_with("commitment_") && !name.ends_with("_signed.json")
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn test_setup_pay_settle_lifecycle() {
+    let Ok(rpc_url) = std::env::var(RPC_URL_ENV) else {
+        println!("skipping: {} is not set", RPC_URL_ENV);
+        return;
+    };
+
+    let dir = std::env::temp_dir().join(format!("spillman_integration_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let config_path = integration_config(&rpc_url, &dir);
+    let config_path = config_path.to_str().unwrap();
+    let output_dir = dir.to_str().unwrap();
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    // 1. Open the channel and wait for the funding transaction to be committed
+    commands::setup::execute_v2(
+        config_path,
+        output_dir,
+        None,
+        None,
+        Some(now + CHANNEL_TIMEOUT_SECONDS),
+        1000,
+        ChangePolicy::default(),
+        false,
+        false,
+        true,
+        false,
+        Some(WAIT),
+        None,
+        InfoFormat::Json,
+        None,
+    )
+    .await
+    .expect("set-up should broadcast an accepted funding transaction");
+    let channel_file = dir.join("secrets").join("channel_info.json");
+    assert!(channel_file.exists());
+
+    // 2. Pay off-chain: the user-signed commitment is saved under secrets/
+    let before = commitment_files();
+    commands::pay::execute(
+        "100",
+        channel_file.to_str().unwrap(),
+        config_path,
+        1000,
+        false,
+        true,
+        false,
+        false,
+        None,
+    )
+    .await
+    .expect("pay should build a commitment transaction");
+    let created: Vec<_> = commitment_files().difference(&before).cloned().collect();
+    assert_eq!(created.len(), 1, "pay should save exactly one commitment");
+
+    // 3. The merchant countersigns and settles on-chain
+    commands::settle::execute(
+        created[0].to_str().unwrap(),
+        channel_file.to_str().unwrap(),
+        config_path,
+        true,
+        false,
+        Some(WAIT),
+        None,
+        false,
+        false,
+    )
+    .await
+    .expect("settle should broadcast an accepted commitment transaction");
+
+    fs::remove_file(&created[0]).ok();
+    fs::remove_dir_all(&dir).ok();
+}
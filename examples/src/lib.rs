@@ -0,0 +1,21 @@
+//! Spillman channel library behind `spillman-cli`
+//!
+//! [`Channel`] drives a channel from Rust code; the CLI commands in [`commands`] wrap it
+//! with config files, channel info files and progress output.
+
+pub mod channel;
+pub mod commands;
+pub mod error;
+#[cfg(all(test, feature = "integration"))]
+mod integration_tests;
+pub mod signer;
+pub mod tx_builder;
+pub mod utils;
+
+pub use channel::{
+    BuilderBackend, Channel, ChannelBackend, ChannelParams, ChannelState, ChannelStatus,
+};
+pub use tx_builder::signing::{
+    spillman_commitment_signing_message, spillman_signing_message,
+    spillman_signing_message_for_version,
+};
@@ -1,14 +1,28 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 
-mod commands;
-mod tx_builder;
-mod utils;
+use spillman_channel_examples::{commands, tx_builder, utils};
 
 #[derive(Parser)]
 #[command(name = "spillman-cli")]
 #[command(about = "Spillman Channel CLI - 单向支付通道管理工具", long_about = None)]
 struct Cli {
+    /// 输出结构化 JSON 事件到 stderr（便于自动化流程解析）
+    #[arg(long, global = true)]
+    json_logs: bool,
+
+    /// 忽略本地缓存的 genesis cell deps，重新从链上查询
+    #[arg(long, global = true)]
+    refresh_deps: bool,
+
+    /// 节点 RPC 请求超时（秒），覆盖配置中的 network.rpc_timeout_secs
+    #[arg(long, global = true)]
+    rpc_timeout: Option<u64>,
+
+    /// 广播用交易文件格式：ckb-cli 会额外生成 ckb-cli tx 格式的副本
+    #[arg(long, global = true, value_enum, default_value_t = utils::ckb_cli::TxEmitFormat::Json)]
+    emit: utils::ckb_cli::TxEmitFormat,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -41,10 +55,26 @@ enum Commands {
         #[arg(long, default_value = "1000")]
         fee_rate: u64,
 
+        /// 费率策略（slow|standard|fast，查询节点费率统计，覆盖 --fee-rate）
+        #[arg(long, value_enum)]
+        fee_rate_strategy: Option<utils::fee::FeeRateStrategy>,
+
+        /// 最小找零（CKB），低于该值的用户找零按 --on-dust 处理（默认 0：仅处理不足以成 cell 的找零）
+        #[arg(long, default_value = "0")]
+        min_change: ckb_sdk::HumanCapacity,
+
+        /// 找零过小时的处理方式（fail|burn|add-to-funding，默认 burn：作为手续费）
+        #[arg(long, value_enum, default_value = "burn")]
+        on_dust: tx_builder::capacity_utils::DustPolicy,
+
         /// 是否使用 co-fund 模式（User + Merchant 共同出资）
         #[arg(long, default_value = "false")]
         co_fund: bool,
 
+        /// 创建 args version 1 的通道：签名同时覆盖 cell deps（等同 channel.bind_cell_deps = true）
+        #[arg(long)]
+        bind_cell_deps: bool,
+
         /// 使用 funding_v2 实现（新版本）
         #[arg(long, default_value = "false")]
         use_v2: bool,
@@ -53,9 +83,53 @@ enum Commands {
         #[arg(long)]
         broadcast: bool,
 
+        /// 不广播，改为输出 send_transaction 的 JSON-RPC 请求（最后一行，可通过管道交给 curl 等工具发送，需 --use-v2）
+        #[arg(long, requires = "use_v2", conflicts_with_all = ["broadcast", "batch", "check", "save_unsigned"])]
+        raw: bool,
+
+        /// 广播后等待 N 个确认再退出（仅 v2 / batch，需配合 --broadcast）
+        #[arg(long, requires = "broadcast")]
+        wait_confirmations: Option<u64>,
+
+        /// 等待确认的超时时间（秒）
+        #[arg(long, default_value = "600")]
+        confirmation_timeout: u64,
+
         /// xUDT amount (for xUDT channels, optional)
         #[arg(long)]
         xudt_amount: Option<u128>,
+
+        /// 通道信息文件格式（json|toml|yaml，决定 channel_info 的扩展名）
+        #[arg(long, value_enum, default_value = "json")]
+        info_format: utils::channel_info::InfoFormat,
+
+        /// 批量模式：从 TOML 文件读取多个通道（user_address / capacity_ckb / timeout_timestamp），
+        /// 使用 v2 实现逐个创建，第 i 个通道写入 secrets/<i>/
+        #[arg(long, conflicts_with_all = ["capacity", "timeout_timestamp"])]
+        batch: Option<String>,
+
+        /// 预检模式：只查询用户余额并估算所需 CKB（通道容量 + 手续费 + co-fund buffer），不构建交易
+        #[arg(long, conflicts_with_all = ["batch", "broadcast"])]
+        check: bool,
+
+        /// 不签名，将 funding 交易（含正确大小的占位 witness）保存到该路径，供外部工具签名（需 --use-v2）
+        #[arg(long, requires = "use_v2", conflicts_with_all = ["co_fund", "batch", "broadcast", "check"])]
+        save_unsigned: Option<String>,
+    },
+
+    /// 生成新的用户/商户密钥对和配置文件模板
+    Generate {
+        /// 网络（testnet 或 mainnet）
+        #[arg(long, default_value = "testnet")]
+        network: String,
+
+        /// 配置文件输出路径
+        #[arg(long, default_value = "config.toml")]
+        output: String,
+
+        /// 覆盖已存在的配置文件
+        #[arg(long)]
+        force: bool,
     },
 
     /// 签名交易
@@ -65,21 +139,41 @@ enum Commands {
         tx_file: String,
 
         /// 私钥文件路径
-        #[arg(long)]
-        privkey_path: String,
+        #[arg(long, required_unless_present = "aggregate")]
+        privkey_path: Option<String>,
 
         /// 是否为商户签名
         #[arg(long, default_value = "false")]
         is_merchant: bool,
+
+        /// 多签商户：只用本私钥做部分签名，输出到 --out（65 字节签名 + 多签配置中的位置）
+        #[arg(long, requires = "out")]
+        partial: bool,
+
+        /// 部分签名 / 聚合后交易的输出文件路径
+        #[arg(long)]
+        out: Option<String>,
+
+        /// 多签商户：聚合 --sigs 中的部分签名，组装最终 witness
+        #[arg(long, conflicts_with = "partial", requires = "sigs")]
+        aggregate: bool,
+
+        /// 部分签名文件列表（逗号分隔，如 a.sig,b.sig）
+        #[arg(long, value_delimiter = ',')]
+        sigs: Vec<String>,
+
+        /// 配置文件路径（用于查询 Spillman cell 的 args 版本）
+        #[arg(long, default_value = "config.toml")]
+        config: String,
     },
 
     /// 创建链下支付（commitment transaction）
     Pay {
-        /// 支付金额（支持小数，如 "100" 或 "100.5" CKB）
+        /// 支付金额（支持小数，如 "100" 或 "100.5" CKB；"all" 表示全部可支付余额，"50%" 表示其中一半）
         #[arg(long)]
         amount: String,
 
-        /// 通道信息文件路径（包含 Spillman Lock cell 信息）
+        /// 通道信息文件路径（包含 Spillman Lock cell 信息，按扩展名识别 json/toml/yaml）
         #[arg(long, default_value = "secrets/channel_info.json")]
         channel_file: String,
 
@@ -90,6 +184,30 @@ enum Commands {
         /// 交易费率（shannons per KB，默认 1000）
         #[arg(long, default_value = "1000")]
         fee_rate: u64,
+
+        /// 费率策略（slow|standard|fast，查询节点费率统计，覆盖 --fee-rate）
+        #[arg(long, value_enum)]
+        fee_rate_strategy: Option<utils::fee::FeeRateStrategy>,
+
+        /// 将 --amount 解释为 xUDT 代币数量（精度取自 config.usdi.decimal，如 "5" 表示 5 USDI）
+        #[arg(long)]
+        xudt: bool,
+
+        /// 使用 commitment_v2 实现（新版本）
+        #[arg(long, default_value = "false")]
+        use_v2: bool,
+
+        /// 最终支付：同时生成关闭通道交易（secrets/close_*.json），等待商户补签后广播
+        #[arg(long)]
+        close_after: bool,
+
+        /// 距离超时不足 [channel] timeout_warn_seconds（默认 1 小时）时仍继续支付
+        #[arg(long)]
+        force_near_timeout: bool,
+
+        /// 不签名，将 commitment 交易（含正确大小的占位 witness）保存到该路径，供外部工具签名（需 --use-v2）
+        #[arg(long, requires = "use_v2", conflicts_with = "close_after")]
+        save_unsigned: Option<String>,
     },
 
     /// 商户结算 commitment transaction
@@ -98,6 +216,10 @@ enum Commands {
         #[arg(long)]
         tx_file: String,
 
+        /// 通道信息文件路径（pay 在同目录的 commitments/ 中记录每笔 commitment，用于识别过期的 commitment）
+        #[arg(long, default_value = "secrets/channel_info.json")]
+        channel_file: String,
+
         /// 配置文件路径
         #[arg(long, default_value = "config.toml")]
         config: String,
@@ -105,6 +227,88 @@ enum Commands {
         /// 是否自动广播交易到链上（默认不广播，需要明确指定）
         #[arg(long)]
         broadcast: bool,
+
+        /// 不广播，改为输出 send_transaction 的 JSON-RPC 请求（最后一行，可通过管道交给 curl 等工具发送）
+        #[arg(long, conflicts_with = "broadcast")]
+        raw: bool,
+
+        /// 广播后等待 N 个确认再退出（需配合 --broadcast）
+        #[arg(long, requires = "broadcast")]
+        wait_confirmations: Option<u64>,
+
+        /// 等待确认的超时时间（秒）
+        #[arg(long, default_value = "600")]
+        confirmation_timeout: u64,
+
+        /// 费率策略（slow|standard|fast），广播前检查 commitment 费率是否低于当前网络费率
+        #[arg(long, value_enum)]
+        fee_rate_strategy: Option<utils::fee::FeeRateStrategy>,
+
+        /// Commitment 费率低于网络费率时，由商户追加手续费 input 和找零（签名的通道输出不变）
+        #[arg(long, requires = "fee_rate_strategy")]
+        bump_fee: bool,
+
+        /// 允许结算非最新（商户金额更低）的 commitment
+        #[arg(long)]
+        allow_stale: bool,
+    },
+
+    /// 商户提取已收款项，用户余额转入新的 Spillman cell（通道保持开启，双方签名）
+    Splice {
+        /// 最新的 commitment transaction 文件路径
+        #[arg(long)]
+        tx_file: String,
+
+        /// 通道信息文件路径（pay 在同目录的 commitments/ 中记录每笔 commitment，用于识别过期的 commitment）
+        #[arg(long, default_value = "secrets/channel_info.json")]
+        channel_file: String,
+
+        /// 配置文件路径
+        #[arg(long, default_value = "config.toml")]
+        config: String,
+
+        /// 新通道的超时时间戳（Unix 秒）
+        #[arg(long)]
+        timeout_timestamp: u64,
+
+        /// 手续费率（shannon/KB，默认 1000），由商户提取的金额支付
+        #[arg(long, default_value = "1000")]
+        fee_rate: u64,
+
+        /// 费率策略（slow|standard|fast，查询节点费率统计，覆盖 --fee-rate）
+        #[arg(long, value_enum)]
+        fee_rate_strategy: Option<utils::fee::FeeRateStrategy>,
+
+        /// 是否自动广播交易到链上
+        #[arg(long)]
+        broadcast: bool,
+    },
+
+    /// 双方协作将通道余额转入超时时间更晚的新 Spillman cell（商户支付手续费）
+    Rotate {
+        /// 通道信息文件路径
+        #[arg(long, default_value = "secrets/channel_info.json")]
+        channel_file: String,
+
+        /// 配置文件路径
+        #[arg(long, default_value = "config.toml")]
+        config: String,
+
+        /// 新通道的超时时间戳（Unix 秒，须晚于当前超时）
+        #[arg(long)]
+        new_timeout: u64,
+
+        /// 手续费率（shannon/KB，默认 1000），由商户 cell 支付
+        #[arg(long, default_value = "1000")]
+        fee_rate: u64,
+
+        /// 费率策略（slow|standard|fast，查询节点费率统计，覆盖 --fee-rate）
+        #[arg(long, value_enum)]
+        fee_rate_strategy: Option<utils::fee::FeeRateStrategy>,
+
+        /// 是否自动广播交易到链上
+        #[arg(long)]
+        broadcast: bool,
     },
 
     /// 用户退款（超时后）
@@ -121,15 +325,149 @@ enum Commands {
         #[arg(long, default_value = "1000")]
         fee_rate: u64,
 
+        /// 费率策略（slow|standard|fast，查询节点费率统计，覆盖 --fee-rate）
+        #[arg(long, value_enum)]
+        fee_rate_strategy: Option<utils::fee::FeeRateStrategy>,
+
         /// 使用 refund_v2 实现（新版本）
         #[arg(long, default_value = "false")]
         use_v2: bool,
+
+        /// 通道信息文件路径（可选，用于校验 funding 交易与 xUDT 信息）
+        #[arg(long)]
+        channel_file: Option<String>,
+
+        /// 提高手续费率替换按 --fee-rate 构建的退款（shannon/KB，商户与用户重新签名，需 --use-v2）
+        #[arg(long, requires = "use_v2")]
+        bump_fee_rate: Option<u64>,
+
+        /// 不签名，将 refund 交易（含正确大小的占位 witness）保存到该路径，供外部工具签名（需 --use-v2）
+        #[arg(long, requires = "use_v2")]
+        save_unsigned: Option<String>,
+
+        /// Co-fund 退款时商户取回占用容量的地址（默认为配置中的商户地址，需商户签名）
+        #[arg(long)]
+        merchant_out: Option<String>,
+
+        /// 输出 refund 交易的 send_transaction JSON-RPC 请求（最后一行，超时后通过管道交给 curl 等工具发送）
+        #[arg(long, conflicts_with = "save_unsigned")]
+        raw: bool,
+    },
+
+    /// 监控通道，超时后自动构建并广播退款交易
+    Watch {
+        /// 通道信息文件路径
+        #[arg(long, default_value = "secrets/channel_info.json")]
+        channel_file: String,
+
+        /// 配置文件路径
+        #[arg(long, default_value = "config.toml")]
+        config: String,
+
+        /// 手续费率（shannon/KB，默认 1000）
+        #[arg(long, default_value = "1000")]
+        fee_rate: u64,
+
+        /// 费率策略（slow|standard|fast，查询节点费率统计，覆盖 --fee-rate）
+        #[arg(long, value_enum)]
+        fee_rate_strategy: Option<utils::fee::FeeRateStrategy>,
+
+        /// 轮询间隔（秒，默认 60）
+        #[arg(long, default_value = "60")]
+        poll_interval: u64,
+    },
+
+    /// 解析 Spillman Lock args，或 commitment 交易中用户/商户的分配
+    Decode {
+        /// 50 字节 args（hex，可带 0x 前缀）
+        #[arg(long, required_unless_present = "commitment")]
+        args: Option<String>,
+
+        /// Commitment 交易文件，打印用户余额、商户收款和手续费
+        #[arg(long, requires = "funding_capacity")]
+        commitment: Option<String>,
+
+        /// 通道资金（funding cell 的 CKB 容量），配合 --commitment 计算手续费
+        #[arg(long)]
+        funding_capacity: Option<ckb_sdk::HumanCapacity>,
+    },
+
+    /// 查看通道的全部 commitment 支付记录
+    History {
+        /// 通道目录（channel_info 所在目录，支付记录保存在其下的 commitments/）
+        #[arg(long, default_value = "secrets")]
+        channel_dir: String,
+    },
+
+    /// 备份通道目录（通道信息、funding / refund 交易、支付记录，不含私钥）
+    Backup {
+        /// 通道目录（channel_info 所在目录）
+        #[arg(long, default_value = "secrets")]
+        channel_dir: String,
+
+        /// 备份文件路径
+        #[arg(long, default_value = "channel_backup.json")]
+        output: String,
+    },
+
+    /// 从备份文件恢复通道目录
+    Restore {
+        /// 备份文件路径
+        #[arg(long)]
+        snapshot: String,
+
+        /// 恢复到的通道目录（不能已有通道信息）
+        #[arg(long, default_value = "secrets")]
+        channel_dir: String,
+    },
+
+    /// 本地运行合约验证交易（广播前检查 settle / refund 交易能否通过链上脚本）
+    Verify {
+        /// 交易文件路径
+        #[arg(long)]
+        tx_file: String,
+
+        /// 配置文件路径
+        #[arg(long, default_value = "config.toml")]
+        config: String,
+    },
+
+    /// 本地测量合约解锁路径消耗的 cycles（需启用 bench feature）
+    #[cfg(feature = "bench")]
+    Bench {
+        /// 解锁路径
+        #[arg(long, value_enum, default_value = "commitment")]
+        path: commands::bench::BenchPath,
+
+        /// 多签商户，格式 M-of-N（如 2-of-3），不指定则为单签
+        #[arg(long)]
+        multisig: Option<String>,
+
+        /// 使用 xUDT 通道
+        #[arg(long)]
+        xudt: bool,
+
+        /// Spillman Lock 合约二进制路径
+        #[arg(long, default_value = "build/release/spillman-lock")]
+        binary: String,
+
+        /// ckb-auth 二进制路径
+        #[arg(long, default_value = "deps/auth")]
+        auth_binary: String,
+
+        /// simple_udt 二进制路径（--xudt 时使用）
+        #[arg(long, default_value = "deps/simple_udt")]
+        udt_binary: String,
     },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
+    utils::log::set_json_logs(cli.json_logs);
+    utils::deps::set_refresh_deps(cli.refresh_deps);
+    utils::ckb_cli::set_emit_format(cli.emit);
+    utils::rpc::set_cli_timeout(cli.rpc_timeout);
 
     match cli.command {
         Commands::SetUp {
@@ -139,12 +477,49 @@ async fn main() -> Result<()> {
             capacity,
             timeout_timestamp,
             fee_rate,
+            fee_rate_strategy,
+            min_change,
+            on_dust,
             co_fund,
+            bind_cell_deps,
             use_v2,
             broadcast,
+            raw,
+            wait_confirmations,
+            confirmation_timeout,
             xudt_amount,
+            info_format,
+            batch,
+            check,
+            save_unsigned,
         } => {
-            if use_v2 {
+            let fee_rate = utils::fee::resolve_cli_fee_rate(&config, fee_rate, fee_rate_strategy)?;
+            if check {
+                commands::setup::execute_check(&config, capacity, fee_rate, co_fund, xudt_amount)
+                    .await?;
+                return Ok(());
+            }
+            let change_policy =
+                tx_builder::capacity_utils::ChangePolicy::from_cli(min_change, on_dust);
+            let wait =
+                utils::chain::ConfirmationWait::from_cli(wait_confirmations, confirmation_timeout);
+            if let Some(batch) = batch {
+                commands::setup::execute_batch(
+                    &config,
+                    &batch,
+                    &output_dir,
+                    merchant_address.as_deref(),
+                    fee_rate,
+                    change_policy,
+                    co_fund,
+                    bind_cell_deps,
+                    broadcast,
+                    wait,
+                    xudt_amount,
+                    info_format,
+                )
+                .await?;
+            } else if use_v2 {
                 // Use v2 implementation (funding_v2)
                 commands::setup::execute_v2(
                     &config,
@@ -153,9 +528,15 @@ async fn main() -> Result<()> {
                     capacity,
                     timeout_timestamp,
                     fee_rate,
+                    change_policy,
                     co_fund,
+                    bind_cell_deps,
                     broadcast,
+                    raw,
+                    wait,
                     xudt_amount,
+                    info_format,
+                    save_unsigned.as_deref(),
                 )
                 .await?;
             } else {
@@ -167,47 +548,226 @@ async fn main() -> Result<()> {
                     capacity,
                     timeout_timestamp,
                     fee_rate,
+                    change_policy,
                     co_fund,
+                    bind_cell_deps,
+                    info_format,
                 )
                 .await?;
             }
         }
+        Commands::Generate {
+            network,
+            output,
+            force,
+        } => {
+            commands::generate::execute(&network, &output, force).await?;
+        }
         Commands::SignTx {
             tx_file,
             privkey_path,
             is_merchant,
+            partial,
+            out,
+            aggregate,
+            sigs,
+            config,
         } => {
-            commands::sign::execute(&tx_file, &privkey_path, is_merchant).await?;
+            if aggregate {
+                commands::sign::execute_aggregate(&tx_file, &sigs, out.as_deref(), &config).await?;
+            } else {
+                let privkey_path = privkey_path.unwrap_or_default();
+                if partial {
+                    let out = out.unwrap_or_default();
+                    commands::sign::execute_partial(&tx_file, &privkey_path, &out, &config).await?;
+                } else {
+                    commands::sign::execute(&tx_file, &privkey_path, is_merchant).await?;
+                }
+            }
         }
         Commands::Pay {
             amount,
             channel_file,
             config,
             fee_rate,
+            fee_rate_strategy,
+            xudt,
+            use_v2,
+            close_after,
+            force_near_timeout,
+            save_unsigned,
         } => {
-            commands::pay::execute(&amount, &channel_file, &config, fee_rate).await?;
+            let fee_rate = utils::fee::resolve_cli_fee_rate(&config, fee_rate, fee_rate_strategy)?;
+            commands::pay::execute(
+                &amount,
+                &channel_file,
+                &config,
+                fee_rate,
+                xudt,
+                use_v2,
+                close_after,
+                force_near_timeout,
+                save_unsigned.as_deref(),
+            )
+            .await?;
         }
         Commands::Settle {
             tx_file,
+            channel_file,
             config,
             broadcast,
+            raw,
+            wait_confirmations,
+            confirmation_timeout,
+            fee_rate_strategy,
+            bump_fee,
+            allow_stale,
         } => {
-            commands::settle::execute(&tx_file, &config, broadcast).await?;
+            let wait =
+                utils::chain::ConfirmationWait::from_cli(wait_confirmations, confirmation_timeout);
+            commands::settle::execute(
+                &tx_file,
+                &channel_file,
+                &config,
+                broadcast,
+                raw,
+                wait,
+                fee_rate_strategy,
+                bump_fee,
+                allow_stale,
+            )
+            .await?;
+        }
+        Commands::Splice {
+            tx_file,
+            channel_file,
+            config,
+            timeout_timestamp,
+            fee_rate,
+            fee_rate_strategy,
+            broadcast,
+        } => {
+            let fee_rate = utils::fee::resolve_cli_fee_rate(&config, fee_rate, fee_rate_strategy)?;
+            commands::splice::execute(
+                &tx_file,
+                &channel_file,
+                &config,
+                timeout_timestamp,
+                fee_rate,
+                broadcast,
+            )
+            .await?;
+        }
+        Commands::Rotate {
+            channel_file,
+            config,
+            new_timeout,
+            fee_rate,
+            fee_rate_strategy,
+            broadcast,
+        } => {
+            let fee_rate = utils::fee::resolve_cli_fee_rate(&config, fee_rate, fee_rate_strategy)?;
+            commands::rotate::execute(&channel_file, &config, new_timeout, fee_rate, broadcast)
+                .await?;
         }
         Commands::Refund {
             tx_file,
             config,
             fee_rate,
+            fee_rate_strategy,
             use_v2,
+            channel_file,
+            bump_fee_rate,
+            save_unsigned,
+            merchant_out,
+            raw,
         } => {
+            let fee_rate = utils::fee::resolve_cli_fee_rate(&config, fee_rate, fee_rate_strategy)?;
             if use_v2 {
                 // Use v2 implementation (refund_v2)
-                commands::refund::execute_v2(&tx_file, &config, fee_rate).await?;
+                commands::refund::execute_v2(
+                    &tx_file,
+                    &config,
+                    fee_rate,
+                    bump_fee_rate,
+                    channel_file.as_deref(),
+                    save_unsigned.as_deref(),
+                    merchant_out.as_deref(),
+                    raw,
+                )
+                .await?;
             } else {
                 // Use v1 implementation (original refund)
-                commands::refund::execute(&tx_file, &config, fee_rate).await?;
+                commands::refund::execute(
+                    &tx_file,
+                    &config,
+                    fee_rate,
+                    channel_file.as_deref(),
+                    merchant_out.as_deref(),
+                    raw,
+                )
+                .await?;
             }
         }
+        Commands::Watch {
+            channel_file,
+            config,
+            fee_rate,
+            fee_rate_strategy,
+            poll_interval,
+        } => {
+            let fee_rate = utils::fee::resolve_cli_fee_rate(&config, fee_rate, fee_rate_strategy)?;
+            commands::watch::execute(&channel_file, &config, fee_rate, poll_interval).await?;
+        }
+        Commands::Decode {
+            args,
+            commitment,
+            funding_capacity,
+        } => {
+            if let Some(args) = args {
+                commands::decode::execute(&args).await?;
+            }
+            if let (Some(tx_file), Some(funding_capacity)) = (commitment, funding_capacity) {
+                commands::decode::execute_commitment(&tx_file, funding_capacity.into()).await?;
+            }
+        }
+        Commands::History { channel_dir } => {
+            commands::history::execute(&channel_dir).await?;
+        }
+        Commands::Backup {
+            channel_dir,
+            output,
+        } => {
+            commands::backup::execute_backup(&channel_dir, &output).await?;
+        }
+        Commands::Restore {
+            snapshot,
+            channel_dir,
+        } => {
+            commands::backup::execute_restore(&snapshot, &channel_dir).await?;
+        }
+        Commands::Verify { tx_file, config } => {
+            commands::verify::execute(&tx_file, &config).await?;
+        }
+        #[cfg(feature = "bench")]
+        Commands::Bench {
+            path,
+            multisig,
+            xudt,
+            binary,
+            auth_binary,
+            udt_binary,
+        } => {
+            commands::bench::execute(
+                path,
+                multisig.as_deref(),
+                xudt,
+                &binary,
+                &auth_binary,
+                &udt_binary,
+            )
+            .await?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,133 @@
+/// Pluggable signing for channel transactions
+///
+/// Builders take an `Arc<dyn Signer>` instead of raw secret keys, so keys no longer
+/// have to live in the process: a hardware wallet or remote signer only needs to
+/// implement [`Signer`]. [`RawKeySigner`] keeps the current in-memory behavior.
+use anyhow::{anyhow, Result};
+use ckb_crypto::secp::Privkey;
+use ckb_sdk::traits::SignerError;
+use ckb_types::{bytes::Bytes, core::TransactionView};
+use std::sync::Arc;
+
+use crate::utils::crypto::pubkey_hash;
+
+/// Size of a recoverable secp256k1 signature
+pub const RECOVERABLE_SIGNATURE_SIZE: usize = 65;
+
+/// Produces recoverable secp256k1 signatures for keys identified by blake160(pubkey)
+pub trait Signer: Send + Sync {
+    /// Sign a 32-byte message with the key whose pubkey hash is `pubkey_hash`
+    fn sign(
+        &self,
+        message: [u8; 32],
+        pubkey_hash: &[u8; 20],
+    ) -> Result<[u8; RECOVERABLE_SIGNATURE_SIZE]>;
+
+    /// Whether this signer holds the key for `pubkey_hash`
+    fn has_key(&self, pubkey_hash: &[u8; 20]) -> bool;
+}
+
+/// In-memory signer over raw secret keys (e.g. parsed from config.toml)
+pub struct RawKeySigner {
+    keys: Vec<([u8; 20], Privkey)>,
+}
+
+impl RawKeySigner {
+    pub fn new(secret_keys: Vec<secp256k1::SecretKey>) -> Result<Self> {
+        let keys = secret_keys
+            .into_iter()
+            .map(|secret_key| {
+                let privkey = Privkey::from_slice(&secret_key.secret_bytes());
+                let pubkey = privkey
+                    .pubkey()
+                    .map_err(|e| anyhow!("Failed to derive pubkey: {:?}", e))?;
+                Ok((pubkey_hash(&pubkey), privkey))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { keys })
+    }
+
+    /// Shared signer, ready to hand to the transaction builders
+    pub fn new_arc(secret_keys: Vec<secp256k1::SecretKey>) -> Result<Arc<dyn Signer>> {
+        Ok(Arc::new(Self::new(secret_keys)?))
+    }
+}
+
+impl Signer for RawKeySigner {
+    fn sign(
+        &self,
+        message: [u8; 32],
+        pubkey_hash: &[u8; 20],
+    ) -> Result<[u8; RECOVERABLE_SIGNATURE_SIZE]> {
+        let (_, privkey) = self
+            .keys
+            .iter()
+            .find(|(hash, _)| hash == pubkey_hash)
+            .ok_or_else(|| anyhow!("No key for pubkey hash {}", hex::encode(pubkey_hash)))?;
+        let signature = privkey
+            .sign_recoverable(&message.into())
+            .map_err(|e| anyhow!("Failed to sign: {:?}", e))?
+            .serialize();
+        signature
+            .try_into()
+            .map_err(|sig: Vec<u8>| anyhow!("Invalid signature length: {}", sig.len()))
+    }
+
+    fn has_key(&self, pubkey_hash: &[u8; 20]) -> bool {
+        self.keys.iter().any(|(hash, _)| hash == pubkey_hash)
+    }
+}
+
+/// Adapter exposing a [`Signer`] to ckb-sdk's sighash/multisig unlockers
+#[derive(Clone)]
+pub struct SdkSigner(pub Arc<dyn Signer>);
+
+impl ckb_sdk::traits::Signer for SdkSigner {
+    fn match_id(&self, id: &[u8]) -> bool {
+        <&[u8; 20]>::try_from(id).is_ok_and(|hash| self.0.has_key(hash))
+    }
+
+    fn sign(
+        &self,
+        id: &[u8],
+        message: &[u8],
+        recoverable: bool,
+        _tx: &TransactionView,
+    ) -> Result<Bytes, SignerError> {
+        let hash: &[u8; 20] = id.try_into().map_err(|_| SignerError::IdNotFound)?;
+        if !recoverable {
+            return Err(SignerError::Other(anyhow!(
+                "Only recoverable signatures are supported"
+            )));
+        }
+        let message: [u8; 32] = message.try_into().map_err(|_| {
+            SignerError::InvalidMessage(format!("expected length: 32, got: {}", message.len()))
+        })?;
+        let signature = self.0.sign(message, hash)?;
+        Ok(Bytes::from(signature.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_key_signer_matches_privkey_signature() {
+        let secret_key = secp256k1::SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let privkey = Privkey::from_slice(&[7u8; 32]);
+        let hash = pubkey_hash(&privkey.pubkey().unwrap());
+        let signer = RawKeySigner::new(vec![secret_key]).unwrap();
+        let message = [0x42u8; 32];
+
+        assert!(signer.has_key(&hash));
+        assert!(!signer.has_key(&[0u8; 20]));
+        assert!(signer.sign(message, &[0u8; 20]).is_err());
+
+        let expected = privkey
+            .sign_recoverable(&message.into())
+            .unwrap()
+            .serialize();
+        assert_eq!(signer.sign(message, &hash).unwrap().to_vec(), expected);
+    }
+}
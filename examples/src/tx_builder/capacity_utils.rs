@@ -0,0 +1,229 @@
+/// Capacity calculation utilities shared across tx_builder modules
+///
+/// Setup, commitment and refund must agree on the merchant's minimum cell capacity,
+/// otherwise the co-funded amount and the refunded amount drift apart.
+use anyhow::{Context, Result};
+use ckb_sdk::HumanCapacity;
+use ckb_types::{
+    core::Capacity,
+    packed::{CellOutput, Script},
+    prelude::*,
+};
+use clap::ValueEnum;
+
+use crate::error::ChannelError;
+
+/// Size of xUDT cell data (u128 amount, little-endian)
+pub const XUDT_DATA_SIZE: usize = 16;
+
+/// Minimum occupied capacity (shannons) of a merchant cell
+///
+/// CKB-only channels use an empty-data cell; xUDT channels add the type script and
+/// XUDT_DATA_SIZE bytes of data.
+pub fn merchant_min_capacity(merchant_lock: &Script, xudt_type: Option<&Script>) -> Result<u64> {
+    let data_size = if xudt_type.is_some() {
+        XUDT_DATA_SIZE
+    } else {
+        0
+    };
+
+    CellOutput::new_builder()
+        .capacity(Capacity::shannons(0))
+        .lock(merchant_lock.clone())
+        .type_(xudt_type.cloned().pack())
+        .build()
+        .occupied_capacity(Capacity::bytes(data_size)?)
+        .map(|capacity| capacity.as_u64())
+        .context("computing merchant occupied capacity")
+}
+
+/// Check a co-funding merchant contribution against what a refund can return it
+///
+/// The Spillman Lock pins the refund's merchant output to exactly its occupied capacity with
+/// no xUDT, so any capacity above `merchant_min_capacity` or any xUDT the merchant puts in
+/// would end up with the user.
+pub fn check_merchant_contribution(
+    capacity: u64,
+    xudt: u128,
+    merchant_lock: &Script,
+    xudt_type: Option<&Script>,
+) -> Result<()> {
+    let refundable = merchant_min_capacity(merchant_lock, xudt_type)?;
+    if capacity != refundable || xudt != 0 {
+        return Err(ChannelError::MerchantContributionMismatch {
+            capacity,
+            xudt,
+            refundable,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// What happens to funding change below the minimum (`--on-dust`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum DustPolicy {
+    /// Refuse to build the transaction
+    Fail,
+    /// Drop the change, it becomes part of the fee (the historical behavior)
+    #[default]
+    Burn,
+    /// Put the change into the Spillman cell, the user gets it back with the channel
+    AddToFunding,
+}
+
+/// Change handling of a funding transaction (`--min-change` / `--on-dust`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChangePolicy {
+    /// Change below this many shannons is dust, on top of any change too small for a cell
+    pub min_change: u64,
+    pub on_dust: DustPolicy,
+}
+
+/// What a change amount turns into under a ChangePolicy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeOutcome {
+    /// No change left
+    None,
+    /// A change cell of this capacity
+    Output(u64),
+    /// Dust paid as fee
+    Burn(u64),
+    /// Dust added to the Spillman cell
+    AddToFunding(u64),
+}
+
+impl ChangePolicy {
+    /// Build the policy from the CLI flags
+    pub fn from_cli(min_change: HumanCapacity, on_dust: DustPolicy) -> Self {
+        Self {
+            min_change: min_change.into(),
+            on_dust,
+        }
+    }
+
+    /// Decide what `change` shannons become; `min_occupied` is the smallest change cell
+    pub fn resolve(&self, change: u64, min_occupied: u64) -> Result<ChangeOutcome> {
+        if change == 0 {
+            return Ok(ChangeOutcome::None);
+        }
+        let min_change = self.min_change.max(min_occupied);
+        if change >= min_change {
+            return Ok(ChangeOutcome::Output(change));
+        }
+        match self.on_dust {
+            DustPolicy::Fail => Err(ChannelError::DustChange { change, min_change }.into()),
+            DustPolicy::Burn => Ok(ChangeOutcome::Burn(change)),
+            DustPolicy::AddToFunding => Ok(ChangeOutcome::AddToFunding(change)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::{core::ScriptHashType, H256};
+
+    #[test]
+    fn test_merchant_min_capacity() {
+        // secp256k1 sighash lock: code_hash(32) + hash_type(1) + args(20)
+        let merchant_lock = Script::new_builder()
+            .code_hash(H256([0x11; 32]).pack())
+            .hash_type(ScriptHashType::Type)
+            .args([0u8; 20].to_vec().pack())
+            .build();
+        // xUDT type: code_hash(32) + hash_type(1) + args(32)
+        let xudt_type = Script::new_builder()
+            .code_hash(H256([0x22; 32]).pack())
+            .hash_type(ScriptHashType::Data1)
+            .args([0u8; 32].to_vec().pack())
+            .build();
+
+        // capacity(8) + lock(53) = 61 CKB
+        assert_eq!(
+            merchant_min_capacity(&merchant_lock, None).unwrap(),
+            61 * 100_000_000
+        );
+        // capacity(8) + lock(53) + type(65) + data(16) = 142 CKB
+        assert_eq!(
+            merchant_min_capacity(&merchant_lock, Some(&xudt_type)).unwrap(),
+            142 * 100_000_000
+        );
+    }
+
+    #[test]
+    fn test_check_merchant_contribution_rejects_over_contribution() {
+        let merchant_lock = Script::new_builder()
+            .code_hash(H256([0x11; 32]).pack())
+            .hash_type(ScriptHashType::Type)
+            .args([0u8; 20].to_vec().pack())
+            .build();
+        let min = merchant_min_capacity(&merchant_lock, None).unwrap();
+
+        assert!(check_merchant_contribution(min, 0, &merchant_lock, None).is_ok());
+        // More capacity than the refund returns would be stranded
+        let err = check_merchant_contribution(min + 1, 0, &merchant_lock, None).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChannelError>(),
+            Some(ChannelError::MerchantContributionMismatch { refundable, .. }) if *refundable == min
+        ));
+        assert!(check_merchant_contribution(min - 1, 0, &merchant_lock, None).is_err());
+        // So would any merchant xUDT
+        assert!(check_merchant_contribution(min, 1, &merchant_lock, None).is_err());
+    }
+
+    #[test]
+    fn test_change_policy_resolves_dust_per_policy() {
+        let min_occupied = 61 * 100_000_000;
+        let policy = |on_dust| ChangePolicy {
+            min_change: 100 * 100_000_000,
+            on_dust,
+        };
+
+        // 80 CKB fits a cell but is below --min-change
+        let dust = 80 * 100_000_000;
+        let err = policy(DustPolicy::Fail)
+            .resolve(dust, min_occupied)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChannelError>(),
+            Some(ChannelError::DustChange { change, min_change })
+                if *change == dust && *min_change == 100 * 100_000_000
+        ));
+        assert_eq!(
+            policy(DustPolicy::Burn)
+                .resolve(dust, min_occupied)
+                .unwrap(),
+            ChangeOutcome::Burn(dust)
+        );
+        assert_eq!(
+            policy(DustPolicy::AddToFunding)
+                .resolve(dust, min_occupied)
+                .unwrap(),
+            ChangeOutcome::AddToFunding(dust)
+        );
+
+        // Enough change, or none at all, is the same under every policy
+        for on_dust in [DustPolicy::Fail, DustPolicy::Burn, DustPolicy::AddToFunding] {
+            assert_eq!(
+                policy(on_dust).resolve(dust * 2, min_occupied).unwrap(),
+                ChangeOutcome::Output(dust * 2)
+            );
+            assert_eq!(
+                policy(on_dust).resolve(0, min_occupied).unwrap(),
+                ChangeOutcome::None
+            );
+        }
+
+        // The default policy only treats change too small for a cell as dust
+        let default = ChangePolicy::default();
+        assert_eq!(
+            default.resolve(dust, min_occupied).unwrap(),
+            ChangeOutcome::Output(dust)
+        );
+        assert_eq!(
+            default.resolve(min_occupied - 1, min_occupied).unwrap(),
+            ChangeOutcome::Burn(min_occupied - 1)
+        );
+    }
+}
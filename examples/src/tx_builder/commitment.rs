@@ -26,7 +26,6 @@
 /// 2. **Merchant settles**: Merchant adds their signature and broadcasts to chain
 use anyhow::{anyhow, Result};
 use ckb_crypto::secp::Privkey;
-use ckb_hash::blake2b_256;
 use ckb_sdk::{constants::ONE_CKB, unlock::MultisigConfig};
 use ckb_types::{
     bytes::Bytes,
@@ -37,13 +36,18 @@ use ckb_types::{
 };
 use std::str::FromStr;
 
-use crate::{tx_builder::funding_v2::build_multisig_config, utils::config::Config};
-
-use crate::tx_builder::witness_utils::{EMPTY_WITNESS_ARGS_SIZE, SIGNATURE_SIZE, UNLOCK_TYPE_SIZE};
+use crate::{
+    tx_builder::funding_v2::build_multisig_config,
+    tx_builder::signing::spillman_commitment_signing_message,
+    utils::{config::Config, crypto::SpillmanArgs, log::log_event},
+};
+use serde_json::json;
 
-// Constants for witness structure
-const EMPTY_WITNESS_ARGS: [u8; 16] = [16, 0, 0, 0, 16, 0, 0, 0, 16, 0, 0, 0, 16, 0, 0, 0];
-const UNLOCK_TYPE_COMMITMENT: u8 = 0x00;
+use crate::tx_builder::witness_utils::{
+    EMPTY_WITNESS_ARGS, EMPTY_WITNESS_ARGS_SIZE, SIGNATURE_SIZE, UNLOCK_TYPE_COMMITMENT,
+    UNLOCK_TYPE_SIZE,
+};
+use crate::tx_builder::xudt::write_xudt_amount;
 
 /// Build commitment transaction (high-level API)
 ///
@@ -90,11 +94,10 @@ pub fn build_commitment_transaction(
 
     // Parse user private key from config
     let user_privkey = Privkey::from_str(
-        config
+        &config
             .user
-            .private_key
-            .as_ref()
-            .expect("User private_key is required"),
+            .resolve_private_key()?
+            .ok_or_else(|| anyhow!("User private_key is required"))?,
     )
     .map_err(|e| anyhow!("Failed to parse user private key: {:?}", e))?;
 
@@ -161,21 +164,12 @@ pub fn build_commitment_transaction(
 
     // Build xUDT cell dep if this is an xUDT channel
     let xudt_cell_dep = if xudt_type_script.is_some() {
-        if let Some(ref usdi_config) = config.usdi {
-            let xudt_tx_hash = hex::decode(usdi_config.tx_hash.trim_start_matches("0x"))?;
-            let xudt_out_point = OutPoint::new_builder()
-                .tx_hash(ckb_types::packed::Byte32::from_slice(&xudt_tx_hash)?)
-                .index(usdi_config.index)
-                .build();
-            Some(
-                CellDep::new_builder()
-                    .out_point(xudt_out_point)
-                    .dep_type(DepType::Code)
-                    .build(),
-            )
-        } else {
-            return Err(anyhow!("xUDT channel detected but usdi config not found"));
-        }
+        let xudt = config
+            .usdi
+            .as_ref()
+            .ok_or_else(|| anyhow!("xUDT channel detected but usdi config not found"))?
+            .validate()?;
+        Some(xudt.cell_dep)
     } else {
         None
     };
@@ -231,6 +225,16 @@ pub fn build_commitment_transaction(
 
     println!("✓ Commitment transaction saved: {}", output_path);
     println!("  ✅ Transaction is signed by user and ready for merchant to settle");
+    log_event(
+        "commitment",
+        "built",
+        json!({
+            "tx_hash": format!("{:#x}", tx_hash),
+            "fee": actual_fee,
+            "payment": payment_amount,
+            "output_path": output_path,
+        }),
+    );
 
     Ok((tx_hash.unpack(), tx))
 }
@@ -239,7 +243,7 @@ pub fn build_commitment_transaction(
 fn build_commitment_transaction_internal(
     spillman_lock_outpoint: OutPoint,
     spillman_lock_capacity: u64,
-    _spillman_lock_script: Script,
+    spillman_lock_script: Script,
     user_lock_script: Script,
     merchant_lock_script: Script,
     payment_amount: u64,
@@ -256,6 +260,8 @@ fn build_commitment_transaction_internal(
 ) -> Result<(TransactionView, u64)> {
     // Calculate merchant's total capacity (payment + minimum occupied capacity)
     let merchant_total_capacity = payment_amount + merchant_min_capacity;
+    // The args version picks the message the contract checks the signatures against
+    let args_version = SpillmanArgs::from_bytes(&spillman_lock_script.args().raw_data())?.version;
 
     // Iteratively calculate fee to stabilize transaction size
     let max_iterations = 10;
@@ -300,7 +306,7 @@ fn build_commitment_transaction_internal(
                     .type_(Some(type_script.clone()).pack())
                     .capacity(Capacity::shannons(change_amount).pack())
                     .build();
-                let user_data = Bytes::from(xudt_change.to_le_bytes().to_vec());
+                let user_data = write_xudt_amount(xudt_change);
 
                 // Output 1: Merchant's address (payment with xUDT)
                 let merchant_output = CellOutput::new_builder()
@@ -308,7 +314,7 @@ fn build_commitment_transaction_internal(
                     .type_(Some(type_script.clone()).pack())
                     .capacity(Capacity::shannons(merchant_total_capacity).pack())
                     .build();
-                let merchant_data = Bytes::from(xudt_payment.to_le_bytes().to_vec());
+                let merchant_data = write_xudt_amount(xudt_payment);
 
                 (user_output, user_data, merchant_output, merchant_data)
             } else {
@@ -374,8 +380,12 @@ fn build_commitment_transaction_internal(
         let tx_view: TransactionView = tx;
 
         // Sign the transaction with user's key
-        let signed_tx =
-            sign_commitment_transaction(tx_view, user_privkey, merchant_placeholder_size)?;
+        let signed_tx = sign_commitment_transaction(
+            tx_view,
+            user_privkey,
+            merchant_placeholder_size,
+            args_version,
+        )?;
 
         // Calculate actual fee for this transaction
         let tx_size = signed_tx.data().as_reader().serialized_size_in_block() as u64;
@@ -405,9 +415,11 @@ fn sign_commitment_transaction(
     tx: TransactionView,
     user_privkey: &Privkey,
     merchant_placeholder_size: usize,
+    args_version: u8,
 ) -> Result<TransactionView> {
-    // Prepare signing message
-    let signing_message = compute_signing_message(&tx);
+    // Prepare signing message: the Spillman input and both channel outputs
+    let signing_message =
+        spillman_commitment_signing_message(&tx, args_version, tx.outputs().len())?;
 
     // Sign with user's key (following refund_v2.rs pattern)
     let user_sig = user_privkey
@@ -449,17 +461,3 @@ fn sign_commitment_transaction(
 
     Ok(new_tx_view)
 }
-
-/// Compute the signing message for a commitment transaction
-/// This follows the same pattern as refund_v2.rs
-fn compute_signing_message(tx: &TransactionView) -> [u8; 32] {
-    // Clear cell_deps for signing (following CKB's signing convention)
-    let raw_tx = tx
-        .data()
-        .raw()
-        .as_builder()
-        .cell_deps(CellDepVec::default())
-        .build();
-
-    blake2b_256(raw_tx.as_slice())
-}
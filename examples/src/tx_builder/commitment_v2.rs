@@ -0,0 +1,832 @@
+/// Refactored commitment transaction builder following Fiber's TxBuilder pattern
+///
+/// Mirrors funding_v2 and refund_v2 so that all three channel operations share
+/// the same architecture:
+/// - Uses TxBuilder trait for structured transaction construction
+/// - Separates concerns: build_base -> iterative fee calculation -> sign
+/// - Witness assembled through the shared witness_utils helpers
+///
+/// # Commitment Transaction Structure
+///
+/// ## Inputs
+/// - Spillman Lock cell (from funding transaction output `funding_output_index`)
+/// - Since: 0 (no time lock on commitment path)
+///
+/// ## Outputs
+/// - Output 0: User's cell (change, plus remaining xUDT for xUDT channels)
+/// - Output 1: Merchant's cell (payment + minimum occupied capacity, plus paid xUDT)
+///
+/// ## Witness
+/// - EMPTY_WITNESS_ARGS (16 bytes)
+/// - UNLOCK_TYPE_COMMITMENT (1 byte, 0x00)
+/// - Merchant signature placeholder (filled by merchant during settle)
+///   - Single-sig: 65 bytes
+///   - Multisig: multisig_config + threshold * 65 bytes
+/// - User signature (65 bytes)
+///
+/// Total: 147 bytes for a single-sig merchant
+///
+/// # Signing Flow
+///
+/// 1. **User creates commitment**: User signs, merchant part stays a placeholder
+/// 2. **Merchant settles**: Merchant adds their signature and broadcasts to chain
+///
+/// # Example
+///
+/// ```ignore
+/// let request = CommitmentRequest::new(
+///     funding_tx_hash,
+///     funding_tx,
+///     funding_output_index,
+///     user_lock,
+///     merchant_lock,
+///     pay_amount,
+///     fee_rate,
+/// );
+/// let commitment_tx = CommitmentTx::new()
+///     .build(request, context)
+///     .await?
+///     .sign_for_spillman_lock(user_signer.as_ref(), &spillman_lock_args, None)?;
+/// ```
+use anyhow::{anyhow, Result};
+use ckb_sdk::{
+    traits::{CellDepResolver, HeaderDepResolver, TransactionDependencyProvider},
+    tx_builder::{TxBuilder, TxBuilderError},
+    unlock::MultisigConfig,
+    HumanCapacity,
+};
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, DepType, TransactionView},
+    packed::{CellDep, CellInput, CellOutput, OutPoint, Script, Transaction},
+    prelude::*,
+    H256,
+};
+use std::str::FromStr;
+
+use crate::error::ChannelError;
+use crate::signer::{RawKeySigner, Signer};
+use crate::tx_builder::capacity_utils::merchant_min_capacity;
+use crate::tx_builder::signing::spillman_commitment_signing_message;
+use crate::tx_builder::witness_utils::{
+    assemble_spillman_witness, calculate_commitment_witness_size, MerchantSig,
+    EMPTY_WITNESS_ARGS_SIZE, SIGNATURE_SIZE, UNLOCK_TYPE_COMMITMENT, UNLOCK_TYPE_SIZE,
+};
+use crate::tx_builder::xudt::{read_xudt_amount, write_xudt_amount};
+use crate::utils::config::Config;
+use crate::utils::crypto::SpillmanArgs;
+use crate::utils::log::log_event;
+use serde_json::json;
+
+/// Outputs the commitment signatures cover: the user's change and the merchant's payment
+///
+/// Fee inputs and change the merchant appends at settlement come after them.
+pub const COMMITMENT_CHANNEL_OUTPUTS: usize = 2;
+
+/// Commitment request parameters
+#[derive(Clone)]
+pub struct CommitmentRequest {
+    /// The funding transaction hash
+    pub funding_tx_hash: H256,
+    /// The funding transaction
+    pub funding_tx: TransactionView,
+    /// Index of the Spillman Lock cell in the funding transaction outputs
+    pub funding_output_index: u32,
+    /// User's lock script (change destination, output 0)
+    pub user_lock: Script,
+    /// Merchant's lock script (payment destination, output 1)
+    pub merchant_lock: Script,
+    /// CKB paid to the merchant in shannons, on top of the merchant cell's minimum occupied capacity
+    pub pay_amount: u64,
+    /// xUDT paid to the merchant (xUDT channels only)
+    pub xudt_pay_amount: Option<u128>,
+    /// Fee rate in shannon/KB
+    pub fee_rate: u64,
+    /// xUDT cell dep (optional, for xUDT channels)
+    pub xudt_cell_dep: Option<CellDep>,
+}
+
+impl CommitmentRequest {
+    /// Create a request for a CKB channel
+    pub fn new(
+        funding_tx_hash: H256,
+        funding_tx: TransactionView,
+        funding_output_index: u32,
+        user_lock: Script,
+        merchant_lock: Script,
+        pay_amount: u64,
+        fee_rate: u64,
+    ) -> Self {
+        Self {
+            funding_tx_hash,
+            funding_tx,
+            funding_output_index,
+            user_lock,
+            merchant_lock,
+            pay_amount,
+            xudt_pay_amount: None,
+            fee_rate,
+            xudt_cell_dep: None,
+        }
+    }
+
+    /// Pay in xUDT instead (merchant output carries the paid token amount)
+    pub fn with_xudt(mut self, xudt_pay_amount: u128, xudt_cell_dep: CellDep) -> Self {
+        self.xudt_pay_amount = Some(xudt_pay_amount);
+        self.xudt_cell_dep = Some(xudt_cell_dep);
+        self
+    }
+
+    /// Get the Spillman Lock cell being spent and its data
+    fn spillman_cell(&self) -> Result<(CellOutput, Bytes)> {
+        let index = self.funding_output_index as usize;
+        let output = self
+            .funding_tx
+            .outputs()
+            .get(index)
+            .ok_or_else(|| anyhow!("Funding transaction has no output {}", index))?;
+        let data = self
+            .funding_tx
+            .outputs_data()
+            .get(index)
+            .map(|data| data.raw_data())
+            .unwrap_or_default();
+        Ok((output, data))
+    }
+}
+
+/// Commitment context (cell deps and the merchant witness layout)
+///
+/// Signing takes the signer separately, see `CommitmentTx::sign_for_spillman_lock`.
+#[derive(Clone)]
+pub struct CommitmentContext {
+    /// Multisig configuration for merchant (if merchant uses multisig)
+    pub merchant_multisig_config: Option<MultisigConfig>,
+    pub spillman_lock_dep: CellDep,
+    pub auth_dep: CellDep,
+}
+
+/// Commitment transaction wrapper
+#[derive(Clone, Debug, Default)]
+pub struct CommitmentTx {
+    tx: Option<TransactionView>,
+}
+
+impl CommitmentTx {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn take(&mut self) -> Option<TransactionView> {
+        self.tx.take()
+    }
+
+    pub fn into_inner(self) -> Option<TransactionView> {
+        self.tx
+    }
+
+    pub fn update(&mut self, tx: TransactionView) {
+        self.tx = Some(tx);
+    }
+
+    /// Build the commitment transaction
+    pub async fn build(
+        self,
+        request: CommitmentRequest,
+        context: CommitmentContext,
+    ) -> Result<Self> {
+        let builder = CommitmentTxBuilder {
+            commitment_tx: self,
+            request,
+            context,
+        };
+        builder.build_internal().await
+    }
+
+    /// Sign the commitment transaction as the user
+    ///
+    /// Spillman Lock commitment path requires:
+    /// - EMPTY_WITNESS_ARGS (16 bytes)
+    /// - UNLOCK_TYPE_COMMITMENT (1 byte, 0x00)
+    /// - Merchant signature (placeholder here, filled in by the merchant during settle)
+    /// - User signature (65 bytes)
+    pub fn sign_for_spillman_lock(
+        mut self,
+        user_signer: &dyn Signer,
+        spillman_lock_args: &[u8],
+        merchant_multisig_config: Option<&MultisigConfig>,
+    ) -> Result<Self> {
+        let tx = self
+            .take()
+            .ok_or_else(|| anyhow!("No transaction to sign"))?;
+
+        // Verify user pubkey hash matches Spillman Lock args
+        let args = SpillmanArgs::from_bytes(spillman_lock_args)?;
+        let user_hash = args.user_pubkey_hash;
+        if !user_signer.has_key(&user_hash) {
+            return Err(ChannelError::PubkeyHashMismatch { role: "User" }.into());
+        }
+
+        let signing_message =
+            spillman_commitment_signing_message(&tx, args.version, COMMITMENT_CHANNEL_OUTPUTS)?;
+        let user_sig = user_signer.sign(signing_message, &user_hash)?;
+
+        // Merchant part is a zeroed placeholder of the final size
+        let merchant_placeholder = match merchant_multisig_config {
+            Some(config) => MerchantSig::Multi {
+                config: config.to_witness_data(),
+                sigs: vec![[0u8; SIGNATURE_SIZE]; config.threshold() as usize],
+            },
+            None => MerchantSig::Single([0u8; SIGNATURE_SIZE]),
+        };
+
+        let witness_data =
+            assemble_spillman_witness(UNLOCK_TYPE_COMMITMENT, merchant_placeholder, &user_sig);
+
+        let signed_tx = tx
+            .as_advanced_builder()
+            .set_witnesses(vec![witness_data.pack()])
+            .build();
+
+        self.update(signed_tx);
+        Ok(self)
+    }
+
+    /// Fill in the merchant part of a commitment signed by the user
+    ///
+    /// The user signature at the end of the witness is kept as is; the merchant
+    /// placeholder is replaced by real signature(s) over the same message. The message
+    /// covers the Spillman input and the channel outputs only, so fee inputs the merchant
+    /// attaches before or after signing (and their witnesses) are left alone.
+    pub fn sign_as_merchant(
+        mut self,
+        merchant_signer: &dyn Signer,
+        spillman_lock_args: &[u8],
+        merchant_multisig_config: Option<&MultisigConfig>,
+    ) -> Result<Self> {
+        let tx = self
+            .take()
+            .ok_or_else(|| anyhow!("No transaction to sign"))?;
+
+        let witness = tx
+            .witnesses()
+            .get(0)
+            .map(|witness| witness.raw_data())
+            .ok_or_else(|| anyhow!("Commitment transaction has no witness"))?;
+        let expected_size = calculate_commitment_witness_size(merchant_multisig_config);
+        if witness.len() != expected_size {
+            return Err(anyhow!(
+                "Invalid commitment witness length: expected {}, got {}",
+                expected_size,
+                witness.len()
+            ));
+        }
+        let merchant_part =
+            &witness[EMPTY_WITNESS_ARGS_SIZE + UNLOCK_TYPE_SIZE..expected_size - SIGNATURE_SIZE];
+        if merchant_part.iter().any(|&byte| byte != 0) {
+            return Err(anyhow!("Merchant signature already present in transaction"));
+        }
+        let user_sig = &witness[expected_size - SIGNATURE_SIZE..];
+
+        let args = SpillmanArgs::from_bytes(spillman_lock_args)?;
+        let signing_message =
+            spillman_commitment_signing_message(&tx, args.version, COMMITMENT_CHANNEL_OUTPUTS)?;
+        let merchant_sig = MerchantSig::sign(
+            merchant_signer,
+            signing_message,
+            &args.merchant_lock_arg,
+            merchant_multisig_config,
+        )?;
+
+        let witness_data =
+            assemble_spillman_witness(UNLOCK_TYPE_COMMITMENT, merchant_sig, user_sig);
+        let mut witnesses: Vec<_> = tx.witnesses().into_iter().collect();
+        witnesses[0] = witness_data.pack();
+        let signed_tx = tx.as_advanced_builder().set_witnesses(witnesses).build();
+
+        self.update(signed_tx);
+        Ok(self)
+    }
+}
+
+impl From<TransactionView> for CommitmentTx {
+    fn from(tx: TransactionView) -> Self {
+        Self { tx: Some(tx) }
+    }
+}
+
+impl From<Transaction> for CommitmentTx {
+    fn from(tx: Transaction) -> Self {
+        Self {
+            tx: Some(tx.into_view()),
+        }
+    }
+}
+
+/// Internal builder implementing TxBuilder trait
+struct CommitmentTxBuilder {
+    commitment_tx: CommitmentTx,
+    request: CommitmentRequest,
+    context: CommitmentContext,
+}
+
+#[async_trait::async_trait]
+impl TxBuilder for CommitmentTxBuilder {
+    async fn build_base_async(
+        &self,
+        _cell_collector: &mut dyn ckb_sdk::traits::CellCollector,
+        _cell_dep_resolver: &dyn CellDepResolver,
+        _header_dep_resolver: &dyn HeaderDepResolver,
+        _tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<TransactionView, TxBuilderError> {
+        // Rough fee estimate, refined by iterative calculation in build_internal
+        let estimated_fee = 1000u64;
+        self.build_tx_with_fee(estimated_fee)
+            .map_err(TxBuilderError::Other)
+    }
+}
+
+impl CommitmentTxBuilder {
+    /// Internal build method with iterative fee calculation
+    async fn build_internal(self) -> Result<CommitmentTx> {
+        let fee_rate = self.request.fee_rate;
+        let max_iterations = 10;
+        let mut current_fee = 0u64;
+        let mut final_tx: Option<TransactionView> = None;
+
+        for iteration in 0..max_iterations {
+            let temp_tx = self.build_tx_with_fee(current_fee)?;
+
+            // Calculate actual fee for this transaction
+            let tx_size = temp_tx.data().as_reader().serialized_size_in_block() as u64;
+            let actual_fee = (tx_size * fee_rate).div_ceil(1000); // Round up
+
+            // Check if fee has stabilized
+            if actual_fee == current_fee {
+                final_tx = Some(temp_tx);
+                break;
+            }
+
+            current_fee = actual_fee;
+
+            if iteration == max_iterations - 1 {
+                final_tx = Some(temp_tx);
+            }
+        }
+
+        let tx = final_tx.ok_or_else(|| anyhow!("Failed to build transaction"))?;
+
+        let mut commitment_tx = self.commitment_tx;
+        commitment_tx.update(tx);
+        Ok(commitment_tx)
+    }
+
+    /// Helper to build the two-output transaction paying a specific fee
+    fn build_tx_with_fee(&self, fee: u64) -> Result<TransactionView> {
+        let (spillman_cell, spillman_data) = self.request.spillman_cell()?;
+        let spillman_capacity: u64 = spillman_cell.capacity().unpack();
+        let type_script = spillman_cell.type_().to_opt();
+
+        // Outputs data: xUDT change for the user, paid xUDT for the merchant
+        let (user_data, merchant_data) = if type_script.is_some() {
+            let xudt_total = read_xudt_amount(&spillman_data)?;
+            let xudt_pay = self
+                .request
+                .xudt_pay_amount
+                .ok_or_else(|| anyhow!("xUDT payment amount required for xUDT channel"))?;
+            let xudt_change = xudt_total
+                .checked_sub(xudt_pay)
+                .ok_or_else(|| anyhow!("xUDT payment exceeds total amount"))?;
+            (write_xudt_amount(xudt_change), write_xudt_amount(xudt_pay))
+        } else {
+            if self.request.xudt_pay_amount.is_some() {
+                return Err(anyhow!("xUDT payment requested on a CKB-only channel"));
+            }
+            (Bytes::new(), Bytes::new())
+        };
+
+        // Merchant receives payment + minimum occupied capacity of its cell
+        let merchant_capacity =
+            merchant_min_capacity(&self.request.merchant_lock, type_script.as_ref())?
+                .checked_add(self.request.pay_amount)
+                .ok_or_else(|| anyhow!("Payment amount overflow"))?;
+
+        let user_capacity = spillman_capacity
+            .checked_sub(merchant_capacity)
+            .and_then(|c| c.checked_sub(fee))
+            .ok_or(ChannelError::InsufficientCapacity {
+                required: merchant_capacity.saturating_add(fee),
+                available: spillman_capacity,
+            })?;
+
+        let input = CellInput::new_builder()
+            .previous_output(
+                OutPoint::new_builder()
+                    .tx_hash(self.request.funding_tx_hash.pack())
+                    .index(self.request.funding_output_index)
+                    .build(),
+            )
+            .since(0u64) // No time lock for commitment path
+            .build();
+
+        let user_output = CellOutput::new_builder()
+            .capacity(Capacity::shannons(user_capacity))
+            .lock(self.request.user_lock.clone())
+            .type_(type_script.clone().pack())
+            .build();
+        let merchant_output = CellOutput::new_builder()
+            .capacity(Capacity::shannons(merchant_capacity))
+            .lock(self.request.merchant_lock.clone())
+            .type_(type_script.pack())
+            .build();
+
+        // Build witness placeholder (size depends on merchant's signature type)
+        let witness_size =
+            calculate_commitment_witness_size(self.context.merchant_multisig_config.as_ref());
+        let witness_placeholder = vec![0u8; witness_size];
+
+        let mut tx_builder = Transaction::default()
+            .as_advanced_builder()
+            .input(input)
+            .cell_dep(self.context.spillman_lock_dep.clone())
+            .cell_dep(self.context.auth_dep.clone());
+
+        // Add xUDT cell dep if this is an xUDT channel
+        if let Some(ref xudt_cell_dep) = self.request.xudt_cell_dep {
+            tx_builder = tx_builder.cell_dep(xudt_cell_dep.clone());
+        }
+
+        let tx = tx_builder
+            .output(user_output)
+            .output_data(user_data.pack())
+            .output(merchant_output)
+            .output_data(merchant_data.pack())
+            .witness(Bytes::from(witness_placeholder).pack())
+            .build();
+
+        Ok(tx)
+    }
+}
+
+/// Build a code cell dep from a deployment tx hash and index
+pub(crate) fn code_cell_dep(tx_hash: &str, index: u32) -> Result<CellDep> {
+    let tx_hash = H256::from_str(tx_hash.trim_start_matches("0x"))
+        .map_err(|e| anyhow!("Invalid cell dep tx hash '{}': {}", tx_hash, e))?;
+    Ok(CellDep::new_builder()
+        .out_point(
+            OutPoint::new_builder()
+                .tx_hash(tx_hash.pack())
+                .index(index)
+                .build(),
+        )
+        .dep_type(DepType::Code)
+        .build())
+}
+
+/// Build commitment transaction (high-level API)
+///
+/// This function:
+/// - Creates CommitmentRequest and CommitmentContext from config
+/// - Builds the transaction with iterative fee calculation
+/// - Signs with user's key (merchant signs later during settle)
+/// - Saves to file
+///
+/// Returns: (tx_hash, TransactionView)
+///
+/// # Arguments
+/// * `config` - Configuration
+/// * `funding_tx_hash` - The funding transaction hash
+/// * `funding_tx` - The funding transaction
+/// * `funding_output_index` - Index of the Spillman Lock cell in the funding transaction
+/// * `user_lock` - User's lock script (change output)
+/// * `merchant_lock` - Merchant's lock script (payment output)
+/// * `pay_amount` - CKB paid to merchant in shannons (excluding minimum occupied capacity)
+/// * `xudt_pay_amount` - xUDT paid to merchant (xUDT channels only)
+/// * `fee_rate` - Fee rate in shannons per KB
+/// * `output_path` - Path to save the transaction JSON
+/// * `unsigned` - Skip the user signature and save the transaction with its zeroed
+///   witness placeholder, for an external signing tool (`--save-unsigned`)
+#[allow(clippy::too_many_arguments)]
+pub async fn build_commitment_transaction(
+    config: &Config,
+    funding_tx_hash: H256,
+    funding_tx: &TransactionView,
+    funding_output_index: u32,
+    user_lock: Script,
+    merchant_lock: Script,
+    pay_amount: u64,
+    xudt_pay_amount: Option<u128>,
+    fee_rate: u64,
+    output_path: &str,
+    unsigned: bool,
+) -> Result<(H256, TransactionView)> {
+    println!("📝 构建 Commitment 交易 (v2)...");
+
+    let spillman_lock_dep =
+        code_cell_dep(&config.spillman_lock.tx_hash, config.spillman_lock.index)?;
+    let auth_dep = code_cell_dep(&config.auth.tx_hash, config.auth.index)?;
+
+    // Merchant multisig config only determines the witness placeholder size
+    let merchant_multisig_config = if config.merchant.is_multisig() {
+        let secret_keys = config.merchant.get_secret_keys()?;
+        let (threshold, total) = config
+            .merchant
+            .get_multisig_config()
+            .ok_or_else(|| anyhow!("Merchant multisig config is invalid"))?;
+
+        use crate::tx_builder::funding_v2::build_multisig_config;
+        Some(build_multisig_config(&secret_keys, threshold, total)?)
+    } else {
+        None
+    };
+
+    let spillman_cell = funding_tx
+        .outputs()
+        .get(funding_output_index as usize)
+        .ok_or_else(|| anyhow!("Funding transaction has no output {}", funding_output_index))?;
+    let args_bytes: Bytes = spillman_cell.lock().args().unpack();
+
+    let mut request = CommitmentRequest::new(
+        funding_tx_hash,
+        funding_tx.clone(),
+        funding_output_index,
+        user_lock,
+        merchant_lock,
+        pay_amount,
+        fee_rate,
+    );
+    if let Some(xudt_pay_amount) = xudt_pay_amount {
+        let xudt = config
+            .usdi
+            .as_ref()
+            .ok_or_else(|| anyhow!("xUDT channel detected but usdi config not found"))?
+            .validate()?;
+        request = request.with_xudt(xudt_pay_amount, xudt.cell_dep);
+    }
+
+    let context = CommitmentContext {
+        merchant_multisig_config: merchant_multisig_config.clone(),
+        spillman_lock_dep,
+        auth_dep,
+    };
+
+    let spillman_capacity: u64 = spillman_cell.capacity().unpack();
+
+    // Build and sign transaction (user only)
+    let commitment_tx = CommitmentTx::new().build(request, context).await?;
+    let commitment_tx = if unsigned {
+        commitment_tx
+    } else {
+        let user_privkey_hex = config
+            .user
+            .resolve_private_key()?
+            .ok_or_else(|| anyhow!("User private_key is required"))?;
+        let user_signer = RawKeySigner::new_arc(vec![secp256k1::SecretKey::from_slice(
+            &hex::decode(user_privkey_hex.trim_start_matches("0x"))?,
+        )?])?;
+        println!("🔐 签名 Commitment 交易 (User)...");
+        commitment_tx.sign_for_spillman_lock(
+            user_signer.as_ref(),
+            &args_bytes,
+            merchant_multisig_config.as_ref(),
+        )?
+    };
+    let tx = commitment_tx
+        .into_inner()
+        .ok_or_else(|| anyhow!("No transaction"))?;
+    let tx_hash =
+        save_commitment_transaction(&tx, spillman_capacity, pay_amount, output_path, unsigned)?;
+
+    Ok((tx_hash, tx))
+}
+
+/// Print a summary of a commitment and save it as JSON to `output_path`
+///
+/// `spillman_capacity` is the capacity of the Spillman cell it spends, `pay_amount` the
+/// CKB paid to the merchant; `unsigned` marks a transaction with a placeholder witness.
+pub fn save_commitment_transaction(
+    tx: &TransactionView,
+    spillman_capacity: u64,
+    pay_amount: u64,
+    output_path: &str,
+    unsigned: bool,
+) -> Result<H256> {
+    let tx_hash = tx.hash();
+    let output_capacity = |index: usize, role: &str| -> Result<u64> {
+        tx.outputs()
+            .get(index)
+            .map(|output| output.capacity().unpack())
+            .ok_or_else(|| anyhow!("Commitment transaction has no {} output", role))
+    };
+    let user_capacity = output_capacity(0, "user")?;
+    let merchant_capacity = output_capacity(1, "merchant")?;
+    let fee = spillman_capacity
+        .checked_sub(user_capacity)
+        .and_then(|c| c.checked_sub(merchant_capacity))
+        .ok_or_else(|| {
+            anyhow!(
+                "Commitment outputs exceed the Spillman cell capacity {}",
+                spillman_capacity
+            )
+        })?;
+
+    println!("✓ Commitment transaction built");
+    println!("  - Transaction hash: {:#x}", tx_hash);
+    println!(
+        "  - Payment to merchant: {}",
+        HumanCapacity::from(merchant_capacity)
+    );
+    println!("  - Change to user: {}", HumanCapacity::from(user_capacity));
+    println!("  - Transaction fee: {}", HumanCapacity::from(fee));
+
+    // Save transaction
+    let tx_json = ckb_jsonrpc_types::TransactionView::from(tx.clone());
+    let json_str = serde_json::to_string_pretty(&tx_json)?;
+
+    if let Some(parent) = std::path::Path::new(output_path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(output_path, json_str)?;
+
+    println!("✓ Commitment transaction saved: {}", output_path);
+    if unsigned {
+        println!("  - Unsigned: the witness is a zeroed placeholder of the final size");
+    } else {
+        println!("  ✅ Transaction is signed by user and ready for merchant to settle");
+    }
+    log_event(
+        "commitment",
+        "built",
+        json!({
+            "tx_hash": format!("{:#x}", tx_hash),
+            "fee": fee,
+            "payment": pay_amount,
+            "output_path": output_path,
+        }),
+    );
+
+    Ok(tx_hash.unpack())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tx_builder::witness_utils::EMPTY_WITNESS_ARGS;
+    use crate::utils::crypto::pubkey_hash;
+    use ckb_crypto::secp::Privkey;
+
+    const COMMITMENT_WITNESS_SIZE_SINGLE_SIG: usize = 147; // 16 + 1 + 65 + 65
+
+    fn test_lock_script(args: Vec<u8>) -> Script {
+        Script::new_builder()
+            .code_hash(H256([0x11; 32]).pack())
+            .hash_type(ckb_types::core::ScriptHashType::Type)
+            .args(Bytes::from(args).pack())
+            .build()
+    }
+
+    fn test_funding_tx(spillman_capacity: u64, user_pubkey_hash: &[u8]) -> TransactionView {
+        let mut spillman_args = vec![0x44u8; 20];
+        spillman_args.extend_from_slice(user_pubkey_hash);
+        spillman_args.extend_from_slice(&0x4000_0000_6553_f100u64.to_le_bytes());
+        spillman_args.extend_from_slice(&[0, 0]);
+
+        Transaction::default()
+            .as_advanced_builder()
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(spillman_capacity))
+                    .lock(test_lock_script(spillman_args))
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build()
+    }
+
+    fn test_context() -> CommitmentContext {
+        CommitmentContext {
+            merchant_multisig_config: None,
+            spillman_lock_dep: CellDep::default(),
+            auth_dep: CellDep::default(),
+        }
+    }
+
+    #[test]
+    fn test_commitment_request_creation() {
+        let funding_tx = test_funding_tx(1000 * 100_000_000, &[0x22; 20]);
+        let request = CommitmentRequest::new(
+            funding_tx.hash().unpack(),
+            funding_tx.clone(),
+            0,
+            test_lock_script(vec![0x22; 20]),
+            test_lock_script(vec![0x44; 20]),
+            100 * 100_000_000,
+            1000,
+        );
+
+        assert_eq!(request.funding_output_index, 0);
+        assert_eq!(request.pay_amount, 100 * 100_000_000);
+        assert_eq!(request.xudt_pay_amount, None);
+        assert!(request.xudt_cell_dep.is_none());
+
+        let xudt_request = request.with_xudt(5_000_000, CellDep::default());
+        assert_eq!(xudt_request.xudt_pay_amount, Some(5_000_000));
+        assert!(xudt_request.xudt_cell_dep.is_some());
+    }
+
+    #[test]
+    fn test_commitment_witness_size() {
+        assert_eq!(
+            calculate_commitment_witness_size(None),
+            COMMITMENT_WITNESS_SIZE_SINGLE_SIG
+        );
+        assert_eq!(
+            EMPTY_WITNESS_ARGS.len() + 1 + SIGNATURE_SIZE + SIGNATURE_SIZE,
+            COMMITMENT_WITNESS_SIZE_SINGLE_SIG
+        );
+    }
+
+    #[tokio::test]
+    async fn test_build_and_sign_commitment() {
+        let user_privkey = Privkey::from_slice(&[7u8; 32]);
+        let user_hash = pubkey_hash(&user_privkey.pubkey().unwrap());
+        let user_signer =
+            RawKeySigner::new_arc(vec![secp256k1::SecretKey::from_slice(&[7u8; 32]).unwrap()])
+                .unwrap();
+        let spillman_capacity = 1000 * 100_000_000u64;
+        let funding_tx = test_funding_tx(spillman_capacity, &user_hash);
+        let pay_amount = 100 * 100_000_000u64;
+
+        let request = CommitmentRequest::new(
+            funding_tx.hash().unpack(),
+            funding_tx.clone(),
+            0,
+            test_lock_script(user_hash.to_vec()),
+            test_lock_script(vec![0x44; 20]),
+            pay_amount,
+            1000,
+        );
+        let args: Bytes = funding_tx.outputs().get(0).unwrap().lock().args().unpack();
+
+        let tx = CommitmentTx::new()
+            .build(request, test_context())
+            .await
+            .unwrap()
+            .sign_for_spillman_lock(user_signer.as_ref(), &args, None)
+            .unwrap()
+            .into_inner()
+            .unwrap();
+
+        // Two outputs: user change and merchant payment + 61 CKB minimum occupied capacity
+        assert_eq!(tx.outputs().len(), 2);
+        let input_since: u64 = tx.inputs().get(0).unwrap().since().unpack();
+        assert_eq!(input_since, 0);
+        let merchant_capacity: u64 = tx.outputs().get(1).unwrap().capacity().unpack();
+        assert_eq!(merchant_capacity, pay_amount + 61 * 100_000_000);
+
+        let witness = tx.witnesses().get(0).unwrap().raw_data();
+        assert_eq!(witness.len(), COMMITMENT_WITNESS_SIZE_SINGLE_SIG);
+        assert_eq!(&witness[0..16], &EMPTY_WITNESS_ARGS[..]);
+        assert_eq!(witness[16], UNLOCK_TYPE_COMMITMENT);
+        assert_eq!(
+            &witness[17..17 + SIGNATURE_SIZE],
+            &[0u8; SIGNATURE_SIZE][..]
+        );
+        assert_ne!(&witness[17 + SIGNATURE_SIZE..], &[0u8; SIGNATURE_SIZE][..]);
+    }
+
+    #[test]
+    fn test_save_commitment_transaction_rejects_malformed_outputs() {
+        let output_path = std::env::temp_dir().join(format!(
+            "spillman_malformed_commitment_{}.json",
+            std::process::id()
+        ));
+        let output_path = output_path.to_str().unwrap();
+        let output = |capacity: u64| {
+            CellOutput::new_builder()
+                .capacity(Capacity::shannons(capacity))
+                .lock(test_lock_script(vec![0x33; 20]))
+                .build()
+        };
+
+        let single_output = Transaction::default()
+            .as_advanced_builder()
+            .output(output(500))
+            .output_data(Bytes::new().pack())
+            .build();
+        let err =
+            save_commitment_transaction(&single_output, 1000, 0, output_path, false).unwrap_err();
+        assert!(err.to_string().contains("no merchant output"));
+
+        let overpaid = single_output
+            .as_advanced_builder()
+            .output(output(600))
+            .output_data(Bytes::new().pack())
+            .build();
+        let err = save_commitment_transaction(&overpaid, 1000, 0, output_path, false).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("exceed the Spillman cell capacity"));
+        assert!(!std::path::Path::new(output_path).exists());
+    }
+}
@@ -1,20 +1,18 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use ckb_sdk::{
     constants::SIGHASH_TYPE_HASH,
-    rpc::CkbRpcClient,
     traits::{
         CellCollector, CellDepResolver, CellQueryOptions, DefaultCellCollector,
-        DefaultCellDepResolver, DefaultHeaderDepResolver, DefaultTransactionDependencyProvider,
+        DefaultHeaderDepResolver, DefaultTransactionDependencyProvider, LiveCell,
         SecpCkbRawKeySigner, ValueRangeOption,
     },
     transaction::builder::FeeCalculator,
     tx_builder::{transfer::CapacityTransferBuilder, unlock_tx, CapacityBalancer, TxBuilder},
     unlock::{ScriptUnlocker, SecpSighashUnlocker},
-    Address, ScriptId,
+    Address, HumanCapacity, ScriptId,
 };
 use ckb_types::{
     bytes::Bytes,
-    core::BlockView,
     packed::{CellOutput, Script, WitnessArgs},
     prelude::*,
     H256,
@@ -22,7 +20,8 @@ use ckb_types::{
 use std::collections::HashMap;
 use std::fs;
 
-use crate::utils::config::Config;
+use crate::tx_builder::capacity_utils::{merchant_min_capacity, ChangeOutcome, ChangePolicy};
+use crate::utils::{config::Config, deps::load_cell_dep_resolver};
 
 /// Build complete funding transaction with inputs and signatures
 ///
@@ -38,23 +37,18 @@ pub async fn build_funding_transaction(
     config: &Config,
     user_address: &Address,
     spillman_lock_script: &Script,
-    capacity_ckb: u64,
+    capacity: HumanCapacity,
     output_path: &str,
 ) -> Result<(H256, u32)> {
-    let capacity_shannon = capacity_ckb * 100_000_000;
+    let capacity_shannon: u64 = capacity.into();
 
     println!(
-        "  - Spillman Lock cell capacity: {} CKB ({} shannon)",
-        capacity_ckb, capacity_shannon
+        "  - Spillman Lock cell capacity: {} ({} shannon)",
+        capacity, capacity_shannon
     );
 
     // Setup providers from RPC
-    let ckb_client = CkbRpcClient::new(&config.network.rpc_url);
-
-    let cell_dep_resolver = {
-        let genesis_block = ckb_client.get_block_by_number(0.into())?.unwrap();
-        DefaultCellDepResolver::from_genesis(&BlockView::from(genesis_block))?
-    };
+    let cell_dep_resolver = load_cell_dep_resolver(&config.network.rpc_url)?;
     let header_dep_resolver = DefaultHeaderDepResolver::new(&config.network.rpc_url);
     let mut cell_collector = DefaultCellCollector::new(&config.network.rpc_url);
     let tx_dep_provider = DefaultTransactionDependencyProvider::new(&config.network.rpc_url, 10);
@@ -66,9 +60,8 @@ pub async fn build_funding_transaction(
     // We need to re-parse the private key from the config
     let privkey_hex = config
         .user
-        .private_key
-        .as_ref()
-        .expect("User private_key is required");
+        .resolve_private_key()?
+        .ok_or_else(|| anyhow!("User private_key is required"))?;
     let privkey_hex_trimmed = privkey_hex.trim_start_matches("0x");
     let privkey_bytes = hex::decode(privkey_hex_trimmed)
         .map_err(|e| anyhow!("failed to decode private key hex: {}", e))?;
@@ -90,10 +83,7 @@ pub async fn build_funding_transaction(
     let balancer = CapacityBalancer::new_simple(sender, placeholder_witness, 1000);
 
     // Build Spillman Lock cell output
-    let spillman_cell = CellOutput::new_builder()
-        .capacity(capacity_shannon)
-        .lock(spillman_lock_script.clone())
-        .build();
+    let spillman_cell = build_spillman_cell(spillman_lock_script, capacity);
 
     // Build the transaction
     println!("  - 收集用户的 live cells 并构建交易...");
@@ -135,6 +125,14 @@ pub async fn build_funding_transaction(
     Ok((tx_hash.unpack(), 0))
 }
 
+/// Build the Spillman Lock cell output holding `capacity`
+fn build_spillman_cell(spillman_lock_script: &Script, capacity: HumanCapacity) -> CellOutput {
+    CellOutput::new_builder()
+        .capacity(u64::from(capacity))
+        .lock(spillman_lock_script.clone())
+        .build()
+}
+
 /// Build co-fund funding transaction where both user and merchant contribute
 ///
 /// This function:
@@ -145,7 +143,11 @@ pub async fn build_funding_transaction(
 /// - Signs transaction with both private keys
 /// - Saves signed transaction to file
 ///
+/// User change below `change_policy` is failed on, burned as fee or added to the
+/// Spillman cell according to its `on_dust`.
+///
 /// Returns: (tx_hash, output_index) where output_index is the Spillman Lock cell index
+#[allow(clippy::too_many_arguments)]
 pub async fn build_cofund_funding_transaction(
     config: &Config,
     user_address: &Address,
@@ -153,6 +155,7 @@ pub async fn build_cofund_funding_transaction(
     user_capacity_ckb: u64,
     spillman_lock_script: &Script,
     fee_rate: u64,
+    change_policy: ChangePolicy,
     output_path: &str,
 ) -> Result<(H256, u32)> {
     use ckb_types::{core::TransactionBuilder, packed::CellInput};
@@ -163,15 +166,7 @@ pub async fn build_cofund_funding_transaction(
 
     // Calculate merchant's minimum occupied capacity
     let merchant_lock = Script::from(merchant_address);
-    let temp_merchant_cell = CellOutput::new_builder()
-        .capacity(0u64)
-        .lock(merchant_lock.clone())
-        .build();
-
-    let merchant_capacity_shannon = temp_merchant_cell
-        .occupied_capacity(ckb_types::core::Capacity::bytes(0).unwrap())
-        .unwrap()
-        .as_u64();
+    let merchant_capacity_shannon = merchant_min_capacity(&merchant_lock, None)?;
 
     // User adds extra 1 CKB as buffer (for fees, etc.)
     let user_buffer_shannon = 100_000_000;
@@ -183,22 +178,16 @@ pub async fn build_cofund_funding_transaction(
     );
 
     // Setup providers from RPC
-    let ckb_client = CkbRpcClient::new(&config.network.rpc_url);
-
-    let cell_dep_resolver = {
-        let genesis_block = ckb_client.get_block_by_number(0.into())?.unwrap();
-        DefaultCellDepResolver::from_genesis(&BlockView::from(genesis_block))?
-    };
+    let cell_dep_resolver = load_cell_dep_resolver(&config.network.rpc_url)?;
     let _header_dep_resolver = DefaultHeaderDepResolver::new(&config.network.rpc_url);
-    let mut cell_collector = DefaultCellCollector::new(&config.network.rpc_url);
+    let cell_collector = DefaultCellCollector::new(&config.network.rpc_url);
     let tx_dep_provider = DefaultTransactionDependencyProvider::new(&config.network.rpc_url, 10);
 
     // Parse private keys
     let user_privkey_hex = config
         .user
-        .private_key
-        .as_ref()
-        .expect("User private_key is required");
+        .resolve_private_key()?
+        .ok_or_else(|| anyhow!("User private_key is required"))?;
     let user_privkey_hex_trimmed = user_privkey_hex.trim_start_matches("0x");
     let user_privkey_bytes = hex::decode(user_privkey_hex_trimmed)
         .map_err(|e| anyhow!("failed to decode user private key hex: {}", e))?;
@@ -207,30 +196,32 @@ pub async fn build_cofund_funding_transaction(
 
     let merchant_privkey_hex = config
         .merchant
-        .private_key
-        .as_ref()
-        .expect("Merchant private_key is required");
+        .resolve_private_key()?
+        .ok_or_else(|| anyhow!("Merchant private_key is required"))?;
     let merchant_privkey_hex_trimmed = merchant_privkey_hex.trim_start_matches("0x");
     let merchant_privkey_bytes = hex::decode(merchant_privkey_hex_trimmed)
         .map_err(|e| anyhow!("failed to decode merchant private key hex: {}", e))?;
     let merchant_key = secp256k1::SecretKey::from_slice(&merchant_privkey_bytes)
         .map_err(|e| anyhow!("invalid merchant private key: {}", e))?;
 
-    // Step 1: Manually collect cells from user's address
-    println!("  - 收集 User 的 cells...");
+    // Step 1: Build cell queries for both parties
     let user_lock = Script::from(user_address);
     let mut user_query = CellQueryOptions::new_lock(user_lock.clone());
     // Filter: only collect cells WITHOUT type script (plain CKB cells)
     // Set secondary_script_len_range to 0 to filter out cells with type script
     user_query.secondary_script_len_range = Some(ValueRangeOption::new_exact(0));
     user_query.data_len_range = Some(ValueRangeOption::new_exact(0));
+    user_query.min_total_capacity = user_capacity_shannon + user_buffer_shannon;
 
-    let mut user_query_with_capacity = user_query.clone();
-    user_query_with_capacity.min_total_capacity = user_capacity_shannon + user_buffer_shannon;
+    let mut merchant_query = CellQueryOptions::new_lock(merchant_lock.clone());
+    merchant_query.secondary_script_len_range = Some(ValueRangeOption::new_exact(0));
+    merchant_query.data_len_range = Some(ValueRangeOption::new_exact(0));
+    merchant_query.min_total_capacity = merchant_capacity_shannon;
 
-    println!("  - 正在查询 cells（过滤掉 UDT/NFT cells）...");
-    let (user_cells, _total_user_capacity) =
-        cell_collector.collect_live_cells(&user_query_with_capacity, false)?;
+    // Step 2: Collect user and merchant cells concurrently (independent lock scripts)
+    println!("  - 并发收集 User 和 Merchant 的 cells（过滤掉 UDT/NFT cells）...");
+    let (user_cells, merchant_cells) =
+        collect_cells_concurrently(&cell_collector, &user_query, &merchant_query).await?;
 
     if user_cells.is_empty() {
         return Err(anyhow!("User 没有任何可用的 live cells"));
@@ -257,21 +248,6 @@ pub async fn build_cofund_funding_transaction(
         ));
     }
 
-    // Step 2: Manually collect cells from merchant's address
-    println!("  - 收集 Merchant 的 cells...");
-    let mut merchant_query = CellQueryOptions::new_lock(merchant_lock.clone());
-    // Filter: only collect cells WITHOUT type script (plain CKB cells)
-    // Set secondary_script_len_range to 0 to filter out cells with type script
-    merchant_query.secondary_script_len_range = Some(ValueRangeOption::new_exact(0));
-    merchant_query.data_len_range = Some(ValueRangeOption::new_exact(0));
-
-    let mut merchant_query_with_capacity = merchant_query.clone();
-    merchant_query_with_capacity.min_total_capacity = merchant_capacity_shannon;
-
-    println!("  - 正在查询 Merchant cells（过滤掉 UDT/NFT cells）...");
-    let (merchant_cells, _total_merchant_capacity) =
-        cell_collector.collect_live_cells(&merchant_query_with_capacity, false)?;
-
     if merchant_cells.is_empty() {
         return Err(anyhow!("Merchant 没有任何可用的 live cells"));
     }
@@ -334,81 +310,81 @@ pub async fn build_cofund_funding_transaction(
         .capacity(0u64)
         .lock(user_lock.clone())
         .build()
-        .occupied_capacity(ckb_types::core::Capacity::bytes(0).unwrap())
-        .unwrap()
+        .occupied_capacity(ckb_types::core::Capacity::zero())
+        .context("computing user change occupied capacity")?
         .as_u64();
 
     let min_merchant_change = CellOutput::new_builder()
         .capacity(0u64)
         .lock(merchant_lock.clone())
         .build()
-        .occupied_capacity(ckb_types::core::Capacity::bytes(0).unwrap())
-        .unwrap()
+        .occupied_capacity(ckb_types::core::Capacity::zero())
+        .context("computing merchant change occupied capacity")?
         .as_u64();
 
-    // Build Spillman Lock output
-    let spillman_cell = CellOutput::new_builder()
-        .capacity(spillman_capacity)
-        .lock(spillman_lock_script.clone())
-        .build();
-
-    // Helper function to build transaction with given change capacities
-    let build_tx = |user_change_opt: Option<u64>, merchant_change_opt: Option<u64>| {
-        let mut builder = TransactionBuilder::default();
-
-        // Add inputs
-        for cell in &user_cells {
-            builder = builder.input(CellInput::new(cell.out_point.clone(), 0));
-        }
-        for cell in &merchant_cells {
-            builder = builder.input(CellInput::new(cell.out_point.clone(), 0));
-        }
+    // Helper function to build transaction with given change capacities, and the user's
+    // dust added to the Spillman cell (`--on-dust add-to-funding`)
+    let build_tx =
+        |user_change_opt: Option<u64>, merchant_change_opt: Option<u64>, funding_dust: u64| {
+            let mut builder = TransactionBuilder::default();
+            let spillman_cell = CellOutput::new_builder()
+                .capacity(spillman_capacity + funding_dust)
+                .lock(spillman_lock_script.clone())
+                .build();
+
+            // Add inputs
+            for cell in &user_cells {
+                builder = builder.input(CellInput::new(cell.out_point.clone(), 0));
+            }
+            for cell in &merchant_cells {
+                builder = builder.input(CellInput::new(cell.out_point.clone(), 0));
+            }
 
-        // Add Spillman Lock output
-        builder = builder
-            .output(spillman_cell.clone())
-            .output_data(Bytes::new().pack());
-
-        // Add user change output if capacity is sufficient
-        if let Some(change_cap) = user_change_opt {
-            if change_cap >= min_user_change {
-                let change_cell = CellOutput::new_builder()
-                    .capacity(change_cap)
-                    .lock(user_lock.clone())
-                    .build();
-                builder = builder.output(change_cell).output_data(Bytes::new().pack());
+            // Add Spillman Lock output
+            builder = builder
+                .output(spillman_cell)
+                .output_data(Bytes::new().pack());
+
+            // Add user change output if capacity is sufficient
+            if let Some(change_cap) = user_change_opt {
+                if change_cap >= min_user_change {
+                    let change_cell = CellOutput::new_builder()
+                        .capacity(change_cap)
+                        .lock(user_lock.clone())
+                        .build();
+                    builder = builder.output(change_cell).output_data(Bytes::new().pack());
+                }
             }
-        }
 
-        // Add merchant change output if capacity is sufficient
-        if let Some(change_cap) = merchant_change_opt {
-            if change_cap >= min_merchant_change {
-                let change_cell = CellOutput::new_builder()
-                    .capacity(change_cap)
-                    .lock(merchant_lock.clone())
-                    .build();
-                builder = builder.output(change_cell).output_data(Bytes::new().pack());
+            // Add merchant change output if capacity is sufficient
+            if let Some(change_cap) = merchant_change_opt {
+                if change_cap >= min_merchant_change {
+                    let change_cell = CellOutput::new_builder()
+                        .capacity(change_cap)
+                        .lock(merchant_lock.clone())
+                        .build();
+                    builder = builder.output(change_cell).output_data(Bytes::new().pack());
+                }
             }
-        }
 
-        // Add cell deps
-        builder = builder.cell_dep(sighash_dep.clone());
+            // Add cell deps
+            builder = builder.cell_dep(sighash_dep.clone());
 
-        // Add witnesses placeholders with correct size
-        // WitnessArgs with a 65-byte dummy signature in lock field
-        // This ensures the transaction size calculation includes the signature overhead
-        let dummy_signature = vec![0u8; 65];
-        let witness_args = ckb_types::packed::WitnessArgs::new_builder()
-            .lock(Some(Bytes::from(dummy_signature)).pack())
-            .build();
+            // Add witnesses placeholders with correct size
+            // WitnessArgs with a 65-byte dummy signature in lock field
+            // This ensures the transaction size calculation includes the signature overhead
+            let dummy_signature = vec![0u8; 65];
+            let witness_args = ckb_types::packed::WitnessArgs::new_builder()
+                .lock(Some(Bytes::from(dummy_signature)).pack())
+                .build();
 
-        let witness_count = user_cells.len() + merchant_cells.len();
-        for _ in 0..witness_count {
-            builder = builder.witness(witness_args.as_bytes().pack());
-        }
+            let witness_count = user_cells.len() + merchant_cells.len();
+            for _ in 0..witness_count {
+                builder = builder.witness(witness_args.as_bytes().pack());
+            }
 
-        builder.build()
-    };
+            builder.build()
+        };
 
     // Helper function to calculate fee from a transaction
     let calculate_tx_fee = |tx: &ckb_types::core::TransactionView| -> u64 {
@@ -449,33 +425,43 @@ pub async fn build_cofund_funding_transaction(
 
     for iteration in 0..max_iterations {
         // Calculate user change based on current fee estimate
-        let user_change_opt = {
-            let user_available = user_input_capacity
-                .checked_sub(user_capacity_shannon)
-                .and_then(|c| c.checked_sub(user_buffer_shannon))
-                .and_then(|c| c.checked_sub(current_fee));
-
-            match user_available {
-                Some(change) if change >= min_user_change => Some(change),
-                Some(change) if change > 0 => {
-                    if iteration == 0 {
-                        println!("  - ℹ️  User 找零太小 ({} shannon)，将作为手续费", change);
-                    }
-                    None
+        let user_available = user_input_capacity
+            .checked_sub(user_capacity_shannon)
+            .and_then(|c| c.checked_sub(user_buffer_shannon))
+            .and_then(|c| c.checked_sub(current_fee))
+            .ok_or_else(|| {
+                anyhow!(
+                    "User 容量不足支付手续费: input={} CKB, required={} CKB, fee={} shannon",
+                    user_input_capacity as f64 / 100_000_000.0,
+                    (user_capacity_shannon + user_buffer_shannon) as f64 / 100_000_000.0,
+                    current_fee
+                )
+            })?;
+
+        let mut funding_dust = 0;
+        let user_change_opt = match change_policy.resolve(user_available, min_user_change)? {
+            ChangeOutcome::Output(change) => Some(change),
+            ChangeOutcome::Burn(change) => {
+                if iteration == 0 {
+                    println!("  - ℹ️  User 找零太小 ({} shannon)，将作为手续费", change);
                 }
-                _ => {
-                    return Err(anyhow!(
-                        "User 容量不足支付手续费: input={} CKB, required={} CKB, fee={} shannon",
-                        user_input_capacity as f64 / 100_000_000.0,
-                        (user_capacity_shannon + user_buffer_shannon) as f64 / 100_000_000.0,
-                        current_fee
-                    ));
+                None
+            }
+            ChangeOutcome::AddToFunding(change) => {
+                if iteration == 0 {
+                    println!(
+                        "  - ℹ️  User 找零太小 ({} shannon)，将并入 Spillman cell",
+                        change
+                    );
                 }
+                funding_dust = change;
+                None
             }
+            ChangeOutcome::None => None,
         };
 
         // Build transaction with calculated changes
-        let temp_tx = build_tx(user_change_opt, merchant_change_opt);
+        let temp_tx = build_tx(user_change_opt, merchant_change_opt, funding_dust);
 
         // Calculate ACTUAL fee for this transaction (including all outputs and witnesses)
         let actual_fee = calculate_tx_fee(&temp_tx);
@@ -615,3 +601,122 @@ pub async fn build_cofund_funding_transaction(
     // Return tx_hash and output_index (Spillman Lock cell is always at index 0)
     Ok((tx_hash.unpack(), 0))
 }
+
+/// Collect the user's and the merchant's cells at the same time
+///
+/// The two queries target different lock scripts, so each runs on its own clone of
+/// `cell_collector` and the indexer round-trips overlap instead of adding up.
+/// Returns `(user_cells, merchant_cells)`, identical to two sequential collections.
+async fn collect_cells_concurrently<C: CellCollector + Clone>(
+    cell_collector: &C,
+    user_query: &CellQueryOptions,
+    merchant_query: &CellQueryOptions,
+) -> Result<(Vec<LiveCell>, Vec<LiveCell>)> {
+    let mut user_collector = cell_collector.clone();
+    let mut merchant_collector = cell_collector.clone();
+
+    let (user_result, merchant_result) = tokio::join!(
+        user_collector.collect_live_cells_async(user_query, false),
+        merchant_collector.collect_live_cells_async(merchant_query, false),
+    );
+    let (user_cells, _total_user_capacity) = user_result?;
+    let (merchant_cells, _total_merchant_capacity) = merchant_result?;
+
+    Ok((user_cells, merchant_cells))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_sdk::traits::{CellCollectorError, PrimaryScriptType};
+    use ckb_types::packed::{OutPoint, Transaction};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_spillman_cell_takes_fractional_capacity() {
+        let spillman_lock_script = Script::new_builder()
+            .args(Bytes::from(vec![0x11; 50]).pack())
+            .build();
+        let capacity = HumanCapacity::from_str("100.5").unwrap();
+
+        let cell = build_spillman_cell(&spillman_lock_script, capacity);
+        let cell_capacity: u64 = cell.capacity().unpack();
+        assert_eq!(cell_capacity, 10_050_000_000);
+        assert_eq!(cell.lock(), spillman_lock_script);
+    }
+
+    /// Collector answering every query after a fixed delay, like a remote indexer
+    #[derive(Clone)]
+    struct DelayedCellCollector {
+        delay: std::time::Duration,
+    }
+
+    #[async_trait::async_trait]
+    impl CellCollector for DelayedCellCollector {
+        async fn collect_live_cells_async(
+            &mut self,
+            query: &CellQueryOptions,
+            _apply_changes: bool,
+        ) -> Result<(Vec<LiveCell>, u64), CellCollectorError> {
+            tokio::time::sleep(self.delay).await;
+            assert_eq!(query.primary_type, PrimaryScriptType::Lock);
+            let cell = LiveCell {
+                output: CellOutput::new_builder()
+                    .lock(query.primary_script.clone())
+                    .capacity(query.min_total_capacity)
+                    .build(),
+                output_data: Bytes::new(),
+                out_point: OutPoint::new(H256([0x44; 32]).pack(), 0),
+                block_number: 0,
+                tx_index: 0,
+            };
+            Ok((vec![cell], query.min_total_capacity))
+        }
+
+        fn lock_cell(&mut self, _: OutPoint, _: u64) -> Result<(), CellCollectorError> {
+            Ok(())
+        }
+
+        fn apply_tx(&mut self, _: Transaction, _: u64) -> Result<(), CellCollectorError> {
+            Ok(())
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    #[tokio::test]
+    async fn test_cofund_cells_are_collected_concurrently() {
+        let lock = |arg_byte: u8| {
+            Script::new_builder()
+                .code_hash(SIGHASH_TYPE_HASH.pack())
+                .hash_type(ckb_types::core::ScriptHashType::Type)
+                .args(Bytes::from(vec![arg_byte; 20]).pack())
+                .build()
+        };
+        let mut user_query = CellQueryOptions::new_lock(lock(0x11));
+        user_query.min_total_capacity = 10_100_000_000;
+        let mut merchant_query = CellQueryOptions::new_lock(lock(0x22));
+        merchant_query.min_total_capacity = 6_100_000_000;
+
+        let delay = std::time::Duration::from_millis(200);
+        let collector = DelayedCellCollector { delay };
+        let started = std::time::Instant::now();
+        let (user_cells, merchant_cells) =
+            collect_cells_concurrently(&collector, &user_query, &merchant_query)
+                .await
+                .unwrap();
+        let elapsed = started.elapsed();
+
+        // Each party gets the cells of its own query
+        assert_eq!(user_cells.len(), 1);
+        assert_eq!(user_cells[0].output.lock(), lock(0x11));
+        let user_capacity: u64 = user_cells[0].output.capacity().unpack();
+        assert_eq!(user_capacity, 10_100_000_000);
+        assert_eq!(merchant_cells.len(), 1);
+        assert_eq!(merchant_cells[0].output.lock(), lock(0x22));
+
+        // Both round-trips overlap: well under the sequential 2 * delay
+        assert!(elapsed >= delay);
+        assert!(elapsed < delay * 2, "took {:?}", elapsed);
+    }
+}
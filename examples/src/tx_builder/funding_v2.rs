@@ -42,16 +42,15 @@
 /// The magic happens in `build_funding_cell()`: it checks if `outputs` is empty to
 /// determine if this is the first party (user) or second party (merchant), and
 /// adjusts the funding cell capacity accordingly.
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use ckb_sdk::{
     constants::{MultisigScript, ONE_CKB, SIGHASH_TYPE_HASH},
-    rpc::CkbRpcClient,
     traits::{
-        CellCollector, CellDepResolver, DefaultCellCollector, DefaultCellDepResolver,
+        CellCollector, CellCollectorError, CellDepResolver, CellQueryOptions, DefaultCellCollector,
         DefaultHeaderDepResolver, DefaultTransactionDependencyProvider, HeaderDepResolver,
-        SecpCkbRawKeySigner, TransactionDependencyProvider,
+        LiveCell, OffchainCellDepResolver, TransactionDependencyProvider,
     },
-    tx_builder::{unlock_tx, CapacityBalancer, TxBuilder, TxBuilderError},
+    tx_builder::{unlock_tx, CapacityBalancer, CapacityProvider, TxBuilder, TxBuilderError},
     unlock::{
         MultisigConfig as SdkMultisigConfig, ScriptUnlocker, SecpMultisigUnlocker,
         SecpSighashUnlocker,
@@ -60,17 +59,26 @@ use ckb_sdk::{
 };
 use ckb_types::{
     bytes::Bytes,
-    core::{BlockView, Capacity, ScriptHashType, TransactionView},
-    packed::{CellDep, CellOutput, Script, Transaction, WitnessArgs},
+    core::{Capacity, FeeRate, TransactionView},
+    packed::{CellDep, CellOutput, OutPoint, Script, Transaction, WitnessArgs},
     prelude::*,
     H160, H256,
 };
 use std::collections::{HashMap, HashSet};
-use std::str::FromStr;
+use std::sync::Arc;
 
-use crate::utils::config::Config;
+use crate::error::ChannelError;
+use crate::signer::{RawKeySigner, SdkSigner, Signer};
+use crate::tx_builder::capacity_utils::{
+    check_merchant_contribution, merchant_min_capacity, ChangeOutcome, ChangePolicy,
+};
+use crate::tx_builder::xudt::{read_xudt_amount, write_xudt_amount, XUDT_AMOUNT_LEN};
+use crate::utils::{
+    config::Config, deps::load_cell_dep_resolver, fee::report_fee, log::log_event, rpc,
+};
 use ckb_hash::blake2b_256;
 use ckb_sdk::traits::ValueRangeOption;
+use serde_json::json;
 
 /// Funding request parameters
 pub struct FundingRequest {
@@ -84,21 +92,128 @@ pub struct FundingRequest {
     pub xudt_type_script: Option<Script>,
     /// Optional xUDT amount to fund
     pub xudt_amount: Option<u128>,
+    /// OutPoints that must never be collected as funding inputs (e.g. reserved cells)
+    pub exclude_outpoints: Vec<OutPoint>,
+    /// Lock script receiving CKB and xUDT change (default: the first funding source)
+    pub change_lock: Option<Script>,
+    /// What to do with CKB change below the minimum (`--min-change` / `--on-dust`)
+    pub change_policy: ChangePolicy,
+    /// Exact cells to fund from, replacing automatic cell collection
+    ///
+    /// Every cell must belong to a funding source lock and all of them are spent; change
+    /// and fee are still computed. `exclude_outpoints` does not apply.
+    pub explicit_inputs: Option<Vec<OutPoint>>,
 }
 
 /// Funding context (keys and RPC)
 #[derive(Clone)]
 pub struct FundingContext {
-    /// 所有私钥（单签时只有1个，多签时有多个）
-    pub secret_keys: Vec<secp256k1::SecretKey>,
+    /// 签名器（持有单签或多签的全部私钥）
+    pub signer: Arc<dyn Signer>,
     /// 多签配置（可选，仅在多签时使用）- 使用 SDK 的 MultisigConfig
     pub multisig_config: Option<SdkMultisigConfig>,
     pub rpc_url: String,
-    pub funding_source_lock_script: Script,
+    /// Lock scripts to collect funding cells from (the first one also receives change)
+    ///
+    /// `signer` must hold the key of every source lock.
+    pub funding_source_lock_scripts: Vec<Script>,
     /// Optional xUDT cell dep (for xUDT transactions)
     pub xudt_cell_dep: Option<CellDep>,
     /// Optional pre-created cell dep resolver (to avoid repeated genesis queries)
-    pub cell_dep_resolver: Option<DefaultCellDepResolver>,
+    pub cell_dep_resolver: Option<OffchainCellDepResolver>,
+}
+
+impl FundingContext {
+    /// Create a context funded from a single source lock script
+    pub fn new_single_source(
+        signer: Arc<dyn Signer>,
+        multisig_config: Option<SdkMultisigConfig>,
+        rpc_url: String,
+        funding_source_lock_script: Script,
+        xudt_cell_dep: Option<CellDep>,
+        cell_dep_resolver: Option<OffchainCellDepResolver>,
+    ) -> Self {
+        Self {
+            signer,
+            multisig_config,
+            rpc_url,
+            funding_source_lock_scripts: vec![funding_source_lock_script],
+            xudt_cell_dep,
+            cell_dep_resolver,
+        }
+    }
+
+    /// Lock script for change outputs (the first funding source)
+    pub fn change_lock_script(&self) -> Result<&Script> {
+        self.funding_source_lock_scripts
+            .first()
+            .ok_or_else(|| anyhow!("No funding source lock script configured"))
+    }
+
+    /// Queries collecting xUDT cells from every funding source lock
+    pub fn xudt_cell_queries(&self, type_script: &Script) -> Vec<CellQueryOptions> {
+        self.funding_source_lock_scripts
+            .iter()
+            .map(|lock_script| {
+                let mut query = CellQueryOptions::new_lock(lock_script.clone());
+                query.secondary_script = Some(type_script.clone());
+                query.data_len_range = Some(ValueRangeOption::new_min(16));
+                // Set min_total_capacity to a large value to collect all matching cells
+                // Default is 1 shannon which stops after collecting just one cell
+                query.min_total_capacity = u64::MAX;
+                query
+            })
+            .collect()
+    }
+
+    /// Capacity provider covering every funding source lock
+    pub fn capacity_provider(&self, placeholder_witness: WitnessArgs) -> CapacityProvider {
+        CapacityProvider::new_simple(
+            self.funding_source_lock_scripts
+                .iter()
+                .map(|lock_script| (lock_script.clone(), placeholder_witness.clone()))
+                .collect(),
+        )
+    }
+
+    /// Unlockers for the funding source locks
+    ///
+    /// The sighash unlocker is always registered. A source locked by a Legacy or V2
+    /// multisig script gets a `SecpMultisigUnlocker` for that script with `multisig_config`,
+    /// which is then required.
+    pub fn unlockers(&self) -> Result<HashMap<ScriptId, Box<dyn ScriptUnlocker>>> {
+        let signer = SdkSigner(self.signer.clone());
+        let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+        unlockers.insert(
+            ScriptId::new_type(SIGHASH_TYPE_HASH.clone()),
+            Box::new(SecpSighashUnlocker::from(Box::new(signer.clone()) as Box<_>)),
+        );
+
+        let multisig_script_ids = [
+            MultisigScript::Legacy.script_id(),
+            MultisigScript::V2.script_id(),
+        ];
+        for lock_script in &self.funding_source_lock_scripts {
+            let script_id = ScriptId::from(lock_script);
+            if !multisig_script_ids.contains(&script_id) || unlockers.contains_key(&script_id) {
+                continue;
+            }
+            let config = self.multisig_config.clone().ok_or_else(|| {
+                anyhow!(
+                    "Funding source {} is a multisig lock, but no multisig config was given",
+                    hex::encode(lock_script.args().raw_data())
+                )
+            })?;
+            unlockers.insert(
+                script_id,
+                Box::new(SecpMultisigUnlocker::from((
+                    Box::new(signer.clone()) as Box<_>,
+                    config,
+                ))),
+            );
+        }
+        Ok(unlockers)
+    }
 }
 
 /// Funding transaction wrapper
@@ -149,44 +264,15 @@ impl FundingTx {
     }
 
     /// Sign the funding transaction with multiple keys (for co-funding)
+    ///
+    /// `signer` must hold the keys of both parties.
     pub async fn sign_with_multiple_keys(
         mut self,
-        secret_keys: Vec<secp256k1::SecretKey>,
+        signer: Arc<dyn Signer>,
         multisig_config: Option<SdkMultisigConfig>,
         rpc_url: String,
     ) -> Result<Self> {
-        let signer = SecpCkbRawKeySigner::new_with_secret_keys(secret_keys);
-
-        let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
-
-        // Always register SIGHASH unlocker (for user's single-sig inputs)
-        let sighash_unlocker = SecpSighashUnlocker::from(Box::new(signer.clone()) as Box<_>);
-        let sighash_script_id = ScriptId::new_type(SIGHASH_TYPE_HASH.clone());
-        unlockers.insert(
-            sighash_script_id,
-            Box::new(sighash_unlocker) as Box<dyn ScriptUnlocker>,
-        );
-
-        // Register MULTISIG unlocker if merchant is using multisig
-        if let Some(config) = multisig_config {
-            // Register Legacy multisig unlocker
-            let legacy_multisig_unlocker =
-                SecpMultisigUnlocker::from((Box::new(signer.clone()) as Box<_>, config.clone()));
-            let legacy_script_id = MultisigScript::Legacy.script_id();
-            unlockers.insert(
-                legacy_script_id,
-                Box::new(legacy_multisig_unlocker) as Box<dyn ScriptUnlocker>,
-            );
-
-            // Register V2 multisig unlocker
-            let v2_multisig_unlocker =
-                SecpMultisigUnlocker::from((Box::new(signer) as Box<_>, config));
-            let v2_script_id = MultisigScript::V2.script_id();
-            unlockers.insert(
-                v2_script_id,
-                Box::new(v2_multisig_unlocker) as Box<dyn ScriptUnlocker>,
-            );
-        }
+        let unlockers = build_unlockers(signer, multisig_config);
 
         let tx = self
             .take()
@@ -220,6 +306,180 @@ impl From<Transaction> for FundingTx {
     }
 }
 
+/// Cell collector that never yields the excluded OutPoints
+///
+/// Excluded cells are locked in the inner collector so the indexer skips them while
+/// filling `min_total_capacity`, and filtered again from every result as a safeguard.
+#[derive(Clone)]
+struct ExcludingCellCollector<C> {
+    inner: C,
+    excluded: HashSet<OutPoint>,
+}
+
+impl<C: CellCollector> ExcludingCellCollector<C> {
+    fn new(mut inner: C, excluded: &[OutPoint]) -> Result<Self> {
+        for out_point in excluded {
+            // u64::MAX keeps the lock from expiring as the tip advances
+            inner.lock_cell(out_point.clone(), u64::MAX)?;
+        }
+        Ok(Self {
+            inner,
+            excluded: excluded.iter().cloned().collect(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<C: CellCollector + Clone> CellCollector for ExcludingCellCollector<C> {
+    async fn collect_live_cells_async(
+        &mut self,
+        query: &CellQueryOptions,
+        apply_changes: bool,
+    ) -> Result<(Vec<LiveCell>, u64), CellCollectorError> {
+        let (cells, _) = self
+            .inner
+            .collect_live_cells_async(query, apply_changes)
+            .await?;
+        let cells: Vec<LiveCell> = cells
+            .into_iter()
+            .filter(|cell| !self.excluded.contains(&cell.out_point))
+            .collect();
+        let total_capacity = cells
+            .iter()
+            .map(|cell| Unpack::<u64>::unpack(&cell.output.capacity()))
+            .sum();
+        Ok((cells, total_capacity))
+    }
+
+    fn lock_cell(
+        &mut self,
+        out_point: OutPoint,
+        tip_block_number: u64,
+    ) -> Result<(), CellCollectorError> {
+        self.inner.lock_cell(out_point, tip_block_number)
+    }
+
+    fn apply_tx(
+        &mut self,
+        tx: Transaction,
+        tip_block_number: u64,
+    ) -> Result<(), CellCollectorError> {
+        self.inner.apply_tx(tx, tip_block_number)
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+/// Cell collector yielding only a caller-chosen set of cells
+///
+/// Every query returns all unused chosen cells it matches, ignoring `min_total_capacity`,
+/// so the balancer spends the whole set. Cells are marked used on `apply_changes`.
+#[derive(Clone)]
+struct ExplicitCellCollector {
+    cells: Vec<LiveCell>,
+    used: HashSet<OutPoint>,
+}
+
+impl ExplicitCellCollector {
+    fn new(cells: Vec<LiveCell>) -> Self {
+        Self {
+            cells,
+            used: HashSet::new(),
+        }
+    }
+
+    /// Resolve the chosen OutPoints into live cells
+    async fn resolve(
+        out_points: &[OutPoint],
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<Self> {
+        let mut cells = Vec::with_capacity(out_points.len());
+        for out_point in out_points {
+            if cells
+                .iter()
+                .any(|cell: &LiveCell| &cell.out_point == out_point)
+            {
+                return Err(anyhow!("Duplicate input {}", format_out_point(out_point)));
+            }
+            let resolve_err = |e| {
+                anyhow!(
+                    "Failed to resolve input {}: {}",
+                    format_out_point(out_point),
+                    e
+                )
+            };
+            let output = tx_dep_provider
+                .get_cell_async(out_point)
+                .await
+                .map_err(resolve_err)?;
+            let output_data = tx_dep_provider
+                .get_cell_data_async(out_point)
+                .await
+                .map_err(resolve_err)?;
+            cells.push(LiveCell {
+                output,
+                output_data,
+                out_point: out_point.clone(),
+                block_number: 0,
+                tx_index: 0,
+            });
+        }
+        Ok(Self::new(cells))
+    }
+}
+
+#[async_trait::async_trait]
+impl CellCollector for ExplicitCellCollector {
+    async fn collect_live_cells_async(
+        &mut self,
+        query: &CellQueryOptions,
+        apply_changes: bool,
+    ) -> Result<(Vec<LiveCell>, u64), CellCollectorError> {
+        let cells: Vec<LiveCell> = self
+            .cells
+            .iter()
+            .filter(|cell| !self.used.contains(&cell.out_point) && query.match_cell(cell, u64::MAX))
+            .cloned()
+            .collect();
+        if apply_changes {
+            self.used
+                .extend(cells.iter().map(|cell| cell.out_point.clone()));
+        }
+        let total_capacity = cells
+            .iter()
+            .map(|cell| Unpack::<u64>::unpack(&cell.output.capacity()))
+            .sum();
+        Ok((cells, total_capacity))
+    }
+
+    fn lock_cell(&mut self, out_point: OutPoint, _: u64) -> Result<(), CellCollectorError> {
+        self.used.insert(out_point);
+        Ok(())
+    }
+
+    fn apply_tx(&mut self, tx: Transaction, _: u64) -> Result<(), CellCollectorError> {
+        self.used.extend(
+            tx.raw()
+                .inputs()
+                .into_iter()
+                .map(|input| input.previous_output()),
+        );
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.used.clear();
+    }
+}
+
+/// `tx_hash:index` form of an OutPoint for messages
+fn format_out_point(out_point: &OutPoint) -> String {
+    let index: u32 = out_point.index().unpack();
+    format!("{:#x}:{}", out_point.tx_hash(), index)
+}
+
 /// Internal builder implementing TxBuilder trait
 struct FundingTxBuilder {
     funding_tx: FundingTx,
@@ -236,7 +496,8 @@ impl TxBuilder for FundingTxBuilder {
         _header_dep_resolver: &dyn HeaderDepResolver,
         _tx_dep_provider: &dyn TransactionDependencyProvider,
     ) -> Result<TransactionView, TxBuilderError> {
-        let (funding_cell_output, funding_cell_data) = self.build_funding_cell();
+        let (funding_cell_output, funding_cell_data) =
+            self.build_funding_cell().map_err(TxBuilderError::Other)?;
 
         let mut inputs = vec![];
         let mut cell_deps = HashSet::new();
@@ -291,6 +552,14 @@ impl TxBuilder for FundingTxBuilder {
 }
 
 impl FundingTxBuilder {
+    /// Lock script for change outputs: the request's override or the context default
+    fn change_lock_script(&self) -> Result<Script> {
+        match &self.request.change_lock {
+            Some(change_lock) => Ok(change_lock.clone()),
+            None => self.context.change_lock_script().cloned(),
+        }
+    }
+
     /// Build the funding cell output and data
     ///
     /// This method implements incremental construction for co-funding:
@@ -300,7 +569,9 @@ impl FundingTxBuilder {
     /// For xUDT channels:
     /// - Adds type script to the funding cell
     /// - Returns cell data containing xUDT amount (16 bytes, u128 little-endian)
-    fn build_funding_cell(&self) -> (CellOutput, Bytes) {
+    ///
+    /// Fails (instead of panicking) if adding the local amount to the existing one overflows.
+    fn build_funding_cell(&self) -> Result<(CellOutput, Bytes)> {
         // Calculate total capacity (CKB)
         let total_capacity = if let Some(tx) = self.funding_tx.tx.as_ref() {
             if let Some(existing_output) = tx.outputs().get(0) {
@@ -308,7 +579,13 @@ impl FundingTxBuilder {
                 let existing_capacity: u64 = existing_output.capacity().unpack();
                 existing_capacity
                     .checked_add(self.request.local_amount)
-                    .expect("Capacity overflow")
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Funding cell capacity overflow: existing {} + local {} shannons",
+                            existing_capacity,
+                            self.request.local_amount
+                        )
+                    })?
             } else {
                 // First party: use local_amount only
                 self.request.local_amount
@@ -324,12 +601,15 @@ impl FundingTxBuilder {
                 if let Some(existing_data) = tx.outputs_data().get(0) {
                     // Second party: add to existing xUDT amount
                     let existing_data_bytes: Vec<u8> = existing_data.unpack();
-                    if existing_data_bytes.len() >= 16 {
-                        let existing_amount =
-                            u128::from_le_bytes(existing_data_bytes[0..16].try_into().unwrap());
-                        existing_amount
-                            .checked_add(xudt_amount)
-                            .expect("xUDT amount overflow")
+                    if existing_data_bytes.len() >= XUDT_AMOUNT_LEN {
+                        let existing_amount = read_xudt_amount(&existing_data_bytes)?;
+                        existing_amount.checked_add(xudt_amount).ok_or_else(|| {
+                            anyhow!(
+                                "Funding cell xUDT amount overflow: existing {} + local {}",
+                                existing_amount,
+                                xudt_amount
+                            )
+                        })?
                     } else {
                         xudt_amount
                     }
@@ -360,13 +640,13 @@ impl FundingTxBuilder {
         // Build cell data
         let data = if self.request.xudt_type_script.is_some() {
             // xUDT channel: 16 bytes for amount
-            Bytes::from(total_xudt_amount.to_le_bytes().to_vec())
+            write_xudt_amount(total_xudt_amount)
         } else {
             // CKB-only channel: empty data
             Bytes::new()
         };
 
-        (output, data)
+        Ok((output, data))
     }
 
     /// Collect xUDT cells and add change output if needed
@@ -374,16 +654,21 @@ impl FundingTxBuilder {
     /// This method modifies the base transaction to:
     /// 1. Add xUDT inputs to cover the required amount
     /// 2. Add xUDT change output if there's余额
+    /// 3. Pay this party's capacity and fee from the surplus CKB of the xUDT inputs when
+    ///    it is large enough, keeping the leftover CKB in the xUDT change cell
+    ///
+    /// Returns the updated transaction and whether its capacity is already balanced
+    /// (in which case no plain CKB cells are needed).
     async fn balance_xudt_cells(
         &self,
         base_tx: TransactionView,
         cell_collector: &mut dyn CellCollector,
         cell_dep_resolver: &dyn CellDepResolver,
-    ) -> Result<TransactionView> {
+    ) -> Result<(TransactionView, bool)> {
         // Only process if this is an xUDT transaction
         let xudt_amount = match self.request.xudt_amount {
             Some(amount) if amount > 0 => amount,
-            _ => return Ok(base_tx),
+            _ => return Ok((base_tx, false)),
         };
 
         let type_script = self
@@ -391,66 +676,19 @@ impl FundingTxBuilder {
             .xudt_type_script
             .as_ref()
             .ok_or_else(|| anyhow!("xUDT amount specified but no type script"))?;
-        // Collect all cells with matching lock script
-        use ckb_sdk::traits::CellQueryOptions;
-        let mut query = CellQueryOptions::new_lock(self.context.funding_source_lock_script.clone());
-        query.secondary_script = Some(type_script.clone());
-        query.data_len_range = Some(ValueRangeOption::new_min(16));
-        // Set min_total_capacity to a large value to collect all matching cells
-        // Default is 1 shannon which stops after collecting just one cell
-        query.min_total_capacity = u64::MAX;
-        let (cells, _) = cell_collector
-            .collect_live_cells_async(&query, false)
-            .await?;
-
-        println!("  - Found {} cells with matching lock script", cells.len());
-
-        // Filter cells with matching type script and collect xUDT amounts
-        let mut xudt_inputs = vec![];
-        let mut collected_xudt_amount = 0u128;
-        let mut cells_with_type = 0;
-        let mut cells_without_type = 0;
-
-        println!("  - Cells: {:?}", cells.len());
-
-        for cell in cells {
-            // Check if cell has the matching type script
-            if let Some(cell_type) = cell.output.type_().to_opt() {
-                cells_with_type += 1;
-
-                if cell_type.as_slice() == type_script.as_slice() {
-                    // Parse xUDT amount from cell data
-                    let data_bytes = cell.output_data.to_vec();
-                    if data_bytes.len() >= 16 {
-                        let amount = u128::from_le_bytes(data_bytes[0..16].try_into().unwrap());
-                        println!("  - ✓ Found matching xUDT cell with amount: {}", amount);
-                        collected_xudt_amount += amount;
-                        xudt_inputs.push(cell);
-
-                        if collected_xudt_amount >= xudt_amount {
-                            break;
-                        }
-                    }
-                } else {
-                    println!("  - ✗ Type script doesn't match");
-                }
-            } else {
-                cells_without_type += 1;
-            }
+        // Collect all cells with matching lock script from every funding source
+        let mut cells = vec![];
+        for query in self.context.xudt_cell_queries(type_script) {
+            let (source_cells, _) = cell_collector
+                .collect_live_cells_async(&query, false)
+                .await?;
+            cells.extend(source_cells);
         }
 
-        println!(
-            "  - Summary: {} cells with type script, {} cells without type script",
-            cells_with_type, cells_without_type
-        );
+        println!("  - Found {} cells with matching lock script", cells.len());
 
-        if collected_xudt_amount < xudt_amount {
-            return Err(anyhow!(
-                "Insufficient xUDT balance: collected {}, required {}",
-                collected_xudt_amount,
-                xudt_amount
-            ));
-        }
+        let (xudt_inputs, collected_xudt_amount) =
+            select_xudt_cells(cells, type_script, xudt_amount)?;
 
         println!(
             "  - Collected {} xUDT from {} cells",
@@ -490,18 +728,19 @@ impl FundingTxBuilder {
         }
 
         // Add xUDT change output if needed
+        let mut xudt_change_index = None;
         if change_amount > 0 {
             println!("  - Adding xUDT change output: {} xUDT", change_amount);
 
             // Calculate minimum capacity for xUDT change cell
             let change_output = CellOutput::new_builder()
-                .lock(self.context.funding_source_lock_script.clone())
+                .lock(self.change_lock_script()?)
                 .type_(Some(type_script.clone()).pack())
                 .build();
 
             let min_capacity = change_output
-                .occupied_capacity(Capacity::bytes(16).unwrap())
-                .unwrap()
+                .occupied_capacity(Capacity::bytes(XUDT_AMOUNT_LEN)?)
+                .context("computing xUDT change occupied capacity")?
                 .as_u64();
 
             let change_output = change_output
@@ -509,8 +748,9 @@ impl FundingTxBuilder {
                 .capacity(Capacity::shannons(min_capacity).pack())
                 .build();
 
-            let change_data = Bytes::from(change_amount.to_le_bytes().to_vec());
+            let change_data = write_xudt_amount(change_amount);
 
+            xudt_change_index = Some(outputs.len());
             outputs.push(change_output);
             outputs_data.push(change_data.pack());
         }
@@ -518,13 +758,11 @@ impl FundingTxBuilder {
         // Collect cell deps from existing transaction
         let mut cell_deps: Vec<_> = base_tx.cell_deps().into_iter().collect();
 
-        // Resolve and add cell deps for newly added xUDT inputs
-        if !xudt_inputs.is_empty() {
-            // Get lock script from the first xUDT input (they should all have the same lock script)
-            let lock_script = &xudt_inputs[0].output.lock();
-
+        // Resolve and add cell deps for newly added xUDT inputs (inputs may come from
+        // different funding source locks)
+        for cell in &xudt_inputs {
             // Resolve cell dep for the lock script (e.g., secp256k1)
-            if let Some(cell_dep) = cell_dep_resolver.resolve(lock_script) {
+            if let Some(cell_dep) = cell_dep_resolver.resolve(&cell.output.lock()) {
                 // Check if this cell dep is already in the list (compare by out_point)
                 let new_out_point = cell_dep.out_point();
                 let already_exists = cell_deps.iter().any(|d| d.out_point() == new_out_point);
@@ -544,25 +782,206 @@ impl FundingTxBuilder {
             .set_witnesses(witnesses)
             .build();
 
+        let xudt_input_capacity = xudt_inputs
+            .iter()
+            .map(|cell| Unpack::<u64>::unpack(&cell.output.capacity()))
+            .sum();
+        match self.cover_capacity_with_xudt_surplus(&tx, xudt_change_index, xudt_input_capacity) {
+            Some(tx) => Ok((tx, true)),
+            None => Ok((tx, false)),
+        }
+    }
+
+    /// Try to pay this party's capacity and fee from the xUDT inputs alone
+    ///
+    /// The new inputs must cover `local_amount`, the xUDT change cell floor and the fee
+    /// for the bytes this party adds (the existing part of a co-funded transaction has
+    /// already paid for its own size). Whatever is left goes into the xUDT change cell.
+    ///
+    /// Returns `None` when the surplus is not enough, or when there is leftover CKB but
+    /// no xUDT change cell to hold it; the capacity balancer then takes over.
+    fn cover_capacity_with_xudt_surplus(
+        &self,
+        tx: &TransactionView,
+        xudt_change_index: Option<usize>,
+        xudt_input_capacity: u64,
+    ) -> Option<TransactionView> {
+        let fee_rate = FeeRate::from_u64(self.request.fee_rate);
+        let tx_fee = |tx: &TransactionView| {
+            fee_rate
+                .fee(tx.data().as_reader().serialized_size_in_block() as u64)
+                .as_u64()
+        };
+        let paid_fee = self.funding_tx.tx.as_ref().map(tx_fee).unwrap_or(0);
+        let fee = tx_fee(tx).saturating_sub(paid_fee);
+
+        let change_output = xudt_change_index.and_then(|index| tx.outputs().get(index));
+        let change_capacity: u64 = change_output
+            .as_ref()
+            .map(|output| output.capacity().unpack())
+            .unwrap_or(0);
+        let required = self
+            .request
+            .local_amount
+            .checked_add(change_capacity)?
+            .checked_add(fee)?;
+        let leftover = xudt_input_capacity.checked_sub(required)?;
+
+        if leftover == 0 {
+            return Some(tx.clone());
+        }
+        let (index, change_output) = (xudt_change_index?, change_output?);
+
+        println!(
+            "  - xUDT cell 自带的 CKB 足够支付容量和手续费 ({} CKB)，剩余 {} CKB 放入 xUDT 找零",
+            HumanCapacity(fee),
+            HumanCapacity(leftover)
+        );
+        let mut outputs: Vec<_> = tx.outputs().into_iter().collect();
+        outputs[index] = change_output
+            .as_builder()
+            .capacity(Capacity::shannons(change_capacity + leftover).pack())
+            .build();
+        Some(tx.as_advanced_builder().set_outputs(outputs).build())
+    }
+
+    /// Add xUDT inputs, then plain CKB inputs only if the xUDT inputs cannot pay
+    async fn balance_funding_tx(
+        &self,
+        base_tx: TransactionView,
+        balancer: &mut CapacityBalancer,
+        cell_collector: &mut dyn CellCollector,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+        cell_dep_resolver: &dyn CellDepResolver,
+        header_dep_resolver: &dyn HeaderDepResolver,
+    ) -> Result<TransactionView> {
+        let first_new_output = base_tx.outputs().len();
+
+        // Balance xUDT cells first (if this is an xUDT transaction)
+        let (xudt_balanced_tx, capacity_balanced) = self
+            .balance_xudt_cells(base_tx, cell_collector, cell_dep_resolver)
+            .await?;
+        let balanced_tx = if capacity_balanced {
+            xudt_balanced_tx
+        } else {
+            // Balance the transaction (add inputs for this party)
+            balancer.balance_tx_capacity(
+                &xudt_balanced_tx,
+                cell_collector,
+                tx_dep_provider,
+                cell_dep_resolver,
+                header_dep_resolver,
+            )?
+        };
+
+        let tx = self.apply_change_policy(balanced_tx, first_new_output)?;
+        self.check_explicit_inputs_spent(&tx)?;
         Ok(tx)
     }
 
+    /// Ensure every explicit input ended up in the transaction
+    ///
+    /// A chosen cell is left out when it matches no funding source lock (or no query,
+    /// e.g. an xUDT cell of another token) or when fewer xUDT cells already cover the amount.
+    fn check_explicit_inputs_spent(&self, tx: &TransactionView) -> Result<()> {
+        let Some(out_points) = &self.request.explicit_inputs else {
+            return Ok(());
+        };
+        let spent: HashSet<OutPoint> = tx
+            .inputs()
+            .into_iter()
+            .map(|input| input.previous_output())
+            .collect();
+        match out_points
+            .iter()
+            .find(|out_point| !spent.contains(*out_point))
+        {
+            Some(out_point) => Err(anyhow!(
+                "Input {} was not used: it does not belong to a funding source or is not needed",
+                format_out_point(out_point)
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Apply the request's change policy to the CKB change added by this party
+    ///
+    /// The change is the plain cell of the change lock at or after `first_new_output`.
+    /// Dust is rejected, dropped (paid as fee) or moved into the funding cell.
+    fn apply_change_policy(
+        &self,
+        tx: TransactionView,
+        first_new_output: usize,
+    ) -> Result<TransactionView> {
+        let change_lock = self.change_lock_script()?;
+        let change = tx
+            .outputs()
+            .into_iter()
+            .zip(tx.outputs_data())
+            .enumerate()
+            .skip(first_new_output)
+            .find(|(_, (output, data))| {
+                output.lock() == change_lock
+                    && output.type_().is_none()
+                    && data.raw_data().is_empty()
+            });
+        let Some((change_index, (change_output, _))) = change else {
+            return Ok(tx);
+        };
+
+        let change_capacity: u64 = change_output.capacity().unpack();
+        let min_occupied = change_output
+            .occupied_capacity(Capacity::zero())
+            .map_err(|e| anyhow!("Failed to calculate change capacity: {:?}", e))?
+            .as_u64();
+        let funding_dust = match self
+            .request
+            .change_policy
+            .resolve(change_capacity, min_occupied)?
+        {
+            ChangeOutcome::None | ChangeOutcome::Output(_) => return Ok(tx),
+            ChangeOutcome::Burn(dust) => {
+                println!("  - ℹ️  找零太小 ({} shannon)，将作为手续费", dust);
+                0
+            }
+            ChangeOutcome::AddToFunding(dust) => {
+                println!("  - ℹ️  找零太小 ({} shannon)，将并入 Spillman cell", dust);
+                dust
+            }
+        };
+
+        let mut outputs: Vec<_> = tx.outputs().into_iter().collect();
+        let mut outputs_data: Vec<_> = tx.outputs_data().into_iter().collect();
+        outputs.remove(change_index);
+        outputs_data.remove(change_index);
+        let tx = tx
+            .as_advanced_builder()
+            .set_outputs(outputs)
+            .set_outputs_data(outputs_data)
+            .build();
+        if funding_dust == 0 {
+            return Ok(tx);
+        }
+
+        let funding_index = find_funding_output_index(&tx, &self.request.script)? as usize;
+        let mut outputs: Vec<_> = tx.outputs().into_iter().collect();
+        let funding_capacity: u64 = outputs[funding_index].capacity().unpack();
+        outputs[funding_index] = outputs[funding_index]
+            .clone()
+            .as_builder()
+            .capacity(funding_capacity + funding_dust)
+            .build();
+        Ok(tx.as_advanced_builder().set_outputs(outputs).build())
+    }
+
     /// Internal build method that orchestrates the entire build process
     ///
     /// # Arguments
     /// * `should_sign` - Whether to sign the transaction immediately
     async fn build_internal(self, should_sign: bool) -> Result<FundingTx> {
-        // Step 1: Create unlockers with the secret keys from context (user is always single-sig)
-        let signer = SecpCkbRawKeySigner::new_with_secret_keys(self.context.secret_keys.clone());
-        let sighash_unlocker = SecpSighashUnlocker::from(Box::new(signer) as Box<_>);
-        let sighash_script_id = ScriptId::new_type(SIGHASH_TYPE_HASH.clone());
-        let mut unlockers = HashMap::default();
-        unlockers.insert(
-            sighash_script_id,
-            Box::new(sighash_unlocker) as Box<dyn ScriptUnlocker>,
-        );
-
-        let sender = self.context.funding_source_lock_script.clone();
+        // Step 1: Create unlockers with the secret keys from context
+        // Sighash sources use the signer's keys, multisig sources add the multisig config
+        let unlockers = self.context.unlockers()?;
 
         // Step 2: Create capacity balancer with appropriate placeholder witness
         let placeholder_witness = if let Some(ref config) = self.context.multisig_config {
@@ -575,32 +994,32 @@ impl FundingTxBuilder {
                 .build()
         };
 
-        let mut balancer = CapacityBalancer::new_simple(
-            sender.clone(),
-            placeholder_witness,
+        let mut balancer = CapacityBalancer::new_with_provider(
             self.request.fee_rate,
+            self.context.capacity_provider(placeholder_witness),
         );
+        balancer.change_lock_script = Some(self.change_lock_script()?);
 
         // Step 3: Setup providers
-        let ckb_client = CkbRpcClient::new(&self.context.rpc_url);
-
         // Use pre-created resolver from context if available, otherwise create one
         let cell_dep_resolver = if let Some(resolver) = &self.context.cell_dep_resolver {
             resolver.clone()
         } else {
-            match ckb_client.get_block_by_number(0.into())? {
-                Some(genesis_block) => {
-                    DefaultCellDepResolver::from_genesis(&BlockView::from(genesis_block))?
-                }
-                None => {
-                    return Err(anyhow!("Failed to get genesis block"));
-                }
-            }
+            load_cell_dep_resolver(&self.context.rpc_url)?
         };
 
         let header_dep_resolver = DefaultHeaderDepResolver::new(&self.context.rpc_url);
-        let mut cell_collector = DefaultCellCollector::new(&self.context.rpc_url);
         let tx_dep_provider = DefaultTransactionDependencyProvider::new(&self.context.rpc_url, 10);
+        // Explicit inputs replace automatic collection entirely
+        let mut cell_collector: Box<dyn CellCollector> = match &self.request.explicit_inputs {
+            Some(out_points) => {
+                Box::new(ExplicitCellCollector::resolve(out_points, &tx_dep_provider).await?)
+            }
+            None => Box::new(ExcludingCellCollector::new(
+                DefaultCellCollector::new(&self.context.rpc_url),
+                &self.request.exclude_outpoints,
+            )?),
+        };
 
         // Step 4: Build transaction
         let is_incremental = self.funding_tx.tx.is_some();
@@ -609,51 +1028,45 @@ impl FundingTxBuilder {
             // Build without signing (for co-funding - sign later with all keys)
             let base_tx = self
                 .build_base_async(
-                    &mut cell_collector,
+                    cell_collector.as_mut(),
                     &cell_dep_resolver,
                     &header_dep_resolver,
                     &tx_dep_provider,
                 )
                 .await?;
 
-            // Balance xUDT cells first (if this is an xUDT transaction)
-            let xudt_balanced_tx = self
-                .balance_xudt_cells(base_tx, &mut cell_collector, &cell_dep_resolver)
-                .await?;
-
-            // Balance the transaction (add inputs for this party)
-            balancer.balance_tx_capacity(
-                &xudt_balanced_tx,
-                &mut cell_collector,
+            self.balance_funding_tx(
+                base_tx,
+                &mut balancer,
+                cell_collector.as_mut(),
                 &tx_dep_provider,
                 &cell_dep_resolver,
                 &header_dep_resolver,
-            )?
+            )
+            .await?
         } else if is_incremental {
             // Incremental construction: build and balance, but preserve existing signatures
             let base_tx = self
                 .build_base_async(
-                    &mut cell_collector,
+                    cell_collector.as_mut(),
                     &cell_dep_resolver,
                     &header_dep_resolver,
                     &tx_dep_provider,
                 )
                 .await?;
 
-            // Balance xUDT cells first (if this is an xUDT transaction)
-            let xudt_balanced_tx = self
-                .balance_xudt_cells(base_tx, &mut cell_collector, &cell_dep_resolver)
+            // Balance xUDT and capacity (add inputs for this party)
+            let balanced_tx = self
+                .balance_funding_tx(
+                    base_tx,
+                    &mut balancer,
+                    cell_collector.as_mut(),
+                    &tx_dep_provider,
+                    &cell_dep_resolver,
+                    &header_dep_resolver,
+                )
                 .await?;
 
-            // Balance the transaction (add inputs for this party)
-            let balanced_tx = balancer.balance_tx_capacity(
-                &xudt_balanced_tx,
-                &mut cell_collector,
-                &tx_dep_provider,
-                &cell_dep_resolver,
-                &header_dep_resolver,
-            )?;
-
             // Unlock only the NEW inputs added by this party
             // Get the number of existing inputs from the original transaction
             let existing_input_count = self
@@ -707,27 +1120,25 @@ impl FundingTxBuilder {
             // First party: build, balance xUDT, balance capacity, then unlock
             let base_tx = self
                 .build_base_async(
-                    &mut cell_collector,
+                    cell_collector.as_mut(),
                     &cell_dep_resolver,
                     &header_dep_resolver,
                     &tx_dep_provider,
                 )
                 .await?;
 
-            // Balance xUDT cells first (if this is an xUDT transaction)
-            let xudt_balanced_tx = self
-                .balance_xudt_cells(base_tx, &mut cell_collector, &cell_dep_resolver)
+            // Balance xUDT first, then capacity only if the xUDT inputs cannot pay
+            let balanced_tx = self
+                .balance_funding_tx(
+                    base_tx,
+                    &mut balancer,
+                    cell_collector.as_mut(),
+                    &tx_dep_provider,
+                    &cell_dep_resolver,
+                    &header_dep_resolver,
+                )
                 .await?;
 
-            // Balance capacity
-            let balanced_tx = balancer.balance_tx_capacity(
-                &xudt_balanced_tx,
-                &mut cell_collector,
-                &tx_dep_provider,
-                &cell_dep_resolver,
-                &header_dep_resolver,
-            )?;
-
             // Unlock
             let (tx, still_locked_groups) = unlock_tx(balanced_tx, &tx_dep_provider, &unlockers)?;
 
@@ -764,25 +1175,31 @@ impl FundingTxBuilder {
 /// * `capacity` - Capacity (supports both u64 shannon and HumanCapacity)
 ///   - Can be created from u64: `HumanCapacity::from(10000000000)`
 ///   - Can be parsed from string: `HumanCapacity::from_str("100.5")?`
+/// * `change_policy` - What to do with change below `--min-change`
 /// * `output_path` - Path to save the signed transaction JSON
+/// * `unsigned` - Skip signing and save the balanced transaction with its placeholder
+///   witnesses, for an external signing tool (`--save-unsigned`)
 ///
 /// # Examples
 /// ```ignore
 /// // From u64 (shannon)
-/// build_funding_transaction(config, addr, script, 100_00000000.into(), path).await?;
+/// build_funding_transaction(config, addr, script, 100_00000000.into(), 1000, ChangePolicy::default(), path, None, false).await?;
 ///
 /// // From string (CKB)
 /// let capacity = HumanCapacity::from_str("100.5")?;
-/// build_funding_transaction(config, addr, script, capacity, path).await?;
+/// build_funding_transaction(config, addr, script, capacity, 1000, ChangePolicy::default(), path, None, false).await?;
 /// ```
+#[allow(clippy::too_many_arguments)]
 pub async fn build_funding_transaction(
     config: &Config,
     user_address: &Address,
     spillman_lock_script: &Script,
     capacity: HumanCapacity,
     fee_rate: u64,
+    change_policy: ChangePolicy,
     output_path: &str,
     xudt_amount: Option<u128>,
+    unsigned: bool,
 ) -> Result<(H256, u32)> {
     let capacity_shannon: u64 = capacity.into();
 
@@ -791,45 +1208,15 @@ pub async fn build_funding_transaction(
         capacity, capacity_shannon
     );
 
-    // Build xUDT type script and cell dep if xudt_amount is provided
-    let (xudt_type_script, xudt_cell_dep) = if xudt_amount.is_some() {
-        if let Some(ref usdi_config) = config.usdi {
-            // Build xUDT type script
-            let code_hash = H256::from_str(usdi_config.code_hash.trim_start_matches("0x"))
-                .map_err(|e| anyhow!("Invalid code_hash: {}", e))?;
-            let args = ckb_types::bytes::Bytes::from(
-                hex::decode(usdi_config.args.trim_start_matches("0x"))
-                    .map_err(|e| anyhow!("Invalid args hex: {}", e))?,
-            );
-
-            let type_script = Script::new_builder()
-                .code_hash(code_hash.pack())
-                .hash_type(ckb_types::packed::Byte::new(ScriptHashType::Type as u8))
-                .args(args.pack())
-                .build();
-
-            // Build xUDT cell dep
-            let tx_hash = H256::from_str(usdi_config.tx_hash.trim_start_matches("0x"))
-                .map_err(|e| anyhow!("Invalid tx_hash: {}", e))?;
-            let out_point = ckb_types::packed::OutPoint::new_builder()
-                .tx_hash(tx_hash.pack())
-                .index(ckb_types::packed::Uint32::new_unchecked(
-                    usdi_config.index.to_le_bytes().to_vec().into(),
-                ))
-                .build();
-            let cell_dep = CellDep::new_builder()
-                .out_point(out_point)
-                .dep_type(ckb_types::packed::Byte::new(
-                    ckb_types::core::DepType::Code as u8,
-                ))
-                .build();
-
-            println!("  - xUDT amount: {}", xudt_amount.unwrap());
-
-            (Some(type_script), Some(cell_dep))
-        } else {
-            return Err(anyhow!("xUDT amount provided but usdi config not found"));
-        }
+    // xUDT type script and cell dep if xudt_amount is provided
+    let (xudt_type_script, xudt_cell_dep) = if let Some(xudt_amount) = xudt_amount {
+        let xudt = config
+            .usdi
+            .as_ref()
+            .ok_or_else(|| anyhow!("xUDT amount provided but usdi config not found"))?
+            .validate()?;
+        println!("  - xUDT amount: {}", xudt_amount);
+        (Some(xudt.type_script), Some(xudt.cell_dep))
     } else {
         (None, None)
     };
@@ -851,39 +1238,56 @@ pub async fn build_funding_transaction(
         fee_rate, // Use parameter, default 1000 shannon/KB
         xudt_type_script: xudt_type_script.clone(),
         xudt_amount,
+        exclude_outpoints: Vec::new(),
+        change_lock: None,
+        change_policy,
+        explicit_inputs: None,
     };
 
     // Create funding context
     let user_lock = Script::from(user_address);
-    let context = FundingContext {
-        secret_keys,
+    let context = FundingContext::new_single_source(
+        RawKeySigner::new_arc(secret_keys)?,
         multisig_config,
-        rpc_url: config.network.rpc_url.clone(),
-        funding_source_lock_script: user_lock,
+        config.network.rpc_url.clone(),
+        user_lock,
         xudt_cell_dep,
-        cell_dep_resolver: None, // Will be created inside build()
-    };
+        None, // Will be created inside build()
+    );
 
-    // Build and sign transaction
-    println!("  - Building and signing funding transaction...");
-    let signed_tx = FundingTx::new().build(request, context.clone()).await?;
+    // Build and sign transaction (placeholder witnesses only when unsigned)
+    let built_tx = if unsigned {
+        println!("  - Building unsigned funding transaction...");
+        FundingTx::new()
+            .build_without_sign(request, context.clone())
+            .await?
+    } else {
+        println!("  - Building and signing funding transaction...");
+        FundingTx::new().build(request, context.clone()).await?
+    };
 
-    let tx = signed_tx
+    let tx = built_tx
         .into_inner()
         .ok_or_else(|| anyhow!("No transaction"))?;
     let tx_hash = tx.hash();
 
-    println!("✓ Transaction built and signed");
+    if unsigned {
+        println!("✓ Transaction built (unsigned)");
+    } else {
+        println!("✓ Transaction built and signed");
+    }
     println!("  - Transaction hash: {:#x}", tx_hash);
     println!("  - Inputs count: {}", tx.inputs().len());
     println!("  - Outputs count: {}", tx.outputs().len());
 
     // Calculate fee
     let total_input: u64 = {
-        let ckb_client = CkbRpcClient::new(&context.rpc_url);
+        let ckb_client = rpc::client(&context.rpc_url);
         let mut total = 0u64;
         for input in tx.input_pts_iter() {
-            if let Ok(cell_with_status) = ckb_client.get_live_cell(input.into(), false) {
+            if let Ok(cell_with_status) = rpc::with_retry("get_live_cell", || {
+                ckb_client.get_live_cell(input.clone().into(), false)
+            }) {
                 if let Some(cell) = cell_with_status.cell {
                     let capacity: u64 = cell.output.capacity.into();
                     total += capacity;
@@ -900,7 +1304,10 @@ pub async fn build_funding_transaction(
         .sum();
 
     let fee = total_input.saturating_sub(total_output);
-    println!("  - Fee: {} ({} shannon)", HumanCapacity::from(fee), fee);
+    report_fee(&tx, fee, config.channel.min_fee_rate);
+
+    let funding_output_index = find_funding_output_index(&tx, spillman_lock_script)?;
+    check_funding_cell_capacity(&tx, funding_output_index)?;
 
     // Save transaction (with hash field for refund command to use)
     let tx_json = ckb_jsonrpc_types::TransactionView::from(tx);
@@ -911,10 +1318,24 @@ pub async fn build_funding_transaction(
     }
     std::fs::write(output_path, json_str)?;
 
-    println!("✓ Signed funding transaction saved: {}", output_path);
+    if unsigned {
+        println!("✓ Unsigned funding transaction saved: {}", output_path);
+        println!("  - Witnesses are placeholders, sign every input with an external tool");
+    } else {
+        println!("✓ Signed funding transaction saved: {}", output_path);
+    }
+    log_event(
+        "funding",
+        "built",
+        json!({
+            "tx_hash": format!("{:#x}", tx_hash),
+            "fee": fee,
+            "output_index": funding_output_index,
+            "output_path": output_path,
+        }),
+    );
 
-    // Return tx_hash and output_index (funding cell is always at index 0)
-    Ok((tx_hash.unpack(), 0))
+    Ok((tx_hash.unpack(), funding_output_index))
 }
 
 /// Build co-funding transaction (high-level API) - Two party funding
@@ -932,6 +1353,7 @@ pub async fn build_funding_transaction(
 /// * `merchant_address` - The merchant's address
 /// * `user_capacity` - User's capacity to fund (main capacity)
 /// * `spillman_lock_script` - The funding cell lock script
+/// * `change_policy` - What to do with the user's change below `--min-change`
 /// * `output_path` - Path to save the signed transaction JSON
 ///
 /// # Examples
@@ -948,6 +1370,7 @@ pub async fn build_cofund_funding_transaction(
     user_capacity: HumanCapacity,
     spillman_lock_script: &Script,
     fee_rate: u64,
+    change_policy: ChangePolicy,
     output_path: &str,
     user_xudt_amount: Option<u128>,
     merchant_xudt_amount: Option<u128>,
@@ -956,51 +1379,21 @@ pub async fn build_cofund_funding_transaction(
 
     let user_capacity_shannon: u64 = user_capacity.into();
 
-    // Build xUDT type script and cell dep if xudt amounts are provided
+    // xUDT type script and cell dep if xudt amounts are provided
     let (xudt_type_script, xudt_cell_dep) =
         if user_xudt_amount.is_some() || merchant_xudt_amount.is_some() {
-            if let Some(ref usdi_config) = config.usdi {
-                // Build xUDT type script
-                let code_hash = H256::from_str(usdi_config.code_hash.trim_start_matches("0x"))
-                    .map_err(|e| anyhow!("Invalid code_hash: {}", e))?;
-                let args = ckb_types::bytes::Bytes::from(
-                    hex::decode(usdi_config.args.trim_start_matches("0x"))
-                        .map_err(|e| anyhow!("Invalid args hex: {}", e))?,
-                );
-
-                let type_script = Script::new_builder()
-                    .code_hash(code_hash.pack())
-                    .hash_type(ckb_types::packed::Byte::new(ScriptHashType::Type as u8))
-                    .args(args.pack())
-                    .build();
-
-                // Build xUDT cell dep
-                let tx_hash = H256::from_str(usdi_config.tx_hash.trim_start_matches("0x"))
-                    .map_err(|e| anyhow!("Invalid tx_hash: {}", e))?;
-                let out_point = ckb_types::packed::OutPoint::new_builder()
-                    .tx_hash(tx_hash.pack())
-                    .index(ckb_types::packed::Uint32::new_unchecked(
-                        usdi_config.index.to_le_bytes().to_vec().into(),
-                    ))
-                    .build();
-                let cell_dep = CellDep::new_builder()
-                    .out_point(out_point)
-                    .dep_type(ckb_types::packed::Byte::new(
-                        ckb_types::core::DepType::Code as u8,
-                    ))
-                    .build();
-
-                if let Some(user_amt) = user_xudt_amount {
-                    println!("  - User xUDT amount: {}", user_amt);
-                }
-                if let Some(merchant_amt) = merchant_xudt_amount {
-                    println!("  - Merchant xUDT amount: {}", merchant_amt);
-                }
-
-                (Some(type_script), Some(cell_dep))
-            } else {
-                return Err(anyhow!("xUDT amount provided but usdi config not found"));
+            let xudt = config
+                .usdi
+                .as_ref()
+                .ok_or_else(|| anyhow!("xUDT amount provided but usdi config not found"))?
+                .validate()?;
+            if let Some(user_amt) = user_xudt_amount {
+                println!("  - User xUDT amount: {}", user_amt);
             }
+            if let Some(merchant_amt) = merchant_xudt_amount {
+                println!("  - Merchant xUDT amount: {}", merchant_amt);
+            }
+            (Some(xudt.type_script), Some(xudt.cell_dep))
         } else {
             (None, None)
         };
@@ -1008,24 +1401,8 @@ pub async fn build_cofund_funding_transaction(
     // Calculate merchant's minimum occupied capacity
     // NOTE: For xUDT channels, merchant needs extra capacity for type script
     let merchant_lock = Script::from(merchant_address);
-    let mut temp_merchant_cell_builder = CellOutput::new_builder()
-        .capacity(0u64)
-        .lock(merchant_lock.clone());
-
-    // Add type script if this is an xUDT channel
-    if let Some(ref type_script) = xudt_type_script {
-        temp_merchant_cell_builder =
-            temp_merchant_cell_builder.type_(Some(type_script.clone()).pack());
-    }
-
-    let temp_merchant_cell = temp_merchant_cell_builder.build();
-
-    // For xUDT: data is 16 bytes; for CKB-only: data is 0 bytes
-    let data_size = if xudt_type_script.is_some() { 16 } else { 0 };
-    let merchant_capacity_shannon = temp_merchant_cell
-        .occupied_capacity(Capacity::bytes(data_size).unwrap())
-        .unwrap()
-        .as_u64();
+    let merchant_capacity_shannon =
+        merchant_min_capacity(&merchant_lock, xudt_type_script.as_ref())?;
 
     // User adds extra 1 CKB as buffer (for fees, etc.)
     let user_buffer_shannon = ONE_CKB;
@@ -1043,23 +1420,11 @@ pub async fn build_cofund_funding_transaction(
         HumanCapacity::from(merchant_capacity_shannon)
     );
 
-    // Optimization: Query genesis block once and reuse for both parties
+    // Optimization: Resolve genesis cell deps once (cached on disk) and reuse for both parties
     // This avoids slow genesis queries (5-10s each) during Step 1 and Step 2
-    println!("\n🔍 预先查询 genesis block (优化性能)...");
-    let ckb_client = CkbRpcClient::new(&config.network.rpc_url);
-    let cell_dep_resolver = {
-        match ckb_client.get_block_by_number(0.into())? {
-            Some(genesis_block) => {
-                println!("✓ Genesis block 查询完成，将复用于 User 和 Merchant");
-                Some(DefaultCellDepResolver::from_genesis(&BlockView::from(
-                    genesis_block,
-                ))?)
-            }
-            None => {
-                return Err(anyhow!("Failed to get genesis block"));
-            }
-        }
-    };
+    println!("\n🔍 预先加载 genesis cell deps (优化性能)...");
+    let cell_dep_resolver = Some(load_cell_dep_resolver(&config.network.rpc_url)?);
+    println!("✓ Cell deps 已加载，将复用于 User 和 Merchant");
 
     // Parse keys for user and merchant
     let user_secret_keys = config.user.get_secret_keys()?;
@@ -1135,17 +1500,21 @@ pub async fn build_cofund_funding_transaction(
         fee_rate,                  // Use parameter, default 1000 shannon/KB
         xudt_type_script: xudt_type_script.clone(),
         xudt_amount: user_xudt_amount,
+        exclude_outpoints: Vec::new(),
+        change_lock: None,
+        change_policy,
+        explicit_inputs: None,
     };
 
     let user_lock = Script::from(user_address);
-    let user_context = FundingContext {
-        secret_keys: user_secret_keys.clone(),
-        multisig_config: user_multisig_config.clone(),
-        rpc_url: config.network.rpc_url.clone(),
-        funding_source_lock_script: user_lock,
-        xudt_cell_dep: xudt_cell_dep.clone(),
-        cell_dep_resolver: cell_dep_resolver.clone(),
-    };
+    let user_context = FundingContext::new_single_source(
+        RawKeySigner::new_arc(user_secret_keys.clone())?,
+        user_multisig_config.clone(),
+        config.network.rpc_url.clone(),
+        user_lock,
+        xudt_cell_dep.clone(),
+        cell_dep_resolver.clone(),
+    );
 
     let user_tx = FundingTx::new()
         .build_without_sign(user_request, user_context)
@@ -1164,21 +1533,38 @@ pub async fn build_cofund_funding_transaction(
         fee_rate,                      // Use parameter, default 1000 shannon/KB
         xudt_type_script: xudt_type_script.clone(),
         xudt_amount: merchant_xudt_amount,
+        exclude_outpoints: Vec::new(),
+        change_lock: None,
+        // The dust policy is the user's choice, the merchant keeps the default
+        change_policy: ChangePolicy::default(),
+        explicit_inputs: None,
     };
 
-    let merchant_context = FundingContext {
-        secret_keys: merchant_secret_keys.clone(),
-        multisig_config: merchant_multisig_config,
-        rpc_url: config.network.rpc_url.clone(),
-        funding_source_lock_script: merchant_lock,
+    let merchant_context = FundingContext::new_single_source(
+        RawKeySigner::new_arc(merchant_secret_keys.clone())?,
+        merchant_multisig_config,
+        config.network.rpc_url.clone(),
+        merchant_lock,
         xudt_cell_dep,
         cell_dep_resolver,
-    };
+    );
 
+    let user_funding_capacity = funding_cell_capacity(&user_tx, spillman_lock_script)?;
     let combined_tx = user_tx // Incremental construction!
         .build_without_sign(merchant_request, merchant_context.clone())
         .await?;
 
+    // What the merchant actually put into the funding cell must be exactly what a refund
+    // returns it, anything more would be stranded with the user
+    let merchant_contribution = funding_cell_capacity(&combined_tx, spillman_lock_script)?
+        .saturating_sub(user_funding_capacity);
+    check_merchant_contribution(
+        merchant_contribution,
+        merchant_xudt_amount.unwrap_or(0),
+        &Script::from(merchant_address),
+        xudt_type_script.as_ref(),
+    )?;
+
     println!("✓ Merchant 最小占用容量已添加");
 
     // Note: Multisig cell dep is automatically added by SecpMultisigUnlocker during signing
@@ -1207,7 +1593,7 @@ pub async fn build_cofund_funding_transaction(
 
     let final_tx = combined_tx
         .sign_with_multiple_keys(
-            all_secret_keys,
+            RawKeySigner::new_arc(all_secret_keys)?,
             merchant_context.multisig_config.clone(),
             merchant_context.rpc_url.clone(),
         )
@@ -1225,10 +1611,12 @@ pub async fn build_cofund_funding_transaction(
 
     // Calculate fee
     let total_input: u64 = {
-        let ckb_client = CkbRpcClient::new(&merchant_context.rpc_url);
+        let ckb_client = rpc::client(&merchant_context.rpc_url);
         let mut total = 0u64;
         for input in tx.input_pts_iter() {
-            if let Ok(cell_with_status) = ckb_client.get_live_cell(input.into(), false) {
+            if let Ok(cell_with_status) = rpc::with_retry("get_live_cell", || {
+                ckb_client.get_live_cell(input.clone().into(), false)
+            }) {
                 if let Some(cell) = cell_with_status.cell {
                     let capacity: u64 = cell.output.capacity.into();
                     total += capacity;
@@ -1245,11 +1633,17 @@ pub async fn build_cofund_funding_transaction(
         .sum();
 
     let fee = total_input.saturating_sub(total_output);
-    println!("  - Fee: {} ({} shannon)", HumanCapacity::from(fee), fee);
+    report_fee(&tx, fee, config.channel.min_fee_rate);
 
     // Verify funding cell capacity
-    let funding_cell_capacity: u64 =
-        Unpack::<u64>::unpack(&tx.outputs().get(0).unwrap().capacity());
+    let funding_output_index = find_funding_output_index(&tx, spillman_lock_script)?;
+    check_funding_cell_capacity(&tx, funding_output_index)?;
+    let funding_cell_capacity: u64 = Unpack::<u64>::unpack(
+        &tx.outputs()
+            .get(funding_output_index as usize)
+            .unwrap()
+            .capacity(),
+    );
     let expected_capacity = user_capacity_shannon + merchant_capacity_shannon + user_buffer_shannon;
     println!(
         "  - Funding cell capacity: {} ({} shannon)",
@@ -1276,14 +1670,175 @@ pub async fn build_cofund_funding_transaction(
     std::fs::write(output_path, json_str)?;
 
     println!("✓ Signed co-funding transaction saved: {}", output_path);
+    log_event(
+        "funding",
+        "built",
+        json!({
+            "mode": "co-fund",
+            "tx_hash": format!("{:#x}", tx_hash),
+            "fee": fee,
+            "output_index": funding_output_index,
+            "output_path": output_path,
+        }),
+    );
+
+    Ok((tx_hash.unpack(), funding_output_index))
+}
+
+/// Unlockers for sighash inputs and, with `multisig_config`, Legacy and V2 multisig inputs
+pub fn build_unlockers(
+    signer: Arc<dyn Signer>,
+    multisig_config: Option<SdkMultisigConfig>,
+) -> HashMap<ScriptId, Box<dyn ScriptUnlocker>> {
+    let signer = SdkSigner(signer);
+
+    let mut unlockers: HashMap<ScriptId, Box<dyn ScriptUnlocker>> = HashMap::default();
+
+    // Always register SIGHASH unlocker (for user's single-sig inputs)
+    let sighash_unlocker = SecpSighashUnlocker::from(Box::new(signer.clone()) as Box<_>);
+    let sighash_script_id = ScriptId::new_type(SIGHASH_TYPE_HASH.clone());
+    unlockers.insert(
+        sighash_script_id,
+        Box::new(sighash_unlocker) as Box<dyn ScriptUnlocker>,
+    );
+
+    // Register MULTISIG unlocker if merchant is using multisig
+    if let Some(config) = multisig_config {
+        // Register Legacy multisig unlocker
+        let legacy_multisig_unlocker =
+            SecpMultisigUnlocker::from((Box::new(signer.clone()) as Box<_>, config.clone()));
+        let legacy_script_id = MultisigScript::Legacy.script_id();
+        unlockers.insert(
+            legacy_script_id,
+            Box::new(legacy_multisig_unlocker) as Box<dyn ScriptUnlocker>,
+        );
+
+        // Register V2 multisig unlocker
+        let v2_multisig_unlocker = SecpMultisigUnlocker::from((Box::new(signer) as Box<_>, config));
+        let v2_script_id = MultisigScript::V2.script_id();
+        unlockers.insert(
+            v2_script_id,
+            Box::new(v2_multisig_unlocker) as Box<dyn ScriptUnlocker>,
+        );
+    }
+
+    unlockers
+}
+
+/// Capacity of the Spillman Lock cell in a funding transaction under construction
+fn funding_cell_capacity(funding_tx: &FundingTx, spillman_lock_script: &Script) -> Result<u64> {
+    let tx = funding_tx
+        .tx
+        .as_ref()
+        .ok_or_else(|| anyhow!("No transaction"))?;
+    let index = find_funding_output_index(tx, spillman_lock_script)?;
+    Ok(tx
+        .output(index as usize)
+        .map(|output| Unpack::<u64>::unpack(&output.capacity()))
+        .unwrap_or_default())
+}
+
+/// Ensure the funding cell holds at least its own occupied capacity
+///
+/// The Spillman Lock args (50+ bytes) plus an optional xUDT type script and amount push
+/// the minimum well above a plain cell's, so a too-small channel would be rejected on chain.
+fn check_funding_cell_capacity(tx: &TransactionView, funding_output_index: u32) -> Result<()> {
+    let index = funding_output_index as usize;
+    let output = tx
+        .output(index)
+        .ok_or_else(|| anyhow!("Funding output {} not found", index))?;
+    let data_len = tx.outputs_data().get(index).map_or(0, |data| data.len());
+    let capacity: u64 = output.capacity().unpack();
+    let min_capacity: u64 = output
+        .occupied_capacity(Capacity::bytes(data_len)?)
+        .context("computing funding cell occupied capacity")?
+        .as_u64();
+    if capacity < min_capacity {
+        return Err(anyhow!(
+            "Funding cell capacity {} is below its occupied capacity {}, increase the channel capacity",
+            HumanCapacity::from(capacity),
+            HumanCapacity::from(min_capacity)
+        ));
+    }
+    Ok(())
+}
+
+/// Locate the funding (Spillman Lock) cell in a funding transaction
+///
+/// The builder places the funding cell first, but callers must not rely on that:
+/// the index is derived from the lock script so that composed transactions work too.
+pub fn find_funding_output_index(
+    tx: &TransactionView,
+    spillman_lock_script: &Script,
+) -> Result<u32> {
+    tx.outputs()
+        .into_iter()
+        .position(|output| output.lock() == *spillman_lock_script)
+        .map(|index| index as u32)
+        .ok_or_else(|| anyhow!("Funding cell with Spillman Lock not found in funding transaction"))
+}
+
+/// Pick xUDT cells of `type_script` until `xudt_amount` is covered
+///
+/// Returns the selected cells and their total amount, or
+/// `ChannelError::InsufficientXudt` when the cells do not hold enough.
+fn select_xudt_cells(
+    cells: Vec<LiveCell>,
+    type_script: &Script,
+    xudt_amount: u128,
+) -> Result<(Vec<LiveCell>, u128)> {
+    // Filter cells with matching type script and collect xUDT amounts
+    let mut xudt_inputs = vec![];
+    let mut collected_xudt_amount = 0u128;
+    let mut cells_with_type = 0;
+    let mut cells_without_type = 0;
+
+    println!("  - Cells: {:?}", cells.len());
+
+    for cell in cells {
+        // Check if cell has the matching type script
+        if let Some(cell_type) = cell.output.type_().to_opt() {
+            cells_with_type += 1;
+
+            if cell_type.as_slice() == type_script.as_slice() {
+                // Parse xUDT amount from cell data
+                if let Ok(amount) = read_xudt_amount(&cell.output_data) {
+                    println!("  - ✓ Found matching xUDT cell with amount: {}", amount);
+                    collected_xudt_amount += amount;
+                    xudt_inputs.push(cell);
+
+                    if collected_xudt_amount >= xudt_amount {
+                        break;
+                    }
+                }
+            } else {
+                println!("  - ✗ Type script doesn't match");
+            }
+        } else {
+            cells_without_type += 1;
+        }
+    }
+
+    println!(
+        "  - Summary: {} cells with type script, {} cells without type script",
+        cells_with_type, cells_without_type
+    );
 
-    // Return tx_hash and output_index (funding cell is always at index 0)
-    Ok((tx_hash.unpack(), 0))
+    if collected_xudt_amount < xudt_amount {
+        return Err(ChannelError::InsufficientXudt {
+            collected: collected_xudt_amount,
+            required: xudt_amount,
+        }
+        .into());
+    }
+
+    Ok((xudt_inputs, collected_xudt_amount))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ckb_types::core::ScriptHashType;
 
     #[test]
     fn test_funding_request_creation() {
@@ -1294,6 +1849,10 @@ mod tests {
             fee_rate: 1000,
             xudt_type_script: None,
             xudt_amount: None,
+            exclude_outpoints: Vec::new(),
+            change_lock: None,
+            change_policy: ChangePolicy::default(),
+            explicit_inputs: None,
         };
 
         assert_eq!(request.local_amount, 1000_0000_0000);
@@ -1306,6 +1865,721 @@ mod tests {
         assert!(funding_tx.into_inner().is_none());
     }
 
+    #[test]
+    fn test_select_xudt_cells_reports_insufficient_xudt() {
+        let type_script = Script::new_builder()
+            .code_hash(H256([0x50; 32]).pack())
+            .hash_type(ScriptHashType::Data1)
+            .build();
+        let xudt_cell = |amount: u128| LiveCell {
+            output: CellOutput::new_builder()
+                .lock(sighash_lock(0xcc))
+                .type_(Some(type_script.clone()).pack())
+                .build(),
+            output_data: Bytes::from(amount.to_le_bytes().to_vec()),
+            out_point: ckb_types::packed::OutPoint::default(),
+            block_number: 0,
+            tx_index: 0,
+        };
+
+        let (selected, collected) = select_xudt_cells(
+            vec![xudt_cell(60), xudt_cell(50), xudt_cell(70)],
+            &type_script,
+            100,
+        )
+        .unwrap();
+        assert_eq!(selected.len(), 2);
+        assert_eq!(collected, 110);
+
+        let err =
+            select_xudt_cells(vec![xudt_cell(60), xudt_cell(30)], &type_script, 100).unwrap_err();
+        match err.downcast_ref::<ChannelError>() {
+            Some(ChannelError::InsufficientXudt {
+                collected,
+                required,
+            }) => {
+                assert_eq!(*collected, 90);
+                assert_eq!(*required, 100);
+            }
+            other => panic!("expected InsufficientXudt, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_build_funding_cell_overflow_is_an_error() {
+        let spillman_lock = sighash_lock(0x55);
+        let xudt_type_script = Script::new_builder()
+            .code_hash(H256([0x50; 32]).pack())
+            .hash_type(ScriptHashType::Data1)
+            .build();
+        // First party already put near-max capacity and xUDT amount into the funding cell
+        let existing_tx = Transaction::default()
+            .as_advanced_builder()
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(u64::MAX - 10))
+                    .lock(spillman_lock.clone())
+                    .type_(Some(xudt_type_script.clone()).pack())
+                    .build(),
+            )
+            .output_data(Bytes::from((u128::MAX - 10).to_le_bytes().to_vec()).pack())
+            .build();
+        let builder = |local_amount, xudt_amount| FundingTxBuilder {
+            funding_tx: FundingTx::from(existing_tx.clone()),
+            request: FundingRequest {
+                script: spillman_lock.clone(),
+                local_amount,
+                fee_rate: 1000,
+                xudt_type_script: Some(xudt_type_script.clone()),
+                xudt_amount: Some(xudt_amount),
+                exclude_outpoints: Vec::new(),
+                change_lock: None,
+                change_policy: ChangePolicy::default(),
+                explicit_inputs: None,
+            },
+            context: FundingContext::new_single_source(
+                RawKeySigner::new_arc(vec![]).unwrap(),
+                None,
+                String::new(),
+                sighash_lock(0xcc),
+                None,
+                None,
+            ),
+        };
+
+        let err = builder(100, 1).build_funding_cell().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Funding cell capacity overflow: existing {} + local 100 shannons",
+                u64::MAX - 10
+            )
+        );
+
+        let err = builder(1, 100).build_funding_cell().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Funding cell xUDT amount overflow: existing {} + local 100",
+                u128::MAX - 10
+            )
+        );
+
+        // Amounts that fit still build the combined cell
+        let (output, data) = builder(10, 10).build_funding_cell().unwrap();
+        let capacity: u64 = output.capacity().unpack();
+        assert_eq!(capacity, u64::MAX);
+        assert_eq!(data.to_vec(), u128::MAX.to_le_bytes().to_vec());
+    }
+
+    #[test]
+    fn test_funding_cell_below_occupied_capacity_is_an_error() {
+        // 50-byte Spillman Lock args: 8 + 32 + 1 + 50 = 91 CKB occupied, plus the xUDT
+        // type script (33 bytes) and 16-byte amount for xUDT channels
+        let spillman_lock = Script::new_builder()
+            .code_hash(H256([0x55; 32]).pack())
+            .hash_type(ScriptHashType::Type)
+            .args(Bytes::from(vec![0u8; 50]).pack())
+            .build();
+        let xudt_type_script = Script::new_builder()
+            .code_hash(H256([0x50; 32]).pack())
+            .hash_type(ScriptHashType::Data1)
+            .build();
+        let funding_tx = |capacity_ckb: u64, xudt: bool| {
+            let mut output = CellOutput::new_builder()
+                .capacity(Capacity::shannons(capacity_ckb * ONE_CKB))
+                .lock(spillman_lock.clone());
+            let mut data = Bytes::new();
+            if xudt {
+                output = output.type_(Some(xudt_type_script.clone()).pack());
+                data = write_xudt_amount(100);
+            }
+            Transaction::default()
+                .as_advanced_builder()
+                .output(output.build())
+                .output_data(data.pack())
+                .build()
+        };
+
+        let err = check_funding_cell_capacity(&funding_tx(61, false), 0).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Funding cell capacity 61.0 is below its occupied capacity 91.0, increase the channel capacity"
+        );
+        check_funding_cell_capacity(&funding_tx(91, false), 0).unwrap();
+
+        let err = check_funding_cell_capacity(&funding_tx(91, true), 0).unwrap_err();
+        assert!(err.to_string().contains("occupied capacity 140.0"));
+        check_funding_cell_capacity(&funding_tx(140, true), 0).unwrap();
+    }
+
+    fn sighash_lock(arg_byte: u8) -> Script {
+        Script::new_builder()
+            .code_hash(SIGHASH_TYPE_HASH.pack())
+            .hash_type(ScriptHashType::Type)
+            .args(Bytes::from(vec![arg_byte; 20]).pack())
+            .build()
+    }
+
+    /// Collector returning a fixed set of cells and ignoring locks
+    #[derive(Clone, Default)]
+    struct FixedCellCollector {
+        cells: Vec<LiveCell>,
+        locked: Vec<OutPoint>,
+    }
+
+    #[async_trait::async_trait]
+    impl CellCollector for FixedCellCollector {
+        async fn collect_live_cells_async(
+            &mut self,
+            _query: &CellQueryOptions,
+            _apply_changes: bool,
+        ) -> Result<(Vec<LiveCell>, u64), CellCollectorError> {
+            let total = self
+                .cells
+                .iter()
+                .map(|cell| Unpack::<u64>::unpack(&cell.output.capacity()))
+                .sum();
+            Ok((self.cells.clone(), total))
+        }
+
+        fn lock_cell(&mut self, out_point: OutPoint, _: u64) -> Result<(), CellCollectorError> {
+            self.locked.push(out_point);
+            Ok(())
+        }
+
+        fn apply_tx(&mut self, _: Transaction, _: u64) -> Result<(), CellCollectorError> {
+            Ok(())
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    #[tokio::test]
+    async fn test_excluded_outpoint_is_never_collected() {
+        let cell = |index: u32| LiveCell {
+            output: CellOutput::new_builder()
+                .lock(sighash_lock(0xaa))
+                .capacity(Capacity::shannons(100 * ONE_CKB))
+                .build(),
+            output_data: Bytes::new(),
+            out_point: OutPoint::new(H256([0x33; 32]).pack(), index),
+            block_number: 0,
+            tx_index: 0,
+        };
+        let reserved = cell(1).out_point;
+        let inner = FixedCellCollector {
+            cells: vec![cell(0), cell(1), cell(2)],
+            locked: Vec::new(),
+        };
+
+        let mut collector =
+            ExcludingCellCollector::new(inner, std::slice::from_ref(&reserved)).unwrap();
+        assert_eq!(collector.inner.locked, std::slice::from_ref(&reserved));
+
+        let query = CellQueryOptions::new_lock(sighash_lock(0xaa));
+        let (cells, total_capacity) = collector
+            .collect_live_cells_async(&query, false)
+            .await
+            .unwrap();
+        assert_eq!(cells.len(), 2);
+        assert!(cells.iter().all(|cell| cell.out_point != reserved));
+        assert_eq!(total_capacity, 200 * ONE_CKB);
+    }
+
+    #[tokio::test]
+    async fn test_explicit_inputs_fund_from_chosen_cells_only() {
+        use ckb_sdk::traits::{OffchainHeaderDepResolver, OffchainTransactionDependencyProvider};
+
+        let wallet = sighash_lock(0xcc);
+        let out_point = |index: u32| OutPoint::new(H256([0x77; 32]).pack(), index);
+        let mut tx_dep_provider = OffchainTransactionDependencyProvider::default();
+        // Two chosen cells (150 + 120 CKB), a large unchosen one and a cell of another lock
+        for (index, lock, capacity_ckb) in [
+            (0, &wallet, 150),
+            (1, &wallet, 120),
+            (2, &wallet, 1000),
+            (3, &sighash_lock(0xdd), 100),
+        ] {
+            let output = CellOutput::new_builder()
+                .lock(lock.clone())
+                .capacity(Capacity::shannons(capacity_ckb * ONE_CKB))
+                .build();
+            tx_dep_provider
+                .cells
+                .insert((H256([0x77; 32]), index), (output, Bytes::new()));
+        }
+        let mut cell_dep_resolver = OffchainCellDepResolver {
+            items: HashMap::new(),
+        };
+        cell_dep_resolver.items.insert(
+            ScriptId::from(&wallet),
+            (CellDep::default(), "sighash".to_string()),
+        );
+
+        let build = |local_ckb: u64, explicit_inputs: Vec<OutPoint>| {
+            let tx_dep_provider = tx_dep_provider.clone();
+            let cell_dep_resolver = cell_dep_resolver.clone();
+            let wallet = wallet.clone();
+            async move {
+                let builder = FundingTxBuilder {
+                    funding_tx: FundingTx::new(),
+                    request: FundingRequest {
+                        script: sighash_lock(0x55),
+                        local_amount: local_ckb * ONE_CKB,
+                        fee_rate: 1000,
+                        xudt_type_script: None,
+                        xudt_amount: None,
+                        exclude_outpoints: Vec::new(),
+                        change_lock: None,
+                        change_policy: ChangePolicy::default(),
+                        explicit_inputs: Some(explicit_inputs.clone()),
+                    },
+                    context: FundingContext::new_single_source(
+                        RawKeySigner::new_arc(vec![]).unwrap(),
+                        None,
+                        String::new(),
+                        wallet,
+                        None,
+                        None,
+                    ),
+                };
+                let mut collector =
+                    ExplicitCellCollector::resolve(&explicit_inputs, &tx_dep_provider).await?;
+                let placeholder_witness = WitnessArgs::new_builder()
+                    .lock(Some(molecule::bytes::Bytes::from(vec![0u8; 65])).pack())
+                    .build();
+                let mut balancer = CapacityBalancer::new_with_provider(
+                    1000,
+                    builder.context.capacity_provider(placeholder_witness),
+                );
+                let (funding_output, funding_data) = builder.build_funding_cell()?;
+                let base_tx = Transaction::default()
+                    .as_advanced_builder()
+                    .output(funding_output)
+                    .output_data(funding_data.pack())
+                    .build();
+                builder
+                    .balance_funding_tx(
+                        base_tx,
+                        &mut balancer,
+                        &mut collector,
+                        &tx_dep_provider,
+                        &cell_dep_resolver,
+                        &OffchainHeaderDepResolver::default(),
+                    )
+                    .await
+            }
+        };
+
+        // Both chosen cells are spent even though the first one alone would cover 100 CKB
+        let tx = build(100, vec![out_point(0), out_point(1)]).await.unwrap();
+        let inputs: Vec<OutPoint> = tx
+            .inputs()
+            .into_iter()
+            .map(|input| input.previous_output())
+            .collect();
+        assert_eq!(inputs, vec![out_point(0), out_point(1)]);
+        assert_eq!(tx.outputs().len(), 2);
+        let funding_capacity: u64 = tx.outputs().get(0).unwrap().capacity().unpack();
+        let change = tx.outputs().get(1).unwrap();
+        let change_capacity: u64 = change.capacity().unpack();
+        assert_eq!(change.lock(), wallet);
+        assert_eq!(funding_capacity, 100 * ONE_CKB);
+        // Change and fee are still computed from the chosen cells
+        let fee = 270 * ONE_CKB - funding_capacity - change_capacity;
+        assert!(fee > 0 && fee < ONE_CKB);
+
+        // Not enough in the chosen cells: the large unchosen cell is never pulled in
+        let err = build(300, vec![out_point(0), out_point(1)])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("need more capacity"), "{}", err);
+
+        // A chosen cell of another lock cannot be spent
+        let err = build(50, vec![out_point(1), out_point(3)])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("was not used"), "{}", err);
+
+        // Unknown and duplicate OutPoints are rejected up front
+        let err = build(100, vec![out_point(9)]).await.unwrap_err();
+        assert!(
+            err.to_string().contains("Failed to resolve input"),
+            "{}",
+            err
+        );
+        let err = build(100, vec![out_point(0), out_point(0)])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Duplicate input"), "{}", err);
+    }
+
+    /// Resolver without any known cell deps
+    struct NoCellDeps;
+
+    impl CellDepResolver for NoCellDeps {
+        fn resolve(&self, _script: &Script) -> Option<CellDep> {
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn test_xudt_only_funding_pays_capacity_from_xudt_surplus() {
+        let wallet = sighash_lock(0xcc);
+        let type_script = Script::new_builder()
+            .code_hash(H256([0x50; 32]).pack())
+            .hash_type(ScriptHashType::Data1)
+            .build();
+        // The user holds only xUDT cells, each carrying plenty of surplus CKB
+        let xudt_cell = |index: u32, capacity_ckb: u64, amount: u128| LiveCell {
+            output: CellOutput::new_builder()
+                .lock(wallet.clone())
+                .type_(Some(type_script.clone()).pack())
+                .capacity(Capacity::shannons(capacity_ckb * ONE_CKB))
+                .build(),
+            output_data: Bytes::from(amount.to_le_bytes().to_vec()),
+            out_point: OutPoint::new(H256([0x44; 32]).pack(), index),
+            block_number: 0,
+            tx_index: 0,
+        };
+        let builder = FundingTxBuilder {
+            funding_tx: FundingTx::new(),
+            request: FundingRequest {
+                script: sighash_lock(0x55),
+                local_amount: 200 * ONE_CKB,
+                fee_rate: 1000,
+                xudt_type_script: Some(type_script.clone()),
+                xudt_amount: Some(100),
+                exclude_outpoints: Vec::new(),
+                change_lock: None,
+                change_policy: ChangePolicy::default(),
+                explicit_inputs: None,
+            },
+            context: FundingContext::new_single_source(
+                RawKeySigner::new_arc(vec![]).unwrap(),
+                None,
+                String::new(),
+                wallet.clone(),
+                None,
+                None,
+            ),
+        };
+        let (funding_output, funding_data) = builder.build_funding_cell().unwrap();
+        let base_tx = Transaction::default()
+            .as_advanced_builder()
+            .output(funding_output)
+            .output_data(funding_data.pack())
+            .build();
+
+        let mut collector = FixedCellCollector {
+            cells: vec![xudt_cell(0, 300, 80), xudt_cell(1, 300, 80)],
+            locked: Vec::new(),
+        };
+        let (tx, capacity_balanced) = builder
+            .balance_xudt_cells(base_tx.clone(), &mut collector, &NoCellDeps)
+            .await
+            .unwrap();
+        assert!(capacity_balanced);
+        assert_eq!(tx.inputs().len(), 2);
+        assert_eq!(tx.outputs().len(), 2);
+
+        // The xUDT change keeps the remaining tokens and the leftover CKB
+        let change = tx.outputs().get(1).unwrap();
+        assert_eq!(change.lock(), wallet);
+        assert_eq!(change.type_().to_opt(), Some(type_script.clone()));
+        let change_data: Vec<u8> = tx.outputs_data().get(1).unwrap().unpack();
+        assert_eq!(change_data, 60u128.to_le_bytes().to_vec());
+
+        // Inputs pay exactly the funding cell, the change and the fee
+        let fee = FeeRate::from_u64(1000)
+            .fee(tx.data().as_reader().serialized_size_in_block() as u64)
+            .as_u64();
+        let funding_capacity: u64 = tx.outputs().get(0).unwrap().capacity().unpack();
+        let change_capacity: u64 = change.capacity().unpack();
+        assert_eq!(funding_capacity, 200 * ONE_CKB);
+        assert_eq!(funding_capacity + change_capacity + fee, 600 * ONE_CKB);
+
+        // Not enough surplus: the change stays at its floor and plain CKB cells are needed
+        let mut collector = FixedCellCollector {
+            cells: vec![xudt_cell(0, 150, 80), xudt_cell(1, 150, 80)],
+            locked: Vec::new(),
+        };
+        let (tx, capacity_balanced) = builder
+            .balance_xudt_cells(base_tx, &mut collector, &NoCellDeps)
+            .await
+            .unwrap();
+        assert!(!capacity_balanced);
+        let change = tx.outputs().get(1).unwrap();
+        let change_floor = change
+            .occupied_capacity(Capacity::bytes(16).unwrap())
+            .unwrap()
+            .as_u64();
+        assert_eq!(Unpack::<u64>::unpack(&change.capacity()), change_floor);
+    }
+
+    #[tokio::test]
+    async fn test_change_lock_overrides_funding_source() {
+        let wallet = sighash_lock(0xcc);
+        let fresh = sighash_lock(0xdd);
+        let type_script = Script::new_builder()
+            .code_hash(H256([0x50; 32]).pack())
+            .hash_type(ScriptHashType::Data1)
+            .build();
+        let xudt_cell = LiveCell {
+            output: CellOutput::new_builder()
+                .lock(wallet.clone())
+                .type_(Some(type_script.clone()).pack())
+                .capacity(Capacity::shannons(300 * ONE_CKB))
+                .build(),
+            output_data: Bytes::from(80u128.to_le_bytes().to_vec()),
+            out_point: OutPoint::new(H256([0x66; 32]).pack(), 0),
+            block_number: 0,
+            tx_index: 0,
+        };
+        let builder = FundingTxBuilder {
+            funding_tx: FundingTx::new(),
+            request: FundingRequest {
+                script: sighash_lock(0x55),
+                local_amount: 200 * ONE_CKB,
+                fee_rate: 1000,
+                xudt_type_script: Some(type_script.clone()),
+                xudt_amount: Some(50),
+                exclude_outpoints: Vec::new(),
+                change_lock: Some(fresh.clone()),
+                change_policy: ChangePolicy::default(),
+                explicit_inputs: None,
+            },
+            context: FundingContext::new_single_source(
+                RawKeySigner::new_arc(vec![]).unwrap(),
+                None,
+                String::new(),
+                wallet.clone(),
+                None,
+                None,
+            ),
+        };
+
+        // The capacity balancer is configured with the override
+        assert_eq!(builder.change_lock_script().unwrap(), fresh);
+
+        // The xUDT change goes to the override as well, inputs still come from the wallet
+        let (funding_output, funding_data) = builder.build_funding_cell().unwrap();
+        let base_tx = Transaction::default()
+            .as_advanced_builder()
+            .output(funding_output)
+            .output_data(funding_data.pack())
+            .build();
+        let mut collector = FixedCellCollector {
+            cells: vec![xudt_cell],
+            locked: Vec::new(),
+        };
+        let (tx, _) = builder
+            .balance_xudt_cells(base_tx, &mut collector, &NoCellDeps)
+            .await
+            .unwrap();
+        let change = tx.outputs().get(1).unwrap();
+        assert_eq!(change.lock(), fresh);
+        assert_eq!(change.type_().to_opt(), Some(type_script));
+
+        // Without an override change returns to the funding source
+        let builder = FundingTxBuilder {
+            request: FundingRequest {
+                change_lock: None,
+                ..builder.request
+            },
+            ..builder
+        };
+        assert_eq!(builder.change_lock_script().unwrap(), wallet);
+    }
+
+    #[test]
+    fn test_change_policy_handles_dust_change() {
+        use crate::tx_builder::capacity_utils::DustPolicy;
+
+        let wallet = sighash_lock(0xcc);
+        let spillman_lock = sighash_lock(0x55);
+        let builder = |on_dust| FundingTxBuilder {
+            funding_tx: FundingTx::new(),
+            request: FundingRequest {
+                script: spillman_lock.clone(),
+                local_amount: 200 * ONE_CKB,
+                fee_rate: 1000,
+                xudt_type_script: None,
+                xudt_amount: None,
+                exclude_outpoints: Vec::new(),
+                change_lock: None,
+                change_policy: ChangePolicy {
+                    min_change: 100 * ONE_CKB,
+                    on_dust,
+                },
+                explicit_inputs: None,
+            },
+            context: FundingContext::new_single_source(
+                RawKeySigner::new_arc(vec![]).unwrap(),
+                None,
+                String::new(),
+                wallet.clone(),
+                None,
+                None,
+            ),
+        };
+        let cell = |lock: &Script, capacity: u64| {
+            CellOutput::new_builder()
+                .lock(lock.clone())
+                .capacity(Capacity::shannons(capacity))
+                .build()
+        };
+        // Balanced by the capacity balancer: funding cell first, 80 CKB change last
+        let balanced = |change_ckb: u64| {
+            Transaction::default()
+                .as_advanced_builder()
+                .output(cell(&spillman_lock, 200 * ONE_CKB))
+                .output_data(Bytes::new().pack())
+                .output(cell(&wallet, change_ckb * ONE_CKB))
+                .output_data(Bytes::new().pack())
+                .build()
+        };
+        let capacities = |tx: &TransactionView| -> Vec<u64> {
+            tx.outputs()
+                .into_iter()
+                .map(|output| output.capacity().unpack())
+                .collect()
+        };
+
+        let err = builder(DustPolicy::Fail)
+            .apply_change_policy(balanced(80), 1)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChannelError>(),
+            Some(ChannelError::DustChange { .. })
+        ));
+
+        let burned = builder(DustPolicy::Burn)
+            .apply_change_policy(balanced(80), 1)
+            .unwrap();
+        assert_eq!(capacities(&burned), vec![200 * ONE_CKB]);
+        assert_eq!(burned.outputs_data().len(), 1);
+
+        let added = builder(DustPolicy::AddToFunding)
+            .apply_change_policy(balanced(80), 1)
+            .unwrap();
+        assert_eq!(capacities(&added), vec![280 * ONE_CKB]);
+
+        // Change above the minimum is kept whatever the policy
+        let kept = builder(DustPolicy::Fail)
+            .apply_change_policy(balanced(120), 1)
+            .unwrap();
+        assert_eq!(capacities(&kept), vec![200 * ONE_CKB, 120 * ONE_CKB]);
+
+        // Outputs that existed before this party balanced are never treated as change
+        let untouched = builder(DustPolicy::Fail)
+            .apply_change_policy(balanced(80), 2)
+            .unwrap();
+        assert_eq!(capacities(&untouched), vec![200 * ONE_CKB, 80 * ONE_CKB]);
+    }
+
+    #[test]
+    fn test_funding_context_with_multiple_source_locks() {
+        let wallet_a = sighash_lock(0xaa);
+        let wallet_b = sighash_lock(0xbb);
+        let context = FundingContext {
+            signer: RawKeySigner::new_arc(vec![]).unwrap(),
+            multisig_config: None,
+            rpc_url: String::new(),
+            funding_source_lock_scripts: vec![wallet_a.clone(), wallet_b.clone()],
+            xudt_cell_dep: None,
+            cell_dep_resolver: None,
+        };
+        let type_script = Script::new_builder()
+            .code_hash(H256([0x50; 32]).pack())
+            .hash_type(ScriptHashType::Data1)
+            .build();
+
+        // One xUDT query per source lock, all filtered by the xUDT type script
+        let queries = context.xudt_cell_queries(&type_script);
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0].primary_script, wallet_a);
+        assert_eq!(queries[1].primary_script, wallet_b);
+        for query in &queries {
+            assert_eq!(query.secondary_script, Some(type_script.clone()));
+            assert_eq!(query.min_total_capacity, u64::MAX);
+        }
+
+        // Capacity balancer collects from both locks, change goes to the first
+        let provider = context.capacity_provider(WitnessArgs::default());
+        let provider_locks: Vec<_> = provider
+            .lock_scripts
+            .iter()
+            .map(|(lock_script, _, _)| lock_script.clone())
+            .collect();
+        assert_eq!(provider_locks, vec![wallet_a.clone(), wallet_b]);
+        assert_eq!(context.change_lock_script().unwrap(), &wallet_a);
+    }
+
+    #[test]
+    fn test_funding_context_single_source() {
+        let wallet = sighash_lock(0xcc);
+        let context = FundingContext::new_single_source(
+            RawKeySigner::new_arc(vec![]).unwrap(),
+            None,
+            String::new(),
+            wallet.clone(),
+            None,
+            None,
+        );
+
+        assert_eq!(context.funding_source_lock_scripts, vec![wallet.clone()]);
+        assert_eq!(context.change_lock_script().unwrap(), &wallet);
+    }
+
+    #[test]
+    fn test_funding_context_unlockers_for_multisig_source() {
+        let keys: Vec<_> = (1u8..=3)
+            .map(|i| secp256k1::SecretKey::from_slice(&[i; 32]).unwrap())
+            .collect();
+        let multisig_config = build_multisig_config(&keys, 2, 3).unwrap();
+        let v2_id = MultisigScript::V2.script_id();
+        let multisig_wallet = Script::new_builder()
+            .code_hash(v2_id.code_hash.pack())
+            .hash_type(v2_id.hash_type)
+            .args(Bytes::from(multisig_config.hash160().as_bytes().to_vec()).pack())
+            .build();
+        let context = |sources: Vec<Script>, multisig_config| FundingContext {
+            signer: RawKeySigner::new_arc(keys.clone()).unwrap(),
+            multisig_config,
+            rpc_url: String::new(),
+            funding_source_lock_scripts: sources,
+            xudt_cell_dep: None,
+            cell_dep_resolver: None,
+        };
+        let sighash_id = ScriptId::new_type(SIGHASH_TYPE_HASH.clone());
+
+        // Sighash wallet: only the sighash unlocker
+        let unlockers = context(vec![sighash_lock(0xcc)], None).unlockers().unwrap();
+        assert_eq!(unlockers.len(), 1);
+        assert!(unlockers.contains_key(&sighash_id));
+
+        // Multisig wallet: its multisig unlocker matches the wallet's lock args
+        let unlockers = context(
+            vec![multisig_wallet.clone(), sighash_lock(0xcc)],
+            Some(multisig_config),
+        )
+        .unlockers()
+        .unwrap();
+        assert_eq!(unlockers.len(), 2);
+        assert!(unlockers.contains_key(&sighash_id));
+        assert!(!unlockers.contains_key(&MultisigScript::Legacy.script_id()));
+        assert!(unlockers[&v2_id].match_args(&multisig_wallet.args().raw_data()));
+
+        // A multisig wallet without its config cannot be unlocked
+        let err = context(vec![multisig_wallet], None)
+            .unlockers()
+            .map(|_| ())
+            .unwrap_err();
+        assert!(err.to_string().contains("no multisig config"), "{}", err);
+    }
+
     #[test]
     fn test_human_capacity_parsing() {
         use std::str::FromStr;
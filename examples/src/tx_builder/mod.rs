@@ -1,7 +1,11 @@
+pub mod capacity_utils;
 pub mod commitment;
+pub mod commitment_v2;
 pub mod funding;
 pub mod funding_v2;
 pub mod refund;
 pub mod refund_v2;
+pub mod signing;
 pub mod spillman_lock;
 pub mod witness_utils;
+pub mod xudt;
@@ -1,6 +1,5 @@
 use anyhow::{anyhow, Result};
 use ckb_crypto::secp::Privkey;
-use ckb_hash::blake2b_256;
 use ckb_sdk::transaction::builder::FeeCalculator;
 use ckb_types::{
     bytes::Bytes,
@@ -11,7 +10,10 @@ use ckb_types::{
 };
 use std::str::FromStr;
 
+use crate::tx_builder::capacity_utils::merchant_min_capacity;
+use crate::tx_builder::signing::spillman_signing_message_for_version;
 use crate::utils::config::Config;
+use crate::utils::crypto::SpillmanArgs;
 
 // Constants for witness structure
 const EMPTY_WITNESS_ARGS: [u8; 16] = [16, 0, 0, 0, 16, 0, 0, 0, 16, 0, 0, 0, 16, 0, 0, 0];
@@ -51,25 +53,12 @@ pub fn build_refund_transaction(
 
     let spillman_capacity: u64 = Unpack::<u64>::unpack(&spillman_cell.capacity());
 
-    // Parse timeout_since from Spillman Lock args
-    // Args structure (50 bytes): merchant_lock_arg(20) + user_pubkey_hash(20) + timeout_since(8) + algorithm_id(1) + version(1)
-    // Note: timeout_since is already a Since-encoded value (absolute epoch-based)
+    // Parse Spillman Lock args (merchant_lock_arg, user_pubkey_hash, timeout, ...)
     let lock_script = spillman_cell.lock();
-    let args_bytes: Bytes = lock_script.args().unpack();
-    if args_bytes.len() != 50 {
-        return Err(anyhow!(
-            "Invalid Spillman Lock args length: expected 50, got {}",
-            args_bytes.len()
-        ));
-    }
+    let spillman_args = SpillmanArgs::from_bytes(&lock_script.args().raw_data())?;
 
-    // Extract timeout_since from args (bytes 40-48)
-    // This is already a Since-encoded value, use it directly for input.since
-    let timeout_since = u64::from_le_bytes(
-        args_bytes[40..48]
-            .try_into()
-            .map_err(|_| anyhow!("Failed to parse timeout_since from args"))?,
-    );
+    // timeout is already a Since-encoded value; the refund waits out the merchant's grace too
+    let timeout_since = spillman_args.refund_since()?;
 
     println!(
         "    - Spillman Lock cell capacity: {} CKB",
@@ -77,12 +66,11 @@ pub fn build_refund_transaction(
     );
     println!("    - Funding tx hash: {:#x}", funding_tx_hash);
     println!(
-        "    - Timeout since (from Spillman Lock args): 0x{:x}",
+        "    - Timeout since (from Spillman Lock args, + grace): 0x{:x}",
         timeout_since
     );
 
     // Build input from Spillman Lock cell
-    // Use the timeout_since value directly (already Since-encoded)
     let input = CellInput::new_builder()
         .previous_output(
             OutPoint::new_builder()
@@ -90,7 +78,7 @@ pub fn build_refund_transaction(
                 .index(0u32)
                 .build(),
         )
-        .since(timeout_since) // Already encoded as absolute epoch-based time lock
+        .since(timeout_since) // Timeout + grace, in the timeout's own metric
         .build();
 
     println!("    - Input since: 0x{:x}", timeout_since);
@@ -128,18 +116,9 @@ pub fn build_refund_transaction(
         .build();
 
     // Calculate merchant's minimum occupied capacity (for co-fund mode)
-    let merchant_capacity = if let Some(ref merchant_lock) = merchant_lock_script {
-        let merchant_cell = CellOutput::new_builder()
-            .capacity(0u64)
-            .lock(merchant_lock.clone())
-            .build();
-
-        merchant_cell
-            .occupied_capacity(ckb_types::core::Capacity::bytes(0).unwrap())
-            .unwrap()
-            .as_u64()
-    } else {
-        0
+    let merchant_capacity = match merchant_lock_script {
+        Some(ref merchant_lock) => merchant_min_capacity(merchant_lock, None)?,
+        None => 0,
     };
 
     // Helper function to build transaction with given user capacity
@@ -155,7 +134,7 @@ pub fn build_refund_transaction(
             .cell_dep(auth_dep.clone());
 
         // Add outputs based on mode
-        if merchant_lock_script.is_some() {
+        if let Some(ref merchant_lock) = merchant_lock_script {
             // Co-fund mode: 2 outputs
             // Output 0: User
             builder = builder
@@ -172,7 +151,7 @@ pub fn build_refund_transaction(
                 .output(
                     CellOutput::new_builder()
                         .capacity(merchant_capacity)
-                        .lock(merchant_lock_script.as_ref().unwrap().clone())
+                        .lock(merchant_lock.clone())
                         .build(),
                 )
                 .output_data(Bytes::new().pack());
@@ -298,10 +277,9 @@ pub fn build_refund_transaction(
 
     let user_privkey_hex = config
         .user
-        .private_key
-        .as_ref()
-        .expect("User private_key is required");
-    let user_privkey = Privkey::from_str(user_privkey_hex)
+        .resolve_private_key()?
+        .ok_or_else(|| anyhow!("User private_key is required"))?;
+    let user_privkey = Privkey::from_str(&user_privkey_hex)
         .map_err(|e| anyhow!("Failed to parse user private key: {:?}", e))?;
     let user_pubkey = user_privkey
         .pubkey()
@@ -310,10 +288,9 @@ pub fn build_refund_transaction(
 
     let merchant_privkey_hex = config
         .merchant
-        .private_key
-        .as_ref()
-        .expect("Merchant private_key is required");
-    let merchant_privkey = Privkey::from_str(merchant_privkey_hex)
+        .resolve_private_key()?
+        .ok_or_else(|| anyhow!("Merchant private_key is required"))?;
+    let merchant_privkey = Privkey::from_str(&merchant_privkey_hex)
         .map_err(|e| anyhow!("Failed to parse merchant private key: {:?}", e))?;
     let merchant_pubkey = merchant_privkey
         .pubkey()
@@ -321,8 +298,8 @@ pub fn build_refund_transaction(
     let merchant_pubkey_hash_from_privkey = pubkey_hash(&merchant_pubkey);
 
     // Verify pubkey hashes match Spillman Lock args
-    let expected_merchant_hash = &args_bytes[0..20];
-    let expected_user_hash = &args_bytes[20..40];
+    let expected_merchant_hash = spillman_args.merchant_lock_arg;
+    let expected_user_hash = spillman_args.user_pubkey_hash;
 
     if merchant_pubkey_hash_from_privkey != expected_merchant_hash {
         return Err(anyhow!("Merchant pubkey hash mismatch! The private key in config.toml doesn't match the Spillman Lock args."));
@@ -332,7 +309,7 @@ pub fn build_refund_transaction(
     }
 
     // Compute signing message (raw tx without cell_deps)
-    let signing_message = compute_signing_message(&tx);
+    let signing_message = spillman_signing_message_for_version(&tx, spillman_args.version)?;
 
     // Sign with ckb-crypto (merchant first, then user)
     let merchant_sig = merchant_privkey
@@ -382,16 +359,93 @@ pub fn build_refund_transaction(
     Ok(signed_tx)
 }
 
-/// Compute signing message for Spillman Lock
-/// This is the same as in test cases: blake2b_256(raw_tx without cell_deps)
-fn compute_signing_message(tx: &TransactionView) -> [u8; 32] {
-    use ckb_types::packed::CellDepVec;
-    let raw_tx = tx
-        .data()
-        .raw()
-        .as_builder()
-        .cell_deps(CellDepVec::default())
-        .build();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::config::{
+        AuthConfig, ChannelConfig, KeyConfig, NetworkConfig, SpillmanLockConfig,
+    };
+    use ckb_types::core::ScriptHashType;
+
+    fn lock(args: &[u8]) -> Script {
+        Script::new_builder()
+            .code_hash(H256([0x9b; 32]).pack())
+            .hash_type(ScriptHashType::Type)
+            .args(Bytes::copy_from_slice(args).pack())
+            .build()
+    }
 
-    blake2b_256(raw_tx.as_slice())
+    /// Config without any private key
+    fn keyless_config() -> Config {
+        let key = KeyConfig {
+            private_key: None,
+            private_key_env: None,
+            private_key_file: None,
+            multisig_threshold: None,
+            multisig_total: None,
+            private_keys: None,
+            address: String::new(),
+        };
+        Config {
+            network: NetworkConfig::default(),
+            user: key.clone(),
+            merchant: key,
+            channel: ChannelConfig {
+                capacity_ckb: 1000,
+                timeout_epochs: 0,
+                timeout_timestamp: 0,
+                tx_fee_shannon: 0,
+                min_timeout_seconds: 1200,
+                timeout_warn_seconds: 3600,
+                min_fee_rate: 1000,
+                bind_cell_deps: false,
+            },
+            spillman_lock: SpillmanLockConfig {
+                code_hash: format!("{:#x}", H256([0x5a; 32])),
+                hash_type: "type".to_string(),
+                tx_hash: format!("{:#x}", H256([0u8; 32])),
+                index: 0,
+                binary_path: None,
+            },
+            auth: AuthConfig {
+                tx_hash: format!("{:#x}", H256([0u8; 32])),
+                index: 0,
+                binary_path: None,
+            },
+            usdi: None,
+            system_scripts: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_refund_without_private_key_is_an_error() {
+        let args = SpillmanArgs::new_with_algorithm([2; 20], [1; 20], 0x4000_0000_6774_8580, 0);
+        let funding_tx = TransactionBuilder::default()
+            .output(
+                CellOutput::new_builder()
+                    .capacity(1000 * 100_000_000u64)
+                    .lock(lock(&args.to_bytes()))
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build();
+        let output_path = std::env::temp_dir().join("spillman_refund_without_key.json");
+
+        // Used to panic on the missing key instead of returning an error
+        for merchant_lock in [None, Some(lock(&[2; 20]))] {
+            let err = build_refund_transaction(
+                &keyless_config(),
+                funding_tx.hash().unpack(),
+                &funding_tx,
+                lock(&[1; 20]),
+                merchant_lock,
+                0,
+                1000,
+                output_path.to_str().unwrap(),
+            )
+            .unwrap_err();
+            assert!(err.to_string().contains("User private_key is required"));
+        }
+        assert!(!output_path.exists());
+    }
 }
@@ -10,13 +10,19 @@
 /// # Refund Transaction Structure
 ///
 /// ## Inputs
-/// - Spillman Lock cell (from funding transaction output 0)
+/// - Spillman Lock cell (from funding transaction output `funding_output_index`)
 /// - Since: timeout timestamp (read from Spillman Lock args)
+/// - No header deps: the node checks a timestamp since against the chain itself (the
+///   median time of the tip for absolute, of the funding cell's block for relative), and
+///   the Spillman Lock only compares since values, it never loads a header
 ///
 /// ## Outputs
 /// - Single mode: User's cell (full refund minus fee)
 /// - Co-fund mode: User's cell + Merchant's cell (proportional split minus fee)
 ///
+/// A request marked `co_funded` without a merchant lock script is refused with
+/// `ChannelError::CoFundedRefundWithoutMerchantOutput`.
+///
 /// ## Witness
 /// - EMPTY_WITNESS_ARGS (16 bytes)
 /// - UNLOCK_TYPE_TIMEOUT (1 byte, 0x01)
@@ -31,6 +37,10 @@
 ///    - This guarantees the user can always get their funds back after timeout
 /// 2. **After timeout**: User adds their signature and broadcasts
 ///
+/// With an air-gapped user key the two halves run apart: `sign_as_merchant` writes the
+/// merchant part, the offline machine signs `extract_signing_message`, and
+/// `inject_user_signature` adds the 65-byte result on the online machine.
+///
 /// # Example
 ///
 /// ```ignore
@@ -39,9 +49,14 @@
 ///     &config,
 ///     funding_tx_hash,
 ///     &funding_tx,
+///     funding_output_index,
 ///     &user_address,
 ///     None, // No merchant for single-party
+///     false,
+///     fee_rate,
+///     None, // no fee bump
 ///     "output/refund_tx.json",
+///     false, // sign in-process
 /// ).await?;
 ///
 /// // Co-funded refund
@@ -49,13 +64,17 @@
 ///     &config,
 ///     funding_tx_hash,
 ///     &funding_tx,
+///     funding_output_index,
 ///     &user_address,
 ///     Some(&merchant_address),
+///     true, // co_funded: refuses to build without the merchant output
+///     fee_rate,
+///     None, // no fee bump
 ///     "output/refund_tx.json",
+///     false, // sign in-process
 /// ).await?;
 /// ```
 use anyhow::{anyhow, Result};
-use ckb_crypto::secp::Privkey;
 use ckb_hash::blake2b_256;
 use ckb_sdk::{
     traits::{CellDepResolver, HeaderDepResolver, TransactionDependencyProvider},
@@ -65,18 +84,29 @@ use ckb_sdk::{
 use ckb_types::{
     bytes::Bytes,
     core::{Capacity, DepType, TransactionView},
-    packed::{CellDep, CellDepVec, CellInput, CellOutput, OutPoint, Script, Transaction},
+    packed::{CellDep, CellInput, CellOutput, OutPoint, Script, Transaction, WitnessArgs},
     prelude::*,
     H256,
 };
-use std::str::FromStr;
-
+use std::sync::Arc;
+
+use crate::error::ChannelError;
+use crate::signer::{RawKeySigner, Signer};
+use crate::tx_builder::capacity_utils::merchant_min_capacity;
+use crate::tx_builder::signing::spillman_signing_message_for_version;
+use crate::tx_builder::spillman_lock::ensure_spillman_lock;
+use crate::tx_builder::witness_utils::{
+    assemble_spillman_witness, MerchantSig, EMPTY_WITNESS_ARGS, SIGNATURE_SIZE, UNLOCK_TYPE_TIMEOUT,
+};
+use crate::tx_builder::xudt::{read_xudt_amount, write_xudt_amount, XUDT_AMOUNT_LEN};
 use crate::utils::config::Config;
-use crate::utils::crypto::pubkey_hash;
+use crate::utils::crypto::SpillmanArgs;
+use crate::utils::fee::report_fee;
+use crate::utils::log::log_event;
+use serde_json::json;
 
-// Constants for witness structure
-const EMPTY_WITNESS_ARGS: [u8; 16] = [16, 0, 0, 0, 16, 0, 0, 0, 16, 0, 0, 0, 16, 0, 0, 0];
-const UNLOCK_TYPE_TIMEOUT: u8 = 0x01;
+/// Upper bound on refund builds while the fee settles
+const MAX_FEE_ITERATIONS: usize = 10;
 
 /// Calculate refund witness size based on merchant's signature type
 ///
@@ -99,24 +129,45 @@ pub struct RefundRequest {
     pub funding_tx_hash: H256,
     /// The funding transaction
     pub funding_tx: TransactionView,
+    /// Index of the Spillman Lock cell in the funding transaction outputs
+    pub funding_output_index: u32,
     /// User's lock script (refund destination)
     pub user_lock_script: Script,
     /// Merchant's lock script (optional, for co-fund mode)
     pub merchant_lock_script: Option<Script>,
+    /// The merchant contributed to the funding cell, so `merchant_lock_script` is required
+    pub co_funded: bool,
     /// Fee rate in shannon/KB
     pub fee_rate: u64,
     /// xUDT cell dep (optional, for xUDT channels)
     pub xudt_cell_dep: Option<CellDep>,
+    /// Third party paying the fee (optional); the user then gets the full Spillman capacity
+    pub sponsor: Option<RefundSponsor>,
+}
+
+/// Fee sponsor of a refund transaction
+///
+/// Sponsor inputs follow the Spillman input and the sponsor's change follows the refund
+/// outputs. The contract excludes that change from its fee bound, so the channel itself
+/// still pays at most MAX_FEE. Sponsor inputs are signed separately by the sponsor.
+#[derive(Clone)]
+pub struct RefundSponsor {
+    /// Sponsor inputs
+    pub inputs: Vec<CellInput>,
+    /// Total capacity of the sponsor inputs (in shannons)
+    pub input_capacity: u64,
+    /// Lock script receiving the sponsor's change (must differ from user and merchant locks)
+    pub lock: Script,
 }
 
-/// Refund context (keys and RPC)
+/// Refund context (signers and RPC)
 #[derive(Clone)]
 pub struct RefundContext {
     #[allow(dead_code)]
-    pub user_secret_key: secp256k1::SecretKey,
-    /// Merchant secret keys (single-sig: 1 key, multisig: multiple keys)
+    pub user_signer: Arc<dyn Signer>,
+    /// Merchant signer (single-sig: 1 key, multisig: multiple keys)
     #[allow(dead_code)]
-    pub merchant_secret_keys: Option<Vec<secp256k1::SecretKey>>,
+    pub merchant_signer: Option<Arc<dyn Signer>>,
     /// Multisig configuration for merchant (if merchant uses multisig)
     #[allow(dead_code)]
     pub merchant_multisig_config: Option<ckb_sdk::unlock::MultisigConfig>,
@@ -130,6 +181,8 @@ pub struct RefundContext {
 #[derive(Clone, Debug, Default)]
 pub struct RefundTx {
     tx: Option<TransactionView>,
+    /// Capacity of the Spillman Lock input (known when built from the funding transaction)
+    input_capacity: Option<u64>,
 }
 
 impl RefundTx {
@@ -149,8 +202,91 @@ impl RefundTx {
         self.tx = Some(tx);
     }
 
+    /// Rebuild the refund transaction with a new fee rate (RBF / fee bump)
+    ///
+    /// The replacement spends the same Spillman Lock input and only the user output
+    /// shrinks to pay the extra fee. Since the signing message covers the outputs,
+    /// the existing signatures become invalid: the returned transaction carries a
+    /// zeroed witness and must be signed again by both merchant and user via
+    /// `sign_for_spillman_lock`. In other words, fee-bumping requires the merchant
+    /// to sign again (`refund --bump-fee-rate` holds both keys).
+    pub fn rebuild_with_fee_rate(&self, new_rate: u64) -> Result<RefundTx> {
+        let tx = self
+            .tx
+            .as_ref()
+            .ok_or_else(|| anyhow!("No transaction to rebuild"))?;
+        let input_capacity = self.input_capacity.ok_or_else(|| {
+            anyhow!("Spillman Lock input capacity unknown, build the refund from the funding transaction first")
+        })?;
+        if tx.inputs().len() > 1 {
+            return Err(anyhow!(
+                "Sponsored refund: rebuild it with a new fee rate from the sponsor instead"
+            ));
+        }
+
+        // Reset witness to a placeholder of the same size (signatures must be redone)
+        let witness_size = tx
+            .witnesses()
+            .get(0)
+            .ok_or_else(|| anyhow!("Missing witness"))?
+            .raw_data()
+            .len();
+        let unsigned_tx = tx
+            .as_advanced_builder()
+            .set_witnesses(vec![Bytes::from(vec![0u8; witness_size]).pack()])
+            .build();
+
+        // Transaction size doesn't change when only capacities change
+        let tx_size = unsigned_tx.data().as_reader().serialized_size_in_block() as u64;
+        let new_fee = (tx_size * new_rate).div_ceil(1000);
+
+        let mut outputs: Vec<CellOutput> = unsigned_tx.outputs().into_iter().collect();
+        let current_user_capacity: u64 = outputs
+            .first()
+            .ok_or_else(|| anyhow!("Refund transaction has no user output"))?
+            .capacity()
+            .unpack();
+        let other_outputs_capacity: u64 = outputs
+            .iter()
+            .skip(1)
+            .map(|output| Unpack::<u64>::unpack(&output.capacity()))
+            .sum();
+
+        let user_capacity = input_capacity
+            .checked_sub(other_outputs_capacity)
+            .and_then(|c| c.checked_sub(new_fee))
+            .ok_or_else(|| anyhow!("Not enough capacity for refund and fee"))?;
+        if user_capacity >= current_user_capacity {
+            return Err(anyhow!(
+                "New fee rate {} shannon/KB does not increase the fee of the refund transaction",
+                new_rate
+            ));
+        }
+
+        outputs[0] = outputs[0]
+            .clone()
+            .as_builder()
+            .capacity(Capacity::shannons(user_capacity))
+            .build();
+
+        let replacement_tx = unsigned_tx
+            .as_advanced_builder()
+            .set_outputs(outputs)
+            .build();
+        ensure_user_refund_above_minimum(&replacement_tx)?;
+
+        Ok(RefundTx {
+            tx: Some(replacement_tx),
+            input_capacity: self.input_capacity,
+        })
+    }
+
     /// Build the refund transaction
     pub async fn build(self, request: RefundRequest, context: RefundContext) -> Result<Self> {
+        // A single-output refund would hand the merchant's share to the user
+        if request.co_funded && request.merchant_lock_script.is_none() {
+            return Err(ChannelError::CoFundedRefundWithoutMerchantOutput.into());
+        }
         let builder = RefundTxBuilder {
             refund_tx: self,
             request,
@@ -166,10 +302,39 @@ impl RefundTx {
     /// - UNLOCK_TYPE_TIMEOUT (1 byte, 0x01)
     /// - Merchant signature (65 bytes for single-sig, or multisig config + M signatures for multisig)
     /// - User signature (65 bytes)
+    ///
+    /// Both keys are used in one process. For a user key kept offline, use
+    /// `sign_as_merchant`, `extract_signing_message` and `inject_user_signature` instead.
     pub fn sign_for_spillman_lock(
+        self,
+        user_signer: &dyn Signer,
+        merchant_signer: &dyn Signer,
+        spillman_lock_args: &[u8],
+        merchant_multisig_config: Option<&ckb_sdk::unlock::MultisigConfig>,
+    ) -> Result<Self> {
+        // Verify user hash (always single-sig)
+        let expected_user_hash = SpillmanArgs::from_bytes(spillman_lock_args)?.user_pubkey_hash;
+        if !user_signer.has_key(&expected_user_hash) {
+            return Err(ChannelError::PubkeyHashMismatch { role: "User" }.into());
+        }
+
+        let refund_tx = self.sign_as_merchant(
+            merchant_signer,
+            spillman_lock_args,
+            merchant_multisig_config,
+        )?;
+        let signing_message = refund_tx.extract_signing_message(spillman_lock_args)?;
+        let user_sig = user_signer.sign(signing_message, &expected_user_hash)?;
+        refund_tx.inject_user_signature(user_sig)
+    }
+
+    /// Pre-sign the refund transaction as the merchant
+    ///
+    /// Writes the Spillman witness with the merchant part filled in and a zeroed user
+    /// signature, to be completed by `inject_user_signature`.
+    pub fn sign_as_merchant(
         mut self,
-        user_privkey: &Privkey,
-        merchant_secret_keys: &[secp256k1::SecretKey],
+        merchant_signer: &dyn Signer,
         spillman_lock_args: &[u8],
         merchant_multisig_config: Option<&ckb_sdk::unlock::MultisigConfig>,
     ) -> Result<Self> {
@@ -177,20 +342,12 @@ impl RefundTx {
             .take()
             .ok_or_else(|| anyhow!("No transaction to sign"))?;
 
-        // Verify pubkey hashes match Spillman Lock args
-        let user_pubkey = user_privkey
-            .pubkey()
-            .map_err(|e| anyhow!("Failed to get user pubkey: {:?}", e))?;
-
-        let user_pubkey_hash_from_privkey = pubkey_hash(&user_pubkey);
-
-        let expected_merchant_hash = &spillman_lock_args[0..20];
-        let expected_user_hash = &spillman_lock_args[20..40];
+        let args = SpillmanArgs::from_bytes(spillman_lock_args)?;
+        let expected_merchant_hash = args.merchant_lock_arg;
 
         // Verify merchant hash (different logic for single-sig vs multisig)
         if let Some(multisig_config) = merchant_multisig_config {
             // For multisig: merchant_hash should be blake160(multisig_config_data)
-            use ckb_hash::blake2b_256;
             let config_data = multisig_config.to_witness_data();
             let hash = blake2b_256(&config_data);
             let merchant_multisig_hash = &hash[0..20];
@@ -201,100 +358,71 @@ impl RefundTx {
                     hex::encode(merchant_multisig_hash)
                 ));
             }
-        } else {
+        } else if !merchant_signer.has_key(&expected_merchant_hash) {
             // For single-sig: merchant_hash is blake160(pubkey)
-            if merchant_secret_keys.len() != 1 {
-                return Err(anyhow!(
-                    "Single-sig merchant should have exactly 1 secret key"
-                ));
-            }
-            let secp = secp256k1::Secp256k1::new();
-            let merchant_pubkey_secp =
-                secp256k1::PublicKey::from_secret_key(&secp, &merchant_secret_keys[0]);
-            let merchant_pubkey_bytes = merchant_pubkey_secp.serialize();
-            use ckb_hash::blake2b_256;
-            let merchant_pubkey_hash_from_privkey = &blake2b_256(merchant_pubkey_bytes)[0..20];
-            if merchant_pubkey_hash_from_privkey != expected_merchant_hash {
-                return Err(anyhow!("Merchant pubkey hash mismatch!"));
-            }
+            return Err(ChannelError::PubkeyHashMismatch { role: "Merchant" }.into());
         }
 
-        // Verify user hash (always single-sig)
-        if user_pubkey_hash_from_privkey != expected_user_hash {
-            return Err(anyhow!("User pubkey hash mismatch!"));
-        }
+        // Compute signing message for the channel's args version
+        let signing_message = spillman_signing_message_for_version(&tx, args.version)?;
+        let merchant_sig = MerchantSig::sign(
+            merchant_signer,
+            signing_message,
+            &expected_merchant_hash,
+            merchant_multisig_config,
+        )?;
 
-        // Compute signing message (raw tx without cell_deps)
-        let signing_message = compute_signing_message(&tx);
+        let witness_data =
+            assemble_spillman_witness(UNLOCK_TYPE_TIMEOUT, merchant_sig, &[0u8; SIGNATURE_SIZE]);
 
-        // Build witness based on merchant signature type
-        let witness_data = if let Some(multisig_config) = merchant_multisig_config {
-            // Multisig merchant: collect threshold number of signatures
-            let threshold = multisig_config.threshold() as usize;
-            if merchant_secret_keys.len() < threshold {
-                return Err(anyhow!(
-                    "Not enough merchant secret keys: need {}, got {}",
-                    threshold,
-                    merchant_secret_keys.len()
-                ));
-            }
+        // Replace the Spillman witness only, sponsor witnesses (if any) follow it
+        let mut witnesses: Vec<_> = tx.witnesses().into_iter().skip(1).collect();
+        witnesses.insert(0, witness_data.pack());
+        let signed_tx = tx.as_advanced_builder().set_witnesses(witnesses).build();
 
-            let mut merchant_signatures = Vec::new();
-            for key in merchant_secret_keys.iter().take(threshold) {
-                // Convert secp256k1::SecretKey to ckb_crypto::secp::Privkey
-                let privkey_bytes = key.secret_bytes();
-                let merchant_privkey = Privkey::from_slice(&privkey_bytes);
-                let signature = merchant_privkey
-                    .sign_recoverable(&signing_message.into())
-                    .map_err(|e| anyhow!("Failed to sign with merchant key: {:?}", e))?
-                    .serialize();
-                merchant_signatures.extend_from_slice(&signature);
-            }
+        self.update(signed_tx);
+        Ok(self)
+    }
 
-            let user_sig = user_privkey
-                .sign_recoverable(&signing_message.into())
-                .map_err(|e| anyhow!("Failed to sign with user key: {:?}", e))?
-                .serialize();
+    /// Message the user signs, for signing away from the transaction (e.g. air-gapped)
+    ///
+    /// Witnesses are not part of the message, so it is the same before and after the
+    /// merchant pre-signs.
+    pub fn extract_signing_message(&self, spillman_lock_args: &[u8]) -> Result<[u8; 32]> {
+        let tx = self
+            .tx
+            .as_ref()
+            .ok_or_else(|| anyhow!("No transaction to sign"))?;
+        let args = SpillmanArgs::from_bytes(spillman_lock_args)?;
+        spillman_signing_message_for_version(tx, args.version)
+    }
 
-            // Multisig witness: empty_witness_args + unlock_type + multisig_config + merchant_signatures + user_signature
-            let config_data = multisig_config.to_witness_data();
-            [
-                &EMPTY_WITNESS_ARGS[..],
-                &[UNLOCK_TYPE_TIMEOUT][..],
-                &config_data[..],
-                &merchant_signatures[..],
-                &user_sig[..],
-            ]
-            .concat()
-        } else {
-            // Single-sig merchant
-            let privkey_bytes = merchant_secret_keys[0].secret_bytes();
-            let merchant_privkey = Privkey::from_slice(&privkey_bytes);
-            let merchant_sig = merchant_privkey
-                .sign_recoverable(&signing_message.into())
-                .map_err(|e| anyhow!("Failed to sign with merchant key: {:?}", e))?
-                .serialize();
-
-            let user_sig = user_privkey
-                .sign_recoverable(&signing_message.into())
-                .map_err(|e| anyhow!("Failed to sign with user key: {:?}", e))?
-                .serialize();
-
-            // Single-sig witness: empty_witness_args + unlock_type + merchant_sig + user_sig
-            [
-                &EMPTY_WITNESS_ARGS[..],
-                &[UNLOCK_TYPE_TIMEOUT][..],
-                &merchant_sig[..],
-                &user_sig[..],
-            ]
-            .concat()
-        };
+    /// Complete a merchant pre-signed refund with the user's signature
+    ///
+    /// `user_sig` is the 65-byte signature over `extract_signing_message`. It is not
+    /// checked here; the Spillman Lock rejects a wrong signature on chain.
+    pub fn inject_user_signature(mut self, user_sig: [u8; SIGNATURE_SIZE]) -> Result<Self> {
+        let tx = self
+            .take()
+            .ok_or_else(|| anyhow!("No transaction to sign"))?;
 
-        // Rebuild transaction with witness
-        let signed_tx = tx
-            .as_advanced_builder()
-            .set_witnesses(vec![Bytes::from(witness_data).pack()])
-            .build();
+        let mut witness = tx
+            .witnesses()
+            .get(0)
+            .map(|witness| witness.raw_data().to_vec())
+            .ok_or_else(|| anyhow!("Refund transaction has no witness"))?;
+        let min_size = EMPTY_WITNESS_ARGS.len() + 1 + 2 * SIGNATURE_SIZE;
+        if witness.len() < min_size || witness[EMPTY_WITNESS_ARGS.len()] != UNLOCK_TYPE_TIMEOUT {
+            return Err(anyhow!(
+                "Refund witness is not pre-signed by the merchant, call sign_as_merchant first"
+            ));
+        }
+        let user_sig_start = witness.len() - SIGNATURE_SIZE;
+        witness[user_sig_start..].copy_from_slice(&user_sig);
+
+        let mut witnesses: Vec<_> = tx.witnesses().into_iter().skip(1).collect();
+        witnesses.insert(0, Bytes::from(witness).pack());
+        let signed_tx = tx.as_advanced_builder().set_witnesses(witnesses).build();
 
         self.update(signed_tx);
         Ok(self)
@@ -303,7 +431,10 @@ impl RefundTx {
 
 impl From<TransactionView> for RefundTx {
     fn from(tx: TransactionView) -> Self {
-        Self { tx: Some(tx) }
+        Self {
+            tx: Some(tx),
+            input_capacity: None,
+        }
     }
 }
 
@@ -311,6 +442,7 @@ impl From<Transaction> for RefundTx {
     fn from(tx: Transaction) -> Self {
         Self {
             tx: Some(tx.into_view()),
+            input_capacity: None,
         }
     }
 }
@@ -331,91 +463,52 @@ impl TxBuilder for RefundTxBuilder {
         _header_dep_resolver: &dyn HeaderDepResolver,
         _tx_dep_provider: &dyn TransactionDependencyProvider,
     ) -> Result<TransactionView, TxBuilderError> {
-        // Get Spillman Lock cell from funding tx output 0
-        let spillman_cell =
-            self.request.funding_tx.outputs().get(0).ok_or_else(|| {
-                TxBuilderError::Other(anyhow!("Funding transaction has no output 0"))
+        // Get Spillman Lock cell from funding tx output at funding_output_index
+        let funding_output_index = self.request.funding_output_index;
+        let spillman_cell = self
+            .request
+            .funding_tx
+            .outputs()
+            .get(funding_output_index as usize)
+            .ok_or_else(|| {
+                TxBuilderError::Other(anyhow!(
+                    "Funding transaction has no output {}",
+                    funding_output_index
+                ))
             })?;
 
         let spillman_capacity: u64 = spillman_cell.capacity().unpack();
 
         // Check if this is an xUDT channel
-        let xudt_info = if let Some(type_script) = spillman_cell.type_().to_opt() {
-            // Extract xUDT amount from funding cell data
-            let funding_data = self
-                .request
-                .funding_tx
-                .outputs_data()
-                .get(0)
-                .ok_or_else(|| {
-                    TxBuilderError::Other(anyhow!("Funding transaction has no output data 0"))
-                })?;
-            let data_bytes: Vec<u8> = funding_data.unpack();
-
-            if data_bytes.len() >= 16 {
-                let xudt_amount =
-                    u128::from_le_bytes(data_bytes[0..16].try_into().map_err(|_| {
-                        TxBuilderError::Other(anyhow!("Failed to parse xUDT amount"))
-                    })?);
-                Some((type_script, xudt_amount))
-            } else {
-                return Err(TxBuilderError::Other(anyhow!(
-                    "Invalid xUDT data length: {}",
-                    data_bytes.len()
-                )));
-            }
-        } else {
-            None
-        };
+        let xudt_info = self.xudt_info().map_err(TxBuilderError::Other)?;
 
-        // Parse timeout_since from Spillman Lock args
+        // Refund since from Spillman Lock args: the timeout plus the merchant's grace.
+        // No header dep is needed for it (see the module docs), so `_header_dep_resolver`
+        // stays unused and the refund is independent of the tip it was built at.
         let lock_script = spillman_cell.lock();
-        let args_bytes: Bytes = lock_script.args().unpack();
-        if args_bytes.len() != 50 {
-            return Err(TxBuilderError::Other(anyhow!(
-                "Invalid Spillman Lock args length: expected 50, got {}",
-                args_bytes.len()
-            )));
-        }
-
-        // Extract timeout_since from args (bytes 40-48)
-        let timeout_since = u64::from_le_bytes(args_bytes[40..48].try_into().map_err(|_| {
-            TxBuilderError::Other(anyhow!("Failed to parse timeout_since from args"))
-        })?);
+        let timeout_since = SpillmanArgs::from_bytes(&lock_script.args().raw_data())
+            .and_then(|args| args.refund_since())
+            .map_err(TxBuilderError::Other)?;
 
         // Build input with timeout since
         let input = CellInput::new_builder()
             .previous_output(
                 OutPoint::new_builder()
                     .tx_hash(self.request.funding_tx_hash.pack())
-                    .index(0u32)
+                    .index(self.request.funding_output_index)
                     .build(),
             )
             .since(timeout_since)
             .build();
 
         // Calculate merchant's capacity if co-fund
-        let merchant_capacity = if let Some(ref merchant_lock) = self.request.merchant_lock_script {
-            let mut merchant_cell_builder = CellOutput::new_builder()
-                .capacity(Capacity::shannons(0))
-                .lock(merchant_lock.clone());
-
-            // If xUDT channel, merchant cell also needs type script
-            let data_size = if let Some((ref type_script, _)) = xudt_info {
-                merchant_cell_builder =
-                    merchant_cell_builder.type_(Some(type_script.clone()).pack());
-                16 // 16 bytes for xUDT data
-            } else {
-                0
-            };
-
-            let merchant_cell = merchant_cell_builder.build();
-            merchant_cell
-                .occupied_capacity(Capacity::bytes(data_size).unwrap())
-                .unwrap()
-                .as_u64()
-        } else {
-            0
+        let merchant_capacity = match self.request.merchant_lock_script {
+            Some(ref merchant_lock) => merchant_min_capacity(
+                merchant_lock,
+                xudt_info.as_ref().map(|(type_script, _)| type_script),
+            )
+            .map_err(TxBuilderError::Other)?,
+            None => 0,
         };
 
         // Calculate user capacity (spillman_capacity - merchant_capacity - fee_estimate)
@@ -451,7 +544,7 @@ impl TxBuilder for RefundTxBuilder {
             outputs.push(output);
 
             // xUDT amount in data (16 bytes, little-endian u128)
-            outputs_data.push(Bytes::from(xudt_amount.to_le_bytes().to_vec()).pack());
+            outputs_data.push(write_xudt_amount(xudt_amount).pack());
         } else {
             // Regular CKB channel
             let output = CellOutput::new_builder()
@@ -473,7 +566,7 @@ impl TxBuilder for RefundTxBuilder {
                     .build();
                 outputs.push(output);
                 // Merchant gets 0 xUDT (only CKB refund)
-                outputs_data.push(Bytes::from(0u128.to_le_bytes().to_vec()).pack());
+                outputs_data.push(write_xudt_amount(0).pack());
             } else {
                 // Regular CKB channel
                 outputs.push(
@@ -513,143 +606,154 @@ impl TxBuilder for RefundTxBuilder {
 }
 
 impl RefundTxBuilder {
+    /// Get the Spillman Lock cell being refunded
+    fn spillman_cell(&self) -> Result<CellOutput> {
+        self.request
+            .funding_tx
+            .outputs()
+            .get(self.request.funding_output_index as usize)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Funding transaction has no output {}",
+                    self.request.funding_output_index
+                )
+            })
+    }
+
+    /// xUDT type script and amount of the Spillman Lock cell, None for a CKB channel
+    ///
+    /// The refund returns exactly the funding cell's data to the user, so a cell created
+    /// outside set-up must carry a bare 16-byte amount.
+    fn xudt_info(&self) -> Result<Option<(Script, u128)>> {
+        let Some(type_script) = self.spillman_cell()?.type_().to_opt() else {
+            return Ok(None);
+        };
+        let funding_data = self
+            .request
+            .funding_tx
+            .outputs_data()
+            .get(self.request.funding_output_index as usize)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Funding transaction has no output data {}",
+                    self.request.funding_output_index
+                )
+            })?;
+        // Extension data after the amount would be dropped by the refund outputs
+        let data_bytes: Vec<u8> = funding_data.unpack();
+        if data_bytes.len() != XUDT_AMOUNT_LEN {
+            return Err(ChannelError::InvalidXudtData(data_bytes.len()).into());
+        }
+        Ok(Some((type_script, read_xudt_amount(&data_bytes)?)))
+    }
+
     /// Internal build method with iterative fee calculation
     async fn build_internal(self) -> Result<RefundTx> {
         // Get spillman cell capacity
-        let spillman_cell = self
-            .request
-            .funding_tx
-            .outputs()
-            .get(0)
-            .ok_or_else(|| anyhow!("Funding transaction has no output 0"))?;
+        let spillman_cell = self.spillman_cell()?;
         let spillman_capacity: u64 = spillman_cell.capacity().unpack();
 
-        // Check if this is an xUDT channel
-        let has_xudt = spillman_cell.type_().to_opt().is_some();
+        // Calculate merchant's capacity if co-fund (xUDT channels add the type script)
+        let merchant_capacity = match self.request.merchant_lock_script {
+            Some(ref merchant_lock) => {
+                merchant_min_capacity(merchant_lock, spillman_cell.type_().to_opt().as_ref())?
+            }
+            None => 0,
+        };
 
-        // Calculate merchant's capacity if co-fund
-        let merchant_capacity = if let Some(ref merchant_lock) = self.request.merchant_lock_script {
-            let mut merchant_cell_builder = CellOutput::new_builder()
-                .capacity(Capacity::shannons(0))
-                .lock(merchant_lock.clone());
-
-            // If xUDT channel, merchant cell also needs type script
-            let data_size = if has_xudt {
-                let type_script = spillman_cell.type_().to_opt().unwrap();
-                merchant_cell_builder = merchant_cell_builder.type_(Some(type_script).pack());
-                16 // 16 bytes for xUDT data
-            } else {
-                0
-            };
+        let (tx, _) = self.build_with_converged_fee(spillman_capacity, merchant_capacity)?;
+        ensure_user_refund_above_minimum(&tx)?;
+        if self.request.sponsor.is_some() {
+            ensure_sponsor_change_above_minimum(&tx)?;
+        }
 
-            let merchant_cell = merchant_cell_builder.build();
-            merchant_cell
-                .occupied_capacity(Capacity::bytes(data_size).unwrap())
-                .unwrap()
-                .as_u64()
-        } else {
-            0
-        };
+        let mut refund_tx = self.refund_tx;
+        refund_tx.update(tx);
+        refund_tx.input_capacity = Some(spillman_capacity);
+
+        Ok(refund_tx)
+    }
 
-        // Iteratively calculate fee
+    /// Iteratively build the refund until its fee matches its own size at the fee rate
+    ///
+    /// Returns the transaction and the number of builds it took. The size does not depend
+    /// on the capacities, so the fee settles on the second build; after
+    /// MAX_FEE_ITERATIONS the last build is used.
+    fn build_with_converged_fee(
+        &self,
+        spillman_capacity: u64,
+        merchant_capacity: u64,
+    ) -> Result<(TransactionView, usize)> {
         let fee_rate = self.request.fee_rate; // Use parameter, default 1000 shannon/KB
-        let max_iterations = 10;
         let mut current_fee = 0u64;
-        let mut final_tx: Option<TransactionView> = None;
 
-        for iteration in 0..max_iterations {
+        for iteration in 1..=MAX_FEE_ITERATIONS {
+            // The sponsor (if any) pays the fee, otherwise it comes from the user refund
+            let (user_fee, sponsor_change) = match self.request.sponsor {
+                Some(ref sponsor) => {
+                    let change = sponsor
+                        .input_capacity
+                        .checked_sub(current_fee)
+                        .ok_or_else(|| anyhow!("Sponsor inputs cannot cover the refund fee"))?;
+                    (0, change)
+                }
+                None => (current_fee, 0),
+            };
+
             // Calculate user capacity based on current fee
             let user_capacity = if self.request.merchant_lock_script.is_some() {
                 spillman_capacity
                     .checked_sub(merchant_capacity)
-                    .and_then(|c| c.checked_sub(current_fee))
+                    .and_then(|c| c.checked_sub(user_fee))
                     .ok_or_else(|| anyhow!("Not enough capacity for refund outputs and fee"))?
             } else {
                 spillman_capacity
-                    .checked_sub(current_fee)
+                    .checked_sub(user_fee)
                     .ok_or_else(|| anyhow!("Not enough capacity for refund and fee"))?
             };
 
             // Build transaction with calculated capacity
-            let temp_tx = self.build_tx_with_capacity(user_capacity, merchant_capacity)?;
+            let temp_tx =
+                self.build_tx_with_capacity(user_capacity, merchant_capacity, sponsor_change)?;
 
             // Calculate actual fee for this transaction
             let tx_size = temp_tx.data().as_reader().serialized_size_in_block() as u64;
             let actual_fee = (tx_size * fee_rate).div_ceil(1000); // Round up
 
             // Check if fee has stabilized
-            if actual_fee == current_fee {
-                final_tx = Some(temp_tx);
-                break;
+            if actual_fee == current_fee || iteration == MAX_FEE_ITERATIONS {
+                return Ok((temp_tx, iteration));
             }
 
             current_fee = actual_fee;
-
-            if iteration == max_iterations - 1 {
-                final_tx = Some(temp_tx);
-            }
         }
 
-        let tx = final_tx.ok_or_else(|| anyhow!("Failed to build transaction"))?;
-
-        let mut refund_tx = self.refund_tx;
-        refund_tx.update(tx);
-
-        Ok(refund_tx)
+        Err(anyhow!("Failed to build transaction"))
     }
 
     /// Helper to build transaction with specific capacities
+    ///
+    /// `sponsor_change` is only used when the request has a sponsor.
     fn build_tx_with_capacity(
         &self,
         user_capacity: u64,
         merchant_capacity: u64,
+        sponsor_change: u64,
     ) -> Result<TransactionView> {
-        let spillman_cell = self
-            .request
-            .funding_tx
-            .outputs()
-            .get(0)
-            .ok_or_else(|| anyhow!("Funding transaction has no output 0"))?;
+        let spillman_cell = self.spillman_cell()?;
 
         // Check if this is an xUDT channel
-        let xudt_info = if let Some(type_script) = spillman_cell.type_().to_opt() {
-            // Extract xUDT amount from funding cell data
-            let funding_data = self
-                .request
-                .funding_tx
-                .outputs_data()
-                .get(0)
-                .ok_or_else(|| anyhow!("Funding transaction has no output data 0"))?;
-            let data_bytes: Vec<u8> = funding_data.unpack();
-
-            if data_bytes.len() >= 16 {
-                let xudt_amount = u128::from_le_bytes(
-                    data_bytes[0..16]
-                        .try_into()
-                        .map_err(|_| anyhow!("Failed to parse xUDT amount"))?,
-                );
-                Some((type_script, xudt_amount))
-            } else {
-                return Err(anyhow!("Invalid xUDT data length: {}", data_bytes.len()));
-            }
-        } else {
-            None
-        };
+        let xudt_info = self.xudt_info()?;
 
         let lock_script = spillman_cell.lock();
-        let args_bytes: Bytes = lock_script.args().unpack();
-
-        let timeout_since = u64::from_le_bytes(
-            args_bytes[40..48]
-                .try_into()
-                .map_err(|_| anyhow!("Failed to parse timeout_since from args"))?,
-        );
+        let timeout_since =
+            SpillmanArgs::from_bytes(&lock_script.args().raw_data())?.refund_since()?;
 
         let input = CellInput::new_builder()
             .previous_output(
                 OutPoint::new_builder()
                     .tx_hash(self.request.funding_tx_hash.pack())
-                    .index(0u32)
+                    .index(self.request.funding_output_index)
                     .build(),
             )
             .since(timeout_since)
@@ -669,7 +773,7 @@ impl RefundTxBuilder {
             outputs.push(output);
 
             // xUDT amount in data (16 bytes, little-endian u128)
-            outputs_data.push(Bytes::from(xudt_amount.to_le_bytes().to_vec()).pack());
+            outputs_data.push(write_xudt_amount(xudt_amount).pack());
         } else {
             // Regular CKB channel
             let output = CellOutput::new_builder()
@@ -691,7 +795,7 @@ impl RefundTxBuilder {
                     .build();
                 outputs.push(output);
                 // Merchant gets 0 xUDT (only CKB refund)
-                outputs_data.push(Bytes::from(0u128.to_le_bytes().to_vec()).pack());
+                outputs_data.push(write_xudt_amount(0).pack());
             } else {
                 // Regular CKB channel
                 outputs.push(
@@ -707,10 +811,30 @@ impl RefundTxBuilder {
         let witness_size =
             calculate_refund_witness_size(self.context.merchant_multisig_config.as_ref());
         let witness_placeholder = vec![0u8; witness_size];
+        let mut witnesses = vec![Bytes::from(witness_placeholder).pack()];
+        let mut inputs = vec![input];
+
+        // Sponsor inputs after the Spillman input, sponsor change after the refund outputs
+        if let Some(ref sponsor) = self.request.sponsor {
+            let sponsor_placeholder = WitnessArgs::new_builder()
+                .lock(Some(Bytes::from(vec![0u8; 65])).pack())
+                .build();
+            for sponsor_input in &sponsor.inputs {
+                inputs.push(sponsor_input.clone());
+                witnesses.push(sponsor_placeholder.as_bytes().pack());
+            }
+            outputs.push(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(sponsor_change))
+                    .lock(sponsor.lock.clone())
+                    .build(),
+            );
+            outputs_data.push(Bytes::new().pack());
+        }
 
         let mut tx_builder = Transaction::default()
             .as_advanced_builder()
-            .input(input)
+            .set_inputs(inputs)
             .cell_dep(self.context.spillman_lock_dep.clone())
             .cell_dep(self.context.auth_dep.clone());
 
@@ -722,25 +846,56 @@ impl RefundTxBuilder {
         let tx = tx_builder
             .set_outputs(outputs)
             .set_outputs_data(outputs_data)
-            .witness(Bytes::from(witness_placeholder).pack())
+            .set_witnesses(witnesses)
             .build();
 
         Ok(tx)
     }
 }
 
-/// Compute signing message for Spillman Lock
+/// Ensure the user refund output (output 0) covers its minimum occupied capacity
 ///
-/// Spillman Lock signs the raw transaction without cell_deps
-fn compute_signing_message(tx: &TransactionView) -> [u8; 32] {
-    let raw_tx = tx
-        .data()
-        .raw()
-        .as_builder()
-        .cell_deps(CellDepVec::default())
-        .build();
+/// A high fee rate on a small channel could otherwise produce a cell the node rejects.
+fn ensure_user_refund_above_minimum(tx: &TransactionView) -> Result<()> {
+    let user_output = tx
+        .outputs()
+        .get(0)
+        .ok_or_else(|| anyhow!("Refund transaction has no user output"))?;
+    let user_data_len = tx
+        .outputs_data()
+        .get(0)
+        .map(|data| data.raw_data().len())
+        .unwrap_or_default();
 
-    blake2b_256(raw_tx.as_slice())
+    let min_capacity = user_output
+        .occupied_capacity(Capacity::bytes(user_data_len)?)
+        .map_err(|e| anyhow!("Failed to calculate user minimum capacity: {:?}", e))?;
+    let user_capacity: Capacity = user_output.capacity().unpack();
+
+    if user_capacity < min_capacity {
+        return Err(anyhow!("User refund below minimum cell capacity"));
+    }
+
+    Ok(())
+}
+
+/// Ensure the sponsor change output (the last output) covers its minimum occupied capacity
+fn ensure_sponsor_change_above_minimum(tx: &TransactionView) -> Result<()> {
+    let change_output = tx
+        .outputs()
+        .into_iter()
+        .last()
+        .ok_or_else(|| anyhow!("Refund transaction has no sponsor change output"))?;
+    let min_capacity = change_output
+        .occupied_capacity(Capacity::zero())
+        .map_err(|e| anyhow!("Failed to calculate sponsor minimum capacity: {:?}", e))?;
+    let change_capacity: Capacity = change_output.capacity().unpack();
+
+    if change_capacity < min_capacity {
+        return Err(anyhow!("Sponsor change below minimum cell capacity"));
+    }
+
+    Ok(())
 }
 
 /// Build refund transaction (high-level API)
@@ -756,20 +911,41 @@ fn compute_signing_message(tx: &TransactionView) -> [u8; 32] {
 /// * `config` - Configuration
 /// * `funding_tx_hash` - The funding transaction hash
 /// * `funding_tx` - The funding transaction
+/// * `funding_output_index` - Index of the Spillman Lock cell in the funding transaction
 /// * `user_address` - User's refund destination address
 /// * `merchant_address` - Merchant's refund destination address (optional, for co-fund)
+/// * `co_funded` - Whether the channel is co-funded; then `merchant_address` is required
+/// * `bump_fee_rate` - Replace the refund built at `fee_rate` with one paying this higher
+///   rate (`--bump-fee-rate`); both parties sign the replacement
 /// * `output_path` - Path to save the transaction JSON
+/// * `unsigned` - Skip both signatures and save the transaction with its zeroed witness
+///   placeholder, for an external signing tool (`--save-unsigned`)
+#[allow(clippy::too_many_arguments)]
 pub async fn build_refund_transaction(
     config: &Config,
     funding_tx_hash: H256,
     funding_tx: &TransactionView,
+    funding_output_index: u32,
     user_address: &Address,
     merchant_address: Option<&Address>,
+    co_funded: bool,
     fee_rate: u64,
+    bump_fee_rate: Option<u64>,
     output_path: &str,
+    unsigned: bool,
 ) -> Result<(H256, TransactionView)> {
     println!("📝 构建 Refund 交易...");
 
+    // Extract Spillman Lock args from funding transaction, rejecting unrelated cells early
+    let spillman_cell = funding_tx
+        .outputs()
+        .get(funding_output_index as usize)
+        .ok_or_else(|| anyhow!("Funding transaction has no output {}", funding_output_index))?;
+    let lock_script = spillman_cell.lock();
+    ensure_spillman_lock(config, &lock_script)?;
+    let args_bytes: Bytes = lock_script.args().unpack();
+    SpillmanArgs::from_bytes(&args_bytes)?;
+
     let user_lock_script = Script::from(user_address);
     let merchant_lock_script = merchant_address.map(Script::from);
 
@@ -794,15 +970,14 @@ pub async fn build_refund_transaction(
         .dep_type(DepType::Code)
         .build();
 
-    // Parse keys using ckb-crypto for Spillman Lock signing
-    let user_privkey = Privkey::from_str(
-        config
-            .user
-            .private_key
-            .as_ref()
-            .expect("User private_key is required"),
-    )
-    .map_err(|e| anyhow!("Failed to parse user private key: {:?}", e))?;
+    // Parse user key for Spillman Lock signing
+    let user_privkey_hex = config
+        .user
+        .resolve_private_key()?
+        .ok_or_else(|| anyhow!("User private_key is required"))?;
+    let user_privkey_bytes = hex::decode(user_privkey_hex.trim_start_matches("0x"))?;
+    let user_signer =
+        RawKeySigner::new_arc(vec![secp256k1::SecretKey::from_slice(&user_privkey_bytes)?])?;
 
     // Parse merchant keys and multisig config
     let (merchant_privkeys, merchant_multisig_config) = if config.merchant.is_multisig() {
@@ -816,83 +991,52 @@ pub async fn build_refund_transaction(
         use crate::tx_builder::funding_v2::build_multisig_config;
         let multisig_config = build_multisig_config(&secret_keys, threshold, total)?;
 
-        (Some(secret_keys), Some(multisig_config))
+        (secret_keys, Some(multisig_config))
     } else {
         // Single-sig merchant
         let merchant_privkey_str = config
             .merchant
-            .private_key
-            .as_ref()
+            .resolve_private_key()?
             .ok_or_else(|| anyhow!("Merchant private_key is required for single-sig"))?;
         let merchant_secret_key = {
             let key_hex = merchant_privkey_str.trim_start_matches("0x");
             let key_bytes = hex::decode(key_hex)?;
             secp256k1::SecretKey::from_slice(&key_bytes)?
         };
-        (Some(vec![merchant_secret_key]), None)
+        (vec![merchant_secret_key], None)
     };
-
-    // Clone merchant_privkeys for signing
-    let merchant_privkeys_for_sign = merchant_privkeys.clone();
-
-    // Extract Spillman Lock args from funding transaction
-    let spillman_cell = funding_tx
-        .outputs()
-        .get(0)
-        .ok_or_else(|| anyhow!("Funding transaction has no output 0"))?;
-    let lock_script = spillman_cell.lock();
-    let args_bytes: Bytes = lock_script.args().unpack();
-    if args_bytes.len() != 50 {
-        return Err(anyhow!(
-            "Invalid Spillman Lock args length: expected 50, got {}",
-            args_bytes.len()
-        ));
-    }
+    let merchant_signer = RawKeySigner::new_arc(merchant_privkeys)?;
 
     // Check if this is an xUDT channel and build xUDT cell dep if needed
     let xudt_cell_dep = if spillman_cell.type_().to_opt().is_some() {
-        if let Some(ref usdi_config) = config.usdi {
-            let xudt_tx_hash = hex::decode(usdi_config.tx_hash.trim_start_matches("0x"))?;
-            let xudt_out_point = OutPoint::new_builder()
-                .tx_hash(ckb_types::packed::Byte32::from_slice(&xudt_tx_hash)?)
-                .index(usdi_config.index)
-                .build();
-            let xudt_dep = CellDep::new_builder()
-                .out_point(xudt_out_point)
-                .dep_type(DepType::Code)
-                .build();
-            Some(xudt_dep)
-        } else {
-            return Err(anyhow!("xUDT channel detected but usdi config not found"));
-        }
+        let xudt = config
+            .usdi
+            .as_ref()
+            .ok_or_else(|| anyhow!("xUDT channel detected but usdi config not found"))?
+            .validate()?;
+        Some(xudt.cell_dep)
     } else {
         None
     };
 
-    // Create user secret key for RefundContext
-    let user_privkey_hex = config
-        .user
-        .private_key
-        .as_ref()
-        .expect("User private_key is required");
-    let user_privkey_bytes = hex::decode(user_privkey_hex.trim_start_matches("0x"))?;
-    let user_secret_key = secp256k1::SecretKey::from_slice(&user_privkey_bytes)?;
-
     let request = RefundRequest {
         funding_tx_hash,
         funding_tx: funding_tx.clone(),
+        funding_output_index,
         user_lock_script,
         merchant_lock_script,
+        co_funded,
         fee_rate,
         xudt_cell_dep,
+        sponsor: None,
     };
 
     // Clone merchant_multisig_config for later use in signing
     let merchant_multisig_config_for_sign = merchant_multisig_config.clone();
 
     let context = RefundContext {
-        user_secret_key,
-        merchant_secret_keys: merchant_privkeys,
+        user_signer: user_signer.clone(),
+        merchant_signer: Some(merchant_signer.clone()),
         merchant_multisig_config,
         rpc_url: config.network.rpc_url.clone(),
         spillman_lock_dep: spillman_dep,
@@ -902,14 +1046,36 @@ pub async fn build_refund_transaction(
     // Build transaction
     let refund_tx = RefundTx::new().build(request, context).await?;
 
+    // Fee bump: the replacement spends the same input, so it needs fresh signatures
+    let refund_tx = match bump_fee_rate {
+        Some(new_rate) => {
+            let replaced_hash = refund_tx
+                .clone()
+                .into_inner()
+                .ok_or_else(|| anyhow!("No transaction"))?
+                .hash();
+            println!(
+                "⛽ 提高 Refund 手续费率: {} → {} shannon/KB",
+                fee_rate, new_rate
+            );
+            println!("  - 被替换的交易: {:#x}", replaced_hash);
+            refund_tx.rebuild_with_fee_rate(new_rate)?
+        }
+        None => refund_tx,
+    };
+
     // Sign transaction with Spillman Lock witness structure
-    println!("🔐 签名 Refund 交易 (Spillman Lock: Merchant + User)...");
-    let refund_tx = refund_tx.sign_for_spillman_lock(
-        &user_privkey,
-        merchant_privkeys_for_sign.as_ref().unwrap(),
-        &args_bytes,
-        merchant_multisig_config_for_sign.as_ref(),
-    )?;
+    let refund_tx = if unsigned {
+        refund_tx
+    } else {
+        println!("🔐 签名 Refund 交易 (Spillman Lock: Merchant + User)...");
+        refund_tx.sign_for_spillman_lock(
+            user_signer.as_ref(),
+            merchant_signer.as_ref(),
+            &args_bytes,
+            merchant_multisig_config_for_sign.as_ref(),
+        )?
+    };
 
     let tx = refund_tx
         .into_inner()
@@ -922,6 +1088,18 @@ pub async fn build_refund_transaction(
     println!("  - Inputs count: {}", tx.inputs().len());
     println!("  - Outputs count: {}", tx.outputs().len());
 
+    let spillman_capacity: u64 = spillman_cell.capacity().unpack();
+    let total_output: u64 = tx
+        .outputs()
+        .into_iter()
+        .map(|o| Unpack::<u64>::unpack(&o.capacity()))
+        .sum();
+    report_fee(
+        &tx,
+        spillman_capacity.saturating_sub(total_output),
+        config.channel.min_fee_rate,
+    );
+
     if merchant_address.is_some() {
         println!("  - Mode: Co-fund (2 outputs)");
         let user_cap: u64 = tx.outputs().get(0).unwrap().capacity().unpack();
@@ -944,7 +1122,27 @@ pub async fn build_refund_transaction(
     std::fs::write(output_path, json_str)?;
 
     println!("✓ Refund transaction saved: {}", output_path);
-    println!("  ✅ Transaction is signed and ready to broadcast after timeout");
+    if unsigned {
+        println!("  - Unsigned: the witness is a zeroed placeholder of the final size");
+    } else {
+        println!("  ✅ Transaction is signed and ready to broadcast after timeout");
+    }
+
+    let spillman_capacity: u64 = spillman_cell.capacity().unpack();
+    let total_output: u64 = tx
+        .outputs()
+        .into_iter()
+        .map(|o| Unpack::<u64>::unpack(&o.capacity()))
+        .sum();
+    log_event(
+        "refund",
+        "built",
+        json!({
+            "tx_hash": format!("{:#x}", tx_hash),
+            "fee": spillman_capacity.saturating_sub(total_output),
+            "output_path": output_path,
+        }),
+    );
 
     Ok((tx_hash.unpack(), tx))
 }
@@ -952,6 +1150,7 @@ pub async fn build_refund_transaction(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tx_builder::signing::spillman_signing_message;
 
     const REFUND_WITNESS_SIZE_SINGLE_SIG: usize = 147; // 16 + 1 + 65 + 65
 
@@ -968,4 +1167,679 @@ mod tests {
             REFUND_WITNESS_SIZE_SINGLE_SIG
         );
     }
+
+    const TEST_TIMEOUT_SINCE: u64 = 0x4000_0000_6553_f100;
+
+    fn test_lock_script(args: Vec<u8>) -> Script {
+        Script::new_builder()
+            .code_hash(H256([0x11; 32]).pack())
+            .hash_type(ckb_types::core::ScriptHashType::Type)
+            .args(Bytes::from(args).pack())
+            .build()
+    }
+
+    /// Funding tx with an unrelated output placed before the Spillman Lock cell (index 1)
+    fn test_funding_tx(spillman_capacity: u64) -> TransactionView {
+        let mut spillman_args = vec![0u8; 40];
+        spillman_args.extend_from_slice(&TEST_TIMEOUT_SINCE.to_le_bytes());
+        spillman_args.extend_from_slice(&[0, 0]);
+
+        Transaction::default()
+            .as_advanced_builder()
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(500 * 100_000_000))
+                    .lock(test_lock_script(vec![0x22; 20]))
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(spillman_capacity))
+                    .lock(test_lock_script(spillman_args))
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build()
+    }
+
+    async fn build_test_refund(funding_tx: &TransactionView, fee_rate: u64) -> Result<RefundTx> {
+        build_test_refund_with_sponsor(funding_tx, fee_rate, None).await
+    }
+
+    async fn build_test_refund_with_sponsor(
+        funding_tx: &TransactionView,
+        fee_rate: u64,
+        sponsor: Option<RefundSponsor>,
+    ) -> Result<RefundTx> {
+        let request = RefundRequest {
+            funding_tx_hash: funding_tx.hash().unpack(),
+            funding_tx: funding_tx.clone(),
+            funding_output_index: 1,
+            user_lock_script: test_lock_script(vec![0x33; 20]),
+            merchant_lock_script: None,
+            co_funded: false,
+            fee_rate,
+            xudt_cell_dep: None,
+            sponsor,
+        };
+        RefundTx::new().build(request, test_refund_context()).await
+    }
+
+    fn test_refund_context() -> RefundContext {
+        RefundContext {
+            user_signer: RawKeySigner::new_arc(vec![
+                secp256k1::SecretKey::from_slice(&[1u8; 32]).unwrap()
+            ])
+            .unwrap(),
+            merchant_signer: None,
+            merchant_multisig_config: None,
+            rpc_url: String::new(),
+            spillman_lock_dep: CellDep::default(),
+            auth_dep: CellDep::default(),
+        }
+    }
+
+    fn refund_fee(tx: &TransactionView, input_capacity: u64) -> u64 {
+        let output_capacity: u64 = tx
+            .outputs()
+            .into_iter()
+            .map(|output| Unpack::<u64>::unpack(&output.capacity()))
+            .sum();
+        input_capacity - output_capacity
+    }
+
+    #[tokio::test]
+    async fn test_refund_with_spillman_cell_at_nonzero_index() {
+        let spillman_capacity = 1000 * 100_000_000u64;
+        let funding_tx = test_funding_tx(spillman_capacity);
+        let funding_tx_hash: H256 = funding_tx.hash().unpack();
+
+        let tx = build_test_refund(&funding_tx, 1000)
+            .await
+            .unwrap()
+            .into_inner()
+            .unwrap();
+
+        let input = tx.inputs().get(0).unwrap();
+        let previous_output = input.previous_output();
+        let input_tx_hash: H256 = previous_output.tx_hash().unpack();
+        let input_index: u32 = previous_output.index().unpack();
+        let input_since: u64 = input.since().unpack();
+        assert_eq!(input_tx_hash, funding_tx_hash);
+        assert_eq!(input_index, 1);
+        assert_eq!(
+            input_since,
+            TEST_TIMEOUT_SINCE + crate::utils::crypto::TIMEOUT_GRACE_SECONDS
+        );
+        // The timestamp since is validated against chain time, not against a header dep
+        assert!(tx.header_deps().is_empty());
+
+        let user_capacity: u64 = tx.outputs().get(0).unwrap().capacity().unpack();
+        assert!(user_capacity < spillman_capacity);
+        assert!(user_capacity > spillman_capacity - 100_000);
+    }
+
+    #[tokio::test]
+    async fn test_sponsored_refund_keeps_full_capacity_for_user() {
+        let spillman_capacity = 1000 * 100_000_000;
+        let funding_tx = test_funding_tx(spillman_capacity);
+        let sponsor_input = CellInput::new(OutPoint::new(H256([0x44; 32]).pack(), 0), 0);
+        let sponsor = RefundSponsor {
+            inputs: vec![sponsor_input.clone()],
+            input_capacity: 100 * 100_000_000,
+            lock: test_lock_script(vec![0x55; 20]),
+        };
+
+        let refund = build_test_refund_with_sponsor(&funding_tx, 1000, Some(sponsor.clone()))
+            .await
+            .unwrap();
+        let tx = refund.into_inner().unwrap();
+
+        // Spillman input first, then the sponsor input, each with a witness
+        assert_eq!(tx.inputs().len(), 2);
+        assert_eq!(tx.inputs().get(1).unwrap(), sponsor_input);
+        assert_eq!(tx.witnesses().len(), 2);
+
+        // User output keeps the full Spillman capacity, the sponsor change pays the fee
+        let capacity =
+            |index: usize| -> u64 { tx.outputs().get(index).unwrap().capacity().unpack() };
+        assert_eq!(tx.outputs().len(), 2);
+        assert_eq!(capacity(0), spillman_capacity);
+        assert_eq!(tx.outputs().get(1).unwrap().lock(), sponsor.lock);
+        let fee = sponsor.input_capacity - capacity(1);
+        let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+        assert_eq!(fee, (tx_size * 1000).div_ceil(1000));
+
+        // A sponsor too small for its own change cell is rejected
+        let poor_sponsor = RefundSponsor {
+            input_capacity: 30 * 100_000_000,
+            ..sponsor
+        };
+        let err = build_test_refund_with_sponsor(&funding_tx, 1000, Some(poor_sponsor))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Sponsor change below minimum cell capacity"
+        );
+    }
+
+    #[test]
+    fn test_refund_fee_converges_before_iteration_cap() {
+        let spillman_capacity = 1000 * 100_000_000u64;
+        let funding_tx = test_funding_tx(spillman_capacity);
+
+        for fee_rate in [1000, 5000, 100_000] {
+            let builder = RefundTxBuilder {
+                refund_tx: RefundTx::new(),
+                request: RefundRequest {
+                    funding_tx_hash: funding_tx.hash().unpack(),
+                    funding_tx: funding_tx.clone(),
+                    funding_output_index: 1,
+                    user_lock_script: test_lock_script(vec![0x33; 20]),
+                    merchant_lock_script: None,
+                    co_funded: false,
+                    fee_rate,
+                    xudt_cell_dep: None,
+                    sponsor: None,
+                },
+                context: test_refund_context(),
+            };
+            let (tx, iterations) = builder
+                .build_with_converged_fee(spillman_capacity, 0)
+                .unwrap();
+            assert!(
+                iterations < MAX_FEE_ITERATIONS,
+                "fee rate {} took {} iterations",
+                fee_rate,
+                iterations
+            );
+
+            // The fee is the size at the fee rate, rounded up to the next shannon
+            let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+            let fee = refund_fee(&tx, spillman_capacity);
+            assert!(fee * 1000 >= tx_size * fee_rate, "fee rate {}", fee_rate);
+            assert!(
+                fee * 1000 < tx_size * fee_rate + 1000,
+                "fee rate {}",
+                fee_rate
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_with_higher_fee_rate() {
+        let spillman_capacity = 1000 * 100_000_000u64;
+        let funding_tx = test_funding_tx(spillman_capacity);
+
+        let refund_tx = build_test_refund(&funding_tx, 1000).await.unwrap();
+        let original = refund_tx.clone().into_inner().unwrap();
+        let original_fee = refund_fee(&original, spillman_capacity);
+
+        let bumped = refund_tx
+            .rebuild_with_fee_rate(5000)
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        let bumped_fee = refund_fee(&bumped, spillman_capacity);
+        let tx_size = bumped.data().as_reader().serialized_size_in_block() as u64;
+
+        // Same input, higher fee paid by the user output only
+        assert_eq!(bumped.inputs().as_slice(), original.inputs().as_slice());
+        assert_eq!(bumped_fee, (tx_size * 5000).div_ceil(1000));
+        assert!(bumped_fee > original_fee);
+        assert_eq!(
+            bumped.witnesses().get(0).unwrap().raw_data().len(),
+            original.witnesses().get(0).unwrap().raw_data().len()
+        );
+
+        // Lowering the fee rate is not a valid replacement
+        let expensive = build_test_refund(&funding_tx, 5000).await.unwrap();
+        assert!(expensive.rebuild_with_fee_rate(1000).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_co_funded_refund_requires_merchant_output() {
+        let funding_tx = test_funding_tx(1000 * 100_000_000);
+        let mut request = RefundRequest {
+            funding_tx_hash: funding_tx.hash().unpack(),
+            funding_tx: funding_tx.clone(),
+            funding_output_index: 1,
+            user_lock_script: test_lock_script(vec![0x33; 20]),
+            merchant_lock_script: None,
+            co_funded: true,
+            fee_rate: 1000,
+            xudt_cell_dep: None,
+            sponsor: None,
+        };
+
+        // Single-output refund of a co-funded channel is refused
+        let err = RefundTx::new()
+            .build(request.clone(), test_refund_context())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChannelError>(),
+            Some(ChannelError::CoFundedRefundWithoutMerchantOutput)
+        ));
+
+        // With the merchant output it builds the 2-output form
+        request.merchant_lock_script = Some(test_lock_script(vec![0x44; 20]));
+        let refund = RefundTx::new()
+            .build(request, test_refund_context())
+            .await
+            .unwrap();
+        assert_eq!(refund.into_inner().unwrap().outputs().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_refund_below_minimum_capacity_rejected() {
+        // 62 CKB channel: user cell needs 61 CKB (8 capacity + 53 lock)
+        let funding_tx = test_funding_tx(62 * 100_000_000);
+
+        // Fee of several CKB pushes the user output below 61 CKB
+        let err = build_test_refund(&funding_tx, 1_000_000_000)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "User refund below minimum cell capacity");
+
+        // A normal fee rate still succeeds
+        assert!(build_test_refund(&funding_tx, 1000).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_refund_rejects_malformed_xudt_funding_data() {
+        let spillman_capacity = 1000 * 100_000_000u64;
+        let funding_tx = test_funding_tx(spillman_capacity);
+        let xudt_funding_tx = |data: Vec<u8>| {
+            let mut outputs: Vec<CellOutput> = funding_tx.outputs().into_iter().collect();
+            outputs[1] = outputs[1]
+                .clone()
+                .as_builder()
+                .type_(Some(test_lock_script(vec![0x66; 32])).pack())
+                .build();
+            funding_tx
+                .as_advanced_builder()
+                .set_outputs(outputs)
+                .set_outputs_data(vec![Bytes::new().pack(), Bytes::from(data).pack()])
+                .build()
+        };
+
+        // Truncated amount, and an amount followed by extra bytes the refund would drop
+        for data in [vec![0x01; 8], vec![0x01; 20]] {
+            let len = data.len();
+            let err = build_test_refund(&xudt_funding_tx(data), 1000)
+                .await
+                .unwrap_err();
+            assert!(
+                err.to_string().contains(&format!(
+                    "Invalid xUDT funding cell data: expected 16 bytes, got {}",
+                    len
+                )),
+                "unexpected error: {}",
+                err
+            );
+        }
+
+        // A well-formed amount is refunded in full
+        let tx = build_test_refund(&xudt_funding_tx(500u128.to_le_bytes().to_vec()), 1000)
+            .await
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        let user_data = tx.outputs_data().get(0).unwrap().raw_data();
+        assert_eq!(user_data.as_ref(), 500u128.to_le_bytes());
+    }
+
+    /// Signer that never touches a key, like a hardware or remote signer would
+    struct MockSigner {
+        pubkey_hash: [u8; 20],
+        signature_byte: u8,
+        messages: std::sync::Mutex<Vec<[u8; 32]>>,
+    }
+
+    impl Signer for MockSigner {
+        fn sign(&self, message: [u8; 32], pubkey_hash: &[u8; 20]) -> Result<[u8; 65]> {
+            assert_eq!(pubkey_hash, &self.pubkey_hash);
+            self.messages.lock().unwrap().push(message);
+            Ok([self.signature_byte; 65])
+        }
+
+        fn has_key(&self, pubkey_hash: &[u8; 20]) -> bool {
+            pubkey_hash == &self.pubkey_hash
+        }
+    }
+
+    fn test_config(spillman_code_hash: H256) -> Config {
+        use crate::utils::config::{
+            AuthConfig, ChannelConfig, KeyConfig, NetworkConfig, SpillmanLockConfig,
+        };
+        let key = KeyConfig {
+            private_key: None,
+            private_key_env: None,
+            private_key_file: None,
+            multisig_threshold: None,
+            multisig_total: None,
+            private_keys: None,
+            address: String::new(),
+        };
+        Config {
+            network: NetworkConfig::default(),
+            user: key.clone(),
+            merchant: key,
+            channel: ChannelConfig {
+                capacity_ckb: 1000,
+                timeout_epochs: 0,
+                timeout_timestamp: 0,
+                tx_fee_shannon: 0,
+                min_timeout_seconds: 1200,
+                timeout_warn_seconds: 3600,
+                min_fee_rate: 1000,
+                bind_cell_deps: false,
+            },
+            spillman_lock: SpillmanLockConfig {
+                code_hash: format!("{:#x}", spillman_code_hash),
+                hash_type: "type".to_string(),
+                tx_hash: format!("{:#x}", H256([0u8; 32])),
+                index: 0,
+                binary_path: None,
+            },
+            auth: AuthConfig {
+                tx_hash: format!("{:#x}", H256([0u8; 32])),
+                index: 0,
+                binary_path: None,
+            },
+            usdi: None,
+            system_scripts: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refund_rejects_funding_cell_with_foreign_lock() {
+        // Output 0 is a 50-byte-args cell, but not locked by the configured Spillman Lock
+        let funding_tx = test_funding_tx(1000 * 100_000_000);
+        let foreign_tx = funding_tx
+            .as_advanced_builder()
+            .set_outputs(vec![funding_tx.output(1).unwrap()])
+            .set_outputs_data(vec![Bytes::new().pack()])
+            .build();
+        let config = test_config(H256([0x99; 32]));
+        let user_address = Address::new(
+            ckb_sdk::NetworkType::Testnet,
+            test_lock_script(vec![0x33; 20]).into(),
+            true,
+        );
+
+        let err = build_refund_transaction(
+            &config,
+            foreign_tx.hash().unpack(),
+            &foreign_tx,
+            0,
+            &user_address,
+            None,
+            false,
+            1000,
+            None,
+            "/nonexistent/refund_tx.json",
+            false,
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("not locked by the configured Spillman Lock"),
+            "unexpected error: {}",
+            err
+        );
+
+        // The configured lock passes the check and only fails later (no user key here)
+        let config = test_config(H256([0x11; 32]));
+        let err = build_refund_transaction(
+            &config,
+            foreign_tx.hash().unpack(),
+            &foreign_tx,
+            0,
+            &user_address,
+            None,
+            false,
+            1000,
+            None,
+            "/nonexistent/refund_tx.json",
+            false,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "User private_key is required");
+    }
+
+    #[tokio::test]
+    async fn test_sign_for_spillman_lock_with_mock_signer() {
+        // test_funding_tx uses all-zero merchant and user pubkey hashes
+        let funding_tx = test_funding_tx(1000 * 100_000_000);
+        let args: Bytes = funding_tx.outputs().get(1).unwrap().lock().args().unpack();
+        let mock_signer = |signature_byte| MockSigner {
+            pubkey_hash: [0u8; 20],
+            signature_byte,
+            messages: Default::default(),
+        };
+        let user_signer = mock_signer(0xaa);
+        let merchant_signer = mock_signer(0xbb);
+
+        let tx = build_test_refund(&funding_tx, 1000)
+            .await
+            .unwrap()
+            .sign_for_spillman_lock(&user_signer, &merchant_signer, &args, None)
+            .unwrap()
+            .into_inner()
+            .unwrap();
+
+        let witness = tx.witnesses().get(0).unwrap().raw_data();
+        assert_eq!(witness.len(), REFUND_WITNESS_SIZE_SINGLE_SIG);
+        assert_eq!(witness[16], UNLOCK_TYPE_TIMEOUT);
+        assert_eq!(&witness[17..82], &[0xbb; 65][..]);
+        assert_eq!(&witness[82..147], &[0xaa; 65][..]);
+
+        // Both parties signed the same message, once each
+        let user_messages = user_signer.messages.lock().unwrap().clone();
+        assert_eq!(user_messages.len(), 1);
+        assert_eq!(*merchant_signer.messages.lock().unwrap(), user_messages);
+        assert_eq!(user_messages[0], spillman_signing_message(&tx));
+
+        // A signer without the user's key is rejected
+        let stranger = MockSigner {
+            pubkey_hash: [0x99; 20],
+            signature_byte: 0xcc,
+            messages: Default::default(),
+        };
+        let err = build_test_refund(&funding_tx, 1000)
+            .await
+            .unwrap()
+            .sign_for_spillman_lock(&stranger, &merchant_signer, &args, None)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "User pubkey hash mismatch!");
+    }
+
+    #[tokio::test]
+    async fn test_detached_user_signature_matches_in_process_signing() {
+        let funding_tx = test_funding_tx(1000 * 100_000_000);
+        let args: Bytes = funding_tx.outputs().get(1).unwrap().lock().args().unpack();
+        let mock_signer = |signature_byte| MockSigner {
+            pubkey_hash: [0u8; 20],
+            signature_byte,
+            messages: Default::default(),
+        };
+        let user_signer = mock_signer(0xaa);
+        let merchant_signer = mock_signer(0xbb);
+        let refund_tx = build_test_refund(&funding_tx, 1000).await.unwrap();
+
+        // A user signature needs the merchant's half first
+        let err = refund_tx
+            .clone()
+            .inject_user_signature([0xaa; 65])
+            .unwrap_err();
+        assert!(err.to_string().contains("call sign_as_merchant first"));
+
+        // Online: the merchant pre-signs, leaving the user signature zeroed
+        let half_signed = refund_tx
+            .clone()
+            .sign_as_merchant(&merchant_signer, &args, None)
+            .unwrap();
+        let half_witness = half_signed
+            .clone()
+            .into_inner()
+            .unwrap()
+            .witnesses()
+            .get(0)
+            .unwrap()
+            .raw_data();
+        assert_eq!(&half_witness[82..147], &[0u8; 65][..]);
+
+        // Offline: the user signs the message only
+        let message = half_signed.extract_signing_message(&args).unwrap();
+        assert_eq!(message, refund_tx.extract_signing_message(&args).unwrap());
+        let user_sig = user_signer.sign(message, &[0u8; 20]).unwrap();
+
+        // Online: combine
+        let detached = half_signed
+            .inject_user_signature(user_sig)
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        let in_process = refund_tx
+            .sign_for_spillman_lock(&mock_signer(0xaa), &mock_signer(0xbb), &args, None)
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        assert_eq!(
+            detached.witnesses().as_bytes(),
+            in_process.witnesses().as_bytes()
+        );
+        assert_eq!(*merchant_signer.messages.lock().unwrap(), vec![message]);
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_refund_has_final_size_placeholder_witness() {
+        let funding_tx = test_funding_tx(1000 * 100_000_000);
+        let args: Bytes = funding_tx.outputs().get(1).unwrap().lock().args().unpack();
+        let mut config = test_config(H256([0x11; 32]));
+        config.user.private_key = Some(format!("0x{}", "01".repeat(32)));
+        config.merchant.private_key = Some(format!("0x{}", "02".repeat(32)));
+        let user_address = Address::new(
+            ckb_sdk::NetworkType::Testnet,
+            test_lock_script(vec![0x33; 20]).into(),
+            true,
+        );
+        let output_path = std::env::temp_dir().join(format!(
+            "spillman_unsigned_refund_{}.json",
+            std::process::id()
+        ));
+
+        let (_, unsigned_tx) = build_refund_transaction(
+            &config,
+            funding_tx.hash().unpack(),
+            &funding_tx,
+            1,
+            &user_address,
+            None,
+            false,
+            1000,
+            None,
+            output_path.to_str().unwrap(),
+            true,
+        )
+        .await
+        .unwrap();
+
+        // The saved file holds the unsigned transaction: one zeroed witness of the final size
+        let saved: ckb_jsonrpc_types::TransactionView =
+            serde_json::from_str(&std::fs::read_to_string(&output_path).unwrap()).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        let saved = Transaction::from(saved.inner).into_view();
+        assert_eq!(saved.hash(), unsigned_tx.hash());
+        assert_eq!(saved.witnesses().len(), 1);
+        let placeholder = saved.witnesses().get(0).unwrap().raw_data();
+        assert_eq!(placeholder.len(), REFUND_WITNESS_SIZE_SINGLE_SIG);
+        assert!(placeholder.iter().all(|byte| *byte == 0));
+
+        // Signing fills the placeholder without resizing it, so the fee still holds
+        let mock_signer = |signature_byte| MockSigner {
+            pubkey_hash: [0u8; 20],
+            signature_byte,
+            messages: Default::default(),
+        };
+        let signed = RefundTx::from(unsigned_tx.clone())
+            .sign_for_spillman_lock(&mock_signer(0xaa), &mock_signer(0xbb), &args, None)
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        assert_eq!(
+            signed.data().serialized_size_in_block(),
+            saved.data().serialized_size_in_block()
+        );
+        assert_eq!(signed.outputs().as_bytes(), saved.outputs().as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_bump_fee_rate_replaces_refund_with_higher_fee() {
+        let spillman_capacity = 1000 * 100_000_000u64;
+        let funding_tx = test_funding_tx(spillman_capacity);
+        let mut config = test_config(H256([0x11; 32]));
+        config.user.private_key = Some(format!("0x{}", "01".repeat(32)));
+        config.merchant.private_key = Some(format!("0x{}", "02".repeat(32)));
+        let user_address = Address::new(
+            ckb_sdk::NetworkType::Testnet,
+            test_lock_script(vec![0x33; 20]).into(),
+            true,
+        );
+        let output_path = std::env::temp_dir().join(format!(
+            "spillman_bumped_refund_{}.json",
+            std::process::id()
+        ));
+
+        let build = |bump_fee_rate| {
+            build_refund_transaction(
+                &config,
+                funding_tx.hash().unpack(),
+                &funding_tx,
+                1,
+                &user_address,
+                None,
+                false,
+                1000,
+                bump_fee_rate,
+                output_path.to_str().unwrap(),
+                true,
+            )
+        };
+        let (_, original) = build(None).await.unwrap();
+        let (_, bumped) = build(Some(5000)).await.unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+
+        // Same input, the user output pays the higher fee
+        assert_eq!(bumped.inputs().as_slice(), original.inputs().as_slice());
+        assert_ne!(bumped.hash(), original.hash());
+        let tx_size = bumped.data().as_reader().serialized_size_in_block() as u64;
+        assert_eq!(
+            refund_fee(&bumped, spillman_capacity),
+            (tx_size * 5000).div_ceil(1000)
+        );
+
+        // A bump below the build rate is rejected
+        assert!(build_refund_transaction(
+            &config,
+            funding_tx.hash().unpack(),
+            &funding_tx,
+            1,
+            &user_address,
+            None,
+            false,
+            5000,
+            Some(1000),
+            output_path.to_str().unwrap(),
+            true,
+        )
+        .await
+        .is_err());
+    }
 }
@@ -0,0 +1,230 @@
+use anyhow::{anyhow, Result};
+use ckb_hash::blake2b_256;
+use ckb_types::{core::TransactionView, packed::CellDepVec, prelude::*};
+
+/// Args version whose signing message clears `cell_deps` (the default)
+pub const ARGS_VERSION_DETACHED_CELL_DEPS: u8 = 0;
+/// Args version whose signing message includes `cell_deps`
+pub const ARGS_VERSION_BOUND_CELL_DEPS: u8 = 1;
+/// Version flag: the refund needs only the user signature; the low 7 bits pick the message
+pub const ARGS_FLAG_USER_ONLY_REFUND: u8 = 0x80;
+
+/// Compute the message both parties sign for a Spillman Lock transaction
+///
+/// Spillman Lock signs `blake2b_256` of the funding cell's OutPoint followed by the raw
+/// transaction with `cell_deps` cleared, so witnesses and cell deps can change without
+/// invalidating signatures. This is the same message the contract rebuilds on-chain for
+/// version 0 args.
+pub fn spillman_signing_message(tx: &TransactionView) -> [u8; 32] {
+    let raw_tx = tx
+        .data()
+        .raw()
+        .as_builder()
+        .cell_deps(CellDepVec::default())
+        .build();
+
+    bind_to_funding_cell(tx, raw_tx.as_slice())
+}
+
+/// Compute the signing message for the args `version` of the channel
+///
+/// Version 1 keeps `cell_deps` in the hashed raw transaction, binding the signatures to
+/// the exact Spillman Lock and auth cells referenced. Flags such as
+/// `ARGS_FLAG_USER_ONLY_REFUND` do not change the message.
+pub fn spillman_signing_message_for_version(tx: &TransactionView, version: u8) -> Result<[u8; 32]> {
+    match version & !ARGS_FLAG_USER_ONLY_REFUND {
+        ARGS_VERSION_DETACHED_CELL_DEPS => Ok(spillman_signing_message(tx)),
+        ARGS_VERSION_BOUND_CELL_DEPS => Ok(bind_to_funding_cell(tx, tx.data().raw().as_slice())),
+        _ => Err(anyhow!(
+            "Unsupported Spillman Lock args version: {}",
+            version
+        )),
+    }
+}
+
+/// `blake2b_256(funding OutPoint || raw_tx)`
+///
+/// Hashing the OutPoint of the Spillman cell explicitly keeps a signature made for one
+/// channel from verifying on another channel with identical args. The Spillman cell is
+/// always input 0 of the transactions built here.
+fn bind_to_funding_cell(tx: &TransactionView, raw_tx: &[u8]) -> [u8; 32] {
+    let out_point = tx
+        .inputs()
+        .get(0)
+        .map(|input| input.previous_output())
+        .unwrap_or_default();
+    let mut preimage = out_point.as_slice().to_vec();
+    preimage.extend_from_slice(raw_tx);
+    blake2b_256(preimage)
+}
+
+/// Compute the commitment signing message when the merchant has added fee inputs
+///
+/// The contract signs a commitment over the Spillman input (input 0) and its leading
+/// `channel_outputs` outputs only, so fee inputs and change appended at settlement keep
+/// the user's signature valid. Without fee inputs this equals the version's message.
+pub fn spillman_commitment_signing_message(
+    tx: &TransactionView,
+    version: u8,
+    channel_outputs: usize,
+) -> Result<[u8; 32]> {
+    let spillman_input = tx
+        .inputs()
+        .get(0)
+        .ok_or_else(|| anyhow!("Commitment transaction has no input"))?;
+    let signed_tx = tx
+        .as_advanced_builder()
+        .set_inputs(vec![spillman_input])
+        .set_outputs(tx.outputs().into_iter().take(channel_outputs).collect())
+        .set_outputs_data(
+            tx.outputs_data()
+                .into_iter()
+                .take(channel_outputs)
+                .collect(),
+        )
+        .build();
+
+    spillman_signing_message_for_version(&signed_tx, version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::{
+        bytes::Bytes,
+        core::Capacity,
+        packed::{CellDep, CellInput, CellOutput, OutPoint, Transaction},
+        H256,
+    };
+
+    #[test]
+    fn test_spillman_signing_message_ignores_cell_deps_and_witnesses() {
+        let tx = Transaction::default()
+            .as_advanced_builder()
+            .input(CellInput::new(OutPoint::new(H256([0x11; 32]).pack(), 0), 0))
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(100 * 100_000_000))
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build();
+        // blake2b_256("ckb-default-hash") of the input OutPoint followed by the molecule
+        // RawTransaction with empty cell_deps
+        let expected = "986a9ea181af06c62e49e3bba58a9c0b7233287b1ac70e5bf2068c17ed636bcf";
+        assert_eq!(hex::encode(spillman_signing_message(&tx)), expected);
+
+        let with_deps = tx
+            .as_advanced_builder()
+            .cell_dep(
+                CellDep::new_builder()
+                    .out_point(OutPoint::new(H256([0x22; 32]).pack(), 1))
+                    .build(),
+            )
+            .witness(Bytes::from(vec![0xaa; 65]).pack())
+            .build();
+        assert_eq!(hex::encode(spillman_signing_message(&with_deps)), expected);
+    }
+
+    #[test]
+    fn test_signing_message_for_each_args_version() {
+        let tx = Transaction::default()
+            .as_advanced_builder()
+            .input(CellInput::new(OutPoint::new(H256([0x11; 32]).pack(), 0), 0))
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(100 * 100_000_000))
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build();
+        let with_deps = tx
+            .as_advanced_builder()
+            .cell_dep(
+                CellDep::new_builder()
+                    .out_point(OutPoint::new(H256([0x22; 32]).pack(), 1))
+                    .build(),
+            )
+            .build();
+
+        // Version 0 is the cell-deps-free message
+        assert_eq!(
+            spillman_signing_message_for_version(&with_deps, ARGS_VERSION_DETACHED_CELL_DEPS)
+                .unwrap(),
+            spillman_signing_message(&tx)
+        );
+
+        // Version 1 commits to the deps: adding one changes the message
+        let bound = |tx: &TransactionView| {
+            spillman_signing_message_for_version(tx, ARGS_VERSION_BOUND_CELL_DEPS).unwrap()
+        };
+        assert_eq!(bound(&tx), spillman_signing_message(&tx));
+        assert_ne!(bound(&with_deps), bound(&tx));
+        let out_point = OutPoint::new(H256([0x11; 32]).pack(), 0);
+        assert_eq!(
+            bound(&with_deps),
+            blake2b_256([out_point.as_slice(), with_deps.data().raw().as_slice()].concat())
+        );
+
+        assert!(spillman_signing_message_for_version(&tx, 2).is_err());
+
+        // The user-only refund flag keeps the scheme's message
+        assert_eq!(
+            spillman_signing_message_for_version(
+                &with_deps,
+                ARGS_VERSION_BOUND_CELL_DEPS | ARGS_FLAG_USER_ONLY_REFUND
+            )
+            .unwrap(),
+            bound(&with_deps)
+        );
+    }
+
+    #[test]
+    fn test_commitment_signing_message_ignores_merchant_fee_input() {
+        let commitment = Transaction::default()
+            .as_advanced_builder()
+            .input(CellInput::new(OutPoint::new(H256([0x11; 32]).pack(), 0), 0))
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(600 * 100_000_000))
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(400 * 100_000_000))
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build();
+        let settled = commitment
+            .as_advanced_builder()
+            .input(CellInput::new(OutPoint::new(H256([0x33; 32]).pack(), 2), 0))
+            .output(
+                CellOutput::new_builder()
+                    .capacity(Capacity::shannons(199 * 100_000_000))
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build();
+
+        for version in [
+            ARGS_VERSION_DETACHED_CELL_DEPS,
+            ARGS_VERSION_BOUND_CELL_DEPS,
+        ] {
+            let signed = spillman_signing_message_for_version(&commitment, version).unwrap();
+            assert_eq!(
+                spillman_commitment_signing_message(&commitment, version, 2).unwrap(),
+                signed
+            );
+            assert_eq!(
+                spillman_commitment_signing_message(&settled, version, 2).unwrap(),
+                signed
+            );
+        }
+        assert_ne!(
+            spillman_signing_message(&settled),
+            spillman_signing_message(&commitment)
+        );
+    }
+}
@@ -1,52 +1,69 @@
 use anyhow::{anyhow, Result};
 use ckb_crypto::secp::Pubkey;
-use ckb_sdk::{constants::MultisigScript, Address, Since, SinceType};
+use ckb_sdk::{constants::MultisigScript, Address, ScriptId, Since, SinceType};
 use ckb_types::{bytes::Bytes, core::ScriptHashType, packed, prelude::*, H256};
 use std::str::FromStr;
 
 use crate::utils::config::Config;
-use crate::utils::crypto::{pubkey_hash, SpillmanLockArgs};
+use crate::utils::crypto::{pubkey_hash, SpillmanArgs};
 
 /// Detect multisig algorithm_id from merchant address
 /// Returns:
 /// - 0: single-sig
 /// - 6: multisig Legacy (hash_type = Type)
 /// - 7: multisig V2 (hash_type = Data1)
+///
+/// The merchant address must use one of the system scripts in `config.system_scripts`,
+/// otherwise the contract would never accept its commitment output.
 fn detect_multisig_algorithm_id(config: &Config) -> Result<u8> {
-    if !config.merchant.is_multisig() {
-        return Ok(0); // single-sig
-    }
-
     // Parse merchant address to get lock script
     let merchant_address = Address::from_str(&config.merchant.address)
         .map_err(|e| anyhow!("Failed to parse merchant address: {}", e))?;
 
     let lock_script = packed::Script::from(&merchant_address);
     let code_hash: H256 = lock_script.code_hash().unpack();
+    let matches = |script_id: &ScriptId| {
+        code_hash == script_id.code_hash && lock_script.hash_type() == script_id.hash_type.into()
+    };
+
+    if !config.merchant.is_multisig() {
+        let sighash_script_id = config.system_scripts.sighash_script_id()?;
+        if !matches(&sighash_script_id) {
+            return Err(anyhow!(
+                "Merchant address is not a secp256k1 sighash address (expected code hash {:#x})",
+                sighash_script_id.code_hash
+            ));
+        }
+        return Ok(0); // single-sig
+    }
 
-    // Compare with known multisig script IDs
-    let legacy_script_id = MultisigScript::Legacy.script_id();
-    let v2_script_id = MultisigScript::V2.script_id();
+    // Compare with the configured multisig script IDs
+    let legacy_script_id = config
+        .system_scripts
+        .multisig_script_id(MultisigScript::Legacy)?;
+    let v2_script_id = config
+        .system_scripts
+        .multisig_script_id(MultisigScript::V2)?;
 
-    if code_hash == legacy_script_id.code_hash
-        && lock_script.hash_type() == legacy_script_id.hash_type.into()
-    {
+    if matches(&legacy_script_id) {
         Ok(6) // Legacy multisig
-    } else if code_hash == v2_script_id.code_hash
-        && lock_script.hash_type() == v2_script_id.hash_type.into()
-    {
+    } else if matches(&v2_script_id) {
         Ok(7) // V2 multisig
     } else {
         Err(anyhow!("Unknown multisig type for merchant address"))
     }
 }
 
-/// Build Spillman Lock script with pre-computed merchant pubkey hash
-/// This is useful for multisig scenarios where merchant_pubkey_hash is blake160(multisig_config)
-pub fn build_spillman_lock_script_with_hash(
+/// Spillman Lock script of a channel, derived from the config alone
+///
+/// Pure: nothing is built, signed or printed, so integrators can show the funding address
+/// (or `calc_script_hash()`) before committing to a funding transaction. The merchant's
+/// algorithm_id is detected from `config.merchant.address`; `merchant_lock_arg` is
+/// blake160(pubkey) or blake160(multisig_config).
+pub fn spillman_lock_script(
     config: &Config,
     user_pubkey: &Pubkey,
-    merchant_pubkey_hash: &[u8],
+    merchant_lock_arg: &[u8],
     timeout_timestamp: u64,
 ) -> Result<packed::Script> {
     // Detect algorithm_id from merchant address
@@ -58,12 +75,23 @@ pub fn build_spillman_lock_script_with_hash(
     build_spillman_lock_script_with_hash_and_algorithm(
         config,
         user_pubkey,
-        merchant_pubkey_hash,
+        merchant_lock_arg,
         timeout_timestamp,
         algorithm_id,
     )
 }
 
+/// Build Spillman Lock script with pre-computed merchant pubkey hash
+/// This is useful for multisig scenarios where merchant_pubkey_hash is blake160(multisig_config)
+pub fn build_spillman_lock_script_with_hash(
+    config: &Config,
+    user_pubkey: &Pubkey,
+    merchant_pubkey_hash: &[u8],
+    timeout_timestamp: u64,
+) -> Result<packed::Script> {
+    spillman_lock_script(config, user_pubkey, merchant_pubkey_hash, timeout_timestamp)
+}
+
 /// Build Spillman Lock script with pre-computed merchant pubkey hash and explicit algorithm_id
 /// This is useful for multisig scenarios where merchant_pubkey_hash is blake160(multisig_config)
 pub fn build_spillman_lock_script_with_hash_and_algorithm(
@@ -83,14 +111,26 @@ pub fn build_spillman_lock_script_with_hash_and_algorithm(
     // Convert &[u8] to [u8; 20]
     let mut merchant_hash_array = [0u8; 20];
     merchant_hash_array.copy_from_slice(&merchant_pubkey_hash[0..20]);
-    let args = SpillmanLockArgs::new_with_algorithm(
+    let mut args = SpillmanArgs::new_with_algorithm(
         merchant_hash_array,
         user_pubkey_hash,
         timeout_since.value(),
         algorithm_id,
     );
-    let args_bytes = args.to_bytes();
+    args.version = config.channel.args_version();
+
+    let (code_hash, hash_type) = spillman_lock_code(config)?;
 
+    let hash_type_byte: packed::Byte = hash_type.into();
+    Ok(packed::Script::new_builder()
+        .code_hash(code_hash.pack())
+        .hash_type(hash_type_byte)
+        .args(Bytes::copy_from_slice(&args.to_bytes()).pack())
+        .build())
+}
+
+/// Parse the configured Spillman Lock code_hash and hash_type
+fn spillman_lock_code(config: &Config) -> Result<(H256, ScriptHashType)> {
     let code_hash_str = config.spillman_lock.code_hash.trim_start_matches("0x");
     let code_hash = H256::from_str(code_hash_str).map_err(|e| {
         anyhow!(
@@ -106,11 +146,164 @@ pub fn build_spillman_lock_script_with_hash_and_algorithm(
         "data2" => ScriptHashType::Data2,
         _ => return Err(anyhow!("Invalid hash type")),
     };
+    Ok((code_hash, hash_type))
+}
 
+/// Ensure a lock script is the Spillman Lock configured in `config.spillman_lock`
+///
+/// Catches transactions that point at an unrelated cell before any signing happens.
+pub fn ensure_spillman_lock(config: &Config, lock_script: &packed::Script) -> Result<()> {
+    let (code_hash, hash_type) = spillman_lock_code(config)?;
+    let actual_code_hash: H256 = lock_script.code_hash().unpack();
     let hash_type_byte: packed::Byte = hash_type.into();
-    Ok(packed::Script::new_builder()
-        .code_hash(code_hash.pack())
-        .hash_type(hash_type_byte)
-        .args(Bytes::from(args_bytes).pack())
-        .build())
+
+    if actual_code_hash != code_hash || lock_script.hash_type() != hash_type_byte {
+        return Err(anyhow!(
+            "Cell is not locked by the configured Spillman Lock: expected code_hash {:#x} ({}), got {:#x} (hash_type {})",
+            code_hash,
+            config.spillman_lock.hash_type,
+            actual_code_hash,
+            lock_script.hash_type()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_crypto::secp::Privkey;
+    use ckb_sdk::{AddressPayload, NetworkType};
+
+    const DEV_SIGHASH_CODE_HASH: H256 = H256([0x51; 32]);
+    const DEV_MULTISIG_LEGACY_CODE_HASH: H256 = H256([0x52; 32]);
+
+    fn address(code_hash: &H256, hash_type: ScriptHashType) -> String {
+        let payload =
+            AddressPayload::new_full(hash_type, code_hash.pack(), Bytes::from(vec![0x77; 20]));
+        Address::new(NetworkType::Dev, payload, true).to_string()
+    }
+
+    /// Config for a dev chain whose merchant uses the given address
+    fn dev_config(merchant: &str, multisig: bool, system_scripts: &str) -> Config {
+        let multisig_keys = if multisig {
+            "multisig_threshold = 1\nmultisig_total = 1\nprivate_keys = [\"01\"]"
+        } else {
+            "private_key = \"01\""
+        };
+        let toml = format!(
+            r#"
+[network]
+rpc_url = "http://127.0.0.1:8114"
+
+[user]
+private_key = "02"
+address = "{merchant}"
+
+[merchant]
+{multisig_keys}
+address = "{merchant}"
+
+[channel]
+capacity_ckb = 1000
+timeout_timestamp = 1763367827
+tx_fee_shannon = 100000
+
+[spillman_lock]
+code_hash = "0x{spillman}"
+hash_type = "type"
+tx_hash = "0x{spillman}"
+index = 0
+
+[auth]
+tx_hash = "0x{spillman}"
+index = 1
+
+{system_scripts}
+"#,
+            spillman = "11".repeat(32),
+        );
+        toml::from_str(&toml).unwrap()
+    }
+
+    #[test]
+    fn test_spillman_lock_script_with_custom_system_scripts() {
+        let user_pubkey = Privkey::from_slice(&[2u8; 32]).pubkey().unwrap();
+        let system_scripts = format!(
+            "[system_scripts]\nsecp256k1_code_hash = \"{:#x}\"\nmultisig_legacy_code_hash = \"{:#x}\"",
+            DEV_SIGHASH_CODE_HASH, DEV_MULTISIG_LEGACY_CODE_HASH
+        );
+
+        // Single-sig merchant on the dev chain's sighash script
+        let merchant = address(&DEV_SIGHASH_CODE_HASH, ScriptHashType::Type);
+        let config = dev_config(&merchant, false, &system_scripts);
+        let script =
+            build_spillman_lock_script_with_hash(&config, &user_pubkey, &[0x77; 20], 1763367827)
+                .unwrap();
+        let args = SpillmanArgs::from_bytes(&script.args().raw_data()).unwrap();
+        assert_eq!(args.algorithm_id, 0);
+
+        // Legacy multisig merchant on the dev chain's multisig script
+        let merchant = address(&DEV_MULTISIG_LEGACY_CODE_HASH, ScriptHashType::Type);
+        let config = dev_config(&merchant, true, &system_scripts);
+        let script =
+            build_spillman_lock_script_with_hash(&config, &user_pubkey, &[0x77; 20], 1763367827)
+                .unwrap();
+        let args = SpillmanArgs::from_bytes(&script.args().raw_data()).unwrap();
+        assert_eq!(args.algorithm_id, 6);
+
+        // Without the section the mainnet/testnet hashes apply and the dev address is rejected
+        let config = dev_config(&merchant, true, "");
+        assert!(build_spillman_lock_script_with_hash(
+            &config,
+            &user_pubkey,
+            &[0x77; 20],
+            1763367827
+        )
+        .is_err());
+        let merchant = address(&DEV_SIGHASH_CODE_HASH, ScriptHashType::Type);
+        let config = dev_config(&merchant, false, "");
+        assert!(build_spillman_lock_script_with_hash(
+            &config,
+            &user_pubkey,
+            &[0x77; 20],
+            1763367827
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_spillman_lock_script_hash_is_deterministic() {
+        let user_pubkey = Privkey::from_slice(&[2u8; 32]).pubkey().unwrap();
+        let system_scripts = format!(
+            "[system_scripts]\nsecp256k1_code_hash = \"{:#x}\"",
+            DEV_SIGHASH_CODE_HASH
+        );
+        let merchant = address(&DEV_SIGHASH_CODE_HASH, ScriptHashType::Type);
+        let config = dev_config(&merchant, false, &system_scripts);
+
+        let script_hash = |timeout: u64| {
+            spillman_lock_script(&config, &user_pubkey, &[0x77; 20], timeout)
+                .unwrap()
+                .calc_script_hash()
+        };
+
+        assert_eq!(script_hash(1763367827), script_hash(1763367827));
+        assert_ne!(script_hash(1763367827), script_hash(1763367828));
+        assert_eq!(
+            script_hash(1763367827),
+            build_spillman_lock_script_with_hash(&config, &user_pubkey, &[0x77; 20], 1763367827)
+                .unwrap()
+                .calc_script_hash()
+        );
+
+        // `set-up --bind-cell-deps` creates a version 1 channel, a different script
+        let mut bound_config = config.clone();
+        bound_config.channel.bind_cell_deps = true;
+        let script =
+            spillman_lock_script(&bound_config, &user_pubkey, &[0x77; 20], 1763367827).unwrap();
+        let args = SpillmanArgs::from_bytes(&script.args().raw_data()).unwrap();
+        assert_eq!(args.version, 1);
+        assert_ne!(script.calc_script_hash(), script_hash(1763367827));
+    }
 }
@@ -3,7 +3,11 @@
 /// This module provides common functions for calculating witness sizes
 /// for different signature types (single-sig vs multisig) used in
 /// Spillman Channel transactions.
+use anyhow::{anyhow, Result};
 use ckb_sdk::unlock::MultisigConfig;
+use ckb_types::bytes::Bytes;
+
+use crate::signer::Signer;
 
 /// Size of a single ECDSA signature (r + s + v)
 pub const SIGNATURE_SIZE: usize = 65;
@@ -14,6 +18,105 @@ pub const EMPTY_WITNESS_ARGS_SIZE: usize = 16;
 /// Size of unlock type byte
 pub const UNLOCK_TYPE_SIZE: usize = 1;
 
+/// Empty WitnessArgs placeholder prefix (for xUDT compatibility)
+pub const EMPTY_WITNESS_ARGS: [u8; EMPTY_WITNESS_ARGS_SIZE] =
+    [16, 0, 0, 0, 16, 0, 0, 0, 16, 0, 0, 0, 16, 0, 0, 0];
+
+/// Unlock type for the commitment path
+pub const UNLOCK_TYPE_COMMITMENT: u8 = 0x00;
+
+/// Unlock type for the timeout (refund) path
+pub const UNLOCK_TYPE_TIMEOUT: u8 = 0x01;
+
+/// Unlock type for the splice path (merchant payout + new Spillman cell)
+pub const UNLOCK_TYPE_SPLICE: u8 = 0x02;
+
+/// Merchant part of a Spillman Lock witness
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MerchantSig {
+    /// Single-sig merchant: one 65-byte signature
+    Single([u8; SIGNATURE_SIZE]),
+    /// Multisig merchant: multisig_config (S | R | M | N | PubKeyHash...) + M signatures
+    Multi {
+        config: Vec<u8>,
+        sigs: Vec<[u8; SIGNATURE_SIZE]>,
+    },
+}
+
+impl MerchantSig {
+    /// Size of the merchant part in the witness
+    pub fn size(&self) -> usize {
+        match self {
+            MerchantSig::Single(_) => SIGNATURE_SIZE,
+            MerchantSig::Multi { config, sigs } => config.len() + sigs.len() * SIGNATURE_SIZE,
+        }
+    }
+
+    /// Sign `message` as the merchant
+    ///
+    /// Single-sig merchants sign with the key of `merchant_lock_arg`; multisig
+    /// merchants sign with the first `threshold` keys of the config held by the
+    /// signer, in config order.
+    pub fn sign(
+        signer: &dyn Signer,
+        message: [u8; 32],
+        merchant_lock_arg: &[u8; 20],
+        multisig_config: Option<&MultisigConfig>,
+    ) -> Result<Self> {
+        let Some(config) = multisig_config else {
+            return Ok(MerchantSig::Single(
+                signer.sign(message, merchant_lock_arg)?,
+            ));
+        };
+
+        let threshold = config.threshold() as usize;
+        let sigs = config
+            .sighash_addresses()
+            .iter()
+            .filter(|hash| signer.has_key(&hash.0))
+            .take(threshold)
+            .map(|hash| signer.sign(message, &hash.0))
+            .collect::<Result<Vec<_>>>()?;
+        if sigs.len() < threshold {
+            return Err(anyhow!(
+                "Not enough merchant secret keys: need {}, got {}",
+                threshold,
+                sigs.len()
+            ));
+        }
+        Ok(MerchantSig::Multi {
+            config: config.to_witness_data(),
+            sigs,
+        })
+    }
+}
+
+/// Assemble a Spillman Lock witness
+///
+/// Layout:
+/// - EMPTY_WITNESS_ARGS: 16 bytes
+/// - unlock_type: 1 byte
+/// - Merchant part: signature (single-sig) or multisig_config + signatures (multisig)
+/// - User signature: SIGNATURE_SIZE bytes
+pub fn assemble_spillman_witness(unlock_type: u8, merchant: MerchantSig, user_sig: &[u8]) -> Bytes {
+    let mut witness = Vec::with_capacity(
+        EMPTY_WITNESS_ARGS_SIZE + UNLOCK_TYPE_SIZE + merchant.size() + user_sig.len(),
+    );
+    witness.extend_from_slice(&EMPTY_WITNESS_ARGS);
+    witness.push(unlock_type);
+    match merchant {
+        MerchantSig::Single(sig) => witness.extend_from_slice(&sig),
+        MerchantSig::Multi { config, sigs } => {
+            witness.extend_from_slice(&config);
+            for sig in sigs {
+                witness.extend_from_slice(&sig);
+            }
+        }
+    }
+    witness.extend_from_slice(user_sig);
+    Bytes::from(witness)
+}
+
 /// Calculate the size of merchant signature in witness
 ///
 /// Returns:
@@ -68,6 +171,18 @@ pub fn calculate_refund_witness_size(merchant_multisig_config: Option<&MultisigC
     base_size + merchant_sig_size + user_sig_size
 }
 
+/// Calculate the total size of commitment witness
+///
+/// Same layout as the refund witness, with UNLOCK_TYPE_COMMITMENT instead
+pub fn calculate_commitment_witness_size(
+    merchant_multisig_config: Option<&MultisigConfig>,
+) -> usize {
+    EMPTY_WITNESS_ARGS_SIZE
+        + UNLOCK_TYPE_SIZE
+        + calculate_merchant_signature_size(merchant_multisig_config)
+        + SIGNATURE_SIZE
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -87,4 +202,54 @@ mod tests {
             EMPTY_WITNESS_ARGS_SIZE + UNLOCK_TYPE_SIZE + SIGNATURE_SIZE + SIGNATURE_SIZE
         );
     }
+
+    #[test]
+    fn test_assemble_single_sig_witness_matches_manual_layout() {
+        let merchant_sig = [1u8; SIGNATURE_SIZE];
+        let user_sig = [2u8; SIGNATURE_SIZE];
+
+        let manual = [
+            &EMPTY_WITNESS_ARGS[..],
+            &[UNLOCK_TYPE_TIMEOUT][..],
+            &merchant_sig[..],
+            &user_sig[..],
+        ]
+        .concat();
+
+        let witness = assemble_spillman_witness(
+            UNLOCK_TYPE_TIMEOUT,
+            MerchantSig::Single(merchant_sig),
+            &user_sig,
+        );
+        assert_eq!(witness.to_vec(), manual);
+        assert_eq!(witness.len(), calculate_refund_witness_size(None));
+    }
+
+    #[test]
+    fn test_assemble_multisig_witness_matches_manual_layout() {
+        // 2-of-3 multisig config: S | R | M | N | 3 * PubKeyHash
+        let mut config = vec![0u8, 0, 2, 3];
+        config.extend_from_slice(&[7u8; 60]);
+        let sigs = vec![[3u8; SIGNATURE_SIZE], [4u8; SIGNATURE_SIZE]];
+        let user_sig = [5u8; SIGNATURE_SIZE];
+
+        let manual = [
+            &EMPTY_WITNESS_ARGS[..],
+            &[UNLOCK_TYPE_COMMITMENT][..],
+            &config[..],
+            &sigs[0][..],
+            &sigs[1][..],
+            &user_sig[..],
+        ]
+        .concat();
+
+        let merchant = MerchantSig::Multi {
+            config: config.clone(),
+            sigs: sigs.clone(),
+        };
+        assert_eq!(merchant.size(), config.len() + 2 * SIGNATURE_SIZE);
+
+        let witness = assemble_spillman_witness(UNLOCK_TYPE_COMMITMENT, merchant, &user_sig);
+        assert_eq!(witness.to_vec(), manual);
+    }
 }
@@ -0,0 +1,63 @@
+/// xUDT amount encoding shared across tx_builder modules
+///
+/// An xUDT cell stores its amount in the first 16 bytes of cell data as a little-endian
+/// u128; anything after it belongs to xUDT extensions and is not part of the amount.
+use anyhow::{anyhow, Result};
+use ckb_types::bytes::Bytes;
+
+/// Size of the amount at the start of xUDT cell data
+pub const XUDT_AMOUNT_LEN: usize = 16;
+
+/// Amount held by xUDT cell `data`, rejecting data too short to hold one
+pub fn read_xudt_amount(data: &[u8]) -> Result<u128> {
+    let amount: [u8; XUDT_AMOUNT_LEN] = data
+        .get(..XUDT_AMOUNT_LEN)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| {
+            anyhow!(
+                "xUDT cell data is {} bytes, too short for a {}-byte amount",
+                data.len(),
+                XUDT_AMOUNT_LEN
+            )
+        })?;
+    Ok(u128::from_le_bytes(amount))
+}
+
+/// Cell data holding `amount`, for an xUDT output without extension data
+pub fn write_xudt_amount(amount: u128) -> Bytes {
+    Bytes::copy_from_slice(&amount.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_xudt_amount() {
+        let data = write_xudt_amount(1_000_000);
+        assert_eq!(data.len(), XUDT_AMOUNT_LEN);
+        assert_eq!(read_xudt_amount(&data).unwrap(), 1_000_000);
+
+        // Extension data after the amount is ignored
+        let mut extended = write_xudt_amount(u128::MAX).to_vec();
+        extended.extend_from_slice(&[0xff; 4]);
+        assert_eq!(read_xudt_amount(&extended).unwrap(), u128::MAX);
+    }
+
+    #[test]
+    fn test_read_zero_xudt_amount() {
+        assert_eq!(write_xudt_amount(0).as_ref(), &[0u8; XUDT_AMOUNT_LEN]);
+        assert_eq!(read_xudt_amount(&[0u8; XUDT_AMOUNT_LEN]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_read_xudt_amount_rejects_short_data() {
+        let err = read_xudt_amount(&[0x01; 15]).unwrap_err();
+        assert!(
+            err.to_string().contains("15 bytes"),
+            "unexpected error: {}",
+            err
+        );
+        assert!(read_xudt_amount(&[]).is_err());
+    }
+}
@@ -0,0 +1,260 @@
+use anyhow::{anyhow, Result};
+use ckb_jsonrpc_types::Status;
+use ckb_sdk::rpc::CkbRpcClient;
+use ckb_types::{packed::OutPoint, H256};
+use std::time::{Duration, Instant};
+
+use crate::error::ChannelError;
+use crate::utils::rpc;
+
+/// Interval between two `get_transaction` polls while waiting for confirmations
+pub const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// `--wait-confirmations` settings of a broadcasting command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfirmationWait {
+    /// Number of confirmations to wait for
+    pub confirmations: u64,
+    /// Give up after this long
+    pub timeout: Duration,
+}
+
+impl ConfirmationWait {
+    /// Build the settings from the CLI flags (`None` when not waiting)
+    pub fn from_cli(confirmations: Option<u64>, timeout_secs: u64) -> Option<Self> {
+        confirmations.map(|confirmations| Self {
+            confirmations,
+            timeout: Duration::from_secs(timeout_secs),
+        })
+    }
+
+    /// Wait for `tx_hash` according to these settings
+    pub async fn wait(&self, client: &CkbRpcClient, tx_hash: &H256) -> Result<u64> {
+        wait_for_confirmations(client, tx_hash, self.confirmations, self.timeout).await
+    }
+}
+
+/// Where a broadcast transaction stands after one poll
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfirmationState {
+    /// Still in the pool (pending / proposed) or not seen by the node yet
+    Waiting(Status),
+    /// Committed, but with fewer confirmations than required
+    Confirming(u64),
+    /// Committed with at least the required confirmations
+    Confirmed(u64),
+    /// Removed from the pool, it will never be committed
+    Rejected(String),
+}
+
+/// Decide the confirmation state from a `get_transaction` status and the tip block
+///
+/// A transaction committed in the tip block has 1 confirmation.
+pub fn confirmation_state(
+    status: Status,
+    block_number: Option<u64>,
+    reason: Option<String>,
+    tip_block_number: u64,
+    required: u64,
+) -> ConfirmationState {
+    match (status, block_number) {
+        (Status::Committed, Some(block_number)) => {
+            let confirmations = tip_block_number.saturating_sub(block_number) + 1;
+            if confirmations >= required {
+                ConfirmationState::Confirmed(confirmations)
+            } else {
+                ConfirmationState::Confirming(confirmations)
+            }
+        }
+        (Status::Rejected, _) => {
+            ConfirmationState::Rejected(reason.unwrap_or_else(|| "unknown reason".to_string()))
+        }
+        (status, _) => ConfirmationState::Waiting(status),
+    }
+}
+
+/// Poll `get_transaction` until `tx_hash` reaches `confirmations` confirmations
+///
+/// Prints progress on every poll. Fails if the transaction is rejected or `timeout`
+/// elapses first; returns the number of confirmations reached otherwise.
+pub async fn wait_for_confirmations(
+    client: &CkbRpcClient,
+    tx_hash: &H256,
+    confirmations: u64,
+    timeout: Duration,
+) -> Result<u64> {
+    println!(
+        "\n⏳ 等待交易确认 ({} 个确认，超时 {} 秒)...",
+        confirmations,
+        timeout.as_secs()
+    );
+    let started = Instant::now();
+
+    loop {
+        let tx_status = client
+            .get_transaction(tx_hash.clone())
+            .map_err(|e| ChannelError::RpcError(format!("{:?}", e)))?
+            .map(|tx| tx.tx_status);
+        let tip_block_number: u64 = client
+            .get_tip_block_number()
+            .map_err(|e| ChannelError::RpcError(format!("{:?}", e)))?
+            .value();
+        let state = match tx_status {
+            Some(tx_status) => confirmation_state(
+                tx_status.status,
+                tx_status.block_number.map(|number| number.value()),
+                tx_status.reason,
+                tip_block_number,
+                confirmations,
+            ),
+            None => ConfirmationState::Waiting(Status::Unknown),
+        };
+
+        match state {
+            ConfirmationState::Confirmed(reached) => {
+                println!("✓ 交易已确认: {} 个确认", reached);
+                return Ok(reached);
+            }
+            ConfirmationState::Rejected(reason) => {
+                return Err(anyhow!(
+                    "Transaction {:#x} was rejected: {}",
+                    tx_hash,
+                    reason
+                ));
+            }
+            ConfirmationState::Confirming(reached) => {
+                println!("  - 已上链: {}/{} 个确认", reached, confirmations);
+            }
+            ConfirmationState::Waiting(status) => {
+                println!("  - 交易状态: {:?}", status);
+            }
+        }
+
+        if started.elapsed() >= timeout {
+            return Err(anyhow!(
+                "Timed out after {} seconds waiting for {} confirmations of {:#x}",
+                timeout.as_secs(),
+                confirmations,
+                tx_hash
+            ));
+        }
+        tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await;
+    }
+}
+
+/// Whether a channel's funding cell can still be spent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FundingCellStatus {
+    /// Unspent: the refund can still claim it
+    Live,
+    /// Already consumed, by a settled commitment or an earlier refund
+    Spent,
+    /// Not known to the node (funding not committed yet) or the node could not be asked
+    Unknown,
+}
+
+/// Source of `get_live_cell` statuses ("live", "dead", "unknown")
+pub trait LiveCellSource {
+    fn live_cell_status(&self, out_point: &OutPoint) -> Result<String>;
+}
+
+impl LiveCellSource for CkbRpcClient {
+    fn live_cell_status(&self, out_point: &OutPoint) -> Result<String> {
+        let cell = rpc::with_retry("get_live_cell", || {
+            self.get_live_cell(out_point.clone().into(), false)
+        })
+        .map_err(|e| ChannelError::RpcError(format!("{:?}", e)))?;
+        Ok(cell.status)
+    }
+}
+
+/// Ask `client` whether the funding cell at `out_point` is still live
+///
+/// An RPC failure is reported as `Unknown`: the caller decides whether to go on without
+/// the answer.
+pub fn funding_cell_status(
+    client: &impl LiveCellSource,
+    out_point: &OutPoint,
+) -> FundingCellStatus {
+    match client.live_cell_status(out_point).as_deref() {
+        Ok("live") => FundingCellStatus::Live,
+        Ok("dead") => FundingCellStatus::Spent,
+        _ => FundingCellStatus::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::prelude::*;
+
+    /// Answers `get_live_cell` with a fixed status, or fails like an unreachable node
+    struct FixedStatus(Option<&'static str>);
+
+    impl LiveCellSource for FixedStatus {
+        fn live_cell_status(&self, _out_point: &OutPoint) -> Result<String> {
+            self.0
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("connection refused"))
+        }
+    }
+
+    #[test]
+    fn test_funding_cell_status() {
+        let out_point = OutPoint::new(H256([0x11; 32]).pack(), 0);
+        let status = |answer| funding_cell_status(&FixedStatus(answer), &out_point);
+
+        assert_eq!(status(Some("live")), FundingCellStatus::Live);
+        assert_eq!(status(Some("dead")), FundingCellStatus::Spent);
+        assert_eq!(status(Some("unknown")), FundingCellStatus::Unknown);
+        assert_eq!(status(None), FundingCellStatus::Unknown);
+    }
+
+    #[test]
+    fn test_confirmation_state() {
+        // In the pool or unknown to the node: keep waiting
+        assert_eq!(
+            confirmation_state(Status::Pending, None, None, 100, 3),
+            ConfirmationState::Waiting(Status::Pending)
+        );
+        assert_eq!(
+            confirmation_state(Status::Proposed, None, None, 100, 3),
+            ConfirmationState::Waiting(Status::Proposed)
+        );
+        assert_eq!(
+            confirmation_state(Status::Unknown, None, None, 100, 3),
+            ConfirmationState::Waiting(Status::Unknown)
+        );
+
+        // Committed in the tip block counts as one confirmation
+        assert_eq!(
+            confirmation_state(Status::Committed, Some(100), None, 100, 3),
+            ConfirmationState::Confirming(1)
+        );
+        assert_eq!(
+            confirmation_state(Status::Committed, Some(98), None, 100, 3),
+            ConfirmationState::Confirmed(3)
+        );
+        assert_eq!(
+            confirmation_state(Status::Committed, Some(90), None, 100, 3),
+            ConfirmationState::Confirmed(11)
+        );
+        // The tip may lag behind the committing block on a load-balanced node
+        assert_eq!(
+            confirmation_state(Status::Committed, Some(101), None, 100, 1),
+            ConfirmationState::Confirmed(1)
+        );
+
+        // Rejected transactions never confirm
+        assert_eq!(
+            confirmation_state(
+                Status::Rejected,
+                None,
+                Some("Resolve failed".to_string()),
+                100,
+                3
+            ),
+            ConfirmationState::Rejected("Resolve failed".to_string())
+        );
+    }
+}
@@ -0,0 +1,360 @@
+use anyhow::{anyhow, Result};
+use ckb_sdk::Address;
+use ckb_types::{packed::Script, prelude::*, H256};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::error::ChannelError;
+use crate::tx_builder::signing::ARGS_VERSION_BOUND_CELL_DEPS;
+use crate::tx_builder::spillman_lock::build_spillman_lock_script_with_hash;
+use crate::utils::config::Config;
+use crate::utils::crypto::parse_privkey;
+
+/// On-disk format of the channel info file (`set-up --info-format`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum InfoFormat {
+    /// Pretty-printed JSON (channel_info.json)
+    #[default]
+    Json,
+    /// TOML (channel_info.toml)
+    Toml,
+    /// YAML (channel_info.yaml)
+    Yaml,
+}
+
+impl InfoFormat {
+    /// File extension written for this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            InfoFormat::Json => "json",
+            InfoFormat::Toml => "toml",
+            InfoFormat::Yaml => "yaml",
+        }
+    }
+
+    /// Detect the format from a file extension, falling back to JSON
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => InfoFormat::Toml,
+            Some("yaml") | Some("yml") => InfoFormat::Yaml,
+            _ => InfoFormat::Json,
+        }
+    }
+
+    fn serialize(self, info: &ChannelInfo) -> Result<String> {
+        match self {
+            InfoFormat::Json => Ok(serde_json::to_string_pretty(info)?),
+            InfoFormat::Toml => Ok(toml::to_string_pretty(info)?),
+            InfoFormat::Yaml => Ok(serde_yaml::to_string(info)?),
+        }
+    }
+
+    fn deserialize(self, content: &str) -> Result<ChannelInfo> {
+        match self {
+            InfoFormat::Json => Ok(serde_json::from_str(content)?),
+            InfoFormat::Toml => Ok(toml::from_str(content)?),
+            InfoFormat::Yaml => Ok(serde_yaml::from_str(content)?),
+        }
+    }
+}
+
+/// Channel information produced by `set-up` and consumed by `pay` / `refund`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelInfo {
+    pub user_address: String,
+    pub merchant_address: String,
+    pub capacity_ckb: u64,
+    #[allow(dead_code)]
+    pub timeout_epochs: u64, // Deprecated, keeping for backwards compatibility
+    #[allow(dead_code)]
+    pub current_timestamp: u64,
+    pub timeout_timestamp: u64,
+    pub spillman_lock_script_hash: String,
+    pub funding_tx_hash: String,
+    pub funding_output_index: u32,
+    // xUDT fields (optional, only present in xUDT channels)
+    /// Hex-encoded serialized xUDT type script (molecule `Script`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xudt_type_script: Option<String>,
+    /// Funded xUDT amount in smallest unit, stored as string to avoid u128 parsing issues
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xudt_amount: Option<String>,
+    // Machine-readable timeline (optional, absent in files from older versions)
+    /// Unix timestamp (seconds) at which the user can broadcast the refund
+    /// (= timeout_timestamp + the merchant's grace)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refund_available_at: Option<u64>,
+    /// Unix timestamp (seconds) at which set-up built the funding transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub funding_created_at: Option<u64>,
+    /// Whether the merchant contributed to the funding cell (absent in older files)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub co_funded: Option<bool>,
+    /// Spillman Lock args version of the channel (absent in older files, which are version 0)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args_version: Option<u8>,
+}
+
+impl ChannelInfo {
+    /// Whether this channel is backed by an xUDT token
+    pub fn is_xudt(&self) -> bool {
+        self.xudt_type_script.is_some()
+    }
+
+    /// Rebuild the channel's Spillman Lock script from `config`
+    ///
+    /// Derived the way set-up does (user key, merchant lock arg, timeout, configured
+    /// Spillman Lock code) and checked against `spillman_lock_script_hash`: a mismatch
+    /// means the config drifted since set-up, e.g. another user key or contract deployment.
+    pub fn rebuild_spillman_script(&self, config: &Config) -> Result<Script> {
+        let user_privkey =
+            parse_privkey(&config.user.resolve_private_key()?.ok_or_else(|| {
+                anyhow!("User private_key is required to rebuild the Spillman Lock script")
+            })?)?;
+        let user_pubkey = user_privkey.pubkey()?;
+
+        // blake160 of the merchant pubkey or multisig config, as in the merchant address
+        let merchant_address = Address::from_str(&config.merchant.address)
+            .map_err(|e| anyhow!("Failed to parse merchant address: {}", e))?;
+        let merchant_args = Script::from(&merchant_address).args().raw_data();
+        let merchant_lock_arg = merchant_args
+            .get(0..20)
+            .ok_or_else(|| anyhow!("Merchant address args are shorter than 20 bytes"))?;
+
+        // The channel keeps the args version it was set up with, whatever the config says now
+        let mut config = config.clone();
+        config.channel.bind_cell_deps = self.args_version == Some(ARGS_VERSION_BOUND_CELL_DEPS);
+
+        let script = build_spillman_lock_script_with_hash(
+            &config,
+            &user_pubkey,
+            merchant_lock_arg,
+            self.timeout_timestamp,
+        )?;
+
+        let recorded = H256::from_str(self.spillman_lock_script_hash.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid spillman_lock_script_hash in channel info: {}", e))?;
+        let rebuilt: H256 = script.calc_script_hash().unpack();
+        if rebuilt != recorded {
+            return Err(ChannelError::SpillmanScriptMismatch { recorded, rebuilt }.into());
+        }
+        Ok(script)
+    }
+
+    /// Decode the xUDT type script (if any)
+    pub fn xudt_type_script(&self) -> Result<Option<Script>> {
+        match self.xudt_type_script {
+            Some(ref script_hex) => {
+                let script_bytes = hex::decode(script_hex.trim_start_matches("0x"))
+                    .map_err(|e| anyhow!("Invalid xudt_type_script hex: {}", e))?;
+                let script = Script::from_slice(&script_bytes)
+                    .map_err(|e| anyhow!("Invalid xudt_type_script encoding: {}", e))?;
+                Ok(Some(script))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Decode the funded xUDT amount in smallest unit (if any)
+    pub fn xudt_amount(&self) -> Result<Option<u128>> {
+        match self.xudt_amount {
+            Some(ref amount) => {
+                let amount = amount
+                    .parse::<u128>()
+                    .map_err(|e| anyhow!("Invalid xudt_amount '{}': {}", amount, e))?;
+                Ok(Some(amount))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Encode an xUDT type script for storage in `ChannelInfo`
+pub fn encode_xudt_type_script(type_script: &Script) -> String {
+    format!("0x{}", hex::encode(type_script.as_slice()))
+}
+
+/// Write channel information to `<dir>/channel_info.<ext>`, returning the file path
+pub fn write_channel_info(info: &ChannelInfo, dir: &Path, format: InfoFormat) -> Result<PathBuf> {
+    let path = dir.join(format!("channel_info.{}", format.extension()));
+    fs::write(&path, format.serialize(info)?)?;
+    Ok(path)
+}
+
+/// Load channel information, detecting JSON / TOML / YAML by file extension
+pub fn load_channel_info(file_path: &str) -> Result<ChannelInfo> {
+    let content = fs::read_to_string(file_path)
+        .map_err(|e| anyhow!("Failed to read channel info file {}: {}", file_path, e))?;
+
+    let info = InfoFormat::from_path(Path::new(file_path))
+        .deserialize(&content)
+        .map_err(|e| anyhow!("Failed to parse channel info: {}", e))?;
+
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::{bytes::Bytes, core::ScriptHashType, H256};
+
+    fn sample_type_script() -> Script {
+        Script::new_builder()
+            .code_hash(H256([0x50; 32]).pack())
+            .hash_type(ScriptHashType::Data1)
+            .args(Bytes::from(vec![0xab; 32]).pack())
+            .build()
+    }
+
+    fn sample_xudt_channel_info() -> ChannelInfo {
+        ChannelInfo {
+            user_address: "ckt1user".to_string(),
+            merchant_address: "ckt1merchant".to_string(),
+            capacity_ckb: 1000,
+            timeout_epochs: 0,
+            current_timestamp: 1_700_000_000,
+            timeout_timestamp: 1_700_086_400,
+            spillman_lock_script_hash: format!("{:#x}", H256([0x11; 32])),
+            funding_tx_hash: format!("{:#x}", H256([0x22; 32])),
+            funding_output_index: 0,
+            xudt_type_script: Some(encode_xudt_type_script(&sample_type_script())),
+            xudt_amount: Some(u128::MAX.to_string()),
+            refund_available_at: Some(1_700_086_400),
+            funding_created_at: Some(1_700_000_000),
+            co_funded: Some(false),
+            args_version: None,
+        }
+    }
+
+    #[test]
+    fn test_channel_info_xudt_round_trip() {
+        let type_script = sample_type_script();
+        let info = sample_xudt_channel_info();
+
+        let json = serde_json::to_string_pretty(&info).unwrap();
+        let decoded: ChannelInfo = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(decoded, info);
+        assert!(decoded.is_xudt());
+        assert_eq!(decoded.xudt_type_script().unwrap(), Some(type_script));
+        assert_eq!(decoded.xudt_amount().unwrap(), Some(u128::MAX));
+    }
+
+    #[test]
+    fn test_rebuild_spillman_script_detects_config_drift() {
+        use ckb_sdk::{AddressPayload, NetworkType};
+        use ckb_types::H160;
+
+        let merchant = Address::new(
+            NetworkType::Testnet,
+            AddressPayload::from_pubkey_hash(H160([0x77; 20])),
+            true,
+        );
+        let config: Config = toml::from_str(&format!(
+            r#"
+[network]
+rpc_url = "http://127.0.0.1:8114"
+
+[user]
+private_key = "0x{user_key}"
+address = "{merchant}"
+
+[merchant]
+private_key = "0x{merchant_key}"
+address = "{merchant}"
+
+[channel]
+capacity_ckb = 1000
+timeout_timestamp = 1700086400
+tx_fee_shannon = 100000
+
+[spillman_lock]
+code_hash = "0x{spillman}"
+hash_type = "type"
+tx_hash = "0x{spillman}"
+index = 0
+
+[auth]
+tx_hash = "0x{spillman}"
+index = 1
+"#,
+            user_key = "01".repeat(32),
+            merchant_key = "02".repeat(32),
+            spillman = "11".repeat(32),
+        ))
+        .unwrap();
+
+        // The script set-up would have built for this config
+        let mut info = sample_xudt_channel_info();
+        let user_pubkey = parse_privkey(&"01".repeat(32)).unwrap().pubkey().unwrap();
+        let script = build_spillman_lock_script_with_hash(
+            &config,
+            &user_pubkey,
+            &[0x77; 20],
+            info.timeout_timestamp,
+        )
+        .unwrap();
+        info.spillman_lock_script_hash = format!("{:#x}", script.calc_script_hash());
+        assert_eq!(info.rebuild_spillman_script(&config).unwrap(), script);
+
+        // A tampered hash is reported as drift
+        info.spillman_lock_script_hash = format!("{:#x}", H256([0x99; 32]));
+        let err = info.rebuild_spillman_script(&config).unwrap_err();
+        match err.downcast_ref::<ChannelError>() {
+            Some(ChannelError::SpillmanScriptMismatch { recorded, rebuilt }) => {
+                assert_eq!(*recorded, H256([0x99; 32]));
+                assert_eq!(rebuilt.pack(), script.calc_script_hash());
+            }
+            other => panic!("expected SpillmanScriptMismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_channel_info_ckb_only_omits_xudt_fields() {
+        let json = r#"{
+            "user_address": "ckt1user",
+            "merchant_address": "ckt1merchant",
+            "capacity_ckb": 1000,
+            "timeout_epochs": 0,
+            "current_timestamp": 1700000000,
+            "timeout_timestamp": 1700086400,
+            "spillman_lock_script_hash": "0x00",
+            "funding_tx_hash": "0x00",
+            "funding_output_index": 0
+        }"#;
+
+        let info: ChannelInfo = serde_json::from_str(json).unwrap();
+        assert!(!info.is_xudt());
+        assert_eq!(info.xudt_type_script().unwrap(), None);
+        assert_eq!(info.xudt_amount().unwrap(), None);
+
+        let encoded = serde_json::to_string(&info).unwrap();
+        assert!(!encoded.contains("xudt_type_script"));
+        assert!(!encoded.contains("xudt_amount"));
+    }
+
+    #[test]
+    fn test_channel_info_round_trip_in_every_format() {
+        let info = sample_xudt_channel_info();
+        let dir =
+            std::env::temp_dir().join(format!("spillman_channel_info_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        for format in [InfoFormat::Json, InfoFormat::Toml, InfoFormat::Yaml] {
+            let path = write_channel_info(&info, &dir, format).unwrap();
+            assert_eq!(
+                path.file_name().unwrap().to_str().unwrap(),
+                format!("channel_info.{}", format.extension())
+            );
+            assert_eq!(InfoFormat::from_path(&path), format);
+
+            let loaded = load_channel_info(path.to_str().unwrap()).unwrap();
+            assert_eq!(loaded, info, "{:?} round trip", format);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use ckb_jsonrpc_types::TransactionView;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Format of the transaction file handed to `ckb-cli tx send` (`--emit`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TxEmitFormat {
+    /// Only the TransactionView JSON read back by spillman-cli itself
+    #[default]
+    Json,
+    /// Additionally write a copy in ckb-cli's `tx` file schema
+    CkbCli,
+}
+
+/// Whether saved transactions also get a ckb-cli copy (`--emit ckb-cli`)
+static EMIT_CKB_CLI: AtomicBool = AtomicBool::new(false);
+
+/// Select the format used by [`ckb_cli_tx_file`]
+pub fn set_emit_format(format: TxEmitFormat) {
+    EMIT_CKB_CLI.store(format == TxEmitFormat::CkbCli, Ordering::Relaxed);
+}
+
+/// Convert a transaction into ckb-cli's `tx` file schema
+///
+/// The transaction is already fully signed by the time it is saved, so
+/// `multisig_configs` and `signatures` are left empty.
+pub fn to_ckb_cli_tx(tx: &TransactionView) -> Value {
+    json!({
+        "transaction": tx.inner,
+        "multisig_configs": {},
+        "signatures": {},
+    })
+}
+
+/// Path of the ckb-cli copy next to a saved transaction: `x.json` -> `x.ckb-cli.json`
+fn ckb_cli_path(tx_path: &Path) -> PathBuf {
+    let stem = tx_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    tx_path.with_file_name(format!("{}.ckb-cli.json", stem))
+}
+
+/// File to pass to `ckb-cli tx send --tx-file` for a transaction saved at `tx_path`
+///
+/// With `--emit ckb-cli` the ckb-cli copy is written next to `tx_path` and its
+/// path returned; otherwise `tx_path` is returned unchanged.
+pub fn ckb_cli_tx_file(tx_path: impl AsRef<Path>) -> Result<PathBuf> {
+    let tx_path = tx_path.as_ref();
+    if !EMIT_CKB_CLI.load(Ordering::Relaxed) {
+        return Ok(tx_path.to_path_buf());
+    }
+
+    let tx: TransactionView = serde_json::from_str(
+        &std::fs::read_to_string(tx_path)
+            .with_context(|| format!("reading {}", tx_path.display()))?,
+    )
+    .with_context(|| format!("parsing {}", tx_path.display()))?;
+    let out_path = ckb_cli_path(tx_path);
+    std::fs::write(
+        &out_path,
+        serde_json::to_string_pretty(&to_ckb_cli_tx(&tx))?,
+    )
+    .with_context(|| format!("writing {}", out_path.display()))?;
+    println!("📄 ckb-cli 格式交易已保存: {}", out_path.display());
+    Ok(out_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::{core::TransactionBuilder, prelude::*};
+
+    #[test]
+    fn test_to_ckb_cli_tx_top_level_keys() {
+        let tx = TransactionBuilder::default().build();
+        let value = to_ckb_cli_tx(&TransactionView::from(tx.clone()));
+
+        let mut keys: Vec<_> = value.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, ["multisig_configs", "signatures", "transaction"]);
+
+        let inner: ckb_jsonrpc_types::Transaction =
+            serde_json::from_value(value["transaction"].clone()).unwrap();
+        let packed: ckb_types::packed::Transaction = inner.into();
+        assert_eq!(packed.as_slice(), tx.data().as_slice());
+    }
+
+    #[test]
+    fn test_ckb_cli_path() {
+        assert_eq!(
+            ckb_cli_path(Path::new("secrets/refund_tx_1.json")),
+            PathBuf::from("secrets/refund_tx_1.ckb-cli.json")
+        );
+    }
+}
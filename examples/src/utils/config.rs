@@ -1,22 +1,150 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+use ckb_sdk::{
+    constants::{MultisigScript, SIGHASH_TYPE_HASH},
+    util::blake160,
+    Address, AddressPayload, NetworkType, ScriptId,
+};
+use ckb_types::{
+    bytes::Bytes,
+    core::{DepType, ScriptHashType},
+    packed::{CellDep, OutPoint, Script},
+    prelude::*,
+    H256,
+};
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::{fs, str::FromStr};
+
+use crate::tx_builder::signing::{ARGS_VERSION_BOUND_CELL_DEPS, ARGS_VERSION_DETACHED_CELL_DEPS};
+use crate::utils::{fee::DEFAULT_FEE_RATE, rpc};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
+    /// `network = "testnet"` or a `[network]` table; see `resolve_network_defaults`
+    #[serde(default)]
     pub network: NetworkConfig,
     pub user: KeyConfig,
     pub merchant: KeyConfig,
     pub channel: ChannelConfig,
+    #[serde(default)]
     pub spillman_lock: SpillmanLockConfig,
+    #[serde(default)]
     pub auth: AuthConfig,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub usdi: Option<XudtConfig>,
+    #[serde(default)]
+    pub system_scripts: SystemScriptsConfig,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(from = "NetworkConfigToml")]
 pub struct NetworkConfig {
+    /// Public network whose well-known values fill the unset fields
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<NetworkName>,
     pub rpc_url: String,
+    /// Per-request RPC timeout in seconds (default 30, `--rpc-timeout` overrides it)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_timeout_secs: Option<u64>,
+    /// Retries of idempotent RPC reads with exponential backoff (default 2)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_retries: Option<u32>,
+}
+
+/// Public CKB networks with built-in defaults
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkName {
+    Testnet,
+    Mainnet,
+}
+
+/// `network` is either a bare network name or a `[network]` table
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NetworkConfigToml {
+    Name(NetworkName),
+    Table {
+        #[serde(default)]
+        name: Option<NetworkName>,
+        #[serde(default)]
+        rpc_url: String,
+        #[serde(default)]
+        rpc_timeout_secs: Option<u64>,
+        #[serde(default)]
+        rpc_retries: Option<u32>,
+    },
+}
+
+impl From<NetworkConfigToml> for NetworkConfig {
+    fn from(network: NetworkConfigToml) -> Self {
+        match network {
+            NetworkConfigToml::Name(name) => Self {
+                name: Some(name),
+                ..Default::default()
+            },
+            NetworkConfigToml::Table {
+                name,
+                rpc_url,
+                rpc_timeout_secs,
+                rpc_retries,
+            } => Self {
+                name,
+                rpc_url,
+                rpc_timeout_secs,
+                rpc_retries,
+            },
+        }
+    }
+}
+
+/// Spillman Lock deployment on testnet (type id code hash, cell at index 0)
+const TESTNET_SPILLMAN_LOCK_CODE_HASH: &str =
+    "0x41fa54ee27a517db245b014116fe2baff1dcb639d42fc14be43c315ea3cef9f2";
+const TESTNET_SPILLMAN_LOCK_TX_HASH: &str =
+    "0x3ad0f4b3f08927b79d8a94bbad5f265694e969ab2ddfde178893e1c6a954dd5f";
+/// ckb-auth deployment on testnet (cell at index 0)
+const TESTNET_AUTH_TX_HASH: &str =
+    "0x3f0fe5376b847b0c286184bb59d38765841e135d7d64f87b2bf7014c6316eee2";
+
+impl NetworkName {
+    /// Public RPC endpoint
+    pub fn rpc_url(self) -> &'static str {
+        match self {
+            NetworkName::Testnet => "https://testnet.ckb.dev",
+            NetworkName::Mainnet => "https://mainnet.ckb.dev",
+        }
+    }
+
+    /// Published Spillman Lock cell: (code_hash, hash_type, tx_hash, index)
+    pub fn spillman_lock_deployment(
+        self,
+    ) -> Option<(&'static str, &'static str, &'static str, u32)> {
+        match self {
+            NetworkName::Testnet => Some((
+                TESTNET_SPILLMAN_LOCK_CODE_HASH,
+                "type",
+                TESTNET_SPILLMAN_LOCK_TX_HASH,
+                0,
+            )),
+            NetworkName::Mainnet => None,
+        }
+    }
+
+    /// Address network type
+    pub fn network_type(self) -> NetworkType {
+        match self {
+            NetworkName::Testnet => NetworkType::Testnet,
+            NetworkName::Mainnet => NetworkType::Mainnet,
+        }
+    }
+
+    /// Published ckb-auth cell: (tx_hash, index)
+    pub fn auth_deployment(self) -> Option<(&'static str, u32)> {
+        match self {
+            NetworkName::Testnet => Some((TESTNET_AUTH_TX_HASH, 0)),
+            NetworkName::Mainnet => None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -25,6 +153,13 @@ pub struct KeyConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub private_key: Option<String>,
 
+    // 单签私钥的外部来源，优先级：private_key_file > private_key_env > private_key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_key_env: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_key_file: Option<String>,
+
     // 多签字段（新增，都是可选的）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub multisig_threshold: Option<u8>,
@@ -46,20 +181,114 @@ pub struct ChannelConfig {
     pub timeout_epochs: u64, // Deprecated, keeping for backwards compatibility
     pub timeout_timestamp: u64,
     pub tx_fee_shannon: u64,
+    /// Minimum lead time (seconds) between now and timeout_timestamp at set-up
+    #[serde(default = "default_min_timeout_seconds")]
+    pub min_timeout_seconds: u64,
+    /// `pay` refuses to pay within this many seconds of timeout unless forced
+    #[serde(default = "default_timeout_warn_seconds")]
+    pub timeout_warn_seconds: u64,
+    /// Fee rate floor (shannon/KB) below which built transactions are warned about
+    #[serde(default = "default_min_fee_rate")]
+    pub min_fee_rate: u64,
+    /// Create version 1 channels whose signatures also cover the cell deps
+    #[serde(default)]
+    pub bind_cell_deps: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+impl ChannelConfig {
+    /// Spillman Lock args version of the channels set up with this config
+    pub fn args_version(&self) -> u8 {
+        if self.bind_cell_deps {
+            ARGS_VERSION_BOUND_CELL_DEPS
+        } else {
+            ARGS_VERSION_DETACHED_CELL_DEPS
+        }
+    }
+}
+
+/// Default minimum channel lead time: 20 minutes
+pub const DEFAULT_MIN_TIMEOUT_SECONDS: u64 = 1200;
+
+/// Default near-timeout payment warning window: 1 hour
+pub const DEFAULT_TIMEOUT_WARN_SECONDS: u64 = 3600;
+
+fn default_min_timeout_seconds() -> u64 {
+    DEFAULT_MIN_TIMEOUT_SECONDS
+}
+
+fn default_timeout_warn_seconds() -> u64 {
+    DEFAULT_TIMEOUT_WARN_SECONDS
+}
+
+fn default_min_fee_rate() -> u64 {
+    DEFAULT_FEE_RATE
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
 pub struct SpillmanLockConfig {
     pub code_hash: String,
     pub hash_type: String,
     pub tx_hash: String,
     pub index: u32,
+    /// Local copy of the deployed binary, used by `verify`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary_path: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
 pub struct AuthConfig {
     pub tx_hash: String,
     pub index: u32,
+    /// Local copy of the deployed binary, used by `verify`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary_path: Option<String>,
+}
+
+/// System script code hashes the Spillman Lock expects for user / merchant outputs
+///
+/// Unset fields default to the mainnet/testnet values. A dev chain with different
+/// system scripts sets them here; the contract must then be rebuilt with the same
+/// hashes (see `SPILLMAN_SECP256K1_*` in contracts/spillman-lock/build.rs).
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct SystemScriptsConfig {
+    /// secp256k1_blake160_sighash_all (hash_type = type)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secp256k1_code_hash: Option<String>,
+    /// secp256k1_blake160_multisig_all Legacy (hash_type = type)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multisig_legacy_code_hash: Option<String>,
+    /// secp256k1_blake160_multisig_all V2 (hash_type = data1)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multisig_v2_code_hash: Option<String>,
+}
+
+impl SystemScriptsConfig {
+    /// Script id of the single-sig sighash lock
+    pub fn sighash_script_id(&self) -> Result<ScriptId> {
+        let code_hash = Self::code_hash_or(&self.secp256k1_code_hash, SIGHASH_TYPE_HASH)?;
+        Ok(ScriptId::new_type(code_hash))
+    }
+
+    /// Script id of a multisig lock, keeping the hash_type of `multisig_script`
+    pub fn multisig_script_id(&self, multisig_script: MultisigScript) -> Result<ScriptId> {
+        let default = multisig_script.script_id();
+        let configured = match multisig_script {
+            MultisigScript::Legacy => &self.multisig_legacy_code_hash,
+            MultisigScript::V2 => &self.multisig_v2_code_hash,
+        };
+        let code_hash = Self::code_hash_or(configured, default.code_hash)?;
+        Ok(ScriptId::new(code_hash, default.hash_type))
+    }
+
+    fn code_hash_or(configured: &Option<String>, default: H256) -> Result<H256> {
+        match configured {
+            Some(code_hash) => H256::from_str(code_hash.trim_start_matches("0x"))
+                .map_err(|e| anyhow!("Invalid system script code hash '{}': {}", code_hash, e)),
+            None => Ok(default),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -72,20 +301,102 @@ pub struct XudtConfig {
     pub decimal: u8,
 }
 
+impl XudtConfig {
+    /// Parse every field once into the typed values the builders use
+    ///
+    /// Called by `Config::validate`, so a malformed `[usdi]` section fails at load time.
+    /// The type script uses hash type `type`, as the funding cell is built.
+    pub fn validate(&self) -> Result<ResolvedXudt> {
+        let code_hash = H256::from_str(self.code_hash.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid usdi.code_hash '{}': {}", self.code_hash, e))?;
+        let args = hex::decode(self.args.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid usdi.args '{}': {}", self.args, e))?;
+        let tx_hash = H256::from_str(self.tx_hash.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid usdi.tx_hash '{}': {}", self.tx_hash, e))?;
+        // u128 amounts: 10^38 is the largest power of ten that fits
+        if self.decimal > 38 {
+            return Err(anyhow!(
+                "Invalid usdi.decimal {}: must be at most 38",
+                self.decimal
+            ));
+        }
+
+        let type_script = Script::new_builder()
+            .code_hash(code_hash.pack())
+            .hash_type(ScriptHashType::Type)
+            .args(Bytes::from(args).pack())
+            .build();
+        let cell_dep = CellDep::new_builder()
+            .out_point(
+                OutPoint::new_builder()
+                    .tx_hash(tx_hash.pack())
+                    .index(self.index)
+                    .build(),
+            )
+            .dep_type(DepType::Code)
+            .build();
+        Ok(ResolvedXudt {
+            type_script,
+            cell_dep,
+            decimal: self.decimal,
+        })
+    }
+}
+
+/// A validated `[usdi]` section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedXudt {
+    /// xUDT type script of the configured token
+    pub type_script: Script,
+    /// Code cell dep of the xUDT script
+    pub cell_dep: CellDep,
+    pub decimal: u8,
+}
+
 impl KeyConfig {
     /// 判断是否为多签配置
     pub fn is_multisig(&self) -> bool {
         self.private_keys.is_some() && self.multisig_threshold.is_some()
     }
 
+    /// 是否配置了单签私钥（inline、环境变量或文件）
+    pub fn has_private_key(&self) -> bool {
+        self.private_key.is_some()
+            || self.private_key_env.is_some()
+            || self.private_key_file.is_some()
+    }
+
+    /// 解析单签私钥
+    ///
+    /// Sources are tried in order `private_key_file` > `private_key_env` > `private_key`.
+    /// A configured file must be readable; an unset variable falls back to the inline key.
+    /// Returns `None` when no single-sig key is configured at all.
+    pub fn resolve_private_key(&self) -> Result<Option<String>> {
+        if let Some(path) = &self.private_key_file {
+            let key = std::fs::read_to_string(path)
+                .with_context(|| format!("reading private_key_file {}", path))?;
+            return Ok(Some(key.trim().to_string()));
+        }
+        if let Some(var) = &self.private_key_env {
+            match std::env::var(var) {
+                Ok(key) => return Ok(Some(key.trim().to_string())),
+                Err(_) if self.private_key.is_none() => {
+                    return Err(anyhow!("private_key_env {} is not set", var));
+                }
+                Err(_) => {}
+            }
+        }
+        Ok(self.private_key.clone())
+    }
+
     /// 获取所有密钥（兼容单签和多签）
     pub fn get_secret_keys(&self) -> Result<Vec<secp256k1::SecretKey>> {
         if let Some(keys) = &self.private_keys {
             // 多签模式
             keys.iter().map(|k| Self::parse_secret_key(k)).collect()
-        } else if let Some(key) = &self.private_key {
+        } else if let Some(key) = self.resolve_private_key()? {
             // 单签模式（向后兼容）
-            Ok(vec![Self::parse_secret_key(key)?])
+            Ok(vec![Self::parse_secret_key(&key)?])
         } else {
             Err(anyhow!(
                 "Neither private_key nor private_keys is configured"
@@ -105,15 +416,15 @@ impl KeyConfig {
     /// 验证配置的合法性
     pub fn validate(&self, name: &str) -> Result<()> {
         // 检查是否至少有一种配置
-        if self.private_key.is_none() && self.private_keys.is_none() {
+        if !self.has_private_key() && self.private_keys.is_none() {
             return Err(anyhow!(
-                "{}: must specify either private_key or private_keys",
+                "{}: must specify either private_key (or private_key_env / private_key_file) or private_keys",
                 name
             ));
         }
 
         // 检查不能同时配置两种
-        if self.private_key.is_some() && self.private_keys.is_some() {
+        if self.has_private_key() && self.private_keys.is_some() {
             return Err(anyhow!(
                 "{}: cannot specify both private_key and private_keys",
                 name
@@ -151,6 +462,51 @@ impl KeyConfig {
         Ok(())
     }
 
+    /// 验证单签地址与私钥匹配
+    ///
+    /// `address` must be the sighash address of `private_key`. The address is derived for
+    /// `network` (or the configured address's own network when unset) with the configured
+    /// sighash script, and compared as a lock script so short and full formats both pass.
+    /// Multisig keys are not checked, nor are keys from an env var or file that do not
+    /// resolve yet: those are reported when the key is used.
+    pub fn validate_address(
+        &self,
+        name: &str,
+        network: Option<NetworkName>,
+        sighash_script_id: &ScriptId,
+    ) -> Result<()> {
+        let Ok(Some(private_key)) = self.resolve_private_key() else {
+            return Ok(());
+        };
+
+        let secret_key = Self::parse_secret_key(&private_key)
+            .map_err(|e| anyhow!("{}: invalid private_key: {}", name, e))?;
+        let pubkey =
+            secp256k1::PublicKey::from_secret_key(&secp256k1::Secp256k1::new(), &secret_key);
+        let configured = Address::from_str(&self.address)
+            .map_err(|e| anyhow!("{}: invalid address '{}': {}", name, self.address, e))?;
+
+        let network_type = network.map_or(configured.network(), NetworkName::network_type);
+        let payload = AddressPayload::new_full(
+            sighash_script_id.hash_type,
+            sighash_script_id.code_hash.pack(),
+            blake160(&pubkey.serialize()).as_bytes().to_vec().into(),
+        );
+        let derived = Address::new(network_type, payload, true);
+
+        if configured.network() != network_type
+            || Script::from(&configured) != Script::from(&derived)
+        {
+            return Err(anyhow!(
+                "{}: address {} does not match private_key, which derives {}",
+                name,
+                self.address,
+                derived
+            ));
+        }
+        Ok(())
+    }
+
     /// 解析私钥字符串
     fn parse_secret_key(key_str: &str) -> Result<secp256k1::SecretKey> {
         let key_hex = key_str.trim_start_matches("0x");
@@ -164,18 +520,385 @@ impl Config {
     pub fn validate(&self) -> Result<()> {
         self.user.validate("user")?;
         self.merchant.validate("merchant")?;
+        if let Some(usdi) = &self.usdi {
+            usdi.validate()?;
+        }
+
+        // Fields a network preset may fill in, required once defaults are resolved
+        let required = [
+            ("network.rpc_url", &self.network.rpc_url),
+            ("spillman_lock.code_hash", &self.spillman_lock.code_hash),
+            ("spillman_lock.hash_type", &self.spillman_lock.hash_type),
+            ("spillman_lock.tx_hash", &self.spillman_lock.tx_hash),
+            ("auth.tx_hash", &self.auth.tx_hash),
+        ];
+        for (field, value) in required {
+            if value.is_empty() {
+                return Err(anyhow!(
+                    "{} is required (set it, or network = \"testnet\" for the published defaults)",
+                    field
+                ));
+            }
+        }
         Ok(())
     }
+
+    /// 验证单签地址与私钥匹配（用户和单签商户）
+    pub fn validate_addresses(&self) -> Result<()> {
+        let sighash_script_id = self.system_scripts.sighash_script_id()?;
+        self.user
+            .validate_address("user", self.network.name, &sighash_script_id)?;
+        self.merchant
+            .validate_address("merchant", self.network.name, &sighash_script_id)
+    }
+
+    /// Fill unset fields from the well-known values of `network.name`
+    ///
+    /// Explicit fields always win. A cell dep is one unit: the preset `tx_hash` and
+    /// `index` are only used when `tx_hash` is unset. Networks without a published
+    /// Spillman Lock / auth deployment leave those sections to the config.
+    pub fn resolve_network_defaults(&mut self) {
+        let Some(name) = self.network.name else {
+            return;
+        };
+
+        if self.network.rpc_url.is_empty() {
+            self.network.rpc_url = name.rpc_url().to_string();
+        }
+
+        // secp256k1 system scripts are the same on testnet and mainnet
+        let scripts = &mut self.system_scripts;
+        scripts
+            .secp256k1_code_hash
+            .get_or_insert_with(|| format!("{:#x}", SIGHASH_TYPE_HASH));
+        scripts
+            .multisig_legacy_code_hash
+            .get_or_insert_with(|| format!("{:#x}", MultisigScript::Legacy.script_id().code_hash));
+        scripts
+            .multisig_v2_code_hash
+            .get_or_insert_with(|| format!("{:#x}", MultisigScript::V2.script_id().code_hash));
+
+        if let Some((code_hash, hash_type, tx_hash, index)) = name.spillman_lock_deployment() {
+            let spillman_lock = &mut self.spillman_lock;
+            if spillman_lock.code_hash.is_empty() {
+                spillman_lock.code_hash = code_hash.to_string();
+            }
+            if spillman_lock.hash_type.is_empty() {
+                spillman_lock.hash_type = hash_type.to_string();
+            }
+            if spillman_lock.tx_hash.is_empty() {
+                spillman_lock.tx_hash = tx_hash.to_string();
+                spillman_lock.index = index;
+            }
+        }
+
+        if let Some((tx_hash, index)) = name.auth_deployment() {
+            if self.auth.tx_hash.is_empty() {
+                self.auth.tx_hash = tx_hash.to_string();
+                self.auth.index = index;
+            }
+        }
+    }
 }
 
 /// Load configuration from specified path
 pub fn load_config(config_path: &str) -> Result<Config> {
     let config_str = fs::read_to_string(config_path)
         .map_err(|_| anyhow!("Failed to read config file: {}", config_path))?;
-    let config: Config = toml::from_str(&config_str)?;
+    let mut config: Config = toml::from_str(&config_str)?;
+    config.resolve_network_defaults();
 
     // 验证配置
     config.validate()?;
+    config.validate_addresses()?;
+    rpc::configure(&config.network);
 
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYS_AND_CHANNEL: &str = r#"
+[user]
+private_key = "01"
+address = "ckt1-user"
+
+[merchant]
+private_key = "02"
+address = "ckt1-merchant"
+
+[channel]
+capacity_ckb = 1000
+timeout_timestamp = 1763367827
+tx_fee_shannon = 100000
+"#;
+
+    fn resolve(toml_str: &str) -> Config {
+        let mut config: Config = toml::from_str(toml_str).unwrap();
+        config.resolve_network_defaults();
+        config
+    }
+
+    #[test]
+    fn test_testnet_preset_fills_defaults() {
+        let config = resolve(&format!("network = \"testnet\"\n{}", KEYS_AND_CHANNEL));
+        config.validate().unwrap();
+
+        assert_eq!(config.network.name, Some(NetworkName::Testnet));
+        assert_eq!(config.network.rpc_url, "https://testnet.ckb.dev");
+        assert_eq!(
+            config.spillman_lock.code_hash,
+            TESTNET_SPILLMAN_LOCK_CODE_HASH
+        );
+        assert_eq!(config.spillman_lock.hash_type, "type");
+        assert_eq!(config.spillman_lock.tx_hash, TESTNET_SPILLMAN_LOCK_TX_HASH);
+        assert_eq!(config.auth.tx_hash, TESTNET_AUTH_TX_HASH);
+        assert_eq!(
+            config.system_scripts.sighash_script_id().unwrap(),
+            ScriptId::new_type(SIGHASH_TYPE_HASH)
+        );
+        assert_eq!(
+            config
+                .system_scripts
+                .multisig_script_id(MultisigScript::V2)
+                .unwrap(),
+            MultisigScript::V2.script_id()
+        );
+    }
+
+    #[test]
+    fn test_explicit_fields_override_network_preset() {
+        let config = resolve(&format!(
+            r#"
+[network]
+name = "testnet"
+rpc_url = "http://127.0.0.1:8114"
+{}
+[spillman_lock]
+tx_hash = "0x{}"
+index = 2
+
+[system_scripts]
+secp256k1_code_hash = "0x{}"
+"#,
+            KEYS_AND_CHANNEL,
+            "11".repeat(32),
+            "22".repeat(32)
+        ));
+        config.validate().unwrap();
+
+        assert_eq!(config.network.rpc_url, "http://127.0.0.1:8114");
+        assert_eq!(config.network.rpc_timeout_secs, None);
+        assert_eq!(
+            config.spillman_lock.tx_hash,
+            format!("0x{}", "11".repeat(32))
+        );
+        assert_eq!(config.spillman_lock.index, 2);
+        // Unset fields of the section still come from the preset
+        assert_eq!(
+            config.spillman_lock.code_hash,
+            TESTNET_SPILLMAN_LOCK_CODE_HASH
+        );
+        assert_eq!(
+            config.system_scripts.secp256k1_code_hash,
+            Some(format!("0x{}", "22".repeat(32)))
+        );
+
+        // Mainnet has no published Spillman Lock: the deployment must be configured
+        let config = resolve(&format!("network = \"mainnet\"\n{}", KEYS_AND_CHANNEL));
+        assert_eq!(config.network.rpc_url, "https://mainnet.ckb.dev");
+        let err = config.validate().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("spillman_lock.code_hash is required"));
+    }
+
+    #[test]
+    fn test_validate_addresses_matches_private_keys() {
+        let user_key = "11".repeat(32);
+        let merchant_key = "22".repeat(32);
+        let sighash_address = |key: &str| {
+            let secret_key = KeyConfig::parse_secret_key(key).unwrap();
+            let pubkey =
+                secp256k1::PublicKey::from_secret_key(&secp256k1::Secp256k1::new(), &secret_key);
+            Address::new(
+                NetworkType::Testnet,
+                AddressPayload::from_pubkey(&pubkey),
+                true,
+            )
+            .to_string()
+        };
+        let config_with = |user_address: &str| {
+            resolve(&format!(
+                r#"
+network = "testnet"
+
+[user]
+private_key = "{}"
+address = "{}"
+
+[merchant]
+private_key = "{}"
+address = "{}"
+
+[channel]
+capacity_ckb = 1000
+timeout_timestamp = 1763367827
+tx_fee_shannon = 100000
+"#,
+                user_key,
+                user_address,
+                merchant_key,
+                sighash_address(&merchant_key)
+            ))
+        };
+
+        config_with(&sighash_address(&user_key))
+            .validate_addresses()
+            .unwrap();
+
+        // The merchant's address configured for the user key is rejected with both values
+        let merchant_address = sighash_address(&merchant_key);
+        let err = config_with(&merchant_address)
+            .validate_addresses()
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("user: address"), "{}", err);
+        assert!(err.contains(&merchant_address), "{}", err);
+        assert!(err.contains(&sighash_address(&user_key)), "{}", err);
+    }
+
+    #[test]
+    fn test_usdi_validate_resolves_typed_values() {
+        let usdi = XudtConfig {
+            code_hash: format!("0x{}", "50".repeat(32)),
+            hash_type: "type".to_string(),
+            args: format!("0x{}", "ab".repeat(32)),
+            tx_hash: format!("0x{}", "51".repeat(32)),
+            index: 3,
+            decimal: 6,
+        };
+        let xudt = usdi.validate().unwrap();
+        assert_eq!(xudt.type_script.code_hash(), H256([0x50; 32]).pack());
+        assert_eq!(xudt.type_script.hash_type(), ScriptHashType::Type.into());
+        assert_eq!(xudt.type_script.args().raw_data().to_vec(), vec![0xab; 32]);
+        assert_eq!(
+            xudt.cell_dep.out_point(),
+            OutPoint::new(H256([0x51; 32]).pack(), 3)
+        );
+        assert_eq!(xudt.cell_dep.dep_type(), DepType::Code.into());
+        assert_eq!(xudt.decimal, 6);
+
+        // Each malformed field is named in the error
+        let malformed = [
+            XudtConfig {
+                code_hash: "0x1234".to_string(),
+                ..usdi.clone()
+            },
+            XudtConfig {
+                args: "0xzz".to_string(),
+                ..usdi.clone()
+            },
+            XudtConfig {
+                tx_hash: "not-a-hash".to_string(),
+                ..usdi.clone()
+            },
+            XudtConfig {
+                decimal: 39,
+                ..usdi.clone()
+            },
+        ];
+        for (usdi, field) in malformed
+            .iter()
+            .zip(["code_hash", "args", "tx_hash", "decimal"])
+        {
+            let err = usdi.validate().unwrap_err();
+            assert!(
+                err.to_string().contains(&format!("usdi.{}", field)),
+                "{}",
+                err
+            );
+        }
+
+        // A malformed [usdi] section fails when the config is validated
+        let mut config = resolve(&format!("network = \"testnet\"\n{}", KEYS_AND_CHANNEL));
+        config.usdi = Some(malformed[0].clone());
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("usdi.code_hash"), "{}", err);
+    }
+
+    #[test]
+    fn test_private_key_sources_and_precedence() {
+        let key = |toml_str: &str| -> KeyConfig {
+            toml::from_str(&format!("{}\naddress = \"ckt1-user\"", toml_str)).unwrap()
+        };
+        let var = format!("SPILLMAN_TEST_KEY_{}", std::process::id());
+        let missing_var = format!("SPILLMAN_TEST_MISSING_KEY_{}", std::process::id());
+        std::env::set_var(&var, "0x02\n");
+        let path = std::env::temp_dir().join(format!("spillman_key_{}", std::process::id()));
+        let file_key = "03".repeat(32);
+        std::fs::write(&path, format!("{}\n", file_key)).unwrap();
+        let path = path.to_str().unwrap();
+
+        // Each source on its own
+        let inline = key("private_key = \"01\"");
+        assert_eq!(inline.resolve_private_key().unwrap().as_deref(), Some("01"));
+        let env = key(&format!("private_key_env = \"{}\"", var));
+        assert_eq!(env.resolve_private_key().unwrap().as_deref(), Some("0x02"));
+        let file = key(&format!("private_key_file = \"{}\"", path));
+        assert_eq!(
+            file.resolve_private_key().unwrap().as_deref(),
+            Some(file_key.as_str())
+        );
+        assert_eq!(file.get_secret_keys().unwrap().len(), 1);
+        file.validate("user").unwrap();
+
+        // file > env > inline
+        let all = key(&format!(
+            "private_key = \"01\"\nprivate_key_env = \"{}\"\nprivate_key_file = \"{}\"",
+            var, path
+        ));
+        assert_eq!(
+            all.resolve_private_key().unwrap().as_deref(),
+            Some(file_key.as_str())
+        );
+        let env_and_inline = key(&format!(
+            "private_key = \"01\"\nprivate_key_env = \"{}\"",
+            var
+        ));
+        assert_eq!(
+            env_and_inline.resolve_private_key().unwrap().as_deref(),
+            Some("0x02")
+        );
+
+        // An unset variable falls back to the inline key, or fails without one
+        let fallback = key(&format!(
+            "private_key = \"01\"\nprivate_key_env = \"{}\"",
+            missing_var
+        ));
+        assert_eq!(
+            fallback.resolve_private_key().unwrap().as_deref(),
+            Some("01")
+        );
+        let unset = key(&format!("private_key_env = \"{}\"", missing_var));
+        let err = unset.resolve_private_key().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("private_key_env {} is not set", missing_var)
+        );
+        assert!(unset.get_secret_keys().is_err());
+
+        // A configured file that cannot be read is an error, not a fallback
+        std::fs::remove_file(path).unwrap();
+        let err = all.resolve_private_key().unwrap_err();
+        assert!(err.to_string().contains("private_key_file"), "{}", err);
+
+        // No source configured
+        let none = key("");
+        assert_eq!(none.resolve_private_key().unwrap(), None);
+        assert!(none.validate("user").is_err());
+        std::env::remove_var(&var);
+    }
+}
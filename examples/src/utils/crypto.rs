@@ -1,6 +1,9 @@
 use anyhow::{anyhow, Result};
 use ckb_crypto::secp::{Privkey, Pubkey};
-use ckb_sdk::util::blake160;
+use ckb_sdk::{constants::VALUE_MASK as SINCE_VALUE_MASK, util::blake160, Since, SinceType};
+use ckb_types::core::EpochNumberWithFraction;
+
+use crate::error::ChannelError;
 
 /// Calculate pubkey hash using Blake160 (CKB standard)
 pub fn pubkey_hash(pubkey: &Pubkey) -> [u8; 20] {
@@ -20,40 +23,326 @@ pub fn parse_privkey(hex: &str) -> Result<Privkey> {
     Ok(Privkey::from_slice(&bytes))
 }
 
-/// Spillman Lock Args structure (50 bytes)
-/// Layout: merchant_lock_arg(20) + user_pubkey_hash(20) + timeout_timestamp(8) + algorithm_id(1) + version(1)
-#[derive(Debug, Clone)]
-pub struct SpillmanLockArgs {
-    pub merchant_pubkey_hash: [u8; 20],
+/// Length of the Spillman Lock args
+pub const SPILLMAN_ARGS_LEN: usize = 50;
+
+/// Length of the Spillman Lock args with the optional trailing user_algorithm_id
+pub const SPILLMAN_ARGS_WITH_USER_ALGORITHM_LEN: usize = SPILLMAN_ARGS_LEN + 1;
+
+/// Length of the optional trailing fallback timeout, after the user_algorithm_id if any
+pub const FALLBACK_TIMEOUT_LEN: usize = 8;
+
+/// Length of the optional arbiter pubkey hash, always the last field of the args
+pub const ARBITER_PUBKEY_HASH_LEN: usize = 20;
+
+/// Merchant grace after the timeout, mirroring the contract's TIMEOUT_GRACE_* constants
+///
+/// A refund input's since must be at least `timeout + grace`, added in the timeout's metric.
+pub const TIMEOUT_GRACE_SECONDS: u64 = 600;
+pub const TIMEOUT_GRACE_BLOCKS: u64 = 75;
+pub const TIMEOUT_GRACE_EPOCH_DENOMINATOR: u64 = 24;
+
+/// Spillman Lock Args structure (50 bytes, 51 with a user_algorithm_id, 8 more with a
+/// fallback timeout, 20 more with an arbiter)
+/// Layout: merchant_lock_arg(20) + user_pubkey_hash(20) + timeout(8) + algorithm_id(1) + version(1)
+///   + user_algorithm_id(1, optional) + fallback_timeout(8, optional)
+///   + arbiter_pubkey_hash(20, optional)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpillmanArgs {
+    /// blake160 of the merchant pubkey, or of the multisig config
+    pub merchant_lock_arg: [u8; 20],
     pub user_pubkey_hash: [u8; 20],
-    pub timeout_timestamp: u64,
-    pub algorithm_id: u8, // 0 for single-sig, 6 for multi-sig
+    /// Since-encoded refund timeout, used as-is for the refund input's `since`
+    pub timeout: u64,
+    pub algorithm_id: u8, // 0 for single-sig, 6/7 for multi-sig
     pub version: u8,
+    /// ckb-auth algorithm of the user signature: 0 (CKB) is left out of the args,
+    /// 1..=5 (Ethereum, EOS, Tron, Bitcoin, Dogecoin) are appended. This CLI signs for 0 only.
+    pub user_algorithm_id: u8,
+    /// Since-encoded deadline, later than `timeout`, from which the user refunds without the
+    /// merchant's signature
+    pub fallback_timeout: Option<u64>,
+    /// blake160 of the arbiter pubkey, enabling the arbitration path
+    pub arbiter_pubkey_hash: Option<[u8; 20]>,
 }
 
-impl SpillmanLockArgs {
+impl SpillmanArgs {
     pub fn new_with_algorithm(
-        merchant_pubkey_hash: [u8; 20],
+        merchant_lock_arg: [u8; 20],
         user_pubkey_hash: [u8; 20],
-        timeout_timestamp: u64,
+        timeout: u64,
         algorithm_id: u8,
     ) -> Self {
         Self {
-            merchant_pubkey_hash,
+            merchant_lock_arg,
             user_pubkey_hash,
-            timeout_timestamp,
+            timeout,
             algorithm_id,
             version: 0,
+            user_algorithm_id: 0,
+            fallback_timeout: None,
+            arbiter_pubkey_hash: None,
         }
     }
 
+    /// Parse the args of a Spillman Lock script
+    ///
+    /// Fails with `ChannelError::InvalidArgsLength` unless `bytes` is 50 or 51 bytes, or 58
+    /// or 59 with a fallback timeout, each plus 20 with an arbiter. Like the contract, the
+    /// arbiter is split off the end first.
+    pub fn from_bytes(full: &[u8]) -> Result<Self> {
+        let (bytes, arbiter_pubkey_hash) =
+            if full.len() >= SPILLMAN_ARGS_LEN + ARBITER_PUBKEY_HASH_LEN {
+                let (bytes, arbiter) = full.split_at(full.len() - ARBITER_PUBKEY_HASH_LEN);
+                let mut arbiter_pubkey_hash = [0u8; ARBITER_PUBKEY_HASH_LEN];
+                arbiter_pubkey_hash.copy_from_slice(arbiter);
+                (bytes, Some(arbiter_pubkey_hash))
+            } else {
+                (full, None)
+            };
+        let (has_user_algorithm_id, has_fallback_timeout) = match bytes.len() {
+            SPILLMAN_ARGS_LEN => (false, false),
+            SPILLMAN_ARGS_WITH_USER_ALGORITHM_LEN => (true, false),
+            len if len == SPILLMAN_ARGS_LEN + FALLBACK_TIMEOUT_LEN => (false, true),
+            len if len == SPILLMAN_ARGS_WITH_USER_ALGORITHM_LEN + FALLBACK_TIMEOUT_LEN => {
+                (true, true)
+            }
+            _ => return Err(ChannelError::InvalidArgsLength(full.len()).into()),
+        };
+        let mut merchant_lock_arg = [0u8; 20];
+        merchant_lock_arg.copy_from_slice(&bytes[0..20]);
+        let mut user_pubkey_hash = [0u8; 20];
+        user_pubkey_hash.copy_from_slice(&bytes[20..40]);
+        let mut timeout = [0u8; 8];
+        timeout.copy_from_slice(&bytes[40..48]);
+
+        Ok(Self {
+            merchant_lock_arg,
+            user_pubkey_hash,
+            timeout: u64::from_le_bytes(timeout),
+            algorithm_id: bytes[48],
+            version: bytes[49],
+            user_algorithm_id: if has_user_algorithm_id {
+                bytes[SPILLMAN_ARGS_LEN]
+            } else {
+                0
+            },
+            fallback_timeout: has_fallback_timeout.then(|| {
+                let mut fallback_timeout = [0u8; FALLBACK_TIMEOUT_LEN];
+                fallback_timeout.copy_from_slice(&bytes[bytes.len() - FALLBACK_TIMEOUT_LEN..]);
+                u64::from_le_bytes(fallback_timeout)
+            }),
+            arbiter_pubkey_hash,
+        })
+    }
+
+    /// Earliest since a refund input may use: the timeout plus the merchant's grace
+    ///
+    /// Epoch grace keeps the timeout's epoch length and rounds the added index up, so the
+    /// result is never earlier than the contract's `timeout + 1/24 epoch`.
+    pub fn refund_since(&self) -> Result<u64> {
+        let timeout = Since::from_raw_value(self.timeout);
+        let (since_type, value) = timeout
+            .extract_metric()
+            .filter(|_| timeout.flags_is_valid())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Invalid timeout since in Spillman Lock args: {:#x}",
+                    self.timeout
+                )
+            })?;
+        let value = match since_type {
+            SinceType::BlockNumber => value.checked_add(TIMEOUT_GRACE_BLOCKS),
+            SinceType::Timestamp => value.checked_add(TIMEOUT_GRACE_SECONDS),
+            SinceType::EpochNumberWithFraction => {
+                let epoch = EpochNumberWithFraction::from_full_value(value);
+                let length = epoch.length().max(1);
+                let index = epoch.index() + length.div_ceil(TIMEOUT_GRACE_EPOCH_DENOMINATOR);
+                Some(
+                    EpochNumberWithFraction::new(
+                        epoch.number() + index / length,
+                        index % length,
+                        length,
+                    )
+                    .full_value(),
+                )
+            }
+        }
+        .filter(|value| value & !SINCE_VALUE_MASK == 0)
+        .ok_or_else(|| anyhow!("Refund since overflows for timeout {:#x}", self.timeout))?;
+
+        Ok(Since::new(since_type, value, timeout.is_relative()).value())
+    }
+
+    /// Args bytes, 51 long only when the user signs with a non-CKB algorithm, plus 8 with a
+    /// fallback timeout and 20 with an arbiter
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(50);
-        bytes.extend_from_slice(&self.merchant_pubkey_hash);
-        bytes.extend_from_slice(&self.user_pubkey_hash);
-        bytes.extend_from_slice(&self.timeout_timestamp.to_le_bytes());
-        bytes.push(self.algorithm_id);
-        bytes.push(self.version);
+        let mut bytes = vec![0u8; SPILLMAN_ARGS_LEN];
+        bytes[0..20].copy_from_slice(&self.merchant_lock_arg);
+        bytes[20..40].copy_from_slice(&self.user_pubkey_hash);
+        bytes[40..48].copy_from_slice(&self.timeout.to_le_bytes());
+        bytes[48] = self.algorithm_id;
+        bytes[49] = self.version;
+        if self.user_algorithm_id != 0 {
+            bytes.push(self.user_algorithm_id);
+        }
+        if let Some(fallback_timeout) = self.fallback_timeout {
+            bytes.extend_from_slice(&fallback_timeout.to_le_bytes());
+        }
+        if let Some(arbiter_pubkey_hash) = self.arbiter_pubkey_hash {
+            bytes.extend_from_slice(&arbiter_pubkey_hash);
+        }
         bytes
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spillman_args_round_trip() {
+        let args = SpillmanArgs {
+            merchant_lock_arg: [0x11; 20],
+            user_pubkey_hash: [0x22; 20],
+            timeout: 0x4000_0000_6543_2100,
+            algorithm_id: 7,
+            version: 1,
+            user_algorithm_id: 0,
+            fallback_timeout: None,
+            arbiter_pubkey_hash: None,
+        };
+
+        let bytes = args.to_bytes();
+        assert_eq!(bytes.len(), SPILLMAN_ARGS_LEN);
+        assert_eq!(&bytes[0..20], &[0x11; 20]);
+        assert_eq!(&bytes[20..40], &[0x22; 20]);
+        assert_eq!(&bytes[40..48], &0x4000_0000_6543_2100u64.to_le_bytes());
+        assert_eq!(bytes[48], 7);
+        assert_eq!(bytes[49], 1);
+        assert_eq!(SpillmanArgs::from_bytes(&bytes).unwrap(), args);
+
+        // An Ethereum user appends user_algorithm_id = 1
+        let args = SpillmanArgs {
+            user_algorithm_id: 1,
+            ..args
+        };
+        let bytes = args.to_bytes();
+        assert_eq!(bytes.len(), SPILLMAN_ARGS_WITH_USER_ALGORITHM_LEN);
+        assert_eq!(bytes[50], 1);
+        assert_eq!(SpillmanArgs::from_bytes(&bytes).unwrap(), args);
+
+        // The fallback timeout trails the optional user_algorithm_id
+        let fallback_timeout = 0x4000_0000_6600_0000u64;
+        let args = SpillmanArgs {
+            fallback_timeout: Some(fallback_timeout),
+            ..args
+        };
+        let bytes = args.to_bytes();
+        assert_eq!(
+            bytes.len(),
+            SPILLMAN_ARGS_WITH_USER_ALGORITHM_LEN + FALLBACK_TIMEOUT_LEN
+        );
+        assert_eq!(&bytes[51..59], &fallback_timeout.to_le_bytes());
+        assert_eq!(SpillmanArgs::from_bytes(&bytes).unwrap(), args);
+
+        let args = SpillmanArgs {
+            user_algorithm_id: 0,
+            ..args
+        };
+        let bytes = args.to_bytes();
+        assert_eq!(bytes.len(), SPILLMAN_ARGS_LEN + FALLBACK_TIMEOUT_LEN);
+        assert_eq!(SpillmanArgs::from_bytes(&bytes).unwrap(), args);
+        assert!(SpillmanArgs::from_bytes(&bytes[..55]).is_err());
+    }
+
+    #[test]
+    fn test_spillman_args_round_trip_all_layouts() {
+        let base =
+            SpillmanArgs::new_with_algorithm([0x11; 20], [0x22; 20], 0x4000_0000_6543_2100, 0);
+        for (user_algorithm_id, fallback_timeout, arbiter_pubkey_hash, len) in [
+            (0, None, None, 50),
+            (1, None, None, 51),
+            (0, Some(0x4000_0000_6600_0000), None, 58),
+            (1, Some(0x4000_0000_6600_0000), None, 59),
+            (0, None, Some([0x33; 20]), 70),
+            (1, None, Some([0x33; 20]), 71),
+            (0, Some(0x4000_0000_6600_0000), Some([0x33; 20]), 78),
+            (1, Some(0x4000_0000_6600_0000), Some([0x33; 20]), 79),
+        ] {
+            let args = SpillmanArgs {
+                user_algorithm_id,
+                fallback_timeout,
+                arbiter_pubkey_hash,
+                ..base.clone()
+            };
+            let bytes = args.to_bytes();
+            assert_eq!(bytes.len(), len);
+            if arbiter_pubkey_hash.is_some() {
+                assert_eq!(&bytes[len - ARBITER_PUBKEY_HASH_LEN..], &[0x33; 20]);
+            }
+            assert_eq!(SpillmanArgs::from_bytes(&bytes).unwrap(), args);
+        }
+    }
+
+    #[test]
+    fn test_refund_since_adds_grace() {
+        let args = |timeout: Since| {
+            SpillmanArgs::new_with_algorithm([0x11; 20], [0x22; 20], timeout.value(), 0)
+        };
+
+        let timeout = Since::new(SinceType::Timestamp, 1_735_689_600, false);
+        assert_eq!(
+            args(timeout).refund_since().unwrap(),
+            Since::new(
+                SinceType::Timestamp,
+                1_735_689_600 + TIMEOUT_GRACE_SECONDS,
+                false
+            )
+            .value()
+        );
+
+        let timeout = Since::new(SinceType::BlockNumber, 86_400, true);
+        assert_eq!(
+            args(timeout).refund_since().unwrap(),
+            Since::new(SinceType::BlockNumber, 86_400 + TIMEOUT_GRACE_BLOCKS, true).value()
+        );
+
+        // 1/24 of an 1800-block epoch is 75 blocks; the grace carries into the next epoch
+        let epoch = |number, index, length| {
+            Since::new(
+                SinceType::EpochNumberWithFraction,
+                EpochNumberWithFraction::new(number, index, length).full_value(),
+                false,
+            )
+        };
+        assert_eq!(
+            args(epoch(42, 100, 1800)).refund_since().unwrap(),
+            epoch(42, 175, 1800).value()
+        );
+        assert_eq!(
+            args(epoch(42, 1750, 1800)).refund_since().unwrap(),
+            epoch(43, 25, 1800).value()
+        );
+
+        // Invalid flags never reach the timeout
+        assert!(args(Since::from_raw_value(0x6000_0000_0000_0000))
+            .refund_since()
+            .is_err());
+    }
+
+    #[test]
+    fn test_spillman_args_rejects_wrong_length() {
+        let bytes = SpillmanArgs::new_with_algorithm([0x11; 20], [0x22; 20], 1, 0).to_bytes();
+
+        for len in [0, 49, 52, 60, 69, 72, 80] {
+            let mut input = bytes.clone();
+            input.resize(len, 0);
+            let err = SpillmanArgs::from_bytes(&input).unwrap_err();
+            match err.downcast_ref::<ChannelError>() {
+                Some(ChannelError::InvalidArgsLength(got)) => assert_eq!(*got, len),
+                other => panic!("expected InvalidArgsLength, got {:?}", other),
+            }
+        }
+    }
+}
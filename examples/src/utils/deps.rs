@@ -0,0 +1,178 @@
+use anyhow::{anyhow, Result};
+use ckb_sdk::{
+    constants::{
+        MultisigScript, GENESIS_BLOCK_HASH_MAINNET, GENESIS_BLOCK_HASH_TESTNET, SIGHASH_TYPE_HASH,
+    },
+    traits::{DefaultCellDepResolver, OffchainCellDepResolver},
+    ScriptId,
+};
+use ckb_types::{core::BlockView, packed, H256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::utils::rpc;
+
+/// Directory holding the genesis cell dep caches
+const DEPS_CACHE_DIR: &str = "secrets";
+
+/// Whether the cached cell deps should be ignored and re-queried (`--refresh-deps`)
+static REFRESH_DEPS: AtomicBool = AtomicBool::new(false);
+
+/// Force the next resolver load to re-query the genesis block
+pub fn set_refresh_deps(refresh: bool) {
+    REFRESH_DEPS.store(refresh, Ordering::Relaxed);
+}
+
+/// A single genesis-derived cell dep, keyed by the script it unlocks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedCellDep {
+    pub name: String,
+    pub code_hash: H256,
+    pub hash_type: ckb_jsonrpc_types::ScriptHashType,
+    pub cell_dep: ckb_jsonrpc_types::CellDep,
+}
+
+/// Genesis-derived system cell deps (secp256k1 sighash / multisig) for one network
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedCellDeps {
+    pub genesis_hash: H256,
+    pub deps: Vec<CachedCellDep>,
+}
+
+impl CachedCellDeps {
+    /// Extract the system script cell deps from a genesis-based resolver
+    pub fn from_resolver(genesis_hash: H256, resolver: &DefaultCellDepResolver) -> Self {
+        let script_ids = [
+            ScriptId::new_type(SIGHASH_TYPE_HASH),
+            MultisigScript::Legacy.script_id(),
+            MultisigScript::V2.script_id(),
+        ];
+
+        let deps = script_ids
+            .into_iter()
+            .filter_map(|script_id| {
+                resolver
+                    .get(&script_id)
+                    .map(|(cell_dep, name)| CachedCellDep {
+                        name: name.clone(),
+                        code_hash: script_id.code_hash.clone(),
+                        hash_type: script_id.hash_type.into(),
+                        cell_dep: cell_dep.clone().into(),
+                    })
+            })
+            .collect();
+
+        Self { genesis_hash, deps }
+    }
+
+    /// Rebuild a cell dep resolver from the cached deps
+    pub fn to_resolver(&self) -> OffchainCellDepResolver {
+        let items: HashMap<ScriptId, (packed::CellDep, String)> = self
+            .deps
+            .iter()
+            .map(|dep| {
+                (
+                    ScriptId::new(dep.code_hash.clone(), dep.hash_type.into()),
+                    (dep.cell_dep.clone().into(), dep.name.clone()),
+                )
+            })
+            .collect();
+        OffchainCellDepResolver { items }
+    }
+}
+
+/// Cache file name for a network, e.g. `secrets/cell_deps_testnet.json`
+fn cache_path(genesis_hash: &H256) -> PathBuf {
+    let network = if *genesis_hash == GENESIS_BLOCK_HASH_MAINNET {
+        "mainnet".to_string()
+    } else if *genesis_hash == GENESIS_BLOCK_HASH_TESTNET {
+        "testnet".to_string()
+    } else {
+        // Devnets are keyed by genesis hash, so a reset chain gets a fresh cache
+        format!("devnet_{}", &hex::encode(genesis_hash.as_bytes())[0..8])
+    };
+    PathBuf::from(DEPS_CACHE_DIR).join(format!("cell_deps_{}.json", network))
+}
+
+/// Load the system cell dep resolver, using the on-disk cache when possible
+///
+/// Only the genesis block hash is queried on a cache hit; the full genesis block
+/// is fetched and parsed on a miss, or when `--refresh-deps` is set.
+pub fn load_cell_dep_resolver(rpc_url: &str) -> Result<OffchainCellDepResolver> {
+    let ckb_client = rpc::client(rpc_url);
+    let genesis_hash = rpc::with_retry("get_block_hash", || ckb_client.get_block_hash(0.into()))?
+        .ok_or_else(|| anyhow!("Failed to get genesis block hash"))?;
+    let path = cache_path(&genesis_hash);
+
+    if !REFRESH_DEPS.load(Ordering::Relaxed) {
+        if let Some(cached) = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str::<CachedCellDeps>(&json).ok())
+            .filter(|cached| cached.genesis_hash == genesis_hash)
+        {
+            println!("✓ 使用缓存的 cell deps: {}", path.display());
+            return Ok(cached.to_resolver());
+        }
+    }
+
+    let genesis_block = rpc::with_retry("get_block_by_number", || {
+        ckb_client.get_block_by_number(0.into())
+    })?
+    .ok_or_else(|| anyhow!("Failed to get genesis block"))?;
+    let resolver = DefaultCellDepResolver::from_genesis(&BlockView::from(genesis_block))?;
+    let cached = CachedCellDeps::from_resolver(genesis_hash, &resolver);
+
+    std::fs::create_dir_all(DEPS_CACHE_DIR)?;
+    std::fs::write(&path, serde_json::to_string_pretty(&cached)?)
+        .map_err(|e| anyhow!("Failed to write cell deps cache {}: {}", path.display(), e))?;
+    println!("✓ Cell deps 已缓存到: {}", path.display());
+
+    Ok(cached.to_resolver())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_sdk::traits::CellDepResolver;
+    use ckb_types::{
+        bytes::Bytes,
+        core::{DepType, ScriptHashType},
+        packed::{OutPoint, Script},
+        prelude::*,
+    };
+
+    #[test]
+    fn test_cached_cell_deps_round_trip() {
+        let sighash_dep = packed::CellDep::new_builder()
+            .out_point(OutPoint::new(H256([0x71; 32]).pack(), 0))
+            .dep_type(DepType::DepGroup)
+            .build();
+        let cached = CachedCellDeps {
+            genesis_hash: GENESIS_BLOCK_HASH_TESTNET,
+            deps: vec![CachedCellDep {
+                name: "Secp256k1 blake160 sighash all".to_string(),
+                code_hash: SIGHASH_TYPE_HASH,
+                hash_type: ScriptHashType::Type.into(),
+                cell_dep: sighash_dep.clone().into(),
+            }],
+        };
+
+        let json = serde_json::to_string_pretty(&cached).unwrap();
+        let decoded: CachedCellDeps = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, cached);
+
+        // The rebuilt resolver resolves sighash locks to the cached dep
+        let user_lock = Script::new_builder()
+            .code_hash(SIGHASH_TYPE_HASH.pack())
+            .hash_type(ScriptHashType::Type)
+            .args(Bytes::from(vec![0u8; 20]).pack())
+            .build();
+        assert_eq!(decoded.to_resolver().resolve(&user_lock), Some(sighash_dep));
+        assert_eq!(
+            cache_path(&decoded.genesis_hash),
+            PathBuf::from("secrets/cell_deps_testnet.json")
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::{anyhow, Result};
+use ckb_sdk::{rpc::CkbRpcClient, HumanCapacity};
+use ckb_types::core::TransactionView;
+use clap::ValueEnum;
+
+use crate::utils::config::load_config;
+use crate::utils::rpc;
+
+/// Default fee rate (shannon/KB), also the node's default minimum relay fee rate
+pub const DEFAULT_FEE_RATE: u64 = 1000;
+
+/// How aggressively to outbid the node's recent median fee rate (`--fee-rate-strategy`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FeeRateStrategy {
+    /// Median of recent blocks
+    Slow,
+    /// 1.5x the median
+    Standard,
+    /// 2x the median
+    Fast,
+}
+
+impl FeeRateStrategy {
+    /// Multiplier applied to the base rate, in percent
+    pub fn multiplier_percent(self) -> u64 {
+        match self {
+            FeeRateStrategy::Slow => 100,
+            FeeRateStrategy::Standard => 150,
+            FeeRateStrategy::Fast => 200,
+        }
+    }
+
+    /// Apply the strategy to a base rate, never going below DEFAULT_FEE_RATE
+    pub fn apply(self, base_rate: u64) -> u64 {
+        let rate = base_rate.saturating_mul(self.multiplier_percent()) / 100;
+        rate.max(DEFAULT_FEE_RATE)
+    }
+}
+
+/// Query the node's fee rate statistics and pick a rate for the strategy
+///
+/// Falls back to DEFAULT_FEE_RATE as the base when the node has no recent statistics.
+pub fn resolve_fee_rate(client: &CkbRpcClient, strategy: FeeRateStrategy) -> Result<u64> {
+    let base_rate = client
+        .get_fee_rate_statistics(None)
+        .map_err(|e| anyhow!("Failed to query fee rate statistics: {:?}", e))?
+        .map(|stats| stats.median.value())
+        .unwrap_or(DEFAULT_FEE_RATE);
+
+    Ok(strategy.apply(base_rate))
+}
+
+/// Resolve the fee rate for a command: the strategy, if given, overrides `--fee-rate`
+pub fn resolve_cli_fee_rate(
+    config_path: &str,
+    fee_rate: u64,
+    strategy: Option<FeeRateStrategy>,
+) -> Result<u64> {
+    let Some(strategy) = strategy else {
+        return Ok(fee_rate);
+    };
+
+    let config = load_config(config_path)?;
+    let client = rpc::client(&config.network.rpc_url);
+    let resolved = resolve_fee_rate(&client, strategy)?;
+    println!(
+        "✓ 费率策略 {:?}: {} shannons/KB（覆盖 --fee-rate {}）",
+        strategy, resolved, fee_rate
+    );
+    Ok(resolved)
+}
+
+/// Effective fee rate (shannon/KB) of a transaction paying `fee` at `tx_size` bytes
+pub fn tx_fee_rate(fee: u64, tx_size: u64) -> u64 {
+    if tx_size == 0 {
+        return 0;
+    }
+    fee.saturating_mul(1000) / tx_size
+}
+
+/// Print a built transaction's fee and effective fee rate, returning the rate
+///
+/// Warns when the rate is below `min_fee_rate`, the floor the node relays at
+/// (`channel.min_fee_rate` in the config).
+pub fn report_fee(tx: &TransactionView, fee: u64, min_fee_rate: u64) -> u64 {
+    let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+    let rate = tx_fee_rate(fee, tx_size);
+    println!("  - Fee: {} ({} shannon)", HumanCapacity::from(fee), fee);
+    println!(
+        "  - 交易大小: {} bytes, 有效费率: {} shannons/KB",
+        tx_size, rate
+    );
+    if rate < min_fee_rate {
+        println!(
+            "  - ⚠️  有效费率低于最低费率 {} shannons/KB，节点可能拒绝该交易",
+            min_fee_rate
+        );
+    }
+    rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strategy_multiplier_mapping() {
+        let base_rate = 4000;
+
+        assert_eq!(FeeRateStrategy::Slow.apply(base_rate), 4000);
+        assert_eq!(FeeRateStrategy::Standard.apply(base_rate), 6000);
+        assert_eq!(FeeRateStrategy::Fast.apply(base_rate), 8000);
+
+        // Never below the minimum relay fee rate on an idle network
+        assert_eq!(FeeRateStrategy::Fast.apply(0), DEFAULT_FEE_RATE);
+        assert_eq!(FeeRateStrategy::Slow.apply(600), DEFAULT_FEE_RATE);
+    }
+
+    #[test]
+    fn test_effective_fee_rate() {
+        assert_eq!(tx_fee_rate(500, 500), 1000);
+        assert_eq!(tx_fee_rate(1000, 300), 3333);
+        assert_eq!(tx_fee_rate(1000, 0), 0);
+
+        // Default transaction: its size decides the rate for a fixed fee
+        let tx = TransactionView::new_advanced_builder().build();
+        let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+        assert_eq!(report_fee(&tx, tx_size, DEFAULT_FEE_RATE), 1000);
+        assert_eq!(report_fee(&tx, tx_size * 2, DEFAULT_FEE_RATE), 2000);
+    }
+}
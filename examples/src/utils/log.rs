@@ -0,0 +1,51 @@
+use serde_json::{Map, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether structured JSON events are enabled (`--json-logs`)
+static JSON_LOGS: AtomicBool = AtomicBool::new(false);
+
+/// Enable or disable structured JSON events
+pub fn set_json_logs(enabled: bool) {
+    JSON_LOGS.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether structured JSON events are enabled
+pub fn json_logs_enabled() -> bool {
+    JSON_LOGS.load(Ordering::Relaxed)
+}
+
+/// Build a structured event: `{"stage": .., "event": .., ...fields}`
+pub fn build_event(stage: &str, event: &str, fields: Value) -> Value {
+    let mut object = Map::new();
+    object.insert("stage".to_string(), Value::from(stage));
+    object.insert("event".to_string(), Value::from(event));
+    if let Value::Object(fields) = fields {
+        object.extend(fields);
+    }
+    Value::Object(object)
+}
+
+/// Write a structured event to stderr (one JSON object per line)
+///
+/// No-op unless `--json-logs` is set, so human-readable output is unchanged by default.
+pub fn log_event(stage: &str, event: &str, fields: Value) {
+    if json_logs_enabled() {
+        eprintln!("{}", build_event(stage, event, fields));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_build_event_flattens_fields() {
+        let event = build_event("funding", "built", json!({"tx_hash": "0x01", "fee": 12345}));
+
+        assert_eq!(
+            event,
+            json!({"stage": "funding", "event": "built", "tx_hash": "0x01", "fee": 12345})
+        );
+    }
+}
@@ -1,2 +1,10 @@
+pub mod chain;
+pub mod channel_info;
+pub mod ckb_cli;
 pub mod config;
 pub mod crypto;
+pub mod deps;
+pub mod fee;
+pub mod log;
+pub mod rpc;
+pub mod storage;
@@ -0,0 +1,219 @@
+use ckb_sdk::rpc::CkbRpcClient;
+use serde_json::{json, Value};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::utils::config::NetworkConfig;
+
+/// Default per-request RPC timeout (`network.rpc_timeout_secs`)
+pub const DEFAULT_RPC_TIMEOUT_SECS: u64 = 30;
+
+/// Default number of retries of an idempotent read (`network.rpc_retries`)
+pub const DEFAULT_RPC_RETRIES: u32 = 2;
+
+/// Delay before the first retry, doubled for each later one
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Timeout and retry policy for node RPC calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RpcPolicy {
+    pub timeout: Duration,
+    pub retries: u32,
+    pub backoff: Duration,
+}
+
+impl Default for RpcPolicy {
+    fn default() -> Self {
+        DEFAULT_POLICY
+    }
+}
+
+const DEFAULT_POLICY: RpcPolicy = RpcPolicy {
+    timeout: Duration::from_secs(DEFAULT_RPC_TIMEOUT_SECS),
+    retries: DEFAULT_RPC_RETRIES,
+    backoff: RETRY_BACKOFF,
+};
+
+impl RpcPolicy {
+    /// Policy of the `[network]` config, unset fields keep their defaults
+    pub fn from_network(network: &NetworkConfig) -> Self {
+        Self {
+            timeout: network
+                .rpc_timeout_secs
+                .map_or(DEFAULT_POLICY.timeout, Duration::from_secs),
+            retries: network.rpc_retries.unwrap_or(DEFAULT_RPC_RETRIES),
+            backoff: RETRY_BACKOFF,
+        }
+    }
+
+    /// Delay before retrying after `failures` failed attempts, None once retries are used up
+    pub fn retry_delay(&self, failures: u32) -> Option<Duration> {
+        if failures == 0 || failures > self.retries {
+            return None;
+        }
+        Some(self.backoff.saturating_mul(1 << (failures - 1).min(16)))
+    }
+
+    /// Run an idempotent read, retrying failures with exponential backoff
+    ///
+    /// Returns the last error once the retries are used up. Only use it for calls that
+    /// are safe to repeat: a retried `send_transaction` could be reported as failed
+    /// after the first attempt already reached the node.
+    pub fn call<T, E: Debug>(
+        &self,
+        what: &str,
+        mut op: impl FnMut() -> Result<T, E>,
+    ) -> Result<T, E> {
+        let mut failures = 0;
+        loop {
+            match op() {
+                Ok(value) => return Ok(value),
+                Err(err) => {
+                    failures += 1;
+                    let Some(delay) = self.retry_delay(failures) else {
+                        return Err(err);
+                    };
+                    println!(
+                        "⚠️  {} 失败 ({:?})，{} ms 后重试 ({}/{})",
+                        what,
+                        err,
+                        delay.as_millis(),
+                        failures,
+                        self.retries
+                    );
+                    std::thread::sleep(delay);
+                }
+            }
+        }
+    }
+}
+
+/// Policy in effect, set from the config by `load_config`
+static POLICY: RwLock<RpcPolicy> = RwLock::new(DEFAULT_POLICY);
+
+/// `--rpc-timeout` in seconds, 0 when unset; overrides `network.rpc_timeout_secs`
+static CLI_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(0);
+
+/// Record the `--rpc-timeout` override
+pub fn set_cli_timeout(secs: Option<u64>) {
+    CLI_TIMEOUT_SECS.store(secs.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Apply the `[network]` RPC settings of a loaded config
+pub fn configure(network: &NetworkConfig) {
+    let mut policy = RpcPolicy::from_network(network);
+    let cli_timeout = CLI_TIMEOUT_SECS.load(Ordering::Relaxed);
+    if cli_timeout > 0 {
+        policy.timeout = Duration::from_secs(cli_timeout);
+    }
+    *POLICY.write().unwrap_or_else(|e| e.into_inner()) = policy;
+}
+
+/// Policy in effect
+pub fn policy() -> RpcPolicy {
+    *POLICY.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// RPC client using the configured request timeout
+pub fn client(rpc_url: &str) -> CkbRpcClient {
+    let timeout = policy().timeout;
+    CkbRpcClient::with_builder(rpc_url, |builder| builder.timeout(timeout))
+        .unwrap_or_else(|_| CkbRpcClient::new(rpc_url))
+}
+
+/// Run an idempotent read under the configured retry policy
+pub fn with_retry<T, E: Debug>(what: &str, op: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+    policy().call(what, op)
+}
+
+/// `send_transaction` JSON-RPC request body for `tx`, as `CkbRpcClient` posts it
+///
+/// The params are the transaction and a null outputs validator, the node's default.
+pub fn send_transaction_request(tx: &ckb_jsonrpc_types::Transaction) -> Value {
+    json!({
+        "id": 1,
+        "jsonrpc": "2.0",
+        "method": "send_transaction",
+        "params": [tx, null],
+    })
+}
+
+/// `--raw`: print the `send_transaction` request for `tx` instead of sending it
+///
+/// The request is the last line of stdout, ready for `tail -n 1 | curl -d @- <rpc_url>`.
+pub fn print_send_transaction_request(tx: &ckb_jsonrpc_types::Transaction) {
+    println!("\n📡 未广播，以下最后一行为 send_transaction 请求，可直接发送到节点:");
+    println!("{}", send_transaction_request(tx));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::{core::TransactionBuilder, packed::CellOutput, prelude::*};
+
+    #[test]
+    fn test_send_transaction_request_envelope() {
+        let tx = TransactionBuilder::default()
+            .output(CellOutput::new_builder().capacity(61_0000_0000u64).build())
+            .output_data(ckb_types::packed::Bytes::default())
+            .build();
+        let tx_json = ckb_jsonrpc_types::TransactionView::from(tx).inner;
+
+        let request = send_transaction_request(&tx_json);
+
+        assert_eq!(request["id"], 1);
+        assert_eq!(request["jsonrpc"], "2.0");
+        assert_eq!(request["method"], "send_transaction");
+        let params = request["params"].as_array().unwrap();
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[0], serde_json::to_value(&tx_json).unwrap());
+        assert_eq!(params[0]["outputs"][0]["capacity"], "0x16b969d00");
+        assert!(params[0].get("hash").is_none());
+        assert!(params[1].is_null());
+
+        // One line, so it can be cut from the rest of the output
+        assert!(!request.to_string().contains('\n'));
+    }
+
+    #[test]
+    fn test_retry_until_success_or_retries_used_up() {
+        let policy = RpcPolicy {
+            timeout: Duration::from_secs(1),
+            retries: 3,
+            backoff: Duration::ZERO,
+        };
+
+        // Fails twice, then succeeds on the third attempt
+        let mut attempts = 0;
+        let result: Result<u32, &str> = policy.call("mock", || {
+            attempts += 1;
+            if attempts <= 2 {
+                Err("node hiccup")
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result, Ok(3));
+
+        // Never succeeds: 1 attempt + 3 retries, then the last error
+        let mut attempts = 0;
+        let result: Result<(), String> = policy.call("mock", || {
+            attempts += 1;
+            Err(format!("failure {}", attempts))
+        });
+        assert_eq!(result, Err("failure 4".to_string()));
+        assert_eq!(attempts, 4);
+
+        // Backoff doubles per retry
+        let policy = RpcPolicy {
+            backoff: Duration::from_millis(500),
+            ..policy
+        };
+        assert_eq!(policy.retry_delay(1), Some(Duration::from_millis(500)));
+        assert_eq!(policy.retry_delay(2), Some(Duration::from_millis(1000)));
+        assert_eq!(policy.retry_delay(3), Some(Duration::from_millis(2000)));
+        assert_eq!(policy.retry_delay(4), None);
+    }
+}
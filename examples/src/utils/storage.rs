@@ -0,0 +1,407 @@
+use anyhow::{anyhow, Result};
+use ckb_types::{core::TransactionView, prelude::*};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::utils::channel_info::{load_channel_info, write_channel_info, ChannelInfo, InfoFormat};
+
+/// Sub-directory of the channel directory holding one record per commitment
+pub const COMMITMENTS_DIR: &str = "commitments";
+
+/// Signed funding transaction written by `set-up` into the channel directory
+pub const FUNDING_TX_FILE: &str = "funding_tx_signed.json";
+
+/// File name prefix of the refund transactions saved by `refund` / `watch`
+pub const REFUND_TX_PREFIX: &str = "refund_tx_";
+
+/// Format version of `ChannelSnapshot`
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// One commitment signed by the user, as recorded by `pay`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitmentRecord {
+    /// 1 for the first commitment of the channel, increasing by one per payment
+    pub sequence: u64,
+    /// Unix timestamp (seconds) when the commitment was signed
+    pub created_at: u64,
+    /// Commitment transaction hash (0x-prefixed)
+    pub tx_hash: String,
+    /// Path of the saved commitment transaction
+    pub tx_file: String,
+    /// Cumulative CKB paid to the merchant, excluding its minimum occupied capacity (shannons)
+    pub paid_amount: u64,
+    /// Capacity of the user output (shannons)
+    pub user_capacity: u64,
+    /// Capacity of the merchant output (shannons)
+    pub merchant_capacity: u64,
+    /// Cumulative xUDT paid to the merchant (xUDT channels only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xudt_paid_amount: Option<u128>,
+    /// xUDT kept by the user (xUDT channels only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_xudt_amount: Option<u128>,
+}
+
+impl CommitmentRecord {
+    /// Build the record of a commitment transaction (output 0: user, output 1: merchant)
+    pub fn from_commitment_tx(
+        sequence: u64,
+        created_at: u64,
+        tx_file: &str,
+        paid_amount: u64,
+        tx: &TransactionView,
+    ) -> Result<Self> {
+        let output = |index: usize| {
+            tx.outputs()
+                .get(index)
+                .ok_or_else(|| anyhow!("Commitment transaction has no output {}", index))
+        };
+        let xudt_amount = |index: usize| -> Option<u128> {
+            let data: Vec<u8> = tx.outputs_data().get(index)?.unpack();
+            Some(u128::from_le_bytes(data.get(..16)?.try_into().ok()?))
+        };
+        let is_xudt = output(1)?.type_().is_some();
+
+        Ok(Self {
+            sequence,
+            created_at,
+            tx_hash: format!("{:#x}", tx.hash()),
+            tx_file: tx_file.to_string(),
+            paid_amount,
+            user_capacity: output(0)?.capacity().unpack(),
+            merchant_capacity: output(1)?.capacity().unpack(),
+            xudt_paid_amount: if is_xudt { xudt_amount(1) } else { None },
+            user_xudt_amount: if is_xudt { xudt_amount(0) } else { None },
+        })
+    }
+}
+
+/// Directory of the commitment records of the channel stored in `channel_dir`
+pub fn commitments_dir(channel_dir: &Path) -> PathBuf {
+    channel_dir.join(COMMITMENTS_DIR)
+}
+
+/// Save `record` as `<channel_dir>/commitments/<sequence>.json`
+pub fn save_commitment_record(channel_dir: &Path, record: &CommitmentRecord) -> Result<PathBuf> {
+    let dir = commitments_dir(channel_dir);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.json", record.sequence));
+    fs::write(&path, serde_json::to_string_pretty(record)?)?;
+    Ok(path)
+}
+
+/// Load every commitment record of the channel, ordered by sequence
+///
+/// A channel without any payment yet has no records.
+pub fn load_commitment_records(channel_dir: &Path) -> Result<Vec<CommitmentRecord>> {
+    let dir = commitments_dir(channel_dir);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut records = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let content = fs::read_to_string(&path)?;
+        let record: CommitmentRecord = serde_json::from_str(&content).map_err(|e| {
+            anyhow!(
+                "Failed to parse commitment record {}: {}",
+                path.display(),
+                e
+            )
+        })?;
+        records.push(record);
+    }
+    records.sort_by_key(|record| record.sequence);
+    Ok(records)
+}
+
+/// Sequence number of the next commitment after `records`
+pub fn next_sequence(records: &[CommitmentRecord]) -> u64 {
+    records.last().map_or(1, |record| record.sequence + 1)
+}
+
+/// Everything needed to restore a channel directory: channel info, funding transaction,
+/// the latest refund transaction and the commitment ledger
+///
+/// Private keys are never part of a snapshot; they stay in the config, so a backup can be
+/// kept somewhere less protected than the keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelSnapshot {
+    /// `SNAPSHOT_VERSION` of the exporting CLI
+    pub version: u32,
+    /// File name of the channel info (`channel_info.json`, `.toml` or `.yaml`)
+    pub channel_info_file: String,
+    pub channel_info: ChannelInfo,
+    /// Signed funding transaction, if the channel directory holds one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub funding_tx: Option<ckb_jsonrpc_types::TransactionView>,
+    /// File name and transaction of the latest refund, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refund_tx: Option<(String, ckb_jsonrpc_types::TransactionView)>,
+    /// Commitment records, ordered by sequence
+    #[serde(default)]
+    pub commitments: Vec<CommitmentRecord>,
+}
+
+/// Collect the channel stored in `channel_dir` into a snapshot
+pub fn export(channel_dir: &Path) -> Result<ChannelSnapshot> {
+    let channel_info_path = find_channel_info(channel_dir)
+        .ok_or_else(|| anyhow!("No channel info found in {}", channel_dir.display()))?;
+    let channel_info_file = file_name(&channel_info_path)?;
+    let channel_info = load_channel_info(
+        channel_info_path
+            .to_str()
+            .ok_or_else(|| anyhow!("Invalid channel info path"))?,
+    )?;
+
+    let funding_tx_path = channel_dir.join(FUNDING_TX_FILE);
+    let funding_tx = if funding_tx_path.exists() {
+        Some(read_tx_json(&funding_tx_path)?)
+    } else {
+        None
+    };
+
+    // Refund files are named by their Unix timestamp, so the greatest name is the latest
+    let mut refund_files = Vec::new();
+    for entry in fs::read_dir(channel_dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.starts_with(REFUND_TX_PREFIX) && name.ends_with(".json") {
+            refund_files.push(name);
+        }
+    }
+    let refund_tx = match refund_files.into_iter().max() {
+        Some(name) => {
+            let tx = read_tx_json(&channel_dir.join(&name))?;
+            Some((name, tx))
+        }
+        None => None,
+    };
+
+    Ok(ChannelSnapshot {
+        version: SNAPSHOT_VERSION,
+        channel_info_file,
+        channel_info,
+        funding_tx,
+        refund_tx,
+        commitments: load_commitment_records(channel_dir)?,
+    })
+}
+
+/// Write `snapshot` into `channel_dir`, creating it if needed
+///
+/// Refuses a directory that already holds a channel info file, so a restore never
+/// overwrites a live channel.
+pub fn import(snapshot: &ChannelSnapshot, channel_dir: &Path) -> Result<()> {
+    if snapshot.version > SNAPSHOT_VERSION {
+        return Err(anyhow!(
+            "Unsupported channel snapshot version {} (this CLI reads up to {})",
+            snapshot.version,
+            SNAPSHOT_VERSION
+        ));
+    }
+    if let Some(ref funding_tx) = snapshot.funding_tx {
+        let funding_tx_hash = format!("{:#x}", funding_tx.hash);
+        if funding_tx_hash != snapshot.channel_info.funding_tx_hash {
+            return Err(anyhow!(
+                "Snapshot funding tx {} does not match the channel info's {}",
+                funding_tx_hash,
+                snapshot.channel_info.funding_tx_hash
+            ));
+        }
+    }
+
+    // The name comes from the snapshot file: keep it inside the channel directory
+    if let Some((ref name, _)) = snapshot.refund_tx {
+        if !name.starts_with(REFUND_TX_PREFIX) || Path::new(name).file_name() != Some(name.as_ref())
+        {
+            return Err(anyhow!("Invalid refund tx file name in snapshot: {}", name));
+        }
+    }
+    if let Some(existing) = find_channel_info(channel_dir) {
+        return Err(anyhow!(
+            "{} already exists, restore into an empty channel directory",
+            existing.display()
+        ));
+    }
+
+    fs::create_dir_all(channel_dir)?;
+    let format = InfoFormat::from_path(Path::new(&snapshot.channel_info_file));
+    write_channel_info(&snapshot.channel_info, channel_dir, format)?;
+    if let Some(ref funding_tx) = snapshot.funding_tx {
+        fs::write(
+            channel_dir.join(FUNDING_TX_FILE),
+            serde_json::to_string_pretty(funding_tx)?,
+        )?;
+    }
+    if let Some((ref name, ref refund_tx)) = snapshot.refund_tx {
+        fs::write(
+            channel_dir.join(name),
+            serde_json::to_string_pretty(refund_tx)?,
+        )?;
+    }
+    for record in &snapshot.commitments {
+        save_commitment_record(channel_dir, record)?;
+    }
+    Ok(())
+}
+
+/// Channel info file in `channel_dir`, whichever of the supported formats it uses
+fn find_channel_info(channel_dir: &Path) -> Option<PathBuf> {
+    InfoFormat::value_variants()
+        .iter()
+        .map(|format| channel_dir.join(format!("channel_info.{}", format.extension())))
+        .find(|path| path.exists())
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Invalid file name: {}", path.display()))
+}
+
+fn read_tx_json(path: &Path) -> Result<ckb_jsonrpc_types::TransactionView> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content)
+        .map_err(|e| anyhow!("Failed to parse transaction {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::{bytes::Bytes, core::TransactionBuilder, packed::CellOutput};
+
+    fn record(sequence: u64, paid_amount: u64) -> CommitmentRecord {
+        CommitmentRecord {
+            sequence,
+            created_at: 1_700_000_000 + sequence,
+            tx_hash: format!("0x{:064x}", sequence),
+            tx_file: format!("secrets/commitment_{}_ckb.json", sequence),
+            paid_amount,
+            user_capacity: 100_000_000_000 - paid_amount,
+            merchant_capacity: 6_100_000_000 + paid_amount,
+            xudt_paid_amount: None,
+            user_xudt_amount: None,
+        }
+    }
+
+    #[test]
+    fn test_load_commitment_records_ordered_by_sequence() {
+        let dir =
+            std::env::temp_dir().join(format!("spillman_storage_test_{}", std::process::id()));
+        assert_eq!(load_commitment_records(&dir).unwrap(), Vec::new());
+
+        // Saved out of order, with sequence 10 sorting before 2 by file name
+        let records = [
+            record(2, 2_000_000_000),
+            record(10, 3_000_000_000),
+            record(1, 1_000_000_000),
+        ];
+        for record in &records {
+            save_commitment_record(&dir, record).unwrap();
+        }
+
+        let loaded = load_commitment_records(&dir).unwrap();
+        let sequences: Vec<u64> = loaded.iter().map(|record| record.sequence).collect();
+        assert_eq!(sequences, vec![1, 2, 10]);
+        assert_eq!(loaded[0], records[2]);
+        assert_eq!(next_sequence(&loaded), 11);
+        assert_eq!(next_sequence(&[]), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_channel_snapshot_round_trip() {
+        let root =
+            std::env::temp_dir().join(format!("spillman_snapshot_test_{}", std::process::id()));
+        let (source, restored) = (root.join("source"), root.join("restored"));
+        fs::create_dir_all(&source).unwrap();
+
+        let tx = |capacity: u64| {
+            ckb_jsonrpc_types::TransactionView::from(
+                TransactionBuilder::default()
+                    .output(CellOutput::new_builder().capacity(capacity).build())
+                    .output_data(Bytes::new().pack())
+                    .build(),
+            )
+        };
+        let funding_tx = tx(100_000_000_000);
+        let info = ChannelInfo {
+            user_address: "ckt1user".to_string(),
+            merchant_address: "ckt1merchant".to_string(),
+            capacity_ckb: 1000,
+            timeout_epochs: 0,
+            current_timestamp: 1_700_000_000,
+            timeout_timestamp: 1_700_086_400,
+            spillman_lock_script_hash: format!("0x{}", "11".repeat(32)),
+            funding_tx_hash: format!("{:#x}", funding_tx.hash),
+            funding_output_index: 0,
+            xudt_type_script: None,
+            xudt_amount: None,
+            refund_available_at: Some(1_700_087_000),
+            funding_created_at: Some(1_700_000_000),
+            co_funded: Some(false),
+            args_version: None,
+        };
+
+        // A populated channel directory: TOML channel info, funding tx, two refunds (the
+        // later one is kept) and the commitment ledger
+        write_channel_info(&info, &source, InfoFormat::Toml).unwrap();
+        fs::write(
+            source.join(FUNDING_TX_FILE),
+            serde_json::to_string_pretty(&funding_tx).unwrap(),
+        )
+        .unwrap();
+        for (timestamp, capacity) in [(1_700_090_000u64, 1u64), (1_700_095_000, 2)] {
+            fs::write(
+                source.join(format!("{}{}.json", REFUND_TX_PREFIX, timestamp)),
+                serde_json::to_string_pretty(&tx(capacity)).unwrap(),
+            )
+            .unwrap();
+        }
+        for sequence in 1..=2 {
+            save_commitment_record(&source, &record(sequence, sequence * 1_000_000_000)).unwrap();
+        }
+
+        let snapshot = export(&source).unwrap();
+        assert_eq!(snapshot.channel_info_file, "channel_info.toml");
+        assert_eq!(
+            snapshot.refund_tx.as_ref().map(|(name, _)| name.as_str()),
+            Some("refund_tx_1700095000.json")
+        );
+        assert_eq!(snapshot.commitments.len(), 2);
+
+        // Through the backup file and into a fresh directory
+        let backup: ChannelSnapshot =
+            serde_json::from_str(&serde_json::to_string_pretty(&snapshot).unwrap()).unwrap();
+        import(&backup, &restored).unwrap();
+
+        for file in [
+            "channel_info.toml",
+            FUNDING_TX_FILE,
+            "refund_tx_1700095000.json",
+            "commitments/1.json",
+            "commitments/2.json",
+        ] {
+            assert_eq!(
+                fs::read(source.join(file)).unwrap(),
+                fs::read(restored.join(file)).unwrap(),
+                "{} differs after restore",
+                file
+            );
+        }
+        assert!(!restored.join("refund_tx_1700090000.json").exists());
+
+        // A second restore would overwrite the channel
+        assert!(import(&backup, &restored).is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,6 +1,7 @@
 use crate::Loader;
 use ckb_sdk::util::blake160;
 use ckb_std::since::{EpochNumberWithFraction, Since};
+use ckb_testtool::builtin::ALWAYS_SUCCESS;
 use ckb_testtool::context::Context;
 use ckb_testtool::{
     ckb_crypto::secp::Generator,
@@ -12,22 +13,119 @@ use ckb_testtool::{
         prelude::*,
     },
 };
+use sha3::{Digest, Keccak256};
+use std::env;
+use std::sync::LazyLock;
 
 const EMPTY_WITNESS_ARGS: [u8; 16] = [16, 0, 0, 0, 16, 0, 0, 0, 16, 0, 0, 0, 16, 0, 0, 0];
 const UNLOCK_TYPE_COMMITMENT: u8 = 0x00;
 const UNLOCK_TYPE_TIMEOUT: u8 = 0x01;
-
-// Mainnet/Testnet secp256k1_blake160_sighash_all code_hash
-const SECP256K1_CODE_HASH: [u8; 32] = [
-    0x9b, 0xd7, 0xe0, 0x6f, 0x3e, 0xcf, 0x4b, 0xe0, 0xf2, 0xfc, 0xd2, 0x18, 0x8b, 0x23, 0xf1, 0xb9,
-    0xfc, 0xc8, 0x8e, 0x5d, 0x4b, 0x65, 0xa8, 0x63, 0x7b, 0x17, 0x72, 0x3b, 0xbd, 0xa3, 0xcc, 0xe8,
-];
-
-// Mainnet/Testnet secp256k1_blake160_multisig_all code_hash
-const SECP256K1_MULTISIG_CODE_HASH: [u8; 32] = [
-    0x5c, 0x50, 0x69, 0xeb, 0x08, 0x57, 0xef, 0xc6, 0x5e, 0x1b, 0xca, 0x0c, 0x07, 0xdf, 0x34, 0xc3,
-    0x16, 0x63, 0xb3, 0x62, 0x2f, 0xd3, 0x87, 0x6c, 0x87, 0x63, 0x20, 0xfc, 0x96, 0x34, 0xe2, 0xa8,
-];
+const UNLOCK_TYPE_SPLICE: u8 = 0x02;
+const UNLOCK_TYPE_ARBITRATION: u8 = 0x03;
+const ARBITRATION_SIGNER_USER: u8 = 0x01;
+const ARBITRATION_SIGNER_MERCHANT: u8 = 0x02;
+const ARBITRATION_SIGNER_ARBITER: u8 = 0x04;
+const VERSION_FLAG_USER_ONLY_REFUND: u8 = 0x80;
+const COMMITMENT_FLAG_USER_LOCK_OVERRIDE: u8 = 0x80;
+const USER_ALGORITHM_ETHEREUM: u8 = 1;
+
+// Spillman Lock error codes (see `Error` in contracts/spillman-lock)
+const ERROR_WITNESS_LEN: i8 = 6;
+const ERROR_ARGS_LEN: i8 = 16;
+const ERROR_UNSUPPORTED_VERSION: i8 = 7;
+const ERROR_COMMITMENT_MUST_HAVE_EXACTLY_TWO_OUTPUTS: i8 = 9;
+const ERROR_REFUND_MUST_HAVE_ONE_OR_TWO_OUTPUTS: i8 = 10;
+const ERROR_TIMEOUT_NOT_REACHED: i8 = 11;
+const ERROR_INVALID_LOCK_ARGS: i8 = 12;
+const ERROR_USER_PUBKEY_HASH_MISMATCH: i8 = 13;
+const ERROR_UNSUPPORTED_AUTH_ALGORITHM: i8 = 23;
+const ERROR_MERCHANT_PUBKEY_HASH_MISMATCH: i8 = 14;
+const ERROR_MERCHANT_CAPACITY_EXCESSIVE: i8 = 21;
+const ERROR_AUTH: i8 = 17;
+const ERROR_EXCESSIVE_FEE: i8 = 18;
+const ERROR_TYPE_SCRIPT_MISMATCH: i8 = 19;
+const ERROR_INVALID_MULTISIG_CONFIG: i8 = 22;
+const ERROR_USER_MERCHANT_LOCK_COLLISION: i8 = 24;
+const ERROR_INVALID_MERCHANT_OUTPUT_COUNT: i8 = 25;
+const ERROR_XUDT_CONSERVATION_VIOLATION: i8 = 26;
+const ERROR_UNEXPECTED_SINCE_ON_COMMITMENT: i8 = 27;
+const ERROR_OUTPUT_BELOW_MIN_CAPACITY: i8 = 28;
+const ERROR_SPLICE_MUST_HAVE_EXACTLY_TWO_OUTPUTS: i8 = 29;
+const ERROR_SPLICE_LOCK_MISMATCH: i8 = 30;
+const ERROR_INVALID_UNLOCK_TYPE: i8 = 8;
+const ERROR_INVALID_ARBITRATION_SIGNERS: i8 = 31;
+
+// Merchant grace after the timeout (see TIMEOUT_GRACE_* in contracts/spillman-lock)
+const TIMEOUT_GRACE_SECONDS: u64 = 600;
+const TIMEOUT_GRACE_BLOCKS: u64 = 75;
+const TIMEOUT_GRACE_EPOCH_DENOMINATOR: u64 = 24;
+
+// secp256k1_blake160_sighash_all code_hash, defaulting to Mainnet/Testnet.
+// Set SPILLMAN_SECP256K1_CODE_HASH to the same value the contract was built with
+// when testing against a custom chain (see contracts/spillman-lock/build.rs).
+static SECP256K1_CODE_HASH: LazyLock<[u8; 32]> = LazyLock::new(|| {
+    system_script_code_hash(
+        "SPILLMAN_SECP256K1_CODE_HASH",
+        [
+            0x9b, 0xd7, 0xe0, 0x6f, 0x3e, 0xcf, 0x4b, 0xe0, 0xf2, 0xfc, 0xd2, 0x18, 0x8b, 0x23,
+            0xf1, 0xb9, 0xfc, 0xc8, 0x8e, 0x5d, 0x4b, 0x65, 0xa8, 0x63, 0x7b, 0x17, 0x72, 0x3b,
+            0xbd, 0xa3, 0xcc, 0xe8,
+        ],
+    )
+});
+
+// secp256k1_blake160_multisig_all code_hash, defaulting to Mainnet/Testnet
+// (override: SPILLMAN_SECP256K1_MULTISIG_CODE_HASH)
+static SECP256K1_MULTISIG_CODE_HASH: LazyLock<[u8; 32]> = LazyLock::new(|| {
+    system_script_code_hash(
+        "SPILLMAN_SECP256K1_MULTISIG_CODE_HASH",
+        [
+            0x5c, 0x50, 0x69, 0xeb, 0x08, 0x57, 0xef, 0xc6, 0x5e, 0x1b, 0xca, 0x0c, 0x07, 0xdf,
+            0x34, 0xc3, 0x16, 0x63, 0xb3, 0x62, 0x2f, 0xd3, 0x87, 0x6c, 0x87, 0x63, 0x20, 0xfc,
+            0x96, 0x34, 0xe2, 0xa8,
+        ],
+    )
+});
+
+// secp256k1_blake160_multisig_all V2 code_hash (hash_type Data1), defaulting to
+// Mainnet/Testnet (override: SPILLMAN_SECP256K1_MULTISIG_V2_CODE_HASH)
+static SECP256K1_MULTISIG_V2_CODE_HASH: LazyLock<[u8; 32]> = LazyLock::new(|| {
+    system_script_code_hash(
+        "SPILLMAN_SECP256K1_MULTISIG_V2_CODE_HASH",
+        [
+            0x36, 0xc9, 0x71, 0xb8, 0xd4, 0x1f, 0xbd, 0x94, 0xaa, 0xbc, 0xa7, 0x7d, 0xc7, 0x5e,
+            0x82, 0x67, 0x29, 0xac, 0x98, 0x44, 0x7b, 0x46, 0xf9, 0x1e, 0x00, 0x79, 0x61, 0x55,
+            0xdd, 0xdb, 0x0d, 0x29,
+        ],
+    )
+});
+
+// Omnilock type hash, defaulting to Mainnet (override: SPILLMAN_OMNILOCK_CODE_HASH)
+static OMNILOCK_CODE_HASH: LazyLock<[u8; 32]> = LazyLock::new(|| {
+    system_script_code_hash(
+        "SPILLMAN_OMNILOCK_CODE_HASH",
+        [
+            0x9b, 0x81, 0x97, 0x93, 0xa6, 0x44, 0x63, 0xae, 0xd7, 0x7c, 0x61, 0x5d, 0x6c, 0xb2,
+            0x26, 0xee, 0xa5, 0x48, 0x7c, 0xcf, 0xc0, 0x78, 0x30, 0x43, 0xa5, 0x87, 0x25, 0x4c,
+            0xda, 0x2b, 0x6f, 0x26,
+        ],
+    )
+});
+
+/// Code hash from the hex env var `var`, or `default` when unset
+fn system_script_code_hash(var: &str, default: [u8; 32]) -> [u8; 32] {
+    let Ok(value) = env::var(var) else {
+        return default;
+    };
+    let value = value.trim_start_matches("0x");
+    assert_eq!(value.len(), 64, "{} must be 32 bytes of hex", var);
+    let mut code_hash = [0u8; 32];
+    for (i, byte) in code_hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&value[i * 2..i * 2 + 2], 16)
+            .unwrap_or_else(|e| panic!("decode {}: {}", var, e));
+    }
+    code_hash
+}
 
 // Include your tests here
 // See https://github.com/xxuejie/ckb-native-build-sample/blob/main/tests/src/tests.rs for more examples
@@ -146,6 +244,10 @@ fn test_spillman_lock_commitment_path() {
         &merchant_key,
     );
 
+    // Commitments carry no timelock
+    let success_since: u64 = success_tx.inputs().get(0).unwrap().since().unpack();
+    assert_eq!(success_since, 0);
+
     // run
     let cycles = context
         .verify_tx(&success_tx, 10_000_000)
@@ -487,38 +589,63 @@ fn test_spillman_lock_timeout_path_with_co_funding() {
         .expect("pass verification");
     println!("consume cycles (co-funding refund): {}", cycles);
 
-    // Test: wrong merchant output (not merchant's address) should fail
-    let wrong_merchant_lock = Script::new_builder()
+    // Test: the merchant signs a refund paying its capacity to another lock of the same
+    // size (`--merchant-out`), which the contract accepts
+    let merchant_out_lock = Script::new_builder()
         .code_hash(SECP256K1_CODE_HASH.pack())
         .hash_type(ScriptHashType::Type.into())
-        .args(Bytes::from(vec![0u8; 20]).pack()) // wrong pubkey hash
+        .args(Bytes::from(vec![0x33u8; 20]).pack())
         .build();
+    let merchant_out_outputs = |merchant_lock: Script, merchant_capacity: u64| {
+        vec![
+            CellOutput::new_builder()
+                .capacity((total_capacity - merchant_capacity - 100_000_000).pack())
+                .lock(user_lock_script.clone())
+                .build(),
+            CellOutput::new_builder()
+                .capacity(merchant_capacity.pack())
+                .lock(merchant_lock)
+                .build(),
+        ]
+    };
 
-    let wrong_outputs = vec![
-        CellOutput::new_builder()
-            .capacity((total_capacity - merchant_capacity_u64 - 100_000_000).pack())
-            .lock(user_lock_script.clone())
-            .build(),
-        CellOutput::new_builder()
-            .capacity(merchant_capacity_u64.pack())
-            .lock(wrong_merchant_lock)
-            .build(),
-    ];
-
-    let wrong_tx = build_and_sign_tx(
+    let merchant_out_tx = build_and_sign_tx(
         success_tx.cell_deps(),
         input.clone(),
-        wrong_outputs,
+        merchant_out_outputs(merchant_out_lock, merchant_capacity_u64),
         vec![Bytes::new(); 2],
         UNLOCK_TYPE_TIMEOUT,
         &user_key,
         &merchant_key,
     );
+    let cycles = context
+        .verify_tx(&merchant_out_tx, 10_000_000)
+        .expect("merchant-signed refund to another merchant lock should pass");
+    println!(
+        "consume cycles (co-funding refund, merchant out): {}",
+        cycles
+    );
 
+    // Test: a larger lock would take back more than the merchant's occupied capacity
+    let larger_lock = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(vec![0x33u8; 40]).pack())
+        .build();
+    let larger_capacity = merchant_capacity_u64 + 20 * 100_000_000;
+    let larger_tx = build_and_sign_tx(
+        success_tx.cell_deps(),
+        input.clone(),
+        merchant_out_outputs(larger_lock, larger_capacity),
+        vec![Bytes::new(); 2],
+        UNLOCK_TYPE_TIMEOUT,
+        &user_key,
+        &merchant_key,
+    );
     let err = context
-        .verify_tx(&wrong_tx, 10_000_000)
-        .expect_err("wrong merchant output should fail verification");
-    println!("error (wrong merchant output): {:?}", err);
+        .verify_tx(&larger_tx, 10_000_000)
+        .expect_err("merchant out lock larger than the args lock should fail");
+    assert_script_error(err, ERROR_MERCHANT_CAPACITY_EXCESSIVE);
 
     // Test: merchant capacity exceeds occupied capacity should fail
     let excessive_capacity = merchant_capacity_u64 + 100_000_000; // 1 CKB more than needed
@@ -1122,6 +1249,127 @@ fn test_spillman_lock_commitment_path_with_multisig_merchant() {
     println!("error (insufficient signatures): {:?}", err);
 }
 
+#[test]
+fn test_spillman_lock_commitment_path_with_multisig_v2_merchant() {
+    // Commitment with a 2-of-3 V2 multisig merchant (algorithm_id = 7): the merchant output
+    // is the V2 multisig lock (hash_type Data1), signatures go through ckb-auth multisig
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key1 = generator.gen_keypair();
+    let merchant_key2 = generator.gen_keypair();
+    let merchant_key3 = generator.gen_keypair();
+
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_since = Since::from_timestamp(1735689600, true).expect("valid timestamp since");
+    let algorithm_id: u8 = 7; // Multi-sig V2
+
+    // Multisig config: S=0, R=0, M=2, N=3 (S stays 0 for V2)
+    let multisig_config = [
+        &[0u8, 0, 2, 3][..],
+        blake160(&merchant_key1.1.serialize()).as_ref(),
+        blake160(&merchant_key2.1.serialize()).as_ref(),
+        blake160(&merchant_key3.1.serialize()).as_ref(),
+    ]
+    .concat();
+    let merchant_lock_arg = &blake2b_256(&multisig_config)[0..20];
+
+    let args = [
+        merchant_lock_arg,
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[algorithm_id],
+        &[0u8],
+    ]
+    .concat();
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+    let merchant_lock_script = |code_hash: &[u8; 32], hash_type: ScriptHashType| {
+        Script::new_builder()
+            .code_hash(code_hash.pack())
+            .hash_type(hash_type.into())
+            .args(Bytes::from(merchant_lock_arg.to_vec()).pack())
+            .build()
+    };
+
+    let cell_deps: CellDepVec = vec![
+        CellDep::new_builder()
+            .out_point(spillman_lock_out_point)
+            .build(),
+        CellDep::new_builder().out_point(auth_out_point).build(),
+    ]
+    .pack();
+
+    let input = CellInput::new_builder()
+        .previous_output(
+            context.create_cell(
+                CellOutput::new_builder()
+                    .capacity(100_100_000_000u64.pack()) // 1001 CKB
+                    .lock(lock_script)
+                    .build(),
+                Bytes::new(),
+            ),
+        )
+        .build();
+
+    let commitment = |merchant_lock: Script, multisig_config: &[u8]| {
+        let outputs = vec![
+            CellOutput::new_builder()
+                .capacity(50_000_000_000u64.pack()) // 500 CKB
+                .lock(user_lock_script.clone())
+                .build(),
+            CellOutput::new_builder()
+                .capacity(50_000_000_000u64.pack()) // 500 CKB
+                .lock(merchant_lock)
+                .build(),
+        ];
+        build_and_sign_tx_multisig(
+            cell_deps.clone(),
+            input.clone(),
+            outputs,
+            vec![Bytes::new(); 2],
+            UNLOCK_TYPE_COMMITMENT,
+            &user_key,
+            &[&merchant_key1, &merchant_key2],
+            multisig_config,
+        )
+    };
+
+    let v2_lock = merchant_lock_script(&SECP256K1_MULTISIG_V2_CODE_HASH, ScriptHashType::Data1);
+    let cycles = context
+        .verify_tx(&commitment(v2_lock.clone(), &multisig_config), 10_000_000)
+        .expect("V2 multisig commitment should pass");
+    println!("consume cycles (multisig v2 commitment): {}", cycles);
+
+    // The merchant output must be the V2 lock, not the Legacy one with the same args
+    let legacy_lock = merchant_lock_script(&SECP256K1_MULTISIG_CODE_HASH, ScriptHashType::Type);
+    let err = context
+        .verify_tx(&commitment(legacy_lock, &multisig_config), 10_000_000)
+        .expect_err("Legacy merchant lock on a V2 channel should fail");
+    assert_script_error(err, ERROR_MERCHANT_PUBKEY_HASH_MISMATCH);
+
+    // A V2 config that does not hash to the merchant_lock_arg in args (threshold 1 instead of 2)
+    let mut mismatched_config = multisig_config.clone();
+    mismatched_config[2] = 1;
+    let err = context
+        .verify_tx(&commitment(v2_lock, &mismatched_config), 10_000_000)
+        .expect_err("mismatched V2 multisig config should fail");
+    assert_script_error(err, ERROR_INVALID_MULTISIG_CONFIG);
+}
+
 #[test]
 fn test_spillman_lock_timeout_path_with_multisig_merchant() {
     let mut context = Context::default();
@@ -1252,6 +1500,128 @@ fn test_spillman_lock_timeout_path_with_multisig_merchant() {
     println!("error (timeout not reached): {:?}", err);
 }
 
+#[test]
+fn test_spillman_lock_multisig_v2_config_from_sdk() {
+    // The V2 multisig config exactly as the off-chain SDK serializes it: S stays 0
+    // (reserved) for V2 too, V2 is only told apart by algorithm_id and the lock script
+    use ckb_sdk::{constants::MultisigScript, unlock::MultisigConfig};
+
+    let mut context = Context::default();
+
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let merchant_key1 = Generator::random_keypair();
+    let merchant_key2 = Generator::random_keypair();
+    let merchant_key3 = Generator::random_keypair();
+    let user_key = Generator::random_keypair();
+
+    let sdk_config = MultisigConfig::new_with(
+        MultisigScript::V2,
+        vec![
+            blake160(&merchant_key1.1.serialize()),
+            blake160(&merchant_key2.1.serialize()),
+            blake160(&merchant_key3.1.serialize()),
+        ],
+        0,
+        2,
+    )
+    .expect("valid multisig config");
+    let multisig_config = sdk_config.to_witness_data();
+    assert_eq!(multisig_config[0], 0, "SDK writes S = 0 for V2");
+
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_timestamp = 1735689600u64; // 2025-01-01 00:00:00 UTC
+    let timeout_since =
+        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+    let algorithm_id: u8 = 7; // Multi-sig V2
+    let version: u8 = 0;
+
+    // The args commit to blake160 of the SDK bytes
+    let merchant_lock_arg = &blake2b_256(&multisig_config)[0..20];
+    let args = [
+        merchant_lock_arg,
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[algorithm_id],
+        &[version],
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let spillman_lock_dep = CellDep::new_builder()
+        .out_point(spillman_lock_out_point)
+        .build();
+    let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
+    let cell_deps = vec![spillman_lock_dep, auth_dep].pack();
+
+    let input_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(100_100_000_000u64.pack()) // 1001 CKB
+            .lock(lock_script.clone())
+            .build(),
+        Bytes::new(),
+    );
+
+    let since_value = Since::from_timestamp(timeout_timestamp + 86400, true).expect("valid since");
+    let input = CellInput::new_builder()
+        .previous_output(input_out_point)
+        .since(since_value.as_u64().pack())
+        .build();
+
+    // Refund: all funds go back to user
+    let outputs = vec![CellOutput::new_builder()
+        .capacity(100_000_000_000u64.pack()) // 1000 CKB (1 CKB fee)
+        .lock(user_lock_script)
+        .build()];
+    let outputs_data = vec![Bytes::new(); 1];
+
+    let success_tx = build_and_sign_tx_multisig(
+        cell_deps.clone(),
+        input.clone(),
+        outputs.clone(),
+        outputs_data.clone(),
+        UNLOCK_TYPE_TIMEOUT,
+        &user_key,
+        &[&merchant_key1, &merchant_key2],
+        &multisig_config,
+    );
+    let cycles = context
+        .verify_tx(&success_tx, 10_000_000)
+        .expect("SDK-produced V2 multisig config should pass");
+    println!("consume cycles (multisig v2 sdk config): {}", cycles);
+
+    // S = 1 is not a V2 marker: rejected before the config hash is even compared
+    let mut wrong_format = multisig_config.clone();
+    wrong_format[0] = 1;
+    let fail_tx = build_and_sign_tx_multisig(
+        cell_deps,
+        input,
+        outputs,
+        outputs_data,
+        UNLOCK_TYPE_TIMEOUT,
+        &user_key,
+        &[&merchant_key1, &merchant_key2],
+        &wrong_format,
+    );
+    let err = context
+        .verify_tx(&fail_tx, 10_000_000)
+        .expect_err("format_version 1 should fail");
+    assert_script_error(err, ERROR_INVALID_MULTISIG_CONFIG);
+}
+
 #[test]
 fn test_spillman_lock_multisig_error_scenarios() {
     let mut context = Context::default();
@@ -1416,6 +1786,156 @@ fn test_spillman_lock_multisig_error_scenarios() {
     println!("error (mismatched config): {:?}", err2);
 }
 
+#[test]
+fn test_spillman_lock_multisig_config_bounds() {
+    let mut context = Context::default();
+
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let merchant_key1 = Generator::random_keypair();
+    let merchant_key2 = Generator::random_keypair();
+    let user_key = Generator::random_keypair();
+
+    let merchant_pubkey_hash1 = blake160(&merchant_key1.1.serialize());
+    let merchant_pubkey_hash2 = blake160(&merchant_key2.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_since = Since::from_timestamp(1735689600u64, true).expect("valid timestamp since");
+    let algorithm_id: u8 = 6; // Multi-sig
+    let version: u8 = 0;
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let spillman_lock_dep = CellDep::new_builder()
+        .out_point(spillman_lock_out_point.clone())
+        .build();
+    let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
+    let cell_deps = vec![spillman_lock_dep, auth_dep].pack();
+
+    // (case, multisig config, merchant signers, expected error)
+    // Each channel is locked to blake160 of its own config, so the config hash matches
+    // and only the header bounds or the pubkey hash entries can reject it
+    let cases: Vec<(&str, Vec<u8>, Vec<_>, i8)> = vec![
+        (
+            "N=0",
+            vec![0u8, 0, 1, 0],
+            vec![&merchant_key1],
+            ERROR_INVALID_MULTISIG_CONFIG,
+        ),
+        (
+            "M=0",
+            [
+                &[0u8, 0, 0, 2][..],
+                merchant_pubkey_hash1.as_ref(),
+                merchant_pubkey_hash2.as_ref(),
+            ]
+            .concat(),
+            vec![&merchant_key1],
+            ERROR_INVALID_MULTISIG_CONFIG,
+        ),
+        (
+            "M>N",
+            [
+                &[0u8, 0, 3, 2][..],
+                merchant_pubkey_hash1.as_ref(),
+                merchant_pubkey_hash2.as_ref(),
+            ]
+            .concat(),
+            vec![&merchant_key1, &merchant_key2],
+            ERROR_INVALID_MULTISIG_CONFIG,
+        ),
+        (
+            // Claims 255 pubkeys, the most one byte holds, but carries 2: the
+            // witness is too short for N keys and is never read past its end
+            "oversized N",
+            [
+                &[0u8, 0, 1, 255][..],
+                merchant_pubkey_hash1.as_ref(),
+                merchant_pubkey_hash2.as_ref(),
+            ]
+            .concat(),
+            vec![&merchant_key1],
+            ERROR_WITNESS_LEN,
+        ),
+        (
+            // 2-of-3 where key 1 is listed twice: its signature must not count twice
+            "duplicate pubkey hash",
+            [
+                &[0u8, 0, 2, 3][..],
+                merchant_pubkey_hash1.as_ref(),
+                merchant_pubkey_hash1.as_ref(),
+                merchant_pubkey_hash2.as_ref(),
+            ]
+            .concat(),
+            vec![&merchant_key1, &merchant_key1],
+            ERROR_INVALID_MULTISIG_CONFIG,
+        ),
+    ];
+
+    for (case, multisig_config, merchant_keys, expected_error) in cases {
+        let merchant_lock_arg = &blake2b_256(&multisig_config)[0..20];
+        let args = [
+            merchant_lock_arg,
+            user_pubkey_hash.as_ref(),
+            &timeout_since.as_u64().to_le_bytes(),
+            &[algorithm_id],
+            &[version],
+        ]
+        .concat();
+        let lock_script = context
+            .build_script(&spillman_lock_out_point, Bytes::from(args))
+            .expect("script");
+        let merchant_lock_script = Script::new_builder()
+            .code_hash(SECP256K1_MULTISIG_CODE_HASH.pack())
+            .hash_type(ScriptHashType::Type.into())
+            .args(Bytes::from(merchant_lock_arg.to_vec()).pack())
+            .build();
+
+        let input_out_point = context.create_cell(
+            CellOutput::new_builder()
+                .capacity(100_100_000_000u64.pack())
+                .lock(lock_script)
+                .build(),
+            Bytes::new(),
+        );
+        let input = CellInput::new_builder()
+            .previous_output(input_out_point)
+            .build();
+        let outputs = vec![
+            CellOutput::new_builder()
+                .capacity(50_000_000_000u64.pack())
+                .lock(user_lock_script.clone())
+                .build(),
+            CellOutput::new_builder()
+                .capacity(50_000_000_000u64.pack())
+                .lock(merchant_lock_script)
+                .build(),
+        ];
+
+        let fail_tx = build_and_sign_tx_multisig(
+            cell_deps.clone(),
+            input,
+            outputs,
+            vec![Bytes::new(); 2],
+            UNLOCK_TYPE_COMMITMENT,
+            &user_key,
+            &merchant_keys,
+            &multisig_config,
+        );
+
+        let err = context.verify_tx(&fail_tx, 10_000_000).expect_err(case);
+        println!("error ({}): {:?}", case, err);
+        assert_script_error(err, expected_error);
+    }
+}
+
 // Helper function to build and sign transaction with multisig merchant
 #[allow(clippy::too_many_arguments)]
 fn build_and_sign_tx_multisig(
@@ -1514,14 +2034,36 @@ fn build_and_sign_tx(
     tx.as_advanced_builder().witness(witness.pack()).build()
 }
 
+fn assert_script_error(err: ckb_testtool::ckb_error::Error, err_code: i8) {
+    let error_string = err.to_string();
+    assert!(
+        error_string.contains(format!("error code {} ", err_code).as_str()),
+        "error_string: {}, expected_error_code: {}",
+        error_string,
+        err_code
+    );
+}
+
+/// Mirrors `spillman_signing_message` in the examples crate (`tx_builder::signing`)
 fn compute_signing_message(tx: &TransactionView) -> [u8; 32] {
-    let tx = tx
+    let raw_tx = tx
         .data()
         .raw()
         .as_builder()
         .cell_deps(Default::default())
         .build();
-    blake2b_256(tx.as_slice())
+    bind_to_funding_cell(tx, raw_tx.as_slice())
+}
+
+/// Signing message of version 1 args: the raw transaction including cell_deps
+fn compute_signing_message_with_cell_deps(tx: &TransactionView) -> [u8; 32] {
+    bind_to_funding_cell(tx, tx.data().raw().as_slice())
+}
+
+/// blake2b_256(OutPoint of input 0 || raw transaction)
+fn bind_to_funding_cell(tx: &TransactionView, raw_tx: &[u8]) -> [u8; 32] {
+    let out_point = tx.inputs().get(0).unwrap().previous_output();
+    blake2b_256([out_point.as_slice(), raw_tx].concat())
 }
 
 /// Test timeout path with timestamp-based since (instead of epoch-based)
@@ -1596,10 +2138,12 @@ fn test_spillman_lock_timeout_path_with_timestamp() {
         Bytes::new(),
     );
 
-    // Build refund transaction with timestamp since
+    // Build refund transaction with timestamp since (the earliest one past the grace window)
+    let refund_since = Since::from_timestamp(timeout_timestamp + TIMEOUT_GRACE_SECONDS, true)
+        .expect("valid timestamp since");
     let input = CellInput::new_builder()
         .previous_output(input_out_point.clone())
-        .since(timeout_since.as_u64().pack()) // Use timestamp since!
+        .since(refund_since.as_u64().pack()) // Use timestamp since!
         .build();
 
     // Calculate capacities
@@ -1736,51 +2280,317 @@ fn test_spillman_lock_timeout_path_with_timestamp() {
     println!("\n=== All Timestamp Since Tests Passed! ===\n");
 }
 
+/// Test timeout path with a relative timestamp since stored in args
+/// The refund window is tied to the funding cell's confirmation instead of an absolute clock
 #[test]
-fn test_spillman_lock_commitment_path_with_xudt() {
-    // Test commitment path with xUDT: merchant receives xUDT payment
+fn test_spillman_lock_timeout_path_with_relative_since() {
+    // deploy contract
     let mut context = Context::default();
     let loader = Loader::default();
     let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
     let auth_bin: Bytes = loader.load_binary("../../deps/auth");
-    let simple_udt_bin: Bytes = loader.load_binary("../../deps/simple_udt");
     let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
     let auth_out_point = context.deploy_cell(auth_bin);
-    let simple_udt_out_point = context.deploy_cell(simple_udt_bin);
 
     let mut generator = Generator::new();
     let user_key = generator.gen_keypair();
     let merchant_key = generator.gen_keypair();
 
-    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
-    let user_pubkey_hash = blake160(&user_key.1.serialize());
-    let timeout_timestamp = 1735689600u64;
+    // Refundable 1 day after the funding cell was committed
+    let relative_window = 24 * 60 * 60u64;
     let timeout_since =
-        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
-    let algorithm_id: u8 = 0;
-    let version: u8 = 0;
+        Since::from_timestamp(relative_window, false).expect("valid relative since");
 
-    let args = [
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let spillman_lock_args = [
         merchant_pubkey_hash.as_ref(),
         user_pubkey_hash.as_ref(),
         &timeout_since.as_u64().to_le_bytes(),
-        &[algorithm_id],
-        &[version],
+        &[0u8], // algorithm_id: single-sig
+        &[0u8], // version
     ]
     .concat();
 
-    let lock_script = context
-        .build_script(&spillman_lock_out_point, Bytes::from(args))
-        .expect("script");
-
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
     let user_lock_script = Script::new_builder()
         .code_hash(SECP256K1_CODE_HASH.pack())
         .hash_type(ScriptHashType::Type.into())
         .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
         .build();
 
-    let merchant_lock_script = Script::new_builder()
-        .code_hash(SECP256K1_CODE_HASH.pack())
+    let spillman_lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(spillman_lock_args))
+        .expect("script");
+
+    let cell_deps = CellDepVec::new_builder()
+        .push(
+            CellDep::new_builder()
+                .out_point(spillman_lock_out_point)
+                .build(),
+        )
+        .push(CellDep::new_builder().out_point(auth_out_point).build())
+        .build();
+
+    let total_capacity = 500_0000_0000u64; // 500 CKB
+    let input_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(total_capacity.pack())
+            .lock(spillman_lock_script)
+            .build(),
+        Bytes::new(),
+    );
+
+    let merchant_lock_cell_capacity = {
+        use ckb_testtool::ckb_types::core::Capacity;
+        CellOutput::new_builder()
+            .capacity(0u64.pack())
+            .lock(merchant_lock_script.clone())
+            .build()
+            .occupied_capacity(Capacity::bytes(0).unwrap())
+            .unwrap()
+            .as_u64()
+    };
+    let outputs = vec![
+        CellOutput::new_builder()
+            .capacity((total_capacity - merchant_lock_cell_capacity).pack())
+            .lock(user_lock_script)
+            .build(),
+        CellOutput::new_builder()
+            .capacity(merchant_lock_cell_capacity.pack())
+            .lock(merchant_lock_script)
+            .build(),
+    ];
+    let outputs_data: Vec<Bytes> = vec![Bytes::new(), Bytes::new()];
+
+    let refund_with_since = |since: Since| {
+        let input = CellInput::new_builder()
+            .previous_output(input_out_point.clone())
+            .since(since.as_u64().pack())
+            .build();
+        build_and_sign_tx(
+            cell_deps.clone(),
+            input,
+            outputs.clone(),
+            outputs_data.clone(),
+            UNLOCK_TYPE_TIMEOUT,
+            &user_key,
+            &merchant_key,
+        )
+    };
+
+    // Relative since covering the full window plus the merchant's grace: pass
+    let tx = refund_with_since(
+        Since::from_timestamp(relative_window + TIMEOUT_GRACE_SECONDS, false).unwrap(),
+    );
+    context
+        .verify_tx(&tx, 10_000_000)
+        .expect("relative since == relative timeout + grace should pass");
+
+    let tx = refund_with_since(Since::from_timestamp(relative_window * 2, false).unwrap());
+    context
+        .verify_tx(&tx, 10_000_000)
+        .expect("relative since after the window should pass");
+
+    // Relative since shorter than the window: fail
+    let tx = refund_with_since(Since::from_timestamp(relative_window - 3600, false).unwrap());
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("relative since before the window should fail");
+    assert_script_error(err, ERROR_TIMEOUT_NOT_REACHED);
+
+    // Absolute since cannot satisfy a relative timeout, however large
+    let tx = refund_with_since(Since::from_timestamp(4_000_000_000, true).unwrap());
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("absolute since vs relative timeout should fail");
+    assert_script_error(err, ERROR_TIMEOUT_NOT_REACHED);
+
+    // Relative since with a different metric (blocks) is incomparable
+    let tx = refund_with_since(Since::from_block_number(relative_window, false).unwrap());
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("relative block since vs relative timestamp timeout should fail");
+    assert_script_error(err, ERROR_TIMEOUT_NOT_REACHED);
+}
+
+/// Test the merchant's grace window: refunds are rejected in [timeout, timeout + grace)
+/// and accepted from timeout + grace on, for every since metric
+#[test]
+fn test_spillman_lock_timeout_grace_period() {
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+    let cell_deps = CellDepVec::new_builder()
+        .push(
+            CellDep::new_builder()
+                .out_point(spillman_lock_out_point.clone())
+                .build(),
+        )
+        .push(CellDep::new_builder().out_point(auth_out_point).build())
+        .build();
+
+    // Refund the whole 500 CKB to the user from a channel with the given timeout
+    let mut refund = |timeout: Since, since: Since| {
+        let spillman_lock_args = [
+            merchant_pubkey_hash.as_ref(),
+            user_pubkey_hash.as_ref(),
+            &timeout.as_u64().to_le_bytes(),
+            &[0u8], // algorithm_id: single-sig
+            &[0u8], // version
+        ]
+        .concat();
+        let spillman_lock_script = context
+            .build_script(&spillman_lock_out_point, Bytes::from(spillman_lock_args))
+            .expect("script");
+        let total_capacity = 500_0000_0000u64;
+        let input_out_point = context.create_cell(
+            CellOutput::new_builder()
+                .capacity(total_capacity.pack())
+                .lock(spillman_lock_script)
+                .build(),
+            Bytes::new(),
+        );
+        let input = CellInput::new_builder()
+            .previous_output(input_out_point)
+            .since(since.as_u64().pack())
+            .build();
+        let outputs = vec![CellOutput::new_builder()
+            .capacity((total_capacity - 1000).pack())
+            .lock(user_lock_script.clone())
+            .build()];
+        build_and_sign_tx(
+            cell_deps.clone(),
+            input,
+            outputs,
+            vec![Bytes::new()],
+            UNLOCK_TYPE_TIMEOUT,
+            &user_key,
+            &merchant_key,
+        )
+    };
+
+    let timeout_timestamp = 1735689600u64;
+    let timestamp = |ts: u64| Since::from_timestamp(ts, true).unwrap();
+    let block = |number: u64| Since::from_block_number(number, true).unwrap();
+    let epoch = |index: u64, length: u64| {
+        Since::from_epoch(EpochNumberWithFraction::new(42, index, length), true)
+    };
+    let cases = [
+        // (timeout, since, accepted)
+        (
+            timestamp(timeout_timestamp),
+            timestamp(timeout_timestamp),
+            false,
+        ),
+        (
+            timestamp(timeout_timestamp),
+            timestamp(timeout_timestamp + TIMEOUT_GRACE_SECONDS - 1),
+            false,
+        ),
+        (
+            timestamp(timeout_timestamp),
+            timestamp(timeout_timestamp + TIMEOUT_GRACE_SECONDS),
+            true,
+        ),
+        (block(1000), block(1000), false),
+        (block(1000), block(1000 + TIMEOUT_GRACE_BLOCKS - 1), false),
+        (block(1000), block(1000 + TIMEOUT_GRACE_BLOCKS), true),
+        (epoch(0, 1), epoch(0, 1), false),
+        (
+            epoch(0, 1),
+            epoch(1, TIMEOUT_GRACE_EPOCH_DENOMINATOR * 2),
+            false,
+        ),
+        (epoch(0, 1), epoch(1, TIMEOUT_GRACE_EPOCH_DENOMINATOR), true),
+    ];
+
+    let txs: Vec<_> = cases
+        .iter()
+        .map(|(timeout, since, _)| refund(*timeout, *since))
+        .collect();
+    for ((timeout, since, accepted), tx) in cases.iter().zip(txs) {
+        let result = context.verify_tx(&tx, 10_000_000);
+        if *accepted {
+            result.unwrap_or_else(|err| {
+                panic!(
+                    "since {:#x} past timeout {:#x} + grace should pass: {:?}",
+                    since.as_u64(),
+                    timeout.as_u64(),
+                    err
+                )
+            });
+        } else {
+            let err = result.expect_err("refund within the grace window should fail");
+            assert_script_error(err, ERROR_TIMEOUT_NOT_REACHED);
+        }
+    }
+}
+
+#[test]
+fn test_spillman_lock_commitment_path_with_xudt() {
+    // Test commitment path with xUDT: merchant receives xUDT payment
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let simple_udt_bin: Bytes = loader.load_binary("../../deps/simple_udt");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+    let simple_udt_out_point = context.deploy_cell(simple_udt_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_timestamp = 1735689600u64;
+    let timeout_since =
+        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+    let algorithm_id: u8 = 0;
+    let version: u8 = 0;
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[algorithm_id],
+        &[version],
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
         .hash_type(ScriptHashType::Type.into())
         .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
         .build();
@@ -1884,6 +2694,130 @@ fn test_spillman_lock_commitment_path_with_xudt() {
     println!("error (merchant xUDT is 0): {:?}", err);
 }
 
+#[test]
+fn test_spillman_lock_commitment_path_xudt_conservation() {
+    // Output xUDT amounts must add up to the channel's input amount, even when the
+    // xUDT type script itself would accept the change (owner mode, or burning)
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let simple_udt_bin: Bytes = loader.load_binary("../../deps/simple_udt");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+    let simple_udt_out_point = context.deploy_cell(simple_udt_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_timestamp = 1735689600u64;
+    let timeout_since =
+        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+    let algorithm_id: u8 = 0;
+    let version: u8 = 0;
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[algorithm_id],
+        &[version],
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    // The channel cell's own lock is the UDT owner, so simple_udt runs in owner mode
+    // and accepts any output amount
+    let owner_lock_hash = lock_script.calc_script_hash();
+    let type_script = context
+        .build_script(&simple_udt_out_point, owner_lock_hash.as_bytes())
+        .expect("script");
+
+    let spillman_lock_dep = CellDep::new_builder()
+        .out_point(spillman_lock_out_point)
+        .build();
+    let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
+    let simple_udt_dep = CellDep::new_builder()
+        .out_point(simple_udt_out_point)
+        .build();
+    let cell_deps = vec![spillman_lock_dep, auth_dep, simple_udt_dep].pack();
+
+    let input_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(100_100_000_000u64.pack())
+            .lock(lock_script.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build(),
+        1000u128.to_le_bytes().to_vec().into(),
+    );
+    let input = CellInput::new_builder()
+        .previous_output(input_out_point)
+        .build();
+
+    let outputs = vec![
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(user_lock_script.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build(),
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(merchant_lock_script.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build(),
+    ];
+    let build_tx = |user_amount: u128, merchant_amount: u128| {
+        build_and_sign_tx(
+            cell_deps.clone(),
+            input.clone(),
+            outputs.clone(),
+            vec![
+                user_amount.to_le_bytes().to_vec().into(),
+                merchant_amount.to_le_bytes().to_vec().into(),
+            ],
+            UNLOCK_TYPE_COMMITMENT,
+            &user_key,
+            &merchant_key,
+        )
+    };
+
+    // Conserving: 300 + 700 == 1000
+    let cycles = context
+        .verify_tx(&build_tx(300, 700), 10_000_000)
+        .expect("pass verification");
+    println!("consume cycles (conserving xUDT commitment): {}", cycles);
+
+    // Inflating: 1000 + 700 > 1000
+    let err = context
+        .verify_tx(&build_tx(1000, 700), 10_000_000)
+        .expect_err("token-inflating commitment should fail");
+    assert_script_error(err, ERROR_XUDT_CONSERVATION_VIOLATION);
+
+    // Destroying: 200 + 700 < 1000
+    let err = context
+        .verify_tx(&build_tx(200, 700), 10_000_000)
+        .expect_err("token-destroying commitment should fail");
+    assert_script_error(err, ERROR_XUDT_CONSERVATION_VIOLATION);
+}
+
 #[test]
 fn test_spillman_lock_commitment_path_output_structure_errors() {
     // Test various output structure errors in commitment path
@@ -2056,9 +2990,17 @@ fn test_spillman_lock_commitment_path_output_structure_errors() {
     println!("error (Output 1 wrong): {:?}", err);
 }
 
+// Append the optional merchant output count byte to a signed Spillman witness
+fn with_merchant_output_count(tx: TransactionView, count: u8) -> TransactionView {
+    let mut witness = tx.witnesses().get(0).unwrap().raw_data().to_vec();
+    witness.push(count);
+    tx.as_advanced_builder()
+        .set_witnesses(vec![Bytes::from(witness).pack()])
+        .build()
+}
+
 #[test]
-fn test_spillman_lock_ommitment_path_witness_format_errors() {
-    // Test various witness format errors
+fn test_spillman_lock_commitment_path_multiple_merchant_outputs() {
     let mut context = Context::default();
     let loader = Loader::default();
     let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
@@ -2069,21 +3011,18 @@ fn test_spillman_lock_ommitment_path_witness_format_errors() {
     let mut generator = Generator::new();
     let user_key = generator.gen_keypair();
     let merchant_key = generator.gen_keypair();
+    let stranger_key = generator.gen_keypair();
 
     let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
     let user_pubkey_hash = blake160(&user_key.1.serialize());
-    let timeout_timestamp = 1735689600u64;
-    let timeout_since =
-        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
-    let algorithm_id: u8 = 0;
-    let version: u8 = 0;
+    let timeout_since = Since::from_timestamp(1735689600u64, true).expect("valid timestamp since");
 
     let args = [
         merchant_pubkey_hash.as_ref(),
         user_pubkey_hash.as_ref(),
         &timeout_since.as_u64().to_le_bytes(),
-        &[algorithm_id],
-        &[version],
+        &[0u8], // algorithm_id: single-sig
+        &[0u8], // version
     ]
     .concat();
 
@@ -2091,104 +3030,373 @@ fn test_spillman_lock_ommitment_path_witness_format_errors() {
         .build_script(&spillman_lock_out_point, Bytes::from(args))
         .expect("script");
 
-    let user_lock_script = Script::new_builder()
-        .code_hash(SECP256K1_CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
-        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
-        .build();
-
-    let merchant_lock_script = Script::new_builder()
-        .code_hash(SECP256K1_CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
-        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
-        .build();
+    let sighash_lock = |pubkey_hash: &[u8]| {
+        Script::new_builder()
+            .code_hash(SECP256K1_CODE_HASH.pack())
+            .hash_type(ScriptHashType::Type.into())
+            .args(Bytes::from(pubkey_hash.to_vec()).pack())
+            .build()
+    };
+    let user_lock_script = sighash_lock(user_pubkey_hash.as_ref());
+    let merchant_lock_script = sighash_lock(merchant_pubkey_hash.as_ref());
+    let stranger_lock_script = sighash_lock(blake160(&stranger_key.1.serialize()).as_ref());
 
-    let spillman_lock_dep = CellDep::new_builder()
-        .out_point(spillman_lock_out_point)
-        .build();
-    let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
-    let cell_deps = vec![spillman_lock_dep, auth_dep].pack();
+    let cell_deps = vec![
+        CellDep::new_builder()
+            .out_point(spillman_lock_out_point)
+            .build(),
+        CellDep::new_builder().out_point(auth_out_point).build(),
+    ]
+    .pack();
 
     let input_out_point = context.create_cell(
         CellOutput::new_builder()
-            .capacity(100_100_000_000u64.pack())
-            .lock(lock_script.clone())
+            .capacity(100_000_000_000u64.pack()) // 1000 CKB
+            .lock(lock_script)
             .build(),
         Bytes::new(),
     );
-
     let input = CellInput::new_builder()
         .previous_output(input_out_point)
         .build();
 
-    let outputs = vec![
+    let output = |capacity: u64, lock: &Script| {
         CellOutput::new_builder()
-            .capacity(50_000_000_000u64.pack())
-            .lock(user_lock_script.clone())
+            .capacity(capacity.pack())
+            .lock(lock.clone())
+            .build()
+    };
+    let sign = |outputs: Vec<CellOutput>| {
+        let outputs_len = outputs.len();
+        build_and_sign_tx(
+            cell_deps.clone(),
+            input.clone(),
+            outputs,
+            vec![Bytes::new(); outputs_len],
+            UNLOCK_TYPE_COMMITMENT,
+            &user_key,
+            &merchant_key,
+        )
+    };
+
+    // User 499.5 CKB, merchant 500 CKB (fee 0.5 CKB)
+    let one_merchant_output = vec![
+        output(49_950_000_000, &user_lock_script),
+        output(50_000_000_000, &merchant_lock_script),
+    ];
+    // Same payment split across two merchant outputs
+    let two_merchant_outputs = vec![
+        output(49_950_000_000, &user_lock_script),
+        output(30_000_000_000, &merchant_lock_script),
+        output(20_000_000_000, &merchant_lock_script),
+    ];
+
+    // Test 1: one merchant output with an explicit count of 1
+    let tx = with_merchant_output_count(sign(one_merchant_output.clone()), 1);
+    context
+        .verify_tx(&tx, 10_000_000)
+        .expect("one merchant output with count 1 should pass");
+
+    // Test 2: two merchant outputs with a count of 2
+    let tx = with_merchant_output_count(sign(two_merchant_outputs.clone()), 2);
+    context
+        .verify_tx(&tx, 10_000_000)
+        .expect("two merchant outputs with count 2 should pass");
+
+    // Test 3: without the count byte the default exactly-two-outputs rule applies
+    let tx = sign(two_merchant_outputs.clone());
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("three outputs without count byte should fail");
+    assert_script_error(err, ERROR_COMMITMENT_MUST_HAVE_EXACTLY_TWO_OUTPUTS);
+
+    // Test 4: the extra output is not the merchant's
+    let tx = with_merchant_output_count(
+        sign(vec![
+            output(49_950_000_000, &user_lock_script),
+            output(30_000_000_000, &merchant_lock_script),
+            output(20_000_000_000, &stranger_lock_script),
+        ]),
+        2,
+    );
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("extra output to a third party should fail");
+    assert_script_error(err, ERROR_MERCHANT_PUBKEY_HASH_MISMATCH);
+
+    // Test 5: count does not match the outputs
+    let tx = with_merchant_output_count(sign(one_merchant_output.clone()), 2);
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("count larger than merchant outputs should fail");
+    assert_script_error(err, ERROR_INVALID_MERCHANT_OUTPUT_COUNT);
+
+    let tx = with_merchant_output_count(sign(two_merchant_outputs), 1);
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("count smaller than merchant outputs should fail");
+    assert_script_error(err, ERROR_INVALID_MERCHANT_OUTPUT_COUNT);
+
+    // Test 6: zero merchant outputs is never valid
+    let tx = with_merchant_output_count(sign(one_merchant_output), 0);
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("count 0 should fail");
+    assert_script_error(err, ERROR_INVALID_MERCHANT_OUTPUT_COUNT);
+}
+
+/// Commitment path with the user lock override flag (high bit of the merchant output count
+/// byte): the user output may go to a fresh address the user signed for
+#[test]
+fn test_spillman_lock_commitment_path_user_lock_override() {
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+    let fresh_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_since = Since::from_timestamp(1735689600u64, true).expect("valid timestamp since");
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[0u8], // algorithm_id: single-sig
+        &[0u8], // version
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+
+    let sighash_lock = |pubkey_hash: &[u8]| {
+        Script::new_builder()
+            .code_hash(SECP256K1_CODE_HASH.pack())
+            .hash_type(ScriptHashType::Type.into())
+            .args(Bytes::from(pubkey_hash.to_vec()).pack())
+            .build()
+    };
+    let user_lock_script = sighash_lock(user_pubkey_hash.as_ref());
+    let merchant_lock_script = sighash_lock(merchant_pubkey_hash.as_ref());
+    let fresh_lock_script = sighash_lock(blake160(&fresh_key.1.serialize()).as_ref());
+
+    let cell_deps = vec![
+        CellDep::new_builder()
+            .out_point(spillman_lock_out_point)
             .build(),
+        CellDep::new_builder().out_point(auth_out_point).build(),
+    ]
+    .pack();
+
+    let input_out_point = context.create_cell(
         CellOutput::new_builder()
-            .capacity(50_000_000_000u64.pack())
-            .lock(merchant_lock_script)
+            .capacity(100_000_000_000u64.pack()) // 1000 CKB
+            .lock(lock_script)
             .build(),
-    ];
+        Bytes::new(),
+    );
+    let input = CellInput::new_builder()
+        .previous_output(input_out_point)
+        .build();
 
-    let outputs_data = vec![Bytes::new(); 2];
+    let output = |capacity: u64, lock: &Script| {
+        CellOutput::new_builder()
+            .capacity(capacity.pack())
+            .lock(lock.clone())
+            .build()
+    };
+    let sign = |outputs: Vec<CellOutput>| {
+        let outputs_len = outputs.len();
+        build_and_sign_tx(
+            cell_deps.clone(),
+            input.clone(),
+            outputs,
+            vec![Bytes::new(); outputs_len],
+            UNLOCK_TYPE_COMMITMENT,
+            &user_key,
+            &merchant_key,
+        )
+    };
+    // User 499.5 CKB to `user_lock`, merchant 500 CKB (fee 0.5 CKB)
+    let outputs = |user_lock: &Script| {
+        vec![
+            output(49_950_000_000, user_lock),
+            output(50_000_000_000, &merchant_lock_script),
+        ]
+    };
+    let override_one = COMMITMENT_FLAG_USER_LOCK_OVERRIDE | 1;
+
+    // Test 1: default destination, with and without the flag
+    let tx = sign(outputs(&user_lock_script));
+    context
+        .verify_tx(&tx, 10_000_000)
+        .expect("default user lock should pass");
+    let tx = with_merchant_output_count(sign(outputs(&user_lock_script)), override_one);
+    context
+        .verify_tx(&tx, 10_000_000)
+        .expect("default user lock with the override flag should pass");
+
+    // Test 2: a fresh user lock needs the flag
+    let err = context
+        .verify_tx(&sign(outputs(&fresh_lock_script)), 10_000_000)
+        .expect_err("fresh user lock without the flag should fail");
+    assert_script_error(err, ERROR_USER_PUBKEY_HASH_MISMATCH);
+
+    let tx = with_merchant_output_count(sign(outputs(&fresh_lock_script)), override_one);
+    context
+        .verify_tx(&tx, 10_000_000)
+        .expect("fresh user lock with the override flag should pass");
+
+    // Test 3: the flag combines with several merchant outputs
+    let tx = with_merchant_output_count(
+        sign(vec![
+            output(49_950_000_000, &fresh_lock_script),
+            output(30_000_000_000, &merchant_lock_script),
+            output(20_000_000_000, &merchant_lock_script),
+        ]),
+        COMMITMENT_FLAG_USER_LOCK_OVERRIDE | 2,
+    );
+    context
+        .verify_tx(&tx, 10_000_000)
+        .expect("override with two merchant outputs should pass");
 
-    let tx = TransactionBuilder::default()
-        .cell_deps(cell_deps.clone())
-        .input(input.clone())
-        .outputs(outputs.clone())
-        .outputs_data(outputs_data.clone().pack())
-        .build();
+    // Test 4: the user output cannot be redirected to the merchant
+    let tx = with_merchant_output_count(sign(outputs(&merchant_lock_script)), override_one);
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("user output to the merchant lock should fail");
+    assert_script_error(err, ERROR_USER_MERCHANT_LOCK_COLLISION);
 
-    // Test 1: Witness too short (less than min length)
-    let short_witness = [0u8; 10]; // Way too short
-    let fail_tx_1 = tx
+    // Test 5: the user signed the lock, so swapping it afterwards breaks the signature
+    let signed = with_merchant_output_count(sign(outputs(&fresh_lock_script)), override_one);
+    let tampered = signed
         .as_advanced_builder()
-        .witness(Bytes::from(short_witness.to_vec()).pack())
+        .set_outputs(outputs(&sighash_lock(&[0x42; 20])))
         .build();
-
     let err = context
-        .verify_tx(&fail_tx_1, 10_000_000)
-        .expect_err("short witness should fail");
-    println!("error (witness too short): {:?}", err);
+        .verify_tx(&tampered, 10_000_000)
+        .expect_err("user lock swapped after signing should fail");
+    assert_script_error(err, ERROR_AUTH);
+
+    // Test 6: the flag alone is no merchant output count
+    let tx = with_merchant_output_count(
+        sign(outputs(&fresh_lock_script)),
+        COMMITMENT_FLAG_USER_LOCK_OVERRIDE,
+    );
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("override flag with count 0 should fail");
+    assert_script_error(err, ERROR_INVALID_MERCHANT_OUTPUT_COUNT);
+}
 
-    // Test 2: Wrong empty_witness_args prefix
-    let message = compute_signing_message(&tx);
-    let user_signature = user_key
-        .0
-        .sign_recoverable(&message.into())
-        .unwrap()
-        .serialize();
-    let merchant_signature = merchant_key
-        .0
-        .sign_recoverable(&message.into())
-        .unwrap()
-        .serialize();
+#[test]
+fn test_spillman_lock_rejects_mismatched_auth_binary() {
+    // A cell dep posing as auth (same size, one byte patched) must not be spawned
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let mut fake_auth_bin = auth_bin.to_vec();
+    *fake_auth_bin.last_mut().unwrap() ^= 0xff;
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+    let fake_auth_out_point = context.deploy_cell(Bytes::from(fake_auth_bin));
 
-    let wrong_empty_witness_args = [99u8; 16]; // Wrong prefix
-    let wrong_witness = [
-        &wrong_empty_witness_args[..],
-        &[UNLOCK_TYPE_COMMITMENT][..],
-        &merchant_signature[..],
-        &user_signature[..],
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_since = Since::from_timestamp(1735689600u64, true).expect("valid timestamp since");
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[0u8], // algorithm_id: single-sig
+        &[0u8], // version
     ]
     .concat();
 
-    let fail_tx_2 = tx
-        .as_advanced_builder()
-        .witness(wrong_witness.pack())
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+
+    let sighash_lock = |pubkey_hash: &[u8]| {
+        Script::new_builder()
+            .code_hash(SECP256K1_CODE_HASH.pack())
+            .hash_type(ScriptHashType::Type.into())
+            .args(Bytes::from(pubkey_hash.to_vec()).pack())
+            .build()
+    };
+    let outputs = vec![
+        CellOutput::new_builder()
+            .capacity(49_950_000_000u64.pack())
+            .lock(sighash_lock(user_pubkey_hash.as_ref()))
+            .build(),
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(sighash_lock(merchant_pubkey_hash.as_ref()))
+            .build(),
+    ];
+
+    let input_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(100_000_000_000u64.pack()) // 1000 CKB
+            .lock(lock_script)
+            .build(),
+        Bytes::new(),
+    );
+    let input = CellInput::new_builder()
+        .previous_output(input_out_point)
         .build();
 
+    let spillman_lock_dep = CellDep::new_builder()
+        .out_point(spillman_lock_out_point)
+        .build();
+    let sign_with_auth_dep = |auth_dep: CellDep| {
+        build_and_sign_tx(
+            vec![spillman_lock_dep.clone(), auth_dep].pack(),
+            input.clone(),
+            outputs.clone(),
+            vec![Bytes::new(); 2],
+            UNLOCK_TYPE_COMMITMENT,
+            &user_key,
+            &merchant_key,
+        )
+    };
+
+    // Test 1: the pinned auth binary verifies the signatures
+    let tx = sign_with_auth_dep(CellDep::new_builder().out_point(auth_out_point).build());
+    context
+        .verify_tx(&tx, 10_000_000)
+        .expect("pinned auth binary should pass");
+
+    // Test 2: a substituted auth binary is rejected instead of spawned
+    let tx = sign_with_auth_dep(
+        CellDep::new_builder()
+            .out_point(fake_auth_out_point)
+            .build(),
+    );
     let err = context
-        .verify_tx(&fail_tx_2, 10_000_000)
-        .expect_err("wrong empty_witness_args should fail");
-    println!("error (wrong empty_witness_args): {:?}", err);
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("mismatched auth binary should fail");
+    assert_script_error(err, ERROR_AUTH);
 }
 
 #[test]
-fn test_spillman_lock_ommitment_path_args_validation_errors() {
-    // Test various args validation errors
+fn test_spillman_lock_commitment_path_excessive_fee() {
+    // Implicit fee (input - outputs) in commitment path must not exceed MAX_FEE (1 CKB)
     let mut context = Context::default();
     let loader = Loader::default();
     let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
@@ -2205,6 +3413,21 @@ fn test_spillman_lock_ommitment_path_args_validation_errors() {
     let timeout_timestamp = 1735689600u64;
     let timeout_since =
         Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+    let algorithm_id: u8 = 0;
+    let version: u8 = 0;
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[algorithm_id],
+        &[version],
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
 
     let user_lock_script = Script::new_builder()
         .code_hash(SECP256K1_CODE_HASH.pack())
@@ -2219,77 +3442,66 @@ fn test_spillman_lock_ommitment_path_args_validation_errors() {
         .build();
 
     let spillman_lock_dep = CellDep::new_builder()
-        .out_point(spillman_lock_out_point.clone())
+        .out_point(spillman_lock_out_point)
         .build();
     let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
     let cell_deps = vec![spillman_lock_dep, auth_dep].pack();
 
-    // Test 1: Args too short (not 50 bytes)
-    let short_args = vec![0u8; 20]; // Only 20 bytes
-    let lock_script_1 = context
-        .build_script(&spillman_lock_out_point, Bytes::from(short_args))
-        .expect("script");
-
-    let input_out_point_1 = context.create_cell(
+    let input_out_point = context.create_cell(
         CellOutput::new_builder()
-            .capacity(100_100_000_000u64.pack())
-            .lock(lock_script_1.clone())
+            .capacity(100_100_000_000u64.pack()) // 1001 CKB
+            .lock(lock_script.clone())
             .build(),
         Bytes::new(),
     );
 
-    let input_1 = CellInput::new_builder()
-        .previous_output(input_out_point_1)
+    let input = CellInput::new_builder()
+        .previous_output(input_out_point)
         .build();
 
-    let outputs = vec![
+    // Test 1: fee exactly at MAX_FEE (1 CKB) should pass
+    let outputs_max_fee = vec![
         CellOutput::new_builder()
-            .capacity(50_000_000_000u64.pack())
+            .capacity(50_000_000_000u64.pack()) // 500 CKB
             .lock(user_lock_script.clone())
             .build(),
         CellOutput::new_builder()
-            .capacity(50_000_000_000u64.pack())
+            .capacity(50_000_000_000u64.pack()) // 500 CKB
             .lock(merchant_lock_script.clone())
             .build(),
     ];
 
-    let fail_tx_1 = build_and_sign_tx(
+    let success_tx = build_and_sign_tx(
         cell_deps.clone(),
-        input_1,
-        outputs.clone(),
+        input.clone(),
+        outputs_max_fee,
         vec![Bytes::new(); 2],
         UNLOCK_TYPE_COMMITMENT,
         &user_key,
         &merchant_key,
     );
 
-    let err = context
-        .verify_tx(&fail_tx_1, 10_000_000)
-        .expect_err("args too short should fail");
-    println!("error (args too short): {:?}", err);
-
-    // Test 2: Args too long
-    let long_args = vec![0u8; 100]; // 100 bytes
-    let lock_script_2 = context
-        .build_script(&spillman_lock_out_point, Bytes::from(long_args))
-        .expect("script");
+    let cycles = context
+        .verify_tx(&success_tx, 10_000_000)
+        .expect("commitment with fee at MAX_FEE should pass");
+    println!("consume cycles (fee = MAX_FEE): {}", cycles);
 
-    let input_out_point_2 = context.create_cell(
+    // Test 2: user change shrunk by 100 CKB, implicit fee 101 CKB (should fail with ExcessiveFee)
+    let outputs_excessive_fee = vec![
         CellOutput::new_builder()
-            .capacity(100_100_000_000u64.pack())
-            .lock(lock_script_2.clone())
+            .capacity(40_000_000_000u64.pack()) // 400 CKB
+            .lock(user_lock_script.clone())
             .build(),
-        Bytes::new(),
-    );
-
-    let input_2 = CellInput::new_builder()
-        .previous_output(input_out_point_2)
-        .build();
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack()) // 500 CKB
+            .lock(merchant_lock_script.clone())
+            .build(),
+    ];
 
-    let fail_tx_2 = build_and_sign_tx(
+    let fail_tx = build_and_sign_tx(
         cell_deps.clone(),
-        input_2,
-        outputs.clone(),
+        input.clone(),
+        outputs_excessive_fee,
         vec![Bytes::new(); 2],
         UNLOCK_TYPE_COMMITMENT,
         &user_key,
@@ -2297,98 +3509,147 @@ fn test_spillman_lock_ommitment_path_args_validation_errors() {
     );
 
     let err = context
-        .verify_tx(&fail_tx_2, 10_000_000)
-        .expect_err("args too long should fail");
-    println!("error (args too long): {:?}", err);
-
-    // Test 3: Unsupported version (not 0)
-    let bad_version: u8 = 1; // Wrong version
-    let args_bad_version = [
-        merchant_pubkey_hash.as_ref(),
-        user_pubkey_hash.as_ref(),
-        &timeout_since.as_u64().to_le_bytes(),
-        &[0u8][..], // algorithm_id = 0
-        &[bad_version][..],
-    ]
-    .concat();
+        .verify_tx(&fail_tx, 10_000_000)
+        .expect_err("commitment with excessive implicit fee should fail");
+    println!("error (excessive fee): {:?}", err);
+}
 
-    let lock_script_3 = context
-        .build_script(&spillman_lock_out_point, Bytes::from(args_bad_version))
-        .expect("script");
-
-    let input_out_point_3 = context.create_cell(
-        CellOutput::new_builder()
-            .capacity(100_100_000_000u64.pack())
-            .lock(lock_script_3.clone())
-            .build(),
-        Bytes::new(),
-    );
-
-    let input_3 = CellInput::new_builder()
-        .previous_output(input_out_point_3)
-        .build();
+#[test]
+fn test_spillman_lock_commitment_path_output_below_min_capacity() {
+    // Each commitment output must hold at least its occupied capacity
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let simple_udt_bin: Bytes = loader.load_binary("../../deps/simple_udt");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+    let simple_udt_out_point = context.deploy_cell(simple_udt_bin);
 
-    let fail_tx_3 = build_and_sign_tx(
-        cell_deps.clone(),
-        input_3,
-        outputs.clone(),
-        vec![Bytes::new(); 2],
-        UNLOCK_TYPE_COMMITMENT,
-        &user_key,
-        &merchant_key,
-    );
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
 
-    let err = context
-        .verify_tx(&fail_tx_3, 10_000_000)
-        .expect_err("unsupported version should fail");
-    println!("error (unsupported version): {:?}", err);
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_since = Since::from_timestamp(1735689600u64, true).expect("valid timestamp since");
+    let algorithm_id: u8 = 0;
+    let version: u8 = 0;
 
-    // Test 4: Invalid algorithm_id
-    let invalid_algorithm_id: u8 = 99; // Not 0, 6, or 7
-    let args_bad_algorithm = [
+    let args = [
         merchant_pubkey_hash.as_ref(),
         user_pubkey_hash.as_ref(),
         &timeout_since.as_u64().to_le_bytes(),
-        &[invalid_algorithm_id][..],
-        &[0u8][..], // version = 0
+        &[algorithm_id],
+        &[version],
     ]
     .concat();
 
-    let lock_script_4 = context
-        .build_script(&spillman_lock_out_point, Bytes::from(args_bad_algorithm))
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+    let type_script = context
+        .build_script(&simple_udt_out_point, [42u8; 32].to_vec().into())
         .expect("script");
 
-    let input_out_point_4 = context.create_cell(
-        CellOutput::new_builder()
-            .capacity(100_100_000_000u64.pack())
-            .lock(lock_script_4.clone())
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let cell_deps = vec![
+        CellDep::new_builder()
+            .out_point(spillman_lock_out_point)
             .build(),
-        Bytes::new(),
-    );
+        CellDep::new_builder().out_point(auth_out_point).build(),
+        CellDep::new_builder()
+            .out_point(simple_udt_out_point)
+            .build(),
+    ]
+    .pack();
 
-    let input_4 = CellInput::new_builder()
-        .previous_output(input_out_point_4)
-        .build();
+    let input_capacity = 100_100_000_000u64; // 1001 CKB
+    let fee = 100_000_000u64; // 1 CKB
+    let occupied = |output: &CellOutput, data: &Bytes| -> u64 {
+        output
+            .occupied_capacity(ckb_testtool::ckb_types::core::Capacity::bytes(data.len()).unwrap())
+            .unwrap()
+            .as_u64()
+    };
+    // Commitment whose user output holds one shannon less than its occupied capacity;
+    // the merchant takes the rest, so only the minimum is violated
+    let build_tx = |context: &mut Context, type_script: Option<Script>, data: [Bytes; 2]| {
+        let input_out_point = context.create_cell(
+            CellOutput::new_builder()
+                .capacity(input_capacity.pack())
+                .lock(lock_script.clone())
+                .type_(type_script.clone().pack())
+                .build(),
+            if type_script.is_some() {
+                1000u128.to_le_bytes().to_vec().into()
+            } else {
+                Bytes::new()
+            },
+        );
+        let user_output = CellOutput::new_builder()
+            .lock(user_lock_script.clone())
+            .type_(type_script.clone().pack())
+            .build();
+        let user_capacity = occupied(&user_output, &data[0]) - 1;
+        let outputs = vec![
+            user_output
+                .as_builder()
+                .capacity(user_capacity.pack())
+                .build(),
+            CellOutput::new_builder()
+                .capacity((input_capacity - fee - user_capacity).pack())
+                .lock(merchant_lock_script.clone())
+                .type_(type_script.pack())
+                .build(),
+        ];
+        build_and_sign_tx(
+            cell_deps.clone(),
+            CellInput::new_builder()
+                .previous_output(input_out_point)
+                .build(),
+            outputs,
+            data.to_vec(),
+            UNLOCK_TYPE_COMMITMENT,
+            &user_key,
+            &merchant_key,
+        )
+    };
 
-    let fail_tx_4 = build_and_sign_tx(
-        cell_deps,
-        input_4,
-        outputs,
-        vec![Bytes::new(); 2],
-        UNLOCK_TYPE_COMMITMENT,
-        &user_key,
-        &merchant_key,
+    // Test 1: pure CKB channel, user output at 61 CKB - 1 shannon
+    let tx = build_tx(&mut context, None, [Bytes::new(), Bytes::new()]);
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("user output below its occupied capacity should fail");
+    assert_script_error(err, ERROR_OUTPUT_BELOW_MIN_CAPACITY);
+
+    // Test 2: xUDT channel, the minimum includes the type script and 16-byte amount
+    let tx = build_tx(
+        &mut context,
+        Some(type_script),
+        [
+            300u128.to_le_bytes().to_vec().into(),
+            700u128.to_le_bytes().to_vec().into(),
+        ],
     );
-
     let err = context
-        .verify_tx(&fail_tx_4, 10_000_000)
-        .expect_err("invalid algorithm_id should fail");
-    println!("error (invalid algorithm_id): {:?}", err);
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("xUDT user output below its occupied capacity should fail");
+    assert_script_error(err, ERROR_OUTPUT_BELOW_MIN_CAPACITY);
 }
 
 #[test]
-fn test_spillman_lock_commitment_path_multiple_inputs() {
-    // Test multiple inputs (should fail with Error::MultipleInputs)
+fn test_spillman_lock_commitment_path_rejects_input_since() {
     let mut context = Context::default();
     let loader = Loader::default();
     let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
@@ -2439,87 +3700,71 @@ fn test_spillman_lock_commitment_path_multiple_inputs() {
     let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
     let cell_deps = vec![spillman_lock_dep, auth_dep].pack();
 
-    // Create 2 inputs with the same lock script
-    let input_out_point_1 = context.create_cell(
-        CellOutput::new_builder()
-            .capacity(50_000_000_000u64.pack())
-            .lock(lock_script.clone())
-            .build(),
-        Bytes::new(),
-    );
-
-    let input_out_point_2 = context.create_cell(
+    let input_out_point = context.create_cell(
         CellOutput::new_builder()
-            .capacity(50_000_000_000u64.pack())
+            .capacity(100_100_000_000u64.pack()) // 1001 CKB
             .lock(lock_script.clone())
             .build(),
         Bytes::new(),
     );
 
-    let input_1 = CellInput::new_builder()
-        .previous_output(input_out_point_1)
-        .build();
-
-    let input_2 = CellInput::new_builder()
-        .previous_output(input_out_point_2)
-        .build();
-
     let outputs = vec![
         CellOutput::new_builder()
-            .capacity(50_000_000_000u64.pack())
+            .capacity(50_000_000_000u64.pack()) // 500 CKB
             .lock(user_lock_script.clone())
             .build(),
         CellOutput::new_builder()
-            .capacity(50_000_000_000u64.pack())
-            .lock(merchant_lock_script)
+            .capacity(50_000_000_000u64.pack()) // 500 CKB
+            .lock(merchant_lock_script.clone())
             .build(),
     ];
 
-    let outputs_data = vec![Bytes::new(); 2];
-
-    // Build transaction with 2 inputs
-    let tx = TransactionBuilder::default()
-        .cell_deps(cell_deps)
-        .inputs(vec![input_1, input_2]) // 2 inputs!
-        .outputs(outputs)
-        .outputs_data(outputs_data.pack())
-        .build();
-
-    let message = compute_signing_message(&tx);
-    let user_signature = user_key
-        .0
-        .sign_recoverable(&message.into())
-        .unwrap()
-        .serialize();
-    let merchant_signature = merchant_key
-        .0
-        .sign_recoverable(&message.into())
-        .unwrap()
-        .serialize();
-
-    let witness = [
-        &EMPTY_WITNESS_ARGS[..],
-        &[UNLOCK_TYPE_COMMITMENT][..],
-        &merchant_signature[..],
-        &user_signature[..],
-    ]
-    .concat();
+    // Any timelock on the commitment input is rejected, before or after the timeout
+    let early_since = Since::from_timestamp(timeout_timestamp - 86400, true).unwrap();
+    let late_since = Since::from_timestamp(timeout_timestamp + 86400, true).unwrap();
+    let block_since = Since::from_block_number(1, false).unwrap();
+    for since in [early_since, late_since, block_since] {
+        let input = CellInput::new_builder()
+            .previous_output(input_out_point.clone())
+            .since(since.as_u64().pack())
+            .build();
+        let fail_tx = build_and_sign_tx(
+            cell_deps.clone(),
+            input,
+            outputs.clone(),
+            vec![Bytes::new(); 2],
+            UNLOCK_TYPE_COMMITMENT,
+            &user_key,
+            &merchant_key,
+        );
+
+        let err = context
+            .verify_tx(&fail_tx, 10_000_000)
+            .expect_err("commitment with an input since should fail");
+        assert_script_error(err, ERROR_UNEXPECTED_SINCE_ON_COMMITMENT);
+    }
 
-    let fail_tx = tx
-        .as_advanced_builder()
-        .witness(witness.pack())
-        .witness(Bytes::new().pack()) // witness for 2nd input
+    // The same commitment without a since passes
+    let input = CellInput::new_builder()
+        .previous_output(input_out_point)
         .build();
-
-    let err = context
-        .verify_tx(&fail_tx, 10_000_000)
-        .expect_err("multiple inputs should fail");
-    println!("error (multiple inputs): {:?}", err);
+    let success_tx = build_and_sign_tx(
+        cell_deps,
+        input,
+        outputs,
+        vec![Bytes::new(); 2],
+        UNLOCK_TYPE_COMMITMENT,
+        &user_key,
+        &merchant_key,
+    );
+    context
+        .verify_tx(&success_tx, 10_000_000)
+        .expect("commitment without since should pass");
 }
 
 #[test]
-fn test_spillman_lock_timeout_path_too_many_outputs() {
-    // Test timeout path with 3+ outputs (should fail)
+fn test_spillman_lock_commitment_path_user_merchant_lock_collision() {
+    // Args where merchant lock arg == user pubkey hash: both outputs would go to the same lock
     let mut context = Context::default();
     let loader = Loader::default();
     let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
@@ -2529,9 +3774,7 @@ fn test_spillman_lock_timeout_path_too_many_outputs() {
 
     let mut generator = Generator::new();
     let user_key = generator.gen_keypair();
-    let merchant_key = generator.gen_keypair();
 
-    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
     let user_pubkey_hash = blake160(&user_key.1.serialize());
     let timeout_timestamp = 1735689600u64;
     let timeout_since =
@@ -2540,7 +3783,7 @@ fn test_spillman_lock_timeout_path_too_many_outputs() {
     let version: u8 = 0;
 
     let args = [
-        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(), // merchant lock arg == user pubkey hash
         user_pubkey_hash.as_ref(),
         &timeout_since.as_u64().to_le_bytes(),
         &[algorithm_id],
@@ -2558,12 +3801,6 @@ fn test_spillman_lock_timeout_path_too_many_outputs() {
         .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
         .build();
 
-    let merchant_lock_script = Script::new_builder()
-        .code_hash(SECP256K1_CODE_HASH.pack())
-        .hash_type(ScriptHashType::Type.into())
-        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
-        .build();
-
     let spillman_lock_dep = CellDep::new_builder()
         .out_point(spillman_lock_out_point)
         .build();
@@ -2578,58 +3815,48 @@ fn test_spillman_lock_timeout_path_too_many_outputs() {
         Bytes::new(),
     );
 
-    let since_timestamp = timeout_timestamp + 86400;
-    let since_value = Since::from_timestamp(since_timestamp, true).expect("valid since");
-
     let input = CellInput::new_builder()
         .previous_output(input_out_point)
-        .since(since_value.as_u64().pack())
         .build();
 
-    // 3 outputs (should fail, max is 2)
     let outputs = vec![
         CellOutput::new_builder()
-            .capacity(33_333_333_333u64.pack())
+            .capacity(50_000_000_000u64.pack())
             .lock(user_lock_script.clone())
             .build(),
         CellOutput::new_builder()
-            .capacity(33_333_333_333u64.pack())
-            .lock(merchant_lock_script)
-            .build(),
-        CellOutput::new_builder()
-            .capacity(33_333_333_333u64.pack())
+            .capacity(50_000_000_000u64.pack())
             .lock(user_lock_script.clone())
             .build(),
     ];
 
+    // The same key signs as both merchant and user
     let fail_tx = build_and_sign_tx(
         cell_deps,
         input,
         outputs,
-        vec![Bytes::new(); 3],
-        UNLOCK_TYPE_TIMEOUT,
+        vec![Bytes::new(); 2],
+        UNLOCK_TYPE_COMMITMENT,
+        &user_key,
         &user_key,
-        &merchant_key,
     );
 
     let err = context
         .verify_tx(&fail_tx, 10_000_000)
-        .expect_err("timeout with 3 outputs should fail");
-    println!("error (3 outputs in timeout): {:?}", err);
+        .expect_err("commitment with identical user and merchant locks should fail");
+    println!("error (user/merchant lock collision): {:?}", err);
+    assert_script_error(err, ERROR_USER_MERCHANT_LOCK_COLLISION);
 }
 
 #[test]
-fn test_spillman_lock_commitment_path_type_script_mandatory() {
-    // Test that when input has type script, outputs MUST also have type script
-    // This validates the security fix for xUDT asset protection
+fn test_spillman_lock_commitment_path_swapped_outputs() {
+    // Correct user and merchant locks, but merchant at index 0 and user at index 1
     let mut context = Context::default();
     let loader = Loader::default();
     let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
     let auth_bin: Bytes = loader.load_binary("../../deps/auth");
-    let simple_udt_bin: Bytes = loader.load_binary("../../deps/simple_udt");
     let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
     let auth_out_point = context.deploy_cell(auth_bin);
-    let simple_udt_out_point = context.deploy_cell(simple_udt_bin);
 
     let mut generator = Generator::new();
     let user_key = generator.gen_keypair();
@@ -2661,196 +3888,197 @@ fn test_spillman_lock_commitment_path_type_script_mandatory() {
         .hash_type(ScriptHashType::Type.into())
         .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
         .build();
-
     let merchant_lock_script = Script::new_builder()
         .code_hash(SECP256K1_CODE_HASH.pack())
         .hash_type(ScriptHashType::Type.into())
         .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
         .build();
 
-    // Create xUDT type script
-    let udt_owner_lock_hash = [42u8; 32];
-    let type_script = context
-        .build_script(&simple_udt_out_point, udt_owner_lock_hash.to_vec().into())
-        .expect("script");
-
     let spillman_lock_dep = CellDep::new_builder()
         .out_point(spillman_lock_out_point)
         .build();
     let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
-    let simple_udt_dep = CellDep::new_builder()
-        .out_point(simple_udt_out_point)
-        .build();
-    let cell_deps = vec![spillman_lock_dep, auth_dep, simple_udt_dep].pack();
-
-    let xudt_amount = 1000u128;
+    let cell_deps = vec![spillman_lock_dep, auth_dep].pack();
 
-    // Create input with xUDT type script
     let input_out_point = context.create_cell(
         CellOutput::new_builder()
             .capacity(100_100_000_000u64.pack())
             .lock(lock_script.clone())
-            .type_(Some(type_script.clone()).pack())
             .build(),
-        xudt_amount.to_le_bytes().to_vec().into(),
+        Bytes::new(),
     );
 
     let input = CellInput::new_builder()
         .previous_output(input_out_point)
         .build();
 
-    // Test 1: User output missing type script should fail
-    // This would cause xUDT to be lost permanently
-    let outputs_user_missing_type = vec![
+    let outputs = vec![
         CellOutput::new_builder()
             .capacity(50_000_000_000u64.pack())
-            .lock(user_lock_script.clone())
-            // Missing type script! xUDT would be lost
+            .lock(merchant_lock_script)
             .build(),
         CellOutput::new_builder()
             .capacity(50_000_000_000u64.pack())
-            .lock(merchant_lock_script.clone())
-            .type_(Some(type_script.clone()).pack())
+            .lock(user_lock_script)
             .build(),
     ];
 
-    let outputs_data_1: Vec<Bytes> = vec![
-        Bytes::new(), // no xUDT data
-        700u128.to_le_bytes().to_vec().into(),
-    ];
-
-    let fail_tx_1 = build_and_sign_tx(
-        cell_deps.clone(),
-        input.clone(),
-        outputs_user_missing_type,
-        outputs_data_1,
-        UNLOCK_TYPE_COMMITMENT,
-        &user_key,
-        &merchant_key,
-    );
+    let fail_tx = build_and_sign_tx(
+        cell_deps,
+        input,
+        outputs,
+        vec![Bytes::new(); 2],
+        UNLOCK_TYPE_COMMITMENT,
+        &user_key,
+        &merchant_key,
+    );
 
     let err = context
-        .verify_tx(&fail_tx_1, 10_000_000)
-        .expect_err("user output missing type script should fail");
-    println!("error (user missing type): {:?}", err);
+        .verify_tx(&fail_tx, 10_000_000)
+        .expect_err("commitment with swapped user and merchant outputs should fail");
+    println!("error (swapped outputs): {:?}", err);
+    assert_script_error(err, ERROR_USER_PUBKEY_HASH_MISMATCH);
+}
 
-    // Test 2: Merchant output missing type script should fail
-    // This would cause xUDT to be lost permanently
-    let outputs_merchant_missing_type = vec![
-        CellOutput::new_builder()
-            .capacity(50_000_000_000u64.pack())
-            .lock(user_lock_script.clone())
-            .type_(Some(type_script.clone()).pack())
-            .build(),
-        CellOutput::new_builder()
-            .capacity(50_000_000_000u64.pack())
-            .lock(merchant_lock_script.clone())
-            // Missing type script! Merchant's xUDT would be lost
-            .build(),
-    ];
+#[test]
+fn test_spillman_lock_ommitment_path_witness_format_errors() {
+    // Test various witness format errors
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
 
-    let outputs_data_2: Vec<Bytes> = vec![
-        300u128.to_le_bytes().to_vec().into(),
-        Bytes::new(), // no xUDT data
-    ];
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
 
-    let fail_tx_2 = build_and_sign_tx(
-        cell_deps.clone(),
-        input.clone(),
-        outputs_merchant_missing_type,
-        outputs_data_2,
-        UNLOCK_TYPE_COMMITMENT,
-        &user_key,
-        &merchant_key,
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_timestamp = 1735689600u64;
+    let timeout_since =
+        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+    let algorithm_id: u8 = 0;
+    let version: u8 = 0;
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[algorithm_id],
+        &[version],
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let spillman_lock_dep = CellDep::new_builder()
+        .out_point(spillman_lock_out_point)
+        .build();
+    let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
+    let cell_deps = vec![spillman_lock_dep, auth_dep].pack();
+
+    let input_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(100_100_000_000u64.pack())
+            .lock(lock_script.clone())
+            .build(),
+        Bytes::new(),
     );
 
-    let err = context
-        .verify_tx(&fail_tx_2, 10_000_000)
-        .expect_err("merchant output missing type script should fail");
-    println!("error (merchant missing type): {:?}", err);
+    let input = CellInput::new_builder()
+        .previous_output(input_out_point)
+        .build();
 
-    // Test 3: Both outputs missing type script should fail
-    // This would cause ALL xUDT to be lost permanently
-    let outputs_both_missing_type = vec![
+    let outputs = vec![
         CellOutput::new_builder()
             .capacity(50_000_000_000u64.pack())
             .lock(user_lock_script.clone())
-            // Missing type script!
             .build(),
         CellOutput::new_builder()
             .capacity(50_000_000_000u64.pack())
-            .lock(merchant_lock_script.clone())
-            // Missing type script!
+            .lock(merchant_lock_script)
             .build(),
     ];
 
-    let outputs_data_3: Vec<Bytes> = vec![
-        Bytes::new(), // no xUDT data
-        Bytes::new(), // no xUDT data
-    ];
+    let outputs_data = vec![Bytes::new(); 2];
 
-    let fail_tx_3 = build_and_sign_tx(
-        cell_deps.clone(),
-        input.clone(),
-        outputs_both_missing_type,
-        outputs_data_3,
-        UNLOCK_TYPE_COMMITMENT,
-        &user_key,
-        &merchant_key,
-    );
+    let tx = TransactionBuilder::default()
+        .cell_deps(cell_deps.clone())
+        .input(input.clone())
+        .outputs(outputs.clone())
+        .outputs_data(outputs_data.clone().pack())
+        .build();
+
+    // Test 1: Witness too short (less than min length)
+    let short_witness = [0u8; 10]; // Way too short
+    let fail_tx_1 = tx
+        .as_advanced_builder()
+        .witness(Bytes::from(short_witness.to_vec()).pack())
+        .build();
 
     let err = context
-        .verify_tx(&fail_tx_3, 10_000_000)
-        .expect_err("both outputs missing type script should fail");
-    println!("error (both missing type): {:?}", err);
+        .verify_tx(&fail_tx_1, 10_000_000)
+        .expect_err("short witness should fail");
+    println!("error (witness too short): {:?}", err);
 
-    // Test 4: Correct case - both outputs have type script (should pass)
-    let outputs_correct = vec![
-        CellOutput::new_builder()
-            .capacity(50_000_000_000u64.pack())
-            .lock(user_lock_script.clone())
-            .type_(Some(type_script.clone()).pack())
-            .build(),
-        CellOutput::new_builder()
-            .capacity(50_000_000_000u64.pack())
-            .lock(merchant_lock_script.clone())
-            .type_(Some(type_script.clone()).pack())
-            .build(),
-    ];
+    // Test 2: Wrong empty_witness_args prefix
+    let message = compute_signing_message(&tx);
+    let user_signature = user_key
+        .0
+        .sign_recoverable(&message.into())
+        .unwrap()
+        .serialize();
+    let merchant_signature = merchant_key
+        .0
+        .sign_recoverable(&message.into())
+        .unwrap()
+        .serialize();
 
-    let outputs_data_correct: Vec<Bytes> = vec![
-        300u128.to_le_bytes().to_vec().into(),
-        700u128.to_le_bytes().to_vec().into(),
-    ];
+    let wrong_empty_witness_args = [99u8; 16]; // Wrong prefix
+    let wrong_witness = [
+        &wrong_empty_witness_args[..],
+        &[UNLOCK_TYPE_COMMITMENT][..],
+        &merchant_signature[..],
+        &user_signature[..],
+    ]
+    .concat();
 
-    let success_tx = build_and_sign_tx(
-        cell_deps,
-        input,
-        outputs_correct,
-        outputs_data_correct,
-        UNLOCK_TYPE_COMMITMENT,
-        &user_key,
-        &merchant_key,
-    );
+    let fail_tx_2 = tx
+        .as_advanced_builder()
+        .witness(wrong_witness.pack())
+        .build();
 
-    let cycles = context
-        .verify_tx(&success_tx, 10_000_000)
-        .expect("both outputs with correct type script should pass");
-    println!("consume cycles (correct type scripts): {}", cycles);
+    let err = context
+        .verify_tx(&fail_tx_2, 10_000_000)
+        .expect_err("wrong empty_witness_args should fail");
+    println!("error (wrong empty_witness_args): {:?}", err);
 }
 
 #[test]
-fn test_spillman_lock_refund_path_type_script_mandatory() {
-    // Test that when input has type script, outputs MUST also have type script in refund path
-    // This validates the security fix for xUDT asset protection in timeout refund scenario
+fn test_spillman_lock_commitment_path_witness_args_wrapped() {
+    // The same signatures must verify in the bare layout and inside WitnessArgs.lock
     let mut context = Context::default();
     let loader = Loader::default();
     let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
     let auth_bin: Bytes = loader.load_binary("../../deps/auth");
-    let simple_udt_bin: Bytes = loader.load_binary("../../deps/simple_udt");
     let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
     let auth_out_point = context.deploy_cell(auth_bin);
-    let simple_udt_out_point = context.deploy_cell(simple_udt_bin);
 
     let mut generator = Generator::new();
     let user_key = generator.gen_keypair();
@@ -2858,18 +4086,14 @@ fn test_spillman_lock_refund_path_type_script_mandatory() {
 
     let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
     let user_pubkey_hash = blake160(&user_key.1.serialize());
-    let timeout_timestamp = 1735689600u64;
-    let timeout_since =
-        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
-    let algorithm_id: u8 = 0;
-    let version: u8 = 0;
+    let timeout_since = Since::from_timestamp(1735689600u64, true).expect("valid timestamp since");
 
     let args = [
         merchant_pubkey_hash.as_ref(),
         user_pubkey_hash.as_ref(),
         &timeout_since.as_u64().to_le_bytes(),
-        &[algorithm_id],
-        &[version],
+        &[0u8], // algorithm_id: single-sig
+        &[0u8], // version
     ]
     .concat();
 
@@ -2889,199 +4113,2713 @@ fn test_spillman_lock_refund_path_type_script_mandatory() {
         .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
         .build();
 
-    // Create xUDT type script
-    let udt_owner_lock_hash = [42u8; 32];
-    let type_script = context
-        .build_script(&simple_udt_out_point, udt_owner_lock_hash.to_vec().into())
-        .expect("script");
-
     let spillman_lock_dep = CellDep::new_builder()
         .out_point(spillman_lock_out_point)
         .build();
     let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
-    let simple_udt_dep = CellDep::new_builder()
-        .out_point(simple_udt_out_point)
-        .build();
-    let cell_deps = vec![spillman_lock_dep, auth_dep, simple_udt_dep].pack();
-
-    let xudt_amount = 1000u128;
-
-    // Calculate merchant cell capacity for co-funding scenario
-    let merchant_cell = CellOutput::new_builder()
-        .capacity(0u64.pack())
-        .lock(merchant_lock_script.clone())
-        .type_(Some(type_script.clone()).pack())
-        .build();
-    let merchant_occupied = merchant_cell
-        .occupied_capacity(ckb_testtool::ckb_types::core::Capacity::bytes(16).unwrap())
-        .unwrap();
-    let merchant_capacity_u64: u64 = merchant_occupied.as_u64();
-
-    let total_capacity = 100_000_000_000u64 + merchant_capacity_u64;
+    let cell_deps = vec![spillman_lock_dep, auth_dep].pack();
 
-    // Create input with xUDT type script
     let input_out_point = context.create_cell(
         CellOutput::new_builder()
-            .capacity(total_capacity.pack())
+            .capacity(100_100_000_000u64.pack())
             .lock(lock_script.clone())
-            .type_(Some(type_script.clone()).pack())
             .build(),
-        xudt_amount.to_le_bytes().to_vec().into(),
+        Bytes::new(),
     );
 
-    let since_timestamp = timeout_timestamp + 86400; // 1 day after timeout
-    let since_value = Since::from_timestamp(since_timestamp, true).expect("valid since");
-
     let input = CellInput::new_builder()
         .previous_output(input_out_point)
-        .since(since_value.as_u64().pack())
         .build();
 
-    // Test 1: User output missing type script should fail (single output scenario)
-    // For single output, use total_capacity minus fee
-    let outputs_user_missing_type = vec![CellOutput::new_builder()
-        .capacity((total_capacity - 100_000_000).pack()) // 1 CKB fee
-        .lock(user_lock_script.clone())
-        // Missing type script! xUDT would be lost
-        .build()];
+    let outputs = vec![
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(user_lock_script)
+            .build(),
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(merchant_lock_script)
+            .build(),
+    ];
 
-    let outputs_data_1: Vec<Bytes> = vec![Bytes::new()];
+    let outputs_data = vec![Bytes::new(); 2];
 
-    let fail_tx_1 = build_and_sign_tx(
-        cell_deps.clone(),
-        input.clone(),
-        outputs_user_missing_type,
-        outputs_data_1,
-        UNLOCK_TYPE_TIMEOUT,
+    // Test 1: Bare layout (EMPTY_WITNESS_ARGS + payload)
+    let bare_tx = build_and_sign_tx(
+        cell_deps,
+        input,
+        outputs,
+        outputs_data,
+        UNLOCK_TYPE_COMMITMENT,
         &user_key,
         &merchant_key,
     );
-
+    context
+        .verify_tx(&bare_tx, 10_000_000)
+        .expect("bare witness should pass verification");
+
+    // Test 2: Same payload wrapped in a proper WitnessArgs molecule
+    let bare_witness = bare_tx.witnesses().get(0).unwrap().raw_data();
+    let payload = bare_witness.slice(EMPTY_WITNESS_ARGS.len()..);
+    let wrapped_witness = WitnessArgs::new_builder()
+        .lock(Some(payload.clone()).pack())
+        .build();
+    let wrapped_tx = bare_tx
+        .as_advanced_builder()
+        .set_witnesses(vec![wrapped_witness.as_bytes().pack()])
+        .build();
+    context
+        .verify_tx(&wrapped_tx, 10_000_000)
+        .expect("WitnessArgs-wrapped witness should pass verification");
+
+    // Test 3: Wrapped witness with a tampered user signature still fails
+    let mut tampered_payload = payload.to_vec();
+    let last = tampered_payload.len() - 1;
+    tampered_payload[last] ^= 0xff;
+    let tampered_witness = WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(tampered_payload)).pack())
+        .build();
+    let tampered_tx = bare_tx
+        .as_advanced_builder()
+        .set_witnesses(vec![tampered_witness.as_bytes().pack()])
+        .build();
     let err = context
-        .verify_tx(&fail_tx_1, 10_000_000)
-        .expect_err("user output missing type script should fail");
-    println!("error (refund: user missing type): {:?}", err);
+        .verify_tx(&tampered_tx, 10_000_000)
+        .expect_err("tampered wrapped witness should fail");
+    println!("error (tampered wrapped witness): {:?}", err);
+}
 
-    // Test 2: User output missing type script in co-funding scenario
-    let outputs_user_missing_type_cofund = vec![
+#[test]
+fn test_spillman_lock_ommitment_path_args_validation_errors() {
+    // Test various args validation errors
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_timestamp = 1735689600u64;
+    let timeout_since =
+        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let spillman_lock_dep = CellDep::new_builder()
+        .out_point(spillman_lock_out_point.clone())
+        .build();
+    let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
+    let cell_deps = vec![spillman_lock_dep, auth_dep].pack();
+
+    // Test 1: Args too short (not 50 bytes)
+    let short_args = vec![0u8; 20]; // Only 20 bytes
+    let lock_script_1 = context
+        .build_script(&spillman_lock_out_point, Bytes::from(short_args))
+        .expect("script");
+
+    let input_out_point_1 = context.create_cell(
         CellOutput::new_builder()
-            .capacity((total_capacity - merchant_capacity_u64 - 100_000_000).pack())
+            .capacity(100_100_000_000u64.pack())
+            .lock(lock_script_1.clone())
+            .build(),
+        Bytes::new(),
+    );
+
+    let input_1 = CellInput::new_builder()
+        .previous_output(input_out_point_1)
+        .build();
+
+    let outputs = vec![
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
             .lock(user_lock_script.clone())
-            // Missing type script!
             .build(),
         CellOutput::new_builder()
-            .capacity(merchant_capacity_u64.pack())
+            .capacity(50_000_000_000u64.pack())
             .lock(merchant_lock_script.clone())
-            .type_(Some(type_script.clone()).pack())
             .build(),
     ];
 
-    let outputs_data_2: Vec<Bytes> = vec![Bytes::new(), 0u128.to_le_bytes().to_vec().into()];
+    let fail_tx_1 = build_and_sign_tx(
+        cell_deps.clone(),
+        input_1,
+        outputs.clone(),
+        vec![Bytes::new(); 2],
+        UNLOCK_TYPE_COMMITMENT,
+        &user_key,
+        &merchant_key,
+    );
+
+    let err = context
+        .verify_tx(&fail_tx_1, 10_000_000)
+        .expect_err("args too short should fail");
+    println!("error (args too short): {:?}", err);
+
+    // Test 2: Args too long
+    let long_args = vec![0u8; 100]; // 100 bytes
+    let lock_script_2 = context
+        .build_script(&spillman_lock_out_point, Bytes::from(long_args))
+        .expect("script");
+
+    let input_out_point_2 = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(100_100_000_000u64.pack())
+            .lock(lock_script_2.clone())
+            .build(),
+        Bytes::new(),
+    );
+
+    let input_2 = CellInput::new_builder()
+        .previous_output(input_out_point_2)
+        .build();
 
     let fail_tx_2 = build_and_sign_tx(
         cell_deps.clone(),
-        input.clone(),
-        outputs_user_missing_type_cofund,
-        outputs_data_2,
-        UNLOCK_TYPE_TIMEOUT,
+        input_2,
+        outputs.clone(),
+        vec![Bytes::new(); 2],
+        UNLOCK_TYPE_COMMITMENT,
         &user_key,
         &merchant_key,
     );
 
     let err = context
         .verify_tx(&fail_tx_2, 10_000_000)
-        .expect_err("co-funding: user output missing type script should fail");
-    println!("error (refund co-funding: user missing type): {:?}", err);
+        .expect_err("args too long should fail");
+    println!("error (args too long): {:?}", err);
 
-    // Test 3: Merchant output missing type script in co-funding scenario
-    let outputs_merchant_missing_type = vec![
-        CellOutput::new_builder()
-            .capacity((total_capacity - merchant_capacity_u64 - 100_000_000).pack())
-            .lock(user_lock_script.clone())
-            .type_(Some(type_script.clone()).pack())
-            .build(),
+    // Test 3: Unsupported version (not 0 or 1)
+    let bad_version: u8 = 2; // Wrong version
+    let args_bad_version = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[0u8][..], // algorithm_id = 0
+        &[bad_version][..],
+    ]
+    .concat();
+
+    let lock_script_3 = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args_bad_version))
+        .expect("script");
+
+    let input_out_point_3 = context.create_cell(
         CellOutput::new_builder()
-            .capacity(merchant_capacity_u64.pack())
-            .lock(merchant_lock_script.clone())
-            // Missing type script!
+            .capacity(100_100_000_000u64.pack())
+            .lock(lock_script_3.clone())
             .build(),
-    ];
+        Bytes::new(),
+    );
 
-    let outputs_data_3: Vec<Bytes> = vec![xudt_amount.to_le_bytes().to_vec().into(), Bytes::new()];
+    let input_3 = CellInput::new_builder()
+        .previous_output(input_out_point_3)
+        .build();
 
     let fail_tx_3 = build_and_sign_tx(
         cell_deps.clone(),
-        input.clone(),
-        outputs_merchant_missing_type,
-        outputs_data_3,
-        UNLOCK_TYPE_TIMEOUT,
+        input_3,
+        outputs.clone(),
+        vec![Bytes::new(); 2],
+        UNLOCK_TYPE_COMMITMENT,
         &user_key,
         &merchant_key,
     );
 
     let err = context
         .verify_tx(&fail_tx_3, 10_000_000)
-        .expect_err("co-funding: merchant output missing type script should fail");
-    println!(
-        "error (refund co-funding: merchant missing type): {:?}",
-        err
-    );
+        .expect_err("unsupported version should fail");
+    println!("error (unsupported version): {:?}", err);
+    assert_script_error(err, ERROR_UNSUPPORTED_VERSION);
 
-    // Test 4: Correct case - single output with type script (should pass)
-    let outputs_correct_single = vec![CellOutput::new_builder()
-        .capacity((total_capacity - 100_000_000).pack()) // 1 CKB fee
-        .lock(user_lock_script.clone())
-        .type_(Some(type_script.clone()).pack())
-        .build()];
+    // Test 4: Invalid algorithm_id
+    let invalid_algorithm_id: u8 = 99; // Not 0, 6, or 7
+    let args_bad_algorithm = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[invalid_algorithm_id][..],
+        &[0u8][..], // version = 0
+    ]
+    .concat();
 
-    let outputs_data_correct_single: Vec<Bytes> = vec![xudt_amount.to_le_bytes().to_vec().into()];
+    let lock_script_4 = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args_bad_algorithm))
+        .expect("script");
 
-    let success_tx_1 = build_and_sign_tx(
+    let input_out_point_4 = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(100_100_000_000u64.pack())
+            .lock(lock_script_4.clone())
+            .build(),
+        Bytes::new(),
+    );
+
+    let input_4 = CellInput::new_builder()
+        .previous_output(input_out_point_4)
+        .build();
+
+    let fail_tx_4 = build_and_sign_tx(
         cell_deps.clone(),
-        input.clone(),
-        outputs_correct_single,
-        outputs_data_correct_single,
-        UNLOCK_TYPE_TIMEOUT,
+        input_4,
+        outputs.clone(),
+        vec![Bytes::new(); 2],
+        UNLOCK_TYPE_COMMITMENT,
         &user_key,
         &merchant_key,
     );
 
-    let cycles = context
-        .verify_tx(&success_tx_1, 10_000_000)
-        .expect("single output with correct type script should pass");
-    println!("consume cycles (refund single output): {}", cycles);
+    let err = context
+        .verify_tx(&fail_tx_4, 10_000_000)
+        .expect_err("invalid algorithm_id should fail");
+    println!("error (invalid algorithm_id): {:?}", err);
+    assert_script_error(err, ERROR_INVALID_LOCK_ARGS);
 
-    // Test 5: Correct case - co-funding with both type scripts (should pass)
-    let outputs_correct_cofund = vec![
-        CellOutput::new_builder()
-            .capacity((total_capacity - merchant_capacity_u64 - 100_000_000).pack())
-            .lock(user_lock_script.clone())
-            .type_(Some(type_script.clone()).pack())
-            .build(),
-        CellOutput::new_builder()
-            .capacity(merchant_capacity_u64.pack())
-            .lock(merchant_lock_script.clone())
-            .type_(Some(type_script.clone()).pack())
-            .build(),
+    // Test 5: Known ckb-auth algorithm_id that Spillman Lock does not support
+    let unsupported_algorithm_id: u8 = 1;
+    let args_unsupported_algorithm = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[unsupported_algorithm_id][..],
+        &[0u8][..], // version = 0
+    ]
+    .concat();
+
+    let lock_script_5 = context
+        .build_script(
+            &spillman_lock_out_point,
+            Bytes::from(args_unsupported_algorithm),
+        )
+        .expect("script");
+
+    let input_out_point_5 = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(100_100_000_000u64.pack())
+            .lock(lock_script_5.clone())
+            .build(),
+        Bytes::new(),
+    );
+
+    let input_5 = CellInput::new_builder()
+        .previous_output(input_out_point_5)
+        .build();
+
+    let fail_tx_5 = build_and_sign_tx(
+        cell_deps,
+        input_5,
+        outputs,
+        vec![Bytes::new(); 2],
+        UNLOCK_TYPE_COMMITMENT,
+        &user_key,
+        &merchant_key,
+    );
+
+    let err = context
+        .verify_tx(&fail_tx_5, 10_000_000)
+        .expect_err("unsupported algorithm_id should fail");
+    println!("error (unsupported algorithm_id): {:?}", err);
+    assert_script_error(err, ERROR_UNSUPPORTED_AUTH_ALGORITHM);
+}
+
+#[test]
+fn test_spillman_lock_commitment_path_version_binds_cell_deps() {
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+    // An unrelated cell a relayer could add as an extra dep
+    let extra_out_point = context.deploy_cell(Bytes::from(vec![0u8; 8]));
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_since = Since::from_timestamp(1735689600u64, true).expect("valid timestamp since");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let spillman_lock_dep = CellDep::new_builder()
+        .out_point(spillman_lock_out_point.clone())
+        .build();
+    let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
+    let extra_dep = CellDep::new_builder().out_point(extra_out_point).build();
+    let cell_deps = vec![spillman_lock_dep, auth_dep].pack();
+
+    let outputs = vec![
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(user_lock_script)
+            .build(),
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(merchant_lock_script)
+            .build(),
+    ];
+
+    // Sign `tx` over `message` with both keys
+    let sign = |tx: &TransactionView, message: [u8; 32]| {
+        let user_signature = user_key
+            .0
+            .sign_recoverable(&message.into())
+            .unwrap()
+            .serialize();
+        let merchant_signature = merchant_key
+            .0
+            .sign_recoverable(&message.into())
+            .unwrap()
+            .serialize();
+        let witness = [
+            &EMPTY_WITNESS_ARGS[..],
+            &[UNLOCK_TYPE_COMMITMENT][..],
+            &merchant_signature[..],
+            &user_signature[..],
+        ]
+        .concat();
+        tx.as_advanced_builder().witness(witness.pack()).build()
+    };
+    // Append an extra cell dep after signing
+    let add_dep =
+        |tx: &TransactionView| tx.as_advanced_builder().cell_dep(extra_dep.clone()).build();
+
+    for version in [0u8, 1u8] {
+        let args = [
+            merchant_pubkey_hash.as_ref(),
+            user_pubkey_hash.as_ref(),
+            &timeout_since.as_u64().to_le_bytes(),
+            &[0u8][..], // algorithm_id = 0
+            &[version][..],
+        ]
+        .concat();
+        let lock_script = context
+            .build_script(&spillman_lock_out_point, Bytes::from(args))
+            .expect("script");
+        let input_out_point = context.create_cell(
+            CellOutput::new_builder()
+                .capacity(100_100_000_000u64.pack())
+                .lock(lock_script)
+                .build(),
+            Bytes::new(),
+        );
+        let tx = TransactionBuilder::default()
+            .cell_deps(cell_deps.clone())
+            .input(
+                CellInput::new_builder()
+                    .previous_output(input_out_point)
+                    .build(),
+            )
+            .outputs(outputs.clone())
+            .outputs_data(vec![Bytes::new(); 2].pack())
+            .build();
+
+        let detached = sign(&tx, compute_signing_message(&tx));
+        let bound = sign(&tx, compute_signing_message_with_cell_deps(&tx));
+
+        if version == 0 {
+            // Version 0: deps are not signed, so they can change afterwards
+            context
+                .verify_tx(&detached, 10_000_000)
+                .expect("version 0 signs without cell_deps");
+            context
+                .verify_tx(&add_dep(&detached), 10_000_000)
+                .expect("version 0 tolerates changed cell_deps");
+            let err = context
+                .verify_tx(&bound, 10_000_000)
+                .expect_err("version 0 rejects a message including cell_deps");
+            assert_script_error(err, ERROR_AUTH);
+        } else {
+            // Version 1: signatures commit to the exact deps
+            context
+                .verify_tx(&bound, 10_000_000)
+                .expect("version 1 signs with cell_deps");
+            let err = context
+                .verify_tx(&add_dep(&bound), 10_000_000)
+                .expect_err("version 1 rejects changed cell_deps");
+            assert_script_error(err, ERROR_AUTH);
+            let err = context
+                .verify_tx(&detached, 10_000_000)
+                .expect_err("version 1 rejects a message without cell_deps");
+            assert_script_error(err, ERROR_AUTH);
+        }
+    }
+}
+/// The version byte selects the signing scheme and the length selects the layout: versions
+/// 0 and 1 accept the fixed 50 bytes with or without the optional trailing fields, any other
+/// version is rejected before its length is looked at
+#[test]
+fn test_spillman_lock_args_version_layouts() {
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_timestamp = 1735689600u64;
+    let timeout_since =
+        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+    let fallback_since = Since::from_timestamp(timeout_timestamp + 30 * 24 * 3600, true)
+        .expect("valid timestamp since");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let spillman_lock_dep = CellDep::new_builder()
+        .out_point(spillman_lock_out_point.clone())
+        .build();
+    let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
+    let cell_deps = vec![spillman_lock_dep, auth_dep].pack();
+
+    let outputs = vec![
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(user_lock_script)
+            .build(),
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(merchant_lock_script)
+            .build(),
+    ];
+
+    // Commitment of a channel with `version` and `trailing` optional fields, signed with
+    // the message of that version
+    let mut build_tx = |version: u8, trailing: &[u8]| {
+        let args = [
+            merchant_pubkey_hash.as_ref(),
+            user_pubkey_hash.as_ref(),
+            &timeout_since.as_u64().to_le_bytes(),
+            &[0u8][..], // algorithm_id = 0
+            &[version][..],
+            trailing,
+        ]
+        .concat();
+        let lock_script = context
+            .build_script(&spillman_lock_out_point, Bytes::from(args))
+            .expect("script");
+        let input_out_point = context.create_cell(
+            CellOutput::new_builder()
+                .capacity(100_100_000_000u64.pack())
+                .lock(lock_script)
+                .build(),
+            Bytes::new(),
+        );
+        let tx = TransactionBuilder::default()
+            .cell_deps(cell_deps.clone())
+            .input(
+                CellInput::new_builder()
+                    .previous_output(input_out_point)
+                    .build(),
+            )
+            .outputs(outputs.clone())
+            .outputs_data(vec![Bytes::new(); 2].pack())
+            .build();
+
+        let message = if version & 0x7f == 1 {
+            compute_signing_message_with_cell_deps(&tx)
+        } else {
+            compute_signing_message(&tx)
+        };
+        let user_signature = user_key
+            .0
+            .sign_recoverable(&message.into())
+            .unwrap()
+            .serialize();
+        let merchant_signature = merchant_key
+            .0
+            .sign_recoverable(&message.into())
+            .unwrap()
+            .serialize();
+        let witness = [
+            &EMPTY_WITNESS_ARGS[..],
+            &[UNLOCK_TYPE_COMMITMENT][..],
+            &merchant_signature[..],
+            &user_signature[..],
+        ]
+        .concat();
+        tx.as_advanced_builder().witness(witness.pack()).build()
+    };
+
+    let fallback = fallback_since.as_u64().to_le_bytes();
+    let user_algorithm_and_fallback = [&[0u8][..], &fallback[..]].concat();
+    let layouts: [&[u8]; 4] = [&[], &[0u8], &fallback, &user_algorithm_and_fallback];
+
+    // Test 1: versions 0 and 1 with the fixed layout and every optional field combination
+    let mut txs = Vec::new();
+    for version in [0u8, 1u8] {
+        for trailing in layouts {
+            txs.push((version, trailing.len(), build_tx(version, trailing)));
+        }
+    }
+    // Test 1b: the full extended tail (user algorithm, fallback timeout, arbiter) is not
+    // tied to version 1: version 0 args carrying it are accepted the same way, and a tail
+    // one byte too long is rejected by length for both versions
+    let arbiter_pubkey_hash = [0x5au8; 20];
+    let extended_tail = [&user_algorithm_and_fallback[..], &arbiter_pubkey_hash[..]].concat();
+    let overlong_tail = [&extended_tail[..], &[0u8][..]].concat();
+    for version in [0u8, 1u8] {
+        txs.push((
+            version,
+            extended_tail.len(),
+            build_tx(version, &extended_tail),
+        ));
+    }
+    let overlong: Vec<_> = [0u8, 1u8]
+        .into_iter()
+        .map(|version| (version, build_tx(version, &overlong_tail)))
+        .collect();
+    // Test 2: version 2 with the same layouts, and with a trailing length no version accepts
+    let mut unsupported = Vec::new();
+    for trailing in layouts.into_iter().chain([&[0u8; 3][..]]) {
+        unsupported.push((2u8, trailing.len(), build_tx(2, trailing)));
+    }
+    // The user-only refund flag does not turn version 2 into a known one
+    unsupported.push((0x82, 0, build_tx(0x82, &[])));
+
+    for (version, trailing_len, tx) in txs {
+        context.verify_tx(&tx, 10_000_000).unwrap_or_else(|e| {
+            panic!(
+                "version {} with {} trailing bytes should pass: {:?}",
+                version, trailing_len, e
+            )
+        });
+    }
+    for (version, trailing_len, tx) in unsupported {
+        let err = context
+            .verify_tx(&tx, 10_000_000)
+            .expect_err("unsupported version should fail");
+        println!(
+            "error (version {:#x}, {} trailing bytes): {:?}",
+            version, trailing_len, err
+        );
+        assert_script_error(err, ERROR_UNSUPPORTED_VERSION);
+    }
+    for (version, tx) in overlong {
+        let err = context
+            .verify_tx(&tx, 10_000_000)
+            .expect_err("overlong tail should fail");
+        println!("error (version {}, overlong tail): {:?}", version, err);
+        assert_script_error(err, ERROR_ARGS_LEN);
+    }
+}
+
+#[test]
+fn test_spillman_lock_commitment_path_multiple_inputs() {
+    // Test multiple inputs (should fail with Error::MultipleInputs)
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_timestamp = 1735689600u64;
+    let timeout_since =
+        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+    let algorithm_id: u8 = 0;
+    let version: u8 = 0;
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[algorithm_id],
+        &[version],
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let spillman_lock_dep = CellDep::new_builder()
+        .out_point(spillman_lock_out_point)
+        .build();
+    let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
+    let cell_deps = vec![spillman_lock_dep, auth_dep].pack();
+
+    // Create 2 inputs with the same lock script
+    let input_out_point_1 = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(lock_script.clone())
+            .build(),
+        Bytes::new(),
+    );
+
+    let input_out_point_2 = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(lock_script.clone())
+            .build(),
+        Bytes::new(),
+    );
+
+    let input_1 = CellInput::new_builder()
+        .previous_output(input_out_point_1)
+        .build();
+
+    let input_2 = CellInput::new_builder()
+        .previous_output(input_out_point_2)
+        .build();
+
+    let outputs = vec![
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(user_lock_script.clone())
+            .build(),
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(merchant_lock_script)
+            .build(),
+    ];
+
+    let outputs_data = vec![Bytes::new(); 2];
+
+    // Build transaction with 2 inputs
+    let tx = TransactionBuilder::default()
+        .cell_deps(cell_deps)
+        .inputs(vec![input_1, input_2]) // 2 inputs!
+        .outputs(outputs)
+        .outputs_data(outputs_data.pack())
+        .build();
+
+    let message = compute_signing_message(&tx);
+    let user_signature = user_key
+        .0
+        .sign_recoverable(&message.into())
+        .unwrap()
+        .serialize();
+    let merchant_signature = merchant_key
+        .0
+        .sign_recoverable(&message.into())
+        .unwrap()
+        .serialize();
+
+    let witness = [
+        &EMPTY_WITNESS_ARGS[..],
+        &[UNLOCK_TYPE_COMMITMENT][..],
+        &merchant_signature[..],
+        &user_signature[..],
+    ]
+    .concat();
+
+    let fail_tx = tx
+        .as_advanced_builder()
+        .witness(witness.pack())
+        .witness(Bytes::new().pack()) // witness for 2nd input
+        .build();
+
+    let err = context
+        .verify_tx(&fail_tx, 10_000_000)
+        .expect_err("multiple inputs should fail");
+    println!("error (multiple inputs): {:?}", err);
+}
+
+#[test]
+fn test_spillman_lock_timeout_path_too_many_outputs() {
+    // Test timeout path with 3+ outputs (should fail)
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_timestamp = 1735689600u64;
+    let timeout_since =
+        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+    let algorithm_id: u8 = 0;
+    let version: u8 = 0;
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[algorithm_id],
+        &[version],
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let spillman_lock_dep = CellDep::new_builder()
+        .out_point(spillman_lock_out_point)
+        .build();
+    let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
+    let cell_deps = vec![spillman_lock_dep, auth_dep].pack();
+
+    let input_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(100_100_000_000u64.pack())
+            .lock(lock_script.clone())
+            .build(),
+        Bytes::new(),
+    );
+
+    let since_timestamp = timeout_timestamp + 86400;
+    let since_value = Since::from_timestamp(since_timestamp, true).expect("valid since");
+
+    let input = CellInput::new_builder()
+        .previous_output(input_out_point)
+        .since(since_value.as_u64().pack())
+        .build();
+
+    // 3 outputs (should fail, max is 2)
+    let outputs = vec![
+        CellOutput::new_builder()
+            .capacity(33_333_333_333u64.pack())
+            .lock(user_lock_script.clone())
+            .build(),
+        CellOutput::new_builder()
+            .capacity(33_333_333_333u64.pack())
+            .lock(merchant_lock_script)
+            .build(),
+        CellOutput::new_builder()
+            .capacity(33_333_333_333u64.pack())
+            .lock(user_lock_script.clone())
+            .build(),
+    ];
+
+    let fail_tx = build_and_sign_tx(
+        cell_deps,
+        input,
+        outputs,
+        vec![Bytes::new(); 3],
+        UNLOCK_TYPE_TIMEOUT,
+        &user_key,
+        &merchant_key,
+    );
+
+    let err = context
+        .verify_tx(&fail_tx, 10_000_000)
+        .expect_err("timeout with 3 outputs should fail");
+    println!("error (3 outputs in timeout): {:?}", err);
+}
+
+#[test]
+fn test_spillman_lock_timeout_path_with_fee_sponsor() {
+    // A third party pays the refund fee with its own input and takes change after the
+    // refund outputs, so the user gets the full channel capacity back
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+    let always_success_out_point = context.deploy_cell(ALWAYS_SUCCESS.clone());
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_timestamp = 1735689600u64;
+    let timeout_since =
+        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+    let algorithm_id: u8 = 0;
+    let version: u8 = 0;
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[algorithm_id],
+        &[version],
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+    let sponsor_lock_script = context
+        .build_script(&always_success_out_point, Bytes::from(vec![0x5a]))
+        .expect("script");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let cell_deps = vec![
+        CellDep::new_builder()
+            .out_point(spillman_lock_out_point)
+            .build(),
+        CellDep::new_builder().out_point(auth_out_point).build(),
+        CellDep::new_builder()
+            .out_point(always_success_out_point)
+            .build(),
+    ]
+    .pack();
+
+    let spillman_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(100_100_000_000u64.pack()) // 1001 CKB
+            .lock(lock_script)
+            .build(),
+        Bytes::new(),
+    );
+    let sponsor_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(20_000_000_000u64.pack()) // 200 CKB
+            .lock(sponsor_lock_script.clone())
+            .build(),
+        Bytes::new(),
+    );
+
+    let since_value = Since::from_timestamp(timeout_timestamp + 86400, true).expect("valid since");
+    let inputs = vec![
+        CellInput::new_builder()
+            .previous_output(spillman_out_point)
+            .since(since_value.as_u64().pack())
+            .build(),
+        CellInput::new_builder()
+            .previous_output(sponsor_out_point)
+            .build(),
+    ];
+
+    let output = |capacity: u64, lock: &Script| {
+        CellOutput::new_builder()
+            .capacity(capacity.pack())
+            .lock(lock.clone())
+            .build()
+    };
+    let sign_refund = |outputs: Vec<CellOutput>| {
+        let tx = TransactionBuilder::default()
+            .cell_deps(cell_deps.clone())
+            .inputs(inputs.clone())
+            .outputs_data(vec![Bytes::new(); outputs.len()].pack())
+            .outputs(outputs)
+            .build();
+        let message = compute_signing_message(&tx);
+        let sign = |key: &ckb_testtool::ckb_crypto::secp::Privkey| {
+            key.sign_recoverable(&message.into()).unwrap().serialize()
+        };
+        let witness = [
+            &EMPTY_WITNESS_ARGS[..],
+            &[UNLOCK_TYPE_TIMEOUT][..],
+            &sign(&merchant_key.0)[..],
+            &sign(&user_key.0)[..],
+        ]
+        .concat();
+        tx.as_advanced_builder()
+            .witness(witness.pack())
+            .witness(Bytes::new().pack())
+            .build()
+    };
+
+    // Test 1: user gets all 1001 CKB, the sponsor pays 0.01 CKB fee
+    let tx = sign_refund(vec![
+        output(100_100_000_000, &user_lock_script),
+        output(19_999_000_000, &sponsor_lock_script),
+    ]);
+    let cycles = context
+        .verify_tx(&tx, 10_000_000)
+        .expect("sponsored refund should pass");
+    println!("consume cycles (sponsored refund): {}", cycles);
+
+    // Test 2: sponsor change cannot hide a fee taken from the channel
+    // (user gets 900 CKB; the missing 101 CKB goes to the sponsor)
+    let tx = sign_refund(vec![
+        output(90_000_000_000, &user_lock_script),
+        output(30_000_000_000, &sponsor_lock_script),
+    ]);
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("channel fee above MAX_FEE should fail with a sponsor");
+    assert_script_error(err, ERROR_EXCESSIVE_FEE);
+
+    // Test 3: nothing but sponsor change may follow the sponsor outputs
+    let tx = sign_refund(vec![
+        output(100_000_000_000, &user_lock_script),
+        output(19_999_000_000, &sponsor_lock_script),
+        output(10_000_000, &user_lock_script),
+    ]);
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("output after sponsor change should fail");
+    assert_script_error(err, ERROR_REFUND_MUST_HAVE_ONE_OR_TWO_OUTPUTS);
+}
+
+#[test]
+fn test_spillman_lock_commitment_path_with_merchant_fee_input() {
+    // The user signs a two-output commitment; at settlement the merchant adds its own
+    // fee input and change without invalidating the user's signature
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+    let always_success_out_point = context.deploy_cell(ALWAYS_SUCCESS.clone());
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_since = Since::from_timestamp(1735689600u64, true).expect("valid timestamp since");
+    let algorithm_id: u8 = 0;
+    let version: u8 = 0;
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[algorithm_id],
+        &[version],
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+    // Stands in for the merchant's wallet lock guarding the fee input
+    let fee_lock_script = context
+        .build_script(&always_success_out_point, Bytes::from(vec![0x6b]))
+        .expect("script");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let cell_deps = vec![
+        CellDep::new_builder()
+            .out_point(spillman_lock_out_point)
+            .build(),
+        CellDep::new_builder().out_point(auth_out_point).build(),
+    ]
+    .pack();
+
+    let spillman_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(100_100_000_000u64.pack()) // 1001 CKB
+            .lock(lock_script)
+            .build(),
+        Bytes::new(),
+    );
+    let fee_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(20_000_000_000u64.pack()) // 200 CKB
+            .lock(fee_lock_script.clone())
+            .build(),
+        Bytes::new(),
+    );
+    let fee_input = CellInput::new_builder()
+        .previous_output(fee_out_point)
+        .build();
+    let always_success_dep = CellDep::new_builder()
+        .out_point(always_success_out_point)
+        .build();
+
+    let output = |capacity: u64, lock: &Script| {
+        CellOutput::new_builder()
+            .capacity(capacity.pack())
+            .lock(lock.clone())
+            .build()
+    };
+
+    // The user's commitment: 600 CKB back to the user, 400 CKB to the merchant, 1 CKB fee
+    let commitment = build_and_sign_tx(
+        cell_deps,
+        CellInput::new_builder()
+            .previous_output(spillman_out_point)
+            .build(),
+        vec![
+            output(60_000_000_000, &user_lock_script),
+            output(40_000_000_000, &merchant_lock_script),
+        ],
+        vec![Bytes::new(); 2],
+        UNLOCK_TYPE_COMMITMENT,
+        &user_key,
+        &merchant_key,
+    );
+    let user_message = compute_signing_message(&commitment);
+
+    // Settlement appends the fee input, its change and the fee lock's cell dep; the
+    // merchant signs the same message as the user
+    let settle = |change: CellOutput| {
+        commitment
+            .as_advanced_builder()
+            .cell_dep(always_success_dep.clone())
+            .input(fee_input.clone())
+            .output(change)
+            .output_data(Bytes::new().pack())
+            .witness(Bytes::new().pack())
+            .build()
+    };
+
+    // Test 1: the merchant pays an extra 0.01 CKB fee, both signatures stay valid
+    let tx = settle(output(19_999_000_000, &fee_lock_script));
+    assert_ne!(compute_signing_message(&tx), user_message);
+    let cycles = context
+        .verify_tx(&tx, 10_000_000)
+        .expect("commitment with a merchant fee input should pass");
+    println!("consume cycles (merchant fee input): {}", cycles);
+
+    // Test 2: change may only go to a fee input's lock
+    let tx = settle(output(19_999_000_000, &user_lock_script));
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("change not locked by a fee input should fail");
+    assert_script_error(err, ERROR_COMMITMENT_MUST_HAVE_EXACTLY_TWO_OUTPUTS);
+
+    // Test 3: without a fee input, a third output is still rejected
+    let tx = commitment
+        .as_advanced_builder()
+        .output(output(6_100_000_000, &fee_lock_script))
+        .output_data(Bytes::new().pack())
+        .build();
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("extra output without a fee input should fail");
+    assert_script_error(err, ERROR_COMMITMENT_MUST_HAVE_EXACTLY_TWO_OUTPUTS);
+
+    // Test 4: a fee-free commitment, the user gets exactly 601 CKB and the merchant's
+    // fee input pays the whole fee
+    let fee_free = build_and_sign_tx(
+        commitment.cell_deps(),
+        commitment.inputs().get(0).unwrap(),
+        vec![
+            output(60_100_000_000, &user_lock_script),
+            output(40_000_000_000, &merchant_lock_script),
+        ],
+        vec![Bytes::new(); 2],
+        UNLOCK_TYPE_COMMITMENT,
+        &user_key,
+        &merchant_key,
+    );
+    let tx = fee_free
+        .as_advanced_builder()
+        .cell_dep(always_success_dep.clone())
+        .input(fee_input.clone())
+        .output(output(19_999_000_000, &fee_lock_script))
+        .output_data(Bytes::new().pack())
+        .witness(Bytes::new().pack())
+        .build();
+    let cycles = context
+        .verify_tx(&tx, 10_000_000)
+        .expect("fee-free commitment settled with a merchant fee input should pass");
+    println!("consume cycles (fee-free commitment): {}", cycles);
+}
+
+#[test]
+fn test_spillman_lock_commitment_path_type_script_mandatory() {
+    // Test that when input has type script, outputs MUST also have type script
+    // This validates the security fix for xUDT asset protection
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let simple_udt_bin: Bytes = loader.load_binary("../../deps/simple_udt");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+    let simple_udt_out_point = context.deploy_cell(simple_udt_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_timestamp = 1735689600u64;
+    let timeout_since =
+        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+    let algorithm_id: u8 = 0;
+    let version: u8 = 0;
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[algorithm_id],
+        &[version],
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    // Create xUDT type script
+    let udt_owner_lock_hash = [42u8; 32];
+    let type_script = context
+        .build_script(&simple_udt_out_point, udt_owner_lock_hash.to_vec().into())
+        .expect("script");
+
+    let spillman_lock_dep = CellDep::new_builder()
+        .out_point(spillman_lock_out_point)
+        .build();
+    let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
+    let simple_udt_dep = CellDep::new_builder()
+        .out_point(simple_udt_out_point)
+        .build();
+    let cell_deps = vec![spillman_lock_dep, auth_dep, simple_udt_dep].pack();
+
+    let xudt_amount = 1000u128;
+
+    // Create input with xUDT type script
+    let input_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(100_100_000_000u64.pack())
+            .lock(lock_script.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build(),
+        xudt_amount.to_le_bytes().to_vec().into(),
+    );
+
+    let input = CellInput::new_builder()
+        .previous_output(input_out_point)
+        .build();
+
+    // Test 1: User output missing type script should fail
+    // This would cause xUDT to be lost permanently
+    let outputs_user_missing_type = vec![
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(user_lock_script.clone())
+            // Missing type script! xUDT would be lost
+            .build(),
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(merchant_lock_script.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build(),
+    ];
+
+    let outputs_data_1: Vec<Bytes> = vec![
+        Bytes::new(), // no xUDT data
+        700u128.to_le_bytes().to_vec().into(),
+    ];
+
+    let fail_tx_1 = build_and_sign_tx(
+        cell_deps.clone(),
+        input.clone(),
+        outputs_user_missing_type,
+        outputs_data_1,
+        UNLOCK_TYPE_COMMITMENT,
+        &user_key,
+        &merchant_key,
+    );
+
+    let err = context
+        .verify_tx(&fail_tx_1, 10_000_000)
+        .expect_err("user output missing type script should fail");
+    println!("error (user missing type): {:?}", err);
+
+    // Test 2: Merchant output missing type script should fail
+    // This would cause xUDT to be lost permanently
+    let outputs_merchant_missing_type = vec![
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(user_lock_script.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build(),
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(merchant_lock_script.clone())
+            // Missing type script! Merchant's xUDT would be lost
+            .build(),
+    ];
+
+    let outputs_data_2: Vec<Bytes> = vec![
+        300u128.to_le_bytes().to_vec().into(),
+        Bytes::new(), // no xUDT data
+    ];
+
+    let fail_tx_2 = build_and_sign_tx(
+        cell_deps.clone(),
+        input.clone(),
+        outputs_merchant_missing_type,
+        outputs_data_2,
+        UNLOCK_TYPE_COMMITMENT,
+        &user_key,
+        &merchant_key,
+    );
+
+    let err = context
+        .verify_tx(&fail_tx_2, 10_000_000)
+        .expect_err("merchant output missing type script should fail");
+    println!("error (merchant missing type): {:?}", err);
+
+    // Test 3: Both outputs missing type script should fail
+    // This would cause ALL xUDT to be lost permanently
+    let outputs_both_missing_type = vec![
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(user_lock_script.clone())
+            // Missing type script!
+            .build(),
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(merchant_lock_script.clone())
+            // Missing type script!
+            .build(),
+    ];
+
+    let outputs_data_3: Vec<Bytes> = vec![
+        Bytes::new(), // no xUDT data
+        Bytes::new(), // no xUDT data
+    ];
+
+    let fail_tx_3 = build_and_sign_tx(
+        cell_deps.clone(),
+        input.clone(),
+        outputs_both_missing_type,
+        outputs_data_3,
+        UNLOCK_TYPE_COMMITMENT,
+        &user_key,
+        &merchant_key,
+    );
+
+    let err = context
+        .verify_tx(&fail_tx_3, 10_000_000)
+        .expect_err("both outputs missing type script should fail");
+    println!("error (both missing type): {:?}", err);
+
+    // Test 4: Correct case - both outputs have type script (should pass)
+    let outputs_correct = vec![
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(user_lock_script.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build(),
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(merchant_lock_script.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build(),
+    ];
+
+    let outputs_data_correct: Vec<Bytes> = vec![
+        300u128.to_le_bytes().to_vec().into(),
+        700u128.to_le_bytes().to_vec().into(),
+    ];
+
+    let success_tx = build_and_sign_tx(
+        cell_deps,
+        input,
+        outputs_correct,
+        outputs_data_correct,
+        UNLOCK_TYPE_COMMITMENT,
+        &user_key,
+        &merchant_key,
+    );
+
+    let cycles = context
+        .verify_tx(&success_tx, 10_000_000)
+        .expect("both outputs with correct type script should pass");
+    println!("consume cycles (correct type scripts): {}", cycles);
+}
+
+#[test]
+fn test_spillman_lock_commitment_path_ckb_channel_rejects_merchant_type() {
+    // A pure CKB channel (input without type script) must not let the merchant
+    // output carry a type script: the merchant's CKB would end up bound to a type
+    // the channel never agreed on
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+    let always_success_out_point = context.deploy_cell(ALWAYS_SUCCESS.clone());
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_since = Since::from_timestamp(1735689600u64, true).expect("valid timestamp since");
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[0u8],
+        &[0u8],
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    // Always-success type, so only Spillman Lock can reject the transaction
+    let type_script = context
+        .build_script(&always_success_out_point, Bytes::new())
+        .expect("script");
+
+    let cell_deps = vec![
+        CellDep::new_builder()
+            .out_point(spillman_lock_out_point)
+            .build(),
+        CellDep::new_builder().out_point(auth_out_point).build(),
+        CellDep::new_builder()
+            .out_point(always_success_out_point)
+            .build(),
+    ]
+    .pack();
+
+    let input_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(100_100_000_000u64.pack())
+            .lock(lock_script)
+            .build(),
+        Bytes::new(),
+    );
+    let input = CellInput::new_builder()
+        .previous_output(input_out_point)
+        .build();
+
+    let build_tx = |merchant_type: Option<Script>| {
+        let outputs = vec![
+            CellOutput::new_builder()
+                .capacity(50_000_000_000u64.pack())
+                .lock(user_lock_script.clone())
+                .build(),
+            CellOutput::new_builder()
+                .capacity(50_000_000_000u64.pack())
+                .lock(merchant_lock_script.clone())
+                .type_(merchant_type.pack())
+                .build(),
+        ];
+        build_and_sign_tx(
+            cell_deps.clone(),
+            input.clone(),
+            outputs,
+            vec![Bytes::new(), Bytes::new()],
+            UNLOCK_TYPE_COMMITMENT,
+            &user_key,
+            &merchant_key,
+        )
+    };
+
+    // Merchant output carries a type script the input does not have
+    let err = context
+        .verify_tx(&build_tx(Some(type_script)), 10_000_000)
+        .expect_err("merchant output with unexpected type script should fail");
+    assert_script_error(err, ERROR_TYPE_SCRIPT_MISMATCH);
+
+    // Without the type script the same commitment passes
+    let cycles = context
+        .verify_tx(&build_tx(None), 10_000_000)
+        .expect("pure CKB commitment should pass");
+    println!("consume cycles (pure CKB commitment): {}", cycles);
+}
+
+#[test]
+fn test_spillman_lock_refund_path_type_script_mandatory() {
+    // Test that when input has type script, outputs MUST also have type script in refund path
+    // This validates the security fix for xUDT asset protection in timeout refund scenario
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let simple_udt_bin: Bytes = loader.load_binary("../../deps/simple_udt");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+    let simple_udt_out_point = context.deploy_cell(simple_udt_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_timestamp = 1735689600u64;
+    let timeout_since =
+        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+    let algorithm_id: u8 = 0;
+    let version: u8 = 0;
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[algorithm_id],
+        &[version],
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    // Create xUDT type script
+    let udt_owner_lock_hash = [42u8; 32];
+    let type_script = context
+        .build_script(&simple_udt_out_point, udt_owner_lock_hash.to_vec().into())
+        .expect("script");
+
+    let spillman_lock_dep = CellDep::new_builder()
+        .out_point(spillman_lock_out_point)
+        .build();
+    let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
+    let simple_udt_dep = CellDep::new_builder()
+        .out_point(simple_udt_out_point)
+        .build();
+    let cell_deps = vec![spillman_lock_dep, auth_dep, simple_udt_dep].pack();
+
+    let xudt_amount = 1000u128;
+
+    // Calculate merchant cell capacity for co-funding scenario
+    let merchant_cell = CellOutput::new_builder()
+        .capacity(0u64.pack())
+        .lock(merchant_lock_script.clone())
+        .type_(Some(type_script.clone()).pack())
+        .build();
+    let merchant_occupied = merchant_cell
+        .occupied_capacity(ckb_testtool::ckb_types::core::Capacity::bytes(16).unwrap())
+        .unwrap();
+    let merchant_capacity_u64: u64 = merchant_occupied.as_u64();
+
+    let total_capacity = 100_000_000_000u64 + merchant_capacity_u64;
+
+    // Create input with xUDT type script
+    let input_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(total_capacity.pack())
+            .lock(lock_script.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build(),
+        xudt_amount.to_le_bytes().to_vec().into(),
+    );
+
+    let since_timestamp = timeout_timestamp + 86400; // 1 day after timeout
+    let since_value = Since::from_timestamp(since_timestamp, true).expect("valid since");
+
+    let input = CellInput::new_builder()
+        .previous_output(input_out_point)
+        .since(since_value.as_u64().pack())
+        .build();
+
+    // Test 1: User output missing type script should fail (single output scenario)
+    // For single output, use total_capacity minus fee
+    let outputs_user_missing_type = vec![CellOutput::new_builder()
+        .capacity((total_capacity - 100_000_000).pack()) // 1 CKB fee
+        .lock(user_lock_script.clone())
+        // Missing type script! xUDT would be lost
+        .build()];
+
+    let outputs_data_1: Vec<Bytes> = vec![Bytes::new()];
+
+    let fail_tx_1 = build_and_sign_tx(
+        cell_deps.clone(),
+        input.clone(),
+        outputs_user_missing_type,
+        outputs_data_1,
+        UNLOCK_TYPE_TIMEOUT,
+        &user_key,
+        &merchant_key,
+    );
+
+    let err = context
+        .verify_tx(&fail_tx_1, 10_000_000)
+        .expect_err("user output missing type script should fail");
+    println!("error (refund: user missing type): {:?}", err);
+
+    // Test 2: User output missing type script in co-funding scenario
+    let outputs_user_missing_type_cofund = vec![
+        CellOutput::new_builder()
+            .capacity((total_capacity - merchant_capacity_u64 - 100_000_000).pack())
+            .lock(user_lock_script.clone())
+            // Missing type script!
+            .build(),
+        CellOutput::new_builder()
+            .capacity(merchant_capacity_u64.pack())
+            .lock(merchant_lock_script.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build(),
+    ];
+
+    let outputs_data_2: Vec<Bytes> = vec![Bytes::new(), 0u128.to_le_bytes().to_vec().into()];
+
+    let fail_tx_2 = build_and_sign_tx(
+        cell_deps.clone(),
+        input.clone(),
+        outputs_user_missing_type_cofund,
+        outputs_data_2,
+        UNLOCK_TYPE_TIMEOUT,
+        &user_key,
+        &merchant_key,
+    );
+
+    let err = context
+        .verify_tx(&fail_tx_2, 10_000_000)
+        .expect_err("co-funding: user output missing type script should fail");
+    println!("error (refund co-funding: user missing type): {:?}", err);
+
+    // Test 3: Merchant output missing type script in co-funding scenario
+    let outputs_merchant_missing_type = vec![
+        CellOutput::new_builder()
+            .capacity((total_capacity - merchant_capacity_u64 - 100_000_000).pack())
+            .lock(user_lock_script.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build(),
+        CellOutput::new_builder()
+            .capacity(merchant_capacity_u64.pack())
+            .lock(merchant_lock_script.clone())
+            // Missing type script!
+            .build(),
+    ];
+
+    let outputs_data_3: Vec<Bytes> = vec![xudt_amount.to_le_bytes().to_vec().into(), Bytes::new()];
+
+    let fail_tx_3 = build_and_sign_tx(
+        cell_deps.clone(),
+        input.clone(),
+        outputs_merchant_missing_type,
+        outputs_data_3,
+        UNLOCK_TYPE_TIMEOUT,
+        &user_key,
+        &merchant_key,
+    );
+
+    let err = context
+        .verify_tx(&fail_tx_3, 10_000_000)
+        .expect_err("co-funding: merchant output missing type script should fail");
+    println!(
+        "error (refund co-funding: merchant missing type): {:?}",
+        err
+    );
+
+    // Test 4: Correct case - single output with type script (should pass)
+    let outputs_correct_single = vec![CellOutput::new_builder()
+        .capacity((total_capacity - 100_000_000).pack()) // 1 CKB fee
+        .lock(user_lock_script.clone())
+        .type_(Some(type_script.clone()).pack())
+        .build()];
+
+    let outputs_data_correct_single: Vec<Bytes> = vec![xudt_amount.to_le_bytes().to_vec().into()];
+
+    let success_tx_1 = build_and_sign_tx(
+        cell_deps.clone(),
+        input.clone(),
+        outputs_correct_single,
+        outputs_data_correct_single,
+        UNLOCK_TYPE_TIMEOUT,
+        &user_key,
+        &merchant_key,
+    );
+
+    let cycles = context
+        .verify_tx(&success_tx_1, 10_000_000)
+        .expect("single output with correct type script should pass");
+    println!("consume cycles (refund single output): {}", cycles);
+
+    // Test 5: Correct case - co-funding with both type scripts (should pass)
+    let outputs_correct_cofund = vec![
+        CellOutput::new_builder()
+            .capacity((total_capacity - merchant_capacity_u64 - 100_000_000).pack())
+            .lock(user_lock_script.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build(),
+        CellOutput::new_builder()
+            .capacity(merchant_capacity_u64.pack())
+            .lock(merchant_lock_script.clone())
+            .type_(Some(type_script.clone()).pack())
+            .build(),
+    ];
+
+    let outputs_data_correct_cofund: Vec<Bytes> = vec![
+        xudt_amount.to_le_bytes().to_vec().into(),
+        0u128.to_le_bytes().to_vec().into(),
+    ];
+
+    let success_tx_2 = build_and_sign_tx(
+        cell_deps,
+        input,
+        outputs_correct_cofund,
+        outputs_data_correct_cofund,
+        UNLOCK_TYPE_TIMEOUT,
+        &user_key,
+        &merchant_key,
+    );
+
+    let cycles = context
+        .verify_tx(&success_tx_2, 10_000_000)
+        .expect("co-funding with correct type scripts should pass");
+    println!("consume cycles (refund co-funding): {}", cycles);
+}
+
+#[test]
+fn test_spillman_lock_splice_path() {
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let spillman_args = |user_pubkey_hash: &[u8], timeout: u64| -> Bytes {
+        [
+            merchant_pubkey_hash.as_ref(),
+            user_pubkey_hash,
+            &Since::from_timestamp(timeout, true)
+                .expect("valid timestamp since")
+                .as_u64()
+                .to_le_bytes(),
+            &[0u8],
+            &[0u8],
+        ]
+        .concat()
+        .into()
+    };
+
+    let lock_script = context
+        .build_script(
+            &spillman_lock_out_point,
+            spillman_args(user_pubkey_hash.as_ref(), 1735689600),
+        )
+        .expect("script");
+    // Same channel, timeout pushed back by 30 days
+    let new_lock_script = context
+        .build_script(
+            &spillman_lock_out_point,
+            spillman_args(user_pubkey_hash.as_ref(), 1735689600 + 30 * 86400),
+        )
+        .expect("script");
+
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let cell_deps = vec![
+        CellDep::new_builder()
+            .out_point(spillman_lock_out_point.clone())
+            .build(),
+        CellDep::new_builder().out_point(auth_out_point).build(),
+    ]
+    .pack();
+
+    let input_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(100_100_000_000u64.pack())
+            .lock(lock_script)
+            .build(),
+        Bytes::new(),
+    );
+    let input = CellInput::new_builder()
+        .previous_output(input_out_point)
+        .build();
+
+    let splice_outputs = |new_lock: Script, merchant_lock: Script| {
+        vec![
+            // User's remaining balance stays in the channel
+            CellOutput::new_builder()
+                .capacity(60_000_000_000u64.pack())
+                .lock(new_lock)
+                .build(),
+            // Merchant payout
+            CellOutput::new_builder()
+                .capacity(40_000_000_000u64.pack())
+                .lock(merchant_lock)
+                .build(),
+        ]
+    };
+    let sign = |outputs: Vec<CellOutput>| {
+        let outputs_data = vec![Bytes::new(); outputs.len()];
+        build_and_sign_tx(
+            cell_deps.clone(),
+            input.clone(),
+            outputs,
+            outputs_data,
+            UNLOCK_TYPE_SPLICE,
+            &user_key,
+            &merchant_key,
+        )
+    };
+
+    let tx = sign(splice_outputs(
+        new_lock_script.clone(),
+        merchant_lock_script.clone(),
+    ));
+    let cycles = context
+        .verify_tx(&tx, 10_000_000)
+        .expect("splice into the same channel should pass");
+    println!("consume cycles (splice): {}", cycles);
+
+    // The new Spillman cell must belong to the same user
+    let other_user_lock = context
+        .build_script(
+            &spillman_lock_out_point,
+            spillman_args(&[0x42u8; 20], 1735689600 + 30 * 86400),
+        )
+        .expect("script");
+    let err = context
+        .verify_tx(
+            &sign(splice_outputs(
+                other_user_lock,
+                merchant_lock_script.clone(),
+            )),
+            10_000_000,
+        )
+        .expect_err("splice to another user's channel should fail");
+    assert_script_error(err, ERROR_SPLICE_LOCK_MISMATCH);
+
+    // The payout must go to the merchant
+    let err = context
+        .verify_tx(
+            &sign(splice_outputs(
+                new_lock_script.clone(),
+                new_lock_script.clone(),
+            )),
+            10_000_000,
+        )
+        .expect_err("splice paying someone else should fail");
+    assert_script_error(err, ERROR_MERCHANT_PUBKEY_HASH_MISMATCH);
+
+    // No outputs beyond the new channel and the payout
+    let mut outputs = splice_outputs(new_lock_script, merchant_lock_script.clone());
+    outputs[1] = outputs[1]
+        .clone()
+        .as_builder()
+        .capacity(30_000_000_000u64.pack())
+        .build();
+    outputs.push(
+        CellOutput::new_builder()
+            .capacity(10_000_000_000u64.pack())
+            .lock(merchant_lock_script)
+            .build(),
+    );
+    let err = context
+        .verify_tx(&sign(outputs), 10_000_000)
+        .expect_err("splice with three outputs should fail");
+    assert_script_error(err, ERROR_SPLICE_MUST_HAVE_EXACTLY_TWO_OUTPUTS);
+}
+
+#[test]
+fn test_spillman_lock_signature_does_not_replay_across_channels() {
+    // Two channels between the same user and merchant with identical args (same
+    // timeout): a commitment signed for channel A must not unlock channel B, even
+    // when the two transactions are otherwise identical
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_since = Since::from_timestamp(1735689600u64, true).expect("valid timestamp since");
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[0u8],
+        &[0u8],
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let cell_deps = vec![
+        CellDep::new_builder()
+            .out_point(spillman_lock_out_point)
+            .build(),
+        CellDep::new_builder().out_point(auth_out_point).build(),
+    ]
+    .pack();
+
+    let funding_cell = CellOutput::new_builder()
+        .capacity(100_100_000_000u64.pack())
+        .lock(lock_script)
+        .build();
+    let channel_a = context.create_cell(funding_cell.clone(), Bytes::new());
+    let channel_b = context.create_cell(funding_cell, Bytes::new());
+
+    let outputs = vec![
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(user_lock_script)
+            .build(),
+        CellOutput::new_builder()
+            .capacity(50_000_000_000u64.pack())
+            .lock(merchant_lock_script)
+            .build(),
     ];
 
-    let outputs_data_correct_cofund: Vec<Bytes> = vec![
-        xudt_amount.to_le_bytes().to_vec().into(),
-        0u128.to_le_bytes().to_vec().into(),
+    let commitment_a = build_and_sign_tx(
+        cell_deps,
+        CellInput::new_builder().previous_output(channel_a).build(),
+        outputs,
+        vec![Bytes::new(), Bytes::new()],
+        UNLOCK_TYPE_COMMITMENT,
+        &user_key,
+        &merchant_key,
+    );
+    let cycles = context
+        .verify_tx(&commitment_a, 10_000_000)
+        .expect("commitment on its own channel should pass");
+    println!("consume cycles (commitment on channel A): {}", cycles);
+
+    // Same transaction and witness, spending channel B instead
+    let replayed = commitment_a
+        .as_advanced_builder()
+        .set_inputs(vec![CellInput::new_builder()
+            .previous_output(channel_b)
+            .build()])
+        .build();
+    assert_ne!(
+        compute_signing_message(&replayed),
+        compute_signing_message(&commitment_a)
+    );
+    let err = context
+        .verify_tx(&replayed, 10_000_000)
+        .expect_err("signature of channel A should not unlock channel B");
+    assert_script_error(err, ERROR_AUTH);
+}
+
+#[test]
+fn test_spillman_lock_user_only_refund() {
+    // Args with the user-only refund flag: after timeout + grace the user refunds with
+    // their signature alone, before it the timeout path stays closed
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_timestamp = 1735689600u64;
+    let timeout_since =
+        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+
+    let args = [
+        merchant_pubkey_hash.as_ref(),
+        user_pubkey_hash.as_ref(),
+        &timeout_since.as_u64().to_le_bytes(),
+        &[0u8],
+        &[VERSION_FLAG_USER_ONLY_REFUND],
+    ]
+    .concat();
+
+    let lock_script = context
+        .build_script(&spillman_lock_out_point, Bytes::from(args))
+        .expect("script");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let cell_deps: CellDepVec = vec![
+        CellDep::new_builder()
+            .out_point(spillman_lock_out_point)
+            .build(),
+        CellDep::new_builder().out_point(auth_out_point).build(),
+    ]
+    .pack();
+
+    let input_out_point = context.create_cell(
+        CellOutput::new_builder()
+            .capacity(500_0000_0000u64.pack())
+            .lock(lock_script)
+            .build(),
+        Bytes::new(),
+    );
+
+    // Refund to `outputs` signed by `signer` alone, spending the channel with `since`
+    let user_only_refund_to = |outputs: Vec<CellOutput>,
+                               since: u64,
+                               signer: &ckb_testtool::ckb_crypto::secp::Privkey|
+     -> TransactionView {
+        let outputs_data = vec![Bytes::new().pack(); outputs.len()];
+        let tx = TransactionBuilder::default()
+            .cell_deps(cell_deps.clone())
+            .input(
+                CellInput::new_builder()
+                    .previous_output(input_out_point.clone())
+                    .since(since.pack())
+                    .build(),
+            )
+            .outputs(outputs)
+            .outputs_data(outputs_data)
+            .build();
+        let signature = signer
+            .sign_recoverable(&compute_signing_message(&tx).into())
+            .unwrap()
+            .serialize();
+        let witness = [
+            &EMPTY_WITNESS_ARGS[..],
+            &[UNLOCK_TYPE_TIMEOUT][..],
+            &signature[..],
+        ]
+        .concat();
+        tx.as_advanced_builder().witness(witness.pack()).build()
+    };
+    let user_only_refund =
+        |since: u64, signer: &ckb_testtool::ckb_crypto::secp::Privkey| -> TransactionView {
+            let output = CellOutput::new_builder()
+                .capacity((500_0000_0000u64 - 1_000_000).pack())
+                .lock(user_lock_script.clone())
+                .build();
+            user_only_refund_to(vec![output], since, signer)
+        };
+
+    let refund_since = Since::from_timestamp(timeout_timestamp + TIMEOUT_GRACE_SECONDS, true)
+        .expect("valid timestamp since");
+    let tx = user_only_refund(refund_since.as_u64(), &user_key.0);
+    let cycles = context
+        .verify_tx(&tx, 10_000_000)
+        .expect("user-only refund after timeout should pass");
+    println!("consume cycles (user-only refund): {}", cycles);
+
+    // Before timeout + grace the user cannot refund
+    let tx = user_only_refund(timeout_since.as_u64(), &user_key.0);
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("user-only refund before timeout should fail");
+    assert_script_error(err, ERROR_TIMEOUT_NOT_REACHED);
+
+    // The one signature must still be the user's
+    let tx = user_only_refund(refund_since.as_u64(), &merchant_key.0);
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("refund signed by the merchant alone should fail");
+    assert_script_error(err, ERROR_AUTH);
+
+    // Without the merchant's signature a co-fund refund must pay the merchant lock from args
+    let merchant_out_lock = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(vec![0x33u8; 20]).pack())
+        .build();
+    let merchant_capacity = 61_0000_0000u64;
+    let outputs = vec![
+        CellOutput::new_builder()
+            .capacity((500_0000_0000u64 - merchant_capacity - 1_000_000).pack())
+            .lock(user_lock_script.clone())
+            .build(),
+        CellOutput::new_builder()
+            .capacity(merchant_capacity.pack())
+            .lock(merchant_out_lock)
+            .build(),
     ];
+    let tx = user_only_refund_to(outputs, refund_since.as_u64(), &user_key.0);
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("user-only refund to another merchant lock should fail");
+    assert_script_error(err, ERROR_MERCHANT_PUBKEY_HASH_MISMATCH);
+}
 
-    let success_tx_2 = build_and_sign_tx(
-        cell_deps,
-        input,
-        outputs_correct_cofund,
-        outputs_data_correct_cofund,
+#[test]
+fn test_spillman_lock_fallback_timeout_refund() {
+    // Args with a fallback timeout: from timeout + grace the refund needs both signatures,
+    // from fallback_timeout + grace the user's alone
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let timeout_timestamp = 1735689600u64;
+    let fallback_timestamp = timeout_timestamp + 30 * 24 * 3600;
+    let timeout_since =
+        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+
+    // 58-byte args: the trailing 8 bytes are the fallback timeout
+    let spillman_args = |fallback_timeout: u64| -> Bytes {
+        Bytes::from(
+            [
+                merchant_pubkey_hash.as_ref(),
+                user_pubkey_hash.as_ref(),
+                &timeout_since.as_u64().to_le_bytes(),
+                &[0u8],
+                &[0u8],
+                &fallback_timeout.to_le_bytes(),
+            ]
+            .concat(),
+        )
+    };
+    let fallback_since =
+        Since::from_timestamp(fallback_timestamp, true).expect("valid timestamp since");
+
+    let user_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let cell_deps: CellDepVec = vec![
+        CellDep::new_builder()
+            .out_point(spillman_lock_out_point.clone())
+            .build(),
+        CellDep::new_builder().out_point(auth_out_point).build(),
+    ]
+    .pack();
+
+    let mut create_channel = |args: Bytes| -> OutPoint {
+        let lock_script = context
+            .build_script(&spillman_lock_out_point, args)
+            .expect("script");
+        context.create_cell(
+            CellOutput::new_builder()
+                .capacity(500_0000_0000u64.pack())
+                .lock(lock_script)
+                .build(),
+            Bytes::new(),
+        )
+    };
+    let input_out_point = create_channel(spillman_args(fallback_since.as_u64()));
+    let same_deadline_out_point = create_channel(spillman_args(timeout_since.as_u64()));
+
+    let refund_input = |out_point: &OutPoint, since: u64| -> CellInput {
+        CellInput::new_builder()
+            .previous_output(out_point.clone())
+            .since(since.pack())
+            .build()
+    };
+    let refund_outputs = vec![CellOutput::new_builder()
+        .capacity((500_0000_0000u64 - 1_000_000).pack())
+        .lock(user_lock_script)
+        .build()];
+
+    // Refund signed by the user alone
+    let user_only_refund = |input: CellInput| -> TransactionView {
+        let tx = TransactionBuilder::default()
+            .cell_deps(cell_deps.clone())
+            .input(input)
+            .outputs(refund_outputs.clone())
+            .output_data(Bytes::new().pack())
+            .build();
+        let signature = user_key
+            .0
+            .sign_recoverable(&compute_signing_message(&tx).into())
+            .unwrap()
+            .serialize();
+        let witness = [
+            &EMPTY_WITNESS_ARGS[..],
+            &[UNLOCK_TYPE_TIMEOUT][..],
+            &signature[..],
+        ]
+        .concat();
+        tx.as_advanced_builder().witness(witness.pack()).build()
+    };
+
+    let refund_since = Since::from_timestamp(timeout_timestamp + TIMEOUT_GRACE_SECONDS, true)
+        .expect("valid timestamp since");
+    let fallback_refund_since =
+        Since::from_timestamp(fallback_timestamp + TIMEOUT_GRACE_SECONDS, true)
+            .expect("valid timestamp since");
+
+    // First deadline: both signatures refund, the user alone cannot yet
+    let tx = build_and_sign_tx(
+        cell_deps.clone(),
+        refund_input(&input_out_point, refund_since.as_u64()),
+        refund_outputs.clone(),
+        vec![Bytes::new()],
         UNLOCK_TYPE_TIMEOUT,
         &user_key,
         &merchant_key,
     );
+    let cycles = context
+        .verify_tx(&tx, 10_000_000)
+        .expect("two-signature refund after timeout should pass");
+    println!("consume cycles (two-signature refund): {}", cycles);
 
+    let tx = user_only_refund(refund_input(&input_out_point, refund_since.as_u64()));
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("user-only refund before the fallback timeout should fail");
+    assert_script_error(err, ERROR_TIMEOUT_NOT_REACHED);
+
+    // Second deadline: the user refunds alone
+    let tx = user_only_refund(refund_input(
+        &input_out_point,
+        fallback_refund_since.as_u64(),
+    ));
     let cycles = context
-        .verify_tx(&success_tx_2, 10_000_000)
-        .expect("co-funding with correct type scripts should pass");
-    println!("consume cycles (refund co-funding): {}", cycles);
+        .verify_tx(&tx, 10_000_000)
+        .expect("user-only refund after the fallback timeout should pass");
+    println!("consume cycles (fallback user-only refund): {}", cycles);
+
+    // A fallback timeout not later than the timeout is rejected
+    let tx = user_only_refund(refund_input(
+        &same_deadline_out_point,
+        fallback_refund_since.as_u64(),
+    ));
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("fallback timeout equal to the timeout should fail");
+    assert_script_error(err, ERROR_INVALID_LOCK_ARGS);
+}
+
+#[test]
+fn test_spillman_lock_arbitration_path() {
+    // Args with an arbiter: any two of user, merchant and arbiter close the channel with an
+    // agreed split, without waiting for the timeout
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+    let arbiter_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_pubkey_hash = blake160(&user_key.1.serialize());
+    let arbiter_pubkey_hash = blake160(&arbiter_key.1.serialize());
+    let timeout_since = Since::from_timestamp(1735689600u64, true).expect("valid timestamp since");
+
+    // 70-byte args: the trailing 20 bytes are the arbiter pubkey hash
+    let spillman_args = |arbiter: &[u8]| -> Bytes {
+        Bytes::from(
+            [
+                merchant_pubkey_hash.as_ref(),
+                user_pubkey_hash.as_ref(),
+                &timeout_since.as_u64().to_le_bytes(),
+                &[0u8],
+                &[0u8],
+                arbiter,
+            ]
+            .concat(),
+        )
+    };
+
+    let secp256k1_lock = |pubkey_hash: &[u8]| -> Script {
+        Script::new_builder()
+            .code_hash(SECP256K1_CODE_HASH.pack())
+            .hash_type(ScriptHashType::Type.into())
+            .args(Bytes::from(pubkey_hash.to_vec()).pack())
+            .build()
+    };
+
+    let cell_deps: CellDepVec = vec![
+        CellDep::new_builder()
+            .out_point(spillman_lock_out_point.clone())
+            .build(),
+        CellDep::new_builder().out_point(auth_out_point).build(),
+    ]
+    .pack();
+
+    let mut create_channel = |args: Bytes| -> OutPoint {
+        let lock_script = context
+            .build_script(&spillman_lock_out_point, args)
+            .expect("script");
+        context.create_cell(
+            CellOutput::new_builder()
+                .capacity(500_0000_0000u64.pack())
+                .lock(lock_script)
+                .build(),
+            Bytes::new(),
+        )
+    };
+    let input_out_point = create_channel(spillman_args(arbiter_pubkey_hash.as_ref()));
+    let no_arbiter_out_point = create_channel(spillman_args(&[]));
+
+    // Agreed split: 300 CKB back to the user, the rest to the merchant
+    let outputs = vec![
+        CellOutput::new_builder()
+            .capacity(300_0000_0000u64.pack())
+            .lock(secp256k1_lock(user_pubkey_hash.as_ref()))
+            .build(),
+        CellOutput::new_builder()
+            .capacity((200_0000_0000u64 - 1_000_000).pack())
+            .lock(secp256k1_lock(merchant_pubkey_hash.as_ref()))
+            .build(),
+    ];
+
+    // Signatures follow the witness order: merchant, user, arbiter
+    let arbitration_tx = |out_point: &OutPoint,
+                          signers: u8,
+                          keys: &[&(
+        ckb_testtool::ckb_crypto::secp::Privkey,
+        ckb_testtool::ckb_crypto::secp::Pubkey,
+    )]|
+     -> TransactionView {
+        let tx = TransactionBuilder::default()
+            .cell_deps(cell_deps.clone())
+            .input(
+                CellInput::new_builder()
+                    .previous_output(out_point.clone())
+                    .build(),
+            )
+            .outputs(outputs.clone())
+            .outputs_data(vec![Bytes::new(); 2].pack())
+            .build();
+        let message = compute_signing_message(&tx);
+        let signatures: Vec<u8> = keys
+            .iter()
+            .flat_map(|key| key.0.sign_recoverable(&message.into()).unwrap().serialize())
+            .collect();
+        let witness = [
+            &EMPTY_WITNESS_ARGS[..],
+            &[UNLOCK_TYPE_ARBITRATION, signers][..],
+            &signatures[..],
+        ]
+        .concat();
+        tx.as_advanced_builder().witness(witness.pack()).build()
+    };
+
+    // Each of the three pairs closes the channel
+    let pairs = [
+        (
+            "user + merchant",
+            ARBITRATION_SIGNER_USER | ARBITRATION_SIGNER_MERCHANT,
+            [&merchant_key, &user_key],
+        ),
+        (
+            "user + arbiter",
+            ARBITRATION_SIGNER_USER | ARBITRATION_SIGNER_ARBITER,
+            [&user_key, &arbiter_key],
+        ),
+        (
+            "merchant + arbiter",
+            ARBITRATION_SIGNER_MERCHANT | ARBITRATION_SIGNER_ARBITER,
+            [&merchant_key, &arbiter_key],
+        ),
+    ];
+    for (name, signers, keys) in pairs {
+        let tx = arbitration_tx(&input_out_point, signers, &keys);
+        let cycles = context
+            .verify_tx(&tx, 10_000_000)
+            .unwrap_or_else(|e| panic!("arbitration by {} should pass: {}", name, e));
+        println!("consume cycles (arbitration, {}): {}", name, cycles);
+    }
+
+    // A single signature is not enough, even the arbiter's
+    let tx = arbitration_tx(
+        &input_out_point,
+        ARBITRATION_SIGNER_ARBITER,
+        &[&arbiter_key],
+    );
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("single-signature arbitration should fail");
+    assert_script_error(err, ERROR_INVALID_ARBITRATION_SIGNERS);
+
+    // The arbiter's slot signed by another key
+    let tx = arbitration_tx(
+        &input_out_point,
+        ARBITRATION_SIGNER_USER | ARBITRATION_SIGNER_ARBITER,
+        &[&user_key, &merchant_key],
+    );
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("arbitration with a wrong arbiter signature should fail");
+    assert_script_error(err, ERROR_AUTH);
+
+    // Without an arbiter in args there is no arbitration path
+    let tx = arbitration_tx(
+        &no_arbiter_out_point,
+        ARBITRATION_SIGNER_USER | ARBITRATION_SIGNER_MERCHANT,
+        &[&merchant_key, &user_key],
+    );
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("arbitration without an arbiter should fail");
+    assert_script_error(err, ERROR_INVALID_UNLOCK_TYPE);
+}
+
+/// Ethereum address of a key: keccak256(uncompressed_pubkey)[12..32]
+fn eth_address(pubkey: &ckb_testtool::ckb_crypto::secp::Pubkey) -> [u8; 20] {
+    let hash = Keccak256::digest(pubkey.as_bytes());
+    hash[12..32].try_into().unwrap()
+}
+
+/// personal_sign signature over a 32-byte message, as produced by MetaMask
+fn eth_sign(key: &ckb_testtool::ckb_crypto::secp::Privkey, message: &[u8; 32]) -> Vec<u8> {
+    let digest: [u8; 32] = Keccak256::new()
+        .chain_update(b"\x19Ethereum Signed Message:\n32")
+        .chain_update(message)
+        .finalize()
+        .into();
+    key.sign_recoverable(&digest.into()).unwrap().serialize()
+}
+
+/// The user signs with an Ethereum key (user_algorithm_id = 1) and receives on Omnilock
+#[test]
+fn test_spillman_lock_ethereum_user() {
+    let mut context = Context::default();
+    let loader = Loader::default();
+    let spillman_lock_bin: Bytes = loader.load_binary("spillman-lock");
+    let auth_bin: Bytes = loader.load_binary("../../deps/auth");
+    let spillman_lock_out_point = context.deploy_cell(spillman_lock_bin);
+    let auth_out_point = context.deploy_cell(auth_bin);
+
+    let mut generator = Generator::new();
+    let user_key = generator.gen_keypair();
+    let merchant_key = generator.gen_keypair();
+
+    let merchant_pubkey_hash = blake160(&merchant_key.1.serialize());
+    let user_address = eth_address(&user_key.1);
+    let timeout_timestamp = 1735689600u64; // 2025-01-01 00:00:00 UTC
+    let timeout_since =
+        Since::from_timestamp(timeout_timestamp, true).expect("valid timestamp since");
+
+    // 51-byte args: the trailing byte is user_algorithm_id
+    let spillman_args = |user_algorithm_id: &[u8]| -> Bytes {
+        Bytes::from(
+            [
+                merchant_pubkey_hash.as_ref(),
+                &user_address[..],
+                &timeout_since.as_u64().to_le_bytes(),
+                &[0u8], // merchant algorithm_id: single-sig
+                &[0u8], // version
+                user_algorithm_id,
+            ]
+            .concat(),
+        )
+    };
+
+    // Omnilock with Ethereum auth: [auth flag = 1] + [address(20)] + [omnilock flags = 0]
+    let user_lock_script = Script::new_builder()
+        .code_hash(OMNILOCK_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(
+            Bytes::from([&[USER_ALGORITHM_ETHEREUM][..], &user_address[..], &[0u8]].concat())
+                .pack(),
+        )
+        .build();
+    let merchant_lock_script = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(merchant_pubkey_hash.as_ref().to_vec()).pack())
+        .build();
+
+    let spillman_lock_dep = CellDep::new_builder()
+        .out_point(spillman_lock_out_point.clone())
+        .build();
+    let auth_dep = CellDep::new_builder().out_point(auth_out_point).build();
+    let cell_deps = vec![spillman_lock_dep, auth_dep].pack();
+
+    // Channel input of 1001 CKB locked with `args`, spent with `since`
+    let channel_input = |context: &mut Context, args: Bytes, since: u64| -> CellInput {
+        let lock_script = context
+            .build_script(&spillman_lock_out_point, args)
+            .expect("script");
+        let out_point = context.create_cell(
+            CellOutput::new_builder()
+                .capacity(100_100_000_000u64.pack())
+                .lock(lock_script)
+                .build(),
+            Bytes::new(),
+        );
+        CellInput::new_builder()
+            .previous_output(out_point)
+            .since(since.pack())
+            .build()
+    };
+
+    // Merchant signs the Spillman message with its CKB key, the user with `user_signer`
+    let sign = |tx: TransactionView,
+                unlock_type: u8,
+                user_signer: &dyn Fn(&[u8; 32]) -> Vec<u8>|
+     -> TransactionView {
+        let message = compute_signing_message(&tx);
+        let merchant_signature = merchant_key
+            .0
+            .sign_recoverable(&message.into())
+            .unwrap()
+            .serialize();
+        let witness = [
+            &EMPTY_WITNESS_ARGS[..],
+            &[unlock_type][..],
+            &merchant_signature[..],
+            &user_signer(&message)[..],
+        ]
+        .concat();
+        tx.as_advanced_builder().witness(witness.pack()).build()
+    };
+    let eth_user = |message: &[u8; 32]| eth_sign(&user_key.0, message);
+    let ckb_user = |message: &[u8; 32]| {
+        user_key
+            .0
+            .sign_recoverable(&(*message).into())
+            .unwrap()
+            .serialize()
+    };
+
+    // Commitment: 500 CKB back to the user's Omnilock, 500 CKB to the merchant
+    let commitment = TransactionBuilder::default()
+        .cell_deps(cell_deps.clone())
+        .input(channel_input(
+            &mut context,
+            spillman_args(&[USER_ALGORITHM_ETHEREUM]),
+            0,
+        ))
+        .outputs(vec![
+            CellOutput::new_builder()
+                .capacity(50_000_000_000u64.pack())
+                .lock(user_lock_script.clone())
+                .build(),
+            CellOutput::new_builder()
+                .capacity(50_000_000_000u64.pack())
+                .lock(merchant_lock_script.clone())
+                .build(),
+        ])
+        .outputs_data(vec![Bytes::new(); 2].pack())
+        .build();
+    let tx = sign(commitment.clone(), UNLOCK_TYPE_COMMITMENT, &eth_user);
+    let cycles = context
+        .verify_tx(&tx, 10_000_000)
+        .expect("commitment signed by the Ethereum user should pass");
+    println!("consume cycles (ethereum user commitment): {}", cycles);
+
+    // A CKB-style signature by the same key does not verify as Ethereum auth
+    let tx = sign(commitment, UNLOCK_TYPE_COMMITMENT, &ckb_user);
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("CKB signature for an Ethereum user should fail");
+    assert_script_error(err, ERROR_AUTH);
+
+    // Refund after timeout + grace, 1000 CKB back to the user
+    let refund_since = Since::from_timestamp(timeout_timestamp + TIMEOUT_GRACE_SECONDS, true)
+        .expect("valid timestamp since")
+        .as_u64();
+    let refund = |input: CellInput, user_lock: Script| -> TransactionView {
+        TransactionBuilder::default()
+            .cell_deps(cell_deps.clone())
+            .input(input)
+            .output(
+                CellOutput::new_builder()
+                    .capacity(100_000_000_000u64.pack())
+                    .lock(user_lock)
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build()
+    };
+    let input = channel_input(
+        &mut context,
+        spillman_args(&[USER_ALGORITHM_ETHEREUM]),
+        refund_since,
+    );
+    let tx = sign(
+        refund(input.clone(), user_lock_script.clone()),
+        UNLOCK_TYPE_TIMEOUT,
+        &eth_user,
+    );
+    let cycles = context
+        .verify_tx(&tx, 10_000_000)
+        .expect("refund to the Ethereum user should pass");
+    println!("consume cycles (ethereum user refund): {}", cycles);
+
+    // The Ethereum address under a sighash lock is not the user's output lock
+    let sighash_lock = Script::new_builder()
+        .code_hash(SECP256K1_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(user_address.to_vec()).pack())
+        .build();
+    let tx = sign(refund(input, sighash_lock), UNLOCK_TYPE_TIMEOUT, &eth_user);
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("refund to a sighash lock of an Ethereum user should fail");
+    assert_script_error(err, ERROR_USER_PUBKEY_HASH_MISMATCH);
+
+    // Multisig is a known ckb-auth id but the user signs with one key
+    let input = channel_input(&mut context, spillman_args(&[6]), refund_since);
+    let tx = sign(
+        refund(input, user_lock_script.clone()),
+        UNLOCK_TYPE_TIMEOUT,
+        &eth_user,
+    );
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("multisig user algorithm should fail");
+    assert_script_error(err, ERROR_UNSUPPORTED_AUTH_ALGORITHM);
+
+    // Not a ckb-auth id at all
+    let input = channel_input(&mut context, spillman_args(&[0xff]), refund_since);
+    let tx = sign(
+        refund(input, user_lock_script.clone()),
+        UNLOCK_TYPE_TIMEOUT,
+        &eth_user,
+    );
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("unknown user algorithm should fail");
+    assert_script_error(err, ERROR_INVALID_LOCK_ARGS);
+
+    // Args longer than 51 bytes are rejected
+    let input = channel_input(
+        &mut context,
+        spillman_args(&[USER_ALGORITHM_ETHEREUM, 0]),
+        refund_since,
+    );
+    let tx = sign(
+        refund(input, user_lock_script),
+        UNLOCK_TYPE_TIMEOUT,
+        &eth_user,
+    );
+    let err = context
+        .verify_tx(&tx, 10_000_000)
+        .expect_err("52-byte args should fail");
+    assert_script_error(err, ERROR_ARGS_LEN);
 }